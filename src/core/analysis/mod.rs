@@ -16,3 +16,13 @@ pub use self::token_stream::TokenStream;
 
 mod char_buffer;
 pub mod whitespace_tokenizer;
+
+/// Added to the position of the tokens of each value of a multi-valued field
+/// after the first one, as Lucene's `Analyzer` does by default.
+pub const POSITION_INCREMENT_GAP: i32 = 0;
+
+/// Added to the offsets of the tokens of each value of a multi-valued field
+/// after the first one, on top of the final offset of the previous value.
+/// The offsets of the values joined by a single separator char line up with
+/// the indexed ones.
+pub const OFFSET_GAP: usize = 1;
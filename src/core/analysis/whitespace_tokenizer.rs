@@ -38,6 +38,7 @@ pub struct WhitespaceTokenizer {
     final_offset: usize,
     term_attr: CharTermAttribute,
     offset_attr: OffsetAttribute,
+    position_attr: PositionIncrementAttribute,
     io_buffer: CharacterBuffer,
     reader: Box<dyn Read>,
 }
@@ -51,6 +52,7 @@ impl fmt::Debug for WhitespaceTokenizer {
             .field("final_offset", &self.final_offset)
             .field("term_attr", &self.term_attr)
             .field("offset_attr", &self.offset_attr)
+            .field("position_attr", &self.position_attr)
             .field("io_buffer", &self.io_buffer)
             .finish()
     }
@@ -65,6 +67,7 @@ impl WhitespaceTokenizer {
             final_offset: 0,
             term_attr: CharTermAttribute::new(),
             offset_attr: OffsetAttribute::new(),
+            position_attr: PositionIncrementAttribute::new(),
             io_buffer: CharacterBuffer::new(vec![], 0, 0),
             reader,
         }
@@ -85,6 +88,7 @@ impl WhitespaceTokenizer {
     fn clear_attributes(&mut self) {
         self.term_attr.clear();
         self.offset_attr.clear();
+        self.position_attr.clear();
     }
 
     fn correct_offset(&self, offset: usize) -> usize {
@@ -144,7 +148,10 @@ impl TokenStream for WhitespaceTokenizer {
     fn end(&mut self) -> Result<()> {
         self.offset_attr.end();
         self.term_attr.end();
-        Ok(())
+        self.position_attr.end();
+        // the final offset is added to the offsets of the next value of the field
+        self.offset_attr
+            .set_offset(self.final_offset, self.final_offset)
     }
 
     fn reset(&mut self) -> Result<()> {
@@ -165,7 +172,7 @@ impl TokenStream for WhitespaceTokenizer {
     }
 
     fn position_attribute_mut(&mut self) -> &mut PositionIncrementAttribute {
-        unimplemented!()
+        &mut self.position_attr
     }
 
     fn term_bytes_attribute_mut(&mut self) -> &mut TermToBytesRefAttribute {
@@ -12,13 +12,16 @@
 // limitations under the License.
 
 use core::store::{BufferedChecksumIndexInput, ChecksumIndexInput};
-use core::store::{DataInput, DataOutput, IndexInput, IndexOutput};
+use core::store::{DataInput, DataOutput, Directory, IOContext, IndexInput, IndexOutput};
 
 use core::util::string_util::id2str;
 use core::util::string_util::ID_LENGTH;
 use error::ErrorKind::{CorruptIndex, IllegalArgument, IllegalState};
 use error::Result;
 use std::io::Read;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crossbeam::thread as crossbeam_thread;
 
 pub const CODEC_MAGIC: i32 = 0x3FD7_6C17;
 pub const FOOTER_MAGIC: i32 = !CODEC_MAGIC;
@@ -331,3 +334,67 @@ pub fn checksum_entire_file<T: IndexInput + ?Sized>(input: &T) -> Result<i64> {
     check_checksum(&mut checksum, actual)?;
     Ok(actual)
 }
+
+/// Verifies the checksums of the given files of `directory` with up to
+/// `num_threads` threads, each file being read through `checksum_entire_file`.
+///
+/// All the files are verified even if some of them fail, the returned
+/// `CorruptIndex` error lists all the failures.
+pub fn verify_checksums_in_parallel<D: Directory + Sync>(
+    directory: &D,
+    files: &[String],
+    num_threads: usize,
+) -> Result<()> {
+    let num_threads = num_threads.max(1).min(files.len());
+    let next_file = AtomicUsize::new(0);
+    let results = crossbeam_thread::scope(|s| {
+        let handles: Vec<_> = (0..num_threads)
+            .map(|_| {
+                let next_file = &next_file;
+                s.spawn(move |_| {
+                    let mut failures = Vec::new();
+                    loop {
+                        let i = next_file.fetch_add(1, Ordering::AcqRel);
+                        if i >= files.len() {
+                            break;
+                        }
+                        let res = directory
+                            .open_input(&files[i], &IOContext::READ_ONCE)
+                            .and_then(|input| checksum_entire_file(input.as_ref()));
+                        if let Err(e) = res {
+                            failures.push(format!("{}: {}", files[i], e));
+                        }
+                    }
+                    failures
+                })
+            })
+            .collect();
+        handles.into_iter().map(|h| h.join()).collect::<Vec<_>>()
+    });
+    let results = match results {
+        Ok(r) => r,
+        Err(e) => bail!(IllegalState(format!(
+            "checksum verification thread panicked: {:?}",
+            e
+        ))),
+    };
+
+    let mut failures = Vec::new();
+    for res in results {
+        match res {
+            Ok(f) => failures.extend(f),
+            Err(e) => bail!(IllegalState(format!(
+                "checksum verification thread panicked: {:?}",
+                e
+            ))),
+        }
+    }
+    if !failures.is_empty() {
+        bail!(CorruptIndex(format!(
+            "checksum verification failed for {} file(s): {}",
+            failures.len(),
+            failures.join("; ")
+        )));
+    }
+    Ok(())
+}
@@ -38,7 +38,6 @@ use core::util::{BitsRef, BytesRef, DocId, LongValues, MatchNoBits, ReusableIter
 use error::Result;
 
 use std::ptr;
-use std::rc::Rc;
 use std::sync::Arc;
 
 /// Abstract API that consumes terms, doc, freq, prox, offset and
@@ -958,7 +957,7 @@ struct SortedDocValuesSub {
     values: SortedDocValuesRef,
     doc_id: DocId,
     max_doc: i32,
-    map: Rc<dyn LongValues>,
+    map: Arc<dyn LongValues>,
     base: DocIdMergerSubBase,
 }
 
@@ -967,7 +966,7 @@ impl SortedDocValuesSub {
         doc_map: Arc<LiveDocsDocMap>,
         values: SortedDocValuesRef,
         max_doc: i32,
-        map: Rc<dyn LongValues>,
+        map: Arc<dyn LongValues>,
     ) -> Self {
         let base = DocIdMergerSubBase::new(doc_map);
         SortedDocValuesSub {
@@ -1249,7 +1248,7 @@ struct SortedSetDocValuesSub {
     values: SortedSetDocValuesRef,
     doc_id: DocId,
     max_doc: i32,
-    map: Rc<dyn LongValues>,
+    map: Arc<dyn LongValues>,
     base: DocIdMergerSubBase,
 }
 
@@ -1258,7 +1257,7 @@ impl SortedSetDocValuesSub {
         doc_map: Arc<LiveDocsDocMap>,
         values: SortedSetDocValuesRef,
         max_doc: i32,
-        map: Rc<dyn LongValues>,
+        map: Arc<dyn LongValues>,
     ) -> Self {
         let base = DocIdMergerSubBase::new(doc_map);
         SortedSetDocValuesSub {
@@ -51,7 +51,10 @@ pub const PAY_CODEC: &str = "Lucene50PostingsWriterPay";
 
 // Increment version to change it
 const VERSION_START: i32 = 0;
-pub const VERSION_CURRENT: i32 = VERSION_START;
+/// The skip entries of the fields with freqs end with the highest freq by
+/// norm of the docs they skip.
+const VERSION_IMPACT_SKIP_DATA: i32 = 1;
+pub const VERSION_CURRENT: i32 = VERSION_IMPACT_SKIP_DATA;
 
 fn clone_option_index_input(input: &Option<Box<dyn IndexInput>>) -> Result<Box<dyn IndexInput>> {
     debug_assert!(input.is_some());
@@ -183,6 +186,7 @@ impl Lucene50PostingsReader {
         let index_has_positions = options.has_positions();
         let index_has_offsets = options.has_offsets();
         let index_has_payloads = field_info.has_store_payloads;
+        let index_has_impacts = self.version >= VERSION_IMPACT_SKIP_DATA && options.has_freqs();
 
         Ok(
             if !index_has_positions
@@ -194,6 +198,7 @@ impl Lucene50PostingsReader {
                     state,
                     flags,
                     self.for_util.clone(),
+                    index_has_impacts,
                 )?)
             } else if (!index_has_offsets
                 || !PostingIteratorFlags::feature_requested(flags, PostingIteratorFlags::OFFSETS))
@@ -210,6 +215,7 @@ impl Lucene50PostingsReader {
                     state,
                     flags,
                     self.for_util.clone(),
+                    index_has_impacts,
                 )?)
             } else {
                 debug_assert!(self.pos_in.is_some());
@@ -222,6 +228,7 @@ impl Lucene50PostingsReader {
                     state,
                     flags,
                     self.for_util.clone(),
+                    index_has_impacts,
                 )?)
             },
         )
@@ -365,6 +372,8 @@ pub struct BlockDocIterator {
     singleton_doc_id: DocId,
 
     for_util: ForUtil,
+    /// whether the skip entries have impacts
+    index_has_impacts: bool,
 }
 
 impl BlockDocIterator {
@@ -374,6 +383,7 @@ impl BlockDocIterator {
         term_state: &BlockTermState,
         flags: u16,
         for_util: ForUtil,
+        index_has_impacts: bool,
     ) -> Result<BlockDocIterator> {
         let options = &field_info.index_options;
         let mut iterator = BlockDocIterator {
@@ -401,6 +411,7 @@ impl BlockDocIterator {
             index_has_offsets: options.has_offsets(),
             index_has_payloads: field_info.has_store_payloads,
             for_util,
+            index_has_impacts,
         };
         iterator.reset(term_state, flags)?;
         Ok(iterator)
@@ -480,6 +491,57 @@ impl BlockDocIterator {
         self.doc_buffer_upto = 0;
         Ok(())
     }
+
+    /// Moves the skipper to the block containing `target` and seeks the doc
+    /// input to it if it's after the current block.
+    fn skip_to(&mut self, target: DocId) -> Result<()> {
+        if self.skipper.is_none() {
+            // Lazy init: first time this enum has ever been used for skipping
+            self.skipper = Some(Lucene50SkipReader::new(
+                clone_option_index_input(&self.doc_in)?,
+                MAX_SKIP_LEVELS,
+                self.index_has_pos,
+                self.index_has_offsets,
+                self.index_has_payloads,
+                self.index_has_impacts,
+            ));
+        }
+
+        let skipper = self.skipper.as_mut().unwrap();
+
+        if !self.skipped {
+            debug_assert_ne!(self.skip_offset, -1);
+            // This is the first time this enum has skipped
+            // since reset() was called; load the skip data:
+            skipper.init(
+                self.doc_term_start_fp + self.skip_offset,
+                self.doc_term_start_fp,
+                0,
+                0,
+                self.doc_freq,
+            )?;
+            self.skipped = true;
+        }
+
+        // always plus one to fix the result, since skip position in Lucene50SkipReader
+        // is a little different from MultiLevelSkipListReader
+        let new_doc_upto = skipper.skip_to(target)? + 1;
+
+        if new_doc_upto > self.doc_upto {
+            // Skipper moved
+            debug_assert_eq!(new_doc_upto % BLOCK_SIZE, 0);
+            self.doc_upto = new_doc_upto;
+
+            // Force to read next block
+            self.doc_buffer_upto = BLOCK_SIZE;
+            self.accum = skipper.doc(); // actually, this is just lastSkipEntry
+            self.doc_in.as_mut().unwrap().seek(skipper.doc_pointer())?; // now point to the block we want to search
+        }
+        // next time we call advance, this is used to
+        // foresee whether skipper is necessary.
+        self.next_skip_doc = skipper.next_skip_doc();
+        Ok(())
+    }
 }
 
 impl PostingIterator for BlockDocIterator {
@@ -503,6 +565,21 @@ impl PostingIterator for BlockDocIterator {
     fn payload(&self) -> Result<Payload> {
         Ok(Payload::new())
     }
+
+    fn advance_shallow(&mut self, target: DocId) -> Result<()> {
+        if self.doc_freq > BLOCK_SIZE && target > self.next_skip_doc {
+            self.skip_to(target)?;
+        }
+        Ok(())
+    }
+
+    fn impacts(&self, level: usize) -> Option<(DocId, &[Impact])> {
+        if self.skipped {
+            self.skipper.as_ref().unwrap().impacts(level)
+        } else {
+            None
+        }
+    }
 }
 
 impl DocIterator for BlockDocIterator {
@@ -534,50 +611,7 @@ impl DocIterator for BlockDocIterator {
         // current skip docID < docIDs generated from current buffer <= next skip docID
         // we don't need to skip if target is buffered already
         if self.doc_freq > BLOCK_SIZE && target > self.next_skip_doc {
-            if self.skipper.is_none() {
-                // Lazy init: first time this enum has ever been used for skipping
-                self.skipper = Some(Lucene50SkipReader::new(
-                    clone_option_index_input(&self.doc_in)?,
-                    MAX_SKIP_LEVELS,
-                    self.index_has_pos,
-                    self.index_has_offsets,
-                    self.index_has_payloads,
-                ));
-            }
-
-            let skipper = self.skipper.as_mut().unwrap();
-
-            if !self.skipped {
-                debug_assert_ne!(self.skip_offset, -1);
-                // This is the first time this enum has skipped
-                // since reset() was called; load the skip data:
-                skipper.init(
-                    self.doc_term_start_fp + self.skip_offset,
-                    self.doc_term_start_fp,
-                    0,
-                    0,
-                    self.doc_freq,
-                )?;
-                self.skipped = true;
-            }
-
-            // always plus one to fix the result, since skip position in Lucene50SkipReader
-            // is a little different from MultiLevelSkipListReader
-            let new_doc_upto = skipper.skip_to(target)? + 1;
-
-            if new_doc_upto > self.doc_upto {
-                // Skipper moved
-                debug_assert_eq!(new_doc_upto % BLOCK_SIZE, 0);
-                self.doc_upto = new_doc_upto;
-
-                // Force to read next block
-                self.doc_buffer_upto = BLOCK_SIZE;
-                self.accum = skipper.doc(); // actually, this is just lastSkipEntry
-                self.doc_in.as_mut().unwrap().seek(skipper.doc_pointer())?; // now point to the block we want to search
-            }
-            // next time we call advance, this is used to
-            // foresee whether skipper is necessary.
-            self.next_skip_doc = skipper.next_skip_doc();
+            self.skip_to(target)?;
         }
         if self.doc_upto == self.doc_freq {
             self.doc = NO_MORE_DOCS;
@@ -685,6 +719,8 @@ pub struct BlockPostingIterator {
     singleton_doc_id: i32,
 
     for_util: ForUtil,
+    /// whether the skip entries have impacts
+    index_has_impacts: bool,
 }
 
 impl BlockPostingIterator {
@@ -695,6 +731,7 @@ impl BlockPostingIterator {
         term_state: &BlockTermState,
         _flags: u16,
         for_util: ForUtil,
+        index_has_impacts: bool,
     ) -> Result<BlockPostingIterator> {
         let options = &field_info.index_options;
         let mut iterator = BlockPostingIterator {
@@ -729,6 +766,7 @@ impl BlockPostingIterator {
             index_has_offsets: options.has_offsets(),
             index_has_payloads: field_info.has_store_payloads,
             for_util,
+            index_has_impacts,
         };
         iterator.reset(term_state)?;
         Ok(iterator)
@@ -951,6 +989,7 @@ impl DocIterator for BlockPostingIterator {
                     self.index_has_pos,
                     self.index_has_offsets,
                     self.index_has_payloads,
+                    self.index_has_impacts,
                 ));
             }
 
@@ -1117,10 +1156,12 @@ pub struct EverythingIterator {
     singleton_doc_id: i32,
     // docid when there is a single pulsed posting, otherwise -1
     for_util: ForUtil,
+    /// whether the skip entries have impacts
+    index_has_impacts: bool,
 }
 
 impl<'a> EverythingIterator {
-    //#[allow(too_many_arguments)]
+    #[allow(too_many_arguments)]
     pub fn new(
         start_doc_in: Box<dyn IndexInput>,
         pos_in: Box<dyn IndexInput>,
@@ -1129,6 +1170,7 @@ impl<'a> EverythingIterator {
         term_state: &BlockTermState,
         flags: u16,
         for_util: ForUtil,
+        index_has_impacts: bool,
     ) -> Result<EverythingIterator> {
         let encoded = vec![0 as u8; MAX_ENCODED_SIZE];
         let index_has_offsets = field_info.index_options.has_offsets();
@@ -1205,6 +1247,7 @@ impl<'a> EverythingIterator {
             skipped: false,
             total_term_freq: 0,
             for_util,
+            index_has_impacts,
         };
 
         iterator.reset(term_state, flags)?;
@@ -1713,6 +1756,7 @@ impl DocIterator for EverythingIterator {
                     true,
                     self.index_has_offsets,
                     self.index_has_payloads,
+                    self.index_has_impacts,
                 ));
             }
 
@@ -1831,6 +1875,22 @@ impl PostingIterator for Lucene50PostingIterEnum {
             Lucene50PostingIterEnum::Everything(i) => i.payload(),
         }
     }
+
+    fn advance_shallow(&mut self, target: DocId) -> Result<()> {
+        match self {
+            Lucene50PostingIterEnum::Doc(i) => i.advance_shallow(target),
+            Lucene50PostingIterEnum::Posting(i) => i.advance_shallow(target),
+            Lucene50PostingIterEnum::Everything(i) => i.advance_shallow(target),
+        }
+    }
+
+    fn impacts(&self, level: usize) -> Option<(DocId, &[Impact])> {
+        match self {
+            Lucene50PostingIterEnum::Doc(i) => i.impacts(level),
+            Lucene50PostingIterEnum::Posting(i) => i.impacts(level),
+            Lucene50PostingIterEnum::Everything(i) => i.impacts(level),
+        }
+    }
 }
 
 impl DocIterator for Lucene50PostingIterEnum {
@@ -1906,3 +1966,129 @@ impl DocIterator for Lucene50PostingIterEnum {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::analysis::whitespace_tokenizer::WhitespaceTokenizer;
+    use core::analysis::TokenStream;
+    use core::doc::{Field, FieldType};
+    use core::index::{
+        Fieldable, IndexOptions, IndexReader, IndexWriter, IndexWriterConfig, LeafReader,
+        NumericDocValues, TermIterator, Terms,
+    };
+    use core::store::RAMDirectory;
+
+    use std::io::Cursor;
+
+    // a doc whose body is `freq` times the term "a", as for the `i`-th doc
+    // of the impacts tests
+    fn body_doc(i: usize) -> Vec<Box<dyn Fieldable>> {
+        let mut field_type = FieldType::default();
+        field_type.index_options = IndexOptions::DocsAndFreqsAndPositions;
+        let freq = match i {
+            50 => 5,
+            200 => 3,
+            _ => 1,
+        };
+        let text = vec!["a"; freq].join(" ");
+        let tokens: Box<dyn TokenStream> = Box::new(WhitespaceTokenizer::new(Box::new(
+            Cursor::new(text.into_bytes()),
+        )));
+        vec![Box::new(Field::new("body".into(), field_type, None, Some(tokens)))]
+    }
+
+    // the impacts of the second block of the docs of `body_doc`
+    fn second_block_impacts(norms: &dyn NumericDocValues) -> Vec<Impact> {
+        let mut impacts = vec![
+            Impact {
+                freq: 1,
+                norm: norms.get(199).unwrap(),
+            },
+            Impact {
+                freq: 3,
+                norm: norms.get(200).unwrap(),
+            },
+        ];
+        impacts.sort_by_key(|i| i.norm);
+        assert_ne!(impacts[0].norm, impacts[1].norm);
+        impacts
+    }
+
+    #[test]
+    fn test_impacts() {
+        let dir = Arc::new(RAMDirectory::default());
+        let writer = IndexWriter::new(dir, Arc::new(IndexWriterConfig::default())).unwrap();
+        // two full blocks and a vint block
+        for i in 0..300 {
+            writer.add_document(body_doc(i)).unwrap();
+        }
+        let reader = writer.get_reader(true, false).unwrap();
+        let leaves = reader.leaves();
+        assert_eq!(leaves.len(), 1);
+        let terms = leaves[0].reader.terms("body").unwrap().unwrap();
+        let mut terms_iter = terms.iterator().unwrap();
+        assert!(terms_iter.seek_exact(b"a").unwrap());
+
+        let mut postings = terms_iter
+            .postings_with_flags(PostingIteratorFlags::FREQS)
+            .unwrap();
+        // no skip data read in the first block
+        postings.advance_shallow(100).unwrap();
+        assert_eq!(postings.impacts(0), None);
+        postings.advance_shallow(200).unwrap();
+        // one impact per field length of the second block
+        let norms = leaves[0].reader.norm_values("body").unwrap().unwrap();
+        let impacts = second_block_impacts(norms.as_ref());
+        assert_eq!(postings.impacts(0), Some((255, impacts.as_slice())));
+        assert_eq!(postings.impacts(1), None);
+        assert_eq!(postings.advance(200).unwrap(), 200);
+        assert_eq!(postings.freq().unwrap(), 3);
+        // nothing is known of the docs after the last skip point
+        postings.advance_shallow(280).unwrap();
+        assert_eq!(postings.impacts(0), None);
+        assert_eq!(postings.advance(280).unwrap(), 280);
+
+        // the skip data with impacts is read back by the positions iterator too
+        let mut postings = terms_iter
+            .postings_with_flags(PostingIteratorFlags::POSITIONS)
+            .unwrap();
+        assert_eq!(postings.advance(200).unwrap(), 200);
+        assert_eq!(postings.freq().unwrap(), 3);
+        assert_eq!(postings.next_position().unwrap(), 0);
+        assert_eq!(postings.advance(299).unwrap(), 299);
+        assert_eq!(postings.next().unwrap(), NO_MORE_DOCS);
+
+        writer.close().unwrap();
+    }
+
+    #[test]
+    fn test_merged_impacts() {
+        let dir = Arc::new(RAMDirectory::default());
+        let writer = IndexWriter::new(dir, Arc::new(IndexWriterConfig::default())).unwrap();
+        for i in 0..300 {
+            writer.add_document(body_doc(i)).unwrap();
+            if i == 150 {
+                writer.commit().unwrap();
+            }
+        }
+        writer.force_merge(1, true).unwrap();
+        let reader = writer.get_reader(true, false).unwrap();
+        let leaves = reader.leaves();
+        assert_eq!(leaves.len(), 1);
+        let terms = leaves[0].reader.terms("body").unwrap().unwrap();
+        let mut terms_iter = terms.iterator().unwrap();
+        assert!(terms_iter.seek_exact(b"a").unwrap());
+
+        // the norms are merged before the postings, which record them
+        let mut postings = terms_iter
+            .postings_with_flags(PostingIteratorFlags::FREQS)
+            .unwrap();
+        postings.advance_shallow(200).unwrap();
+        let norms = leaves[0].reader.norm_values("body").unwrap().unwrap();
+        let impacts = second_block_impacts(norms.as_ref());
+        assert_eq!(postings.impacts(0), Some((255, impacts.as_slice())));
+
+        writer.close().unwrap();
+    }
+}
@@ -21,7 +21,7 @@ use core::codec::{BlockTermState, Codec};
 use core::index::TermIterator;
 use core::index::INDEX_MAX_POSITION;
 use core::index::{segment_file_name, SegmentWriteState};
-use core::index::{FieldInfo, IndexOptions};
+use core::index::{FieldInfo, IndexOptions, NumericDocValues, NumericDocValuesRef};
 use core::search::posting_iterator::*;
 use core::search::{DocIterator, NO_MORE_DOCS};
 use core::store::{DataOutput, Directory, IndexOutput};
@@ -30,6 +30,8 @@ use core::util::packed_misc::COMPACT;
 use core::util::DocId;
 use error::{ErrorKind, Result};
 
+use std::collections::{BTreeMap, HashMap};
+
 /// Concrete class that writes docId(maybe frq,pos,offset,payloads) list
 /// with postings format.
 ///
@@ -46,6 +48,8 @@ pub struct Lucene50PostingsWriter<O: IndexOutput> {
 
     doc_delta_buffer: Vec<i32>,
     freq_buffer: Vec<i32>,
+    // the norms of the buffered docs, only filled if the field has freqs
+    norm_buffer: Vec<i64>,
     doc_buffer_upto: usize,
 
     pos_delta_buffer: Vec<i32>,
@@ -62,6 +66,8 @@ pub struct Lucene50PostingsWriter<O: IndexOutput> {
     last_block_pay_fp: i64,
     last_block_pos_buffer_upto: usize,
     last_block_payload_byte_upto: usize,
+    // the highest freq by norm of the docs of the last complete block
+    last_block_impacts: BTreeMap<i64, i32>,
 
     last_doc_id: DocId,
     last_position: usize,
@@ -82,6 +88,11 @@ pub struct Lucene50PostingsWriter<O: IndexOutput> {
     write_positions: bool,
     write_payloads: bool,
     write_offsets: bool,
+
+    // the norms of the segment by field, recorded in the impacts
+    norms: HashMap<String, NumericDocValuesRef>,
+    // the norms of the current field, `None` if it has no norms
+    field_norms: Option<NumericDocValuesRef>,
 }
 
 impl<O: IndexOutput> Lucene50PostingsWriter<O> {
@@ -181,6 +192,7 @@ impl<O: IndexOutput> Lucene50PostingsWriter<O> {
 
             doc_delta_buffer: vec![0i32; max_data_size()],
             freq_buffer: vec![0i32; max_data_size()],
+            norm_buffer: vec![0i64; max_data_size()],
             doc_buffer_upto: 0,
 
             pos_delta_buffer,
@@ -197,6 +209,7 @@ impl<O: IndexOutput> Lucene50PostingsWriter<O> {
             last_block_pay_fp: 0,
             last_block_pos_buffer_upto: 0,
             last_block_payload_byte_upto: 0,
+            last_block_impacts: BTreeMap::new(),
 
             last_doc_id: 0,
             last_position: 0,
@@ -211,6 +224,8 @@ impl<O: IndexOutput> Lucene50PostingsWriter<O> {
             write_positions: false,
             write_payloads: false,
             write_offsets: false,
+            norms: state.norms.clone(),
+            field_norms: None,
         })
     }
 
@@ -281,6 +296,7 @@ impl<O: IndexOutput> Lucene50PostingsWriter<O> {
                 self.last_block_pay_fp,
                 self.last_block_pos_buffer_upto,
                 self.last_block_payload_byte_upto,
+                &self.last_block_impacts,
                 self.doc_out.file_pointer(),
             )?;
         }
@@ -294,6 +310,10 @@ impl<O: IndexOutput> Lucene50PostingsWriter<O> {
         self.doc_delta_buffer[self.doc_buffer_upto] = doc_delta;
         if self.write_freqs {
             self.freq_buffer[self.doc_buffer_upto] = term_doc_freq;
+            self.norm_buffer[self.doc_buffer_upto] = match self.field_norms {
+                Some(ref norms) => norms.get(doc_id)?,
+                None => 0,
+            };
         }
 
         self.doc_buffer_upto += 1;
@@ -415,6 +435,14 @@ impl<O: IndexOutput> Lucene50PostingsWriter<O> {
         // write them to skip file.
         if self.doc_buffer_upto == BLOCK_SIZE as usize {
             self.last_block_doc_id = self.last_doc_id;
+            self.last_block_impacts.clear();
+            if self.write_freqs {
+                let block = self.norm_buffer.iter().zip(&self.freq_buffer);
+                for (&norm, &freq) in block.take(BLOCK_SIZE as usize) {
+                    let max_freq = self.last_block_impacts.entry(norm).or_insert(0);
+                    *max_freq = (*max_freq).max(freq);
+                }
+            }
             if self.pos_out.is_some() {
                 if self.pay_out.is_some() {
                     self.last_block_pay_fp = self.pay_out.as_ref().unwrap().file_pointer();
@@ -671,7 +699,13 @@ impl<O: IndexOutput> PostingsWriterBase for Lucene50PostingsWriter<O> {
 
     fn set_field(&mut self, field_info: &FieldInfo) -> i32 {
         self.set_field_base(field_info);
+        self.field_norms = if field_info.has_norms() {
+            self.norms.get(&field_info.name).cloned()
+        } else {
+            None
+        };
         self.skip_writer.set_field(
+            self.write_freqs,
             self.write_positions,
             self.write_offsets,
             self.write_payloads,
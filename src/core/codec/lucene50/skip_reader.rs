@@ -15,6 +15,7 @@ use std::io;
 use std::io::Read;
 
 use core::codec::lucene50::posting_format::BLOCK_SIZE;
+use core::search::posting_iterator::Impact;
 use core::store::DataInput;
 use core::store::IndexInput;
 use core::store::RandomAccessInput;
@@ -171,6 +172,9 @@ pub struct Lucene50SkipReader {
     pay_pointer: Option<Vec<i64>>,
     pos_buffer_upto: Option<Vec<i32>>,
     payload_byte_upto: Option<Vec<i32>>,
+    /// impacts of the docs up to the current skip entry per level, only if
+    /// the skip data has impacts
+    impacts: Option<Vec<Vec<Impact>>>,
 
     last_pos_pointer: i64,
     last_pay_pointer: i64,
@@ -212,6 +216,7 @@ impl Lucene50SkipReader {
             pay_pointer: self.pay_pointer.clone(),
             pos_buffer_upto: self.pos_buffer_upto.clone(),
             payload_byte_upto: self.payload_byte_upto.clone(),
+            impacts: self.impacts.clone(),
 
             last_pos_pointer: self.last_pos_pointer,
             last_pay_pointer: self.last_pay_pointer,
@@ -221,12 +226,15 @@ impl Lucene50SkipReader {
         })
     }
 
+    /// `has_impacts` tells whether the skip entries end with the impacts of
+    /// the docs they skip, see `Lucene50SkipWriter`.
     pub fn new(
         skip_stream: Box<dyn IndexInput>,
         max_skip_levels: usize,
         has_pos: bool,
         has_offsets: bool,
         has_payloads: bool,
+        has_impacts: bool,
     ) -> Lucene50SkipReader {
         // fields for MultiLevelSkipReader part
         let max_number_of_skip_levels = max_skip_levels;
@@ -268,6 +276,11 @@ impl Lucene50SkipReader {
                 pay_pointer = Some(vec![0_i64; max_skip_levels]);
             }
         };
+        let impacts = if has_impacts {
+            Some(vec![Vec::new(); max_skip_levels])
+        } else {
+            None
+        };
         Lucene50SkipReader {
             // MultiLevelSkipListReader
             max_number_of_skip_levels,
@@ -291,6 +304,7 @@ impl Lucene50SkipReader {
             pay_pointer,
             pos_buffer_upto,
             payload_byte_upto,
+            impacts,
 
             last_pos_pointer: 0,
             last_pay_pointer: 0,
@@ -383,6 +397,21 @@ impl Lucene50SkipReader {
         self.skip_doc[0]
     }
 
+    /// Returns the current skip entry of `level` and the impacts of the docs
+    /// up to it, `None` if the skip data has no impacts or the level is
+    /// exhausted. The higher the level, the wider the range of docs.
+    pub fn impacts(&self, level: usize) -> Option<(DocId, &[Impact])> {
+        match self.impacts {
+            Some(ref impacts)
+                if (level as i32) < self.number_of_skip_levels
+                    && self.skip_doc[level] != i32::max_value() =>
+            {
+                Some((self.skip_doc[level], &impacts[level]))
+            }
+            _ => None,
+        }
+    }
+
     /// Seeks the skip entry on the given level
     pub fn seek_child(&mut self, level: i32) -> Result<()> {
         let ulevel = level as usize;
@@ -451,6 +480,19 @@ impl Lucene50SkipReader {
                 self.pay_pointer.as_mut().unwrap()[level] += pointer;
             }
         }
+
+        if let Some(ref mut impacts) = self.impacts {
+            let stream = self.skip_stream[level].as_mut().unwrap();
+            let impacts = &mut impacts[level];
+            impacts.clear();
+            let count = stream.read_vint()?;
+            let mut norm = 0;
+            for _ in 0..count {
+                norm = stream.read_zlong()?.wrapping_add(norm);
+                let freq = stream.read_vint()?;
+                impacts.push(Impact { freq, norm });
+            }
+        }
         Ok(delta)
     }
 
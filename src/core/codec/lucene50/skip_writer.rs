@@ -12,6 +12,7 @@
 // limitations under the License.
 
 use std::cmp::min;
+use std::collections::BTreeMap;
 
 use core::store::{DataOutput, IndexOutput, RAMOutputStream};
 use core::util::fill_slice;
@@ -38,11 +39,17 @@ const SKIP_MULTIPLIER: u32 = 8;
 /// 2. its related file points(position, payload),
 /// 3. related numbers or uptos(position, payload).
 /// 4. start offset.
+/// 5. the impacts of the docs since the previous skip point of the same
+///    level, if the field has freqs: for each distinct norm, sorted, the
+///    delta of the norm and the highest freq of the docs with that norm.
 pub struct Lucene50SkipWriter {
     last_skip_doc: Vec<i32>,
     last_skip_doc_pointer: Vec<i64>,
     last_skip_pos_pointer: Vec<i64>,
     last_skip_pay_pointer: Vec<i64>,
+    /// highest freq by norm of the docs buffered since the last skip point
+    /// per level
+    impacts: Vec<BTreeMap<i64, i32>>,
     // these three output must be parameter when called
     //    doc_out: Box<IndexOutput>,
     //    pos_out: Option<Box<IndexOutput>>,
@@ -53,6 +60,7 @@ pub struct Lucene50SkipWriter {
     cur_pay_pointer: i64,
     cur_pos_buffer_upto: usize,
     cur_payload_byte_upto: usize,
+    field_has_freqs: bool,
     field_has_positions: bool,
     field_has_offsets: bool,
     field_has_payloads: bool,
@@ -107,12 +115,14 @@ impl Lucene50SkipWriter {
             last_skip_doc_pointer: vec![0i64; max_skip_levels],
             last_skip_pos_pointer,
             last_skip_pay_pointer,
+            impacts: vec![BTreeMap::new(); max_skip_levels],
             cur_doc: 0,
             cur_doc_pointer: 0,
             cur_pos_pointer: 0,
             cur_pay_pointer: 0,
             cur_pos_buffer_upto: 0,
             cur_payload_byte_upto: 0,
+            field_has_freqs: false,
             field_has_positions: false,
             field_has_offsets: false,
             field_has_payloads: false,
@@ -129,10 +139,12 @@ impl Lucene50SkipWriter {
 
     pub fn set_field(
         &mut self,
+        field_has_freqs: bool,
         field_has_positions: bool,
         field_has_offsets: bool,
         field_has_payloads: bool,
     ) {
+        self.field_has_freqs = field_has_freqs;
         self.field_has_positions = field_has_positions;
         self.field_has_offsets = field_has_offsets;
         self.field_has_payloads = field_has_payloads;
@@ -153,6 +165,9 @@ impl Lucene50SkipWriter {
         if !self.initialized {
             self.reset_skip_base();
             fill_slice(&mut self.last_skip_doc, 0);
+            for impacts in &mut self.impacts {
+                impacts.clear();
+            }
             fill_slice(&mut self.last_skip_doc_pointer, self.last_doc_fp);
             if self.field_has_positions {
                 fill_slice(&mut self.last_skip_pos_pointer, self.last_pos_fp);
@@ -183,7 +198,9 @@ impl Lucene50SkipWriter {
         }
     }
 
-    /// Sets the values for the current skip data.
+    /// Sets the values for the current skip data, `block_impacts` maps the
+    /// norms of the docs of the block ending at `doc` to their highest freq.
+    #[allow(too_many_arguments)]
     pub fn buffer_skip(
         &mut self,
         doc: DocId,
@@ -192,9 +209,16 @@ impl Lucene50SkipWriter {
         pay_fp: i64,
         pos_buffer_upto: usize,
         payload_byte_upto: usize,
+        block_impacts: &BTreeMap<i64, i32>,
         doc_out_pointer: i64,
     ) -> Result<()> {
         self.init_skip();
+        for impacts in &mut self.impacts {
+            for (&norm, &freq) in block_impacts {
+                let max_freq = impacts.entry(norm).or_insert(freq);
+                *max_freq = (*max_freq).max(freq);
+            }
+        }
         self.cur_doc = doc;
         self.cur_doc_pointer = doc_out_pointer;
         self.cur_pos_pointer = pos_fp;
@@ -285,6 +309,18 @@ impl Lucene50SkipWriter {
             }
         }
 
+        if self.field_has_freqs {
+            let output = &mut self.skip_buffer[level];
+            output.write_vint(self.impacts[level].len() as i32)?;
+            let mut last_norm = 0;
+            for (&norm, &freq) in &self.impacts[level] {
+                output.write_zlong(norm.wrapping_sub(last_norm))?;
+                output.write_vint(freq)?;
+                last_norm = norm;
+            }
+        }
+        self.impacts[level].clear();
+
         Ok(())
     }
 }
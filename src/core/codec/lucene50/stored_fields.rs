@@ -23,31 +23,50 @@ use core::codec::Codec;
 use core::index::field_info::FieldInfos;
 use core::index::SegmentInfo;
 use core::store::{Directory, IOContext};
-use error::{Error as CoreError, ErrorKind::IllegalState, Result};
+use error::{
+    Error as CoreError,
+    ErrorKind::{CorruptIndex, IllegalState},
+    Result,
+};
 
 const MODE_KEY: &str = "Lucene50StoredFieldsFormat.mode";
 
-#[derive(Debug, Copy, Clone)]
+/// The compression of the stored fields, recorded per segment so that the
+/// segments written with different modes can be read together.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum StoredFieldCompressMode {
+    /// LZ4 compressed blocks of 16KB, fast to compress and decompress.
     BestSpeed,
+    /// Deflate compressed blocks of 60KB, a higher compression ratio at the
+    /// cost of speed.
     BestCompression,
 }
 
+impl StoredFieldCompressMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StoredFieldCompressMode::BestSpeed => "BEST_SPEED",
+            StoredFieldCompressMode::BestCompression => "BEST_COMPRESSION",
+        }
+    }
+}
+
 impl FromStr for StoredFieldCompressMode {
     type Err = CoreError;
     fn from_str(v: &str) -> Result<Self> {
-        let r = if v == "BEST_SPEED" {
-            StoredFieldCompressMode::BestSpeed
-        } else {
-            StoredFieldCompressMode::BestCompression
-        };
-        Ok(r)
+        match v {
+            "BEST_SPEED" => Ok(StoredFieldCompressMode::BestSpeed),
+            "BEST_COMPRESSION" => Ok(StoredFieldCompressMode::BestCompression),
+            _ => bail!(CorruptIndex(format!(
+                "unknown stored fields compress mode: {}",
+                v
+            ))),
+        }
     }
 }
 
 #[derive(Copy, Clone)]
 pub struct Lucene50StoredFieldsFormat {
-    #[allow(dead_code)]
     mode: StoredFieldCompressMode,
 }
 
@@ -62,6 +81,10 @@ impl Lucene50StoredFieldsFormat {
         }
     }
 
+    pub fn mode(&self) -> StoredFieldCompressMode {
+        self.mode
+    }
+
     pub fn format(&self, mode: &StoredFieldCompressMode) -> CompressingStoredFieldsFormat {
         match mode {
             StoredFieldCompressMode::BestSpeed => CompressingStoredFieldsFormat::new(
@@ -126,7 +149,70 @@ impl StoredFieldsFormat for Lucene50StoredFieldsFormat {
         }
 
         si.attributes
-            .insert(MODE_KEY.to_string(), "BEST_SPEED".to_string());
+            .insert(MODE_KEY.to_string(), self.mode.as_str().to_string());
         self.format(&self.mode).fields_writer(directory, si, ioctx)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::codec::CodecEnum;
+    use core::doc::{Field, STORE_FIELD_TYPE};
+    use core::index::{Fieldable, IndexReader, IndexWriter, IndexWriterConfig, SegmentInfos};
+    use core::store::RAMDirectory;
+    use core::util::VariantValue;
+
+    fn text(seed: usize, len: usize) -> String {
+        let words = ["lorem", "ipsum", "dolor", "sit", "amet", "rucene", "stored"];
+        let mut text = String::with_capacity(len + 8);
+        let mut i = seed;
+        while text.len() < len {
+            text.push_str(words[i % words.len()]);
+            text.push(' ');
+            i = i.wrapping_mul(31).wrapping_add(7);
+        }
+        text
+    }
+
+    #[test]
+    fn test_stored_fields_compress_modes() {
+        for &mode in &[
+            StoredFieldCompressMode::BestSpeed,
+            StoredFieldCompressMode::BestCompression,
+        ] {
+            let dir = Arc::new(RAMDirectory::default());
+            let mut config = IndexWriterConfig::default();
+            config.set_stored_fields_compress_mode(mode);
+            let writer = IndexWriter::new(Arc::clone(&dir), Arc::new(config)).unwrap();
+
+            // the large docs are compressed in several slices of a chunk
+            let lengths = [100, 300_000, 50, 200_000, 1_000];
+            for (i, &len) in lengths.iter().enumerate() {
+                let body = VariantValue::VString(text(i, len));
+                let doc = vec![Field::new(
+                    "body".into(),
+                    STORE_FIELD_TYPE.clone(),
+                    Some(body),
+                    None,
+                )];
+                writer.add_document(doc).unwrap();
+            }
+            writer.commit().unwrap();
+
+            let infos = SegmentInfos::<RAMDirectory, CodecEnum>::read_latest_commit(&dir).unwrap();
+            assert_eq!(infos.segments.len(), 1);
+            let attributes = &infos.segments[0].info.attributes;
+            assert_eq!(attributes.get(MODE_KEY).unwrap(), mode.as_str());
+
+            let reader = writer.get_reader(true, false).unwrap();
+            let fields = vec!["body".to_string()];
+            for (i, &len) in lengths.iter().enumerate().rev() {
+                let doc = reader.document(i as i32, &fields).unwrap();
+                let body = doc.fields[0].field.fields_data().unwrap();
+                assert_eq!(body.get_string().unwrap(), text(i, len).as_str());
+            }
+            writer.close().unwrap();
+        }
+    }
+}
@@ -1147,3 +1147,54 @@ where
         self.values.reset();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::doc::NumericDocValuesField;
+    use core::index::{Fieldable, IndexReader, IndexWriter, IndexWriterConfig};
+    use core::index::{LeafReader, NumericDocValues};
+    use core::store::RAMDirectory;
+
+    use std::sync::Arc;
+
+    #[test]
+    fn test_numeric_compression() {
+        let dir = Arc::new(RAMDirectory::default());
+        let mut config = IndexWriterConfig::default();
+        config.use_compound_file = false;
+        let writer = IndexWriter::new(Arc::clone(&dir), Arc::new(config)).unwrap();
+        let levels = [100i64, 2_000, 30_000, 400_000];
+        let timestamp = |i: i64| 1_500_000_000_000 + i * 60_000;
+        for i in 0..10_000i64 {
+            let doc: Vec<Box<dyn Fieldable>> = vec![
+                Box::new(NumericDocValuesField::new("timestamp", timestamp(i))),
+                Box::new(NumericDocValuesField::new("level", levels[i as usize % 4])),
+            ];
+            writer.add_document(doc).unwrap();
+        }
+        let reader = writer.get_reader(true, false).unwrap();
+        let leaves = reader.leaves();
+        assert_eq!(leaves.len(), 1);
+        let timestamps = leaves[0].reader.get_numeric_doc_values("timestamp").unwrap();
+        let level_values = leaves[0].reader.get_numeric_doc_values("level").unwrap();
+        for doc in &[0, 1, 4_999, 9_999] {
+            assert_eq!(timestamps.get(*doc).unwrap(), timestamp(i64::from(*doc)));
+            assert_eq!(level_values.get(*doc).unwrap(), levels[*doc as usize % 4]);
+        }
+
+        // the timestamps take 16 bits per value with GCD_COMPRESSED instead of
+        // 32 with DELTA_COMPRESSED, the levels 2 bits with TABLE_COMPRESSED
+        // instead of 20
+        let data_len: i64 = dir
+            .list_all()
+            .unwrap()
+            .iter()
+            .filter(|f| f.ends_with(".dvd"))
+            .map(|f| dir.file_length(f).unwrap())
+            .sum();
+        assert!(data_len < 25_000, "doc values data of {} bytes", data_len);
+
+        writer.close().unwrap();
+    }
+}
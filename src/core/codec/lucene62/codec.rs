@@ -45,6 +45,13 @@ pub struct Lucene62Codec {
 
 impl Default for Lucene62Codec {
     fn default() -> Lucene62Codec {
+        Self::with_stored_fields_mode(StoredFieldCompressMode::BestSpeed)
+    }
+}
+
+impl Lucene62Codec {
+    /// Creates the codec compressing the stored fields with the given mode.
+    pub fn with_stored_fields_mode(mode: StoredFieldCompressMode) -> Lucene62Codec {
         Lucene62Codec {
             field_infos_format: Lucene60FieldInfosFormat::default(),
             segment_info_format: Lucene62SegmentInfoFormat::default(),
@@ -52,9 +59,7 @@ impl Default for Lucene62Codec {
             compound_format: Lucene50CompoundFormat {},
             term_vector_format: term_vectors_format(),
             live_docs_format: Lucene50LiveDocsFormat {},
-            stored_fields_format: Lucene50StoredFieldsFormat::new(Some(
-                StoredFieldCompressMode::BestSpeed,
-            )),
+            stored_fields_format: Lucene50StoredFieldsFormat::new(Some(mode)),
             doc_values_format: PerFieldDocValuesFormat::default(),
             norms_format: Lucene53NormsFormat::default(),
             points_format: Lucene60PointsFormat {},
@@ -198,7 +198,7 @@ fn read_segment_info_from_index<D: Directory, C: Codec>(
                 }
             }
             if missing_value.is_some() {
-                sort_field.set_missing_value(missing_value);
+                sort_field.set_missing_value(missing_value)?;
             }
             sort_fields.push(sort_field);
         }
@@ -306,15 +306,16 @@ impl<D: Directory, DW: Directory, C: Codec> DocValuesFieldsWriter<D, DW, C> {
             self.segment_write_state.segment_suffix = old_suffix;
         }
 
-        if let Some(p) = field.put_attribute(
+        let prev = field.put_attribute(
             PER_FIELD_VALUE_SUFFIX_KEY.to_string(),
             format!("{}", suffix.unwrap()),
-        ) {
+        );
+        if field.dv_gen == -1 && prev.is_some() {
             bail!(IllegalState(format!(
                 "found existing value for {}, field={}, old={}, new={}",
                 PER_FIELD_VALUE_SUFFIX_KEY,
                 field.name,
-                p,
+                prev.unwrap(),
                 suffix.unwrap()
             )));
         }
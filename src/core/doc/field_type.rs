@@ -30,6 +30,9 @@ pub struct FieldType {
     pub doc_values_type: DocValuesType,
     pub dimension_count: u32,
     pub dimension_num_bytes: u32,
+    /// Max number of tokens indexed for this field in a document, the
+    /// tokens after it are dropped. `None` means unlimited.
+    pub max_field_length: Option<u32>,
 }
 
 impl Default for FieldType {
@@ -46,6 +49,7 @@ impl Default for FieldType {
             doc_values_type: DocValuesType::Null,
             dimension_count: 0,
             dimension_num_bytes: 0,
+            max_field_length: None,
         }
     }
 }
@@ -77,6 +81,7 @@ impl FieldType {
             doc_values_type,
             dimension_count,
             dimension_num_bytes,
+            max_field_length: None,
         }
     }
 
@@ -145,6 +150,21 @@ impl FieldType {
         self.tokenized
     }
 
+    pub fn max_field_length(&self) -> Option<u32> {
+        self.max_field_length
+    }
+
+    /// Limits the number of tokens indexed for this field in a document,
+    /// across all of its values. The remaining tokens are not indexed and
+    /// the document is counted by `IndexWriter::num_truncated_docs`.
+    pub fn set_max_field_length(&mut self, max_field_length: u32) -> Result<()> {
+        if max_field_length == 0 {
+            bail!(IllegalArgument("max_field_length must be > 0".into()));
+        }
+        self.max_field_length = Some(max_field_length);
+        Ok(())
+    }
+
     pub fn set_dimensions(&mut self, dimension_count: u32, dimension_num_bytes: u32) -> Result<()> {
         if dimension_count > MAX_DIMENSIONS {
             bail!(IllegalArgument(format!(
@@ -201,6 +221,7 @@ pub const NUMERIC_DOC_VALUES_FIELD_TYPE: FieldType = FieldType {
     doc_values_type: DocValuesType::Numeric,
     dimension_count: 0,
     dimension_num_bytes: 0,
+    max_field_length: None,
 };
 
 pub const SORTED_NUMERIC_DOC_VALUES_FIELD_TYPE: FieldType = FieldType {
@@ -215,6 +236,7 @@ pub const SORTED_NUMERIC_DOC_VALUES_FIELD_TYPE: FieldType = FieldType {
     doc_values_type: DocValuesType::SortedNumeric,
     dimension_count: 0,
     dimension_num_bytes: 0,
+    max_field_length: None,
 };
 
 pub const BINARY_DOC_VALUES_FIELD_TYPE: FieldType = FieldType {
@@ -229,6 +251,7 @@ pub const BINARY_DOC_VALUES_FIELD_TYPE: FieldType = FieldType {
     doc_values_type: DocValuesType::Binary,
     dimension_count: 0,
     dimension_num_bytes: 0,
+    max_field_length: None,
 };
 
 pub const SORTED_DOC_VALUES_FIELD_TYPE: FieldType = FieldType {
@@ -243,6 +266,7 @@ pub const SORTED_DOC_VALUES_FIELD_TYPE: FieldType = FieldType {
     doc_values_type: DocValuesType::Sorted,
     dimension_count: 0,
     dimension_num_bytes: 0,
+    max_field_length: None,
 };
 
 pub const SORTED_SET_DOC_VALUES_FIELD_TYPE: FieldType = FieldType {
@@ -257,4 +281,5 @@ pub const SORTED_SET_DOC_VALUES_FIELD_TYPE: FieldType = FieldType {
     doc_values_type: DocValuesType::SortedSet,
     dimension_count: 0,
     dimension_num_bytes: 0,
+    max_field_length: None,
 };
@@ -12,10 +12,12 @@
 // limitations under the License.
 
 use core::codec::Codec;
+use core::search::boolean_query::BooleanQuery;
 use core::search::point_range::{PointRangeQuery, PointValueType};
 use core::search::Query;
 use core::util::numeric;
 
+use error::ErrorKind::IllegalArgument;
 use error::Result;
 
 use num_traits::float::Float;
@@ -198,6 +200,35 @@ impl DoublePoint {
         DoublePoint::new_multi_range_query(field, &[lower], &[upper])
     }
 
+    /// Create a range query for double values, each bound being inclusive or
+    /// exclusive. An exclusive bound is moved by one ULP, see `next_up` and
+    /// `next_down`, an exclusive infinite bound matches no value.
+    pub fn new_bounded_range_query<C: Codec>(
+        field: String,
+        lower: f64,
+        lower_inclusive: bool,
+        upper: f64,
+        upper_inclusive: bool,
+    ) -> Result<Box<dyn Query<C>>> {
+        let neg_infinity: f64 = Float::neg_infinity();
+        let infinity: f64 = Float::infinity();
+        if (!lower_inclusive && lower == infinity) || (!upper_inclusive && upper == neg_infinity) {
+            // empty range
+            return DoublePoint::new_range_query(field, infinity, neg_infinity);
+        }
+        let lower = if lower_inclusive {
+            lower
+        } else {
+            DoublePoint::next_up(lower)
+        };
+        let upper = if upper_inclusive {
+            upper
+        } else {
+            DoublePoint::next_down(upper)
+        };
+        DoublePoint::new_range_query(field, lower, upper)
+    }
+
     /// Create a range query for n-dimensional double values.
     pub fn new_multi_range_query<C: Codec>(
         field: String,
@@ -293,6 +324,34 @@ impl LongPoint {
         LongPoint::new_multi_range_query(field, &[lower], &[upper])
     }
 
+    /// Create a range query for long values, each bound being inclusive or
+    /// exclusive. An exclusive bound is moved by one, an exclusive bound of
+    /// `i64::max_value()` for `lower` or `i64::min_value()` for `upper`
+    /// matches no value.
+    pub fn new_bounded_range_query<C: Codec>(
+        field: String,
+        lower: i64,
+        lower_inclusive: bool,
+        upper: i64,
+        upper_inclusive: bool,
+    ) -> Result<Box<dyn Query<C>>> {
+        let lower = if lower_inclusive {
+            Some(lower)
+        } else {
+            lower.checked_add(1)
+        };
+        let upper = if upper_inclusive {
+            Some(upper)
+        } else {
+            upper.checked_sub(1)
+        };
+        match (lower, upper) {
+            (Some(lower), Some(upper)) => LongPoint::new_range_query(field, lower, upper),
+            // empty range
+            _ => LongPoint::new_range_query(field, i64::max_value(), i64::min_value()),
+        }
+    }
+
     pub fn new_multi_range_query<C: Codec>(
         field: String,
         lower: &[i64],
@@ -307,3 +366,289 @@ impl LongPoint {
         )?))
     }
 }
+
+const LAT_SCALE: f64 = (1u64 << 32) as f64 / 180.0;
+const LAT_DECODE: f64 = 1.0 / LAT_SCALE;
+const LON_SCALE: f64 = (1u64 << 32) as f64 / 360.0;
+const LON_DECODE: f64 = 1.0 / LON_SCALE;
+
+/// An indexed location field, a latitude/longitude pair indexed as a two
+/// dimensional point of 4 bytes per dimension, latitude first.
+///
+/// Each coordinate is quantized to 32 bits, so the precision is about 1cm.
+pub struct LatLonPoint;
+
+impl LatLonPoint {
+    pub const BYTES_PER_DIM: usize = 4;
+
+    /// Quantizes a latitude, rounding down.
+    pub fn encode_latitude(latitude: f64) -> Result<i32> {
+        Self::check_latitude(latitude)?;
+        Ok((Self::clamp_latitude(latitude) / LAT_DECODE).floor() as i32)
+    }
+
+    /// Quantizes a latitude, rounding up.
+    pub fn encode_latitude_ceil(latitude: f64) -> Result<i32> {
+        Self::check_latitude(latitude)?;
+        // the values just below the max round up to 2^31
+        let encoded = (Self::clamp_latitude(latitude) / LAT_DECODE).ceil();
+        Ok(encoded.min(f64::from(i32::max_value())) as i32)
+    }
+
+    pub fn decode_latitude(encoded: i32) -> f64 {
+        f64::from(encoded) * LAT_DECODE
+    }
+
+    /// Quantizes a longitude, rounding down.
+    pub fn encode_longitude(longitude: f64) -> Result<i32> {
+        Self::check_longitude(longitude)?;
+        Ok((Self::clamp_longitude(longitude) / LON_DECODE).floor() as i32)
+    }
+
+    /// Quantizes a longitude, rounding up.
+    pub fn encode_longitude_ceil(longitude: f64) -> Result<i32> {
+        Self::check_longitude(longitude)?;
+        // the values just below the max round up to 2^31
+        let encoded = (Self::clamp_longitude(longitude) / LON_DECODE).ceil();
+        Ok(encoded.min(f64::from(i32::max_value())) as i32)
+    }
+
+    pub fn decode_longitude(encoded: i32) -> f64 {
+        f64::from(encoded) * LON_DECODE
+    }
+
+    /// Encodes a location as the packed value indexed for the point field.
+    pub fn pack(latitude: f64, longitude: f64) -> Result<Vec<u8>> {
+        Ok(IntPoint::pack(&[
+            Self::encode_latitude(latitude)?,
+            Self::encode_longitude(longitude)?,
+        ]))
+    }
+
+    /// Create a query for the points inside a bounding box, all bounds
+    /// being inclusive.
+    ///
+    /// When `min_longitude > max_longitude` the box crosses the dateline
+    /// and the query matches either side of it.
+    pub fn new_box_query<C: Codec>(
+        field: String,
+        min_latitude: f64,
+        max_latitude: f64,
+        min_longitude: f64,
+        max_longitude: f64,
+    ) -> Result<Box<dyn Query<C>>> {
+        let min_lat = Self::encode_latitude_ceil(min_latitude)?;
+        let max_lat = Self::encode_latitude(max_latitude)?;
+        let min_lon = Self::encode_longitude_ceil(min_longitude)?;
+        let max_lon = Self::encode_longitude(max_longitude)?;
+
+        if min_longitude > max_longitude {
+            let east = Self::new_box_internal(
+                field.clone(),
+                [min_lat, min_lon],
+                [max_lat, i32::max_value()],
+            )?;
+            let west = Self::new_box_internal(
+                field,
+                [min_lat, i32::min_value()],
+                [max_lat, max_lon],
+            )?;
+            BooleanQuery::build(vec![], vec![east, west], vec![])
+        } else {
+            Self::new_box_internal(field, [min_lat, min_lon], [max_lat, max_lon])
+        }
+    }
+
+    fn new_box_internal<C: Codec>(
+        field: String,
+        lower: [i32; 2],
+        upper: [i32; 2],
+    ) -> Result<Box<dyn Query<C>>> {
+        Ok(Box::new(PointRangeQuery::new(
+            field,
+            IntPoint::pack(&lower),
+            IntPoint::pack(&upper),
+            2,
+            PointValueType::Integer,
+        )?))
+    }
+
+    fn check_latitude(latitude: f64) -> Result<()> {
+        if latitude.is_nan() || latitude < -90.0 || latitude > 90.0 {
+            bail!(IllegalArgument(format!(
+                "invalid latitude {}, must be in [-90.0, 90.0]",
+                latitude
+            )));
+        }
+        Ok(())
+    }
+
+    fn check_longitude(longitude: f64) -> Result<()> {
+        if longitude.is_nan() || longitude < -180.0 || longitude > 180.0 {
+            bail!(IllegalArgument(format!(
+                "invalid longitude {}, must be in [-180.0, 180.0]",
+                longitude
+            )));
+        }
+        Ok(())
+    }
+
+    // 90.0 and 180.0 would overflow the encoded i32
+    fn clamp_latitude(latitude: f64) -> f64 {
+        if latitude >= 90.0 {
+            DoublePoint::next_down(latitude)
+        } else {
+            latitude
+        }
+    }
+
+    fn clamp_longitude(longitude: f64) -> f64 {
+        if longitude >= 180.0 {
+            DoublePoint::next_down(longitude)
+        } else {
+            longitude
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::codec::CodecEnum;
+    use core::doc::{Field, FieldType};
+    use core::index::{IndexWriter, IndexWriterConfig};
+    use core::search::collector::top_docs::TopDocsCollector;
+    use core::search::searcher::{DefaultIndexSearcher, IndexSearcher};
+    use core::store::RAMDirectory;
+    use core::util::{DocId, VariantValue};
+
+    use std::sync::Arc;
+
+    fn bounds(query: Box<dyn Query<CodecEnum>>) -> String {
+        let query = format!("{}", query);
+        query[query.find("lower:").unwrap()..query.len() - 1].to_string()
+    }
+
+    // the docs of a single segment whose values of the one-dimension point
+    // field "f" are `points`, matched by `query`
+    fn matching_docs(points: Vec<Vec<u8>>, query: Box<dyn Query<CodecEnum>>) -> Vec<DocId> {
+        let dir = Arc::new(RAMDirectory::default());
+        let writer = IndexWriter::new(dir, Arc::new(IndexWriterConfig::default())).unwrap();
+        for point in points {
+            let mut field_type = FieldType::default();
+            field_type.set_dimensions(1, point.len() as u32).unwrap();
+            let value = Some(VariantValue::Binary(point));
+            writer
+                .add_document(vec![Field::new("f".into(), field_type, value, None)])
+                .unwrap();
+        }
+        let reader = Arc::new(writer.get_reader(true, false).unwrap());
+        let searcher = DefaultIndexSearcher::new(Arc::clone(&reader));
+        let mut collector = TopDocsCollector::new(100);
+        searcher.search(query.as_ref(), &mut collector).unwrap();
+        let mut docs: Vec<DocId> = collector
+            .top_docs()
+            .score_docs()
+            .iter()
+            .map(|d| d.doc_id())
+            .collect();
+        docs.sort();
+        writer.close().unwrap();
+        docs
+    }
+
+    #[test]
+    fn test_long_bounded_range_query() {
+        let max = i64::max_value();
+        let values = [9, 10, 11, 19, 20, 21, max];
+        let search = |lower, lower_inclusive, upper, upper_inclusive| {
+            let points = values.iter().map(|&v| LongPoint::pack(&[v])).collect();
+            let query = LongPoint::new_bounded_range_query(
+                "f".into(),
+                lower,
+                lower_inclusive,
+                upper,
+                upper_inclusive,
+            )
+            .unwrap();
+            matching_docs(points, query)
+        };
+        assert_eq!(search(10, false, 20, true), vec![2, 3, 4]);
+        assert_eq!(search(10, true, 20, false), vec![1, 2, 3]);
+        assert_eq!(search(10, true, 20, true), vec![1, 2, 3, 4]);
+        // no value is above the greatest long
+        assert!(search(max, false, max, true).is_empty());
+        assert_eq!(search(max, true, max, true), vec![6]);
+    }
+
+    #[test]
+    fn test_double_bounded_range_query() {
+        assert!(DoublePoint::next_up(1.0) > 1.0 && DoublePoint::next_down(2.0) < 2.0);
+        let infinity = ::std::f64::INFINITY;
+        let values = [
+            1.0,
+            DoublePoint::next_up(1.0),
+            1.5,
+            DoublePoint::next_down(2.0),
+            2.0,
+            -1.5,
+            2.5,
+            infinity,
+        ];
+        let search = |lower, lower_inclusive, upper, upper_inclusive| {
+            let points = values.iter().map(|&v| DoublePoint::pack(&[v])).collect();
+            let query = DoublePoint::new_bounded_range_query(
+                "f".into(),
+                lower,
+                lower_inclusive,
+                upper,
+                upper_inclusive,
+            )
+            .unwrap();
+            matching_docs(points, query)
+        };
+        // the exclusive bounds only leave out the bounds themselves
+        assert_eq!(search(1.0, false, 2.0, false), vec![1, 2, 3]);
+        assert_eq!(search(-1.5, true, 2.5, true), vec![0, 1, 2, 3, 4, 5, 6]);
+        assert_eq!(search(2.0, true, infinity, true), vec![4, 6, 7]);
+        // no value is above infinity
+        assert!(search(infinity, false, infinity, true).is_empty());
+    }
+
+    #[test]
+    fn test_lat_lon_encoding() {
+        for &(lat, lon) in &[(0.0, 0.0), (45.5, -122.7), (-90.0, -180.0), (90.0, 180.0)] {
+            let lat_encoded = LatLonPoint::encode_latitude(lat).unwrap();
+            let lon_encoded = LatLonPoint::encode_longitude(lon).unwrap();
+            assert!((LatLonPoint::decode_latitude(lat_encoded) - lat).abs() < 1e-7);
+            assert!((LatLonPoint::decode_longitude(lon_encoded) - lon).abs() < 1e-7);
+            assert!(LatLonPoint::encode_latitude_ceil(lat).unwrap() >= lat_encoded);
+        }
+        assert_eq!(LatLonPoint::encode_latitude(-90.0).unwrap(), i32::min_value());
+        assert_eq!(LatLonPoint::encode_longitude(180.0).unwrap(), i32::max_value());
+        assert_eq!(LatLonPoint::encode_latitude_ceil(90.0).unwrap(), i32::max_value());
+        assert_eq!(LatLonPoint::encode_longitude_ceil(180.0).unwrap(), i32::max_value());
+        assert!(LatLonPoint::encode_latitude(90.5).is_err());
+        assert!(LatLonPoint::pack(0.0, -180.5).is_err());
+    }
+
+    #[test]
+    fn test_lat_lon_box_query() {
+        let query: Box<dyn Query<CodecEnum>> =
+            LatLonPoint::new_box_query("loc".into(), 10.0, 20.0, 30.0, 40.0).unwrap();
+        assert!(format!("{}", query).contains("num_dims: 2"));
+        let lower = format!(
+            "lower: [{}, {}]",
+            LatLonPoint::encode_latitude_ceil(10.0).unwrap(),
+            LatLonPoint::encode_longitude_ceil(30.0).unwrap()
+        );
+        assert!(bounds(query).starts_with(&lower));
+
+        // crossing the dateline
+        let query: Box<dyn Query<CodecEnum>> =
+            LatLonPoint::new_box_query("loc".into(), 10.0, 20.0, 170.0, -170.0).unwrap();
+        assert!(query.as_any().downcast_ref::<BooleanQuery<CodecEnum>>().is_some());
+        assert!(LatLonPoint::new_box_query::<CodecEnum>("loc".into(), 91.0, 0.0, 0.0, 0.0)
+            .is_err());
+    }
+}
@@ -29,6 +29,7 @@ use std::f32::EPSILON;
 pub mod frag_list_builder;
 pub mod fragments_builder;
 pub mod fvh_highlighter;
+pub mod unified_highlighter;
 
 ///
 // Encodes original text. The Encoder works with the {@link Formatter} to generate output.
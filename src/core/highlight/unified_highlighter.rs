@@ -0,0 +1,682 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::analysis::whitespace_tokenizer::WhitespaceTokenizer;
+use core::analysis::{TokenStream, OFFSET_GAP, POSITION_INCREMENT_GAP};
+use core::codec::Codec;
+use core::highlight::{DefaultEncoder, Encoder};
+use core::index::{Fieldable, IndexReader, LeafReaderContext, Term, TermIterator, Terms};
+use core::search::boolean_query::BooleanQuery;
+use core::search::boost::BoostQuery;
+use core::search::phrase_query::PhraseQuery;
+use core::search::posting_iterator::{PostingIterator, PostingIteratorFlags};
+use core::search::searcher::{IndexSearcher, SearchPlanBuilder};
+use core::search::spans::span::{
+    PostingsFlag, SpanCollector, SpanQuery, SpanQueryEnum, SpanWeight, Spans, NO_MORE_POSITIONS,
+};
+use core::search::spans::span_boost::SpanBoostQuery;
+use core::search::spans::span_near::{SpanNearQuery, SpanNearQueryBuilder};
+use core::search::spans::span_not::SpanNotQuery;
+use core::search::spans::span_or::SpanOrQuery;
+use core::search::spans::span_term::SpanTermQuery;
+use core::search::{DocIterator, Query};
+use core::util::DocId;
+use error::Result;
+
+use std::collections::HashMap;
+use std::io::Cursor;
+
+pub const DEFAULT_MAX_PASSAGES: usize = 1;
+pub const DEFAULT_MAX_PASSAGE_LENGTH: usize = 256;
+
+/// Joins the values of a multi-valued field, it is `OFFSET_GAP` chars long so
+/// the offsets indexed for each value point into the joined text.
+pub const MULTI_VALUED_SEPARATOR: &str = "\u{2029}";
+
+/// The chars ending a passage, passages never span several values.
+const PASSAGE_ENDS: &str = ".!?\n。！？\u{2029}";
+
+/// Builds the token stream of a field value, like the one it was indexed with.
+pub type Analyzer = dyn Fn(&str) -> Box<dyn TokenStream>;
+
+/// A passage of a field value, with its matches surrounded by tags.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snippet {
+    pub text: String,
+    /// Char offset of the passage in the values of the field joined by
+    /// `MULTI_VALUED_SEPARATOR`.
+    pub start_offset: usize,
+    /// Char offset of the end of the passage, exclusive.
+    pub end_offset: usize,
+    pub num_matches: usize,
+}
+
+/// Highlights the matches of a query in the stored values of a field, using
+/// the offsets indexed in the postings of the field when it is indexed with
+/// `IndexOptions::DocsAndFreqsAndPositionsAndOffsets`, so the values aren't
+/// analyzed again.
+///
+/// Otherwise the values are re-analyzed with the analyzer, by default a
+/// `WhitespaceTokenizer`, which must produce the indexed tokens. Phrase
+/// queries are then matched on the token positions, and span queries
+/// highlight all the occurrences of their terms.
+///
+/// Phrase and span queries, also inside boolean and boost queries, only
+/// highlight the positions of their actual matches. The other queries
+/// highlight all the occurrences of their `extract_terms`. The filter and
+/// `SHOULD_NOT` clauses of a boolean query are not highlighted.
+///
+/// The value is split into passages at sentence ends, or after at most
+/// `max_passage_length` chars, without cutting a match. The passages with the
+/// most matches are returned, ordered by offset.
+pub struct UnifiedHighlighter {
+    pre_tag: String,
+    post_tag: String,
+    max_passages: usize,
+    max_passage_length: usize,
+    encoder: Box<dyn Encoder>,
+    analyzer: Box<Analyzer>,
+}
+
+impl Default for UnifiedHighlighter {
+    fn default() -> Self {
+        UnifiedHighlighter {
+            pre_tag: "<b>".to_string(),
+            post_tag: "</b>".to_string(),
+            max_passages: DEFAULT_MAX_PASSAGES,
+            max_passage_length: DEFAULT_MAX_PASSAGE_LENGTH,
+            encoder: Box::new(DefaultEncoder),
+            analyzer: Box::new(|text: &str| -> Box<dyn TokenStream> {
+                Box::new(WhitespaceTokenizer::new(Box::new(Cursor::new(
+                    text.as_bytes().to_vec(),
+                ))))
+            }),
+        }
+    }
+}
+
+impl UnifiedHighlighter {
+    pub fn with_tags(mut self, pre_tag: &str, post_tag: &str) -> UnifiedHighlighter {
+        self.pre_tag = pre_tag.to_string();
+        self.post_tag = post_tag.to_string();
+        self
+    }
+
+    pub fn with_max_passages(mut self, max_passages: usize) -> UnifiedHighlighter {
+        assert!(max_passages > 0);
+        self.max_passages = max_passages;
+        self
+    }
+
+    pub fn with_max_passage_length(mut self, max_passage_length: usize) -> UnifiedHighlighter {
+        assert!(max_passage_length > 0);
+        self.max_passage_length = max_passage_length;
+        self
+    }
+
+    /// Encodes the text around the tags, e.g. with `SimpleHtmlEncoder`.
+    pub fn with_encoder(mut self, encoder: Box<dyn Encoder>) -> UnifiedHighlighter {
+        self.encoder = encoder;
+        self
+    }
+
+    /// Re-analyzes the values of the fields indexed without offsets.
+    pub fn with_analyzer(mut self, analyzer: Box<Analyzer>) -> UnifiedHighlighter {
+        self.analyzer = analyzer;
+        self
+    }
+
+    /// Returns the best passages of the stored values of `field` in `doc`,
+    /// none if the query doesn't match the field of the doc.
+    pub fn highlight<C: Codec, IS: IndexSearcher<C>>(
+        &self,
+        searcher: &IS,
+        query: &dyn Query<C>,
+        field: &str,
+        doc: DocId,
+    ) -> Result<Vec<Snippet>> {
+        let reader = searcher.reader();
+        let leaf = reader.leaf_reader_for_doc(doc);
+        let terms = match leaf.reader.terms(field)? {
+            Some(terms) => terms,
+            None => return Ok(vec![]),
+        };
+
+        let document = reader.document(doc, &[field.to_string()])?;
+        let values: Vec<String> = document
+            .fields
+            .iter()
+            .filter_map(|f| f.field.string_value())
+            .map(str::to_string)
+            .collect();
+        if values.is_empty() {
+            return Ok(vec![]);
+        }
+        let mut offsets = vec![];
+        if terms.has_offsets()? {
+            let source = OffsetSource::Postings {
+                searcher,
+                leaf: &leaf,
+                doc: doc - leaf.doc_base,
+            };
+            collect_offsets(&source, query, field, &mut offsets)?;
+        } else {
+            let tokens = self.analyze(&values)?;
+            collect_offsets(&OffsetSource::Tokens(&tokens), query, field, &mut offsets)?;
+        }
+        Ok(self.snippets(&values.join(MULTI_VALUED_SEPARATOR), offsets))
+    }
+
+    /// The tokens of `values` by term, with the positions and offsets the
+    /// indexing chain gives them.
+    fn analyze(&self, values: &[String]) -> Result<HashMap<Vec<u8>, Vec<Token>>> {
+        let mut tokens: HashMap<Vec<u8>, Vec<Token>> = HashMap::new();
+        let mut position = -1;
+        let mut offset = 0;
+        for value in values {
+            let mut token_stream = (self.analyzer)(value.as_str());
+            token_stream.reset()?;
+            while token_stream.increment_token()? {
+                position += token_stream
+                    .position_attribute_mut()
+                    .get_position_increment() as i32;
+                let token = Token {
+                    position,
+                    start_offset: (offset + token_stream.offset_attribute().start_offset()) as i32,
+                    end_offset: (offset + token_stream.offset_attribute().end_offset()) as i32,
+                };
+                let term = token_stream
+                    .term_bytes_attribute()
+                    .get_bytes_ref()
+                    .bytes()
+                    .to_vec();
+                tokens.entry(term).or_insert_with(Vec::new).push(token);
+            }
+            token_stream.end()?;
+            position += token_stream
+                .position_attribute_mut()
+                .get_position_increment() as i32
+                + POSITION_INCREMENT_GAP;
+            offset += value.chars().count() + OFFSET_GAP;
+        }
+        Ok(tokens)
+    }
+
+    fn snippets(&self, text: &str, mut offsets: Vec<(i32, i32)>) -> Vec<Snippet> {
+        let chars: Vec<char> = text.chars().collect();
+        offsets.retain(|&(start, end)| start >= 0 && start < end && end as usize <= chars.len());
+        offsets.sort();
+        // the overlapping matches are merged
+        let mut matches: Vec<(usize, usize)> = vec![];
+        for (start, end) in offsets {
+            let (start, end) = (start as usize, end as usize);
+            match matches.last_mut() {
+                Some(last) if start < last.1 => last.1 = last.1.max(end),
+                _ => matches.push((start, end)),
+            }
+        }
+        if matches.is_empty() {
+            return vec![];
+        }
+
+        // whether a passage may end at each offset
+        let mut breakable = vec![true; chars.len() + 1];
+        for &(start, end) in &matches {
+            for b in &mut breakable[start + 1..end] {
+                *b = false;
+            }
+        }
+        let mut passages = vec![];
+        let mut start = 0;
+        for (i, c) in chars.iter().enumerate() {
+            if (PASSAGE_ENDS.contains(*c) || i + 1 - start >= self.max_passage_length)
+                && breakable[i + 1]
+            {
+                passages.push((start, i + 1));
+                start = i + 1;
+            }
+        }
+        if start < chars.len() {
+            passages.push((start, chars.len()));
+        }
+
+        // (passage, first match, number of matches)
+        let mut scored = vec![];
+        let mut m = 0;
+        for (p, &(_, end)) in passages.iter().enumerate() {
+            let first = m;
+            while m < matches.len() && matches[m].0 < end {
+                m += 1;
+            }
+            if m > first {
+                scored.push((p, first, m - first));
+            }
+        }
+        scored.sort_by(|a, b| b.2.cmp(&a.2).then(a.0.cmp(&b.0)));
+        scored.truncate(self.max_passages);
+        scored.sort();
+
+        scored
+            .into_iter()
+            .map(|(p, first, num_matches)| {
+                let (mut start, mut end) = passages[p];
+                while chars[start].is_whitespace() && start < matches[first].0 {
+                    start += 1;
+                }
+                while chars[end - 1].is_whitespace() && end > matches[first + num_matches - 1].1 {
+                    end -= 1;
+                }
+                let mut text = String::new();
+                let mut pos = start;
+                for &(match_start, match_end) in &matches[first..first + num_matches] {
+                    self.push_encoded(&mut text, &chars[pos..match_start]);
+                    text.push_str(&self.pre_tag);
+                    self.push_encoded(&mut text, &chars[match_start..match_end]);
+                    text.push_str(&self.post_tag);
+                    pos = match_end;
+                }
+                self.push_encoded(&mut text, &chars[pos..end]);
+                Snippet {
+                    text,
+                    start_offset: start,
+                    end_offset: end,
+                    num_matches,
+                }
+            })
+            .collect()
+    }
+
+    fn push_encoded(&self, text: &mut String, chars: &[char]) {
+        let original: String = chars.iter().collect();
+        text.push_str(&self.encoder.encode_text(&original));
+    }
+}
+
+/// A position of a term in the re-analyzed values of a field.
+struct Token {
+    position: i32,
+    start_offset: i32,
+    end_offset: i32,
+}
+
+/// Where the offsets of the matches in a doc are read from.
+enum OffsetSource<'a, 'b, C: Codec> {
+    /// The postings of the field, indexed with offsets.
+    Postings {
+        searcher: &'a dyn SearchPlanBuilder<C>,
+        leaf: &'a LeafReaderContext<'b, C>,
+        doc: DocId,
+    },
+    /// The tokens of the re-analyzed values of the field.
+    Tokens(&'a HashMap<Vec<u8>, Vec<Token>>),
+}
+
+/// Adds the offsets of the matches of `query` in `field` of the doc.
+fn collect_offsets<C: Codec>(
+    source: &OffsetSource<'_, '_, C>,
+    query: &dyn Query<C>,
+    field: &str,
+    offsets: &mut Vec<(i32, i32)>,
+) -> Result<()> {
+    let any = query.as_any();
+    if let Some(query) = any.downcast_ref::<BooleanQuery<C>>() {
+        for sub in query.must_queries().iter().chain(query.should_queries()) {
+            collect_offsets(source, sub.as_ref(), field, offsets)?;
+        }
+        return Ok(());
+    }
+    if let Some(query) = any.downcast_ref::<BoostQuery<C>>() {
+        return collect_offsets(source, query.query(), field, offsets);
+    }
+    if let Some(query) = any.downcast_ref::<PhraseQuery>() {
+        if query.field() == field {
+            match *source {
+                OffsetSource::Postings { .. } => {
+                    let query = phrase_to_span_query(query)?;
+                    span_offsets(source, &query, field, offsets)?;
+                }
+                OffsetSource::Tokens(tokens) => phrase_token_offsets(tokens, query, offsets),
+            }
+        }
+        return Ok(());
+    }
+    if let Some(query) = any.downcast_ref::<SpanTermQuery>() {
+        return span_offsets(source, query, field, offsets);
+    }
+    if let Some(query) = any.downcast_ref::<SpanNearQuery>() {
+        return span_offsets(source, query, field, offsets);
+    }
+    if let Some(query) = any.downcast_ref::<SpanOrQuery>() {
+        return span_offsets(source, query, field, offsets);
+    }
+    if let Some(query) = any.downcast_ref::<SpanNotQuery>() {
+        return span_offsets(source, query, field, offsets);
+    }
+    if let Some(query) = any.downcast_ref::<SpanBoostQuery>() {
+        return span_offsets(source, query, field, offsets);
+    }
+    term_offsets(source, query, field, offsets)
+}
+
+/// A phrase query as an ordered span near query, or as an unordered one
+/// allowing the same number of moved positions if the phrase is sloppy.
+fn phrase_to_span_query(query: &PhraseQuery) -> Result<SpanNearQuery> {
+    let positions = query.positions();
+    let ordered = query.slop() == 0 && positions.windows(2).all(|w| w[0] < w[1]);
+    let mut builder = SpanNearQueryBuilder::new(query.field().to_string(), ordered);
+    for (i, term) in query.terms().iter().enumerate() {
+        if ordered && i > 0 && positions[i] - positions[i - 1] > 1 {
+            builder = builder.add_gap(positions[i] - positions[i - 1] - 1)?;
+        }
+        let clause = SpanQueryEnum::Term(SpanTermQuery::new(term.clone(), None));
+        builder = builder.add_clause(clause)?;
+    }
+    let slop = if ordered {
+        0
+    } else {
+        let width = positions[positions.len() - 1] + 1;
+        query.slop() + (width - positions.len() as i32).max(0)
+    };
+    Ok(builder.slop(slop).build())
+}
+
+fn span_offsets<C: Codec, Q: SpanQuery<C>>(
+    source: &OffsetSource<'_, '_, C>,
+    query: &Q,
+    field: &str,
+    offsets: &mut Vec<(i32, i32)>,
+) -> Result<()> {
+    if SpanQuery::<C>::field(query) != field {
+        return Ok(());
+    }
+    let (searcher, leaf, doc) = match *source {
+        OffsetSource::Postings {
+            searcher,
+            leaf,
+            doc,
+        } => (searcher, leaf, doc),
+        OffsetSource::Tokens(_) => return term_offsets(source, query, field, offsets),
+    };
+    let weight = query.span_weight(searcher, false)?;
+    if let Some(mut spans) = weight.get_spans(leaf, &PostingsFlag::Offsets)? {
+        if spans.advance(doc)? == doc {
+            let mut collector = OffsetsCollector { offsets };
+            while spans.next_start_position()? != NO_MORE_POSITIONS {
+                spans.collect(&mut collector)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Adds the offsets of the matches of a phrase in the re-analyzed tokens, with
+/// each term at its position in the phrase give or take the slop.
+fn phrase_token_offsets(
+    tokens: &HashMap<Vec<u8>, Vec<Token>>,
+    query: &PhraseQuery,
+    offsets: &mut Vec<(i32, i32)>,
+) {
+    let positions = query.positions();
+    let mut occurrences = Vec::with_capacity(positions.len());
+    for term in query.terms() {
+        match tokens.get(&term.bytes) {
+            Some(found) => occurrences.push(found),
+            None => return,
+        }
+    }
+    for first in occurrences[0] {
+        let base = first.position - positions[0];
+        let matched: Option<Vec<&Token>> = occurrences
+            .iter()
+            .zip(positions)
+            .map(|(found, &pos)| {
+                found
+                    .iter()
+                    .find(|t| (t.position - base - pos).abs() <= query.slop())
+            })
+            .collect();
+        if let Some(matched) = matched {
+            offsets.extend(matched.iter().map(|t| (t.start_offset, t.end_offset)));
+        }
+    }
+}
+
+/// Collects the offsets of the term positions of the spans.
+struct OffsetsCollector<'a> {
+    offsets: &'a mut Vec<(i32, i32)>,
+}
+
+impl<'a> SpanCollector for OffsetsCollector<'a> {
+    fn collect_leaf(
+        &mut self,
+        postings: &impl PostingIterator,
+        _position: i32,
+        _term: &Term,
+    ) -> Result<()> {
+        self.offsets.push((postings.start_offset()?, postings.end_offset()?));
+        Ok(())
+    }
+
+    fn reset(&mut self) {}
+}
+
+fn term_offsets<C: Codec>(
+    source: &OffsetSource<'_, '_, C>,
+    query: &dyn Query<C>,
+    field: &str,
+    offsets: &mut Vec<(i32, i32)>,
+) -> Result<()> {
+    let (leaf, doc) = match *source {
+        OffsetSource::Postings { leaf, doc, .. } => (leaf, doc),
+        OffsetSource::Tokens(tokens) => {
+            for term_query in query.extract_terms() {
+                let term = &term_query.term;
+                if term.field() != field {
+                    continue;
+                }
+                if let Some(found) = tokens.get(&term.bytes) {
+                    offsets.extend(found.iter().map(|t| (t.start_offset, t.end_offset)));
+                }
+            }
+            return Ok(());
+        }
+    };
+    let terms = match leaf.reader.terms(field)? {
+        Some(terms) => terms,
+        None => return Ok(()),
+    };
+    let mut terms_iter = terms.iterator()?;
+    for term_query in query.extract_terms() {
+        let term = &term_query.term;
+        if term.field() != field || !terms_iter.seek_exact(&term.bytes)? {
+            continue;
+        }
+        let mut postings = terms_iter.postings_with_flags(PostingIteratorFlags::OFFSETS)?;
+        if postings.advance(doc)? == doc {
+            for _ in 0..postings.freq()? {
+                postings.next_position()?;
+                offsets.push((postings.start_offset()?, postings.end_offset()?));
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::analysis::whitespace_tokenizer::WhitespaceTokenizer;
+    use core::analysis::TokenStream;
+    use core::codec::CodecEnum;
+    use core::doc::{Field, FieldType};
+    use core::highlight::SimpleHtmlEncoder;
+    use core::index::{IndexOptions, IndexWriter, IndexWriterConfig};
+    use core::search::searcher::DefaultIndexSearcher;
+    use core::search::term_query::TermQuery;
+    use core::store::RAMDirectory;
+    use core::util::VariantValue;
+
+    use std::io::Cursor;
+    use std::sync::Arc;
+
+    fn term(text: &str) -> Term {
+        Term::new("body".into(), text.as_bytes().to_vec())
+    }
+
+    fn term_query(text: &str) -> Box<dyn Query<CodecEnum>> {
+        Box::new(TermQuery::new(term(text), 1.0, None))
+    }
+
+    fn phrase_query(texts: &[&str]) -> Box<dyn Query<CodecEnum>> {
+        let terms = texts.iter().map(|t| term(t)).collect();
+        Box::new(PhraseQuery::build(terms, 0, None, None).unwrap())
+    }
+
+    #[test]
+    fn test_snippets() {
+        let highlighter = UnifiedHighlighter::default()
+            .with_max_passages(2)
+            .with_max_passage_length(6)
+            .with_encoder(Box::new(SimpleHtmlEncoder));
+        let text = "a <b> c d e f g h i j k";
+        // the passage at 6 is extended to not cut the match at 10, and the
+        // one at 19 has as many matches but comes later
+        let snippets = highlighter.snippets(text, vec![(0, 1), (10, 13), (2, 5), (20, 21)]);
+        assert_eq!(snippets.len(), 2);
+        assert_eq!(snippets[0].text, "<b>a</b> <b>&lt;b&gt;</b>");
+        assert_eq!(snippets[0].num_matches, 2);
+        assert_eq!(snippets[1].text, "c d <b>e f</b>");
+        assert_eq!((snippets[1].start_offset, snippets[1].end_offset), (6, 13));
+        assert!(highlighter.snippets(text, vec![]).is_empty());
+    }
+
+    #[test]
+    fn test_highlight() {
+        let dir = Arc::new(RAMDirectory::default());
+        let writer = IndexWriter::new(dir, Arc::new(IndexWriterConfig::default())).unwrap();
+        let mut field_type = FieldType::default();
+        field_type.stored = true;
+        field_type.index_options = IndexOptions::DocsAndFreqsAndPositionsAndOffsets;
+        let text = "the quick brown fox\na lazy dog sleeps\nthe fox is quick";
+        let tokens: Box<dyn TokenStream> = Box::new(WhitespaceTokenizer::new(Box::new(
+            Cursor::new(text.as_bytes().to_vec()),
+        )));
+        let doc: Vec<Box<dyn Fieldable>> = vec![Box::new(Field::new(
+            "body".into(),
+            field_type,
+            Some(VariantValue::VString(text.to_string())),
+            Some(tokens),
+        ))];
+        writer.add_document(doc).unwrap();
+        let reader = Arc::new(writer.get_reader(true, false).unwrap());
+        let searcher = DefaultIndexSearcher::new(Arc::clone(&reader));
+
+        let highlight = |highlighter: &UnifiedHighlighter, query: &dyn Query<CodecEnum>| {
+            highlighter
+                .highlight(&searcher, query, "body", 0)
+                .unwrap()
+                .into_iter()
+                .map(|s| s.text)
+                .collect::<Vec<String>>()
+        };
+        let one = UnifiedHighlighter::default();
+        let three = UnifiedHighlighter::default().with_max_passages(3);
+
+        // ties go to the first passage
+        assert_eq!(
+            highlight(&one, term_query("quick").as_ref()),
+            vec!["the <b>quick</b> brown fox"]
+        );
+        assert_eq!(
+            highlight(&three, term_query("fox").as_ref()),
+            vec!["the quick brown <b>fox</b>", "the <b>fox</b> is quick"]
+        );
+        // the last "quick" is not in a phrase match
+        assert_eq!(
+            highlight(&three, phrase_query(&["quick", "brown"]).as_ref()),
+            vec!["the <b>quick</b> <b>brown</b> fox"]
+        );
+        let query = BooleanQuery::build(
+            vec![phrase_query(&["quick", "brown"])],
+            vec![term_query("dog")],
+            vec![],
+        )
+        .unwrap();
+        assert_eq!(
+            highlight(&three, query.as_ref()),
+            vec!["the <b>quick</b> <b>brown</b> fox", "a lazy <b>dog</b> sleeps"]
+        );
+        assert!(highlight(&three, term_query("cat").as_ref()).is_empty());
+        assert!(highlight(&three, phrase_query(&["brown", "quick"]).as_ref()).is_empty());
+
+        writer.close().unwrap();
+    }
+
+    #[test]
+    fn test_highlight_multi_valued() {
+        let values = ["the quick fox", "a lazy dog. the fox sleeps"];
+        // from the indexed offsets, then by re-analyzing the values
+        for &index_options in &[
+            IndexOptions::DocsAndFreqsAndPositionsAndOffsets,
+            IndexOptions::DocsAndFreqsAndPositions,
+        ] {
+            let dir = Arc::new(RAMDirectory::default());
+            let writer = IndexWriter::new(dir, Arc::new(IndexWriterConfig::default())).unwrap();
+            let mut field_type = FieldType::default();
+            field_type.stored = true;
+            field_type.index_options = index_options;
+            let doc: Vec<Box<dyn Fieldable>> = values
+                .iter()
+                .map(|value| {
+                    let tokens: Box<dyn TokenStream> = Box::new(WhitespaceTokenizer::new(
+                        Box::new(Cursor::new(value.as_bytes().to_vec())),
+                    ));
+                    Box::new(Field::new(
+                        "body".into(),
+                        field_type.clone(),
+                        Some(VariantValue::VString(value.to_string())),
+                        Some(tokens),
+                    )) as Box<dyn Fieldable>
+                })
+                .collect();
+            writer.add_document(doc).unwrap();
+            let reader = Arc::new(writer.get_reader(true, false).unwrap());
+            let searcher = DefaultIndexSearcher::new(Arc::clone(&reader));
+            let highlighter = UnifiedHighlighter::default().with_max_passages(3);
+            let highlight = |query: &dyn Query<CodecEnum>| {
+                highlighter.highlight(&searcher, query, "body", 0).unwrap()
+            };
+            let texts = |snippets: Vec<Snippet>| {
+                snippets.into_iter().map(|s| s.text).collect::<Vec<String>>()
+            };
+
+            // the passages don't span the values
+            assert_eq!(
+                texts(highlight(term_query("fox").as_ref())),
+                vec!["the quick <b>fox</b>", "the <b>fox</b> sleeps"]
+            );
+            assert_eq!(
+                texts(highlight(phrase_query(&["quick", "fox"]).as_ref())),
+                vec!["the <b>quick</b> <b>fox</b>"]
+            );
+            // the second value starts after the first one and the separator
+            let snippets = highlight(phrase_query(&["lazy", "dog"]).as_ref());
+            assert_eq!(snippets.len(), 1);
+            assert_eq!(snippets[0].text, "a <b>lazy</b> <b>dog</b>.");
+            assert_eq!((snippets[0].start_offset, snippets[0].end_offset), (14, 25));
+            assert!(highlight(phrase_query(&["fox", "quick"]).as_ref()).is_empty());
+
+            writer.close().unwrap();
+        }
+    }
+}
@@ -0,0 +1,481 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::codec::Codec;
+use core::index::merge_policy::MergePolicy;
+use core::index::merge_scheduler::MergeScheduler;
+use core::index::{Fieldable, IndexWriter};
+use core::store::Directory;
+use error::ErrorKind::{AlreadyClosed, IllegalArgument, IllegalState};
+use error::Result;
+
+use crossbeam::channel::{bounded, Receiver, Sender, TrySendError};
+
+use std::future::Future;
+use std::mem;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, Weak};
+use std::task::{Context, Poll, Waker};
+use std::thread::{self, JoinHandle};
+
+type Job<D, C, MS, MP> = Box<dyn FnOnce(&IndexWriter<D, C, MS, MP>) + Send>;
+
+/// An async friendly wrapper of `IndexWriter`.
+///
+/// The blocking writer calls are run on a dedicated pool of indexing threads,
+/// and each call returns a future which resolves once the documents are
+/// buffered, with the sequence number of the operation.
+///
+/// Calls are queued in a bounded queue. When indexing is stalled because the
+/// flush control is waiting for pending flushes, the indexing threads block,
+/// the queue fills up and the returned futures stay pending until there is
+/// room again, so that callers get backpressure without blocking their own
+/// threads.
+///
+/// Dropping the `AsyncIndexWriter` waits for the queued calls to be done, and
+/// the futures which aren't queued yet fail with `AlreadyClosed`. It neither
+/// flushes, commits nor closes the underlying `IndexWriter`: the added
+/// documents stay buffered until `commit` or `close` is called on `writer()`.
+pub struct AsyncIndexWriter<D, C, MS, MP>
+where
+    D: Directory + Send + Sync + 'static,
+    C: Codec,
+    MS: MergeScheduler,
+    MP: MergePolicy,
+{
+    writer: IndexWriter<D, C, MS, MP>,
+    sender: Option<Arc<Sender<Job<D, C, MS, MP>>>>,
+    waiting: Arc<WaitingSenders>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl<D, C, MS, MP> AsyncIndexWriter<D, C, MS, MP>
+where
+    D: Directory + Send + Sync + 'static,
+    C: Codec,
+    MS: MergeScheduler,
+    MP: MergePolicy,
+{
+    /// Creates a wrapper running the calls on `num_threads` indexing threads,
+    /// with at most `queue_size` calls waiting for a free thread.
+    pub fn new(
+        writer: IndexWriter<D, C, MS, MP>,
+        num_threads: usize,
+        queue_size: usize,
+    ) -> Result<Self> {
+        if num_threads == 0 {
+            bail!(IllegalArgument("num_threads must be greater than 0".into()));
+        }
+
+        let (sender, receiver) = bounded(queue_size);
+        let waiting = Arc::new(WaitingSenders::default());
+        let mut workers = Vec::with_capacity(num_threads);
+        for i in 0..num_threads {
+            let writer = writer.clone();
+            let receiver: Receiver<Job<D, C, MS, MP>> = receiver.clone();
+            let waiting = Arc::clone(&waiting);
+            let handle = thread::Builder::new()
+                .name(format!("Rucene Async Index Thread #{}", i))
+                .spawn(move || {
+                    while let Ok(job) = receiver.recv() {
+                        // a slot of the queue was freed
+                        waiting.wake_all();
+                        job(&writer);
+                    }
+                })?;
+            workers.push(handle);
+        }
+
+        Ok(AsyncIndexWriter {
+            writer,
+            sender: Some(Arc::new(sender)),
+            waiting,
+            workers,
+        })
+    }
+
+    /// Returns the wrapped writer, for the calls which don't need to be async,
+    /// such as `commit`.
+    pub fn writer(&self) -> &IndexWriter<D, C, MS, MP> {
+        &self.writer
+    }
+
+    /// Async version of `IndexWriter::add_document`.
+    pub fn add_document<F: Fieldable + Send + 'static>(
+        &self,
+        doc: Vec<F>,
+    ) -> IndexingFuture<D, C, MS, MP> {
+        self.submit(move |writer| writer.add_document(doc))
+    }
+
+    /// Async version of `IndexWriter::add_documents`.
+    pub fn add_documents<F: Fieldable + Send + 'static>(
+        &self,
+        docs: Vec<Vec<F>>,
+    ) -> IndexingFuture<D, C, MS, MP> {
+        self.submit(move |writer| writer.add_documents(docs))
+    }
+
+    fn submit<T>(&self, task: T) -> IndexingFuture<D, C, MS, MP>
+    where
+        T: FnOnce(&IndexWriter<D, C, MS, MP>) -> Result<u64> + Send + 'static,
+    {
+        let completion = Arc::new(Completion::default());
+        let handle = CompletionHandle(Some(Arc::clone(&completion)));
+        let job: Job<D, C, MS, MP> = Box::new(move |writer: &IndexWriter<D, C, MS, MP>| {
+            handle.complete(task(writer))
+        });
+        let sender = match self.sender {
+            Some(ref s) => Arc::downgrade(s),
+            None => Weak::new(),
+        };
+        IndexingFuture {
+            job: Some(job),
+            sender,
+            waiting: Arc::clone(&self.waiting),
+            completion,
+        }
+    }
+}
+
+impl<D, C, MS, MP> Drop for AsyncIndexWriter<D, C, MS, MP>
+where
+    D: Directory + Send + Sync + 'static,
+    C: Codec,
+    MS: MergeScheduler,
+    MP: MergePolicy,
+{
+    fn drop(&mut self) {
+        // the indexing threads exit once the queued jobs are done, and the
+        // futures which aren't queued yet will fail
+        self.sender.take();
+        self.waiting.wake_all();
+        for worker in self.workers.drain(..) {
+            if worker.join().is_err() {
+                error!("async index thread panicked");
+            }
+        }
+    }
+}
+
+/// The future returned by the calls of `AsyncIndexWriter`.
+pub struct IndexingFuture<D, C, MS, MP>
+where
+    D: Directory + Send + Sync + 'static,
+    C: Codec,
+    MS: MergeScheduler,
+    MP: MergePolicy,
+{
+    // the job to queue, None once it was queued
+    job: Option<Job<D, C, MS, MP>>,
+    sender: Weak<Sender<Job<D, C, MS, MP>>>,
+    waiting: Arc<WaitingSenders>,
+    completion: Arc<Completion>,
+}
+
+impl<D, C, MS, MP> IndexingFuture<D, C, MS, MP>
+where
+    D: Directory + Send + Sync + 'static,
+    C: Codec,
+    MS: MergeScheduler,
+    MP: MergePolicy,
+{
+    /// Tries to queue the job, returns `false` if the queue is full.
+    fn try_queue(&mut self, cx: &mut Context<'_>) -> Result<bool> {
+        let job = match self.job.take() {
+            Some(job) => job,
+            None => return Ok(true),
+        };
+        let sender = match self.sender.upgrade() {
+            Some(sender) => sender,
+            None => bail!(AlreadyClosed("async index writer is closed".into())),
+        };
+        let job = match sender.try_send(job) {
+            Ok(()) => return Ok(true),
+            Err(TrySendError::Full(job)) => job,
+            Err(TrySendError::Disconnected(_)) => {
+                bail!(IllegalState("async index threads are gone".into()))
+            }
+        };
+
+        self.waiting.register(cx.waker());
+        // an indexing thread may have taken a job before we registered
+        match sender.try_send(job) {
+            Ok(()) => Ok(true),
+            Err(TrySendError::Full(job)) => {
+                self.job = Some(job);
+                Ok(false)
+            }
+            Err(TrySendError::Disconnected(_)) => {
+                bail!(IllegalState("async index threads are gone".into()))
+            }
+        }
+    }
+}
+
+impl<D, C, MS, MP> Future for IndexingFuture<D, C, MS, MP>
+where
+    D: Directory + Send + Sync + 'static,
+    C: Codec,
+    MS: MergeScheduler,
+    MP: MergePolicy,
+{
+    type Output = Result<u64>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<u64>> {
+        let this = self.get_mut();
+        match this.try_queue(cx) {
+            Ok(true) => this.completion.poll(cx),
+            Ok(false) => Poll::Pending,
+            Err(e) => Poll::Ready(Err(e)),
+        }
+    }
+}
+
+/// The wakers of the futures waiting for room in the queue.
+#[derive(Default)]
+struct WaitingSenders {
+    wakers: Mutex<Vec<Waker>>,
+}
+
+impl WaitingSenders {
+    fn register(&self, waker: &Waker) {
+        let mut wakers = self.wakers.lock().unwrap_or_else(|e| e.into_inner());
+        if !wakers.iter().any(|w| w.will_wake(waker)) {
+            wakers.push(waker.clone());
+        }
+    }
+
+    fn wake_all(&self) {
+        let wakers = {
+            let mut wakers = self.wakers.lock().unwrap_or_else(|e| e.into_inner());
+            mem::replace(&mut *wakers, Vec::new())
+        };
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+}
+
+#[derive(Default)]
+struct Completion {
+    state: Mutex<CompletionState>,
+}
+
+#[derive(Default)]
+struct CompletionState {
+    result: Option<Result<u64>>,
+    waker: Option<Waker>,
+}
+
+impl Completion {
+    fn complete(&self, result: Result<u64>) {
+        let waker = {
+            let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+            state.result = Some(result);
+            state.waker.take()
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+
+    fn poll(&self, cx: &mut Context<'_>) -> Poll<Result<u64>> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        match state.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// Completes the future of a job, with an error if the job is dropped before
+/// running to the end, e.g. because the indexing thread panicked.
+struct CompletionHandle(Option<Arc<Completion>>);
+
+impl CompletionHandle {
+    fn complete(mut self, result: Result<u64>) {
+        if let Some(completion) = self.0.take() {
+            completion.complete(result);
+        }
+    }
+}
+
+impl Drop for CompletionHandle {
+    fn drop(&mut self) {
+        if let Some(completion) = self.0.take() {
+            completion.complete(Err(
+                IllegalState("indexing job was dropped before completion".into()).into(),
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::doc::NumericDocValuesField;
+    use core::index::{get_last_commit_generation, IndexReader, IndexWriterConfig};
+    use core::store::RAMDirectory;
+    use error::{Error, ErrorKind};
+
+    use std::sync::mpsc;
+    use std::task::{RawWaker, RawWakerVTable};
+    use std::thread::Thread;
+
+    // a waker unparking the thread which created it
+    fn thread_waker() -> Waker {
+        unsafe fn clone_waker(data: *const ()) -> RawWaker {
+            let thread = Arc::from_raw(data as *const Thread);
+            let cloned = Arc::clone(&thread);
+            mem::forget(thread);
+            RawWaker::new(Arc::into_raw(cloned) as *const (), &VTABLE)
+        }
+        unsafe fn wake(data: *const ()) {
+            Arc::from_raw(data as *const Thread).unpark();
+        }
+        unsafe fn wake_by_ref(data: *const ()) {
+            (*(data as *const Thread)).unpark();
+        }
+        unsafe fn drop_waker(data: *const ()) {
+            mem::drop(Arc::from_raw(data as *const Thread));
+        }
+        static VTABLE: RawWakerVTable =
+            RawWakerVTable::new(clone_waker, wake, wake_by_ref, drop_waker);
+
+        let thread = Arc::new(thread::current());
+        let raw = RawWaker::new(Arc::into_raw(thread) as *const (), &VTABLE);
+        unsafe { Waker::from_raw(raw) }
+    }
+
+    fn poll_once<F: Future + Unpin>(future: &mut F) -> Poll<F::Output> {
+        let waker = thread_waker();
+        let mut cx = Context::from_waker(&waker);
+        Pin::new(future).poll(&mut cx)
+    }
+
+    fn block_on<F: Future + Unpin>(mut future: F) -> F::Output {
+        loop {
+            match poll_once(&mut future) {
+                Poll::Ready(output) => return output,
+                Poll::Pending => thread::park(),
+            }
+        }
+    }
+
+    fn doc(id: i64) -> Vec<NumericDocValuesField> {
+        vec![NumericDocValuesField::new("id", id)]
+    }
+
+    #[test]
+    fn test_add_documents() {
+        let config = Arc::new(IndexWriterConfig::default());
+        let writer = IndexWriter::new(Arc::new(RAMDirectory::default()), config).unwrap();
+        let async_writer = AsyncIndexWriter::new(writer, 2, 4).unwrap();
+
+        let futures: Vec<_> = (0..10).map(|i| async_writer.add_document(doc(i))).collect();
+        let mut seq_nos: Vec<u64> = futures.into_iter().map(|f| block_on(f).unwrap()).collect();
+        let docs = vec![doc(10), doc(11)];
+        seq_nos.push(block_on(async_writer.add_documents(docs)).unwrap());
+        seq_nos.sort();
+        seq_nos.dedup();
+        assert_eq!(seq_nos.len(), 11);
+
+        // the resolved documents are buffered, and visible once flushed
+        let reader = async_writer.writer().get_reader(true, false).unwrap();
+        assert_eq!(reader.num_docs(), 12);
+        async_writer.writer().close().unwrap();
+    }
+
+    #[test]
+    fn test_backpressure() {
+        let config = Arc::new(IndexWriterConfig::default());
+        let writer = IndexWriter::new(Arc::new(RAMDirectory::default()), config).unwrap();
+        let async_writer = AsyncIndexWriter::new(writer, 1, 1).unwrap();
+
+        // blocks the only indexing thread until `release` is sent to
+        let (started, wait_started) = mpsc::channel();
+        let (release, wait_release) = mpsc::channel::<()>();
+        let mut blocking = async_writer.submit(move |writer| {
+            started.send(()).unwrap();
+            wait_release.recv().unwrap();
+            writer.add_document(doc(0))
+        });
+        assert!(poll_once(&mut blocking).is_pending());
+        wait_started.recv().unwrap();
+
+        // the second call fills the queue, the third one has to wait
+        let mut queued = async_writer.add_document(doc(1));
+        assert!(poll_once(&mut queued).is_pending());
+        let mut waiting = async_writer.add_document(doc(2));
+        assert!(poll_once(&mut waiting).is_pending());
+        assert!(waiting.job.is_some());
+
+        release.send(()).unwrap();
+        block_on(blocking).unwrap();
+        block_on(queued).unwrap();
+        block_on(waiting).unwrap();
+        let reader = async_writer.writer().get_reader(true, false).unwrap();
+        assert_eq!(reader.num_docs(), 3);
+    }
+
+    #[test]
+    fn test_drop() {
+        let dir = Arc::new(RAMDirectory::default());
+        let config = Arc::new(IndexWriterConfig::default());
+        let writer = IndexWriter::new(Arc::clone(&dir), config).unwrap();
+        let last_commit = get_last_commit_generation(&dir.list_all().unwrap()).unwrap();
+        let async_writer = AsyncIndexWriter::new(writer.clone(), 1, 1).unwrap();
+
+        block_on(async_writer.add_document(doc(0))).unwrap();
+        let not_queued = async_writer.add_document(doc(1));
+        mem::drop(async_writer);
+
+        // the futures which weren't queued fail once the wrapper is dropped
+        match block_on(not_queued) {
+            Err(Error(ErrorKind::AlreadyClosed(_), _)) => {}
+            r => panic!("expected AlreadyClosed, got {:?}", r),
+        }
+        // the added document is still buffered, nothing was committed
+        let gen = get_last_commit_generation(&dir.list_all().unwrap()).unwrap();
+        assert_eq!(gen, last_commit);
+        assert!(!writer.is_closed());
+        assert_eq!(writer.get_reader(true, false).unwrap().num_docs(), 1);
+        writer.commit().unwrap();
+        let gen = get_last_commit_generation(&dir.list_all().unwrap()).unwrap();
+        assert!(gen > last_commit);
+    }
+
+    #[test]
+    fn test_error_propagation() {
+        let config = Arc::new(IndexWriterConfig::default());
+        let writer = IndexWriter::new(Arc::new(RAMDirectory::default()), config).unwrap();
+        let async_writer = AsyncIndexWriter::new(writer, 1, 1).unwrap();
+
+        let failing = async_writer.submit(|_| bail!(IllegalArgument("bad doc".into())));
+        match block_on(failing) {
+            Err(Error(ErrorKind::IllegalArgument(ref msg), _)) => assert_eq!(msg, "bad doc"),
+            r => panic!("expected IllegalArgument, got {:?}", r),
+        }
+
+        // the errors of the writer reach the futures
+        async_writer.writer().close().unwrap();
+        match block_on(async_writer.add_document(doc(0))) {
+            Err(Error(ErrorKind::AlreadyClosed(_), _)) => {}
+            r => panic!("expected AlreadyClosed, got {:?}", r),
+        }
+    }
+}
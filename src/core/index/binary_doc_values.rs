@@ -25,6 +25,23 @@ use std::sync::Arc;
 
 pub trait BinaryDocValues: Send + Sync {
     fn get(&self, doc_id: DocId) -> Result<Vec<u8>>;
+
+    /// Calls `f` with each doc below `max_doc` and its value, by increasing
+    /// doc id. The docs without value have an empty value.
+    ///
+    /// This calls `get` for each doc by default, the values stored one after
+    /// the other are streamed instead, which is much faster to scan them all.
+    fn for_each(
+        &self,
+        max_doc: DocId,
+        f: &mut dyn FnMut(DocId, &[u8]) -> Result<()>,
+    ) -> Result<()> {
+        for doc in 0..max_doc {
+            let value = self.get(doc)?;
+            f(doc, &value)?;
+        }
+        Ok(())
+    }
 }
 
 pub type BinaryDocValuesRef = Arc<dyn BinaryDocValues>;
@@ -35,6 +52,17 @@ impl BinaryDocValues for EmptyBinaryDocValues {
     fn get(&self, _doc_id: DocId) -> Result<Vec<u8>> {
         Ok(Vec::with_capacity(0))
     }
+
+    fn for_each(
+        &self,
+        max_doc: DocId,
+        f: &mut dyn FnMut(DocId, &[u8]) -> Result<()>,
+    ) -> Result<()> {
+        for doc in 0..max_doc {
+            f(doc, &[])?;
+        }
+        Ok(())
+    }
 }
 
 pub trait LongBinaryDocValues: BinaryDocValues {
@@ -67,6 +95,22 @@ impl BinaryDocValues for FixedBinaryDocValues {
     fn get(&self, doc_id: DocId) -> Result<Vec<u8>> {
         FixedBinaryDocValues::get64(self, i64::from(doc_id))
     }
+
+    fn for_each(
+        &self,
+        max_doc: DocId,
+        f: &mut dyn FnMut(DocId, &[u8]) -> Result<()>,
+    ) -> Result<()> {
+        let length = self.buffer_len;
+        let mut data = IndexInput::clone(self.data.as_ref())?;
+        data.seek(0)?;
+        let mut buffer = vec![0u8; length];
+        for doc in 0..max_doc {
+            data.read_bytes(&mut buffer, 0, length)?;
+            f(doc, &buffer)?;
+        }
+        Ok(())
+    }
 }
 
 pub struct VariableBinaryDocValues<T: LongValues> {
@@ -97,6 +141,28 @@ impl<T: LongValues> BinaryDocValues for VariableBinaryDocValues<T> {
     fn get(&self, doc_id: DocId) -> Result<Vec<u8>> {
         VariableBinaryDocValues::get64(self, i64::from(doc_id))
     }
+
+    fn for_each(
+        &self,
+        max_doc: DocId,
+        f: &mut dyn FnMut(DocId, &[u8]) -> Result<()>,
+    ) -> Result<()> {
+        let mut data = IndexInput::clone(self.data.as_ref())?;
+        // the values are stored one after the other, by doc
+        let mut start_address = self.addresses.get64(0)?;
+        data.seek(start_address)?;
+        let mut buffer = Vec::new();
+        for doc in 0..max_doc {
+            let end_address = self.addresses.get64(i64::from(doc) + 1)?;
+            let length = (end_address - start_address) as usize;
+            buffer.clear();
+            buffer.resize(length, 0u8);
+            data.read_bytes(&mut buffer, 0, length)?;
+            f(doc, &buffer)?;
+            start_address = end_address;
+        }
+        Ok(())
+    }
 }
 
 pub struct CompressedBinaryDocValues {
@@ -172,9 +238,92 @@ impl BinaryDocValues for CompressedBinaryDocValues {
     fn get(&self, doc_id: DocId) -> Result<Vec<u8>> {
         CompressedBinaryDocValues::get64(self, i64::from(doc_id))
     }
+
+    fn for_each(
+        &self,
+        max_doc: DocId,
+        f: &mut dyn FnMut(DocId, &[u8]) -> Result<()>,
+    ) -> Result<()> {
+        // the terms are read in ord order, which is the doc order
+        let mut term_iterator = self.get_term_iterator()?;
+        for doc in 0..max_doc {
+            match term_iterator.next()? {
+                Some(term) => f(doc, &term)?,
+                None => f(doc, &[])?,
+            }
+        }
+        Ok(())
+    }
 }
 
 pub enum BoxedBinaryDocValuesEnum {
     General(Box<dyn LongBinaryDocValues>),
     Compressed(CompressedBinaryDocValues),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::doc::BinaryDocValuesField;
+    use core::index::{Fieldable, IndexReader, IndexWriter, IndexWriterConfig};
+    use core::store::RAMDirectory;
+
+    #[test]
+    fn test_for_each() {
+        let dir = Arc::new(RAMDirectory::default());
+        let writer = IndexWriter::new(dir, Arc::new(IndexWriterConfig::default())).unwrap();
+        for i in 0..100 {
+            let mut doc: Vec<Box<dyn Fieldable>> = vec![Box::new(BinaryDocValuesField::new(
+                "fixed",
+                format!("{:04}", i).as_bytes(),
+            ))];
+            // every third doc has no variable value
+            if i % 3 != 0 {
+                let value = "x".repeat(i % 7);
+                doc.push(Box::new(BinaryDocValuesField::new(
+                    "variable",
+                    value.as_bytes(),
+                )));
+            }
+            writer.add_document(doc).unwrap();
+        }
+        let reader = writer.get_reader(true, false).unwrap();
+        let leaves = reader.leaves();
+        let leaf = &leaves[0];
+        for field in &["fixed", "variable"] {
+            let values = leaf.reader.get_binary_doc_values(field).unwrap();
+            let mut scanned = vec![];
+            values
+                .for_each(leaf.reader.max_doc(), &mut |doc: DocId, value: &[u8]| {
+                    scanned.push((doc, value.to_vec()));
+                    Ok(())
+                })
+                .unwrap();
+            assert_eq!(scanned.len(), 100);
+            for (doc, value) in scanned {
+                assert_eq!(value, values.get(doc).unwrap());
+            }
+        }
+        let variable = leaf.reader.get_binary_doc_values("variable").unwrap();
+        let mut lengths = vec![];
+        variable
+            .for_each(6, &mut |_: DocId, value: &[u8]| {
+                lengths.push(value.len());
+                Ok(())
+            })
+            .unwrap();
+        // the missing values are empty, not the previous ones
+        assert_eq!(lengths, vec![0, 1, 2, 0, 4, 5]);
+
+        let mut count = 0;
+        EmptyBinaryDocValues
+            .for_each(3, &mut |_: DocId, value: &[u8]| {
+                assert!(value.is_empty());
+                count += 1;
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(count, 3);
+        writer.close().unwrap();
+    }
+}
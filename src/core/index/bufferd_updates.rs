@@ -17,6 +17,7 @@ use core::index::merge_policy::MergePolicy;
 use core::index::prefix_code_terms::{FieldTermIter, FieldTermIterator};
 use core::index::prefix_code_terms::{PrefixCodedTerms, PrefixCodedTermsBuilder};
 use core::index::term::SeekStatus;
+use core::index::{NumericDocValuesUpdate, SizeInBytesCalc};
 use core::index::{Fields, TermIterator, Terms};
 use core::index::{IndexReader, LeafReader};
 use core::index::{SegmentCommitInfo, SegmentInfos, Term};
@@ -25,7 +26,7 @@ use core::search::query_cache::{NoCacheQueryCache, QueryCache};
 use core::search::searcher::{DefaultIndexSearcher, IndexSearcher, SearchPlanBuilder};
 use core::search::{DocIterator, Query, NO_MORE_DOCS};
 use core::store::{Directory, IOContext};
-use core::util::DocId;
+use core::util::{BitsRef, DocId, VariantValue};
 
 use std::cmp::{min, Ordering as CmpOrdering};
 use std::collections::{BinaryHeap, HashMap};
@@ -36,7 +37,7 @@ use std::sync::{Arc, Mutex, MutexGuard};
 use std::time::Instant;
 
 use core::index::merge_scheduler::MergeScheduler;
-use error::Result;
+use error::{ErrorKind::IllegalArgument, Result};
 
 // Rough logic: del docIDs are List<i32>.  Say list allocates ~2X size (2 * i32),
 pub const BYTES_PER_DEL_DOCID: usize = 2 * mem::size_of::<DocId>();
@@ -53,6 +54,13 @@ pub const BYTES_PER_DEL_TERM: usize = 6 * mem::size_of::<usize>();
 /// entry costs.
 pub const BYTES_PER_DEL_QUERY_IN_HASH: usize = 4 * mem::size_of::<usize>() + 28;
 
+/// Rough logic: the doc id set is shared, we count the pair in the Vec and the
+/// Arc, the segment name being counted apart.
+pub const BYTES_PER_DEL_DOC_SET: usize = 4 * mem::size_of::<usize>();
+
+/// The value a soft deleted doc is given for the soft deletes field.
+pub const SOFT_DELETED: i64 = 1;
+
 /// Holds buffered deletes and updates, by docID, term or query for a
 /// single segment. This is used to hold buffered pending
 /// deletes and updates against the to-be-flushed segment.  Once the
@@ -64,20 +72,23 @@ pub const BYTES_PER_DEL_QUERY_IN_HASH: usize = 4 * mem::size_of::<usize>() + 28;
 /// DocumentsWriterDeleteQueue
 pub struct BufferedUpdates<C: Codec> {
     pub num_term_deletes: AtomicUsize,
-    // num_numeric_updates: AtomicIsize,
+    // also used as the insertion order of the numeric updates
+    num_numeric_updates: usize,
     // num_binary_updates: AtomicIsize,
     pub deleted_terms: HashMap<Term, DocId>,
     // the key is string represent of query, query is share by multi-thread
     pub deleted_queries: HashMap<String, (Arc<dyn Query<C>>, DocId)>,
     pub deleted_doc_ids: Vec<i32>,
-    // Map<dvField,Map<updateTerm,NumericUpdate>>
-    // For each field we keep an ordered list of NumericUpdates, key'd by the
-    // update Term. LinkedHashMap guarantees we will later traverse the map in
+    // the precomputed doc id sets to delete, by segment name
+    pub deleted_doc_sets: Vec<(String, BitsRef)>,
+    // Map<dvField,Map<updateTerm,(ord,NumericUpdate)>>
+    // For each field we keep the NumericUpdates key'd by the update Term, along
+    // with their insertion order, so that we will later traverse them in
     // insertion order (so that if two terms affect the same document, the last
     // one that came in wins), and helps us detect faster if the same Term is
     // used to update the same field multiple times (so we later traverse it
     // only once).
-    // numeric_updates: HashMap<String, HashMap<Term, NumericDocValuesUpdate>>,
+    pub numeric_updates: HashMap<String, HashMap<Term, (usize, NumericDocValuesUpdate)>>,
     //
     // Map<dvField,Map<updateTerm,BinaryUpdate>>
     // For each field we keep an ordered list of BinaryUpdates, key'd by the
@@ -96,9 +107,12 @@ impl<C: Codec> BufferedUpdates<C> {
     pub fn new(name: String) -> Self {
         BufferedUpdates {
             num_term_deletes: AtomicUsize::new(0),
+            num_numeric_updates: 0,
             deleted_terms: HashMap::new(),
             deleted_queries: HashMap::new(),
             deleted_doc_ids: vec![],
+            deleted_doc_sets: vec![],
+            numeric_updates: HashMap::new(),
             bytes_used: AtomicUsize::new(0),
             segment_name: name,
         }
@@ -114,6 +128,13 @@ impl<C: Codec> BufferedUpdates<C> {
             .fetch_add(BYTES_PER_DEL_TERM, Ordering::AcqRel);
     }
 
+    /// Deletes the docs of `docs` in the segment named `segment`.
+    pub fn add_doc_set(&mut self, segment: String, docs: BitsRef) {
+        let cost = BYTES_PER_DEL_DOC_SET + segment.capacity();
+        self.deleted_doc_sets.push((segment, docs));
+        self.bytes_used.fetch_add(cost, Ordering::AcqRel);
+    }
+
     pub fn add_query(&mut self, query: Arc<dyn Query<C>>, doc_id_upto: DocId) {
         let query_str = format!("{}", &query);
         let query_str_cost = query_str.capacity();
@@ -152,10 +173,51 @@ impl<C: Codec> BufferedUpdates<C> {
         }
     }
 
+    pub fn add_numeric_update(&mut self, mut update: NumericDocValuesUpdate, doc_id_upto: DocId) {
+        let ord = self.num_numeric_updates;
+        self.num_numeric_updates += 1;
+        let field_updates = self
+            .numeric_updates
+            .entry(update.update.field.clone())
+            .or_insert_with(HashMap::new);
+        if let Some(&(_, ref current)) = field_updates.get(&update.update.term) {
+            if doc_id_upto < current.update.doc_id_up_to {
+                // Only record the new number if it's greater than or equal to the
+                // current one. This is important because if multiple threads are
+                // replacing the same doc at nearly the same time, it's possible that
+                // one thread that got a higher docID is scheduled before the other
+                // threads.
+                return;
+            }
+        }
+
+        update.update.doc_id_up_to = doc_id_upto;
+        let cost = update.size_in_bytes();
+        // the update gets a new ord even if the term was already buffered, so
+        // that it is applied after the updates which came in before it.
+        if field_updates
+            .insert(update.update.term.clone(), (ord, update))
+            .is_none()
+        {
+            self.bytes_used.fetch_add(cost, Ordering::AcqRel);
+        }
+    }
+
+    /// Soft deletes the docs containing `term`, by updating the soft deletes
+    /// `field` of the docs to `SOFT_DELETED`.
+    pub fn add_soft_delete(&mut self, term: Term, field: &str, doc_id_upto: DocId) {
+        let value = VariantValue::Long(SOFT_DELETED);
+        let update = NumericDocValuesUpdate::new(term, field.to_string(), value);
+        self.add_numeric_update(update, doc_id_upto);
+    }
+
     pub fn clear(&mut self) {
         self.deleted_terms.clear();
         self.deleted_queries.clear();
         self.deleted_doc_ids.clear();
+        self.deleted_doc_sets.clear();
+        self.numeric_updates.clear();
+        self.num_numeric_updates = 0;
         self.num_term_deletes.store(0, Ordering::Release);
         self.bytes_used.store(0, Ordering::Release);
     }
@@ -164,6 +226,8 @@ impl<C: Codec> BufferedUpdates<C> {
         !self.deleted_terms.is_empty()
             || !self.deleted_doc_ids.is_empty()
             || !self.deleted_queries.is_empty()
+            || !self.deleted_doc_sets.is_empty()
+            || !self.numeric_updates.is_empty()
     }
 }
 
@@ -178,13 +242,20 @@ pub struct FrozenBufferedUpdates<C: Codec> {
     terms: Arc<PrefixCodedTerms>,
     // Parallel array of deleted query, and the doc_id_upto for each
     query_and_limits: Vec<(Arc<dyn Query<C>>, DocId)>,
+    // numeric DV update terms, in the order they were buffered
+    numeric_dv_updates: Vec<NumericDocValuesUpdate>,
+    // the doc id sets to delete, by segment name
+    doc_sets: Vec<(String, BitsRef)>,
     pub bytes_used: usize,
     pub num_term_deletes: usize,
     pub gen: u64,
     // assigned by BufferedUpdatesStream once pushed
     // set to true iff this frozen packet represents a segment private delete.
-    // in that case is should only have queries
+    // in that case is should only have queries and doc values updates
     is_segment_private: bool,
+    // for the segment private packet of a segment sorted on flush, the doc id
+    // each doc had when flushed, which the doc id limits apply to
+    sort_map: Option<Arc<Vec<DocId>>>,
 }
 
 impl<C: Codec> fmt::Display for FrozenBufferedUpdates<C> {
@@ -199,6 +270,12 @@ impl<C: Codec> fmt::Display for FrozenBufferedUpdates<C> {
         if !self.query_and_limits.is_empty() {
             write!(f, " {} deleted queries", self.query_and_limits.len())?;
         }
+        if !self.numeric_dv_updates.is_empty() {
+            write!(f, " {} numeric updates", self.numeric_dv_updates.len())?;
+        }
+        if !self.doc_sets.is_empty() {
+            write!(f, " {} deleted doc sets", self.doc_sets.len())?;
+        }
         if self.bytes_used > 0 {
             write!(f, " bytes_used={}", self.bytes_used)?;
         }
@@ -228,22 +305,57 @@ impl<C: Codec> FrozenBufferedUpdates<C> {
             .map(|(_key, value)| value)
             .collect();
 
+        let mut numeric_updates: Vec<(usize, NumericDocValuesUpdate)> = deletes
+            .numeric_updates
+            .drain()
+            .flat_map(|(_field, updates)| updates.into_iter().map(|(_term, value)| value))
+            .collect();
+        numeric_updates.sort_by_key(|&(ord, _)| ord);
+        let numeric_dv_updates: Vec<_> = numeric_updates.into_iter().map(|(_, u)| u).collect();
+
+        // the doc id sets target the existing segments, not the flushed one
+        let doc_sets = if is_segment_private {
+            deletes.deleted_doc_sets.clear();
+            vec![]
+        } else {
+            mem::replace(&mut deletes.deleted_doc_sets, vec![])
+        };
+
         // TODO if a Term affects multiple fields, we could keep the updates key'd by Term
         // so that it maps to all fields it affects, sorted by their docUpto, and traverse
         // that Term only once, applying the update to all fields that still need to be
         // updated.
-        let bytes_used = terms.ram_bytes_used() + query_and_limits.len() * BYTES_PER_DEL_QUERY;
+        let bytes_used = terms.ram_bytes_used()
+            + query_and_limits.len() * BYTES_PER_DEL_QUERY
+            + numeric_dv_updates
+                .iter()
+                .map(|u| u.size_in_bytes())
+                .sum::<usize>()
+            + doc_sets
+                .iter()
+                .map(|(segment, _)| BYTES_PER_DEL_DOC_SET + segment.capacity())
+                .sum::<usize>();
         FrozenBufferedUpdates {
             terms: Arc::new(terms),
             query_and_limits,
+            numeric_dv_updates,
+            doc_sets,
             bytes_used,
             num_term_deletes: deletes.num_term_deletes.load(Ordering::Acquire),
             gen: u64::max_value(),
             // used as a sentinel of invalid
             is_segment_private,
+            sort_map: None,
         }
     }
 
+    /// Sets the flush time doc id of each doc of the segment this private
+    /// packet applies to, for a segment sorted on flush.
+    pub fn set_sort_map(&mut self, sort_map: Arc<Vec<DocId>>) {
+        debug_assert!(self.is_segment_private);
+        self.sort_map = Some(sort_map);
+    }
+
     pub fn set_del_gen(&mut self, gen: u64) {
         assert_eq!(self.gen, u64::max_value());
         self.gen = gen;
@@ -251,7 +363,10 @@ impl<C: Codec> FrozenBufferedUpdates<C> {
     }
 
     pub fn any(&self) -> bool {
-        self.terms.size > 0 || self.query_and_limits.len() > 0
+        self.terms.size > 0
+            || self.query_and_limits.len() > 0
+            || !self.numeric_dv_updates.is_empty()
+            || !self.doc_sets.is_empty()
     }
 }
 
@@ -392,6 +507,7 @@ impl<C: Codec> BufferedUpdatesStream<C> {
         // We only init these on demand, when we find our first deletes that need to be applied:
         let start = Instant::now();
         let mut total_del_count = 0;
+        let mut total_update_count = 0;
         let mut total_term_visited_count = 0;
 
         let gen = self.next_gen.fetch_add(1, Ordering::AcqRel) as i64;
@@ -456,8 +572,10 @@ impl<C: Codec> BufferedUpdatesStream<C> {
                     let mut del_count = 0;
 
                     // first apply segment-private deletes
+                    let sort_map = updates[del_idx - 1].sort_map.as_ref().map(|m| m.as_slice());
                     del_count += Self::apply_query_deletes(
                         updates[del_idx - 1].query_and_limits.iter(),
+                        sort_map,
                         seg_state,
                     )?;
 
@@ -467,12 +585,32 @@ impl<C: Codec> BufferedUpdatesStream<C> {
                     if coalesce_updates.has_queries() {
                         del_count += Self::apply_query_deletes(
                             coalesce_updates.query_and_limits(),
+                            None,
                             seg_state,
                         )?;
                     }
+                    if coalesce_updates.has_doc_sets() {
+                        del_count +=
+                            Self::apply_doc_set_deletes(&coalesce_updates.doc_sets, seg_state)?;
+                    }
 
                     total_del_count += del_count;
 
+                    let mut update_count = Self::apply_numeric_updates(
+                        updates[del_idx - 1].numeric_dv_updates.iter(),
+                        sort_map,
+                        seg_state,
+                    )?;
+                    if coalesce_updates.has_numeric_dv_updates() {
+                        update_count += Self::apply_numeric_updates(
+                            coalesce_updates.numeric_dv_updates(),
+                            None,
+                            seg_state,
+                        )?;
+                    }
+
+                    total_update_count += update_count;
+
                     // Since we are on a segment private del packet we must not update the
                     // coalescedUpdates here! We can simply advance to the next packet and seginfo.
                     del_idx -= 1;
@@ -491,11 +629,26 @@ impl<C: Codec> BufferedUpdatesStream<C> {
                         if coalesce_updates.has_queries() {
                             del_count += Self::apply_query_deletes(
                                 coalesce_updates.query_and_limits(),
+                                None,
+                                seg_state,
+                            )?;
+                        }
+                        if coalesce_updates.has_doc_sets() {
+                            del_count += Self::apply_doc_set_deletes(
+                                &coalesce_updates.doc_sets,
                                 seg_state,
                             )?;
                         }
 
                         total_del_count += del_count;
+
+                        if coalesce_updates.has_numeric_dv_updates() {
+                            total_update_count += Self::apply_numeric_updates(
+                                coalesce_updates.numeric_dv_updates(),
+                                None,
+                                seg_state,
+                            )?;
+                        }
                     }
 
                     infos_idx -= 1;
@@ -514,11 +667,12 @@ impl<C: Codec> BufferedUpdatesStream<C> {
 
         debug!(
             "BD - apply_deletes took {:?} for {} segments, {} newly deleted docs (query deletes), \
-             {} visited terms",
+             {} visited terms, {} numeric updates",
             Instant::now() - start,
             infos.len(),
             total_del_count,
-            total_term_visited_count
+            total_term_visited_count,
+            total_update_count
         );
 
         // debug_assert!(self.check_delete_stats(&updates));
@@ -526,9 +680,53 @@ impl<C: Codec> BufferedUpdatesStream<C> {
         Ok(None)
     }
 
-    /// Delete by query
+    /// Delete by precomputed doc id sets, the sets of the other segments being
+    /// skipped
+    fn apply_doc_set_deletes<D, MS, MP>(
+        doc_sets: &[(String, BitsRef)],
+        seg_state: &mut SegmentState<D, C, MS, MP>,
+    ) -> Result<u64>
+    where
+        D: Directory + Send + Sync + 'static,
+        MS: MergeScheduler,
+        MP: MergePolicy,
+    {
+        let mut del_count: u64 = 0;
+        let info = &seg_state.rld.info;
+        let mut rld = seg_state.rld.inner.lock()?;
+        for (segment, docs) in doc_sets {
+            if segment != &info.info.name {
+                continue;
+            }
+            if docs.len() > info.info.max_doc() as usize {
+                bail!(IllegalArgument(format!(
+                    "doc set of {} docs is larger than segment '{}' of {} docs",
+                    docs.len(),
+                    segment,
+                    info.info.max_doc()
+                )));
+            }
+            for doc in 0..docs.len() {
+                if !docs.get(doc)? {
+                    continue;
+                }
+                if !seg_state.any {
+                    rld.init_writable_live_docs(info)?;
+                    seg_state.any = true;
+                }
+                if rld.delete(doc as DocId)? {
+                    del_count += 1;
+                }
+            }
+        }
+        Ok(del_count)
+    }
+
+    /// Delete by query, the limits apply to the doc ids of `sort_map` if
+    /// the segment was sorted on flush.
     fn apply_query_deletes<'a, D, MS, MP>(
         queries: impl Iterator<Item = &'a (Arc<dyn Query<C>>, DocId)>,
+        sort_map: Option<&[DocId]>,
         seg_state: &mut SegmentState<D, C, MS, MP>,
     ) -> Result<u64>
     where
@@ -548,7 +746,15 @@ impl<C: Codec> BufferedUpdatesStream<C> {
                 let live_docs = reader.reader.live_docs();
                 loop {
                     let doc = scorer.next()?;
-                    if doc >= *limit {
+                    if doc == NO_MORE_DOCS {
+                        break;
+                    }
+                    if let Some(sort_map) = sort_map {
+                        // the docs below the limit aren't the first ones anymore
+                        if sort_map[doc as usize] >= *limit {
+                            continue;
+                        }
+                    } else if doc >= *limit {
                         break;
                     }
                     if !live_docs.get(doc as usize)? {
@@ -568,6 +774,67 @@ impl<C: Codec> BufferedUpdatesStream<C> {
         Ok(del_count)
     }
 
+    /// Resolves the numeric DV updates to the doc ids of the segment, the
+    /// later updates overriding the earlier ones for the same doc. The limits
+    /// apply to the doc ids of `sort_map` if the segment was sorted on flush.
+    fn apply_numeric_updates<'a, D, MS, MP>(
+        updates: impl Iterator<Item = &'a NumericDocValuesUpdate>,
+        sort_map: Option<&[DocId]>,
+        seg_state: &mut SegmentState<D, C, MS, MP>,
+    ) -> Result<u64>
+    where
+        D: Directory + Send + Sync + 'static,
+        MS: MergeScheduler,
+        MP: MergePolicy,
+    {
+        let mut update_count: u64 = 0;
+        let reader = Arc::clone(seg_state.rld.inner.lock()?.reader());
+        let fields = reader.fields()?;
+        let mut current_field = String::with_capacity(0);
+        let mut terms_iterator = None;
+        for update in updates {
+            let update = &update.update;
+            if terms_iterator.is_none() || update.term.field != current_field {
+                current_field = update.term.field.clone();
+                terms_iterator = match fields.terms(&current_field)? {
+                    Some(terms) => Some(terms.iterator()?),
+                    None => None,
+                };
+            }
+            let iter = match terms_iterator.as_mut() {
+                Some(iter) => iter,
+                None => continue,
+            };
+            if !iter.seek_exact(&update.term.bytes)? {
+                continue;
+            }
+
+            let value = update.value.get_long().unwrap();
+            let field_updates = seg_state
+                .numeric_updates
+                .entry(update.field.clone())
+                .or_insert_with(HashMap::new);
+            // we don't need term frequencies for this
+            let mut postings = iter.postings_with_flags(PostingIteratorFlags::NONE)?;
+            loop {
+                let doc = postings.next()?;
+                if doc == NO_MORE_DOCS {
+                    break;
+                }
+                if let Some(sort_map) = sort_map {
+                    if sort_map[doc as usize] >= update.doc_id_up_to {
+                        continue;
+                    }
+                } else if doc >= update.doc_id_up_to {
+                    break;
+                }
+                field_updates.insert(doc, value);
+                update_count += 1;
+            }
+        }
+        Ok(update_count)
+    }
+
     /// Merge sorts the deleted terms and all segments to resolve terms to doc_ids for deletion.
     fn apply_term_deletes<D, MS, MP>(
         &mut self,
@@ -776,9 +1043,17 @@ impl<C: Codec> BufferedUpdatesStream<C> {
     {
         let mut first_err = Ok(ApplyDeletesResult::new(false, 0, vec![]));
         let mut total_del_count = 0;
+        let mut any_updates = false;
         let mut all_deleted = vec![];
 
         for seg_state in seg_states {
+            if success && !seg_state.numeric_updates.is_empty() {
+                let updates = mem::replace(&mut seg_state.numeric_updates, HashMap::new());
+                match seg_state.rld.write_field_updates(updates) {
+                    Ok(()) => any_updates = true,
+                    Err(e) => first_err = Err(e),
+                }
+            }
             if success {
                 total_del_count +=
                     seg_state.rld.pending_delete_count() - seg_state.start_del_count as u32;
@@ -806,7 +1081,7 @@ impl<C: Codec> BufferedUpdatesStream<C> {
         );
 
         Ok(ApplyDeletesResult::new(
-            total_del_count > 0,
+            total_del_count > 0 || any_updates,
             gen,
             all_deleted,
         ))
@@ -917,6 +1192,8 @@ struct SegmentState<
     postings: Option<CodecPostingIterator<C>>,
     term: Option<Vec<u8>>,
     any: bool,
+    // numeric DV updates resolved to the segment's doc ids, by field
+    numeric_updates: HashMap<String, HashMap<DocId, i64>>,
 }
 
 impl<D, C, MS, MP> SegmentState<D, C, MS, MP>
@@ -940,6 +1217,7 @@ where
             postings: None,
             term: None,
             any: false,
+            numeric_updates: HashMap::new(),
         })
     }
 
@@ -1034,6 +1312,9 @@ where
 struct CoalescedUpdates<C: Codec> {
     queries: HashMap<String, (Arc<dyn Query<C>>, DocId)>,
     terms: Vec<Arc<PrefixCodedTerms>>,
+    // numeric DV updates of each coalesced packet, newest packet first
+    numeric_dv_updates: Vec<Vec<NumericDocValuesUpdate>>,
+    doc_sets: Vec<(String, BitsRef)>,
     total_term_count: usize,
 }
 
@@ -1042,6 +1323,8 @@ impl<C: Codec> Default for CoalescedUpdates<C> {
         CoalescedUpdates {
             queries: HashMap::new(),
             terms: vec![],
+            numeric_dv_updates: vec![],
+            doc_sets: vec![],
             total_term_count: 0,
         }
     }
@@ -1056,6 +1339,23 @@ impl<C: Codec> CoalescedUpdates<C> {
             self.queries
                 .insert(query.to_string(), (Arc::clone(query), i32::max_value()));
         }
+
+        if !up.numeric_dv_updates.is_empty() {
+            let numeric_updates = up
+                .numeric_dv_updates
+                .iter()
+                .map(|u| {
+                    let mut update = u.clone();
+                    update.update.doc_id_up_to = i32::max_value();
+                    update
+                })
+                .collect();
+            self.numeric_dv_updates.push(numeric_updates);
+        }
+
+        for (segment, docs) in &up.doc_sets {
+            self.doc_sets.push((segment.clone(), Arc::clone(docs)));
+        }
     }
 
     fn term_iterator(&self) -> Result<FieldTermIterator> {
@@ -1071,7 +1371,24 @@ impl<C: Codec> CoalescedUpdates<C> {
         !self.queries.is_empty()
     }
 
+    /// Returns the coalesced numeric DV updates, oldest packet first so
+    /// that the newer updates win.
+    pub fn numeric_dv_updates(&self) -> impl Iterator<Item = &NumericDocValuesUpdate> {
+        self.numeric_dv_updates.iter().rev().flat_map(|u| u.iter())
+    }
+
+    pub fn has_numeric_dv_updates(&self) -> bool {
+        !self.numeric_dv_updates.is_empty()
+    }
+
+    pub fn has_doc_sets(&self) -> bool {
+        !self.doc_sets.is_empty()
+    }
+
     pub fn any(&self) -> bool {
-        self.queries.len() > 0 || self.terms.len() > 0
+        self.queries.len() > 0
+            || self.terms.len() > 0
+            || !self.numeric_dv_updates.is_empty()
+            || !self.doc_sets.is_empty()
     }
 }
@@ -13,7 +13,7 @@
 
 use core::index::index_commit::IndexCommit;
 use core::store::Directory;
-use error::Result;
+use error::{ErrorKind::IllegalArgument, Result};
 
 /// Expert: policy for deletion of stale `IndexCommit index commits`.
 ///
@@ -89,7 +89,10 @@ pub trait IndexDeletionPolicy {
     fn on_commit<D: Directory>(&self, commits: Vec<&mut IndexCommit<D>>) -> Result<()>;
 }
 
-#[derive(Default)]
+/// This `IndexDeletionPolicy` implementation that keeps only the most recent
+/// commit and immediately removes all prior commits after a new commit is done.
+/// This is the default deletion policy.
+#[derive(Default, Copy, Clone, Debug)]
 pub struct KeepOnlyLastCommitDeletionPolicy;
 
 impl IndexDeletionPolicy for KeepOnlyLastCommitDeletionPolicy {
@@ -105,3 +108,187 @@ impl IndexDeletionPolicy for KeepOnlyLastCommitDeletionPolicy {
         Ok(())
     }
 }
+
+/// An `IndexDeletionPolicy` which keeps all index commits around, never
+/// deleting them. Old commits are expected to be removed explicitly, e.g. by
+/// `IndexWriter::delete_commit` from an external retention manager.
+#[derive(Default, Copy, Clone, Debug)]
+pub struct KeepAllCommitsDeletionPolicy;
+
+impl IndexDeletionPolicy for KeepAllCommitsDeletionPolicy {
+    fn on_init<D: Directory>(&self, _commits: Vec<&mut IndexCommit<D>>) -> Result<()> {
+        Ok(())
+    }
+
+    fn on_commit<D: Directory>(&self, _commits: Vec<&mut IndexCommit<D>>) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// An `IndexDeletionPolicy` which keeps the `num_to_keep` most recent commits
+/// and removes all older ones.
+#[derive(Copy, Clone, Debug)]
+pub struct KeepLastNCommitsDeletionPolicy {
+    num_to_keep: usize,
+}
+
+impl KeepLastNCommitsDeletionPolicy {
+    pub fn new(num_to_keep: usize) -> Result<KeepLastNCommitsDeletionPolicy> {
+        if num_to_keep == 0 {
+            bail!(IllegalArgument("num_to_keep must be > 0".into()));
+        }
+        Ok(KeepLastNCommitsDeletionPolicy { num_to_keep })
+    }
+}
+
+impl IndexDeletionPolicy for KeepLastNCommitsDeletionPolicy {
+    fn on_init<D: Directory>(&self, commits: Vec<&mut IndexCommit<D>>) -> Result<()> {
+        self.on_commit(commits)
+    }
+
+    fn on_commit<D: Directory>(&self, commits: Vec<&mut IndexCommit<D>>) -> Result<()> {
+        if commits.len() > self.num_to_keep {
+            let num_to_delete = commits.len() - self.num_to_keep;
+            for commit in commits.into_iter().take(num_to_delete) {
+                commit.delete()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The deletion policies an `IndexWriter` can be configured with.
+#[derive(Copy, Clone, Debug)]
+pub enum IndexDeletionPolicyEnum {
+    KeepOnlyLast(KeepOnlyLastCommitDeletionPolicy),
+    KeepAll(KeepAllCommitsDeletionPolicy),
+    KeepLastN(KeepLastNCommitsDeletionPolicy),
+}
+
+impl Default for IndexDeletionPolicyEnum {
+    fn default() -> Self {
+        IndexDeletionPolicyEnum::KeepOnlyLast(KeepOnlyLastCommitDeletionPolicy)
+    }
+}
+
+impl IndexDeletionPolicy for IndexDeletionPolicyEnum {
+    fn on_init<D: Directory>(&self, commits: Vec<&mut IndexCommit<D>>) -> Result<()> {
+        match self {
+            IndexDeletionPolicyEnum::KeepOnlyLast(p) => p.on_init(commits),
+            IndexDeletionPolicyEnum::KeepAll(p) => p.on_init(commits),
+            IndexDeletionPolicyEnum::KeepLastN(p) => p.on_init(commits),
+        }
+    }
+
+    fn on_commit<D: Directory>(&self, commits: Vec<&mut IndexCommit<D>>) -> Result<()> {
+        match self {
+            IndexDeletionPolicyEnum::KeepOnlyLast(p) => p.on_commit(commits),
+            IndexDeletionPolicyEnum::KeepAll(p) => p.on_commit(commits),
+            IndexDeletionPolicyEnum::KeepLastN(p) => p.on_commit(commits),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::store::{FSDirectory, NativeFSLockFactory};
+
+    use std::collections::{HashMap, HashSet};
+
+    type MockDirectory = FSDirectory<NativeFSLockFactory>;
+
+    struct MockCommit {
+        generation: i64,
+        deleted: bool,
+        files: HashSet<String>,
+        user_data: HashMap<String, String>,
+    }
+
+    impl MockCommit {
+        fn new(generation: i64) -> MockCommit {
+            MockCommit {
+                generation,
+                deleted: false,
+                files: HashSet::new(),
+                user_data: HashMap::new(),
+            }
+        }
+    }
+
+    impl IndexCommit<MockDirectory> for MockCommit {
+        fn segments_file_name(&self) -> &str {
+            "segments"
+        }
+
+        fn file_names(&self) -> Result<&HashSet<String>> {
+            Ok(&self.files)
+        }
+
+        fn directory(&self) -> &MockDirectory {
+            unreachable!()
+        }
+
+        fn delete(&mut self) -> Result<()> {
+            self.deleted = true;
+            Ok(())
+        }
+
+        fn is_deleted(&self) -> bool {
+            self.deleted
+        }
+
+        fn segment_count(&self) -> usize {
+            0
+        }
+
+        fn generation(&self) -> i64 {
+            self.generation
+        }
+
+        fn user_data(&self) -> &HashMap<String, String> {
+            &self.user_data
+        }
+
+        fn doc_count(&self) -> i32 {
+            0
+        }
+    }
+
+    fn apply_on_commit(policy: &impl IndexDeletionPolicy, num_commits: i64) -> Vec<i64> {
+        let mut commits: Vec<MockCommit> = (1..=num_commits).map(MockCommit::new).collect();
+        {
+            let refs: Vec<&mut IndexCommit<MockDirectory>> = commits
+                .iter_mut()
+                .map(|c| c as &mut IndexCommit<MockDirectory>)
+                .collect();
+            policy.on_commit(refs).unwrap();
+        }
+        commits
+            .iter()
+            .filter(|c| !c.deleted)
+            .map(|c| c.generation)
+            .collect()
+    }
+
+    #[test]
+    fn test_keep_only_last() {
+        let policy = IndexDeletionPolicyEnum::default();
+        assert_eq!(apply_on_commit(&policy, 3), vec![3]);
+    }
+
+    #[test]
+    fn test_keep_all() {
+        let policy = IndexDeletionPolicyEnum::KeepAll(KeepAllCommitsDeletionPolicy);
+        assert_eq!(apply_on_commit(&policy, 3), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_keep_last_n() {
+        let policy = KeepLastNCommitsDeletionPolicy::new(2).unwrap();
+        let policy = IndexDeletionPolicyEnum::KeepLastN(policy);
+        assert_eq!(apply_on_commit(&policy, 1), vec![1]);
+        assert_eq!(apply_on_commit(&policy, 4), vec![3, 4]);
+        assert!(KeepLastNCommitsDeletionPolicy::new(0).is_err());
+    }
+}
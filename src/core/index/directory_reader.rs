@@ -11,6 +11,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use core::codec::codec_util::verify_checksums_in_parallel;
 use core::codec::{Codec, CodecTVFields};
 use core::doc::{Document, DocumentStoredFieldVisitor};
 use core::index::index_commit::IndexCommit;
@@ -20,7 +21,7 @@ use core::index::merge_policy::MergePolicy;
 use core::index::merge_scheduler::MergeScheduler;
 use core::index::INDEX_FILE_SEGMENTS;
 use core::index::{get_segment_file_name, run_with_find_segment_file, SegmentInfos};
-use core::index::{IndexReader, LeafReader, SegmentReader};
+use core::index::{IndexReader, LeafReader, SegmentCommitInfo, SegmentReader};
 use core::store::{Directory, IOContext};
 use core::util::DocId;
 
@@ -29,8 +30,12 @@ use error::{
     Result,
 };
 
+use num_cpus;
 use std::{collections::HashMap, fmt, sync::Arc};
 
+// max number of threads verifying the checksums in `open_with_checksums`
+const VERIFY_CHECKSUMS_MAX_THREADS: usize = 4;
+
 ///
 // Returns <code>true</code> if an index likely exists at
 // the specified directory.  Note that if a corrupt index
@@ -58,6 +63,44 @@ pub fn index_exist<D: Directory>(directory: &D) -> Result<bool> {
         .any(|f| f.starts_with(&prefix)))
 }
 
+// hides the soft deleted docs of the reader if a soft deletes field is given
+fn apply_soft_deletes<D: Directory + 'static, C: Codec>(
+    reader: SegmentReader<D, C>,
+    soft_deletes_field: Option<&String>,
+) -> Result<SegmentReader<D, C>> {
+    if let Some(field) = soft_deletes_field {
+        if let Some(soft_deletes_reader) = reader.with_soft_deletes(field, None)? {
+            return Ok(soft_deletes_reader);
+        }
+    }
+    Ok(reader)
+}
+
+// The state of a segment a near real-time reader was opened on. A reader of
+// the segment in the same state can be shared by the next near real-time
+// reader: the hard deletes only grow, and the soft deletes and the doc values
+// updates are written as new field infos generations.
+#[derive(Debug, PartialEq)]
+struct SegmentState {
+    name: String,
+    id: Vec<u8>,
+    del_gen: i64,
+    field_infos_gen: i64,
+    num_deleted_docs: u32,
+}
+
+impl SegmentState {
+    fn new<D: Directory, C: Codec>(info: &SegmentCommitInfo<D, C>, num_deleted_docs: u32) -> Self {
+        SegmentState {
+            name: info.info.name.clone(),
+            id: info.info.get_id().to_vec(),
+            del_gen: info.del_gen(),
+            field_infos_gen: info.field_infos_gen(),
+            num_deleted_docs,
+        }
+    }
+}
+
 pub struct StandardDirectoryReader<
     D: Directory + Send + Sync + 'static,
     C: Codec,
@@ -70,9 +113,14 @@ pub struct StandardDirectoryReader<
     pub num_docs: i32,
     starts: Vec<i32>,
     readers: Vec<Arc<SegmentReader<D, C>>>,
-    apply_all_deletes: bool,
-    write_all_deletes: bool,
+    pub(crate) apply_all_deletes: bool,
+    pub(crate) write_all_deletes: bool,
     writer: Option<IndexWriter<D, C, MS, MP>>,
+    // the docs soft deleted through this field are hidden by the readers
+    soft_deletes_field: Option<String>,
+    // the state of the segment of each reader, only kept by the near
+    // real-time readers
+    segment_states: Vec<SegmentState>,
 }
 
 impl<D, C, MS, MP> StandardDirectoryReader<D, C, MS, MP>
@@ -83,11 +131,40 @@ where
     MP: MergePolicy,
 {
     pub fn open(directory: Arc<D>) -> Result<Self> {
+        Self::do_open(directory, None, false)
+    }
+
+    /// Opens the latest commit after verifying the checksums of all the files
+    /// it references, in parallel. Fails with a `CorruptIndex` error listing
+    /// the corrupted files, if any.
+    pub fn open_with_checksums(directory: Arc<D>) -> Result<Self> {
+        Self::do_open(directory, None, true)
+    }
+
+    /// Opens the latest commit hiding the docs soft deleted through the
+    /// numeric doc values `field`, see
+    /// `IndexWriterConfig::set_soft_deletes_field`.
+    pub fn open_with_soft_deletes(directory: Arc<D>, field: &str) -> Result<Self> {
+        Self::do_open(directory, Some(field.to_string()), false)
+    }
+
+    fn do_open(
+        directory: Arc<D>,
+        soft_deletes_field: Option<String>,
+        verify_checksums: bool,
+    ) -> Result<Self> {
         let segment_file_name = get_segment_file_name(directory.as_ref())?;
         let segment_infos = SegmentInfos::read_commit(&directory, &segment_file_name)?;
+        if verify_checksums {
+            let mut files: Vec<String> = segment_infos.files(true).into_iter().collect();
+            files.sort();
+            let num_threads = num_cpus::get().min(VERIFY_CHECKSUMS_MAX_THREADS);
+            verify_checksums_in_parallel(directory.as_ref(), &files, num_threads)?;
+        }
         let mut readers = Vec::with_capacity(segment_infos.segments.len());
         for seg_info in &segment_infos.segments {
             let s = SegmentReader::open(seg_info, &IOContext::READ)?;
+            let s = apply_soft_deletes(s, soft_deletes_field.as_ref())?;
             readers.push(Arc::new(s));
         }
         Ok(Self::new(
@@ -97,56 +174,86 @@ where
             None,
             false,
             false,
+            soft_deletes_field,
         ))
     }
 
-    /// Used by near real-time searcher
+    /// Used by near real-time searcher. The readers of `old_reader` whose
+    /// segment did not change are shared instead of being opened again.
     pub fn open_by_writer(
         writer: IndexWriter<D, C, MS, MP>,
         infos: &SegmentInfos<D, C>,
         apply_all_deletes: bool,
         write_all_deletes: bool,
+        old_reader: Option<&Self>,
     ) -> Result<Self> {
         // IndexWriter synchronizes externally before calling
         // us, which ensures infos will not change; so there's
         // no need to process segments in reverse order
         let num_segments = infos.len();
         let mut readers = Vec::with_capacity(num_segments);
+        let mut segment_states = Vec::with_capacity(num_segments);
+        let soft_deletes_field = writer.config().soft_deletes_field.clone();
 
         let mut segment_infos = infos.clone();
         let mut infos_upto = 0;
         for i in 0..infos.segments.len() {
-            // NOTE: important that we use infos not
-            // segmentInfos here, so that we are passing the
-            // actual instance of SegmentInfoPerCommit in
-            // IndexWriter's segmentInfos:
-            let mut rld = writer.reader_pool().get_or_create(&infos.segments[i])?;
-            let reader = rld.get_readonly_clone(&IOContext::READ)?;
+            let state = SegmentState::new(
+                infos.segments[i].as_ref(),
+                writer.num_deleted_docs(&infos.segments[i]),
+            );
+            let reader = match old_reader.and_then(|r| r.unchanged_reader(&state)) {
+                Some(reader) => reader,
+                None => {
+                    // NOTE: important that we use infos not
+                    // segmentInfos here, so that we are passing the
+                    // actual instance of SegmentInfoPerCommit in
+                    // IndexWriter's segmentInfos:
+                    let mut rld = writer.reader_pool().get_or_create(&infos.segments[i])?;
+                    let reader = rld.get_readonly_clone(&IOContext::READ)?;
+                    let reader = apply_soft_deletes(reader, soft_deletes_field.as_ref())?;
+                    writer.reader_pool().release(&rld, true)?;
+                    Arc::new(reader)
+                }
+            };
             if reader.num_docs() > 0 || writer.keep_fully_deleted_segments() {
                 // Steal the ref:
-                readers.push(Arc::new(reader));
+                readers.push(reader);
+                segment_states.push(state);
                 infos_upto += 1;
             } else {
                 segment_infos.segments.remove(infos_upto);
             }
-            writer.reader_pool().release(&rld, true)?;
         }
         writer.inc_ref_deleter(&segment_infos)?;
         let dir = Arc::clone(writer.directory());
-        Ok(StandardDirectoryReader::new(
+        let mut reader = StandardDirectoryReader::new(
             dir,
             readers,
             segment_infos,
             Some(writer),
             apply_all_deletes,
             write_all_deletes,
-        ))
+            soft_deletes_field,
+        );
+        reader.segment_states = segment_states;
+        Ok(reader)
+    }
+
+    // Returns the reader of the segment in `state`, if it was opened on the
+    // same state.
+    fn unchanged_reader(&self, state: &SegmentState) -> Option<Arc<SegmentReader<D, C>>> {
+        self.segment_states
+            .iter()
+            .position(|s| s == state)
+            .map(|i| Arc::clone(&self.readers[i]))
     }
 
     pub fn open_by_readers(
         directory: Arc<D>,
         infos: SegmentInfos<D, C>,
         old_readers: &[Arc<SegmentReader<D, C>>],
+        soft_deletes_field: Option<String>,
     ) -> Result<Self> {
         let mut reader_indexes: HashMap<&str, usize> = HashMap::with_capacity(old_readers.len());
         for (i, r) in old_readers.iter().enumerate() {
@@ -190,12 +297,15 @@ where
                                 reader.as_ref(),
                             )?
                         };
+                        let new_reader =
+                            apply_soft_deletes(new_reader, soft_deletes_field.as_ref())?;
                         new_readers.push(Arc::new(new_reader));
                     }
                     continue;
                 }
             }
             let new_reader = SegmentReader::open(commit_info, &IOContext::READ)?;
+            let new_reader = apply_soft_deletes(new_reader, soft_deletes_field.as_ref())?;
             new_readers.push(Arc::new(new_reader));
         }
         Ok(StandardDirectoryReader::new(
@@ -205,6 +315,7 @@ where
             None,
             false,
             false,
+            soft_deletes_field,
         ))
     }
 
@@ -215,6 +326,7 @@ where
         writer: Option<IndexWriter<D, C, MS, MP>>,
         apply_all_deletes: bool,
         write_all_deletes: bool,
+        soft_deletes_field: Option<String>,
     ) -> Self {
         let mut starts = Vec::with_capacity(readers.len() + 1);
         let mut max_doc = 0;
@@ -237,6 +349,8 @@ where
             writer,
             apply_all_deletes,
             write_all_deletes,
+            soft_deletes_field,
+            segment_states: Vec::new(),
         }
     }
 
@@ -248,6 +362,11 @@ where
         self.writer.clone()
     }
 
+    /// The readers of the segments, in the order of `leaves`.
+    pub fn segment_readers(&self) -> &[Arc<SegmentReader<D, C>>] {
+        &self.readers
+    }
+
     pub fn version(&self) -> i64 {
         self.segment_infos.version
     }
@@ -271,7 +390,9 @@ where
                 return Ok(None);
             }
 
-            let mut reader = writer.get_reader(self.apply_all_deletes, self.write_all_deletes)?;
+            // shares the readers of the unchanged segments, so that their per
+            // reader state survives the reopen
+            let mut reader = writer.reopen_reader(self)?;
             reader.writer = self.writer.clone();
             if reader.version() == self.segment_infos.version {
                 return Ok(None);
@@ -283,7 +404,8 @@ where
     fn open_from_commit(&self, commit: Option<&IndexCommit<D>>) -> Result<Self> {
         run_with_find_segment_file(&self.directory, commit, |(dir, file_name)| {
             let infos = SegmentInfos::read_commit(dir, file_name)?;
-            Self::open_by_readers(Arc::clone(dir), infos, &self.readers)
+            let soft_deletes_field = self.soft_deletes_field.clone();
+            Self::open_by_readers(Arc::clone(dir), infos, &self.readers, soft_deletes_field)
         })
     }
 
@@ -439,3 +561,101 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::codec::CodecEnum;
+    use core::doc::NumericDocValuesField;
+    use core::index::merge_policy::TieredMergePolicy;
+    use core::index::merge_scheduler::SerialMergeScheduler;
+    use core::index::IndexWriterConfig;
+    use core::store::{DataInput, DataOutput, RAMDirectory};
+    use core::util::external::deferred::Deferred;
+
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    type Reader =
+        StandardDirectoryReader<RAMDirectory, CodecEnum, SerialMergeScheduler, TieredMergePolicy>;
+
+    #[test]
+    fn test_open_with_checksums() {
+        let dir = Arc::new(RAMDirectory::default());
+        let writer = IndexWriter::new(Arc::clone(&dir), Arc::new(IndexWriterConfig::default()))
+            .unwrap();
+        for i in 0..10 {
+            writer
+                .add_document(vec![NumericDocValuesField::new("id", i)])
+                .unwrap();
+        }
+        writer.close().unwrap();
+
+        let reader = Reader::open_with_checksums(Arc::clone(&dir)).unwrap();
+        assert_eq!(reader.num_docs(), 10);
+
+        // flip a byte in the middle of the compound file
+        let cfs = dir
+            .list_all()
+            .unwrap()
+            .into_iter()
+            .find(|f| f.ends_with(".cfs"))
+            .unwrap();
+        let mut bytes = vec![0u8; dir.file_length(&cfs).unwrap() as usize];
+        let mut input = dir.open_input(&cfs, &IOContext::READ).unwrap();
+        let len = bytes.len();
+        input.read_bytes(&mut bytes, 0, len).unwrap();
+        bytes[len / 2] ^= 0xFF;
+        {
+            let mut out = dir.create_output(&cfs, &IOContext::Default).unwrap();
+            out.write_bytes(&bytes, 0, len).unwrap();
+        }
+
+        let err = Reader::open_with_checksums(Arc::clone(&dir)).err().unwrap();
+        assert!(format!("{}", err).contains(&cfs));
+    }
+
+    #[test]
+    fn test_core_cache_key_shared_by_reopened_readers() {
+        let dir = Arc::new(RAMDirectory::default());
+        let writer = IndexWriter::new(dir, Arc::new(IndexWriterConfig::default())).unwrap();
+        writer
+            .add_document(vec![NumericDocValuesField::new("id", 0)])
+            .unwrap();
+        let first = writer.get_reader(true, false).unwrap();
+        writer
+            .add_document(vec![NumericDocValuesField::new("id", 1)])
+            .unwrap();
+        let second = writer.get_reader(true, false).unwrap();
+
+        let closed = Arc::new(AtomicBool::new(false));
+        {
+            let first_leaves = first.leaves();
+            let second_leaves = second.leaves();
+            assert_eq!(first_leaves.len(), 1);
+            assert_eq!(second_leaves.len(), 2);
+            assert_eq!(
+                first_leaves[0].reader.core_cache_key(),
+                second_leaves[0].reader.core_cache_key()
+            );
+            assert_ne!(
+                second_leaves[0].reader.core_cache_key(),
+                second_leaves[1].reader.core_cache_key()
+            );
+
+            let flag = Arc::clone(&closed);
+            first_leaves[0]
+                .reader
+                .add_core_drop_listener(Deferred::new(move || {
+                    flag.store(true, Ordering::Release);
+                }));
+        }
+
+        // the core is still used by the second reader and the writer
+        drop(first);
+        assert!(!closed.load(Ordering::Acquire));
+        drop(second);
+        writer.close().unwrap();
+        drop(writer);
+        assert!(closed.load(Ordering::Acquire));
+    }
+}
@@ -11,7 +11,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use core::analysis::TokenStream;
+use core::analysis::{TokenStream, OFFSET_GAP, POSITION_INCREMENT_GAP};
 use core::codec::{
     Codec, DocValuesFormat, FieldInfosFormat, NormsFormat, PointsFormat, PointsWriter,
     StoredFieldsFormat, StoredFieldsWriter, StoredFieldsWriterEnum,
@@ -316,7 +316,7 @@ where
     unsafe fn process_field(
         &mut self,
         field: &mut impl Fieldable,
-        doc_state: &DocState,
+        doc_state: &mut DocState,
         field_gen: i64,
         field_count: usize,
     ) -> Result<usize> {
@@ -473,6 +473,20 @@ where
         Ok(idx)
     }
 
+    /// The index sort fields must be single-valued numeric doc values.
+    fn validate_index_sort_dv_type(&self, field: &str, dv_type: DocValuesType) -> Result<()> {
+        if let Some(sort) = self.doc_writer().index_sort() {
+            if sort.get_sort().iter().any(|f| f.field() == field) {
+                bail!(IllegalArgument(format!(
+                    "invalid doc values type {:?} for the index sort field '{}', it must be \
+                     Numeric",
+                    dv_type, field
+                )));
+            }
+        }
+        Ok(())
+    }
+
     /// Called from processDocument to index one field's doc value
     fn index_doc_value(
         &mut self,
@@ -481,6 +495,9 @@ where
         field: &impl Fieldable,
         doc_state: &DocState,
     ) -> Result<()> {
+        if dv_type != DocValuesType::Numeric {
+            self.validate_index_sort_dv_type(field.name(), dv_type)?;
+        }
         let per_field = &mut self.field_hash[field_idx];
 
         if per_field.field_info().doc_values_type == DocValuesType::Null {
@@ -638,6 +655,7 @@ where
         // How many indexed field names we've seen (collapses
         // multiple field instances by the same name):
         let mut field_count = 0;
+        doc_state.truncated = false;
         self.fields.clear();
         let field_gen = self.next_field_gen;
         self.next_field_gen += 1;
@@ -674,6 +692,8 @@ where
         // aborting on any exception from this method
         let max_doc = state.segment_info.max_doc();
         self.write_norms(state)?;
+        // the postings record the norms in their impacts
+        state.load_norms()?;
 
         for per_field in &mut self.field_hash {
             let field_info = self.field_infos.get_or_add(&per_field.invert_state.name)?;
@@ -835,7 +855,7 @@ impl<T: TermsHashPerField> PerField<T> {
     fn invert<D, C, MS, MP>(
         &mut self,
         field: &mut impl Fieldable,
-        doc_state: &DocState,
+        doc_state: &mut DocState,
         first: bool,
         index_chain: &mut DefaultIndexingChain<D, C, MS, MP>,
     ) -> Result<()>
@@ -872,6 +892,7 @@ impl<T: TermsHashPerField> PerField<T> {
         // only bother checking offsets if something will consume them.
         // TODO: after we fix analyzers, also check if termVectorOffsets will be indexed.
         let check_offset = index_options == IndexOptions::DocsAndFreqsAndPositionsAndOffsets;
+        let max_field_length = field.field_type().max_field_length;
 
         // To assist people in tracking down problems in analysis components, we wish to
         // write the field name to the infostream when we fail. We expect some caller to
@@ -890,6 +911,14 @@ impl<T: TermsHashPerField> PerField<T> {
             if !end {
                 break;
             }
+            if let Some(max_length) = max_field_length {
+                // the length counts the tokens of all the previous values
+                // of this field in the doc, stop once the limit is hit
+                if self.invert_state.length >= max_length as i32 {
+                    doc_state.truncated = true;
+                    break;
+                }
+            }
 
             // If we hit an exception in stream.next below
             // (which is fairly common, e.g. if analyzer
@@ -965,10 +994,12 @@ impl<T: TermsHashPerField> PerField<T> {
             .get_position_increment() as i32;
         self.invert_state.offset += token_stream.offset_attribute_mut().end_offset();
 
-        //        if analyzed {
-        //            self.invert_state.position += doc_state.analyzer.get_position_increment_gap();
-        //            self.invert_state.offset += doc_state.analyzer.get_offset_gap();
-        //        }
+        // keep the positions and offsets of the next value of the field apart
+        // from this one's
+        if field.field_type().tokenized {
+            self.invert_state.position += POSITION_INCREMENT_GAP;
+            self.invert_state.offset += OFFSET_GAP;
+        }
 
         self.invert_state.boost *= field.boost();
 
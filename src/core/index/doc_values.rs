@@ -13,11 +13,11 @@
 
 use core::codec::Codec;
 use core::index::{
-    BinaryDocValuesRef, MultiTermIterator, NumericDocValues, NumericDocValuesContext,
-    NumericDocValuesRef, ReaderSlice, SearchLeafReader, SingletonSortedNumericDocValues,
-    SingletonSortedSetDocValues, SortedDocValues, SortedDocValuesRef, SortedNumericDocValues,
-    SortedNumericDocValuesRef, SortedSetDocValues, SortedSetDocValuesRef, TermIterator,
-    TermIteratorIndex, NO_MORE_ORDS,
+    BinaryDocValuesRef, DocValuesTermIterator, DocValuesType, LeafReaderContext,
+    MultiTermIterator, NumericDocValues, NumericDocValuesContext, NumericDocValuesRef,
+    ReaderSlice, SearchLeafReader, SingletonSortedNumericDocValues, SingletonSortedSetDocValues,
+    SortedDocValues, SortedDocValuesRef, SortedNumericDocValues, SortedNumericDocValuesRef,
+    SortedSetDocValues, SortedSetDocValuesRef, TermIterator, TermIteratorIndex, NO_MORE_ORDS,
 };
 use core::util::bit_util::BitsRequired;
 use core::util::packed::{
@@ -30,7 +30,7 @@ use core::util::{
 
 use error::Result;
 
-use std::rc::Rc;
+use std::fmt;
 use std::sync::Arc;
 
 pub struct DocValues;
@@ -162,6 +162,12 @@ impl Bits for SortedNumericDocValuesBits {
 }
 
 /// maps per-segment ordinals to/from global ordinal space
+///
+/// The global ords are the ords of the union of the terms of the segments, so
+/// they sort like the terms. The mappings are kept in packed ints, a segment
+/// whose ords are the global ones costing nothing, so the map of a field with
+/// millions of terms takes a few bytes per term: build it once per reader and
+/// share it, e.g. between the searches sorting or faceting on the field.
 // TODO: we could also have a utility method to merge Terms[] and use size() as a weight when we
 // need it TODO: use more efficient packed ints structures?
 // TODO: pull this out? it's pretty generic (maps between N ord()-enabled TermsEnums)
@@ -172,7 +178,7 @@ pub struct OrdinalMap {
     // globalOrd -> first segment container
     first_segments: PackedLongValues,
     // for every segment, segmentOrd -> globalOrd
-    segment_to_global_ords: Vec<Rc<LongValues>>,
+    segment_to_global_ords: Vec<Arc<dyn LongValues>>,
     // the map from/to segment ids
     segment_map: SegmentMap,
 }
@@ -188,6 +194,30 @@ impl OrdinalMap {
         Self::new(subs, segment_map, acceptable_overhead_ratio)
     }
 
+    /// Builds the map of the `SORTED_SET` doc values of `field` over `leaves`,
+    /// the segments without them have no ords.
+    pub fn build_sorted_set<C: Codec>(
+        leaves: &[LeafReaderContext<'_, C>],
+        field: &str,
+    ) -> Result<Self> {
+        let mut subs = Vec::with_capacity(leaves.len());
+        let mut weights = Vec::with_capacity(leaves.len());
+        for leaf in leaves {
+            match leaf.reader.field_info(field) {
+                Some(fi) if fi.doc_values_type == DocValuesType::SortedSet => {
+                    let doc_values = leaf.reader.get_sorted_set_doc_values(field)?;
+                    subs.push(Some(doc_values.term_iterator()?));
+                    weights.push(doc_values.get_value_count());
+                }
+                _ => {
+                    subs.push(Some(DocValuesTermIterator::empty()));
+                    weights.push(0);
+                }
+            }
+        }
+        Self::build(subs, weights, COMPACT)
+    }
+
     fn new<T: TermIterator>(
         mut subs: Vec<Option<T>>,
         segment_map: SegmentMap,
@@ -262,14 +292,14 @@ impl OrdinalMap {
         let first_segments = first_segments_builder.build();
         let global_ord_deltas = global_ord_deltas_builder.build();
 
-        let mut segment_to_global_ords: Vec<Rc<LongValues>> = Vec::with_capacity(subs.len());
+        let mut segment_to_global_ords: Vec<Arc<dyn LongValues>> = Vec::with_capacity(subs.len());
         let mut i = 0;
         for mut d in ord_deltas {
             let deltas = d.build();
             if ord_delta_bits[i] == 0 {
                 // segment ords perfectly match global ordinals
                 // likely in case of low cardinalities and large segments
-                segment_to_global_ords.push(Rc::new(IdentityLongValues {}));
+                segment_to_global_ords.push(Arc::new(IdentityLongValues {}));
             } else {
                 let bits_required = if ord_delta_bits[i] < 0 {
                     64
@@ -296,12 +326,12 @@ impl OrdinalMap {
                         cnt += 1;
                     }
                     debug_assert_eq!(cnt as i64, size);
-                    segment_to_global_ords.push(Rc::new(MutableAsLongValues {
+                    segment_to_global_ords.push(Arc::new(MutableAsLongValues {
                         mutable: new_deltas,
                     }));
                 } else {
                     segment_to_global_ords
-                        .push(Rc::new(PackedLongValuesWrapper { values: deltas }));
+                        .push(Arc::new(PackedLongValuesWrapper { values: deltas }));
                 }
             }
             i += 1;
@@ -328,9 +358,32 @@ impl OrdinalMap {
         global_ord - self.global_ord_deltas.get64(global_ord).unwrap()
     }
 
-    pub fn get_global_ords(&self, index: usize) -> Rc<LongValues> {
+    pub fn get_global_ords(&self, index: usize) -> Arc<dyn LongValues> {
         let i = self.segment_map.old_to_new(index as i32) as usize;
-        Rc::clone(&self.segment_to_global_ords[i])
+        Arc::clone(&self.segment_to_global_ords[i])
+    }
+
+    /// Returns the global ord of the term of ord `segment_ord` in the segment
+    /// `segment_index`.
+    pub fn global_ord(&self, segment_index: usize, segment_ord: i64) -> Result<i64> {
+        let i = self.segment_map.old_to_new(segment_index as i32) as usize;
+        self.segment_to_global_ords[i].get64(segment_ord)
+    }
+
+    /// The number of segments the map was built over.
+    pub fn segment_count(&self) -> usize {
+        self.segment_to_global_ords.len()
+    }
+}
+
+impl fmt::Debug for OrdinalMap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "OrdinalMap(value_count: {}, segment_count: {})",
+            self.value_count(),
+            self.segment_count()
+        )
     }
 }
 
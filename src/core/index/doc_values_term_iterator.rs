@@ -14,6 +14,7 @@
 use core::codec::CompressedBinaryTermIterator;
 use core::index::sorted_doc_values::TailoredSortedDocValues;
 use core::index::sorted_doc_values_term_iterator::SortedDocValuesTermIterator;
+use core::index::multi_doc_values::MultiSortedDocValues;
 use core::index::sorted_set_doc_values::{AddressedRandomAccessOrds, TabledRandomAccessOrds};
 use core::index::sorted_set_doc_values_term_iterator::SortedSetDocValuesTermIterator;
 use core::index::{EmptyTermIterator, OrdTermState, SeekStatus, TermIterator};
@@ -30,6 +31,9 @@ impl DocValuesTermIterator {
     pub fn sorted(d: SortedDocValuesTermIterator<TailoredSortedDocValues>) -> Self {
         DocValuesTermIterator(DocValuesTermIteratorEnum::Sorted(d))
     }
+    pub fn multi_sorted(d: SortedDocValuesTermIterator<MultiSortedDocValues>) -> Self {
+        DocValuesTermIterator(DocValuesTermIteratorEnum::MultiSorted(d))
+    }
     pub fn sorted_set_addr(d: SortedSetDocValuesTermIterator<AddressedRandomAccessOrds>) -> Self {
         DocValuesTermIterator(DocValuesTermIteratorEnum::SortedSetAddr(d))
     }
@@ -44,6 +48,7 @@ impl DocValuesTermIterator {
 enum DocValuesTermIteratorEnum {
     CompBin(CompressedBinaryTermIterator),
     Sorted(SortedDocValuesTermIterator<TailoredSortedDocValues>),
+    MultiSorted(SortedDocValuesTermIterator<MultiSortedDocValues>),
     SortedSetAddr(SortedSetDocValuesTermIterator<AddressedRandomAccessOrds>),
     SortedSetTable(SortedSetDocValuesTermIterator<TabledRandomAccessOrds>),
     Empty(EmptyTermIterator),
@@ -56,6 +61,7 @@ impl TermIterator for DocValuesTermIterator {
         match &mut self.0 {
             DocValuesTermIteratorEnum::CompBin(t) => t.next(),
             DocValuesTermIteratorEnum::Sorted(t) => t.next(),
+            DocValuesTermIteratorEnum::MultiSorted(t) => t.next(),
             DocValuesTermIteratorEnum::SortedSetAddr(t) => t.next(),
             DocValuesTermIteratorEnum::SortedSetTable(t) => t.next(),
             DocValuesTermIteratorEnum::Empty(t) => t.next(),
@@ -66,6 +72,7 @@ impl TermIterator for DocValuesTermIterator {
         match &mut self.0 {
             DocValuesTermIteratorEnum::CompBin(t) => t.seek_exact(text),
             DocValuesTermIteratorEnum::Sorted(t) => t.seek_exact(text),
+            DocValuesTermIteratorEnum::MultiSorted(t) => t.seek_exact(text),
             DocValuesTermIteratorEnum::SortedSetAddr(t) => t.seek_exact(text),
             DocValuesTermIteratorEnum::SortedSetTable(t) => t.seek_exact(text),
             DocValuesTermIteratorEnum::Empty(t) => t.seek_exact(text),
@@ -76,6 +83,7 @@ impl TermIterator for DocValuesTermIterator {
         match &mut self.0 {
             DocValuesTermIteratorEnum::CompBin(t) => t.seek_ceil(text),
             DocValuesTermIteratorEnum::Sorted(t) => t.seek_ceil(text),
+            DocValuesTermIteratorEnum::MultiSorted(t) => t.seek_ceil(text),
             DocValuesTermIteratorEnum::SortedSetAddr(t) => t.seek_ceil(text),
             DocValuesTermIteratorEnum::SortedSetTable(t) => t.seek_ceil(text),
             DocValuesTermIteratorEnum::Empty(t) => t.seek_ceil(text),
@@ -86,6 +94,7 @@ impl TermIterator for DocValuesTermIterator {
         match &mut self.0 {
             DocValuesTermIteratorEnum::CompBin(t) => t.seek_exact_ord(ord),
             DocValuesTermIteratorEnum::Sorted(t) => t.seek_exact_ord(ord),
+            DocValuesTermIteratorEnum::MultiSorted(t) => t.seek_exact_ord(ord),
             DocValuesTermIteratorEnum::SortedSetAddr(t) => t.seek_exact_ord(ord),
             DocValuesTermIteratorEnum::SortedSetTable(t) => t.seek_exact_ord(ord),
             DocValuesTermIteratorEnum::Empty(t) => t.seek_exact_ord(ord),
@@ -96,6 +105,7 @@ impl TermIterator for DocValuesTermIterator {
         match &mut self.0 {
             DocValuesTermIteratorEnum::CompBin(_) => unreachable!(),
             DocValuesTermIteratorEnum::Sorted(t) => t.seek_exact_state(text, state),
+            DocValuesTermIteratorEnum::MultiSorted(t) => t.seek_exact_state(text, state),
             DocValuesTermIteratorEnum::SortedSetAddr(t) => t.seek_exact_state(text, state),
             DocValuesTermIteratorEnum::SortedSetTable(t) => t.seek_exact_state(text, state),
             DocValuesTermIteratorEnum::Empty(_) => unreachable!(),
@@ -106,6 +116,7 @@ impl TermIterator for DocValuesTermIterator {
         match &self.0 {
             DocValuesTermIteratorEnum::CompBin(t) => t.term(),
             DocValuesTermIteratorEnum::Sorted(t) => t.term(),
+            DocValuesTermIteratorEnum::MultiSorted(t) => t.term(),
             DocValuesTermIteratorEnum::SortedSetAddr(t) => t.term(),
             DocValuesTermIteratorEnum::SortedSetTable(t) => t.term(),
             DocValuesTermIteratorEnum::Empty(t) => t.term(),
@@ -116,6 +127,7 @@ impl TermIterator for DocValuesTermIterator {
         match &self.0 {
             DocValuesTermIteratorEnum::CompBin(t) => t.ord(),
             DocValuesTermIteratorEnum::Sorted(t) => t.ord(),
+            DocValuesTermIteratorEnum::MultiSorted(t) => t.ord(),
             DocValuesTermIteratorEnum::SortedSetAddr(t) => t.ord(),
             DocValuesTermIteratorEnum::SortedSetTable(t) => t.ord(),
             DocValuesTermIteratorEnum::Empty(t) => t.ord(),
@@ -126,6 +138,7 @@ impl TermIterator for DocValuesTermIterator {
         match &mut self.0 {
             DocValuesTermIteratorEnum::CompBin(t) => t.doc_freq(),
             DocValuesTermIteratorEnum::Sorted(t) => t.doc_freq(),
+            DocValuesTermIteratorEnum::MultiSorted(t) => t.doc_freq(),
             DocValuesTermIteratorEnum::SortedSetAddr(t) => t.doc_freq(),
             DocValuesTermIteratorEnum::SortedSetTable(t) => t.doc_freq(),
             DocValuesTermIteratorEnum::Empty(t) => t.doc_freq(),
@@ -136,6 +149,7 @@ impl TermIterator for DocValuesTermIterator {
         match &mut self.0 {
             DocValuesTermIteratorEnum::CompBin(t) => t.total_term_freq(),
             DocValuesTermIteratorEnum::Sorted(t) => t.total_term_freq(),
+            DocValuesTermIteratorEnum::MultiSorted(t) => t.total_term_freq(),
             DocValuesTermIteratorEnum::SortedSetAddr(t) => t.total_term_freq(),
             DocValuesTermIteratorEnum::SortedSetTable(t) => t.total_term_freq(),
             DocValuesTermIteratorEnum::Empty(t) => t.total_term_freq(),
@@ -146,6 +160,7 @@ impl TermIterator for DocValuesTermIterator {
         match &mut self.0 {
             DocValuesTermIteratorEnum::CompBin(t) => t.postings(),
             DocValuesTermIteratorEnum::Sorted(t) => t.postings(),
+            DocValuesTermIteratorEnum::MultiSorted(t) => t.postings(),
             DocValuesTermIteratorEnum::SortedSetAddr(t) => t.postings(),
             DocValuesTermIteratorEnum::SortedSetTable(t) => t.postings(),
             DocValuesTermIteratorEnum::Empty(t) => t.postings(),
@@ -156,6 +171,7 @@ impl TermIterator for DocValuesTermIterator {
         match &mut self.0 {
             DocValuesTermIteratorEnum::CompBin(t) => t.postings_with_flags(flags),
             DocValuesTermIteratorEnum::Sorted(t) => t.postings_with_flags(flags),
+            DocValuesTermIteratorEnum::MultiSorted(t) => t.postings_with_flags(flags),
             DocValuesTermIteratorEnum::SortedSetAddr(t) => t.postings_with_flags(flags),
             DocValuesTermIteratorEnum::SortedSetTable(t) => t.postings_with_flags(flags),
             DocValuesTermIteratorEnum::Empty(t) => t.postings_with_flags(flags),
@@ -166,6 +182,7 @@ impl TermIterator for DocValuesTermIterator {
         match &mut self.0 {
             DocValuesTermIteratorEnum::CompBin(_) => unimplemented!(),
             DocValuesTermIteratorEnum::Sorted(t) => t.term_state(),
+            DocValuesTermIteratorEnum::MultiSorted(t) => t.term_state(),
             DocValuesTermIteratorEnum::SortedSetAddr(t) => t.term_state(),
             DocValuesTermIteratorEnum::SortedSetTable(t) => t.term_state(),
             DocValuesTermIteratorEnum::Empty(_) => unimplemented!(),
@@ -176,6 +193,7 @@ impl TermIterator for DocValuesTermIterator {
         match &self.0 {
             DocValuesTermIteratorEnum::CompBin(t) => t.is_empty(),
             DocValuesTermIteratorEnum::Sorted(t) => t.is_empty(),
+            DocValuesTermIteratorEnum::MultiSorted(t) => t.is_empty(),
             DocValuesTermIteratorEnum::SortedSetAddr(t) => t.is_empty(),
             DocValuesTermIteratorEnum::SortedSetTable(t) => t.is_empty(),
             DocValuesTermIteratorEnum::Empty(t) => t.is_empty(),
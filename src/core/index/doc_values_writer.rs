@@ -954,6 +954,7 @@ pub trait SizeInBytesCalc {
 }
 
 /// An in-place update to a DocValues field.
+#[derive(Clone)]
 pub struct DocValuesUpdate {
     pub doc_values_type: DocValuesType,
     pub term: Term,
@@ -1027,8 +1028,9 @@ impl SizeInBytesCalc for BinaryDocValuesUpdate {
     }
 }
 
+#[derive(Clone)]
 pub struct NumericDocValuesUpdate {
-    update: DocValuesUpdate,
+    pub update: DocValuesUpdate,
 }
 
 impl NumericDocValuesUpdate {
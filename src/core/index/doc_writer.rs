@@ -12,7 +12,7 @@
 // limitations under the License.
 
 use core::codec::Codec;
-use core::index::doc_writer_delete_queue::DocumentsWriterDeleteQueue;
+use core::index::doc_writer_delete_queue::{DeleteTerm, DocumentsWriterDeleteQueue};
 use core::index::doc_writer_flush_queue::DocumentsWriterFlushQueue;
 use core::index::flush_control::DocumentsWriterFlushControl;
 use core::index::flush_policy::FlushByRamOrCountsPolicy;
@@ -21,21 +21,20 @@ use core::index::index_writer_config::IndexWriterConfig;
 use core::index::thread_doc_writer::{
     DocumentsWriterPerThread, DocumentsWriterPerThreadPool, ThreadState,
 };
-use core::index::{Fieldable, SegmentInfo, Term};
+use core::index::{Fieldable, NumericDocValuesUpdate, SegmentInfo, Term};
 use core::search::Query;
 use core::store::{Directory, LockValidatingDirectoryWrapper};
-use core::util::Volatile;
+use core::util::{BitsRef, Volatile};
 use error::{ErrorKind::AlreadyClosed, Result};
 
 use crossbeam::queue::SegQueue;
 
 use core::index::merge_policy::MergePolicy;
 use core::index::merge_scheduler::MergeScheduler;
-use std::cmp::max;
 use std::collections::HashSet;
 use std::mem;
-use std::sync::atomic::{AtomicU32, Ordering};
-use std::sync::{Arc, Mutex, MutexGuard, Weak};
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, RwLock, Weak};
 use std::thread;
 
 ///
@@ -107,7 +106,8 @@ pub(crate) struct DocumentsWriter<
     closed: bool,
     num_docs_in_ram: AtomicU32,
     // TODO: cut over to BytesRefHash in BufferedDeletes
-    pub delete_queue: Arc<DocumentsWriterDeleteQueue<C>>,
+    // swapped for a new queue on each full flush, see `set_delete_queue`
+    delete_queue: RwLock<Arc<DocumentsWriterDeleteQueue<C>>>,
     ticket_queue: DocumentsWriterFlushQueue<D, C>,
 
     // we preserve changes during a full flush since IW might not checkout
@@ -121,7 +121,7 @@ pub(crate) struct DocumentsWriter<
     config: Arc<IndexWriterConfig<C, MS, MP>>,
     writer: Weak<IndexWriterInner<D, C, MS, MP>>,
     pub events: SegQueue<WriterEvent<D, C>>,
-    pub last_seq_no: u64,
+    last_seq_no: AtomicU64,
     inited: bool,
     // must init flush_control after new
 }
@@ -147,7 +147,7 @@ where
             directory,
             closed: false,
             num_docs_in_ram: AtomicU32::new(0),
-            delete_queue: Arc::new(DocumentsWriterDeleteQueue::default()),
+            delete_queue: RwLock::new(Arc::new(DocumentsWriterDeleteQueue::default())),
             ticket_queue: DocumentsWriterFlushQueue::new(),
             pending_changes_in_current_full_flush: Volatile::new(false),
             per_thread_pool: DocumentsWriterPerThreadPool::new(),
@@ -156,7 +156,7 @@ where
             config,
             writer: Weak::new(),
             events: SegQueue::new(),
-            last_seq_no: 0,
+            last_seq_no: AtomicU64::new(0),
             inited: false,
         }
     }
@@ -179,19 +179,41 @@ where
         self.writer.upgrade().unwrap()
     }
 
-    unsafe fn doc_writer_mut(&self, _l: &MutexGuard<()>) -> &mut DocumentsWriter<D, C, MS, MP> {
-        let w = self as *const DocumentsWriter<D, C, MS, MP> as *mut DocumentsWriter<D, C, MS, MP>;
-        &mut *w
+    /// Returns the current global delete queue.
+    pub(crate) fn delete_queue(&self) -> Arc<DocumentsWriterDeleteQueue<C>> {
+        let queue = self.delete_queue.read().unwrap_or_else(|e| e.into_inner());
+        Arc::clone(&queue)
     }
 
+    /// Swaps in a new global delete queue, called by the flush control under
+    /// its lock when marking a full flush.
     pub(crate) fn set_delete_queue(&self, delete_queue: Arc<DocumentsWriterDeleteQueue<C>>) {
-        // let l = self.lock.lock().unwrap();
-        // let doc_writer_mut = unsafe { self.doc_writer_mut(&l) };
+        let mut queue = self.delete_queue.write().unwrap_or_else(|e| e.into_inner());
+        *queue = delete_queue;
+    }
 
-        // already locked
-        let w = self as *const DocumentsWriter<D, C, MS, MP> as *mut DocumentsWriter<D, C, MS, MP>;
-        unsafe {
-            (*w).delete_queue = delete_queue;
+    #[cfg(test)]
+    pub fn last_seq_no(&self) -> u64 {
+        self.last_seq_no.load(Ordering::Acquire)
+    }
+
+    pub(crate) fn set_last_seq_no(&self, seq_no: u64) {
+        self.last_seq_no.store(seq_no, Ordering::Release);
+    }
+
+    /// Raises `last_seq_no` to `seq_no` if it is lower.
+    fn update_last_seq_no(&self, seq_no: u64) {
+        let mut current = self.last_seq_no.load(Ordering::Acquire);
+        while current < seq_no {
+            match self.last_seq_no.compare_exchange_weak(
+                current,
+                seq_no,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => break,
+                Err(v) => current = v,
+            }
         }
     }
 
@@ -199,7 +221,7 @@ where
         &self,
         docs: Vec<Vec<F>>,
         // analyzer: Analyzer,
-        del_term: Option<Term>,
+        del_term: Option<DeleteTerm>,
     ) -> Result<(u64, bool)> {
         debug_assert!(self.inited);
         let has_events = self.pre_update()?;
@@ -230,7 +252,7 @@ where
         per_thread: &mut ThreadState<D, C, MS, MP>,
         docs: Vec<Vec<F>>,
         // analyzer: Analyzer,
-        del_term: Option<Term>,
+        del_term: Option<DeleteTerm>,
     ) -> Result<(u64, Option<DocumentsWriterPerThread<D, C, MS, MP>>)> {
         let is_update = del_term.is_some();
 
@@ -278,7 +300,7 @@ where
         &self,
         doc: Vec<F>,
         // analyzer: Analyzer,
-        del_term: Option<Term>,
+        del_term: Option<DeleteTerm>,
     ) -> Result<(u64, bool)> {
         debug_assert!(self.inited);
         let mut has_event = self.pre_update()?;
@@ -309,7 +331,7 @@ where
         per_thread: &mut ThreadState<D, C, MS, MP>,
         doc: Vec<F>,
         // analyzer: Analyzer,
-        del_term: Option<Term>,
+        del_term: Option<DeleteTerm>,
     ) -> Result<(u64, Option<DocumentsWriterPerThread<D, C, MS, MP>>)> {
         let is_update = del_term.is_some();
 
@@ -406,7 +428,7 @@ where
                 Arc::clone(&self.directory_orig),
                 Arc::clone(&self.directory),
                 Arc::clone(&self.config),
-                Arc::clone(&self.delete_queue),
+                self.delete_queue(),
                 pending_num_docs,
             )?;
 
@@ -424,26 +446,62 @@ where
     pub fn delete_queries(&self, queries: Vec<Arc<dyn Query<C>>>) -> Result<(u64, bool)> {
         debug_assert!(self.inited);
         // TODO why is this synchronized?
-        let l = self.lock.lock()?;
-        let doc_writer_mut = unsafe { self.doc_writer_mut(&l) };
-        let seq_no = self.delete_queue.add_delete_queries(queries)?;
-        doc_writer_mut.flush_control.do_on_delete();
+        let _l = self.lock.lock()?;
+        let seq_no = self.delete_queue().add_delete_queries(queries)?;
+        self.flush_control.do_on_delete();
 
         let applyed = self.apply_all_deletes_local()?;
-        doc_writer_mut.last_seq_no = max(self.last_seq_no, seq_no);
+        self.update_last_seq_no(seq_no);
         Ok((seq_no, applyed))
     }
 
     pub fn delete_terms(&self, terms: Vec<Term>) -> Result<(u64, bool)> {
         debug_assert!(self.inited);
         // TODO why is this synchronized?
-        let l = self.lock.lock()?;
-        let doc_writer_mut = unsafe { self.doc_writer_mut(&l) };
-        let seq_no = self.delete_queue.add_delete_terms(terms)?;
-        doc_writer_mut.flush_control.do_on_delete();
+        let _l = self.lock.lock()?;
+        let seq_no = self.delete_queue().add_delete_terms(terms)?;
+        self.flush_control.do_on_delete();
+
+        let applyed = self.apply_all_deletes_local()?;
+        self.update_last_seq_no(seq_no);
+        Ok((seq_no, applyed))
+    }
+
+    pub fn update_numeric_doc_value(&self, update: NumericDocValuesUpdate) -> Result<(u64, bool)> {
+        debug_assert!(self.inited);
+        let _l = self.lock.lock()?;
+        let seq_no = self
+            .delete_queue()
+            .add_numeric_doc_values_update(update)?;
+        self.flush_control.do_on_delete();
+
+        let applyed = self.apply_all_deletes_local()?;
+        self.update_last_seq_no(seq_no);
+        Ok((seq_no, applyed))
+    }
+
+    pub fn delete_doc_sets(&self, doc_sets: Vec<(String, BitsRef)>) -> Result<(u64, bool)> {
+        debug_assert!(self.inited);
+        let _l = self.lock.lock()?;
+        let seq_no = self.delete_queue().add_delete_doc_sets(doc_sets)?;
+        self.flush_control.do_on_delete();
+        // the doc sets only apply to the segments they were computed on, which
+        // may be merged away if the deletes stay buffered
+        self.flush_control.set_apply_all_deletes();
+
+        let applyed = self.apply_all_deletes_local()?;
+        self.update_last_seq_no(seq_no);
+        Ok((seq_no, applyed))
+    }
+
+    pub fn soft_delete_terms(&self, terms: Vec<Term>, field: String) -> Result<(u64, bool)> {
+        debug_assert!(self.inited);
+        let _l = self.lock.lock()?;
+        let seq_no = self.delete_queue().add_soft_delete_terms(terms, field)?;
+        self.flush_control.do_on_delete();
 
         let applyed = self.apply_all_deletes_local()?;
-        doc_writer_mut.last_seq_no = max(self.last_seq_no, seq_no);
+        self.update_last_seq_no(seq_no);
         Ok((seq_no, applyed))
     }
 
@@ -454,7 +512,7 @@ where
     fn apply_all_deletes_local(&self) -> Result<bool> {
         if self.flush_control.get_and_reset_apply_all_deletes() {
             if !self.flush_control.is_full_flush() {
-                self.ticket_queue.add_deletes(&self.delete_queue)?;
+                self.ticket_queue.add_deletes(&self.delete_queue())?;
             }
             self.put_event(WriterEvent::ApplyDeletes);
             Ok(true)
@@ -489,7 +547,7 @@ where
     pub fn abort(&mut self) -> Result<()> {
         let lock = Arc::clone(&self.lock);
         let _l = lock.lock()?;
-        self.delete_queue.clear()?;
+        self.delete_queue().clear()?;
         debug!("DW: start to abort");
 
         for i in 0..self.per_thread_pool.active_thread_state_count() {
@@ -510,7 +568,7 @@ where
         debug!("DW - lock_and_abort_all");
 
         let mut aborted_doc_count = 0;
-        self.delete_queue.clear()?;
+        self.delete_queue().clear()?;
         self.per_thread_pool.set_abort();
         for i in 0..self.per_thread_pool.active_thread_state_count() {
             let per_thread = Arc::clone(&self.per_thread_pool.get_thread_state(i));
@@ -518,17 +576,26 @@ where
             let per_thread_mut = per_thread.thread_state_mut(&guard);
             aborted_doc_count += self.abort_thread_state(per_thread_mut);
         }
-        self.delete_queue.clear()?;
+        self.delete_queue().clear()?;
 
         // jump over any possible in flight ops:
         let jump = self.per_thread_pool.active_thread_state_count() + 1;
-        self.delete_queue.skip_sequence_number(jump as u64);
+        self.delete_queue().skip_sequence_number(jump as u64);
 
         self.flush_control.abort_pending_flushes();
         self.flush_control.wait_for_flush()?;
         Ok(aborted_doc_count)
     }
 
+    /// Lets new thread states be created again after `lock_and_abort_all`,
+    /// must be called once the caller is done with the aborted state, even if
+    /// it failed.
+    /// _l is IndexWriter.full_flush_lock guard
+    pub fn unlock_all_after_abort_all(&self, _l: &MutexGuard<()>) {
+        debug!("DW - unlock_all_after_abort_all");
+        self.per_thread_pool.clear_abort();
+    }
+
     /// Returns how many documents were aborted.
     fn abort_thread_state(&self, per_thread: &mut ThreadState<D, C, MS, MP>) -> u32 {
         if per_thread.inited() {
@@ -658,7 +725,7 @@ where
         // before they are published to the IW. ie we need to check if the
         // ticket queue has any tickets.
         self.num_docs_in_ram.load(Ordering::Acquire) > 0
-            || self.delete_queue.any_changes()
+            || self.delete_queue().any_changes()
             || self.ticket_queue.has_tickets()
             || self.pending_changes_in_current_full_flush.read()
     }
@@ -13,9 +13,10 @@
 
 use core::codec::Codec;
 use core::index::bufferd_updates::{BufferedUpdates, FrozenBufferedUpdates};
+use core::index::NumericDocValuesUpdate;
 use core::index::Term;
 use core::search::{Query, NO_MORE_DOCS};
-use core::util::DocId;
+use core::util::{BitsRef, DocId};
 
 use error::Result;
 
@@ -133,9 +134,40 @@ impl<C: Codec> DocumentsWriterDeleteQueue<C> {
         Ok(seq_no)
     }
 
+    pub fn add_numeric_doc_values_update(&self, update: NumericDocValuesUpdate) -> Result<u64> {
+        let node = Arc::new(DeleteListNode::new(DeleteNode::NumericUpdate(update)));
+        let seq_no = self.add_node(node)?;
+        self.try_apply_global_slice()?;
+        Ok(seq_no)
+    }
+
+    /// Deletes the docs of precomputed doc id sets, each one given with the name
+    /// of the segment its doc ids belong to.
+    pub fn add_delete_doc_sets(&self, doc_sets: Vec<(String, BitsRef)>) -> Result<u64> {
+        let node = Arc::new(DeleteListNode::new(DeleteNode::DocSetArray(doc_sets)));
+        let seq_no = self.add_node(node)?;
+        self.try_apply_global_slice()?;
+        Ok(seq_no)
+    }
+
+    /// Soft deletes the docs containing any of the `terms`, see
+    /// `BufferedUpdates::add_soft_delete`.
+    pub fn add_soft_delete_terms(&self, terms: Vec<Term>, field: String) -> Result<u64> {
+        let node = Arc::new(DeleteListNode::new(DeleteNode::SoftDeleteTermArray(
+            terms, field,
+        )));
+        let seq_no = self.add_node(node)?;
+        self.try_apply_global_slice()?;
+        Ok(seq_no)
+    }
+
     /// invariant for document update
-    pub fn add_term_to_slice(&self, term: Term, slice: &mut DeleteSlice<C>) -> Result<u64> {
-        let del_node = Arc::new(DeleteListNode::new(DeleteNode::Term(term)));
+    pub fn add_term_to_slice(&self, term: DeleteTerm, slice: &mut DeleteSlice<C>) -> Result<u64> {
+        let node = match term {
+            DeleteTerm::Hard(term) => DeleteNode::Term(term),
+            DeleteTerm::Soft(term, field) => DeleteNode::SoftDeleteTermArray(vec![term], field),
+        };
+        let del_node = Arc::new(DeleteListNode::new(node));
         let seq_no = self.add_node(Arc::clone(&del_node))?;
         // this is an update request where the term is the updated documents
         // delTerm. in that case we need to guarantee that this insert is atomic
@@ -276,10 +308,21 @@ impl<C: Codec> DocumentsWriterDeleteQueue<C> {
     }
 }
 
+/// The term whose docs a document update replaces.
+pub enum DeleteTerm {
+    /// the docs are deleted
+    Hard(Term),
+    /// the docs are soft deleted through the given soft deletes field
+    Soft(Term, String),
+}
+
 enum DeleteNode<C: Codec> {
     Term(Term),
     TermArray(Vec<Term>),
+    SoftDeleteTermArray(Vec<Term>, String),
     QueryArray(Vec<Arc<dyn Query<C>>>),
+    DocSetArray(Vec<(String, BitsRef)>),
+    NumericUpdate(NumericDocValuesUpdate),
     None,
     // used for sentinel head
 }
@@ -295,11 +338,24 @@ impl<C: Codec> DeleteNode<C> {
                     buffered_deletes.add_term(t.clone(), doc_id_upto);
                 }
             }
+            DeleteNode::SoftDeleteTermArray(terms, field) => {
+                for t in terms {
+                    buffered_deletes.add_soft_delete(t.clone(), field, doc_id_upto);
+                }
+            }
             DeleteNode::QueryArray(queries) => {
                 for q in queries {
                     buffered_deletes.add_query(Arc::clone(q), doc_id_upto);
                 }
             }
+            DeleteNode::DocSetArray(doc_sets) => {
+                for (segment, docs) in doc_sets {
+                    buffered_deletes.add_doc_set(segment.clone(), Arc::clone(docs));
+                }
+            }
+            DeleteNode::NumericUpdate(update) => {
+                buffered_deletes.add_numeric_update(update.clone(), doc_id_upto);
+            }
             DeleteNode::None => {
                 unreachable!();
             }
@@ -690,6 +690,11 @@ impl FieldNumbers {
         )
     }
 
+    /// Returns true if the field exists and has the given doc values type.
+    pub fn contains(&self, field_name: &str, dv_type: DocValuesType) -> bool {
+        self.inner.lock().unwrap().contains(field_name, dv_type)
+    }
+
     pub fn clear(&self) {
         self.inner.lock().unwrap().clear();
     }
@@ -895,8 +900,6 @@ impl FieldNumbersInner {
         Ok(())
     }
 
-    // TODO used for doc values update
-    #[allow(dead_code)]
     /// return true if the field_name exists in the map and is of the type of dv_type
     fn contains(&self, field_name: &str, dv_type: DocValuesType) -> bool {
         // used by IndexWriter.updateNumericDocValue
@@ -232,23 +232,20 @@ impl<D: Directory + Send + Sync + 'static, C: Codec, MS: MergeScheduler, MP: Mer
         true
     }
 
-    pub fn do_on_delete(&mut self) {
+    pub fn do_on_delete(&self) {
         // pass None this is a global delete no update
-        unsafe {
-            let writer = self as *mut DocumentsWriterFlushControl<D, C, MS, MP>;
-            self.flush_policy.on_delete(&mut *writer, None);
-        }
+        self.flush_policy.on_delete(self, None);
     }
 
     pub fn num_global_term_deletes(&self) -> usize {
         self.documents_writer()
-            .delete_queue
+            .delete_queue()
             .num_global_term_deletes()
             + self.buffered_update_stream().num_terms()
     }
 
     pub fn delete_bytes_used(&self) -> usize {
-        self.documents_writer().delete_queue.ram_bytes_used()
+        self.documents_writer().delete_queue().ram_bytes_used()
             + self.buffered_update_stream().ram_bytes_used()
     }
 
@@ -274,6 +271,17 @@ impl<D: Directory + Send + Sync + 'static, C: Codec, MS: MergeScheduler, MP: Mer
         dwpt
     }
 
+    /// Returns true if the RAM used by the DWPTs and the buffered deletes
+    /// reached `IndexWriterConfig::ram_hard_limit`.
+    pub fn ram_hard_limit_reached(&self) -> bool {
+        match self.config.ram_hard_limit() {
+            Some(limit) => {
+                self.active_bytes + self.flush_bytes + self.delete_bytes_used() as u64 >= limit
+            }
+            None => false,
+        }
+    }
+
     fn stall_limit_bytes(&self) -> u64 {
         if self.config.flush_on_ram() {
             2 * self.config.ram_buffer_size() as u64
@@ -423,9 +431,16 @@ impl<D: Directory + Send + Sync + 'static, C: Codec, MS: MergeScheduler, MP: Mer
         // reach the limit without any ongoing flushes. we need to ensure
         // that we don't stall/block if an ongoing or pending flush can
         // not free up enough memory to release the stall lock.
-        let stall = (self.active_bytes + self.flush_bytes) > limit
+        let mut stall = (self.active_bytes + self.flush_bytes) > limit
             && self.active_bytes < limit
             && !self.closed;
+        // past the RAM hard limit we block indexing threads for as long as
+        // pending or ongoing flushes may free up some memory
+        if !stall && !self.closed && self.inited && self.ram_hard_limit_reached() {
+            stall = self.num_pending.read() > 0
+                || !self.flush_queue.is_empty()
+                || !self.flushing_writers.is_empty();
+        }
         self.stall_control.update_stalled(stall);
         stall
     }
@@ -511,7 +526,7 @@ impl<D: Directory + Send + Sync + 'static, C: Codec, MS: MergeScheduler, MP: Mer
 
             if per_thread_mut.inited()
                 && per_thread_mut.dwpt().delete_queue.generation
-                    != self.documents_writer().delete_queue.generation
+                    != self.documents_writer().delete_queue().generation
             {
                 // There is a flush-all in process and this DWPT is
                 // now stale -- enroll it for flush and try for
@@ -537,15 +552,15 @@ impl<D: Directory + Send + Sync + 'static, C: Codec, MS: MergeScheduler, MP: Mer
             // Insert a gap in seqNo of current active thread count, in the worst
             // case each of those threads now have one operation in flight.  It's fine
             // if we have some sequence numbers that were never assigned:
-            seq_no = self.documents_writer().delete_queue.last_sequence_number()
+            seq_no = self.documents_writer().delete_queue().last_sequence_number()
                 + self.per_thread_pool().active_thread_state_count() as u64
                 + 2;
             let new_queue = Arc::new(DocumentsWriterDeleteQueue::with_generation(
-                self.documents_writer().delete_queue.generation + 1,
+                self.documents_writer().delete_queue().generation + 1,
                 seq_no + 1,
             ));
 
-            flushing_queue = Arc::clone(&self.documents_writer().delete_queue);
+            flushing_queue = self.documents_writer().delete_queue();
             flushing_queue.max_seq_no.set(seq_no + 1);
             self.documents_writer().set_delete_queue(new_queue);
         }
@@ -594,13 +609,13 @@ impl<D: Directory + Send + Sync + 'static, C: Codec, MS: MergeScheduler, MP: Mer
             assert!(
                 !per_thread_mut.inited()
                     || per_thread_mut.dwpt().delete_queue.generation
-                        == self.documents_writer().delete_queue.generation
+                        == self.documents_writer().delete_queue().generation
             );
             assert!(
                 !per_thread_mut.inited()
                     || per_thread_mut.dwpt().delete_queue.as_ref()
                         as *const DocumentsWriterDeleteQueue<C>
-                        == self.documents_writer().delete_queue.as_ref()
+                        == self.documents_writer().delete_queue().as_ref()
                             as *const DocumentsWriterDeleteQueue<C>
             );
         }
@@ -611,7 +626,7 @@ impl<D: Directory + Send + Sync + 'static, C: Codec, MS: MergeScheduler, MP: Mer
         for blocked_flush in &self.blocked_flushes {
             debug_assert_eq!(
                 blocked_flush.dwpt.delete_queue.generation,
-                self.documents_writer().delete_queue.generation
+                self.documents_writer().delete_queue().generation
             );
         }
         true
@@ -626,7 +641,7 @@ impl<D: Directory + Send + Sync + 'static, C: Codec, MS: MergeScheduler, MP: Mer
         let control_mut = unsafe { self.flush_control_mut(&l) };
         if !self.blocked_flushes.is_empty() {
             debug_assert!(self.assert_blocked_flushes());
-            let gen = self.documents_writer().delete_queue.generation;
+            let gen = self.documents_writer().delete_queue().generation;
             control_mut.prune_blocked_queue(gen);
             debug_assert!(self.blocked_flushes.is_empty());
         }
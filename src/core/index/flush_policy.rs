@@ -56,7 +56,7 @@ pub(crate) trait FlushPolicy {
     /// thread holds the lock on the given `ThreadState`
     fn on_delete<D, C, MS, MP>(
         &self,
-        control: &mut DocumentsWriterFlushControl<D, C, MS, MP>,
+        control: &DocumentsWriterFlushControl<D, C, MS, MP>,
         state: Option<&ThreadState<D, C, MS, MP>>,
     ) where
         D: Directory + Send + Sync + 'static,
@@ -224,7 +224,7 @@ impl<C1: Codec, MS1: MergeScheduler, MP1: MergePolicy> FlushPolicy
 {
     fn on_delete<D, C, MS, MP>(
         &self,
-        control: &mut DocumentsWriterFlushControl<D, C, MS, MP>,
+        control: &DocumentsWriterFlushControl<D, C, MS, MP>,
         _state: Option<&ThreadState<D, C, MS, MP>>,
     ) where
         D: Directory + Send + Sync + 'static,
@@ -282,5 +282,14 @@ impl<C1: Codec, MS1: MergeScheduler, MP1: MergePolicy> FlushPolicy
                 self.mark_largest_writer_pending(control, lg, state);
             }
         }
+
+        if !state.flush_pending() && control.ram_hard_limit_reached() {
+            debug!(
+                "FP - ram hard limit reached: active_bytes={}, delete_bytes={}",
+                control.active_bytes,
+                control.delete_bytes_used()
+            );
+            self.mark_largest_writer_pending(control, lg, state);
+        }
     }
 }
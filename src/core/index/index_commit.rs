@@ -69,9 +69,37 @@ pub trait IndexCommit<D> {
     /// Map is (String -> String)
     fn user_data(&self) -> &HashMap<String, String>;
 
+    /// Returns the number of documents (including deleted ones) in this commit.
+    fn doc_count(&self) -> i32;
+
     // package-private API for IndexWriter to init from a commit-point pulled from
     // an NRT or non-NRT reader.
     //    fn reader(&self) -> Option<&StandardDirectoryReader> {
     //        None
     //    }
 }
+
+/// A snapshot of the details of one commit point, as listed by
+/// `IndexWriter::list_commits`.
+#[derive(Clone, Debug)]
+pub struct CommitPointInfo {
+    pub segments_file_name: String,
+    pub generation: i64,
+    pub user_data: HashMap<String, String>,
+    pub files: HashSet<String>,
+    pub segment_count: usize,
+    pub doc_count: i32,
+}
+
+impl CommitPointInfo {
+    pub fn new<D>(commit: &IndexCommit<D>) -> Result<CommitPointInfo> {
+        Ok(CommitPointInfo {
+            segments_file_name: commit.segments_file_name().to_string(),
+            generation: commit.generation(),
+            user_data: commit.user_data().clone(),
+            files: commit.file_names()?.clone(),
+            segment_count: commit.segment_count(),
+            doc_count: commit.doc_count(),
+        })
+    }
+}
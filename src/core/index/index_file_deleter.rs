@@ -12,8 +12,8 @@
 // limitations under the License.
 
 use core::codec::Codec;
-use core::index::delete_policy::{IndexDeletionPolicy, KeepOnlyLastCommitDeletionPolicy};
-use core::index::index_commit::IndexCommit;
+use core::index::delete_policy::{IndexDeletionPolicy, IndexDeletionPolicyEnum};
+use core::index::index_commit::{CommitPointInfo, IndexCommit};
 use core::index::index_writer::INDEX_WRITE_LOCK_NAME;
 use core::index::{
     generation_from_segments_file_name, parse_generation, parse_segment_name, SegmentInfos,
@@ -82,7 +82,7 @@ pub struct IndexFileDeleter<D: Directory, C: Codec> {
     commits_to_delete: Vec<CommitPoint<D>>,
     directory_orig: Arc<D>,
     directory: Arc<LockValidatingDirectoryWrapper<D>>,
-    policy: KeepOnlyLastCommitDeletionPolicy,
+    policy: IndexDeletionPolicyEnum,
     pub starting_commit_deleted: bool,
     last_segment_infos: Option<SegmentInfos<D, C>>,
     inited: bool,
@@ -92,7 +92,7 @@ impl<D: Directory, C: Codec> IndexFileDeleter<D, C> {
     pub fn new(
         directory_orig: Arc<D>,
         directory: Arc<LockValidatingDirectoryWrapper<D>>,
-        // policy: Box<IndexDeletionPolicy>,
+        policy: IndexDeletionPolicyEnum,
     ) -> Self {
         IndexFileDeleter {
             ref_counts: Arc::new(RwLock::new(HashMap::new())),
@@ -101,7 +101,7 @@ impl<D: Directory, C: Codec> IndexFileDeleter<D, C> {
             commits_to_delete: vec![],
             directory_orig,
             directory,
-            policy: KeepOnlyLastCommitDeletionPolicy {},
+            policy,
             starting_commit_deleted: false,
             last_segment_infos: None,
             inited: false,
@@ -372,6 +372,69 @@ impl<D: Directory, C: Codec> IndexFileDeleter<D, C> {
         }
     }
 
+    /// Returns the details of all commits currently in the index, sorted by age
+    /// (the 0th one is the oldest commit).
+    pub fn list_commits(&self) -> Result<Vec<CommitPointInfo>> {
+        let mut commits = Vec::with_capacity(self.commits.len());
+        for commit in &self.commits {
+            commits.push(CommitPointInfo::new(commit)?);
+        }
+        Ok(commits)
+    }
+
+    /// Deletes the commit with the given generation, returning false if there
+    /// is no such commit.
+    ///
+    /// Only the files that are no longer referenced by any other commit, or by
+    /// the writer's in-memory `SegmentInfos`, are removed from the directory.
+    /// The most recent commit can not be deleted.
+    pub fn delete_commit(&mut self, generation: i64) -> Result<bool> {
+        let idx = match self
+            .commits
+            .iter()
+            .position(|c| c.generation == generation)
+        {
+            Some(idx) => idx,
+            None => {
+                return Ok(false);
+            }
+        };
+        if idx == self.commits.len() - 1 {
+            bail!(ErrorKind::IllegalArgument(format!(
+                "commit with generation {} is the most recent commit and can't be deleted",
+                generation
+            )));
+        }
+        self.mark_commit_deleted(idx);
+        self.delete_commits()?;
+        Ok(true)
+    }
+
+    /// Deletes all commits except the most recent one, returning the
+    /// generations of the deleted commits.
+    ///
+    /// This is meant for deletion policies that keep old commits around, like
+    /// `KeepAllCommitsDeletionPolicy`, once the older commits are no longer needed.
+    pub fn delete_unused_commits(&mut self) -> Result<Vec<i64>> {
+        let mut deleted = vec![];
+        if self.commits.len() > 1 {
+            for idx in 0..self.commits.len() - 1 {
+                deleted.push(self.commits[idx].generation);
+                self.mark_commit_deleted(idx);
+            }
+            self.delete_commits()?;
+        }
+        Ok(deleted)
+    }
+
+    fn mark_commit_deleted(&mut self, idx: usize) {
+        if !self.commits[idx].deleted {
+            self.commits[idx].deleted = true;
+            let commit_point = self.commits[idx].clone();
+            self.commits_to_delete.push(commit_point);
+        }
+    }
+
     pub fn exists(&self, filename: &str) -> bool {
         if !self.ref_counts.read().unwrap().contains_key(filename) {
             false
@@ -637,6 +700,7 @@ struct CommitPoint<D: Directory> {
     generation: i64,
     user_data: HashMap<String, String>,
     segment_count: usize,
+    doc_count: i32,
 }
 
 impl<D: Directory> CommitPoint<D> {
@@ -652,8 +716,9 @@ impl<D: Directory> CommitPoint<D> {
             directory_orig,
             commits_to_delete,
             generation: segment_infos.generation,
-            user_data: HashMap::new(),
+            user_data: segment_infos.user_data.clone(),
             segment_count: segment_infos.len(),
+            doc_count: segment_infos.total_max_doc(),
         }
     }
 }
@@ -697,6 +762,10 @@ impl<D: Directory> IndexCommit<D> for CommitPoint<D> {
     fn user_data(&self) -> &HashMap<String, String> {
         &self.user_data
     }
+
+    fn doc_count(&self) -> i32 {
+        self.doc_count
+    }
 }
 
 impl<D: Directory> Clone for CommitPoint<D> {
@@ -710,6 +779,7 @@ impl<D: Directory> Clone for CommitPoint<D> {
             generation: self.generation,
             user_data: self.user_data.clone(),
             segment_count: self.segment_count,
+            doc_count: self.doc_count,
         }
     }
 }
@@ -0,0 +1,362 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::codec::Codec;
+use core::index::directory_reader::index_exist;
+use core::index::index_writer::IndexWriter;
+use core::index::index_writer_config::{IndexWriterConfig, OpenMode};
+use core::index::merge_policy::{MergePolicy, MergeSpecification, MergerTrigger, OneMerge};
+use core::index::merge_scheduler::MergeScheduler;
+use core::index::{SegmentCommitInfo, SegmentInfos, SegmentReader};
+use core::store::Directory;
+use core::util::BitsRef;
+
+use error::{ErrorKind::IllegalArgument, Result};
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// A `MergePolicy` whose forced merges only rewrite the segments which were
+/// not written with the codec of the writer, each one into a new segment of
+/// its own. The other merges are left to the wrapped policy.
+///
+/// The old segments are read with the codec recorded in their segment info,
+/// so their codec must still be known to `Codec::try_from`.
+pub struct UpgradeIndexMergePolicy<MP: MergePolicy> {
+    base: MP,
+}
+
+impl<MP: MergePolicy> UpgradeIndexMergePolicy<MP> {
+    pub fn new(base: MP) -> Self {
+        UpgradeIndexMergePolicy { base }
+    }
+
+    /// Returns true if the segment must be rewritten with `codec`.
+    pub fn should_upgrade_segment<D: Directory, C: Codec>(
+        info: &SegmentCommitInfo<D, C>,
+        codec: &C,
+    ) -> bool {
+        match info.info.codec {
+            Some(ref segment_codec) => segment_codec.name() != codec.name(),
+            None => true,
+        }
+    }
+}
+
+impl<MP: MergePolicy> MergePolicy for UpgradeIndexMergePolicy<MP> {
+    fn find_merges<D, C, MS, P>(
+        &self,
+        merge_trigger: MergerTrigger,
+        segment_infos: &SegmentInfos<D, C>,
+        writer: &IndexWriter<D, C, MS, P>,
+    ) -> Result<Option<MergeSpecification<D, C>>>
+    where
+        D: Directory + Send + Sync + 'static,
+        C: Codec,
+        MS: MergeScheduler,
+        P: MergePolicy,
+    {
+        self.base.find_merges(merge_trigger, segment_infos, writer)
+    }
+
+    fn find_forced_merges<D, C, MS, P>(
+        &self,
+        segment_infos: &SegmentInfos<D, C>,
+        _max_segment_count: u32,
+        segments_to_merge: &HashMap<Arc<SegmentCommitInfo<D, C>>, bool>,
+        writer: &IndexWriter<D, C, MS, P>,
+    ) -> Result<Option<MergeSpecification<D, C>>>
+    where
+        D: Directory + Send + Sync + 'static,
+        C: Codec,
+        MS: MergeScheduler,
+        P: MergePolicy,
+    {
+        let codec = writer.config().codec();
+        let merging = writer.merging_segments();
+        let mut spec = MergeSpecification::default();
+        for info in &segment_infos.segments {
+            if segments_to_merge.contains_key(info)
+                && !merging.contains(&info.info.name)
+                && Self::should_upgrade_segment(info.as_ref(), codec)
+            {
+                let merge = OneMerge::new(vec![Arc::clone(info)], writer.next_merge_id())?;
+                spec.merges.push(merge);
+            }
+        }
+
+        if spec.merges.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(spec))
+        }
+    }
+
+    fn find_forced_deletes_mergers<D, C, MS, P>(
+        &self,
+        segments_infos: &SegmentInfos<D, C>,
+        writer: &IndexWriter<D, C, MS, P>,
+    ) -> Result<Option<MergeSpecification<D, C>>>
+    where
+        D: Directory + Send + Sync + 'static,
+        C: Codec,
+        MS: MergeScheduler,
+        P: MergePolicy,
+    {
+        self.base.find_forced_deletes_mergers(segments_infos, writer)
+    }
+
+    fn max_cfs_segment_size(&self) -> u64 {
+        self.base.max_cfs_segment_size()
+    }
+
+    fn no_cfs_ratio(&self) -> f64 {
+        self.base.no_cfs_ratio()
+    }
+
+    fn retained_soft_deletes<D, C>(&self, reader: &SegmentReader<D, C>) -> Result<Option<BitsRef>>
+    where
+        D: Directory + 'static,
+        C: Codec,
+    {
+        self.base.retained_soft_deletes(reader)
+    }
+}
+
+/// Rewrites the segments of an existing index with the codec of the given
+/// config, so that the index survives format changes without reindexing.
+///
+/// The segments are read with the codec they were written with and copied
+/// into new segments by a merge, the segment infos being updated by the
+/// commit. Segments already on the current codec are left as is, so
+/// upgrading an up to date index is a no-op.
+///
+/// The index must not be opened by another writer while it is upgraded.
+pub struct IndexUpgrader<D, C, MS, MP>
+where
+    D: Directory + Send + Sync + 'static,
+    C: Codec,
+    MS: MergeScheduler,
+    MP: MergePolicy,
+{
+    directory: Arc<D>,
+    config: IndexWriterConfig<C, MS, UpgradeIndexMergePolicy<MP>>,
+}
+
+impl<D, C, MS, MP> IndexUpgrader<D, C, MS, MP>
+where
+    D: Directory + Send + Sync + 'static,
+    C: Codec,
+    MS: MergeScheduler,
+    MP: MergePolicy,
+{
+    /// Creates an upgrader rewriting the index in `directory` with `codec`,
+    /// the merges run by `merge_scheduler`, and `merge_policy` being used for
+    /// the merges which are not upgrades.
+    pub fn new(directory: Arc<D>, codec: Arc<C>, merge_scheduler: MS, merge_policy: MP) -> Self {
+        let merge_policy = UpgradeIndexMergePolicy::new(merge_policy);
+        let mut config = IndexWriterConfig::new(codec, merge_scheduler, merge_policy);
+        config.open_mode = OpenMode::Append;
+        IndexUpgrader { directory, config }
+    }
+
+    /// Rewrites the segments of the index which are not on the current codec,
+    /// and commits the new segment infos.
+    pub fn upgrade(self) -> Result<()> {
+        if !index_exist(self.directory.as_ref())? {
+            bail!(IllegalArgument("there is no index to upgrade".into()));
+        }
+
+        let writer = IndexWriter::new(self.directory, Arc::new(self.config))?;
+        let res = writer.force_merge(1, true).and_then(|_| writer.commit());
+        writer.close()?;
+        res.map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::codec::Lucene62Codec;
+    use core::doc::{Field, STORE_FIELD_TYPE};
+    use core::index::directory_reader::StandardDirectoryReader;
+    use core::index::merge_policy::TieredMergePolicy;
+    use core::index::merge_scheduler::SerialMergeScheduler;
+    use core::index::{Fieldable, IndexReader};
+    use core::store::RAMDirectory;
+    use core::util::{VariantValue, VERSION_LATEST};
+    use error::Error;
+
+    use std::convert::TryFrom;
+
+    const OLD_CODEC: &str = "TestOld";
+    const NEW_CODEC: &str = "TestNew";
+
+    // a copy of `Lucene62Codec` under another name, so that the segments
+    // written with `OLD_CODEC` must be upgraded to `NEW_CODEC`
+    struct TestCodec {
+        name: &'static str,
+        codec: Lucene62Codec,
+    }
+
+    impl TestCodec {
+        fn new(name: &'static str) -> Self {
+            TestCodec {
+                name,
+                codec: Lucene62Codec::default(),
+            }
+        }
+    }
+
+    impl TryFrom<String> for TestCodec {
+        type Error = Error;
+
+        fn try_from(value: String) -> Result<Self> {
+            match value.as_str() {
+                OLD_CODEC => Ok(TestCodec::new(OLD_CODEC)),
+                NEW_CODEC => Ok(TestCodec::new(NEW_CODEC)),
+                _ => bail!(IllegalArgument(format!("Invalid codec name: {}", value))),
+            }
+        }
+    }
+
+    impl Codec for TestCodec {
+        type FieldsProducer = <Lucene62Codec as Codec>::FieldsProducer;
+        type PostingFmt = <Lucene62Codec as Codec>::PostingFmt;
+        type DVFmt = <Lucene62Codec as Codec>::DVFmt;
+        type StoredFmt = <Lucene62Codec as Codec>::StoredFmt;
+        type TVFmt = <Lucene62Codec as Codec>::TVFmt;
+        type FieldFmt = <Lucene62Codec as Codec>::FieldFmt;
+        type SegmentFmt = <Lucene62Codec as Codec>::SegmentFmt;
+        type NormFmt = <Lucene62Codec as Codec>::NormFmt;
+        type LiveDocFmt = <Lucene62Codec as Codec>::LiveDocFmt;
+        type CompoundFmt = <Lucene62Codec as Codec>::CompoundFmt;
+        type PointFmt = <Lucene62Codec as Codec>::PointFmt;
+
+        fn name(&self) -> &str {
+            self.name
+        }
+        fn postings_format(&self) -> Self::PostingFmt {
+            self.codec.postings_format()
+        }
+        fn doc_values_format(&self) -> Self::DVFmt {
+            self.codec.doc_values_format()
+        }
+        fn stored_fields_format(&self) -> Self::StoredFmt {
+            self.codec.stored_fields_format()
+        }
+        fn term_vectors_format(&self) -> Self::TVFmt {
+            self.codec.term_vectors_format()
+        }
+        fn field_infos_format(&self) -> Self::FieldFmt {
+            self.codec.field_infos_format()
+        }
+        fn segment_info_format(&self) -> Self::SegmentFmt {
+            self.codec.segment_info_format()
+        }
+        fn norms_format(&self) -> Self::NormFmt {
+            self.codec.norms_format()
+        }
+        fn live_docs_format(&self) -> Self::LiveDocFmt {
+            self.codec.live_docs_format()
+        }
+        fn compound_format(&self) -> Self::CompoundFmt {
+            self.codec.compound_format()
+        }
+        fn points_format(&self) -> Self::PointFmt {
+            self.codec.points_format()
+        }
+    }
+
+    type TestReader =
+        StandardDirectoryReader<RAMDirectory, TestCodec, SerialMergeScheduler, TieredMergePolicy>;
+
+    fn upgrader(
+        dir: &Arc<RAMDirectory>,
+        codec: &'static str,
+    ) -> IndexUpgrader<RAMDirectory, TestCodec, SerialMergeScheduler, TieredMergePolicy> {
+        IndexUpgrader::new(
+            Arc::clone(dir),
+            Arc::new(TestCodec::new(codec)),
+            SerialMergeScheduler {},
+            TieredMergePolicy::default(),
+        )
+    }
+
+    fn segment_names(dir: &Arc<RAMDirectory>) -> Vec<String> {
+        let infos = SegmentInfos::<RAMDirectory, TestCodec>::read_latest_commit(dir).unwrap();
+        infos.segments.iter().map(|s| s.info.name.clone()).collect()
+    }
+
+    #[test]
+    fn test_upgrade() {
+        let dir = Arc::new(RAMDirectory::default());
+        // there is no index to upgrade yet
+        assert!(upgrader(&dir, NEW_CODEC).upgrade().is_err());
+
+        let config = IndexWriterConfig::new(
+            Arc::new(TestCodec::new(OLD_CODEC)),
+            SerialMergeScheduler {},
+            TieredMergePolicy::default(),
+        );
+        let writer = IndexWriter::new(Arc::clone(&dir), Arc::new(config)).unwrap();
+        // two segments of 3 docs each
+        for i in 0..6 {
+            let value = Some(VariantValue::VString(format!("doc {}", i)));
+            let doc = vec![Field::new(
+                "body".into(),
+                STORE_FIELD_TYPE.clone(),
+                value,
+                None,
+            )];
+            writer.add_document(doc).unwrap();
+            if i == 2 {
+                writer.commit().unwrap();
+            }
+        }
+        writer.close().unwrap();
+        let old_segments = segment_names(&dir);
+        assert_eq!(old_segments.len(), 2);
+
+        upgrader(&dir, NEW_CODEC).upgrade().unwrap();
+
+        // every segment was rewritten with the new codec
+        let infos = SegmentInfos::<RAMDirectory, TestCodec>::read_latest_commit(&dir).unwrap();
+        assert_eq!(infos.segments.len(), 2);
+        for info in &infos.segments {
+            assert!(!old_segments.contains(&info.info.name));
+            assert_eq!(info.info.codec().name(), NEW_CODEC);
+            assert_eq!(info.info.version, VERSION_LATEST);
+        }
+
+        // and the docs are intact
+        let reader = TestReader::open(Arc::clone(&dir)).unwrap();
+        assert_eq!(reader.num_docs(), 6);
+        let fields = vec!["body".to_string()];
+        let mut values: Vec<String> = (0..reader.max_doc())
+            .map(|doc| {
+                let doc = reader.document(doc, &fields).unwrap();
+                let value = doc.fields[0].field.fields_data().unwrap();
+                value.get_string().unwrap().to_string()
+            })
+            .collect();
+        values.sort();
+        let expected: Vec<String> = (0..6).map(|i| format!("doc {}", i)).collect();
+        assert_eq!(values, expected);
+
+        // the segments on the current codec are left as is
+        let new_segments = segment_names(&dir);
+        upgrader(&dir, NEW_CODEC).upgrade().unwrap();
+        assert_eq!(segment_names(&dir), new_segments);
+    }
+}
@@ -11,11 +11,14 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use core::codec::{Codec, CompoundFormat, FieldInfosFormat, LiveDocsFormat, SegmentInfoFormat};
+use core::codec::{checksum_entire_file, Codec, CompoundFormat, FieldInfosFormat};
+use core::codec::{DocValuesConsumer, DocValuesFormat, LiveDocsFormat, SegmentInfoFormat};
 use core::index::bufferd_updates::BufferedUpdatesStream;
 use core::index::bufferd_updates::FrozenBufferedUpdates;
 use core::index::directory_reader::index_exist;
 use core::index::doc_writer::{DocumentsWriter, Event};
+use core::index::doc_writer_delete_queue::DeleteTerm;
+use core::index::index_commit::CommitPointInfo;
 use core::index::index_file_deleter::IndexFileDeleter;
 use core::index::index_writer_config::{IndexWriterConfig, OpenMode};
 use core::index::merge_policy::{MergePolicy, MergeSpecification, MergerTrigger};
@@ -25,20 +28,24 @@ use core::index::merge_state::{DocMap, MergeState};
 use core::index::segment_merger::SegmentMerger;
 use core::index::thread_doc_writer::FlushedSegment;
 use core::index::{
-    file_name_from_generation, get_last_commit_segments_filename, FieldInfos, FieldNumbers,
-    FieldNumbersRef, Fieldable, LeafReader, SegmentCommitInfo, SegmentInfo, SegmentInfos,
-    SegmentReader, StandardDirectoryReader, Term, INDEX_FILE_PENDING_SEGMENTS,
+    file_name_from_generation, get_last_commit_segments_filename, strip_segment_name,
+    DocValuesType, FieldInfo, FieldInfos, FieldNumbers, FieldNumbersRef, Fieldable, IndexOptions,
+    IndexReader, LeafReader, NumericDocValues, NumericDocValuesRef, NumericDocValuesUpdate,
+    SearchLeafReader, SegmentCommitInfo, SegmentInfo, SegmentInfos, SegmentReader,
+    SegmentWriteState, StandardDirectoryReader, Term, INDEX_FILE_PENDING_SEGMENTS,
 };
 use core::search::match_all::MATCH_ALL;
 use core::search::Query;
 use core::store::{
-    Directory, FlushInfo, IOContext, IndexInput, Lock, LockValidatingDirectoryWrapper,
-    RateLimitIndexOutput, RateLimiter, TrackingDirectoryWrapper,
+    Directory, FlushInfo, IOContext, IOContextType, IndexInput, Lock,
+    LockValidatingDirectoryWrapper, MergeInfo, RateLimitIndexOutput, RateLimitedDirectoryWrapper,
+    RateLimiter, SimpleRateLimiter, TrackingDirectoryWrapper,
 };
 use core::util::bits::{Bits, BitsRef};
 use core::util::io::delete_file_ignoring_error;
 use core::util::string_util::random_id;
-use core::util::{to_base36, DerefWrapper, DocId, VERSION_LATEST};
+use core::util::numeric::Numeric;
+use core::util::{to_base36, DerefWrapper, DocId, ReusableIterator, VariantValue, VERSION_LATEST};
 
 use core::index::ErrorKind::MergeAborted;
 use error::ErrorKind::{AlreadyClosed, IllegalArgument, IllegalState, Index, RuntimeError};
@@ -50,10 +57,11 @@ use std::mem;
 use std::ops::Deref;
 use std::ptr;
 use std::sync::atomic::{AtomicBool, AtomicI64, AtomicU32, AtomicU64, Ordering};
-use std::sync::{Arc, Condvar, Mutex, MutexGuard, Weak};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, RwLock, Weak};
 use std::time::{Duration, SystemTime};
 
 use core::index::merge_rate_limiter::MergeRateLimiter;
+use crossbeam::thread as crossbeam_thread;
 use thread_local::ThreadLocal;
 
 /// Hard limit on maximum number of documents that may be added to the index
@@ -185,7 +193,21 @@ where
         apply_all_deletes: bool,
         write_all_deletes: bool,
     ) -> Result<StandardDirectoryReader<D, C, MS, MP>> {
-        IndexWriterInner::get_reader(self, apply_all_deletes, write_all_deletes)
+        IndexWriterInner::get_reader(self, apply_all_deletes, write_all_deletes, None)
+    }
+
+    /// Returns a new near real-time reader like `get_reader`, sharing the
+    /// segment readers of `old_reader` whose segment did not change.
+    pub(crate) fn reopen_reader(
+        &self,
+        old_reader: &StandardDirectoryReader<D, C, MS, MP>,
+    ) -> Result<StandardDirectoryReader<D, C, MS, MP>> {
+        IndexWriterInner::get_reader(
+            self,
+            old_reader.apply_all_deletes,
+            old_reader.write_all_deletes,
+            Some(old_reader),
+        )
     }
 
     #[inline]
@@ -209,6 +231,12 @@ where
         count
     }
 
+    /// Returns the number of documents indexed by this writer which had at
+    /// least one field truncated at its `FieldType::max_field_length`.
+    pub fn num_truncated_docs(&self) -> u64 {
+        self.writer.truncated_docs.load(Ordering::Acquire)
+    }
+
     #[inline]
     /// Returns the Directory used by this index.
     pub fn directory(&self) -> &Arc<D> {
@@ -288,7 +316,7 @@ where
     /// @throws CorruptIndexException if the index is corrupt
     /// @throws IOException if there is a low-level IO error
     pub fn update_document<F: Fieldable>(&self, doc: Vec<F>, term: Option<Term>) -> Result<u64> {
-        IndexWriterInner::update_document(self, doc, term)
+        IndexWriterInner::update_document(self, doc, term.map(DeleteTerm::Hard))
     }
 
     /// Atomically adds a block of documents with sequentially
@@ -333,6 +361,28 @@ where
         IndexWriterInner::update_documents(self, docs, None)
     }
 
+    /// Adds the given documents using `num_threads` concurrent indexing threads.
+    ///
+    /// The documents are split into `num_threads` contiguous partitions, each
+    /// one indexed by its own thread and therefore by its own
+    /// `DocumentsWriterPerThread`. Unlike `add_documents`, the documents are
+    /// not added as a block: their doc ids are not guaranteed to be adjacent
+    /// nor in the order of `docs`.
+    ///
+    /// If indexing a document fails, the other threads stop after their
+    /// current document and the first error is returned. Documents of other
+    /// partitions which were already added are kept.
+    ///
+    /// @return The <a href="#sequence_number">sequence number</a>
+    /// of the last operation
+    pub fn add_documents_parallel<F: Fieldable + Send>(
+        &self,
+        docs: Vec<Vec<F>>,
+        num_threads: usize,
+    ) -> Result<u64> {
+        IndexWriterInner::add_documents_parallel(self, docs, num_threads)
+    }
+
     /// Atomically deletes documents matching the provided
     /// delTerm and adds a block of documents with sequentially
     /// assigned document IDs, such that an external reader
@@ -350,7 +400,7 @@ where
         docs: Vec<Vec<F>>,
         term: Option<Term>,
     ) -> Result<u64> {
-        IndexWriterInner::update_documents(self, docs, term)
+        IndexWriterInner::update_documents(self, docs, term.map(DeleteTerm::Hard))
     }
 
     /// Deletes the document(s) containing any of the
@@ -382,6 +432,75 @@ where
         IndexWriterInner::delete_documents_by_queries(self, queries)
     }
 
+    /// Deletes the documents of precomputed doc id sets, each one given with
+    /// the name of the segment its doc ids belong to, as returned by
+    /// `LeafReader::name`. All given deletes are applied and flushed atomically
+    /// at the same time.
+    ///
+    /// The doc ids must come from a reader on the current segments, an error
+    /// naming the segment is returned if one of them isn't part of the index
+    /// anymore, e.g. because it was merged away since the reader was opened.
+    /// The deletes are applied before this returns, so that a merge can't drop
+    /// their segments in the meantime.
+    ///
+    /// @return The <a href="#sequence_number">sequence number</a>
+    /// for this operation
+    pub fn delete_documents_by_doc_sets(&self, doc_sets: Vec<(String, BitsRef)>) -> Result<u64> {
+        IndexWriterInner::delete_documents_by_doc_sets(self, doc_sets)
+    }
+
+    /// Deletes the doc `doc_id` of `reader`, a reader of this index, by
+    /// clearing its bit in the live docs of its segment. When the doc id is
+    /// already known, e.g. from a primary key lookup, this is much cheaper
+    /// than deleting by term.
+    ///
+    /// Returns false if the segment of the doc isn't part of the index
+    /// anymore, e.g. because it was merged away since `reader` was opened, or
+    /// never was, the segments being matched by name and id:
+    /// the caller should then delete the doc by term instead. Otherwise the
+    /// doc is deleted, unless it already was, and true is returned.
+    pub fn try_delete_document<R>(&self, reader: &R, doc_id: DocId) -> Result<bool>
+    where
+        R: IndexReader<Codec = C> + ?Sized,
+    {
+        self.writer.try_delete_document(reader, doc_id)
+    }
+
+    /// Updates a document by first soft deleting the document(s) containing
+    /// `term` and then adding the new document, atomically like
+    /// `update_document`. The soft deleted documents stay in the index until
+    /// they are dropped by a merge, see
+    /// `IndexWriterConfig::set_soft_deletes_field`, which must be set.
+    ///
+    /// @return The <a href="#sequence_number">sequence number</a>
+    /// for this operation
+    pub fn soft_update_document<F: Fieldable>(&self, term: Term, doc: Vec<F>) -> Result<u64> {
+        let field = IndexWriterInner::soft_deletes_field(self)?;
+        IndexWriterInner::update_document(self, doc, Some(DeleteTerm::Soft(term, field)))
+    }
+
+    /// Soft deletes the document(s) containing any of the terms, like
+    /// `delete_documents_by_terms` but keeping them in the index until they
+    /// are dropped by a merge. `IndexWriterConfig::set_soft_deletes_field`
+    /// must be set.
+    ///
+    /// @return The <a href="#sequence_number">sequence number</a>
+    /// for this operation
+    pub fn soft_delete_documents(&self, terms: Vec<Term>) -> Result<u64> {
+        IndexWriterInner::soft_delete_documents(self, terms)
+    }
+
+    /// Updates a document's `NumericDocValues` for `field` to the given `value`,
+    /// for all documents containing `term`. The field must already exist in the
+    /// index as a numeric doc values field. The update is applied like a delete
+    /// by term, in place, without reindexing the documents.
+    ///
+    /// @return The <a href="#sequence_number">sequence number</a>
+    /// for this operation
+    pub fn update_numeric_doc_value(&self, term: Term, field: &str, value: i64) -> Result<u64> {
+        IndexWriterInner::update_numeric_doc_value(self, term, field, value)
+    }
+
     /// Delete all documents in the index.
     ///
     /// This method will drop all buffered documents and will remove all segments
@@ -445,7 +564,14 @@ where
     /// In general, once this completes, the total size of the
     /// index will be less than the size of the starting index.
     /// It could be quite a bit smaller (if there were many
-    /// pending deletes) or just slightly smaller.
+    /// pending deletes) or just slightly smaller. The segments
+    /// with deletes are rewritten even if there already are at
+    /// most `max_num_segments` segments.
+    ///
+    /// When `do_wait` is true, the call returns once the merges
+    /// are done, and fails if the writer is closed meanwhile.
+    /// The writer lock is released while waiting, so the other
+    /// threads keep indexing.
     ///
     /// If an Exception is hit, for example
     /// due to disk full, the index will not be corrupted and no
@@ -473,6 +599,59 @@ where
         IndexWriterInner::force_merge(self, max_num_segments, do_wait)
     }
 
+    /// Merges the segments with too many deleted docs, so that the space they
+    /// take is reclaimed. The segments to merge are selected by
+    /// `MergePolicy::find_forced_deletes_mergers`, for `TieredMergePolicy`
+    /// the ones whose percentage of deleted docs exceeds
+    /// `set_force_merge_deletes_pct_allowed`.
+    ///
+    /// This is often a costly operation, since the selected segments are
+    /// entirely rewritten, it's usually enough to let the merges selected
+    /// by the `MergePolicy` reclaim the deletes over time.
+    ///
+    /// If `do_wait` is true, the call blocks until the selected merges are
+    /// done, and fails if one of them hit an error or if the writer is
+    /// closed meanwhile. Otherwise the `MergeScheduler` may run them in the
+    /// background.
+    pub fn force_merge_deletes(&self, do_wait: bool) -> Result<()> {
+        IndexWriterInner::force_merge_deletes(self, do_wait)
+    }
+
+    /// Adds the committed segments of the indexes in `dirs` to this index,
+    /// copying their files under new segment names without re-analyzing the
+    /// docs. The write locks of `dirs` are held meanwhile, so no writer may
+    /// change them.
+    ///
+    /// The segments must be written with the codec of this writer, whose
+    /// headers are checked on the copies, and sorted like this index if it
+    /// has an index sort. The copied segments are only published once all of
+    /// them are copied: on error none of them is visible and their files are
+    /// deleted.
+    ///
+    /// The buffered docs and deletes are flushed first, and the deletes
+    /// buffered later don't apply to the added docs.
+    pub fn add_indexes<DI>(&self, dirs: &[Arc<DI>]) -> Result<()>
+    where
+        DI: Directory + Send + Sync + 'static,
+    {
+        IndexWriterInner::add_indexes(self, dirs)
+    }
+
+    /// Merges the live docs of `readers` into a single new segment of this
+    /// index. The deleted docs of the readers are dropped, and the readers
+    /// may be any leaf readers of the codec of this writer, e.g. the
+    /// segment readers of any index, including this one.
+    ///
+    /// Unlike `add_indexes`, which copies the segment files as is, the docs
+    /// are re-encoded like in a merge: this is slower, but the new segment is
+    /// written with the codec of this writer and sorted by its index sort.
+    ///
+    /// The buffered docs and deletes are flushed first, and the deletes
+    /// buffered later don't apply to the added docs.
+    pub fn add_indexes_from_readers(&self, readers: &[Arc<SearchLeafReader<C>>]) -> Result<()> {
+        IndexWriterInner::add_indexes_from_readers(self, readers)
+    }
+
     /// Returns true if there may be changes that have not been
     /// committed.  There are cases where this may return true
     /// when there are no actual "real" changes to the index,
@@ -486,6 +665,28 @@ where
         self.writer.has_uncommitted_changes()
     }
 
+    /// Prepares a commit, the first phase of a two-phase commit: the buffered
+    /// docs and deletes are flushed, the files are synced and a pending
+    /// segments file holding the live commit data is written, but it doesn't
+    /// become the active commit point until `commit` is called. `rollback`
+    /// drops it instead.
+    ///
+    /// No other commit may be prepared meanwhile. If the process crashes
+    /// before `commit`, the index is opened on the previous commit and the
+    /// pending segments file is deleted.
+    ///
+    /// @return The <a href="#sequence_number">sequence number</a>
+    /// of the last operation in the commit, or -1 if there were no changes
+    /// to commit
+    pub fn prepare_commit(&self) -> Result<i64> {
+        IndexWriterInner::prepare_commit(self)
+    }
+
+    /// Commits all changes to the index, finishing the commit prepared by
+    /// `prepare_commit` if there is one.
+    ///
+    /// @return The <a href="#sequence_number">sequence number</a>
+    /// of the last operation in the commit
     pub fn commit(&self) -> Result<i64> {
         IndexWriterInner::commit(self)
     }
@@ -497,6 +698,49 @@ where
     pub fn tragedy(&self) -> Option<&Error> {
         self.writer.tragedy.as_ref()
     }
+
+    /// Sets the commit user data map, which will be stored with the next commit
+    /// and reported for it by `#list_commits`.
+    pub fn set_live_commit_data(&self, user_data: HashMap<String, String>) -> Result<()> {
+        self.writer.set_live_commit_data(user_data)
+    }
+
+    /// Returns the commit user data map which will be stored with the next
+    /// commit.
+    pub fn live_commit_data(&self) -> Result<HashMap<String, String>> {
+        self.writer.live_commit_data()
+    }
+
+    /// Returns the commit user data map of the commit prepared by
+    /// `prepare_commit`, or None if no commit is pending.
+    pub fn pending_commit_data(&self) -> Result<Option<HashMap<String, String>>> {
+        self.writer.pending_commit_data()
+    }
+
+    /// Returns the details (generation, user data, files and doc count) of all
+    /// commit points currently in the index, sorted by age (the 0th one is the
+    /// oldest commit).
+    ///
+    /// Unless the configured `IndexDeletionPolicy` keeps older commits around,
+    /// only the most recent commit is listed.
+    pub fn list_commits(&self) -> Result<Vec<CommitPointInfo>> {
+        self.writer.list_commits()
+    }
+
+    /// Deletes the commit point with the given generation, returning false if
+    /// there is no such commit. The most recent commit can not be deleted.
+    ///
+    /// Files shared with other commits, or still used by this writer or by
+    /// open NRT readers, are kept until they are no longer referenced.
+    pub fn delete_commit(&self, generation: i64) -> Result<bool> {
+        self.writer.delete_commit(generation)
+    }
+
+    /// Deletes all commit points except the most recent one, returning the
+    /// generations of the deleted commits.
+    pub fn delete_unused_commits(&self) -> Result<Vec<i64>> {
+        self.writer.delete_unused_commits()
+    }
 }
 
 // crate public field accesser
@@ -656,7 +900,10 @@ pub(crate) struct IndexWriterInner<
     directory_orig: Arc<D>,
     // wrapped with additional checks
     directory: Arc<LockValidatingDirectoryWrapper<D>>,
-    merge_directory: RateLimitFilterDirectory<LockValidatingDirectoryWrapper<D>, MergeRateLimiter>,
+    merge_directory: RateLimitFilterDirectory<
+        RateLimitedDirectoryWrapper<LockValidatingDirectoryWrapper<D>>,
+        MergeRateLimiter,
+    >,
     change_count: AtomicU64,
     // last change_count that was committed
     last_commit_change_count: AtomicU64,
@@ -668,6 +915,10 @@ pub(crate) struct IndexWriterInner<
     pending_seq_no: AtomicI64,
     pending_commit_change_count: AtomicU64,
     files_to_commit: HashSet<String>,
+    // files whose checksum doesn't need to be verified on commit, either
+    // because it already was or because they were there when the writer was
+    // opened, see `IndexWriterConfig::verify_checksums_on_commit`
+    verified_files: HashSet<String>,
 
     segment_infos: SegmentInfos<D, C>,
     segment_infos_lock: Mutex<()>,
@@ -696,6 +947,8 @@ pub(crate) struct IndexWriterInner<
 
     flush_count: AtomicU32,
     flush_deletes_count: AtomicU32,
+    // number of docs which had a field cut at its `max_field_length`
+    truncated_docs: AtomicU64,
     reader_pool: ReaderPool<D, C, MS, MP>,
     updates_stream_lock: Mutex<()>,
     buffered_updates_stream: BufferedUpdatesStream<C>,
@@ -785,10 +1038,21 @@ where
 
         let rate_limiters = Arc::new(ThreadLocal::default());
 
+        // Throttles all the merges together, if configured:
+        let merge_rate_limiter = match conf.merge_rate_limiter {
+            Some(ref rate_limiter) => Arc::clone(rate_limiter),
+            None => Arc::new(SimpleRateLimiter::unlimited()),
+        };
+        let rate_limited_directory = Arc::new(RateLimitedDirectoryWrapper::new(
+            Arc::clone(&directory),
+            IOContextType::Merge,
+            merge_rate_limiter,
+        ));
+
         // Directory we use for merging, so we can abort running merges, and so
         // merge schedulers can optionally rate-limit per-merge IO:
         let merge_directory =
-            RateLimitFilterDirectory::new(Arc::clone(&directory), Arc::clone(&rate_limiters));
+            RateLimitFilterDirectory::new(rate_limited_directory, Arc::clone(&rate_limiters));
 
         let buffered_updates_stream = BufferedUpdatesStream::default();
         let pool_readers = conf.reader_pooling;
@@ -879,7 +1143,7 @@ where
         let mut deleter = IndexFileDeleter::new(
             Arc::clone(&d),
             Arc::clone(&directory),
-            // conf.index_deletion_policy(),
+            conf.index_deletion_policy(),
         );
         deleter.init(&files, &mut segment_infos, initial_index_exists, false)?;
 
@@ -894,6 +1158,8 @@ where
             segment_infos.changed();
         }
 
+        let verified_files = segment_infos.files(false);
+
         Ok(IndexWriterInner {
             lock: Arc::new(Mutex::new(())),
             cond: Condvar::new(),
@@ -907,6 +1173,7 @@ where
             pending_seq_no: AtomicI64::new(0),
             pending_commit_change_count: AtomicU64::new(0),
             files_to_commit: HashSet::new(),
+            verified_files,
             segment_infos,
             segment_infos_lock: Mutex::new(()),
             global_field_numbers,
@@ -927,6 +1194,7 @@ where
             stop_merges: false,
             flush_count: AtomicU32::new(0),
             flush_deletes_count: AtomicU32::new(0),
+            truncated_docs: AtomicU64::new(0),
             reader_pool: ReaderPool::new(),
             updates_stream_lock: Mutex::new(()),
             buffered_updates_stream,
@@ -951,6 +1219,7 @@ where
         index_writer: &IndexWriter<D, C, MS, MP>,
         apply_all_deletes: bool,
         write_all_deletes: bool,
+        old_reader: Option<&StandardDirectoryReader<D, C, MS, MP>>,
     ) -> Result<StandardDirectoryReader<D, C, MS, MP>> {
         index_writer.writer.ensure_open(true)?;
 
@@ -981,6 +1250,7 @@ where
             index_writer,
             apply_all_deletes,
             write_all_deletes,
+            old_reader,
             &mut any_changes,
         )?;
         {
@@ -1004,6 +1274,7 @@ where
         index_writer: &IndexWriter<D, C, MS, MP>,
         apply_all_deletes: bool,
         write_all_deletes: bool,
+        old_reader: Option<&StandardDirectoryReader<D, C, MS, MP>>,
         any_changes: &mut bool,
     ) -> Result<StandardDirectoryReader<D, C, MS, MP>> {
         let _l = index_writer.writer.full_flush_lock.lock()?;
@@ -1012,6 +1283,7 @@ where
             index_writer,
             apply_all_deletes,
             write_all_deletes,
+            old_reader,
             any_changes,
         );
         // Done: finish the full flush!
@@ -1033,6 +1305,7 @@ where
         index_writer: &IndexWriter<D, C, MS, MP>,
         apply_all_deletes: bool,
         write_all_deletes: bool,
+        old_reader: Option<&StandardDirectoryReader<D, C, MS, MP>>,
         any_changes: &mut bool,
     ) -> Result<StandardDirectoryReader<D, C, MS, MP>> {
         let (changes, _) = index_writer.writer.doc_writer.flush_all_threads()?;
@@ -1067,6 +1340,7 @@ where
                 &index_writer.writer.segment_infos,
                 apply_all_deletes,
                 write_all_deletes,
+                old_reader,
             )?;
             debug!(
                 "IW - return reader version: {}, reader: {:?} ",
@@ -1105,6 +1379,10 @@ where
         self.merge_id_gen.fetch_add(1, Ordering::AcqRel)
     }
 
+    pub(crate) fn inc_truncated_docs(&self) {
+        self.truncated_docs.fetch_add(1, Ordering::AcqRel);
+    }
+
     #[inline]
     fn pool_readers(&self) -> bool {
         self.pool_readers.load(Ordering::Acquire)
@@ -1405,60 +1683,70 @@ where
 
     fn delete_all(index_writer: &IndexWriter<D, C, MS, MP>) -> Result<u64> {
         index_writer.writer.ensure_open(true)?;
-        let seq_no: u64;
+        let l = index_writer.writer.full_flush_lock.lock()?;
+        // TODO this did not locks the rlds, may cause error?
+        let res = Self::delete_all_segments(index_writer, &l);
+        // new documents must be accepted again even if the truncation failed,
+        // otherwise the indexing threads would wait forever for a thread state
+        index_writer.writer.doc_writer.unlock_all_after_abort_all(&l);
+        res
+    }
+
+    /// Aborts the in-RAM segments and drops all the segments of the index,
+    /// `l` is the full flush lock guard.
+    fn delete_all_segments(
+        index_writer: &IndexWriter<D, C, MS, MP>,
+        l: &MutexGuard<()>,
+    ) -> Result<u64> {
+        let aborted_doc_count = index_writer.writer.doc_writer.lock_and_abort_all(l)?;
+        index_writer
+            .writer
+            .pending_num_docs
+            .fetch_sub(aborted_doc_count as i64, Ordering::AcqRel);
+
+        Self::process_events(index_writer, false, true)?;
+
         {
-            let l = index_writer.writer.full_flush_lock.lock()?;
-            // TODO this did not locks the rlds, may cause error?
-            let aborted_doc_count = index_writer.writer.doc_writer.lock_and_abort_all(&l)?;
+            let mut _gl = index_writer.writer.lock.lock()?;
+            let writer_mut = unsafe { index_writer.writer.writer_mut(&_gl) };
+            // Abort any running merges
+            let _gl = writer_mut.abort_merges(_gl)?;
+            // Let merges run again
+            writer_mut.stop_merges = false;
+            // Remove all segments
+            index_writer.writer.pending_num_docs.fetch_sub(
+                index_writer.writer.segment_infos.total_max_doc() as i64,
+                Ordering::AcqRel,
+            );
+            writer_mut.segment_infos.clear();
+            // Ask deleter to locate unreferenced files & remove them:
+            writer_mut
+                .deleter
+                .checkpoint(&index_writer.writer.segment_infos, false)?;
+            // don't refresh the deleter here since there might
+            // be concurrent indexing requests coming in opening
+            // files on the directory after we called DW#abort()
+            // if we do so these indexing requests might hit FNF exceptions.
+            // We will remove the files incrementally as we go...
+
+            // don't bother saving any changes in our segment_infos
+            index_writer.writer.reader_pool.drop_all(false)?;
+            // Mask that the index has changes
             index_writer
                 .writer
-                .pending_num_docs
-                .fetch_sub(aborted_doc_count as i64, Ordering::AcqRel);
-
-            Self::process_events(index_writer, false, true)?;
-
-            {
-                let mut _gl = index_writer.writer.lock.lock()?;
-                let writer_mut = unsafe { index_writer.writer.writer_mut(&_gl) };
-                // Abort any running merges
-                let _gl = writer_mut.abort_merges(_gl)?;
-                // Let merges run again
-                writer_mut.stop_merges = false;
-                // Remove all segments
-                index_writer.writer.pending_num_docs.fetch_sub(
-                    index_writer.writer.segment_infos.total_max_doc() as i64,
-                    Ordering::AcqRel,
-                );
-                writer_mut.segment_infos.clear();
-                // Ask deleter to locate unreferenced files & remove them:
-                writer_mut
-                    .deleter
-                    .checkpoint(&index_writer.writer.segment_infos, false)?;
-                // don't refresh the deleter here since there might
-                // be concurrent indexing requests coming in opening
-                // files on the directory after we called DW#abort()
-                // if we do so these indexing requests might hit FNF exceptions.
-                // We will remove the files incrementally as we go...
-
-                // don't bother saving any changes in our segment_infos
-                index_writer.writer.reader_pool.drop_all(false)?;
-                // Mask that the index has changes
-                index_writer
-                    .writer
-                    .change_count
-                    .fetch_add(1, Ordering::AcqRel);
-                writer_mut.segment_infos.changed();
-                index_writer.writer.global_field_numbers.clear();
+                .change_count
+                .fetch_add(1, Ordering::AcqRel);
+            writer_mut.segment_infos.changed();
+            index_writer.writer.global_field_numbers.clear();
 
-                seq_no = index_writer
-                    .writer
-                    .doc_writer
-                    .delete_queue
-                    .next_sequence_number();
-                writer_mut.doc_writer.last_seq_no = seq_no;
-            }
+            let seq_no = index_writer
+                .writer
+                .doc_writer
+                .delete_queue()
+                .next_sequence_number();
+            index_writer.writer.doc_writer.set_last_seq_no(seq_no);
+            Ok(seq_no)
         }
-        Ok(seq_no)
     }
 
     /// Called whenever the SegmentInfos has been updated and the index files
@@ -1655,6 +1943,52 @@ where
         self.deleter.dec_ref_by_segment(segment_infos)
     }
 
+    fn set_live_commit_data(&self, user_data: HashMap<String, String>) -> Result<()> {
+        let l = self.lock.lock()?;
+        self.ensure_open(true)?;
+        let writer = unsafe { self.writer_mut(&l) };
+        writer.segment_infos.user_data = user_data;
+        writer.changed(&l);
+        Ok(())
+    }
+
+    fn live_commit_data(&self) -> Result<HashMap<String, String>> {
+        let _l = self.lock.lock()?;
+        Ok(self.segment_infos.user_data.clone())
+    }
+
+    fn pending_commit_data(&self) -> Result<Option<HashMap<String, String>>> {
+        let _l = self.lock.lock()?;
+        Ok(self.pending_commit.as_ref().map(|c| c.user_data.clone()))
+    }
+
+    fn list_commits(&self) -> Result<Vec<CommitPointInfo>> {
+        let _l = self.commit_lock.lock()?;
+        self.ensure_open(false)?;
+        self.deleter.list_commits()
+    }
+
+    fn delete_commit(&self, generation: i64) -> Result<bool> {
+        // hold the commit lock so that a concurrent commit can't run the
+        // deletion policy while we are deleting commits, and the writer lock
+        // as the deleter is also checkpointed by flushes and merges
+        let _cl = self.commit_lock.lock()?;
+        let lock = Arc::clone(&self.lock);
+        let l = lock.lock()?;
+        self.ensure_open(false)?;
+        let writer = unsafe { self.writer_mut(&l) };
+        writer.deleter.delete_commit(generation)
+    }
+
+    fn delete_unused_commits(&self) -> Result<Vec<i64>> {
+        let _cl = self.commit_lock.lock()?;
+        let lock = Arc::clone(&self.lock);
+        let l = lock.lock()?;
+        self.ensure_open(false)?;
+        let writer = unsafe { self.writer_mut(&l) };
+        writer.deleter.delete_unused_commits()
+    }
+
     fn process_events(
         index_writer: &IndexWriter<D, C, MS, MP>,
         trigger_merge: bool,
@@ -1805,6 +2139,25 @@ where
         Ok(seq_no)
     }
 
+    fn prepare_commit(index_writer: &IndexWriter<D, C, MS, MP>) -> Result<i64> {
+        index_writer.writer.ensure_open(true)?;
+
+        let mut do_maybe_merge = false;
+        let seq_no = {
+            let l = index_writer.writer.commit_lock.lock()?;
+            let writer = unsafe { index_writer.writer.writer_mut(&l) };
+            let seq_no = writer.prepare_commit_internal(&mut do_maybe_merge, index_writer, &l)?;
+            writer.pending_seq_no.store(seq_no, Ordering::Release);
+            seq_no
+        };
+
+        // we must do this outside of the commit lock else we can deadlock:
+        if do_maybe_merge {
+            Self::maybe_merge(index_writer, MergerTrigger::FullFlush, None)?;
+        }
+        Ok(seq_no)
+    }
+
     // _l is self.commit_lock
     fn prepare_commit_internal(
         &mut self,
@@ -1919,9 +2272,6 @@ where
             self.segment_infos.changed();
         }
 
-        // TODO currently not support SegmentInfos#user_data
-        // if self.commit_user_data.is_some() {}
-
         // Must clone the segmentInfos while we still
         // hold fullFlushLock and while sync'd so that
         // no partial changes (eg a delete w/o
@@ -2033,7 +2383,12 @@ where
         }
 
         let files_to_sync: HashSet<String> = self.pending_commit.as_ref().unwrap().files(false);
-        if let Err(e) = self.directory.sync(&files_to_sync) {
+        let res = if self.config.verify_checksums_on_commit {
+            self.verify_checksums(&files_to_sync)
+        } else {
+            Ok(())
+        };
+        if let Err(e) = res.and_then(|_| self.directory.sync(&files_to_sync)) {
             *pending_commit_set = false;
             self.pending_commit
                 .as_mut()
@@ -2047,6 +2402,27 @@ where
         Ok(())
     }
 
+    /// Re-reads the files of the pending commit which were written since the
+    /// writer was opened and checks their footer checksum, so that corruptions
+    /// happening on write are caught before the commit instead of at read time.
+    fn verify_checksums(&mut self, files: &HashSet<String>) -> Result<()> {
+        for file in files {
+            if self.verified_files.contains(file) {
+                continue;
+            }
+            let input = self.directory.open_input(file, &IOContext::READ_ONCE)?;
+            if let Err(e) = checksum_entire_file(input.as_ref()) {
+                error!("IW - checksum verification of '{}' failed: {:?}", file, e);
+                return Err(e);
+            }
+            self.verified_files.insert(file.clone());
+        }
+        // forget the files which are not referenced anymore
+        self.verified_files.retain(|f| files.contains(f));
+        debug!("IW - verified checksums of all files to commit");
+        Ok(())
+    }
+
     fn finish_commit(&mut self) -> Result<()> {
         let mut commit_completed = false;
 
@@ -2286,7 +2662,7 @@ where
     fn update_documents<F: Fieldable>(
         index_writer: &IndexWriter<D, C, MS, MP>,
         docs: Vec<Vec<F>>,
-        term: Option<Term>,
+        term: Option<DeleteTerm>,
     ) -> Result<u64> {
         index_writer.writer.ensure_open(true)?;
 
@@ -2314,6 +2690,123 @@ where
         Ok(seq_no)
     }
 
+    fn delete_documents_by_doc_sets(
+        index_writer: &IndexWriter<D, C, MS, MP>,
+        doc_sets: Vec<(String, BitsRef)>,
+    ) -> Result<u64> {
+        index_writer.writer.ensure_open(true)?;
+        {
+            let _l = index_writer.writer.lock.lock()?;
+            let segments = &index_writer.writer.segment_infos.segments;
+            for (segment, _) in &doc_sets {
+                if !segments.iter().any(|info| &info.info.name == segment) {
+                    bail!(IllegalArgument(format!(
+                        "segment '{}' of the doc set is not part of the index anymore",
+                        segment
+                    )));
+                }
+            }
+        }
+
+        let (seq_no, changed) = index_writer.writer.doc_writer.delete_doc_sets(doc_sets)?;
+        if changed {
+            Self::process_events(index_writer, true, false)?;
+        }
+        Ok(seq_no)
+    }
+
+    fn try_delete_document<R>(&self, reader: &R, doc_id: DocId) -> Result<bool>
+    where
+        R: IndexReader<Codec = C> + ?Sized,
+    {
+        self.ensure_open(true)?;
+        let leaves = reader.leaves();
+        let leaf = match leaves.iter().rev().find(|leaf| leaf.doc_base <= doc_id) {
+            Some(leaf) if doc_id >= 0 && doc_id - leaf.doc_base < leaf.reader.max_doc() => leaf,
+            _ => bail!(IllegalArgument(format!(
+                "doc_id {} invalid: [max_doc={}]",
+                doc_id,
+                reader.max_doc()
+            ))),
+        };
+        let seg_doc = doc_id - leaf.doc_base;
+
+        let l = self.lock.lock()?;
+        let info = match self
+            .segment_infos
+            .segments
+            .iter()
+            .find(|info| {
+                info.info.name == leaf.reader.name()
+                    && leaf.reader.segment_id() == Some(&info.info.id[..])
+            }) {
+            Some(info) => Arc::clone(info),
+            None => return Ok(false),
+        };
+
+        // Lock order: IW -> BD -> RP
+        let _bl = self.updates_stream_lock.lock()?;
+        let rld = self.reader_pool.get_or_create(&info)?;
+        let res = rld.create_reader_if_not_exist(&IOContext::READ).and_then(|_| {
+            let mut inner = rld.inner.lock()?;
+            inner.init_writable_live_docs(&info)?;
+            inner.delete(seg_doc)
+        });
+        let full_del_count = info.del_count() + rld.pending_delete_count() as i32;
+        let released = self.reader_pool.release(&rld, true);
+        let deleted = res?;
+        released?;
+
+        if deleted {
+            let writer_mut = unsafe { self.writer_mut(&l) };
+            if full_del_count == info.info.max_doc()
+                && !self.keep_fully_deleted_segments
+                && !self.merging_segments.contains(&info.info.name)
+            {
+                writer_mut.segment_infos.remove(&info);
+                self.pending_num_docs
+                    .fetch_sub(i64::from(info.info.max_doc()), Ordering::AcqRel);
+                self.reader_pool.drop(&info)?;
+                writer_mut.check_point(&l)?;
+            } else {
+                writer_mut.changed(&l);
+            }
+        }
+        Ok(true)
+    }
+
+    fn soft_delete_documents(
+        index_writer: &IndexWriter<D, C, MS, MP>,
+        terms: Vec<Term>,
+    ) -> Result<u64> {
+        let field = Self::soft_deletes_field(index_writer)?;
+        index_writer.writer.ensure_open(true)?;
+
+        let (seq_no, changed) = index_writer
+            .writer
+            .doc_writer
+            .soft_delete_terms(terms, field)?;
+        if changed {
+            Self::process_events(index_writer, true, false)?;
+        }
+        Ok(seq_no)
+    }
+
+    /// Returns the configured soft deletes field, registering it as a numeric
+    /// doc values field so that the segments which do not have it yet can be
+    /// updated.
+    fn soft_deletes_field(index_writer: &IndexWriter<D, C, MS, MP>) -> Result<String> {
+        let field = match index_writer.writer.config.soft_deletes_field() {
+            Some(field) => field,
+            None => bail!(IllegalState("soft deletes field is not configured".into())),
+        };
+        index_writer
+            .writer
+            .global_field_numbers
+            .add_or_get(field, 0, DocValuesType::Numeric, 0, 0)?;
+        Ok(field.to_string())
+    }
+
     /// Deletes the document(s) matching any of the provided queries.
     /// All given deletes are applied and flushed atomically at the same time.
     ///
@@ -2344,10 +2837,129 @@ where
         Ok(seq_no)
     }
 
+    fn update_numeric_doc_value(
+        index_writer: &IndexWriter<D, C, MS, MP>,
+        term: Term,
+        field: &str,
+        value: i64,
+    ) -> Result<u64> {
+        index_writer.writer.ensure_open(true)?;
+        if !index_writer
+            .writer
+            .global_field_numbers
+            .contains(field, DocValuesType::Numeric)
+        {
+            bail!(IllegalArgument(format!(
+                "can only update existing numeric-docvalues fields: '{}'",
+                field
+            )));
+        }
+        if let Some(sort) = index_writer.writer.config.index_sort() {
+            if sort.get_sort().iter().any(|sf| sf.field() == field) {
+                bail!(IllegalArgument(format!(
+                    "cannot update doc values field '{}' the index is sorted by",
+                    field
+                )));
+            }
+        }
+
+        let update =
+            NumericDocValuesUpdate::new(term, field.to_string(), VariantValue::Long(value));
+        let (seq_no, changed) = index_writer
+            .writer
+            .doc_writer
+            .update_numeric_doc_value(update)?;
+        if changed {
+            Self::process_events(index_writer, true, false)?;
+        }
+        Ok(seq_no)
+    }
+
+    fn add_documents_parallel<F: Fieldable + Send>(
+        index_writer: &IndexWriter<D, C, MS, MP>,
+        docs: Vec<Vec<F>>,
+        num_threads: usize,
+    ) -> Result<u64> {
+        if num_threads == 0 {
+            bail!(IllegalArgument("num_threads must be greater than 0".into()));
+        }
+        index_writer.writer.ensure_open(true)?;
+        if docs.is_empty() {
+            return Ok(index_writer
+                .writer
+                .doc_writer
+                .delete_queue()
+                .last_sequence_number());
+        }
+
+        // split the docs into contiguous partitions, one per thread
+        let partition_size = (docs.len() + num_threads - 1) / num_threads;
+        let mut partitions = Vec::with_capacity(num_threads);
+        let mut docs = docs;
+        while !docs.is_empty() {
+            let rest = docs.split_off(partition_size.min(docs.len()));
+            partitions.push(docs);
+            docs = rest;
+        }
+
+        let aborted = AtomicBool::new(false);
+        let results = crossbeam_thread::scope(|s| {
+            let handles: Vec<_> = partitions
+                .into_iter()
+                .map(|partition| {
+                    let aborted = &aborted;
+                    s.spawn(move |_| -> Result<u64> {
+                        let mut seq_no = 0;
+                        for doc in partition {
+                            if aborted.load(Ordering::Acquire) {
+                                break;
+                            }
+                            match Self::update_document(index_writer, doc, None) {
+                                Ok(n) => seq_no = seq_no.max(n),
+                                Err(e) => {
+                                    aborted.store(true, Ordering::Release);
+                                    return Err(e);
+                                }
+                            }
+                        }
+                        Ok(seq_no)
+                    })
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join()).collect::<Vec<_>>()
+        });
+        let results = match results {
+            Ok(r) => r,
+            Err(e) => bail!(IllegalState(format!("indexing thread panicked: {:?}", e))),
+        };
+
+        // sequence numbers are assigned by the delete queue, so the max over
+        // the partitions is the sequence number of the last added document
+        let mut seq_no = 0;
+        let mut first_err = None;
+        for res in results {
+            let err = match res {
+                Ok(Ok(n)) => {
+                    seq_no = seq_no.max(n);
+                    continue;
+                }
+                Ok(Err(e)) => e,
+                Err(e) => IllegalState(format!("indexing thread panicked: {:?}", e)).into(),
+            };
+            if first_err.is_none() {
+                first_err = Some(err);
+            }
+        }
+        if let Some(e) = first_err {
+            return Err(e);
+        }
+        Ok(seq_no)
+    }
+
     fn update_document<F: Fieldable>(
         index_writer: &IndexWriter<D, C, MS, MP>,
         doc: Vec<F>,
-        term: Option<Term>,
+        term: Option<DeleteTerm>,
     ) -> Result<u64> {
         index_writer.writer.ensure_open(true)?;
         let (seq_no, changed) = index_writer.writer.doc_writer.update_document(doc, term)?;
@@ -2554,13 +3166,441 @@ where
         Ok(())
     }
 
-    /// Returns true if any merges in pendingMerges or
-    /// runningMerges are maxNumSegments merges.
-    fn max_num_segments_merges_pending(&self, _lock: &MutexGuard<()>) -> bool {
-        self.pending_merges
-            .iter()
-            .any(|m| m.max_num_segments.get().is_some())
-            || self
+    /// Merges the segments selected by the merge policy to expunge their
+    /// deleted docs.
+    fn force_merge_deletes(index_writer: &IndexWriter<D, C, MS, MP>, do_wait: bool) -> Result<()> {
+        index_writer.writer.ensure_open(true)?;
+
+        trace!("IW - force_merge_deletes: flush at force merge deletes");
+
+        Self::flush(index_writer, true, true)?;
+        let mut merge_ids = HashSet::new();
+        {
+            let l = index_writer.writer.lock.lock()?;
+            let writer_mut = unsafe { index_writer.writer.writer_mut(&l) };
+            let spec = writer_mut
+                .config
+                .merge_policy()
+                .find_forced_deletes_mergers(&writer_mut.segment_infos, index_writer)?;
+            if let Some(spec) = spec {
+                for merge in spec.merges {
+                    let id = merge.id;
+                    if writer_mut.register_merge(merge, &l)? {
+                        merge_ids.insert(id);
+                    }
+                }
+            }
+        }
+        index_writer.writer.merge_scheduler.merge(
+            index_writer,
+            MergerTrigger::Explicit,
+            !merge_ids.is_empty(),
+        )?;
+
+        if do_wait && !merge_ids.is_empty() {
+            let mut l = index_writer.writer.lock.lock()?;
+            loop {
+                if let Some(ref tragedy) = index_writer.writer.tragedy {
+                    bail!(IllegalState(format!(
+                        "this writer hit an unrecoverable error; cannot complete \
+                         forceMergeDeletes: {:?}",
+                        tragedy
+                    )));
+                }
+                // Forward any exceptions of our merges in background merge
+                // threads to the current thread:
+                for merge in &index_writer.writer.merge_exceptions {
+                    if merge_ids.contains(&merge.id) {
+                        bail!(RuntimeError("background merge hit exception".into()));
+                    }
+                }
+
+                let running = index_writer
+                    .writer
+                    .pending_merges
+                    .iter()
+                    .any(|m| merge_ids.contains(&m.id))
+                    || index_writer
+                        .writer
+                        .running_merges
+                        .keys()
+                        .any(|id| merge_ids.contains(id));
+                if running {
+                    let (guard, _) = index_writer
+                        .writer
+                        .cond
+                        .wait_timeout(l, Duration::from_millis(1000))?;
+                    l = guard;
+                } else {
+                    break;
+                }
+            }
+
+            // If close is called while we are still
+            // running, throw an exception so the calling
+            // thread will know merging did not complete
+            index_writer.writer.ensure_open(true)?;
+        }
+        Ok(())
+    }
+
+    fn add_indexes<DI: Directory + Send + Sync + 'static>(
+        index_writer: &IndexWriter<D, C, MS, MP>,
+        dirs: &[Arc<DI>],
+    ) -> Result<()> {
+        index_writer.writer.ensure_open(true)?;
+
+        for (i, dir) in dirs.iter().enumerate() {
+            let addr = dir.as_ref() as *const DI as *const u8;
+            if addr == index_writer.writer.directory_orig.as_ref() as *const D as *const u8 {
+                bail!(IllegalArgument("cannot add directory to itself".into()));
+            }
+            if dirs[..i].iter().any(|d| Arc::ptr_eq(d, dir)) {
+                bail!(IllegalArgument(format!(
+                    "directory {} appears more than once",
+                    dir
+                )));
+            }
+        }
+
+        let mut locks = Vec::with_capacity(dirs.len());
+        let mut res = Ok(());
+        for dir in dirs {
+            match dir.obtain_lock(INDEX_WRITE_LOCK_NAME) {
+                Ok(lock) => locks.push(lock),
+                Err(e) => {
+                    res = Err(e);
+                    break;
+                }
+            }
+        }
+        if res.is_ok() {
+            res = Self::do_add_indexes(index_writer, dirs);
+        }
+        for lock in &locks {
+            if let Err(e) = lock.close() {
+                warn!("IW - add_indexes: release write lock failed by '{:?}'", e);
+            }
+        }
+        res?;
+
+        Self::maybe_merge(index_writer, MergerTrigger::Explicit, None)
+    }
+
+    fn do_add_indexes<DI: Directory + Send + Sync + 'static>(
+        index_writer: &IndexWriter<D, C, MS, MP>,
+        dirs: &[Arc<DI>],
+    ) -> Result<()> {
+        let writer = &index_writer.writer;
+        Self::flush(index_writer, false, true)?;
+
+        let mut commits = Vec::with_capacity(dirs.len());
+        let mut total_max_doc = 0i64;
+        for dir in dirs {
+            // reading the commit checks the header of each segment info
+            let sis: SegmentInfos<DI, C> = SegmentInfos::read_latest_commit(dir)?;
+            for info in &sis.segments {
+                let codec_name = info.info.codec().name();
+                if codec_name != writer.config.codec.name() {
+                    bail!(IllegalArgument(format!(
+                        "cannot add segment {} of {}: codec {} doesn't match {}",
+                        info.info.name,
+                        dir,
+                        codec_name,
+                        writer.config.codec.name()
+                    )));
+                }
+                if let Some(index_sort) = writer.config.index_sort() {
+                    if info.info.index_sort() != Some(index_sort) {
+                        bail!(IllegalArgument(format!(
+                            "cannot add segment {} of {}: index sort {:?} doesn't match {:?}",
+                            info.info.name,
+                            dir,
+                            info.info.index_sort(),
+                            index_sort
+                        )));
+                    }
+                }
+                total_max_doc += i64::from(info.info.max_doc());
+            }
+            commits.push(sis);
+        }
+        if writer.pending_num_docs.load(Ordering::Acquire) + total_max_doc
+            > i64::from(INDEX_MAX_DOCS)
+        {
+            bail!(IllegalArgument(format!(
+                "number of documents in the index cannot exceed {}",
+                INDEX_MAX_DOCS
+            )));
+        }
+
+        let mut infos = Vec::new();
+        let mut copied_files = HashSet::new();
+        for sis in &commits {
+            for info in &sis.segments {
+                let res = writer.copy_segment_as_is(info.as_ref(), &mut copied_files);
+                match res {
+                    Ok(new_info) => infos.push(new_info),
+                    Err(e) => {
+                        // Safe: these files must exist
+                        let _ = writer.delete_new_files(&copied_files);
+                        return Err(e);
+                    }
+                }
+            }
+        }
+
+        let l = writer.lock.lock()?;
+        // Now reserve the docs, just before we update SIS:
+        let res = writer.ensure_open(true).and_then(|_| {
+            let num_docs = writer
+                .pending_num_docs
+                .fetch_add(total_max_doc, Ordering::AcqRel);
+            if num_docs + total_max_doc > i64::from(INDEX_MAX_DOCS) {
+                writer
+                    .pending_num_docs
+                    .fetch_sub(total_max_doc, Ordering::AcqRel);
+                bail!(IllegalArgument(format!(
+                    "number of documents in the index cannot exceed {}",
+                    INDEX_MAX_DOCS
+                )));
+            }
+            Ok(())
+        });
+        if let Err(e) = res {
+            let _ = writer.delete_new_files(&copied_files);
+            return Err(e);
+        }
+
+        // the deletes buffered so far don't apply to the new segments
+        let next_gen = {
+            let _bl = writer.updates_stream_lock.lock()?;
+            writer.buffered_updates_stream.get_next_gen()
+        };
+        let writer_mut = unsafe { writer.writer_mut(&l) };
+        for info in infos {
+            info.set_buffered_deletes_gen(next_gen as i64);
+            writer_mut.segment_infos.add(Arc::new(info));
+        }
+        writer_mut.check_point(&l)
+    }
+
+    /// Copies the files of `info` to this index under a new segment name,
+    /// recording them in `copied_files`, and registers its fields.
+    fn copy_segment_as_is<DI: Directory + Send + Sync + 'static>(
+        &self,
+        info: &SegmentCommitInfo<DI, C>,
+        copied_files: &mut HashSet<String>,
+    ) -> Result<SegmentCommitInfo<D, C>> {
+        let fis = read_field_infos(info)?;
+        for fi in fis.by_number.values() {
+            self.global_field_numbers.add_or_get(
+                &fi.name,
+                fi.number,
+                fi.doc_values_type,
+                fi.point_dimension_count,
+                fi.point_num_bytes,
+            )?;
+        }
+
+        let seg_name = self.new_segment_name();
+        let named_for_this_segment =
+            |file: &String| format!("{}{}", seg_name, strip_segment_name(file));
+        let context = IOContext::Flush(FlushInfo::new(
+            info.info.max_doc() as u32,
+            info.size_in_bytes() as u64,
+        ));
+        // Copy the segment's files
+        for file in info.files() {
+            let new_file_name = named_for_this_segment(&file);
+            self.directory.copy_from(
+                Arc::clone(&info.info.directory),
+                &file,
+                &new_file_name,
+                &context,
+            )?;
+            copied_files.insert(new_file_name);
+        }
+
+        // Same segment info as before, but with the new name and directory:
+        // reading the copy with this writer's codec checks its header
+        let mut new_info: SegmentInfo<D, C> = self.config.codec.segment_info_format().read(
+            &self.directory_orig,
+            &seg_name,
+            info.info.id,
+            &context,
+        )?;
+        new_info.set_codec(Arc::clone(&self.config.codec));
+        let field_infos_files = info
+            .field_infos_files()
+            .iter()
+            .map(&named_for_this_segment)
+            .collect();
+        let dv_updates_files = info
+            .dv_updates_files()
+            .into_iter()
+            .map(|(field, files)| (field, files.iter().map(&named_for_this_segment).collect()))
+            .collect();
+        let new_info = SegmentCommitInfo::new(
+            new_info,
+            info.del_count(),
+            info.del_gen(),
+            info.field_infos_gen(),
+            info.doc_values_gen(),
+            dv_updates_files,
+            field_infos_files,
+        );
+        Ok(new_info)
+    }
+
+    fn add_indexes_from_readers(
+        index_writer: &IndexWriter<D, C, MS, MP>,
+        readers: &[Arc<SearchLeafReader<C>>],
+    ) -> Result<()> {
+        let writer = &index_writer.writer;
+        writer.ensure_open(true)?;
+        Self::flush(index_writer, false, true)?;
+
+        let num_docs: i64 = readers.iter().map(|r| i64::from(r.num_docs())).sum();
+        if writer.pending_num_docs.load(Ordering::Acquire) + num_docs > i64::from(INDEX_MAX_DOCS) {
+            bail!(IllegalArgument(format!(
+                "number of documents in the index cannot exceed {}",
+                INDEX_MAX_DOCS
+            )));
+        }
+
+        let context = IOContext::Merge(MergeInfo::new(num_docs as u32, 0, false, None));
+        let dir_wrapper = Arc::new(TrackingDirectoryWrapper::new(Arc::clone(&writer.directory)));
+        let mut si = SegmentInfo::new(
+            VERSION_LATEST.clone(),
+            &writer.new_segment_name(),
+            -1,
+            Arc::clone(&writer.directory_orig),
+            false,
+            Some(Arc::clone(&writer.config.codec)),
+            HashMap::new(),
+            random_id(),
+            HashMap::new(),
+            writer.config.index_sort().map(Clone::clone),
+        )?;
+        let mut details = HashMap::new();
+        details.insert("source".into(), "addIndexes(readers)".into());
+        si.set_diagnostics(details);
+        let mut info = Arc::new(SegmentCommitInfo::new(
+            si,
+            0,
+            -1,
+            -1,
+            -1,
+            HashMap::new(),
+            HashSet::new(),
+        ));
+
+        {
+            let mut merger = SegmentMerger::new(
+                readers.to_vec(),
+                &info.info,
+                Arc::clone(&dir_wrapper),
+                FieldNumbersRef::new(Arc::clone(&writer.global_field_numbers)),
+                context,
+            )?;
+            if !merger.should_merge() {
+                return Ok(());
+            }
+            if let Err(e) = merger.merge() {
+                let _ = writer.delete_new_files(&dir_wrapper.create_files());
+                return Err(e);
+            }
+            merger
+                .merge_state
+                .segment_info()
+                .set_files(&dir_wrapper.create_files())?;
+        }
+
+        let use_compound_file = {
+            let _l = writer.lock.lock()?;
+            if writer.stop_merges {
+                // rollback is in progress
+                writer.delete_new_files(&info.files())?;
+                return Ok(());
+            }
+            writer
+                .config
+                .merge_policy()
+                .use_compound_file(&writer.segment_infos, info.as_ref(), index_writer)
+        };
+
+        let segment_info = Arc::get_mut(&mut info).unwrap();
+        if use_compound_file {
+            let files_to_remove = segment_info.files();
+            let tracking_cfs_dir = TrackingDirectoryWrapper::new(writer.directory.as_ref());
+            let res =
+                writer.create_compound_file(&tracking_cfs_dir, &mut segment_info.info, &context);
+            {
+                let _l = writer.lock.lock()?;
+                // the non cfs files were never registered with IFD
+                writer.delete_new_files(&files_to_remove)?;
+                if let Err(e) = res {
+                    writer.delete_new_files(&segment_info.files())?;
+                    return Err(e);
+                }
+            }
+            segment_info.info.set_use_compound_file();
+        }
+
+        // Have codec write SegmentInfo.  Must do this after creating CFS so
+        // that 1) .si isn't slurped into CFS, and 2) .si reflects
+        // useCompoundFile=true change above:
+        if let Err(e) = writer.config.codec().segment_info_format().write(
+            &writer.directory,
+            &mut segment_info.info,
+            &context,
+        ) {
+            writer.delete_new_files(&segment_info.files())?;
+            return Err(e);
+        }
+
+        {
+            let l = writer.lock.lock()?;
+            // Now reserve the docs, just before we update SIS:
+            let res = writer.ensure_open(true).and_then(|_| {
+                let max_doc = i64::from(segment_info.info.max_doc());
+                let pending = writer.pending_num_docs.fetch_add(max_doc, Ordering::AcqRel);
+                if pending + max_doc > i64::from(INDEX_MAX_DOCS) {
+                    writer.pending_num_docs.fetch_sub(max_doc, Ordering::AcqRel);
+                    bail!(IllegalArgument(format!(
+                        "number of documents in the index cannot exceed {}",
+                        INDEX_MAX_DOCS
+                    )));
+                }
+                Ok(())
+            });
+            if let Err(e) = res {
+                writer.delete_new_files(&segment_info.files())?;
+                return Err(e);
+            }
+
+            // the deletes buffered so far don't apply to the new segment
+            let next_gen = {
+                let _bl = writer.updates_stream_lock.lock()?;
+                writer.buffered_updates_stream.get_next_gen()
+            };
+            segment_info.set_buffered_deletes_gen(next_gen as i64);
+            let writer_mut = unsafe { writer.writer_mut(&l) };
+            writer_mut.segment_infos.add(Arc::clone(&info));
+            writer_mut.check_point(&l)?;
+        }
+
+        Self::maybe_merge(index_writer, MergerTrigger::Explicit, None)
+    }
+
+    /// Returns true if any merges in pendingMerges or
+    /// runningMerges are maxNumSegments merges.
+    fn max_num_segments_merges_pending(&self, _lock: &MutexGuard<()>) -> bool {
+        self.pending_merges
+            .iter()
+            .any(|m| m.max_num_segments.get().is_some())
+            || self
                 .running_merges
                 .values()
                 .any(|m| m.max_num_segments.get().is_some())
@@ -2665,9 +3705,10 @@ where
             writer_mut.merge_finish(&l, merge);
             if res.is_err() {
                 trace!("IW - hit error during merge");
-            } else if !merge.rate_limiter.aborted() && merge.max_num_segments.get().is_some()
-                || (!index_writer.writer.closed.load(Ordering::Acquire)
-                    && !index_writer.writer.closing.load(Ordering::Acquire))
+            } else if !merge.rate_limiter.aborted()
+                && (merge.max_num_segments.get().is_some()
+                    || (!index_writer.writer.closed.load(Ordering::Acquire)
+                        && !index_writer.writer.closing.load(Ordering::Acquire)))
             {
                 // This merge (and, generally, any change to the
                 // segments) may now enable new merges, so we call
@@ -2842,6 +3883,7 @@ where
             index_writer.writer.merge_directory.clone(),
         )));
         merge.readers = Vec::with_capacity(merge.segments.len());
+        merge.hard_live_docs = Vec::with_capacity(merge.segments.len());
 
         // This is try/finally to make sure merger's readers are closed:
         let mut seg_upto = 0;
@@ -2898,14 +3940,32 @@ where
                 reader = Arc::new(new_reader);
             }
 
+            // The soft deleted docs are dropped by the merge, but the ones
+            // the merge policy retains
+            let hard_live_docs = reader.live_docs();
+            if let Some(field) = index_writer.writer.config.soft_deletes_field() {
+                let retained = index_writer
+                    .writer
+                    .config
+                    .merge_policy()
+                    .retained_soft_deletes(reader.as_ref())?;
+                if let Some(r) = reader.with_soft_deletes(field, retained.as_ref())? {
+                    reader = Arc::new(r);
+                }
+            }
+
+            merge.hard_live_docs.push(hard_live_docs);
             merge.readers.push(reader);
             debug_assert!(del_count <= merge.segments[seg_upto].info.max_doc);
             seg_upto += 1;
         }
 
         // Let the merge wrap readers
-        let merge_readers: Vec<Arc<SegmentReader<D, C>>> =
-            merge.readers.iter().map(Arc::clone).collect();
+        let merge_readers: Vec<Arc<SearchLeafReader<C>>> = merge
+            .readers
+            .iter()
+            .map(|r| Arc::clone(r) as Arc<SearchLeafReader<C>>)
+            .collect();
         let mut merger = SegmentMerger::new(
             merge_readers,
             &merge.info.as_ref().unwrap().info,
@@ -2913,6 +3973,7 @@ where
             FieldNumbersRef::new(Arc::clone(&index_writer.writer.global_field_numbers)),
             context,
         )?;
+        merger.merge_state.check_postings_order = index_writer.writer.config.check_merged_postings;
         merge.rate_limiter.check_abort()?;
         merge.merge_start_time.write(Some(SystemTime::now()));
 
@@ -3051,12 +4112,16 @@ where
 
         // lazy init (only when we find a delete to carry over):
         let mut holder = MergedDeletesAndUpdates::default();
+        // numeric DV updates applied while merging, mapped to the merged doc ids
+        let mut merged_numeric_updates: HashMap<String, HashMap<DocId, i64>> = HashMap::new();
         debug_assert_eq!(merge.segments.len(), merge_state.doc_maps.len());
         for i in 0..merge.segments.len() {
             let info = &merge.segments[i];
             min_gen = min_gen.min(info.buffered_deletes_gen());
             let max_doc = info.info.max_doc;
-            let prev_live_docs = merge.readers[i].live_docs();
+            let prev_live_docs = Arc::clone(&merge.hard_live_docs[i]);
+            // the docs the merge copied, which may exclude soft deleted docs
+            let merged_live_docs = merge.readers[i].live_docs();
             let rld = self.reader_pool.get(info.as_ref()).unwrap();
             let inner = rld.inner.lock()?;
 
@@ -3086,7 +4151,7 @@ where
                         if !prev_live_docs.get(j)? {
                             // if the document was deleted before, it better still be deleted.
                             debug_assert!(!cur_live_doc.get(j).unwrap());
-                        } else if !cur_live_doc.get(j)? {
+                        } else if !cur_live_doc.get(j)? && merged_live_docs.get(j)? {
                             // the document was deleted while we are merging:
                             if holder.merged_deletes_and_updates.is_none()
                                 || !holder.inited_writable_live_docs
@@ -3108,7 +4173,7 @@ where
                 debug_assert_eq!(current_live_docs.len(), max_doc as usize);
                 // This segment had no deletes before but now it does:
                 for j in 0..max_doc {
-                    if !current_live_docs.get(j as usize)? {
+                    if !current_live_docs.get(j as usize)? && merged_live_docs.get(j as usize)? {
                         if holder.merged_deletes_and_updates.is_none()
                             || !holder.inited_writable_live_docs
                         {
@@ -3124,6 +4189,29 @@ where
                     }
                 }
             }
+
+            for (field, field_updates) in &inner.merging_numeric_updates {
+                let merged_field_updates = merged_numeric_updates
+                    .entry(field.clone())
+                    .or_insert_with(HashMap::new);
+                for (&doc, &value) in field_updates {
+                    let doc_id =
+                        merge_state.doc_maps[i].get(merge_state.leaf_doc_maps[i].get(doc)?)?;
+                    // the docs deleted before the merge started are gone
+                    if doc_id != -1 {
+                        merged_field_updates.insert(doc_id, value);
+                    }
+                }
+            }
+        }
+
+        if !merged_numeric_updates.is_empty() {
+            holder.init(&self.reader_pool, merge, false)?;
+            holder
+                .merged_deletes_and_updates
+                .as_ref()
+                .unwrap()
+                .write_field_updates(merged_numeric_updates)?;
         }
 
         merge
@@ -3299,6 +4387,7 @@ where
             // merge.readers[i] = None;
         }
         merge.readers.clear();
+        merge.hard_live_docs.clear();
 
         // merge.merge_finished();
 
@@ -3315,10 +4404,9 @@ where
     ) -> Result<()> {
         if drop {
             rld.drop_changes();
+        } else {
+            rld.drop_merging_updates();
         }
-        //            else {
-        //                rld.drop_merging_updates();
-        //            }
         // rld.release(reader);
         self.reader_pool.release(&rld, true)?;
         if drop {
@@ -3751,6 +4839,13 @@ where
         guard.init_writable_live_docs(&self.info)
     }
 
+    /// Writes the numeric doc values updates, key'd by field and doc id, as new
+    /// doc values generations of the segment and reopens the reader on them.
+    pub fn write_field_updates(&self, updates: HashMap<String, HashMap<DocId, i64>>) -> Result<()> {
+        let mut guard = self.inner.lock()?;
+        guard.write_field_updates(&self.info, updates)
+    }
+
     // Commit live docs (writes new _X_N.del files) and field updates (writes new
     // _X_N updates files) to the directory; returns true if it wrote any file
     // and false if there were no new deletes or updates to write:
@@ -3802,6 +4897,11 @@ where
         guard.drop_merging_updates();
     }
 
+    pub fn drop_merging_updates(&self) {
+        let mut guard = self.inner.lock().unwrap();
+        guard.drop_merging_updates();
+    }
+
     /// Returns a reader for merge. this method applies filed update if there are
     /// any and marks that this segment is currently merging.
     pub fn reader_for_merge(&self, context: &IOContext) -> Result<Arc<SegmentReader<D, C>>> {
@@ -3824,8 +4924,8 @@ where
     MS: MergeScheduler,
     MP: MergePolicy,
 {
-    // used for process DocValues update which is not currently implemented
-    _writer: Weak<IndexWriterInner<D, C, MS, MP>>,
+    // used to number the fields which are first added by a DocValues update
+    writer: Weak<IndexWriterInner<D, C, MS, MP>>,
     // Set once (null, and then maybe set, and never set again):
     reader: Option<Arc<SegmentReader<D, C>>>,
     // Holds the current shared (readable and writable)
@@ -3846,7 +4946,7 @@ where
     // That way, when the segment is done merging, IndexWriter can apply the
     // updates on the merged segment too.
     is_merging: bool,
-    // merging_dv_updates: HashMap<String, DocValuesFieldUpdates>,
+    merging_numeric_updates: HashMap<String, HashMap<DocId, i64>>,
 }
 
 impl<D, C, MS, MP> ReadersAndUpdatesInner<D, C, MS, MP>
@@ -3858,12 +4958,13 @@ where
 {
     fn new(writer: Weak<IndexWriterInner<D, C, MS, MP>>) -> Self {
         ReadersAndUpdatesInner {
-            _writer: writer,
+            writer,
             reader: None,
             live_docs: None,
             pending_delete_count: 0,
             live_docs_shared: true,
             is_merging: false,
+            merging_numeric_updates: HashMap::new(),
         }
     }
 
@@ -3876,12 +4977,13 @@ where
         let pending_delete_count = reader.num_deleted_docs();
         debug_assert!(pending_delete_count >= 0);
         ReadersAndUpdatesInner {
-            _writer: writer,
+            writer,
             reader: Some(Arc::new(reader)),
             live_docs: Some(live_docs),
             pending_delete_count: pending_delete_count as u32,
             live_docs_shared: true,
             is_merging: false,
+            merging_numeric_updates: HashMap::new(),
         }
     }
 
@@ -4065,12 +5167,10 @@ where
     }
 
     fn drop_merging_updates(&mut self) {
-        // self.merging_dv_updates.clear();
+        self.merging_numeric_updates.clear();
         self.is_merging = false;
     }
 
-    // TODO used for doc values update
-    #[allow(dead_code)]
     fn write_field_infos_gen<F: FieldInfosFormat>(
         &self,
         info: &Arc<SegmentCommitInfo<D, C>>,
@@ -4090,20 +5190,207 @@ where
         ));
         // separately also track which files were created for this gen
         let tracking_dir = TrackingDirectoryWrapper::new(dir.as_ref());
-        infos_format.write(
+        let res = infos_format.write(
             &tracking_dir,
             &info.info,
             &segment_suffix,
             field_infos,
             &infos_context,
-        )?;
-        info.advance_del_gen();
+        );
+        if let Err(e) = res {
+            // so that a 2nd attempt to write will write to a new file
+            info.advance_next_write_field_infos_gen();
+            for file_name in &tracking_dir.get_create_files() {
+                delete_file_ignoring_error(&tracking_dir, file_name);
+            }
+            return Err(e);
+        }
+        info.advance_field_infos_gen();
         Ok(tracking_dir.get_create_files())
     }
 
     // Writes field updates (new _X_N updates files) to the directory
-    pub fn write_field_updates<DW: Directory>(&self, _dir: &DW) -> Result<()> {
-        unreachable!()
+    pub fn write_field_updates(
+        &mut self,
+        info: &Arc<SegmentCommitInfo<D, C>>,
+        updates: HashMap<String, HashMap<DocId, i64>>,
+    ) -> Result<()> {
+        if updates.is_empty() {
+            return Ok(());
+        }
+        let writer = match self.writer.upgrade() {
+            Some(writer) => writer,
+            None => bail!(AlreadyClosed("this IndexWriter is closed".into())),
+        };
+        self.create_reader_if_not_exist(info, &IOContext::READ)?;
+        let reader = Arc::clone(self.reader());
+
+        // the new field infos gen is a copy of the current one, the fields
+        // first added by an update being numbered by the writer
+        let mut infos: Vec<FieldInfo> = Vec::with_capacity(reader.field_infos.len());
+        for fi in reader.field_infos.by_number.values() {
+            let mut fi = fi.as_ref().clone();
+            let attributes = fi.attributes.read().unwrap().clone();
+            fi.attributes = Arc::new(RwLock::new(attributes));
+            infos.push(fi);
+        }
+        for field in updates.keys() {
+            if let Some(fi) = infos.iter().find(|fi| &fi.name == field) {
+                if fi.doc_values_type != DocValuesType::Numeric {
+                    bail!(IllegalArgument(format!(
+                        "field '{}' is not a numeric doc values field",
+                        field
+                    )));
+                }
+                continue;
+            }
+            let number = writer
+                .global_field_numbers
+                .add_or_get(field, 0, DocValuesType::Numeric, 0, 0)?;
+            infos.push(FieldInfo::new(
+                field.clone(),
+                number,
+                false,
+                false,
+                false,
+                IndexOptions::Null,
+                DocValuesType::Numeric,
+                -1,
+                HashMap::new(),
+                0,
+                0,
+            )?);
+        }
+
+        // write a new doc values gen for each updated field
+        let directory = Arc::clone(&info.info.directory);
+        let max_doc = info.info.max_doc();
+        let mut new_dv_files = HashMap::with_capacity(updates.len());
+        for (field, field_updates) in &updates {
+            let fi = infos.iter_mut().find(|fi| &fi.name == field).unwrap();
+            let gen = info.next_write_doc_values_gen();
+            fi.dv_gen = gen;
+
+            let values = if reader.field_infos.field_info_by_name(field).is_some() {
+                Some((
+                    reader.get_numeric_doc_values(field)?,
+                    reader.get_docs_with_field(field)?,
+                ))
+            } else {
+                None
+            };
+            let mut iter = NumericUpdatesIterator {
+                values,
+                updates: field_updates,
+                doc: 0,
+                max_doc,
+            };
+
+            let tracking_dir = Arc::new(TrackingDirectoryWrapper::new(Arc::clone(&directory)));
+            let est_updates_size = 8 * max_doc as u64;
+            let state = SegmentWriteState::new(
+                Arc::clone(&tracking_dir),
+                info.info.clone(),
+                FieldInfos::new(vec![fi.clone()])?,
+                None,
+                IOContext::Flush(FlushInfo::new(max_doc as u32, est_updates_size)),
+                to_base36(gen as u64),
+            );
+            let res = info
+                .info
+                .codec()
+                .doc_values_format()
+                .fields_consumer(&state)
+                .and_then(|mut consumer| consumer.add_numeric_field(fi, &mut iter));
+            if let Err(e) = res {
+                // so that a 2nd attempt to write will write to a new file
+                info.advance_next_write_doc_values_gen();
+                for file_name in &tracking_dir.get_create_files() {
+                    delete_file_ignoring_error(tracking_dir.as_ref(), file_name);
+                }
+                return Err(e);
+            }
+            info.advance_doc_values_gen();
+            new_dv_files.insert(fi.number as i32, tracking_dir.get_create_files());
+        }
+
+        let field_infos = FieldInfos::new(infos)?;
+        let field_infos_format = info.info.codec().field_infos_format();
+        let field_infos_files =
+            self.write_field_infos_gen(info, &field_infos, &directory, &field_infos_format)?;
+
+        // the fields which were not updated now keep their previous gen files
+        let mut dv_updates_files = info.dv_updates_files();
+        dv_updates_files.extend(new_dv_files);
+        info.set_dv_updates_files(dv_updates_files);
+        info.set_field_infos_files(field_infos_files);
+
+        if self.is_merging {
+            // the updates must also be applied to the merged segment
+            for (field, field_updates) in updates {
+                self.merging_numeric_updates
+                    .entry(field)
+                    .or_insert_with(HashMap::new)
+                    .extend(field_updates);
+            }
+        }
+
+        // reopen the reader on the new field infos and doc values gens, sharing
+        // the live docs with it
+        self.live_docs_shared = true;
+        let live_docs = Arc::clone(self.live_docs());
+        let num_docs = max_doc - info.del_count() - self.pending_delete_count as i32;
+        let new_reader = SegmentReader::build_from(
+            Arc::clone(info),
+            reader.as_ref(),
+            live_docs,
+            num_docs,
+            false,
+        )?;
+        self.reader = Some(Arc::new(new_reader));
+        Ok(())
+    }
+}
+
+/// Iterates the numeric doc values of a field over all the docs of a segment,
+/// the updated docs returning their new value.
+struct NumericUpdatesIterator<'a> {
+    values: Option<(NumericDocValuesRef, BitsRef)>,
+    updates: &'a HashMap<DocId, i64>,
+    doc: DocId,
+    max_doc: DocId,
+}
+
+impl<'a> NumericUpdatesIterator<'a> {
+    fn value(&self, doc: DocId) -> Result<Numeric> {
+        if let Some(&value) = self.updates.get(&doc) {
+            return Ok(Numeric::Long(value));
+        }
+        if let Some((ref values, ref docs_with_field)) = self.values {
+            if docs_with_field.get(doc as usize)? {
+                return Ok(Numeric::Long(values.get(doc)?));
+            }
+        }
+        Ok(Numeric::Null)
+    }
+}
+
+impl<'a> Iterator for NumericUpdatesIterator<'a> {
+    type Item = Result<Numeric>;
+
+    fn next(&mut self) -> Option<Result<Numeric>> {
+        if self.doc >= self.max_doc {
+            return None;
+        }
+        let value = self.value(self.doc);
+        self.doc += 1;
+        Some(value)
+    }
+}
+
+impl<'a> ReusableIterator for NumericUpdatesIterator<'a> {
+    fn reset(&mut self) {
+        self.doc = 0;
     }
 }
 
@@ -4257,3 +5544,1144 @@ where
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::analysis::whitespace_tokenizer::WhitespaceTokenizer;
+    use core::analysis::TokenStream;
+    use core::doc::{Field, FieldType, NumericDocValuesField, NUMERIC_DOC_VALUES_FIELD_TYPE};
+    use core::codec::{CodecEnum, Lucene62Codec};
+    use core::index::merge_policy::{
+        LogByteSizeMergePolicy, NoMergePolicy, SoftDeletesRetentionMergePolicy, TieredMergePolicy,
+    };
+    use core::index::merge_scheduler::SerialMergeScheduler;
+    use core::index::{
+        IndexDeletionPolicyEnum, IndexOptions, IndexReader, KeepAllCommitsDeletionPolicy,
+    };
+    use core::search::searcher::{DefaultIndexSearcher, IndexSearcher};
+    use core::search::sort::Sort;
+    use core::search::sort_field::{SimpleSortField, SortField, SortFieldType};
+    use core::search::term_query::TermQuery;
+    use core::store::{DataInput, DataOutput, RAMDirectory};
+    use core::util::bit_set::{BitSet, FixedBitSet};
+
+    use std::io::Cursor;
+    use std::thread;
+
+    // the type of the untokenized "id" field
+    fn id_type() -> FieldType {
+        let mut id_type = FieldType::default();
+        id_type.tokenized = false;
+        id_type.omit_norms = true;
+        id_type.index_options = IndexOptions::Docs;
+        id_type
+    }
+
+    // a doc with the single field "id"
+    fn id_doc<T: ToString>(id: T) -> Vec<Field> {
+        let id = VariantValue::VString(id.to_string());
+        vec![Field::new("id".into(), id_type(), Some(id), None)]
+    }
+
+    fn id_term(id: &str) -> Term {
+        Term::new("id".into(), id.as_bytes().to_vec())
+    }
+
+    // a writer over a new RAM directory
+    fn open_writer<C: Codec, MS: MergeScheduler, MP: MergePolicy>(
+        config: IndexWriterConfig<C, MS, MP>,
+    ) -> IndexWriter<RAMDirectory, C, MS, MP> {
+        IndexWriter::new(Arc::new(RAMDirectory::default()), Arc::new(config)).unwrap()
+    }
+
+    // adds the id docs 0 to `num_docs - 1`, committing a segment of each
+    // `segment_docs` of them
+    fn add_id_docs<C: Codec, MS: MergeScheduler, MP: MergePolicy>(
+        writer: &IndexWriter<RAMDirectory, C, MS, MP>,
+        num_docs: usize,
+        segment_docs: usize,
+    ) {
+        for i in 0..num_docs {
+            writer.add_document(id_doc(i)).unwrap();
+            if i % segment_docs == segment_docs - 1 {
+                writer.commit().unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn test_concurrent_adds_and_deletes() {
+        let dir = Arc::new(RAMDirectory::default());
+        let writer = IndexWriter::new(dir, Arc::new(IndexWriterConfig::default())).unwrap();
+
+        let handles: Vec<_> = (0..8)
+            .map(|i| {
+                let writer = writer.clone();
+                thread::spawn(move || {
+                    let mut seq_nos = Vec::with_capacity(400);
+                    for j in 0..200 {
+                        let doc = vec![NumericDocValuesField::new("id", i * 1000 + j)];
+                        seq_nos.push(writer.add_document(doc).unwrap());
+                        let term = Term::new("id".into(), format!("{}", j).into_bytes());
+                        seq_nos.push(writer.delete_documents_by_terms(vec![term]).unwrap());
+                    }
+                    seq_nos
+                })
+            })
+            .collect();
+
+        let mut seq_nos = HashSet::new();
+        let mut max_seq_no = 0;
+        for handle in handles {
+            for seq_no in handle.join().unwrap() {
+                assert!(seq_nos.insert(seq_no));
+                max_seq_no = max_seq_no.max(seq_no);
+            }
+        }
+        assert_eq!(seq_nos.len(), 8 * 400);
+        // the last op of each thread is a delete
+        assert_eq!(writer.writer.doc_writer.last_seq_no(), max_seq_no);
+
+        writer.rollback().unwrap();
+    }
+
+    #[test]
+    fn test_ram_hard_limit() {
+        let dir = Arc::new(RAMDirectory::default());
+        let mut config = IndexWriterConfig::default();
+        // only the hard limit may trigger a flush
+        config.set_ram_buffer_size(-1.0);
+        config.set_ram_hard_limit_mb(0.05);
+        let writer = IndexWriter::new(dir, Arc::new(config)).unwrap();
+
+        let handles: Vec<_> = (0..4)
+            .map(|i| {
+                let writer = writer.clone();
+                thread::spawn(move || {
+                    for j in 0..2000 {
+                        let doc = vec![NumericDocValuesField::new("id", i * 10000 + j)];
+                        writer.add_document(doc).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert!(writer.writer.doc_writer.num_docs() < 4 * 2000);
+
+        writer.rollback().unwrap();
+    }
+
+    #[test]
+    fn test_verify_checksums_on_commit() {
+        let dir = Arc::new(RAMDirectory::default());
+        let mut config = IndexWriterConfig::default();
+        config.verify_checksums_on_commit = true;
+        let writer = IndexWriter::new(dir, Arc::new(config)).unwrap();
+
+        for i in 0..2 {
+            for j in 0..10 {
+                let doc = vec![NumericDocValuesField::new("id", i * 100 + j)];
+                writer.add_document(doc).unwrap();
+            }
+            writer.commit().unwrap();
+        }
+
+        writer.close().unwrap();
+    }
+
+    #[test]
+    fn test_verify_checksums_on_commit_corrupted() {
+        let dir = Arc::new(RAMDirectory::default());
+        let mut config = IndexWriterConfig::default();
+        config.verify_checksums_on_commit = true;
+        let writer = IndexWriter::new(Arc::clone(&dir), Arc::new(config)).unwrap();
+        writer
+            .add_document(vec![NumericDocValuesField::new("id", 0)])
+            .unwrap();
+        writer.commit().unwrap();
+        let committed = dir.list_all().unwrap();
+
+        // flush a second segment, then flip a byte of one of its files
+        writer
+            .add_document(vec![NumericDocValuesField::new("id", 1)])
+            .unwrap();
+        writer.get_reader(true, false).unwrap();
+        let name = dir
+            .list_all()
+            .unwrap()
+            .into_iter()
+            .find(|f| !committed.contains(f))
+            .unwrap();
+        let len = dir.file_length(&name).unwrap() as usize;
+        let mut bytes = vec![0u8; len];
+        let mut input = dir.open_input(&name, &IOContext::READ_ONCE).unwrap();
+        input.read_bytes(&mut bytes, 0, len).unwrap();
+        bytes[len / 2] ^= 0xff;
+        dir.delete_file(&name).unwrap();
+        let mut output = dir.create_output(&name, &IOContext::Default).unwrap();
+        output.write_bytes(&bytes, 0, len).unwrap();
+        drop(output);
+
+        assert!(writer.commit().is_err());
+        writer.rollback().unwrap();
+    }
+
+    #[test]
+    fn test_max_field_length() {
+        let dir = Arc::new(RAMDirectory::default());
+        let writer = IndexWriter::new(dir, Arc::new(IndexWriterConfig::default())).unwrap();
+
+        let mut field_type = FieldType::default();
+        field_type.index_options = IndexOptions::DocsAndFreqs;
+        field_type.set_max_field_length(3).unwrap();
+        assert!(field_type.clone().set_max_field_length(0).is_err());
+
+        for text in &["a b c", "a b c d e", "a b"] {
+            let reader = Box::new(Cursor::new(text.as_bytes().to_vec()));
+            let tokens: Box<dyn TokenStream> = Box::new(WhitespaceTokenizer::new(reader));
+            let field = Field::new("text".into(), field_type.clone(), None, Some(tokens));
+            writer.add_document(vec![field]).unwrap();
+        }
+        assert_eq!(writer.num_truncated_docs(), 1);
+
+        writer.rollback().unwrap();
+    }
+
+    #[test]
+    fn test_update_numeric_doc_value() {
+        let writer = open_writer(IndexWriterConfig::default());
+
+        // two segments of 5 docs each
+        for i in 0..10 {
+            let id = VariantValue::VString(format!("{}", i));
+            let id = Field::new("id".into(), id_type(), Some(id), None);
+            let value = Some(VariantValue::Long(i));
+            let value = Field::new("value".into(), NUMERIC_DOC_VALUES_FIELD_TYPE, value, None);
+            writer.add_document(vec![id, value]).unwrap();
+            if i == 4 {
+                writer.commit().unwrap();
+            }
+        }
+
+        writer.update_numeric_doc_value(id_term("3"), "value", 100).unwrap();
+        writer.update_numeric_doc_value(id_term("7"), "value", 200).unwrap();
+        writer.update_numeric_doc_value(id_term("7"), "value", 300).unwrap();
+        assert!(writer.update_numeric_doc_value(id_term("1"), "id", 1).is_err());
+        assert!(writer.update_numeric_doc_value(id_term("1"), "missing", 1).is_err());
+        writer.commit().unwrap();
+
+        let check_values = |writer: &IndexWriter<_, _, _, _>| {
+            let reader = writer.get_reader(true, false).unwrap();
+            let mut values = Vec::with_capacity(10);
+            for leaf in reader.leaves() {
+                let dv = leaf.reader.get_numeric_doc_values("value").unwrap();
+                for doc in 0..leaf.reader.max_doc() {
+                    values.push(dv.get(doc).unwrap());
+                }
+            }
+            assert_eq!(values, vec![0, 1, 2, 100, 4, 5, 6, 300, 8, 9]);
+        };
+        check_values(&writer);
+
+        // the updated values are carried over by merges
+        writer.force_merge(1, true).unwrap();
+        check_values(&writer);
+
+        writer.rollback().unwrap();
+    }
+
+    #[test]
+    fn test_force_merge_reclaims_deletes() {
+        let writer = open_writer(IndexWriterConfig::default());
+        // three segments of 5 docs each
+        add_id_docs(&writer, 15, 5);
+        writer
+            .delete_documents_by_terms(vec![id_term("1"), id_term("7")])
+            .unwrap();
+        writer.commit().unwrap();
+        let reader = writer.get_reader(true, false).unwrap();
+        assert_eq!((reader.leaves().len(), reader.max_doc()), (3, 15));
+
+        assert!(writer.force_merge(0, true).is_err());
+        // the segments are few enough, but the ones with deletes are rewritten
+        writer.force_merge(3, true).unwrap();
+        let reader = writer.get_reader(true, false).unwrap();
+        assert_eq!(reader.leaves().len(), 3);
+        assert_eq!((reader.num_docs(), reader.max_doc()), (13, 13));
+
+        writer.force_merge(1, true).unwrap();
+        let reader = writer.get_reader(true, false).unwrap();
+        assert_eq!(reader.leaves().len(), 1);
+        assert_eq!((reader.num_docs(), reader.max_doc()), (13, 13));
+
+        writer.rollback().unwrap();
+    }
+
+    #[test]
+    fn test_check_merged_postings() {
+        let mut config = IndexWriterConfig::default();
+        config.check_merged_postings = true;
+        let writer = open_writer(config);
+
+        let mut text_type = FieldType::default();
+        text_type.index_options = IndexOptions::DocsAndFreqsAndPositions;
+        // three segments of 4 docs each, all the docs share the term "common"
+        for i in 0..12 {
+            let id = VariantValue::VString(format!("{}", i));
+            let text = format!("common t{}", i % 2);
+            let reader = Box::new(Cursor::new(text.into_bytes()));
+            let tokens: Box<dyn TokenStream> = Box::new(WhitespaceTokenizer::new(reader));
+            let doc = vec![
+                Field::new("id".into(), id_type(), Some(id), None),
+                Field::new("text".into(), text_type.clone(), None, Some(tokens)),
+            ];
+            writer.add_document(doc).unwrap();
+            if i % 4 == 3 {
+                writer.commit().unwrap();
+            }
+        }
+        writer
+            .delete_documents_by_terms(vec![id_term("2"), id_term("5")])
+            .unwrap();
+        writer.commit().unwrap();
+
+        // the merge remaps the postings of each segment through its doc map
+        writer.force_merge(1, true).unwrap();
+        let reader = writer.get_reader(true, false).unwrap();
+        assert_eq!(reader.leaves().len(), 1);
+        assert_eq!((reader.num_docs(), reader.max_doc()), (10, 10));
+        let searcher = DefaultIndexSearcher::new(&reader);
+        let count = |text: &str| {
+            let term = Term::new("text".into(), text.as_bytes().to_vec());
+            searcher.count(&TermQuery::new(term, 1.0, None)).unwrap()
+        };
+        assert_eq!(count("common"), 10);
+        assert_eq!(count("t0"), 5);
+        assert_eq!(count("t1"), 5);
+
+        writer.rollback().unwrap();
+    }
+
+    #[test]
+    fn test_force_merge_deletes() {
+        let mut merge_policy = TieredMergePolicy::default();
+        assert!(merge_policy.set_force_merge_deletes_pct_allowed(120.0).is_err());
+        merge_policy.set_force_merge_deletes_pct_allowed(30.0).unwrap();
+        let config = IndexWriterConfig::new(
+            Arc::new(CodecEnum::Lucene62(Lucene62Codec::default())),
+            SerialMergeScheduler {},
+            merge_policy,
+        );
+        let writer = open_writer(config);
+        // three segments of 5 docs each
+        add_id_docs(&writer, 15, 5);
+        // 40% of the first segment is deleted, 20% of the second one
+        writer
+            .delete_documents_by_terms(vec![id_term("1"), id_term("3"), id_term("7")])
+            .unwrap();
+
+        // only the first segment is rewritten
+        writer.force_merge_deletes(true).unwrap();
+        let reader = writer.get_reader(true, false).unwrap();
+        assert_eq!(reader.leaves().len(), 3);
+        assert_eq!((reader.num_docs(), reader.max_doc()), (12, 13));
+
+        // nothing is left to reclaim above the threshold
+        writer.force_merge_deletes(true).unwrap();
+        let reader = writer.get_reader(true, false).unwrap();
+        assert_eq!((reader.num_docs(), reader.max_doc()), (12, 13));
+
+        writer.rollback().unwrap();
+    }
+
+    #[test]
+    fn test_tiered_merge_policy_deletes_pct_allowed() {
+        let mut config = IndexWriterConfig::default();
+        {
+            let merge_policy = config.merge_policy_mut();
+            assert!(merge_policy.set_segments_per_tier(1.0).is_err());
+            assert!(merge_policy.set_deletes_pct_allowed(60.0).is_err());
+            // every segment is too large to be merged with others
+            merge_policy.set_max_merged_segment_mb(0.0).unwrap();
+            merge_policy.set_deletes_pct_allowed(20.0).unwrap();
+        }
+        let writer = open_writer(config);
+        // three segments of 5 docs each
+        add_id_docs(&writer, 15, 5);
+        let reader = writer.get_reader(true, false).unwrap();
+        assert_eq!((reader.leaves().len(), reader.max_doc()), (3, 15));
+
+        // 40% of the first segment is deleted, 20% of the second one
+        writer
+            .delete_documents_by_terms(vec![id_term("1"), id_term("3"), id_term("7")])
+            .unwrap();
+        // applying the deletes triggers the natural merges, which rewrite the
+        // first segment alone
+        writer.get_reader(true, false).unwrap();
+        let reader = writer.get_reader(true, false).unwrap();
+        assert_eq!(reader.leaves().len(), 3);
+        assert_eq!((reader.num_docs(), reader.max_doc()), (12, 13));
+
+        writer.rollback().unwrap();
+    }
+
+    #[test]
+    fn test_no_merge_policy() {
+        let config = IndexWriterConfig::new(
+            Arc::new(CodecEnum::Lucene62(Lucene62Codec::default())),
+            SerialMergeScheduler {},
+            NoMergePolicy,
+        );
+        let writer = open_writer(config);
+        // one segment per doc
+        add_id_docs(&writer, 30, 1);
+        let spec = NoMergePolicy
+            .find_merges(
+                MergerTrigger::FullFlush,
+                &writer.writer.segment_infos,
+                &writer,
+            )
+            .unwrap();
+        assert!(spec.is_none());
+
+        writer.force_merge(1, true).unwrap();
+        let reader = writer.get_reader(true, false).unwrap();
+        assert_eq!((reader.leaves().len(), reader.max_doc()), (30, 30));
+
+        writer.rollback().unwrap();
+    }
+
+    #[test]
+    fn test_log_byte_size_merge_policy() {
+        let mut merge_policy = LogByteSizeMergePolicy::default();
+        assert!(merge_policy.set_merge_factor(1).is_err());
+        merge_policy.set_merge_factor(3).unwrap();
+        let config = IndexWriterConfig::new(
+            Arc::new(CodecEnum::Lucene62(Lucene62Codec::default())),
+            SerialMergeScheduler {},
+            merge_policy,
+        );
+        let writer = open_writer(config);
+        // the tiny segments are all in the lowest level, and each 3 of them
+        // are merged
+        add_id_docs(&writer, 10, 1);
+        let reader = writer.get_reader(true, false).unwrap();
+        assert_eq!((reader.leaves().len(), reader.max_doc()), (2, 10));
+
+        writer.force_merge(1, true).unwrap();
+        let reader = writer.get_reader(true, false).unwrap();
+        assert_eq!((reader.leaves().len(), reader.max_doc()), (1, 10));
+
+        writer.rollback().unwrap();
+    }
+
+    #[test]
+    fn test_add_indexes() {
+        // two source indexes, the first one with a deleted doc
+        let dir1 = Arc::new(RAMDirectory::default());
+        let writer = IndexWriter::new(Arc::clone(&dir1), Arc::new(IndexWriterConfig::default()))
+            .unwrap();
+        for id in &["a0", "a1", "a2"] {
+            writer.add_document(id_doc(id)).unwrap();
+        }
+        writer.commit().unwrap();
+        writer.delete_documents_by_terms(vec![id_term("a1")]).unwrap();
+        writer.close().unwrap();
+        let dir2 = Arc::new(RAMDirectory::default());
+        let writer = IndexWriter::new(Arc::clone(&dir2), Arc::new(IndexWriterConfig::default()))
+            .unwrap();
+        for id in &["b0", "b1"] {
+            writer.add_document(id_doc(id)).unwrap();
+        }
+        writer.close().unwrap();
+
+        let dir = Arc::new(RAMDirectory::default());
+        let writer =
+            IndexWriter::new(Arc::clone(&dir), Arc::new(IndexWriterConfig::default())).unwrap();
+        writer.add_document(id_doc("c0")).unwrap();
+        assert!(writer.add_indexes(&[Arc::clone(&dir)]).is_err());
+        assert!(writer
+            .add_indexes(&[Arc::clone(&dir1), Arc::clone(&dir1)])
+            .is_err());
+        writer
+            .add_indexes(&[Arc::clone(&dir1), Arc::clone(&dir2)])
+            .unwrap();
+
+        let reader = Arc::new(writer.get_reader(true, false).unwrap());
+        assert_eq!((reader.num_docs(), reader.max_doc()), (5, 6));
+        let searcher = DefaultIndexSearcher::new(Arc::clone(&reader));
+        let count = |id: &str| {
+            let query = TermQuery::new(id_term(id), 1.0, None);
+            searcher.count(&query).unwrap()
+        };
+        assert_eq!(count("a0"), 1);
+        assert_eq!(count("a1"), 0);
+        assert_eq!(count("b1"), 1);
+        assert_eq!(count("c0"), 1);
+        writer.rollback().unwrap();
+
+        // the segments must be sorted like the index
+        let mut config = IndexWriterConfig::default();
+        let sort_field = SortField::Simple(SimpleSortField::new(
+            "value".into(),
+            SortFieldType::Long,
+            false,
+        ));
+        config.set_index_sort(Sort::new(vec![sort_field])).unwrap();
+        let writer = IndexWriter::new(Arc::clone(&dir), Arc::new(config)).unwrap();
+        assert!(writer.add_indexes(&[Arc::clone(&dir2)]).is_err());
+        let reader = writer.get_reader(true, false).unwrap();
+        assert_eq!(reader.max_doc(), 0);
+        writer.rollback().unwrap();
+    }
+
+    #[test]
+    fn test_add_indexes_from_readers() {
+        let writer = open_writer(IndexWriterConfig::default());
+        for id in &["a0", "a1", "a2"] {
+            writer.add_document(id_doc(id)).unwrap();
+        }
+        writer.commit().unwrap();
+        for id in &["b0", "b1"] {
+            writer.add_document(id_doc(id)).unwrap();
+        }
+        writer.delete_documents_by_terms(vec![id_term("a1")]).unwrap();
+        let reader = writer.get_reader(true, false).unwrap();
+        assert_eq!(reader.segment_readers().len(), 2);
+
+        // a reader of another index
+        let other = open_writer(IndexWriterConfig::default());
+        other.add_document(id_doc("c0")).unwrap();
+        let other_reader = other.get_reader(true, false).unwrap();
+
+        // the readers are merged into one segment without the deleted doc
+        let mut readers: Vec<Arc<SearchLeafReader<CodecEnum>>> = vec![];
+        for r in reader.segment_readers() {
+            readers.push(Arc::clone(r));
+        }
+        for r in other_reader.segment_readers() {
+            readers.push(Arc::clone(r));
+        }
+        writer.add_indexes_from_readers(&readers).unwrap();
+        other.rollback().unwrap();
+        let reader = Arc::new(writer.get_reader(true, false).unwrap());
+        assert_eq!(reader.leaves().len(), 3);
+        assert_eq!((reader.num_docs(), reader.max_doc()), (9, 10));
+        let added = &reader.segment_readers()[2];
+        assert_eq!((added.num_docs(), added.max_doc()), (5, 5));
+        let searcher = DefaultIndexSearcher::new(Arc::clone(&reader));
+        let count = |id: &str| {
+            let query = TermQuery::new(id_term(id), 1.0, None);
+            searcher.count(&query).unwrap()
+        };
+        assert_eq!(count("a0"), 2);
+        assert_eq!(count("a1"), 0);
+        assert_eq!(count("b1"), 2);
+        assert_eq!(count("c0"), 1);
+
+        writer.close().unwrap();
+    }
+
+    #[test]
+    fn test_try_delete_document() {
+        let writer = open_writer(IndexWriterConfig::default());
+        for id in &["a0", "a1", "a2"] {
+            writer.add_document(id_doc(id)).unwrap();
+        }
+        writer.commit().unwrap();
+        for id in &["b0", "b1"] {
+            writer.add_document(id_doc(id)).unwrap();
+        }
+        let reader = writer.get_reader(true, false).unwrap();
+        assert_eq!(reader.leaves().len(), 2);
+
+        // "a1" and "b1"
+        assert!(writer.try_delete_document(&reader, 1).unwrap());
+        assert!(writer.try_delete_document(&reader, 4).unwrap());
+        assert!(writer.try_delete_document(&reader, 1).unwrap());
+        assert!(writer.try_delete_document(&reader, 5).is_err());
+        assert!(writer.try_delete_document(&reader, -1).is_err());
+        assert!(writer.has_uncommitted_changes());
+
+        let new_reader = Arc::new(writer.get_reader(true, false).unwrap());
+        assert_eq!((new_reader.num_docs(), new_reader.max_doc()), (3, 5));
+        let searcher = DefaultIndexSearcher::new(Arc::clone(&new_reader));
+        let count = |id: &str| {
+            let query = TermQuery::new(id_term(id), 1.0, None);
+            searcher.count(&query).unwrap()
+        };
+        assert_eq!(count("a1"), 0);
+        assert_eq!(count("b0"), 1);
+        assert_eq!(count("b1"), 0);
+
+        // the segment "_0" of another index isn't the one of the reader
+        let other = open_writer(IndexWriterConfig::default());
+        for id in &["c0", "c1", "c2"] {
+            other.add_document(id_doc(id)).unwrap();
+        }
+        other.commit().unwrap();
+        let other_reader = other.get_reader(true, false).unwrap();
+        assert_eq!(other_reader.leaves()[0].reader.name(), reader.leaves()[0].reader.name());
+        assert!(!other.try_delete_document(&reader, 0).unwrap());
+        assert_eq!(other.get_reader(true, false).unwrap().num_docs(), 3);
+        other.rollback().unwrap();
+
+        // the segments of the old reader are merged away
+        writer.force_merge(1, true).unwrap();
+        assert!(!writer.try_delete_document(&reader, 0).unwrap());
+        writer.commit().unwrap();
+        let reader = writer.get_reader(true, false).unwrap();
+        assert_eq!((reader.num_docs(), reader.max_doc()), (3, 3));
+
+        writer.close().unwrap();
+    }
+
+    #[test]
+    fn test_prepare_commit() {
+        let dir = Arc::new(RAMDirectory::default());
+        let writer =
+            IndexWriter::new(Arc::clone(&dir), Arc::new(IndexWriterConfig::default())).unwrap();
+        writer.add_document(id_doc("a0")).unwrap();
+        writer.commit().unwrap();
+        let last_commit = |writer: &IndexWriter<_, _, _, _>| {
+            writer.list_commits().unwrap().pop().unwrap()
+        };
+
+        writer.add_document(id_doc("a1")).unwrap();
+        let mut user_data = HashMap::new();
+        user_data.insert("tx".to_string(), "1".to_string());
+        writer.set_live_commit_data(user_data.clone()).unwrap();
+        assert_eq!(writer.live_commit_data().unwrap(), user_data);
+        assert_eq!(writer.pending_commit_data().unwrap(), None);
+        assert!(writer.prepare_commit().unwrap() > 0);
+        assert!(writer.prepare_commit().is_err());
+        assert_eq!(writer.pending_commit_data().unwrap(), Some(user_data.clone()));
+        // the prepared commit isn't the active one yet
+        let commit = last_commit(&writer);
+        assert_eq!(commit.doc_count, 1);
+        assert!(commit.user_data.is_empty());
+
+        writer.commit().unwrap();
+        assert_eq!(writer.pending_commit_data().unwrap(), None);
+        let commit = last_commit(&writer);
+        assert_eq!(commit.doc_count, 2);
+        assert_eq!(commit.user_data, user_data);
+
+        // a rollback drops the prepared commit
+        writer.add_document(id_doc("a2")).unwrap();
+        writer.prepare_commit().unwrap();
+        writer.rollback().unwrap();
+        let writer =
+            IndexWriter::new(Arc::clone(&dir), Arc::new(IndexWriterConfig::default())).unwrap();
+        assert_eq!(last_commit(&writer).doc_count, 2);
+        assert_eq!(writer.get_reader(true, false).unwrap().num_docs(), 2);
+        assert!(dir
+            .list_all()
+            .unwrap()
+            .iter()
+            .all(|f| !f.starts_with(INDEX_FILE_PENDING_SEGMENTS)));
+
+        writer.close().unwrap();
+    }
+
+    #[test]
+    fn test_soft_deletes() {
+        // the soft deleted docs with a value >= 8 survive the merges
+        let retain = |v: i64| v >= 8;
+        let merge_policy =
+            SoftDeletesRetentionMergePolicy::new(TieredMergePolicy::default(), "value", retain);
+        let mut config = IndexWriterConfig::new(
+            Arc::new(CodecEnum::Lucene62(Lucene62Codec::default())),
+            SerialMergeScheduler {},
+            merge_policy,
+        );
+        config.set_soft_deletes_field("soft_deleted");
+        let writer = open_writer(config);
+
+        let doc = |id: i64, value: i64| {
+            let id = VariantValue::VString(format!("{}", id));
+            let id = Field::new("id".into(), id_type(), Some(id), None);
+            let value = Some(VariantValue::Long(value));
+            let value = Field::new("value".into(), NUMERIC_DOC_VALUES_FIELD_TYPE, value, None);
+            vec![id, value]
+        };
+        // two segments of 5 docs each
+        for i in 0..10 {
+            writer.add_document(doc(i, i)).unwrap();
+            if i == 4 {
+                writer.commit().unwrap();
+            }
+        }
+
+        writer.soft_update_document(id_term("2"), doc(2, 20)).unwrap();
+        writer
+            .soft_delete_documents(vec![id_term("3"), id_term("8")])
+            .unwrap();
+
+        // returns the values of the visible docs and the total max doc
+        let live_values = |writer: &IndexWriter<_, _, _, _>| {
+            let reader = writer.get_reader(true, false).unwrap();
+            let mut values = Vec::new();
+            for leaf in reader.leaves() {
+                let dv = leaf.reader.get_numeric_doc_values("value").unwrap();
+                let live_docs = leaf.reader.live_docs();
+                for doc in 0..leaf.reader.max_doc() {
+                    if live_docs.get(doc as usize).unwrap() {
+                        values.push(dv.get(doc).unwrap());
+                    }
+                }
+            }
+            assert_eq!(reader.num_docs(), values.len() as i32);
+            (values, reader.max_doc())
+        };
+        let (values, max_doc) = live_values(&writer);
+        assert_eq!(values, vec![0, 1, 4, 5, 6, 7, 9, 20]);
+        assert_eq!(max_doc, 11);
+
+        // the merge drops the soft deleted docs 2 and 3 but retains doc 8
+        writer.force_merge(1, true).unwrap();
+        let (values, max_doc) = live_values(&writer);
+        assert_eq!(values, vec![0, 1, 4, 5, 6, 7, 9, 20]);
+        assert_eq!(max_doc, 9);
+
+        writer.rollback().unwrap();
+    }
+
+    #[test]
+    fn test_delete_documents_by_doc_sets() {
+        let dir = Arc::new(RAMDirectory::default());
+        let writer = IndexWriter::new(dir, Arc::new(IndexWriterConfig::default())).unwrap();
+
+        // two segments of 5 docs each
+        for i in 0..10 {
+            writer
+                .add_document(vec![NumericDocValuesField::new("id", i)])
+                .unwrap();
+            if i == 4 {
+                writer.commit().unwrap();
+            }
+        }
+
+        let reader = writer.get_reader(true, false).unwrap();
+        let doc_sets: Vec<(String, BitsRef)> = reader
+            .leaves()
+            .iter()
+            .map(|leaf| {
+                let mut docs = FixedBitSet::new(leaf.reader.max_doc() as usize);
+                docs.set(1);
+                docs.set(3);
+                let docs: BitsRef = Arc::new(docs);
+                (leaf.reader.name().to_string(), docs)
+            })
+            .collect();
+        writer.delete_documents_by_doc_sets(doc_sets).unwrap();
+        // the doc sets of unknown segments are rejected
+        let docs: BitsRef = Arc::new(FixedBitSet::new(1));
+        assert!(writer
+            .delete_documents_by_doc_sets(vec![("_missing".into(), docs)])
+            .is_err());
+
+        let reader = writer.get_reader(true, false).unwrap();
+        assert_eq!(reader.max_doc(), 10);
+        assert_eq!(reader.num_docs(), 6);
+        for leaf in reader.leaves() {
+            let live_docs = leaf.reader.live_docs();
+            assert!(!live_docs.get(1).unwrap());
+            assert!(!live_docs.get(3).unwrap());
+            assert!(live_docs.get(2).unwrap());
+        }
+
+        writer.rollback().unwrap();
+    }
+
+    #[test]
+    fn test_nrt_reopen_reuses_unchanged_readers() {
+        let writer = open_writer(IndexWriterConfig::default());
+
+        let leaf_addrs = |reader: &StandardDirectoryReader<_, _, _, _>| -> Vec<usize> {
+            reader
+                .leaves()
+                .iter()
+                .map(|leaf| leaf.reader as *const _ as *const u8 as usize)
+                .collect()
+        };
+
+        add_id_docs(&writer, 5, 5);
+        let reader1 = writer.get_reader(true, false).unwrap();
+        assert!(reader1.open_if_changed(None).unwrap().is_none());
+
+        // the new doc is visible without a commit, the first segment is shared
+        writer.add_document(id_doc(5)).unwrap();
+        let reader2 = reader1.open_if_changed(None).unwrap().unwrap();
+        assert_eq!(reader2.num_docs(), 6);
+        let (addrs1, addrs2) = (leaf_addrs(&reader1), leaf_addrs(&reader2));
+        assert_eq!(addrs2.len(), 2);
+        assert_eq!(addrs1[0], addrs2[0]);
+
+        // the buffered delete is applied, only the first segment is reopened
+        writer.delete_documents_by_terms(vec![id_term("1")]).unwrap();
+        let reader3 = reader2.open_if_changed(None).unwrap().unwrap();
+        assert_eq!(reader3.num_docs(), 5);
+        let addrs3 = leaf_addrs(&reader3);
+        assert_ne!(addrs2[0], addrs3[0]);
+        assert_eq!(addrs2[1], addrs3[1]);
+
+        writer.rollback().unwrap();
+    }
+
+    #[test]
+    fn test_nrt_reopen_with_soft_deletes() {
+        let mut config = IndexWriterConfig::new(
+            Arc::new(CodecEnum::Lucene62(Lucene62Codec::default())),
+            SerialMergeScheduler {},
+            TieredMergePolicy::default(),
+        );
+        config.set_soft_deletes_field("soft_deleted");
+        let writer = open_writer(config);
+
+        let leaf_addrs = |reader: &StandardDirectoryReader<_, _, _, _>| -> Vec<usize> {
+            reader
+                .leaves()
+                .iter()
+                .map(|leaf| leaf.reader as *const _ as *const u8 as usize)
+                .collect()
+        };
+
+        // two segments of 5 docs each
+        add_id_docs(&writer, 10, 5);
+        let reader1 = writer.get_reader(true, false).unwrap();
+        assert_eq!(reader1.num_docs(), 10);
+
+        // the soft delete only reopens the second segment
+        writer.soft_delete_documents(vec![id_term("7")]).unwrap();
+        let reader2 = reader1.open_if_changed(None).unwrap().unwrap();
+        assert_eq!(reader2.num_docs(), 9);
+        let (addrs1, addrs2) = (leaf_addrs(&reader1), leaf_addrs(&reader2));
+        assert_eq!(addrs2.len(), 2);
+        assert_eq!(addrs1[0], addrs2[0]);
+        assert_ne!(addrs1[1], addrs2[1]);
+
+        // nothing changed, both segments are shared
+        writer.add_document(id_doc(10)).unwrap();
+        let reader3 = reader2.open_if_changed(None).unwrap().unwrap();
+        assert_eq!(reader3.num_docs(), 10);
+        let addrs3 = leaf_addrs(&reader3);
+        assert_eq!(addrs3.len(), 3);
+        assert_eq!(&addrs2[..], &addrs3[..2]);
+
+        writer.rollback().unwrap();
+    }
+
+    #[test]
+    fn test_add_documents_parallel() {
+        let writer = IndexWriter::new(
+            Arc::new(RAMDirectory::default()),
+            Arc::new(IndexWriterConfig::default()),
+        )
+        .unwrap();
+
+        // a doc failing because its not indexed field stores term vectors
+        let mut bad_type = FieldType::default();
+        bad_type.stored = true;
+        bad_type.store_term_vectors = true;
+        let doc = |id: i64, bad: bool| {
+            let value = Some(VariantValue::Long(id));
+            let mut doc = vec![Field::new("id".into(), NUMERIC_DOC_VALUES_FIELD_TYPE, value, None)];
+            if bad {
+                let value = Some(VariantValue::VString("bad".into()));
+                doc.push(Field::new("bad".into(), bad_type.clone(), value, None));
+            }
+            doc
+        };
+        let live_ids = |writer: &IndexWriter<_, _, _, _>| {
+            let reader = writer.get_reader(true, false).unwrap();
+            let mut ids = Vec::new();
+            for leaf in reader.leaves() {
+                let dv = leaf.reader.get_numeric_doc_values("id").unwrap();
+                let live_docs = leaf.reader.live_docs();
+                for doc in 0..leaf.reader.max_doc() {
+                    if live_docs.get(doc as usize).unwrap() {
+                        ids.push(dv.get(doc).unwrap());
+                    }
+                }
+            }
+            ids.sort();
+            ids
+        };
+
+        assert!(writer.add_documents_parallel(vec![doc(0, false)], 0).is_err());
+
+        // all the docs are added by the concurrent threads
+        let docs = (0..100).map(|i| doc(i, false)).collect();
+        let seq_no = writer.add_documents_parallel(docs, 4).unwrap();
+        assert!(seq_no >= 100);
+        assert_eq!(live_ids(&writer), (0..100).collect::<Vec<_>>());
+
+        // the doc 150 fails as the first doc of the third of the 4 partitions of
+        // 25 docs, whose thread stops there while the others may go on
+        let docs = (100..200).map(|i| doc(i, i == 150)).collect();
+        match writer.add_documents_parallel(docs, 4) {
+            Err(Error(IllegalArgument(_), _)) => {}
+            r => panic!("expected IllegalArgument, got {:?}", r),
+        }
+        let ids = live_ids(&writer);
+        assert_eq!(&ids[..100], &(0..100).collect::<Vec<_>>()[..]);
+        assert!(ids[100..].iter().all(|&id| (id >= 100 && id < 150) || id >= 175));
+        assert!(ids.len() < 200);
+
+        // the failure didn't abort the writer
+        writer.add_document(doc(200, false)).unwrap();
+        assert_eq!(*live_ids(&writer).last().unwrap(), 200);
+        writer.close().unwrap();
+    }
+
+    #[test]
+    fn test_index_after_delete_all() {
+        let writer = IndexWriter::new(
+            Arc::new(RAMDirectory::default()),
+            Arc::new(IndexWriterConfig::default()),
+        )
+        .unwrap();
+        let doc = |id: i64| vec![NumericDocValuesField::new("id", id)];
+
+        // a committed segment and buffered docs
+        for i in 0..5 {
+            writer.add_document(doc(i)).unwrap();
+        }
+        writer.commit().unwrap();
+        for i in 5..10 {
+            writer.add_document(doc(i)).unwrap();
+        }
+        writer.delete_all().unwrap();
+        assert_eq!(writer.get_reader(true, false).unwrap().num_docs(), 0);
+
+        // the aborted thread states are usable again, from any thread
+        let handles: Vec<_> = (0..4)
+            .map(|t| {
+                let writer = writer.clone();
+                thread::spawn(move || {
+                    for i in 0..10 {
+                        writer.add_document(doc(t * 10 + i)).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        writer.commit().unwrap();
+
+        let reader = writer.get_reader(true, false).unwrap();
+        assert_eq!(reader.num_docs(), 40);
+        assert_eq!(reader.max_doc(), 40);
+        writer.close().unwrap();
+    }
+
+    #[test]
+    fn test_index_sort() {
+        let dir = Arc::new(RAMDirectory::default());
+        let sort_by = |field: &str| {
+            Sort::new(vec![SortField::Simple(SimpleSortField::new(
+                field.into(),
+                SortFieldType::Long,
+                false,
+            ))])
+        };
+        let config = |sort: Sort| {
+            let mut config = IndexWriterConfig::default();
+            config.set_index_sort(sort).unwrap();
+            Arc::new(config)
+        };
+        let writer = IndexWriter::new(Arc::clone(&dir), config(sort_by("price"))).unwrap();
+
+        // two segments, each flushed in price order
+        for (i, &price) in [5, 1, 4, 3, 0, 2].iter().enumerate() {
+            writer
+                .add_document(vec![NumericDocValuesField::new("price", price)])
+                .unwrap();
+            if i == 2 {
+                writer.commit().unwrap();
+            }
+        }
+        writer.commit().unwrap();
+
+        let segment_prices = |writer: &IndexWriter<_, _, _, _>| {
+            let reader = writer.get_reader(true, false).unwrap();
+            let mut prices = Vec::new();
+            for leaf in reader.leaves() {
+                assert_eq!(leaf.reader.index_sort(), Some(&sort_by("price")));
+                let dv = leaf.reader.get_numeric_doc_values("price").unwrap();
+                let values: Vec<i64> = (0..leaf.reader.max_doc())
+                    .map(|doc| dv.get(doc).unwrap())
+                    .collect();
+                prices.push(values);
+            }
+            prices
+        };
+        assert_eq!(segment_prices(&writer), vec![vec![1, 4, 5], vec![0, 2, 3]]);
+
+        // the merged segment is sorted too
+        writer.force_merge(1, true).unwrap();
+        assert_eq!(segment_prices(&writer), vec![vec![0, 1, 2, 3, 4, 5]]);
+        writer.close().unwrap();
+
+        // the index can be reopened with the same sort, but not another one
+        let writer = IndexWriter::new(Arc::clone(&dir), config(sort_by("price"))).unwrap();
+        writer.rollback().unwrap();
+        assert!(IndexWriter::new(dir, config(sort_by("id"))).is_err());
+    }
+
+    #[test]
+    fn test_delete_commits_while_indexing() {
+        let mut config = IndexWriterConfig::default();
+        config.index_deletion_policy =
+            IndexDeletionPolicyEnum::KeepAll(KeepAllCommitsDeletionPolicy);
+        config.max_buffered_docs = Some(10);
+        let dir = Arc::new(RAMDirectory::default());
+        let writer = IndexWriter::new(dir, Arc::new(config)).unwrap();
+        for i in 0..5 {
+            writer
+                .add_document(vec![NumericDocValuesField::new("id", i)])
+                .unwrap();
+            writer.commit().unwrap();
+        }
+        assert_eq!(writer.list_commits().unwrap().len(), 5);
+
+        // the flushes of the indexing thread checkpoint the deleter while the
+        // old commits are deleted
+        let indexer = {
+            let writer = writer.clone();
+            thread::spawn(move || {
+                for i in 5..1005 {
+                    writer
+                        .add_document(vec![NumericDocValuesField::new("id", i)])
+                        .unwrap();
+                }
+            })
+        };
+        let generations: Vec<i64> = writer
+            .list_commits()
+            .unwrap()
+            .iter()
+            .map(|c| c.generation)
+            .collect();
+        assert!(writer.delete_commit(generations[0]).unwrap());
+        assert!(!writer.delete_commit(generations[0]).unwrap());
+        assert!(writer.delete_commit(generations[4]).is_err());
+        assert_eq!(writer.delete_unused_commits().unwrap(), &generations[1..4]);
+        indexer.join().unwrap();
+
+        writer.commit().unwrap();
+        assert_eq!(writer.list_commits().unwrap().len(), 2);
+        assert_eq!(writer.delete_unused_commits().unwrap(), vec![generations[4]]);
+        let reader = writer.get_reader(true, false).unwrap();
+        assert_eq!(reader.num_docs(), 1005);
+        writer.rollback().unwrap();
+    }
+
+    #[test]
+    fn test_index_sort_flushed_segment_deletes() {
+        let sort = Sort::new(vec![SortField::Simple(SimpleSortField::new(
+            "price".into(),
+            SortFieldType::Long,
+            false,
+        ))]);
+        let mut config = IndexWriterConfig::default();
+        config.set_index_sort(sort.clone()).unwrap();
+        let writer = open_writer(config);
+
+        let add = |id: i64, price: i64| {
+            let key = Some(VariantValue::VString(format!("{}", id)));
+            let group = Some(VariantValue::VString("a".into()));
+            let value = Some(VariantValue::Long(id));
+            writer
+                .add_document(vec![
+                    Field::new("id".into(), id_type(), key, None),
+                    Field::new("group".into(), id_type(), group, None),
+                    Field::new("value".into(), NUMERIC_DOC_VALUES_FIELD_TYPE, value, None),
+                    NumericDocValuesField::new("price", price),
+                ])
+                .unwrap();
+        };
+        let group = || Term::new("group".into(), b"a".to_vec());
+
+        // the query delete and the update only see the docs added before them,
+        // which are no longer at the same position once the segment is sorted
+        add(0, 4);
+        add(1, 1);
+        let query: Arc<dyn Query<_>> = Arc::new(TermQuery::new(group(), 1.0, None));
+        writer.delete_documents_by_queries(vec![query]).unwrap();
+        add(2, 3);
+        writer.update_numeric_doc_value(group(), "value", 100).unwrap();
+        add(3, 0);
+        add(4, 2);
+        // applied on flush
+        writer.delete_documents_by_terms(vec![id_term("4")]).unwrap();
+        writer.commit().unwrap();
+
+        let reader = writer.get_reader(true, false).unwrap();
+        let leaves = reader.leaves();
+        assert_eq!(leaves.len(), 1);
+        let leaf = &leaves[0];
+        assert_eq!(leaf.reader.index_sort(), Some(&sort));
+        assert_eq!(leaf.reader.num_docs(), 2);
+        let live_docs = leaf.reader.live_docs();
+        let price = leaf.reader.get_numeric_doc_values("price").unwrap();
+        let value = leaf.reader.get_numeric_doc_values("value").unwrap();
+        let mut live = Vec::new();
+        for doc in 0..leaf.reader.max_doc() {
+            if live_docs.get(doc as usize).unwrap() {
+                live.push((price.get(doc).unwrap(), value.get(doc).unwrap()));
+            }
+        }
+        assert_eq!(live, vec![(0, 3), (3, 100)]);
+        writer.rollback().unwrap();
+    }
+
+    #[test]
+    fn test_index_sort_flushed_segment_all_deleted() {
+        let sort = Sort::new(vec![SortField::Simple(SimpleSortField::new(
+            "price".into(),
+            SortFieldType::Long,
+            false,
+        ))]);
+        let mut config = IndexWriterConfig::default();
+        config.set_index_sort(sort.clone()).unwrap();
+        let writer = open_writer(config);
+
+        // already in price order, the flushed segment is kept as written
+        for price in 0..3 {
+            writer
+                .add_document(vec![NumericDocValuesField::new("price", price)])
+                .unwrap();
+        }
+        writer.commit().unwrap();
+        let reader = writer.get_reader(true, false).unwrap();
+        let leaves = reader.leaves();
+        assert_eq!(leaves.len(), 1);
+        assert_eq!(leaves[0].reader.name(), "_0");
+        assert_eq!(leaves[0].reader.index_sort(), Some(&sort));
+        assert_eq!(leaves[0].reader.num_docs(), 3);
+
+        // every doc of the second segment is deleted on flush
+        for price in (3..6).rev() {
+            let id = Some(VariantValue::VString("x".into()));
+            writer
+                .add_document(vec![
+                    Field::new("id".into(), id_type(), id, None),
+                    NumericDocValuesField::new("price", price),
+                ])
+                .unwrap();
+        }
+        writer.delete_documents_by_terms(vec![id_term("x")]).unwrap();
+        writer.commit().unwrap();
+
+        let reader = writer.get_reader(true, false).unwrap();
+        let num_docs: i32 = reader.leaves().iter().map(|l| l.reader.num_docs()).sum();
+        assert_eq!(num_docs, 3);
+        writer.rollback().unwrap();
+    }
+}
@@ -11,12 +11,17 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use core::codec::lucene50::StoredFieldCompressMode;
 use core::codec::{Codec, CodecEnum, Lucene62Codec};
-use core::index::delete_policy::KeepOnlyLastCommitDeletionPolicy;
+use core::index::delete_policy::IndexDeletionPolicyEnum;
 use core::index::merge_policy::{MergePolicy, TieredMergePolicy};
 use core::index::merge_scheduler::MergeScheduler;
 use core::index::merge_scheduler::SerialMergeScheduler;
 use core::search::sort::Sort;
+use core::search::sort_field::{SortField, SortFieldType};
+use core::store::SimpleRateLimiter;
+
+use error::{ErrorKind::IllegalArgument, Result};
 
 use std::sync::Arc;
 
@@ -46,8 +51,26 @@ pub struct IndexWriterConfig<C: Codec, MS: MergeScheduler, MP: MergePolicy> {
     pub reader_pooling: bool,
     pub open_mode: OpenMode,
     pub per_thread_hard_limit_mb: u32,
+    /// Hard upper bound of the RAM used by all the buffered documents and
+    /// deletes, None if disabled. See `set_ram_hard_limit_mb`.
+    pub ram_hard_limit_mb: Option<f64>,
     pub codec: Arc<C>,
     pub commit_on_close: bool,
+    pub index_deletion_policy: IndexDeletionPolicyEnum,
+    /// If true, the files written since the writer was opened are re-read and
+    /// their checksums verified before each commit. This catches corruptions
+    /// happening on write at commit time, but is I/O heavy. Defaults to false.
+    pub verify_checksums_on_commit: bool,
+    /// If true, merges check that the doc ids of the merged postings of each
+    /// term are strictly increasing, and fail with a `CorruptIndex` error
+    /// otherwise. Debug builds always assert it. Defaults to false.
+    pub check_merged_postings: bool,
+    /// The numeric doc values field soft deletes are recorded in, None if
+    /// soft deletes are disabled. See `set_soft_deletes_field`.
+    pub soft_deletes_field: Option<String>,
+    /// Throttles the bytes written by all the merges together, None if
+    /// unlimited. See `set_merge_rate_limiter`.
+    pub merge_rate_limiter: Option<Arc<SimpleRateLimiter>>,
     // pub similarity: Box<Similarity>,
 }
 
@@ -61,6 +84,17 @@ impl Default for IndexWriterConfig<CodecEnum, SerialMergeScheduler, TieredMergeP
     }
 }
 
+impl<MS: MergeScheduler, MP: MergePolicy> IndexWriterConfig<CodecEnum, MS, MP> {
+    /// Sets the compression of the stored fields of the segments written by
+    /// the writer, including the merged ones. The mode is recorded per
+    /// segment, so the segments written with another mode are still read.
+    pub fn set_stored_fields_compress_mode(&mut self, mode: StoredFieldCompressMode) {
+        self.codec = Arc::new(CodecEnum::Lucene62(
+            Lucene62Codec::with_stored_fields_mode(mode),
+        ));
+    }
+}
+
 impl<C: Codec, MS: MergeScheduler, MP: MergePolicy> IndexWriterConfig<C, MS, MP> {
     pub fn new(codec: Arc<C>, merge_scheduler: MS, merge_policy: MP) -> Self {
         IndexWriterConfig {
@@ -75,8 +109,14 @@ impl<C: Codec, MS: MergeScheduler, MP: MergePolicy> IndexWriterConfig<C, MS, MP>
             reader_pooling: true,
             open_mode: OpenMode::CreateOrAppend,
             per_thread_hard_limit_mb: DEFAULT_RAM_PER_THREAD_HARD_LIMIT_MB,
+            ram_hard_limit_mb: None,
             codec,
             commit_on_close: true,
+            index_deletion_policy: IndexDeletionPolicyEnum::default(),
+            verify_checksums_on_commit: false,
+            check_merged_postings: false,
+            soft_deletes_field: None,
+            merge_rate_limiter: None,
             // similarity: Box::new(BM25Similarity::default()),
         }
     }
@@ -124,16 +164,94 @@ impl<C: Codec, MS: MergeScheduler, MP: MergePolicy> IndexWriterConfig<C, MS, MP>
         &self.merge_policy
     }
 
+    /// Returns the merge policy to tune, for example the tiers of the default
+    /// `TieredMergePolicy`.
+    pub fn merge_policy_mut(&mut self) -> &mut MP {
+        &mut self.merge_policy
+    }
+
     pub fn index_sort(&self) -> Option<&Sort> {
         self.index_sort.as_ref()
     }
 
+    /// Sets the sort the docs of each segment are kept in, both when flushed
+    /// and merged, which lets `EarlyTerminatingSortingCollector` stop
+    /// collecting a segment early when searching with the same sort.
+    ///
+    /// Each sort field must be a single-valued numeric doc values field.
+    pub fn set_index_sort(&mut self, sort: Sort) -> Result<()> {
+        for field in sort.get_sort() {
+            let supported = match field {
+                SortField::Simple(_) => match field.field_type() {
+                    SortFieldType::Int
+                    | SortFieldType::Long
+                    | SortFieldType::Float
+                    | SortFieldType::Double => true,
+                    _ => false,
+                },
+                _ => false,
+            };
+            if !supported {
+                bail!(IllegalArgument(format!(
+                    "invalid index sort field: {:?}, only single-valued numeric doc values \
+                     fields are supported",
+                    field
+                )));
+            }
+        }
+        self.index_sort = Some(sort);
+        Ok(())
+    }
+
+    /// Sets the numeric doc values field soft deletes are recorded in. A soft
+    /// deleted doc is not removed from the index, it is given a value for this
+    /// field instead, and is only dropped by the merges, unless the merge
+    /// policy retains it. The readers opened by the writer hide it in the
+    /// meantime.
+    ///
+    /// The field must not be used otherwise by the documents.
+    pub fn set_soft_deletes_field(&mut self, field: &str) {
+        self.soft_deletes_field = Some(field.to_string());
+    }
+
+    pub fn soft_deletes_field(&self) -> Option<&str> {
+        self.soft_deletes_field.as_ref().map(|f| f.as_str())
+    }
+
+    /// Throttles the bytes written by the merges to the rate of the given
+    /// limiter. Keep a reference to it to change the rate while the writer
+    /// is open.
+    pub fn set_merge_rate_limiter(&mut self, rate_limiter: Arc<SimpleRateLimiter>) {
+        self.merge_rate_limiter = Some(rate_limiter);
+    }
+
     pub fn per_thread_hard_limit(&self) -> u64 {
         self.per_thread_hard_limit_mb as u64 * 1024 * 1024
     }
 
-    pub fn index_deletion_policy(&self) -> KeepOnlyLastCommitDeletionPolicy {
-        KeepOnlyLastCommitDeletionPolicy::default()
+    /// Sets a hard upper bound of the RAM used by all the buffered documents
+    /// and deletes, as a last resort guard against OOM on bursts of very large
+    /// documents. A value `<= 0` disables it, which is the default.
+    ///
+    /// Unlike the RAM buffer size, which is a soft trigger, once the limit is
+    /// reached the largest in-memory segment is flushed by the indexing thread
+    /// which crossed it, and new documents are blocked until the flushes in
+    /// flight free enough memory.
+    pub fn set_ram_hard_limit_mb(&mut self, size: f64) {
+        if size <= 0.0 {
+            self.ram_hard_limit_mb = None;
+        } else {
+            self.ram_hard_limit_mb = Some(size);
+        }
+    }
+
+    /// Returns the RAM hard limit in bytes, None if disabled.
+    pub fn ram_hard_limit(&self) -> Option<u64> {
+        self.ram_hard_limit_mb.map(|size| (size * 1024.0 * 1024.0) as u64)
+    }
+
+    pub fn index_deletion_policy(&self) -> IndexDeletionPolicyEnum {
+        self.index_deletion_policy
     }
 
     pub fn merge_scheduler(&self) -> MS {
@@ -19,7 +19,7 @@ use core::codec::{
 use core::index::{
     BinaryDocValuesRef, FieldInfo, FieldInfos, Fields, IndexReader, NumericDocValues,
     NumericDocValuesRef, SortedDocValuesRef, SortedNumericDocValuesRef, SortedSetDocValuesRef,
-    StoredFieldVisitor, Term, TermIterator, Terms,
+    StoredFieldVisitor, Term, TermIterator, Terms, TermsStats,
 };
 use core::search::sort::Sort;
 use core::util::external::deferred::Deferred;
@@ -48,10 +48,27 @@ pub trait LeafReader {
 
     fn name(&self) -> &str;
 
+    /// The unique id of the segment this reader reads, which tells apart the
+    /// segments of the same name, e.g. of other indices. `None` if this
+    /// reader doesn't read a single segment.
+    fn segment_id(&self) -> Option<&[u8]> {
+        None
+    }
+
     fn terms(&self, field: &str) -> Result<Option<<Self::FieldsProducer as Fields>::Terms>> {
         self.fields()?.terms(field)
     }
 
+    /// Returns cheap statistics of the terms of `field`, or None if the field
+    /// has no terms. The stats are read from the terms dictionary metadata,
+    /// so that query rewrites can bound their expansion cost up front.
+    fn terms_stats(&self, field: &str) -> Result<Option<TermsStats>> {
+        match self.terms(field)? {
+            Some(terms) => Ok(Some(TermsStats::from_terms(&terms)?)),
+            None => Ok(None),
+        }
+    }
+
     fn doc_freq(&self, term: &Term) -> Result<i32> {
         if let Some(terms) = self.terms(&term.field)? {
             let mut terms_iter = terms.iterator()?;
@@ -125,16 +142,19 @@ pub trait LeafReader {
     /// spatial searches, or None if there are no point fields.
     fn point_values(&self) -> Option<Self::PointsReader>;
 
-    /// Expert: Returns a key for this IndexReader, so CachingWrapperFilter can find
-    // it again.
-    // This key must not have equals()/hashCode() methods, so &quot;equals&quot; means
-    // &quot;identical&quot;.
+    /// Expert: Returns a key identifying the core of this segment, so caches
+    /// like `LRUQueryCache` can find their entries again.
+    ///
+    /// The readers sharing a core, like the NRT readers reopened on an
+    /// unchanged segment, share this key, and no other core ever gets it.
     fn core_cache_key(&self) -> &str;
 
     /// Returns null if this leaf is unsorted, or the `Sort` that it was sorted by
     fn index_sort(&self) -> Option<&Sort>;
 
-    /// Expert: adds a CoreClosedListener to this reader's shared core
+    /// Expert: adds a listener called once the shared core of this reader is
+    /// dropped, that is when no reader of the segment is left, so caches
+    /// keyed on `core_cache_key` can release their entries.
     fn add_core_drop_listener(&self, listener: Deferred);
 
     // TODO, currently we don't provide remove listener method
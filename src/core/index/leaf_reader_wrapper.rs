@@ -17,7 +17,7 @@ use core::codec::{
     StoredFieldsReader, TermVectorsReader,
 };
 use core::index::sorter::{PackedLongDocMap, SorterDocMap};
-use core::index::SegmentReader;
+use core::index::SearchLeafReader;
 use core::index::StoredFieldVisitor;
 use core::index::{BinaryDocValues, BinaryDocValuesRef};
 use core::index::{DocValuesTermIterator, LeafReader};
@@ -33,7 +33,7 @@ use core::index::{SortedSetDocValues, SortedSetDocValuesContext, SortedSetDocVal
 use core::search::posting_iterator::{PostingIterator, PostingIteratorFlags};
 use core::search::sort::Sort;
 use core::search::{DocIterator, Payload, NO_MORE_DOCS};
-use core::store::{DataInput, Directory, IndexInput, IndexOutput, RAMOutputStream};
+use core::store::{DataInput, IndexInput, IndexOutput, RAMOutputStream};
 use core::util::external::deferred::Deferred;
 use core::util::fst::bytes_store::{BytesStore, StoreBytesReader};
 use core::util::{Bits, BitsContext, BitsRef, DocId};
@@ -46,8 +46,8 @@ use std::sync::Arc;
 
 /// This is a hack to make index sorting fast, with a `LeafReader` that
 /// always returns merge instances when you ask for the codec readers.
-pub struct MergeReaderWrapper<D: Directory + 'static, C: Codec> {
-    reader: Arc<SegmentReader<D, C>>,
+pub struct MergeReaderWrapper<C: Codec> {
+    reader: Arc<SearchLeafReader<C>>,
     fields: CodecFieldsProducer<C>,
     norms: Option<Arc<CodecNormsProducer<C>>>,
     doc_values: Option<Box<dyn DocValuesProducer>>,
@@ -55,8 +55,8 @@ pub struct MergeReaderWrapper<D: Directory + 'static, C: Codec> {
     vectors: Option<Arc<CodecTVReader<C>>>,
 }
 
-impl<D: Directory + 'static, C: Codec> MergeReaderWrapper<D, C> {
-    pub fn new(reader: Arc<SegmentReader<D, C>>) -> Result<MergeReaderWrapper<D, C>> {
+impl<C: Codec> MergeReaderWrapper<C> {
+    pub fn new(reader: Arc<SearchLeafReader<C>>) -> Result<MergeReaderWrapper<C>> {
         let fields = reader.postings_reader()?;
         let norms = reader.norms_reader()?;
         let doc_values = match reader.doc_values_reader()? {
@@ -88,7 +88,7 @@ impl<D: Directory + 'static, C: Codec> MergeReaderWrapper<D, C> {
     }
 }
 
-impl<D: Directory + 'static, C: Codec> LeafReader for MergeReaderWrapper<D, C> {
+impl<C: Codec> LeafReader for MergeReaderWrapper<C> {
     type Codec = C;
     type FieldsProducer = CodecFieldsProducer<C>;
     type TVFields = CodecTVFields<C>;
@@ -109,6 +109,10 @@ impl<D: Directory + 'static, C: Codec> LeafReader for MergeReaderWrapper<D, C> {
         self.reader.name()
     }
 
+    fn segment_id(&self) -> Option<&[u8]> {
+        self.reader.segment_id()
+    }
+
     fn term_vector(&self, doc_id: i32) -> Result<Option<CodecTVFields<C>>> {
         self.check_bounds(doc_id)?;
         if let Some(ref vectors) = self.vectors {
@@ -302,6 +306,10 @@ impl<T: LeafReader + 'static> LeafReader for SortingLeafReader<T> {
         self.reader.name()
     }
 
+    fn segment_id(&self) -> Option<&[u8]> {
+        self.reader.segment_id()
+    }
+
     fn term_vector(&self, doc_id: DocId) -> Result<Option<Self::TVFields>> {
         self.reader.term_vector(self.doc_map.new_to_old(doc_id))
     }
@@ -1333,6 +1341,10 @@ impl<T: LeafReader + 'static> LeafReader for SlowCodecReaderWrapper<T> {
         self.reader.name()
     }
 
+    fn segment_id(&self) -> Option<&[u8]> {
+        self.reader.segment_id()
+    }
+
     fn term_vector(&self, doc_id: DocId) -> Result<Option<Self::TVFields>> {
         self.reader.term_vector(doc_id)
     }
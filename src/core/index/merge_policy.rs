@@ -15,9 +15,10 @@ use core::codec::Codec;
 use core::index::index_writer::IndexWriter;
 use core::index::merge_rate_limiter::MergeRateLimiter;
 use core::index::merge_scheduler::MergeScheduler;
-use core::index::{SegmentCommitInfo, SegmentInfos, SegmentReader};
+use core::index::{DocValuesType, LeafReader, SegmentCommitInfo, SegmentInfos, SegmentReader};
 use core::store::{Directory, MergeInfo};
-use core::util::{ptr_eq, Volatile};
+use core::util::bit_set::{BitSet, FixedBitSet};
+use core::util::{ptr_eq, BitsRef, DocId, Volatile};
 
 use error::{
     ErrorKind::{IllegalArgument, RuntimeError},
@@ -134,6 +135,17 @@ pub trait MergePolicy: 'static {
 
     fn no_cfs_ratio(&self) -> f64;
 
+    /// Returns the soft deleted docs of `reader` a merge must keep, see
+    /// `IndexWriterConfig::set_soft_deletes_field`. The default keeps none of
+    /// them, so that they are dropped by the merge like the deleted docs.
+    fn retained_soft_deletes<D, C>(&self, _reader: &SegmentReader<D, C>) -> Result<Option<BitsRef>>
+    where
+        D: Directory + 'static,
+        C: Codec,
+    {
+        Ok(None)
+    }
+
     ///
     // Returns true if a new segment (regardless of its origin) should use the
     // compound file format. The default implementation returns <code>true</code>
@@ -260,6 +272,9 @@ pub struct OneMerge<D: Directory, C: Codec> {
     // Sum of sizeInBytes of all SegmentInfos; set by IW.mergeInit
     pub total_merge_bytes: u64,
     pub readers: Vec<Arc<SegmentReader<D, C>>>,
+    /// The live docs of the segments when the merge started, without the soft
+    /// deleted docs the readers may also exclude.
+    pub hard_live_docs: Vec<BitsRef>,
     /// segments to be merged
     pub segments: Vec<Arc<SegmentCommitInfo<D, C>>>,
     /// a private `RateLimiter` for this merge, used to rate limit writes and abort.
@@ -291,6 +306,7 @@ impl<D: Directory, C: Codec> OneMerge<D, C> {
             estimated_merge_bytes: Arc::new(Volatile::new(0)),
             total_merge_bytes: 0,
             readers: vec![],
+            hard_live_docs: vec![],
             segments,
             rate_limiter,
             merge_start_time: Arc::new(Volatile::new(None)),
@@ -413,6 +429,7 @@ pub struct TieredMergePolicy {
     floor_segment_bytes: u32,
     segs_per_tier: f64,
     force_merge_deletes_pct_allowed: f64,
+    deletes_pct_allowed: f64,
     reclaim_deletes_weight: f64,
 }
 
@@ -427,6 +444,7 @@ impl Default for TieredMergePolicy {
             floor_segment_bytes: 2 * 1024 * 1024,
             segs_per_tier: 10.0,
             force_merge_deletes_pct_allowed: 10.0,
+            deletes_pct_allowed: 33.0,
             reclaim_deletes_weight: 2.0,
         }
     }
@@ -472,6 +490,84 @@ impl TieredMergePolicy {
         Ok(())
     }
 
+    /// Sets the allowed number of segments per tier. Smaller values mean more
+    /// merging but fewer segments. Default is 10.0.
+    pub fn set_segments_per_tier(&mut self, v: f64) -> Result<()> {
+        if v < 2.0 {
+            bail!(IllegalArgument(format!(
+                "segments_per_tier must be >= 2.0, got {}",
+                v
+            )));
+        }
+        self.segs_per_tier = v;
+        Ok(())
+    }
+
+    /// Segments smaller than this are "rounded up" to this size, ie treated
+    /// as equal (floor) size for merge selection. This is to prevent
+    /// frequent flushing of tiny segments from allowing a long tail in the
+    /// index. Default is 2 MB.
+    pub fn set_floor_segment_mb(&mut self, mut v: f64) -> Result<()> {
+        if v <= 0.0 {
+            bail!(IllegalArgument(format!(
+                "floor_segment_mb must be > 0.0, got {}",
+                v
+            )));
+        }
+        v *= 1024.0 * 1024.0;
+        self.floor_segment_bytes = if v > u32::max_value() as f64 {
+            u32::max_value()
+        } else {
+            v as u32
+        };
+        Ok(())
+    }
+
+    /// Controls the maximum percentage of deleted docs of the segments too
+    /// large to be merged with others, see `set_max_merged_segment_mb`. Such
+    /// a segment with more deletes is rewritten on its own by the natural
+    /// merges. Default is 33%, allowed values are between 20 and 50.
+    pub fn set_deletes_pct_allowed(&mut self, v: f64) -> Result<()> {
+        if v < 20.0 || v > 50.0 {
+            bail!(IllegalArgument(format!(
+                "deletes_pct_allowed must be between 20.0 and 50.0 inclusive, got {}",
+                v
+            )));
+        }
+        self.deletes_pct_allowed = v;
+        Ok(())
+    }
+
+    /// Controls how aggressively merges that reclaim more deletions are
+    /// favored. Higher values will more aggressively target merges that
+    /// reclaim deletions, but be careful not to go so high that way too much
+    /// merging takes place; a value of 3.0 is probably nearly too high. A
+    /// value of 0.0 means deletions don't impact merge selection. Default is
+    /// 2.0.
+    pub fn set_reclaim_deletes_weight(&mut self, v: f64) -> Result<()> {
+        if v < 0.0 {
+            bail!(IllegalArgument(format!(
+                "reclaim_deletes_weight must be >= 0.0, got {}",
+                v
+            )));
+        }
+        self.reclaim_deletes_weight = v;
+        Ok(())
+    }
+
+    /// When forceMergeDeletes is called, we only merge away a segment if
+    /// its delete percentage is over this threshold. Default is 10%.
+    pub fn set_force_merge_deletes_pct_allowed(&mut self, v: f64) -> Result<()> {
+        if v < 0.0 || v > 100.0 {
+            bail!(IllegalArgument(format!(
+                "force_merge_deletes_pct_allowed must be between 0.0 and 100.0 inclusive, got {}",
+                v
+            )));
+        }
+        self.force_merge_deletes_pct_allowed = v;
+        Ok(())
+    }
+
     fn floor_size(&self, bytes: i64) -> i64 {
         bytes.max(self.floor_segment_bytes as i64)
     }
@@ -586,6 +682,19 @@ impl MergePolicy for TieredMergePolicy {
             too_big_count += 1;
         }
 
+        // The too-large segments are never merged with others, so the ones
+        // with too many deletes are rewritten on their own to reclaim them
+        let mut spec = MergeSpecification::default();
+        for info in &infos_sorted[..too_big_count] {
+            let pct_deletes =
+                100.0 * writer.num_deleted_docs(info.as_ref()) as f64 / info.info.max_doc as f64;
+            if pct_deletes > self.deletes_pct_allowed && !merging.contains(&info.info.name) {
+                let merge = OneMerge::new(vec![Arc::clone(info)], writer.next_merge_id())?;
+                to_be_merged.insert(Arc::clone(info));
+                spec.add(merge);
+            }
+        }
+
         let mut last_seg_bytes = i64::max_value();
         let mut last_level_infos = vec![];
         let mut info_levels = vec![];
@@ -632,7 +741,6 @@ impl MergePolicy for TieredMergePolicy {
         }
 
         let allowed_seg_count_int = allowed_seg_count as u32;
-        let mut spec = MergeSpecification::default();
 
         // Cycle to possibly select more than one merge:
         loop {
@@ -779,11 +887,9 @@ impl MergePolicy for TieredMergePolicy {
         let mut eligible = vec![];
         let mut force_merge_running = false;
         let merging = &writer.merging_segments();
-        let mut segment_is_original = false;
 
         for info in &segment_infos.segments {
-            if let Some(is_original) = segments_to_merge.get(info) {
-                segment_is_original = *is_original;
+            if segments_to_merge.contains_key(info) {
                 if !merging.contains(&info.info.name) {
                     eligible.push(info);
                 } else {
@@ -796,13 +902,27 @@ impl MergePolicy for TieredMergePolicy {
             return Ok(None);
         }
 
-        if (max_segment_count > 1 && eligible.len() <= max_segment_count as usize)
-            || (max_segment_count == 1
-                && eligible.len() == 1
-                && (!segment_is_original
-                    || self.is_merged(segment_infos, eligible[0].as_ref(), writer)))
-        {
-            return Ok(None);
+        if eligible.len() <= max_segment_count as usize {
+            // no segments need to be merged together, but the ones which were
+            // there when the forced merge started are rewritten alone to
+            // reclaim their deletes, the merged ones are not original anymore.
+            // A single segment is also rewritten to fix its compound format.
+            let mut spec = MergeSpecification::default();
+            for info in eligible {
+                let rewrite = if max_segment_count == 1 {
+                    !self.is_merged(segment_infos, info, writer)
+                } else {
+                    writer.num_deleted_docs(info) > 0
+                };
+                if segments_to_merge[info] && rewrite {
+                    spec.add(OneMerge::new(vec![Arc::clone(info)], writer.next_merge_id())?);
+                }
+            }
+            if spec.merges.is_empty() {
+                return Ok(None);
+            } else {
+                return Ok(Some(spec));
+            }
         }
 
         {
@@ -910,6 +1030,539 @@ impl MergePolicy for TieredMergePolicy {
     }
 }
 
+/// A `MergePolicy` which never returns merges, for example when the segments
+/// are compacted by an external process.
+#[derive(Default)]
+pub struct NoMergePolicy;
+
+impl MergePolicy for NoMergePolicy {
+    fn find_merges<D, C, MS, MP>(
+        &self,
+        _merge_trigger: MergerTrigger,
+        _segment_infos: &SegmentInfos<D, C>,
+        _writer: &IndexWriter<D, C, MS, MP>,
+    ) -> Result<Option<MergeSpecification<D, C>>>
+    where
+        D: Directory + Send + Sync + 'static,
+        C: Codec,
+        MS: MergeScheduler,
+        MP: MergePolicy,
+    {
+        Ok(None)
+    }
+
+    fn find_forced_merges<D, C, MS, MP>(
+        &self,
+        _segment_infos: &SegmentInfos<D, C>,
+        _max_segment_count: u32,
+        _segments_to_merge: &HashMap<Arc<SegmentCommitInfo<D, C>>, bool>,
+        _writer: &IndexWriter<D, C, MS, MP>,
+    ) -> Result<Option<MergeSpecification<D, C>>>
+    where
+        D: Directory + Send + Sync + 'static,
+        C: Codec,
+        MS: MergeScheduler,
+        MP: MergePolicy,
+    {
+        Ok(None)
+    }
+
+    fn find_forced_deletes_mergers<D, C, MS, MP>(
+        &self,
+        _segments_infos: &SegmentInfos<D, C>,
+        _writer: &IndexWriter<D, C, MS, MP>,
+    ) -> Result<Option<MergeSpecification<D, C>>>
+    where
+        D: Directory + Send + Sync + 'static,
+        C: Codec,
+        MS: MergeScheduler,
+        MP: MergePolicy,
+    {
+        Ok(None)
+    }
+
+    fn max_cfs_segment_size(&self) -> u64 {
+        DEFAULT_MAX_CFS_SEGMENT_SIZE
+    }
+
+    fn no_cfs_ratio(&self) -> f64 {
+        DEFAULT_NO_CFS_RATIO
+    }
+}
+
+/// The span of the levels of `LogByteSizeMergePolicy`: the segments whose
+/// log size is within this span of the largest remaining one are in the same
+/// level.
+const LEVEL_LOG_SPAN: f64 = 0.75;
+
+/// Merges runs of adjacent segments of about the same size. The segments are
+/// grouped in levels of the logarithm of their byte size in base
+/// `merge_factor`, and each `merge_factor` segments of a level are merged.
+///
+/// Unlike `TieredMergePolicy`, only adjacent segments are merged, so the docs
+/// keep their order across merges.
+pub struct LogByteSizeMergePolicy {
+    merge_factor: u32,
+    min_merge_bytes: u64,
+    max_merge_bytes: u64,
+    max_merge_docs: u32,
+    no_cfs_ratio: f64,
+    max_cfs_segment_size: u64,
+}
+
+impl Default for LogByteSizeMergePolicy {
+    fn default() -> Self {
+        LogByteSizeMergePolicy {
+            merge_factor: 10,
+            min_merge_bytes: (1.6 * 1024.0 * 1024.0) as u64,
+            max_merge_bytes: 2048 * 1024 * 1024,
+            max_merge_docs: i32::max_value() as u32,
+            no_cfs_ratio: DEFAULT_NO_CFS_RATIO,
+            max_cfs_segment_size: DEFAULT_MAX_CFS_SEGMENT_SIZE,
+        }
+    }
+}
+
+impl LogByteSizeMergePolicy {
+    /// Sets how many segments are merged at once, and the base of the log
+    /// sizes of the levels. Default is 10.
+    pub fn set_merge_factor(&mut self, v: u32) -> Result<()> {
+        if v < 2 {
+            bail!(IllegalArgument(format!(
+                "merge_factor must be > 1, got {}",
+                v
+            )));
+        }
+        self.merge_factor = v;
+        Ok(())
+    }
+
+    /// The segments smaller than this are all in the lowest level, so that
+    /// tiny flushed segments are merged together. Default is 1.6 MB.
+    pub fn set_min_merge_mb(&mut self, v: f64) -> Result<()> {
+        if v < 0.0 {
+            bail!(IllegalArgument(format!(
+                "min_merge_mb must be >= 0, got {}",
+                v
+            )));
+        }
+        self.min_merge_bytes = mb_to_bytes(v);
+        Ok(())
+    }
+
+    /// The segments larger than this are never merged by the natural merges.
+    /// Default is 2 GB.
+    pub fn set_max_merge_mb(&mut self, v: f64) -> Result<()> {
+        if v < 0.0 {
+            bail!(IllegalArgument(format!(
+                "max_merge_mb must be >= 0, got {}",
+                v
+            )));
+        }
+        self.max_merge_bytes = mb_to_bytes(v);
+        Ok(())
+    }
+
+    /// The segments with more docs than this are never merged by the natural
+    /// merges. Deleted docs are not counted.
+    pub fn set_max_merge_docs(&mut self, v: u32) {
+        self.max_merge_docs = v;
+    }
+
+    fn size_docs<D, C, MS, MP>(
+        &self,
+        info: &SegmentCommitInfo<D, C>,
+        writer: &IndexWriter<D, C, MS, MP>,
+    ) -> u32
+    where
+        D: Directory + Send + Sync + 'static,
+        C: Codec,
+        MS: MergeScheduler,
+        MP: MergePolicy,
+    {
+        info.info.max_doc as u32 - writer.num_deleted_docs(info)
+    }
+
+    /// Returns the merges of the `last` first segments down to
+    /// `max_num_segments`: the runs of `merge_factor` segments first, then a
+    /// final partial merge once there are no full ones left.
+    fn find_forced_merges_max_num_segments<D, C, MS, MP>(
+        &self,
+        infos: &SegmentInfos<D, C>,
+        max_num_segments: u32,
+        mut last: usize,
+        writer: &IndexWriter<D, C, MS, MP>,
+    ) -> Result<Option<MergeSpecification<D, C>>>
+    where
+        D: Directory + Send + Sync + 'static,
+        C: Codec,
+        MS: MergeScheduler,
+        MP: MergePolicy,
+    {
+        let segments = &infos.segments;
+        let merge_factor = self.merge_factor as usize;
+        let max_num_segments = max_num_segments as usize;
+        let mut spec = MergeSpecification::default();
+
+        // First, enroll all "full" merges (size merge_factor) to potentially
+        // be run concurrently:
+        while last + 1 >= merge_factor + max_num_segments {
+            let merge = OneMerge::new(
+                segments[last - merge_factor..last].to_vec(),
+                writer.next_merge_id(),
+            )?;
+            spec.add(merge);
+            last -= merge_factor;
+        }
+
+        // Only if there are no full merges pending do we add a final partial
+        // (< merge_factor segments) merge:
+        if spec.merges.is_empty() {
+            if max_num_segments == 1 {
+                // Since we must merge down to 1 segment, the choice is simple:
+                if last > 1 || !self.is_merged(infos, segments[0].as_ref(), writer) {
+                    spec.add(OneMerge::new(
+                        segments[..last].to_vec(),
+                        writer.next_merge_id(),
+                    )?);
+                }
+            } else if last > max_num_segments {
+                // Take care to pick a partial merge that is least cost, but
+                // does not make the index too lopsided:
+                let final_merge_size = last - max_num_segments + 1;
+                let mut best_size = 0;
+                let mut best_start = 0;
+                for i in 0..=last - final_merge_size {
+                    let sum_size: i64 = segments[i..i + final_merge_size]
+                        .iter()
+                        .map(|info| self.size(info.as_ref(), writer))
+                        .sum();
+                    if i == 0
+                        || (sum_size < 2 * self.size(segments[i - 1].as_ref(), writer)
+                            && sum_size < best_size)
+                    {
+                        best_start = i;
+                        best_size = sum_size;
+                    }
+                }
+                spec.add(OneMerge::new(
+                    segments[best_start..best_start + final_merge_size].to_vec(),
+                    writer.next_merge_id(),
+                )?);
+            }
+        }
+
+        if spec.merges.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(spec))
+        }
+    }
+}
+
+fn mb_to_bytes(mb: f64) -> u64 {
+    let bytes = mb * 1024.0 * 1024.0;
+    if bytes > i64::max_value() as f64 {
+        i64::max_value() as u64
+    } else {
+        bytes as u64
+    }
+}
+
+impl MergePolicy for LogByteSizeMergePolicy {
+    fn find_merges<D, C, MS, MP>(
+        &self,
+        _merge_trigger: MergerTrigger,
+        segment_infos: &SegmentInfos<D, C>,
+        writer: &IndexWriter<D, C, MS, MP>,
+    ) -> Result<Option<MergeSpecification<D, C>>>
+    where
+        D: Directory + Send + Sync + 'static,
+        C: Codec,
+        MS: MergeScheduler,
+        MP: MergePolicy,
+    {
+        let segments = &segment_infos.segments;
+        let merging = writer.merging_segments();
+        let norm = (self.merge_factor as f64).ln();
+
+        // the level of each segment, tiny segments are floored
+        let levels: Vec<f64> = segments
+            .iter()
+            .map(|info| (self.size(info.as_ref(), writer).max(1) as f64).ln() / norm)
+            .collect();
+        let level_floor = if self.min_merge_bytes == 0 {
+            0.0
+        } else {
+            (self.min_merge_bytes as f64).ln() / norm
+        };
+
+        let merge_factor = self.merge_factor as usize;
+        let mut spec = MergeSpecification::default();
+        let mut start = 0;
+        while start < segments.len() {
+            // Find max level of all segments not already quantized.
+            let max_level = levels[start..].iter().cloned().fold(f64::MIN, f64::max);
+
+            // Now search backwards for the rightmost segment that falls into
+            // this level:
+            let level_bottom = if max_level <= level_floor {
+                // All remaining segments fall into the min level
+                -1.0
+            } else {
+                // Force a boundary at the level floor
+                (max_level - LEVEL_LOG_SPAN).max(level_floor)
+            };
+            let mut upto = segments.len() - 1;
+            while upto > start && levels[upto] < level_bottom {
+                upto -= 1;
+            }
+
+            // Finally, record all merges that are viable at this level:
+            let mut end = start + merge_factor;
+            while end <= upto + 1 {
+                let candidate = &segments[start..end];
+                let any_merging = candidate
+                    .iter()
+                    .any(|info| merging.contains(&info.info.name));
+                let any_too_large = candidate.iter().any(|info| {
+                    self.size(info.as_ref(), writer) >= self.max_merge_bytes as i64
+                        || self.size_docs(info.as_ref(), writer) >= self.max_merge_docs
+                });
+                if !any_merging && !any_too_large {
+                    spec.add(OneMerge::new(candidate.to_vec(), writer.next_merge_id())?);
+                }
+                start = end;
+                end = start + merge_factor;
+            }
+            start = upto + 1;
+        }
+
+        if spec.merges.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(spec))
+        }
+    }
+
+    fn find_forced_merges<D, C, MS, MP>(
+        &self,
+        segment_infos: &SegmentInfos<D, C>,
+        max_segment_count: u32,
+        segments_to_merge: &HashMap<Arc<SegmentCommitInfo<D, C>>, bool>,
+        writer: &IndexWriter<D, C, MS, MP>,
+    ) -> Result<Option<MergeSpecification<D, C>>>
+    where
+        D: Directory + Send + Sync + 'static,
+        C: Codec,
+        MS: MergeScheduler,
+        MP: MergePolicy,
+    {
+        debug_assert!(max_segment_count > 0);
+        let segments = &segment_infos.segments;
+
+        // The newest (rightmost) segment to merge, the ones flushed since the
+        // forced merge started are left alone
+        let last = match segments
+            .iter()
+            .rposition(|info| segments_to_merge.contains_key(info))
+        {
+            Some(idx) => idx + 1,
+            None => return Ok(None),
+        };
+
+        // Nothing to do if the segments to merge are few enough, unless the
+        // only one left has to be rewritten
+        let mut num_to_merge = 0;
+        let mut merge_info = None;
+        for info in segments {
+            if let Some(is_original) = segments_to_merge.get(info) {
+                num_to_merge += 1;
+                merge_info = Some((info, *is_original));
+            }
+        }
+        if num_to_merge <= max_segment_count {
+            match merge_info {
+                Some((info, true)) if num_to_merge == 1 => {
+                    if self.is_merged(segment_infos, info.as_ref(), writer) {
+                        return Ok(None);
+                    }
+                }
+                _ => return Ok(None),
+            }
+        }
+
+        self.find_forced_merges_max_num_segments(segment_infos, max_segment_count, last, writer)
+    }
+
+    fn find_forced_deletes_mergers<D, C, MS, MP>(
+        &self,
+        segments_infos: &SegmentInfos<D, C>,
+        writer: &IndexWriter<D, C, MS, MP>,
+    ) -> Result<Option<MergeSpecification<D, C>>>
+    where
+        D: Directory + Send + Sync + 'static,
+        C: Codec,
+        MS: MergeScheduler,
+        MP: MergePolicy,
+    {
+        let segments = &segments_infos.segments;
+        let merge_factor = self.merge_factor as usize;
+        let mut spec = MergeSpecification::default();
+
+        // merges the runs of adjacent segments with deletes, by at most
+        // merge_factor segments
+        let mut first_with_deletions = None;
+        for (i, info) in segments.iter().enumerate() {
+            if writer.num_deleted_docs(info.as_ref()) > 0 {
+                match first_with_deletions {
+                    None => first_with_deletions = Some(i),
+                    Some(first) if i - first == merge_factor => {
+                        let merge =
+                            OneMerge::new(segments[first..i].to_vec(), writer.next_merge_id())?;
+                        spec.add(merge);
+                        first_with_deletions = Some(i);
+                    }
+                    _ => {}
+                }
+            } else if let Some(first) = first_with_deletions.take() {
+                spec.add(OneMerge::new(
+                    segments[first..i].to_vec(),
+                    writer.next_merge_id(),
+                )?);
+            }
+        }
+        if let Some(first) = first_with_deletions {
+            spec.add(OneMerge::new(
+                segments[first..].to_vec(),
+                writer.next_merge_id(),
+            )?);
+        }
+
+        if spec.merges.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(spec))
+        }
+    }
+
+    fn max_cfs_segment_size(&self) -> u64 {
+        self.max_cfs_segment_size
+    }
+
+    fn no_cfs_ratio(&self) -> f64 {
+        self.no_cfs_ratio
+    }
+}
+
+/// A `MergePolicy` which keeps the soft deleted docs whose value for a numeric
+/// doc values field is accepted by a predicate when merging, for example the
+/// docs soft deleted after some checkpoint which replicas may still need. The
+/// other soft deleted docs are dropped, and the merges are selected by the
+/// wrapped policy.
+///
+/// The soft deleted docs which do not have the field are dropped.
+pub struct SoftDeletesRetentionMergePolicy<MP: MergePolicy, F: Fn(i64) -> bool> {
+    base: MP,
+    field: String,
+    retain: F,
+}
+
+impl<MP, F> SoftDeletesRetentionMergePolicy<MP, F>
+where
+    MP: MergePolicy,
+    F: Fn(i64) -> bool + Send + Sync + 'static,
+{
+    pub fn new(base: MP, field: &str, retain: F) -> Self {
+        SoftDeletesRetentionMergePolicy {
+            base,
+            field: field.to_string(),
+            retain,
+        }
+    }
+}
+
+impl<MP, F> MergePolicy for SoftDeletesRetentionMergePolicy<MP, F>
+where
+    MP: MergePolicy,
+    F: Fn(i64) -> bool + Send + Sync + 'static,
+{
+    fn find_merges<D, C, MS, P>(
+        &self,
+        merge_trigger: MergerTrigger,
+        segment_infos: &SegmentInfos<D, C>,
+        writer: &IndexWriter<D, C, MS, P>,
+    ) -> Result<Option<MergeSpecification<D, C>>>
+    where
+        D: Directory + Send + Sync + 'static,
+        C: Codec,
+        MS: MergeScheduler,
+        P: MergePolicy,
+    {
+        self.base.find_merges(merge_trigger, segment_infos, writer)
+    }
+
+    fn find_forced_merges<D, C, MS, P>(
+        &self,
+        segment_infos: &SegmentInfos<D, C>,
+        max_segment_count: u32,
+        segments_to_merge: &HashMap<Arc<SegmentCommitInfo<D, C>>, bool>,
+        writer: &IndexWriter<D, C, MS, P>,
+    ) -> Result<Option<MergeSpecification<D, C>>>
+    where
+        D: Directory + Send + Sync + 'static,
+        C: Codec,
+        MS: MergeScheduler,
+        P: MergePolicy,
+    {
+        self.base
+            .find_forced_merges(segment_infos, max_segment_count, segments_to_merge, writer)
+    }
+
+    fn find_forced_deletes_mergers<D, C, MS, P>(
+        &self,
+        segments_infos: &SegmentInfos<D, C>,
+        writer: &IndexWriter<D, C, MS, P>,
+    ) -> Result<Option<MergeSpecification<D, C>>>
+    where
+        D: Directory + Send + Sync + 'static,
+        C: Codec,
+        MS: MergeScheduler,
+        P: MergePolicy,
+    {
+        self.base.find_forced_deletes_mergers(segments_infos, writer)
+    }
+
+    fn max_cfs_segment_size(&self) -> u64 {
+        self.base.max_cfs_segment_size()
+    }
+
+    fn no_cfs_ratio(&self) -> f64 {
+        self.base.no_cfs_ratio()
+    }
+
+    fn retained_soft_deletes<D, C>(&self, reader: &SegmentReader<D, C>) -> Result<Option<BitsRef>>
+    where
+        D: Directory + 'static,
+        C: Codec,
+    {
+        match reader.field_info(&self.field) {
+            Some(fi) if fi.doc_values_type == DocValuesType::Numeric => {}
+            _ => return Ok(None),
+        }
+        let values = reader.get_numeric_doc_values(&self.field)?;
+        let docs_with_field = reader.get_docs_with_field(&self.field)?;
+        let max_doc = reader.max_doc() as usize;
+        let mut retained = FixedBitSet::new(max_doc);
+        for doc in 0..max_doc {
+            if docs_with_field.get(doc)? && (self.retain)(values.get(doc as DocId)?) {
+                retained.set(doc);
+            }
+        }
+        Ok(Some(Arc::new(retained)))
+    }
+}
+
 struct SegmentByteSizeDescending<
     'a,
     D: Directory + Send + Sync + 'static,
@@ -51,19 +51,18 @@ enum PauseResult {
 
 impl MergeRateLimiter {
     pub fn new() -> Self {
-        let limiter = MergeRateLimiter {
+        // unlimited until the merge scheduler sets a rate
+        MergeRateLimiter {
             total_bytes_written: AtomicU64::new(0),
-            mb_per_sec: Volatile::new(0.0),
+            mb_per_sec: Volatile::new(f64::INFINITY),
             last_time: Volatile::new(SystemTime::now()),
-            min_pause_check_bytes: Volatile::new(0),
+            min_pause_check_bytes: Volatile::new(MAX_MIN_PAUSE_CHECK_BYTES),
             abort: AtomicBool::new(false),
             total_paused_dur: Volatile::new(Duration::default()),
             total_stopped_dur: Volatile::new(Duration::default()),
             lock: Mutex::new(()),
             cond: Condvar::new(),
-        };
-        limiter.set_mb_per_sec(f64::INFINITY);
-        limiter
+        }
     }
 
     fn maybe_pause(&self, bytes: u64, cur_ns: SystemTime) -> Result<PauseResult> {
@@ -128,13 +127,18 @@ impl MergeRateLimiter {
 
 const MIN_PAUSE_CHECK_MSEC: i32 = 25;
 
+const MAX_MIN_PAUSE_CHECK_BYTES: u64 = 64 * 1024 * 1024;
+
 impl RateLimiter for MergeRateLimiter {
-    fn set_mb_per_sec(&self, mb_per_sec: f64) {
-        let _g = self.lock.lock().unwrap();
+    fn set_mb_per_sec(&self, mb_per_sec: f64) -> Result<()> {
         // 0.0 is allowed: it means the merge is paused
-        if mb_per_sec < 0.0 {
-            panic!("mb_per_sec must be position; got: {}", mb_per_sec);
+        if mb_per_sec.is_nan() || mb_per_sec < 0.0 {
+            bail!(ErrorKind::IllegalArgument(format!(
+                "mb_per_sec must be positive; got: {}",
+                mb_per_sec
+            )));
         }
+        let _g = self.lock.lock()?;
 
         self.mb_per_sec.write(mb_per_sec);
         // NOTE: java Double.POSITIVE_INFINITY cast to long is long.MAX_VALUE,
@@ -147,8 +151,9 @@ impl RateLimiter for MergeRateLimiter {
             check_value as u64
         };
         self.min_pause_check_bytes
-            .write(::std::cmp::min(64 * 1024 * 1024, check_bytes));
+            .write(::std::cmp::min(MAX_MIN_PAUSE_CHECK_BYTES, check_bytes));
         self.cond.notify_one();
+        Ok(())
     }
 
     fn mb_per_sec(&self) -> f64 {
@@ -15,7 +15,7 @@ use core::codec::Codec;
 use core::index;
 use core::index::index_writer::IndexWriter;
 use core::index::merge_policy::{MergePolicy, MergerTrigger, OneMerge, OneMergeScheduleInfo};
-use core::store::{Directory, RateLimiter};
+use core::store::{Directory, RateLimiter, SimpleRateLimiter};
 
 use error::{Error, ErrorKind, Result};
 
@@ -136,6 +136,13 @@ impl Ord for MergeTaskInfo {
 ///
 /// If more than *max_merge_count* merges are requested then this class will forcefully
 /// throttle the incoming threads by pausing until one more more merges complete.
+///
+/// With the auto IO throttle, the write rate of the big merges is raised when
+/// merges fall behind and lowered otherwise, see `set_directory_rate_limiter`
+/// to apply it to all the merges together.
+///
+/// Closing the scheduler, which the writer does on close, waits for the
+/// running merge threads to finish.
 #[derive(Clone)]
 pub struct ConcurrentMergeScheduler {
     inner: Arc<ConcurrentMergeSchedulerInner>,
@@ -157,6 +164,85 @@ impl ConcurrentMergeScheduler {
             inner: Arc::new(ConcurrentMergeSchedulerInner::new(max_thread_count)),
         }
     }
+
+    /// Sets the maximum number of merges that may be pending before the
+    /// threads producing segments are stalled, and the maximum number of big
+    /// merges running at once, the others being paused.
+    pub fn set_max_merges_and_threads(
+        &self,
+        max_merge_count: usize,
+        max_thread_count: usize,
+    ) -> Result<()> {
+        if max_thread_count == 0 {
+            bail!(ErrorKind::IllegalArgument(
+                "max_thread_count should be at least 1".into()
+            ));
+        }
+        if max_merge_count < max_thread_count {
+            bail!(ErrorKind::IllegalArgument(format!(
+                "max_merge_count should be at least max_thread_count: {} < {}",
+                max_merge_count, max_thread_count
+            )));
+        }
+        let guard = self.inner.lock.lock()?;
+        let scheduler = unsafe { self.inner.scheduler_mut(&guard) };
+        scheduler.max_merge_count = max_merge_count;
+        scheduler.max_thread_count = max_thread_count;
+        scheduler.update_merge_threads()?;
+        // the stalled threads may go on with a larger max_merge_count
+        scheduler.cond.notify_all();
+        Ok(())
+    }
+
+    pub fn max_merge_count(&self) -> usize {
+        self.inner.max_merge_count
+    }
+
+    pub fn max_thread_count(&self) -> usize {
+        self.inner.max_thread_count
+    }
+
+    /// Turns the auto IO throttle on or off, it is on by default. Without it
+    /// the merges are only throttled by the forced merges rate.
+    pub fn set_auto_io_throttle(&self, enabled: bool) -> Result<()> {
+        let guard = self.inner.lock.lock()?;
+        let scheduler = unsafe { self.inner.scheduler_mut(&guard) };
+        scheduler.do_auto_io_throttle = enabled;
+        scheduler.publish_target_rate()?;
+        scheduler.update_merge_threads()
+    }
+
+    /// Sets the write rate of the forced merges, unlimited by default.
+    pub fn set_force_merge_mb_per_sec(&self, mb_per_sec: f64) -> Result<()> {
+        if mb_per_sec.is_nan() || mb_per_sec <= 0.0 {
+            bail!(ErrorKind::IllegalArgument(format!(
+                "mb_per_sec must be positive; got: {}",
+                mb_per_sec
+            )));
+        }
+        let guard = self.inner.lock.lock()?;
+        let scheduler = unsafe { self.inner.scheduler_mut(&guard) };
+        scheduler.force_merge_mb_per_sec = mb_per_sec;
+        scheduler.update_merge_threads()
+    }
+
+    /// Returns the write rate the auto IO throttle currently targets for each
+    /// big merge.
+    pub fn target_mb_per_sec(&self) -> f64 {
+        let _guard = self.inner.lock.lock().unwrap();
+        self.inner.target_mb_per_sec
+    }
+
+    /// Keeps the rate of `rate_limiter` to the target of the auto IO
+    /// throttle. Passing the limiter of `IndexWriterConfig::set_merge_rate_limiter`
+    /// bounds the writes of all the merges together by the throttled rate,
+    /// and not only each merge.
+    pub fn set_directory_rate_limiter(&self, rate_limiter: Arc<SimpleRateLimiter>) -> Result<()> {
+        let guard = self.inner.lock.lock()?;
+        let scheduler = unsafe { self.inner.scheduler_mut(&guard) };
+        scheduler.directory_rate_limiter = Some(rate_limiter);
+        scheduler.publish_target_rate()
+    }
 }
 
 struct ConcurrentMergeSchedulerInner {
@@ -169,6 +255,7 @@ struct ConcurrentMergeSchedulerInner {
     target_mb_per_sec: f64,
     do_auto_io_throttle: bool,
     force_merge_mb_per_sec: f64,
+    directory_rate_limiter: Option<Arc<SimpleRateLimiter>>,
 }
 
 // Floor for IO write rate limit (we will never go any lower than this)
@@ -197,6 +284,7 @@ impl ConcurrentMergeSchedulerInner {
             target_mb_per_sec: START_MB_PER_SEC,
             do_auto_io_throttle: true,
             force_merge_mb_per_sec: f64::INFINITY,
+            directory_rate_limiter: None,
         }
     }
 
@@ -245,7 +333,7 @@ impl ConcurrentMergeSchedulerInner {
         (true, guard)
     }
 
-    fn update_merge_threads(&mut self) {
+    fn update_merge_threads(&mut self) -> Result<()> {
         let mut active_tasks: Vec<_> = self.merge_tasks.iter().collect();
         active_tasks.sort();
 
@@ -283,8 +371,22 @@ impl ConcurrentMergeSchedulerInner {
                 self.target_mb_per_sec
             };
 
-            task.merge.rate_limiter.set_mb_per_sec(new_mb_per_sec);
+            task.merge.rate_limiter.set_mb_per_sec(new_mb_per_sec)?;
+        }
+        Ok(())
+    }
+
+    /// Applies the target rate of the auto IO throttle to the directory rate
+    /// limiter, if any.
+    fn publish_target_rate(&self) -> Result<()> {
+        if let Some(ref rate_limiter) = self.directory_rate_limiter {
+            if self.do_auto_io_throttle {
+                rate_limiter.set_mb_per_sec(self.target_mb_per_sec)?;
+            } else {
+                rate_limiter.set_mb_per_sec(f64::INFINITY)?;
+            }
         }
+        Ok(())
     }
 
     fn merge_thread_count(&self) -> usize {
@@ -300,16 +402,16 @@ impl ConcurrentMergeSchedulerInner {
     fn update_io_throttle<D: Directory + Send + Sync + 'static, C: Codec>(
         &mut self,
         new_merge: &OneMerge<D, C>,
-    ) {
+    ) -> Result<()> {
         if !self.do_auto_io_throttle {
-            return;
+            return Ok(());
         }
 
         let merge_mb = bytes_to_mb(new_merge.estimated_merge_bytes.read());
         if merge_mb < MIN_BIG_MERGE_MB {
             // Only watch non-trivial merges for throttling; this is safe because the MP must
             // eventually have to do larger merges:
-            return;
+            return Ok(());
         }
 
         let now = SystemTime::now();
@@ -346,13 +448,14 @@ impl ConcurrentMergeSchedulerInner {
             // We are not falling behind: decrease IO throttle by 10%
             self.target_mb_per_sec = MIN_MERGE_MB_PER_SEC.max(self.target_mb_per_sec / 1.1);
         }
+        self.publish_target_rate()?;
 
         let rate = if new_merge.max_num_segments.get().is_some() {
             self.force_merge_mb_per_sec
         } else {
             self.target_mb_per_sec
         };
-        new_merge.rate_limiter.set_mb_per_sec(rate);
+        new_merge.rate_limiter.set_mb_per_sec(rate)
     }
 
     fn is_back_log(&self, now: SystemTime, merge_id: u32, estimated_merge_bytes: u64) -> bool {
@@ -397,7 +500,8 @@ impl MergeScheduler for ConcurrentMergeScheduler {
         if trigger == MergerTrigger::Closing {
             // Disable throttling on close:
             scheduler.target_mb_per_sec = MAX_MERGE_MB_PER_SEC;
-            scheduler.update_merge_threads();
+            scheduler.publish_target_rate()?;
+            scheduler.update_merge_threads()?;
         }
 
         // First, quickly run through the newly proposed merges
@@ -415,7 +519,7 @@ impl MergeScheduler for ConcurrentMergeScheduler {
             }
 
             if let Some(merge) = writer.next_merge() {
-                scheduler.update_io_throttle(&merge);
+                scheduler.update_io_throttle(&merge)?;
 
                 let sentinel = Arc::new(ThreadSentinel);
                 let live_sentinel = Arc::downgrade(&sentinel);
@@ -442,7 +546,7 @@ impl MergeScheduler for ConcurrentMergeScheduler {
                     live_sentinel,
                 };
                 scheduler.merge_tasks.push(merge_task);
-                scheduler.update_merge_threads();
+                scheduler.update_merge_threads()?;
             } else {
                 return Ok(());
             }
@@ -451,9 +555,24 @@ impl MergeScheduler for ConcurrentMergeScheduler {
     }
 
     fn close(&self) -> Result<()> {
-        // do nothing here
-        // this is safe because each MergeThread contain a IndexWriter make sure that
-        // IndexWrite live long enough before all the threads finish running
+        // Waits for the running merge threads, each of them holds an
+        // IndexWriter so it lives long enough, but the merges must not go on
+        // once the writer is closed. A merge thread closing the writer can't
+        // wait for itself.
+        let current_thread = thread::current().id();
+        let mut guard = self.inner.lock.lock()?;
+        while self
+            .inner
+            .merge_tasks
+            .iter()
+            .any(|t| t.thread_id != current_thread && t.thread_alive())
+        {
+            let (g, _) = self
+                .inner
+                .cond
+                .wait_timeout(guard, Duration::from_millis(25))?;
+            guard = g;
+        }
         Ok(())
     }
 }
@@ -488,7 +607,9 @@ impl<D: Directory + Send + Sync + 'static, C: Codec, MP: MergePolicy> MergeThrea
         scheduler_mut
             .merge_tasks
             .drain_filter(|t| t.merge.id == one_merge.id);
-        scheduler_mut.update_merge_threads();
+        if let Err(e) = scheduler_mut.update_merge_threads() {
+            error!("failed to update the merge threads: {:?}", e);
+        }
         // In case we had stalled indexing, we can now wake up
         // and possibly unstall:
         scheduler_mut.cond.notify_all();
@@ -508,3 +629,66 @@ impl<D: Directory + Send + Sync + 'static, C: Codec, MP: MergePolicy> MergeThrea
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::codec::{CodecEnum, Lucene62Codec};
+    use core::doc::{Field, FieldType};
+    use core::index::merge_policy::TieredMergePolicy;
+    use core::index::{IndexOptions, IndexReader, IndexWriterConfig};
+    use core::store::RAMDirectory;
+    use core::util::VariantValue;
+
+    #[test]
+    fn test_concurrent_merge_scheduler() {
+        let merge_scheduler = ConcurrentMergeScheduler::new(2);
+        assert!(merge_scheduler.set_max_merges_and_threads(1, 2).is_err());
+        assert!(merge_scheduler.set_max_merges_and_threads(3, 0).is_err());
+        merge_scheduler.set_max_merges_and_threads(3, 2).unwrap();
+        assert_eq!(merge_scheduler.max_merge_count(), 3);
+        let rate_limiter = Arc::new(SimpleRateLimiter::unlimited());
+        merge_scheduler
+            .set_directory_rate_limiter(Arc::clone(&rate_limiter))
+            .unwrap();
+        assert!(merge_scheduler.set_force_merge_mb_per_sec(0.0).is_err());
+        assert_eq!(rate_limiter.mb_per_sec(), merge_scheduler.target_mb_per_sec());
+
+        let mut merge_policy = TieredMergePolicy::default();
+        merge_policy.set_max_merge_at_once(2).unwrap();
+        merge_policy.set_segments_per_tier(2.0).unwrap();
+        let mut config = IndexWriterConfig::new(
+            Arc::new(CodecEnum::Lucene62(Lucene62Codec::default())),
+            merge_scheduler.clone(),
+            merge_policy,
+        );
+        config.set_merge_rate_limiter(Arc::clone(&rate_limiter));
+        let dir = Arc::new(RAMDirectory::default());
+        let writer = IndexWriter::new(dir, Arc::new(config)).unwrap();
+
+        let mut id_type = FieldType::default();
+        id_type.tokenized = false;
+        id_type.omit_norms = true;
+        id_type.index_options = IndexOptions::Docs;
+        // one segment per doc, merged in the background
+        for i in 0..50 {
+            let id = VariantValue::VString(format!("{}", i));
+            writer
+                .add_document(vec![Field::new("id".into(), id_type.clone(), Some(id), None)])
+                .unwrap();
+            writer.commit().unwrap();
+        }
+        let reader = writer.get_reader(true, false).unwrap();
+        assert_eq!(reader.num_docs(), 50);
+        assert!(reader.leaves().len() < 50);
+
+        // closing waits for the running merges
+        writer.close().unwrap();
+        let _guard = merge_scheduler.inner.lock.lock().unwrap();
+        assert!(merge_scheduler
+            .inner
+            .merge_tasks
+            .iter()
+            .all(|t| !t.thread_alive()));
+    }
+}
@@ -16,6 +16,7 @@ use core::codec::{
     CodecTVFields, CodecTVReader, DocValuesProducer, FieldsProducer, NormsProducer,
     StoredFieldsReader, TermVectorsReader,
 };
+use core::doc::{Document, DocumentStoredFieldVisitor};
 use core::index::leaf_reader_wrapper::{
     LeafReaderAsNormsProducer, LeafReaderAsStoreFieldsReader, LeafReaderAsTermVectorsReaderWrapper,
     MergeReaderWrapper, SlowCodecReaderWrapper, SortingFields, SortingLeafReader,
@@ -23,9 +24,10 @@ use core::index::leaf_reader_wrapper::{
 };
 use core::index::sorter::{MultiSorter, PackedLongDocMap, Sorter, SorterDocMap};
 use core::index::{
-    BinaryDocValuesRef, FieldInfo, FieldInfos, Fields, LeafReader, NumericDocValues,
-    NumericDocValuesRef, PointValues, SeekStatus, SegmentInfo, SegmentReader, SortedDocValuesRef,
-    SortedNumericDocValuesRef, SortedSetDocValuesRef, StoredFieldVisitor, TermIterator, Terms,
+    BinaryDocValuesRef, FieldInfo, FieldInfos, Fields, IndexReader, LeafReader, LeafReaderContext,
+    NumericDocValues, NumericDocValuesRef, PointValues, SearchLeafReader, SeekStatus, SegmentInfo,
+    SortedDocValuesRef, SortedNumericDocValuesRef, SortedSetDocValuesRef, StoredFieldVisitor,
+    TermIterator, Terms,
 };
 use core::search::sort::Sort;
 use core::util::external::deferred::Deferred;
@@ -57,17 +59,17 @@ pub struct MergeState<D: Directory + 'static, C: Codec> {
     pub stored_fields_readers: Vec<
         MergeStoredReaderEnum<
             CodecStoredFieldsReader<C>,
-            SortingLeafReader<MergeReaderWrapper<D, C>>,
+            SortingLeafReader<MergeReaderWrapper<C>>,
         >,
     >,
     pub term_vectors_readers: Vec<
-        Option<MergeTVReaderEnum<CodecTVReader<C>, SortingLeafReader<MergeReaderWrapper<D, C>>>>,
+        Option<MergeTVReaderEnum<CodecTVReader<C>, SortingLeafReader<MergeReaderWrapper<C>>>>,
     >,
     pub norms_producers: Vec<
         Option<
             MergeNormsReaderEnum<
                 CodecNormsProducer<C>,
-                SortingLeafReader<MergeReaderWrapper<D, C>>,
+                SortingLeafReader<MergeReaderWrapper<C>>,
             >,
         >,
     >,
@@ -79,11 +81,14 @@ pub struct MergeState<D: Directory + 'static, C: Codec> {
     pub max_docs: Vec<i32>,
     /// Indicates if the index needs to be sorted
     pub needs_index_sort: bool,
+    /// Whether the order of the merged postings is checked, see
+    /// `IndexWriterConfig::check_merged_postings`
+    pub check_postings_order: bool,
 }
 
 impl<D: Directory + 'static, C: Codec> MergeState<D, C> {
     pub fn new(
-        seg_readers: Vec<Arc<SegmentReader<D, C>>>,
+        seg_readers: Vec<Arc<SearchLeafReader<C>>>,
         segment_info: &SegmentInfo<D, C>,
     ) -> Result<Self> {
         let num_readers = seg_readers.len();
@@ -93,7 +98,7 @@ impl<D: Directory + 'static, C: Codec> MergeState<D, C> {
             leaf_doc_maps.push(DocMapEnum::Default(DefaultDocMap::default()));
         }
         let mut needs_index_sort = false;
-        let readers: Vec<ReaderWrapperEnum<D, C>> = Self::maybe_sort_readers(
+        let readers: Vec<ReaderWrapperEnum<C>> = Self::maybe_sort_readers(
             seg_readers,
             segment_info,
             &mut leaf_doc_maps,
@@ -153,6 +158,7 @@ impl<D: Directory + 'static, C: Codec> MergeState<D, C> {
             points_readers,
             max_docs,
             needs_index_sort,
+            check_postings_order: false,
         })
     }
 
@@ -161,11 +167,11 @@ impl<D: Directory + 'static, C: Codec> MergeState<D, C> {
     }
 
     fn maybe_sort_readers(
-        seg_readers: Vec<Arc<SegmentReader<D, C>>>,
+        seg_readers: Vec<Arc<SearchLeafReader<C>>>,
         segment_info: &SegmentInfo<D, C>,
         leaf_doc_maps: &mut Vec<DocMapEnum>,
         needs_index_sort: &mut bool,
-    ) -> Result<Vec<ReaderWrapperEnum<D, C>>> {
+    ) -> Result<Vec<ReaderWrapperEnum<C>>> {
         if segment_info.index_sort.is_none() {
             let mut readers = Vec::with_capacity(seg_readers.len());
             for leaf in seg_readers {
@@ -196,7 +202,10 @@ impl<D: Directory + 'static, C: Codec> MergeState<D, C> {
 
                 // This segment was written by flush, so documents are not yet sorted, so we sort
                 // them now:
-                let sort_doc_map = sorter.sort_leaf_reader(&leaf.leaf_context())?;
+                let sort_doc_map = {
+                    let parent = LeafAsIndexReader(leaf.as_ref());
+                    sorter.sort_leaf_reader(&LeafReaderContext::new(&parent, leaf.as_ref(), 0, 0))?
+                };
                 if let Some(sort_doc_map) = sort_doc_map {
                     *needs_index_sort = true;
                     let doc_map_ref = Arc::new(sort_doc_map);
@@ -216,8 +225,8 @@ impl<D: Directory + 'static, C: Codec> MergeState<D, C> {
         Ok(readers)
     }
 
-    fn build_doc_maps<D1: Directory>(
-        readers: &[ReaderWrapperEnum<D1, C>],
+    fn build_doc_maps(
+        readers: &[ReaderWrapperEnum<C>],
         index_sort: Option<&Sort>,
         needs_index_sort: &mut bool,
     ) -> Result<Vec<LiveDocsDocMap>> {
@@ -233,8 +242,8 @@ impl<D: Directory + 'static, C: Codec> MergeState<D, C> {
     }
 
     /// remap doc_ids around deletions
-    fn build_deletion_doc_maps<D1: Directory>(
-        readers: &[ReaderWrapperEnum<D1, C>],
+    fn build_deletion_doc_maps(
+        readers: &[ReaderWrapperEnum<C>],
     ) -> Result<Vec<LiveDocsDocMap>> {
         let mut total_docs = 0;
         let num_readers = readers.len();
@@ -267,23 +276,53 @@ impl<D: Directory + 'static, C: Codec> MergeState<D, C> {
     }
 }
 
-pub(crate) enum ReaderWrapperEnum<D: Directory + 'static, C: Codec> {
-    Segment(Arc<SegmentReader<D, C>>),
-    SortedSegment(SlowCodecReaderWrapper<SortingLeafReader<MergeReaderWrapper<D, C>>>),
+/// A single leaf reader seen as an `IndexReader`, to build the
+/// `LeafReaderContext` it is sorted with.
+struct LeafAsIndexReader<'a, C: Codec>(&'a SearchLeafReader<C>);
+
+impl<'a, C: Codec> IndexReader for LeafAsIndexReader<'a, C> {
+    type Codec = C;
+
+    fn leaves(&self) -> Vec<LeafReaderContext<'_, C>> {
+        vec![LeafReaderContext::new(self, self.0, 0, 0)]
+    }
+
+    fn term_vector(&self, doc_id: DocId) -> Result<Option<CodecTVFields<C>>> {
+        self.0.term_vector(doc_id)
+    }
+
+    fn document(&self, doc_id: DocId, fields: &[String]) -> Result<Document> {
+        let mut visitor = DocumentStoredFieldVisitor::new(fields);
+        self.0.document(doc_id, &mut visitor)?;
+        Ok(visitor.document())
+    }
+
+    fn max_doc(&self) -> i32 {
+        self.0.max_doc()
+    }
+
+    fn num_docs(&self) -> i32 {
+        self.0.num_docs()
+    }
+}
+
+pub(crate) enum ReaderWrapperEnum<C: Codec> {
+    Segment(Arc<SearchLeafReader<C>>),
+    SortedSegment(SlowCodecReaderWrapper<SortingLeafReader<MergeReaderWrapper<C>>>),
 }
 
-impl<D: Directory + 'static, C: Codec> LeafReader for ReaderWrapperEnum<D, C> {
+impl<C: Codec> LeafReader for ReaderWrapperEnum<C> {
     type Codec = C;
     type FieldsProducer = MergeFieldsProducer<CodecFieldsProducer<C>>;
     type TVFields = CodecTVFields<C>;
     type TVReader =
-        MergeTVReaderEnum<CodecTVReader<C>, SortingLeafReader<MergeReaderWrapper<D, C>>>;
+        MergeTVReaderEnum<CodecTVReader<C>, SortingLeafReader<MergeReaderWrapper<C>>>;
     type StoredReader = MergeStoredReaderEnum<
         CodecStoredFieldsReader<C>,
-        SortingLeafReader<MergeReaderWrapper<D, C>>,
+        SortingLeafReader<MergeReaderWrapper<C>>,
     >;
     type NormsReader =
-        MergeNormsReaderEnum<CodecNormsProducer<C>, SortingLeafReader<MergeReaderWrapper<D, C>>>;
+        MergeNormsReaderEnum<CodecNormsProducer<C>, SortingLeafReader<MergeReaderWrapper<C>>>;
     type PointsReader = MergePointValuesEnum<Arc<CodecPointsReader<C>>>;
 
     fn codec(&self) -> &Self::Codec {
@@ -311,6 +350,13 @@ impl<D: Directory + 'static, C: Codec> LeafReader for ReaderWrapperEnum<D, C> {
         }
     }
 
+    fn segment_id(&self) -> Option<&[u8]> {
+        match self {
+            ReaderWrapperEnum::Segment(s) => s.segment_id(),
+            ReaderWrapperEnum::SortedSegment(s) => s.segment_id(),
+        }
+    }
+
     fn term_vector(&self, doc_id: DocId) -> Result<Option<CodecTVFields<C>>> {
         match self {
             ReaderWrapperEnum::Segment(s) => LeafReader::term_vector(s.as_ref(), doc_id),
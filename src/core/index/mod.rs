@@ -23,6 +23,12 @@ mod index_writer;
 
 pub use self::index_writer::*;
 
+#[cfg(feature = "async")]
+mod async_writer;
+
+#[cfg(feature = "async")]
+pub use self::async_writer::*;
+
 mod norm_values_writer;
 
 pub use self::norm_values_writer::*;
@@ -51,6 +57,10 @@ mod doc_values;
 
 pub use self::doc_values::*;
 
+mod multi_doc_values;
+
+pub use self::multi_doc_values::*;
+
 mod doc_values_writer;
 
 pub use self::doc_values_writer::*;
@@ -143,6 +153,9 @@ pub mod doc_id_merger;
 mod bufferd_updates;
 mod byte_slice_reader;
 mod delete_policy;
+
+pub use self::delete_policy::*;
+
 mod doc_consumer;
 mod doc_values_term_iterator;
 mod doc_writer;
@@ -151,7 +164,11 @@ mod doc_writer_flush_queue;
 mod flush_control;
 mod flush_policy;
 mod index_commit;
+
+pub use self::index_commit::*;
+
 mod index_file_deleter;
+pub mod index_upgrader;
 pub mod index_writer_config;
 mod leaf_reader_wrapper;
 pub mod merge_policy;
@@ -174,11 +191,13 @@ use std::fmt;
 use std::hash::{Hash, Hasher};
 use std::result;
 use std::sync::atomic::{AtomicBool, AtomicI32, AtomicI64, Ordering as AtomicOrdering};
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 
 use regex::Regex;
 
-use core::codec::{Codec, CodecTVFields, FieldsProducer, LiveDocsFormat};
+use core::codec::{
+    Codec, CodecTVFields, FieldsProducer, LiveDocsFormat, NormsFormat, NormsProducer,
+};
 use core::doc::Document;
 use core::index::bufferd_updates::BufferedUpdates;
 use core::search::sort::Sort;
@@ -705,16 +724,16 @@ pub struct SegmentCommitInfo<D: Directory, C: Codec> {
     /// write
     pub next_write_field_infos_gen: AtomicI64,
     /// Generation number of the DocValues (-1 if there are no updates)
-    pub doc_values_gen: i64,
+    doc_values_gen: AtomicI64,
     /// Normally 1+dvGen, unless an exception was hit on last attempt to
     /// write
     pub next_write_doc_values_gen: AtomicI64,
     /// Track the per-field DocValues update files
-    pub dv_updates_files: HashMap<i32, HashSet<String>>,
+    dv_updates_files: RwLock<HashMap<i32, HashSet<String>>>,
     /// TODO should we add .files() to FieldInfosFormat, like we have on
     /// LiveDocsFormat?
     /// track the fieldInfos update files
-    pub field_infos_files: HashSet<String>,
+    field_infos_files: RwLock<HashSet<String>>,
 
     pub size_in_bytes: AtomicI64,
     // NOTE: only used in-RAM by IW to track buffered deletes;
@@ -750,14 +769,14 @@ impl<D: Directory, C: Codec> SegmentCommitInfo<D, C> {
             next_write_del_gen: AtomicI64::new(if del_gen == -1 { 1i64 } else { del_gen + 1 }),
             field_infos_gen: AtomicI64::new(field_infos_gen),
             next_write_field_infos_gen: AtomicI64::new(field_info_gen),
-            doc_values_gen,
+            doc_values_gen: AtomicI64::new(doc_values_gen),
             next_write_doc_values_gen: AtomicI64::new(if doc_values_gen == -1 {
                 1
             } else {
                 doc_values_gen + 1
             }),
-            dv_updates_files,
-            field_infos_files,
+            dv_updates_files: RwLock::new(dv_updates_files),
+            field_infos_files: RwLock::new(field_infos_files),
             size_in_bytes: AtomicI64::new(-1),
             buffered_deletes_gen: AtomicI64::new(0),
         }
@@ -776,14 +795,14 @@ impl<D: Directory, C: Codec> SegmentCommitInfo<D, C> {
         self.info.codec().live_docs_format().files(self, &mut files);
 
         // must separately add any field updates files
-        for (_, fs) in &self.dv_updates_files {
+        for fs in self.dv_updates_files.read().unwrap().values() {
             for f in fs {
                 files.insert(f.clone());
             }
         }
 
         // must separately add field_infos files
-        for f in &self.field_infos_files {
+        for f in self.field_infos_files.read().unwrap().iter() {
             files.insert(f.clone());
         }
 
@@ -824,6 +843,10 @@ impl<D: Directory, C: Codec> SegmentCommitInfo<D, C> {
             .store(gen, AtomicOrdering::Release)
     }
 
+    pub fn doc_values_gen(&self) -> i64 {
+        self.doc_values_gen.load(AtomicOrdering::Acquire)
+    }
+
     pub fn next_write_doc_values_gen(&self) -> i64 {
         self.next_write_doc_values_gen.load(AtomicOrdering::Acquire)
     }
@@ -833,6 +856,50 @@ impl<D: Directory, C: Codec> SegmentCommitInfo<D, C> {
             .store(gen, AtomicOrdering::Release);
     }
 
+    pub fn advance_doc_values_gen(&self) {
+        self.doc_values_gen
+            .store(self.next_write_doc_values_gen(), AtomicOrdering::Release);
+        self.next_write_doc_values_gen
+            .store(self.doc_values_gen() + 1, AtomicOrdering::Release);
+        self.size_in_bytes.store(-1, AtomicOrdering::Release);
+    }
+
+    /// Called if there was an exception while writing a new generation of
+    /// doc values, so that we don't try to write to the same file more than
+    /// once.
+    pub fn advance_next_write_doc_values_gen(&self) {
+        self.next_write_doc_values_gen
+            .fetch_add(1, AtomicOrdering::AcqRel);
+    }
+
+    /// Called if there was an exception while writing a new generation of
+    /// field infos, so that we don't try to write to the same file more than
+    /// once.
+    pub fn advance_next_write_field_infos_gen(&self) {
+        self.next_write_field_infos_gen
+            .fetch_add(1, AtomicOrdering::AcqRel);
+    }
+
+    /// Returns the per-field doc values update files, key'd by field number.
+    pub fn dv_updates_files(&self) -> HashMap<i32, HashSet<String>> {
+        self.dv_updates_files.read().unwrap().clone()
+    }
+
+    pub fn set_dv_updates_files(&self, dv_updates_files: HashMap<i32, HashSet<String>>) {
+        *self.dv_updates_files.write().unwrap() = dv_updates_files;
+        self.size_in_bytes.store(-1, AtomicOrdering::Release);
+    }
+
+    /// Returns the field infos update files.
+    pub fn field_infos_files(&self) -> HashSet<String> {
+        self.field_infos_files.read().unwrap().clone()
+    }
+
+    pub fn set_field_infos_files(&self, field_infos_files: HashSet<String>) {
+        *self.field_infos_files.write().unwrap() = field_infos_files;
+        self.size_in_bytes.store(-1, AtomicOrdering::Release);
+    }
+
     pub fn advance_field_infos_gen(&self) {
         self.field_infos_gen
             .store(self.next_field_infos_gen(), AtomicOrdering::Release);
@@ -910,9 +977,9 @@ impl<D: Directory, C: Codec> Clone for SegmentCommitInfo<D, C> {
             self.del_count(),
             self.del_gen(),
             self.field_infos_gen(),
-            self.doc_values_gen,
-            self.dv_updates_files.clone(),
-            self.field_infos_files.clone(),
+            self.doc_values_gen(),
+            self.dv_updates_files(),
+            self.field_infos_files(),
         );
         // Not clear that we need to carry over nextWriteDelGen
         // (i.e. do we ever clone after a failed write and
@@ -955,13 +1022,13 @@ impl<D: Directory, C: Codec> Serialize for SegmentCommitInfo<D, C> {
                 .next_write_field_infos_gen
                 .load(AtomicOrdering::Acquire),
         )?;
-        s.serialize_field("doc_values_gen", &self.doc_values_gen)?;
+        s.serialize_field("doc_values_gen", &self.doc_values_gen())?;
         s.serialize_field(
             "next_write_doc_values_gen",
             &self.next_write_doc_values_gen(),
         )?;
-        s.serialize_field("dv_updates_files", &self.dv_updates_files)?;
-        s.serialize_field("field_infos_files", &self.field_infos_files)?;
+        s.serialize_field("dv_updates_files", &self.dv_updates_files())?;
+        s.serialize_field("field_infos_files", &self.field_infos_files())?;
         s.serialize_field("size_in_bytes", &self.size_in_bytes())?;
         s.end()
     }
@@ -1031,6 +1098,10 @@ pub struct SegmentWriteState<D: Directory, DW: Directory, C: Codec> {
     /// {@link IOContext} for all writes; you should pass this
     /// to {@link Directory#createOutput(String,IOContext)}. */
     pub context: IOContext,
+
+    /// The norms of this segment by field, once written, so that the
+    /// postings can record them in their impacts, see `load_norms`.
+    pub norms: HashMap<String, NumericDocValuesRef>,
 }
 
 impl<D: Directory, DW: Directory, C: Codec> SegmentWriteState<D, DW, C> {
@@ -1052,9 +1123,39 @@ impl<D: Directory, DW: Directory, C: Codec> SegmentWriteState<D, DW, C> {
             live_docs: FixedBitSet::default(),
             segment_suffix,
             context,
+            norms: HashMap::new(),
         }
     }
 
+    /// Opens the norms of this segment, which must have been written and
+    /// closed before, for the postings written afterwards.
+    pub fn load_norms(&mut self) -> Result<()> {
+        if !self.field_infos.has_norms {
+            return Ok(());
+        }
+        let field_infos = Arc::new(self.field_infos.clone());
+        let context = IOContext::READ;
+        let read_state = SegmentReadState::new(
+            Arc::clone(&self.directory),
+            &self.segment_info,
+            Arc::clone(&field_infos),
+            &context,
+            self.segment_suffix.clone(),
+        );
+        let producer = self
+            .segment_info
+            .codec()
+            .norms_format()
+            .norms_producer(&read_state)?;
+        for field_info in field_infos.by_number.values() {
+            if field_info.has_norms() {
+                let norms = producer.norms(field_info)?;
+                self.norms.insert(field_info.name.clone(), Arc::from(norms));
+            }
+        }
+        Ok(())
+    }
+
     pub fn seg_updates(&self) -> &BufferedUpdates<C> {
         debug_assert!(self.seg_updates.is_some());
         unsafe { &*self.seg_updates.unwrap() }
@@ -1093,6 +1194,7 @@ impl<D: Directory, DW: Directory, C: Codec> Clone for SegmentWriteState<D, DW, C
             // TODO, fake clone
             segment_suffix: self.segment_suffix.clone(),
             context: self.context,
+            norms: self.norms.clone(),
         }
     }
 }
@@ -1211,6 +1313,7 @@ pub mod tests {
         doc_base: DocId,
         live_docs: BitsRef,
         field_infos: FieldInfos,
+        index_sort: Option<Sort>,
     }
 
     impl MockLeafReader {
@@ -1252,8 +1355,14 @@ pub mod tests {
                 doc_base,
                 live_docs: Arc::new(MatchAllBits::new(0usize)),
                 field_infos: FieldInfos::new(infos).unwrap(),
+                index_sort: None,
             }
         }
+
+        pub fn with_index_sort(mut self, sort: Sort) -> MockLeafReader {
+            self.index_sort = Some(sort);
+            self
+        }
     }
 
     impl LeafReader for MockLeafReader {
@@ -1357,7 +1466,7 @@ pub mod tests {
         }
 
         fn index_sort(&self) -> Option<&Sort> {
-            None
+            self.index_sort.as_ref()
         }
 
         fn store_fields_reader(&self) -> Result<Self::StoredReader> {
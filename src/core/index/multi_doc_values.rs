@@ -0,0 +1,366 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::codec::Codec;
+use core::index::{
+    BinaryDocValues, BinaryDocValuesRef, DocValuesTermIterator, DocValuesType,
+    EmptyBinaryDocValues, EmptyNumericDocValues, EmptySortedDocValues, IndexReader,
+    LeafReaderContext, NumericDocValues, NumericDocValuesContext, NumericDocValuesRef,
+    OrdinalMap, SortedDocValues, SortedDocValuesRef, SortedDocValuesTermIterator,
+};
+use core::util::packed_misc::COMPACT;
+use core::util::DocId;
+
+use error::ErrorKind::IllegalArgument;
+use error::Result;
+
+use std::sync::Arc;
+
+/// Doc values of a composite reader, addressed by the global doc ids of the
+/// reader and read from the doc values of its segments.
+///
+/// Each read looks the segment of the doc up, and the sorted doc values map
+/// the ords of the segments to global ords, built on creation. This is much
+/// slower than reading the doc values of each `LeafReader`, which remains the
+/// way to go on hot paths like collectors: this view is meant for one-off
+/// lookups and tools.
+pub struct MultiDocValues;
+
+impl MultiDocValues {
+    /// Returns the numeric doc values of `field`, which are 0 for the docs of
+    /// the segments without, or None if no segment has numeric doc values for
+    /// `field`.
+    pub fn get_numeric<R: IndexReader + ?Sized>(
+        reader: &R,
+        field: &str,
+    ) -> Result<Option<NumericDocValuesRef>> {
+        let leaves = reader.leaves();
+        if !has_doc_values(&leaves, field, DocValuesType::Numeric) {
+            return Ok(None);
+        }
+        if leaves.len() == 1 {
+            return Ok(Some(leaves[0].reader.get_numeric_doc_values(field)?));
+        }
+        let mut values = Vec::with_capacity(leaves.len());
+        for leaf in &leaves {
+            if leaf_has_doc_values(leaf, field, DocValuesType::Numeric) {
+                values.push(leaf.reader.get_numeric_doc_values(field)?);
+            } else {
+                values.push(Arc::new(EmptyNumericDocValues) as NumericDocValuesRef);
+            }
+        }
+        Ok(Some(Arc::new(MultiNumericDocValues {
+            values,
+            starts: starts(reader, &leaves),
+        })))
+    }
+
+    /// Returns the binary doc values of `field`, which are empty for the docs
+    /// of the segments without, or None if no segment has binary doc values
+    /// for `field`.
+    pub fn get_binary<R: IndexReader + ?Sized>(
+        reader: &R,
+        field: &str,
+    ) -> Result<Option<BinaryDocValuesRef>> {
+        let leaves = reader.leaves();
+        if !has_doc_values(&leaves, field, DocValuesType::Binary) {
+            return Ok(None);
+        }
+        if leaves.len() == 1 {
+            return Ok(Some(leaves[0].reader.get_binary_doc_values(field)?));
+        }
+        let mut values = Vec::with_capacity(leaves.len());
+        for leaf in &leaves {
+            if leaf_has_doc_values(leaf, field, DocValuesType::Binary) {
+                values.push(leaf.reader.get_binary_doc_values(field)?);
+            } else {
+                values.push(Arc::new(EmptyBinaryDocValues) as BinaryDocValuesRef);
+            }
+        }
+        Ok(Some(Arc::new(MultiBinaryDocValues {
+            values,
+            starts: starts(reader, &leaves),
+        })))
+    }
+
+    /// Returns the sorted doc values of `field`, whose ords are global to the
+    /// segments, or None if no segment has sorted doc values for `field`. The
+    /// docs of the segments without have no value.
+    pub fn get_sorted<R: IndexReader + ?Sized>(
+        reader: &R,
+        field: &str,
+    ) -> Result<Option<SortedDocValuesRef>> {
+        let leaves = reader.leaves();
+        if !has_doc_values(&leaves, field, DocValuesType::Sorted) {
+            return Ok(None);
+        }
+        if leaves.len() == 1 {
+            return Ok(Some(leaves[0].reader.get_sorted_doc_values(field)?));
+        }
+        let mut values = Vec::with_capacity(leaves.len());
+        for leaf in &leaves {
+            if leaf_has_doc_values(leaf, field, DocValuesType::Sorted) {
+                values.push(leaf.reader.get_sorted_doc_values(field)?);
+            } else {
+                values.push(Arc::new(EmptySortedDocValues) as SortedDocValuesRef);
+            }
+        }
+        let mut terms = Vec::with_capacity(values.len());
+        let mut weights = Vec::with_capacity(values.len());
+        for dv in &values {
+            terms.push(Some(dv.term_iterator()?));
+            weights.push(dv.get_value_count());
+        }
+        let mapping = OrdinalMap::build(terms, weights, COMPACT)?;
+        Ok(Some(Arc::new(MultiSortedDocValues {
+            inner: Arc::new(MultiSortedDocValuesInner {
+                values,
+                starts: starts(reader, &leaves),
+                mapping,
+            }),
+        })))
+    }
+}
+
+fn leaf_has_doc_values<C: Codec>(
+    leaf: &LeafReaderContext<'_, C>,
+    field: &str,
+    dv_type: DocValuesType,
+) -> bool {
+    leaf.reader
+        .field_info(field)
+        .map_or(false, |fi| fi.doc_values_type == dv_type)
+}
+
+fn has_doc_values<C: Codec>(
+    leaves: &[LeafReaderContext<'_, C>],
+    field: &str,
+    dv_type: DocValuesType,
+) -> bool {
+    leaves
+        .iter()
+        .any(|leaf| leaf_has_doc_values(leaf, field, dv_type))
+}
+
+/// The doc bases of the segments, followed by the max doc of the reader.
+fn starts<R: IndexReader + ?Sized>(
+    reader: &R,
+    leaves: &[LeafReaderContext<'_, R::Codec>],
+) -> Vec<DocId> {
+    let mut starts: Vec<DocId> = leaves.iter().map(|leaf| leaf.doc_base).collect();
+    starts.push(reader.max_doc());
+    starts
+}
+
+/// Returns the index of the segment holding `doc`.
+fn sub_index(starts: &[DocId], doc: DocId) -> Result<usize> {
+    let max_doc = starts[starts.len() - 1];
+    if doc < 0 || doc >= max_doc {
+        bail!(IllegalArgument(format!(
+            "doc_id {} invalid: [max_doc={}]",
+            doc, max_doc
+        )));
+    }
+    // the last segment starting at or before `doc`, which skips the empty ones
+    let mut lo = 0;
+    let mut hi = starts.len() - 1;
+    while hi - lo > 1 {
+        let mid = (lo + hi) / 2;
+        if starts[mid] <= doc {
+            lo = mid;
+        } else {
+            hi = mid;
+        }
+    }
+    Ok(lo)
+}
+
+struct MultiNumericDocValues {
+    values: Vec<NumericDocValuesRef>,
+    starts: Vec<DocId>,
+}
+
+impl NumericDocValues for MultiNumericDocValues {
+    fn get_with_ctx(
+        &self,
+        ctx: NumericDocValuesContext,
+        doc_id: DocId,
+    ) -> Result<(i64, NumericDocValuesContext)> {
+        let i = sub_index(&self.starts, doc_id)?;
+        let value = self.values[i].get(doc_id - self.starts[i])?;
+        Ok((value, ctx))
+    }
+}
+
+struct MultiBinaryDocValues {
+    values: Vec<BinaryDocValuesRef>,
+    starts: Vec<DocId>,
+}
+
+impl BinaryDocValues for MultiBinaryDocValues {
+    fn get(&self, doc_id: DocId) -> Result<Vec<u8>> {
+        let i = sub_index(&self.starts, doc_id)?;
+        self.values[i].get(doc_id - self.starts[i])
+    }
+
+    fn for_each(
+        &self,
+        max_doc: DocId,
+        f: &mut dyn FnMut(DocId, &[u8]) -> Result<()>,
+    ) -> Result<()> {
+        for (i, values) in self.values.iter().enumerate() {
+            let start = self.starts[i];
+            if start >= max_doc {
+                break;
+            }
+            let end = self.starts[i + 1].min(max_doc);
+            values.for_each(end - start, &mut |doc: DocId, value: &[u8]| {
+                f(start + doc, value)
+            })?;
+        }
+        Ok(())
+    }
+}
+
+/// Sorted doc values whose ords are mapped to the global ords of an
+/// `OrdinalMap` over the terms of the segments.
+#[derive(Clone)]
+pub struct MultiSortedDocValues {
+    inner: Arc<MultiSortedDocValuesInner>,
+}
+
+struct MultiSortedDocValuesInner {
+    values: Vec<SortedDocValuesRef>,
+    starts: Vec<DocId>,
+    mapping: OrdinalMap,
+}
+
+impl SortedDocValues for MultiSortedDocValues {
+    fn get_ord(&self, doc_id: DocId) -> Result<i32> {
+        let inner = &self.inner;
+        let i = sub_index(&inner.starts, doc_id)?;
+        let segment_ord = inner.values[i].get_ord(doc_id - inner.starts[i])?;
+        if segment_ord == -1 {
+            return Ok(-1);
+        }
+        let global_ord = inner
+            .mapping
+            .get_global_ords(i)
+            .get64(i64::from(segment_ord))?;
+        Ok(global_ord as i32)
+    }
+
+    fn lookup_ord(&self, ord: i32) -> Result<Vec<u8>> {
+        let inner = &self.inner;
+        let segment = inner.mapping.first_segment_number(i64::from(ord)) as usize;
+        let segment_ord = inner.mapping.first_segment_ord(i64::from(ord));
+        inner.values[segment].lookup_ord(segment_ord as i32)
+    }
+
+    fn get_value_count(&self) -> usize {
+        self.inner.mapping.value_count() as usize
+    }
+
+    fn term_iterator(&self) -> Result<DocValuesTermIterator> {
+        let ti = SortedDocValuesTermIterator::new(self.clone());
+        Ok(DocValuesTermIterator::multi_sorted(ti))
+    }
+}
+
+impl BinaryDocValues for MultiSortedDocValues {
+    fn get(&self, doc_id: DocId) -> Result<Vec<u8>> {
+        let ord = self.get_ord(doc_id)?;
+        if ord == -1 {
+            Ok(Vec::with_capacity(0))
+        } else {
+            self.lookup_ord(ord)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::doc::{
+        BinaryDocValuesField, Field, NumericDocValuesField, SORTED_DOC_VALUES_FIELD_TYPE,
+    };
+    use core::index::{Fieldable, IndexWriter, IndexWriterConfig};
+    use core::store::RAMDirectory;
+
+    fn sorted_field(value: &str) -> Box<dyn Fieldable> {
+        Box::new(Field::new_bytes(
+            "color".into(),
+            value.as_bytes().to_vec(),
+            SORTED_DOC_VALUES_FIELD_TYPE,
+        ))
+    }
+
+    #[test]
+    fn test_multi_doc_values() {
+        let dir = Arc::new(RAMDirectory::default());
+        let writer = IndexWriter::new(dir, Arc::new(IndexWriterConfig::default())).unwrap();
+        let colors = ["red", "green", "blue"];
+        for (i, color) in colors.iter().enumerate() {
+            let doc: Vec<Box<dyn Fieldable>> = vec![
+                Box::new(NumericDocValuesField::new("number", i as i64 * 10)),
+                Box::new(BinaryDocValuesField::new("binary", color.as_bytes())),
+                sorted_field(color),
+            ];
+            writer.add_document(doc).unwrap();
+        }
+        writer.commit().unwrap();
+        // the second segment has no binary doc values, and misses some values
+        for i in 3..5 {
+            let doc: Vec<Box<dyn Fieldable>> =
+                vec![Box::new(NumericDocValuesField::new("number", i * 10))];
+            writer.add_document(doc).unwrap();
+        }
+        for color in &["yellow", "blue"] {
+            writer.add_document(vec![sorted_field(color)]).unwrap();
+        }
+        let reader = writer.get_reader(true, false).unwrap();
+        assert_eq!(reader.leaves().len(), 2);
+
+        let numbers = MultiDocValues::get_numeric(&reader, "number").unwrap().unwrap();
+        let values: Vec<i64> = (0..7).map(|doc| numbers.get(doc).unwrap()).collect();
+        assert_eq!(values, vec![0, 10, 20, 30, 40, 0, 0]);
+        assert!(numbers.get(7).is_err());
+
+        let binary = MultiDocValues::get_binary(&reader, "binary").unwrap().unwrap();
+        assert_eq!(binary.get(1).unwrap(), b"green".to_vec());
+        assert!(binary.get(4).unwrap().is_empty());
+        let mut scanned = vec![];
+        binary
+            .for_each(reader.max_doc(), &mut |doc: DocId, value: &[u8]| {
+                scanned.push((doc, value.to_vec()));
+                Ok(())
+            })
+            .unwrap();
+        let expected: Vec<_> = (0..7).map(|doc| (doc, binary.get(doc).unwrap())).collect();
+        assert_eq!(scanned, expected);
+
+        let sorted = MultiDocValues::get_sorted(&reader, "color").unwrap().unwrap();
+        // blue, green, red, yellow
+        assert_eq!(sorted.get_value_count(), 4);
+        let ords: Vec<i32> = (0..7).map(|doc| sorted.get_ord(doc).unwrap()).collect();
+        assert_eq!(ords, vec![2, 1, 0, -1, -1, 3, 0]);
+        assert_eq!(sorted.lookup_ord(3).unwrap(), b"yellow".to_vec());
+        assert_eq!(sorted.get(6).unwrap(), b"blue".to_vec());
+        assert_eq!(sorted.lookup_term(b"red").unwrap(), 2);
+
+        assert!(MultiDocValues::get_numeric(&reader, "color")
+            .unwrap()
+            .is_none());
+        writer.close().unwrap();
+    }
+}
@@ -416,6 +416,7 @@ struct FieldsMergeState<C: Codec> {
     doc_maps: Vec<Arc<LiveDocsDocMap>>,
     fields_producers: Vec<MergeFieldsProducer<CodecFieldsProducer<C>>>,
     needs_index_sort: bool,
+    check_postings_order: bool,
 }
 
 impl<C: Codec> FieldsMergeState<C> {
@@ -424,6 +425,7 @@ impl<C: Codec> FieldsMergeState<C> {
             doc_maps: state.doc_maps.clone(),
             fields_producers: state.fields_producers.clone(),
             needs_index_sort: state.needs_index_sort,
+            check_postings_order: state.check_postings_order,
         }
     }
 }
@@ -718,7 +720,7 @@ impl<C: Codec, T: TermIterator> TermIterator for MappedMultiTermsIterEnum<C, T>
 }
 
 pub struct MappingMultiPostingsIter<T: PostingIterator> {
-    _field: String,
+    doc_order_checker: DocOrderChecker,
     doc_id_merger: DocIdMergerEnum<MappingPostingsSub<T>>,
     current: *mut MappingPostingsSub<T>, // current is point to doc_id_merge
     // multi_docs_and_positions_iter: Option<MultiPostingsIterator>,
@@ -737,7 +739,7 @@ impl<T: PostingIterator> MappingMultiPostingsIter<T> {
         let doc_id_merger =
             doc_id_merger_of_count(vec![], all_subs.len(), merge_state.needs_index_sort)?;
         Ok(MappingMultiPostingsIter {
-            _field: field,
+            doc_order_checker: DocOrderChecker::new(field, merge_state.check_postings_order),
             doc_id_merger,
             current: ptr::null_mut(),
             // multi_docs_and_positions_iter: None,
@@ -761,6 +763,7 @@ impl<T: PostingIterator> MappingMultiPostingsIter<T> {
             sub.postings = Some(s.postings_iter);
             self.doc_id_merger.subs_mut().push(sub);
         }
+        self.doc_order_checker.reset();
         self.doc_id_merger.reset()
     }
 
@@ -816,7 +819,9 @@ impl<T: PostingIterator> DocIterator for MappingMultiPostingsIter<T> {
     fn next(&mut self) -> Result<DocId> {
         if let Some(sub) = self.doc_id_merger.next()? {
             self.current = sub;
-            Ok(sub.base().mapped_doc_id)
+            let doc = sub.base().mapped_doc_id;
+            self.doc_order_checker.check(doc)?;
+            Ok(doc)
         } else {
             self.current = ptr::null_mut();
             Ok(NO_MORE_DOCS)
@@ -836,6 +841,51 @@ impl<T: PostingIterator> DocIterator for MappingMultiPostingsIter<T> {
     }
 }
 
+/// Checks that the merged postings of a term have strictly increasing doc ids,
+/// which would not be the case if the doc maps of the merged segments were
+/// broken, e.g. with a wrong doc base.
+struct DocOrderChecker {
+    field: String,
+    // if false, only debug builds check the order
+    enabled: bool,
+    last_doc: DocId,
+}
+
+impl DocOrderChecker {
+    fn new(field: String, enabled: bool) -> Self {
+        DocOrderChecker {
+            field,
+            enabled,
+            last_doc: -1,
+        }
+    }
+
+    fn reset(&mut self) {
+        self.last_doc = -1;
+    }
+
+    fn check(&mut self, doc: DocId) -> Result<()> {
+        if self.enabled {
+            if doc <= self.last_doc {
+                bail!(CorruptIndex(format!(
+                    "merged postings of field '{}' are out of order: doc {} after doc {}",
+                    self.field, doc, self.last_doc
+                )));
+            }
+        } else {
+            debug_assert!(
+                doc > self.last_doc,
+                "merged postings of field '{}' are out of order: doc {} after doc {}",
+                self.field,
+                doc,
+                self.last_doc
+            );
+        }
+        self.last_doc = doc;
+        Ok(())
+    }
+}
+
 pub struct MappingPostingsSub<T: PostingIterator> {
     // postings: Option<MultiPostingIterEnum<T>>,
     postings: Option<T>,
@@ -980,3 +1030,35 @@ impl<T: PostingIterator> DocIterator for MappedMultiPostingIterEnum<T> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // maps the docs of each segment with its doc base, like `LiveDocsDocMap`
+    fn check_merged(doc_bases: &[DocId], segments: &[Vec<DocId>]) -> Result<()> {
+        let mut checker = DocOrderChecker::new("title".to_string(), true);
+        for (doc_base, docs) in doc_bases.iter().zip(segments) {
+            for doc in docs {
+                checker.check(doc_base + doc)?;
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_doc_order_checker() {
+        let segments = vec![vec![0, 1, 2], vec![0, 3], vec![1]];
+        assert!(check_merged(&[0, 3, 7], &segments).is_ok());
+
+        // the 2nd segment has a doc base off by one
+        assert!(check_merged(&[0, 2, 7], &segments).is_err());
+
+        // the checker starts over on each term
+        let mut checker = DocOrderChecker::new("title".to_string(), true);
+        checker.check(5).unwrap();
+        assert!(checker.check(5).is_err());
+        checker.reset();
+        assert!(checker.check(0).is_ok());
+    }
+}
@@ -79,6 +79,8 @@ pub struct SegmentInfos<D: Directory, C: Codec> {
     pub lucene_version: Option<Version>,
     /// Version of the oldest segment in the index, or null if there are no segments.
     pub min_seg_version: Option<Version>,
+    /// Opaque map (String -> String) that user can specify during `IndexWriter::commit`.
+    pub user_data: HashMap<String, String>,
     // Only true after prepareCommit has been called and
     // before finishCommit is called
     pending_commit: bool,
@@ -95,6 +97,7 @@ impl<D: Directory, C: Codec> Default for SegmentInfos<D, C> {
             id: [0u8; ID_LENGTH],
             lucene_version: None,
             min_seg_version: None,
+            user_data: HashMap::new(),
             pending_commit: false,
         }
     }
@@ -121,6 +124,7 @@ impl<D: Directory, C: Codec> SegmentInfos<D, C> {
             id,
             lucene_version,
             min_seg_version,
+            user_data: HashMap::new(),
             pending_commit: false,
         }
     }
@@ -281,12 +285,16 @@ impl<D: Directory, C: Codec> SegmentInfos<D, C> {
             }
             output.write_int(del_count)?;
             output.write_long(commit.field_infos_gen())?;
-            output.write_long(commit.doc_values_gen)?;
-            output.write_set_of_strings(&commit.field_infos_files)?;
-            // TODO add doc_values_updates_files
-            output.write_int(0)?;
+            output.write_long(commit.doc_values_gen())?;
+            output.write_set_of_strings(&commit.field_infos_files())?;
+            let dv_updates_files = commit.dv_updates_files();
+            output.write_int(dv_updates_files.len() as i32)?;
+            for (field_number, files) in &dv_updates_files {
+                output.write_int(*field_number)?;
+                output.write_set_of_strings(files)?;
+            }
         }
-        output.write_map_of_strings(&HashMap::with_capacity(0))?;
+        output.write_map_of_strings(&self.user_data)?;
         codec_util::write_footer(output)
     }
 
@@ -543,9 +551,9 @@ impl<D: Directory, C: Codec> SegmentInfos<D, C> {
                 // TODO check version
             }
         }
-        let _user_data = input.read_map_of_strings();
+        let user_data = input.read_map_of_strings()?;
 
-        Ok(SegmentInfos::new(
+        let mut infos = SegmentInfos::new(
             counter as i32,
             version,
             generation,
@@ -554,7 +562,9 @@ impl<D: Directory, C: Codec> SegmentInfos<D, C> {
             id,
             lucene_version,
             min_seg_ver,
-        ))
+        );
+        infos.user_data = user_data;
+        Ok(infos)
     }
 
     pub fn read_latest_commit(directory: &Arc<D>) -> Result<Self> {
@@ -582,6 +592,7 @@ impl<D: Directory, C: Codec> Clone for SegmentInfos<D, C> {
             id,
             lucene_version: self.lucene_version.clone(),
             min_seg_version: self.min_seg_version.clone(),
+            user_data: self.user_data.clone(),
             pending_commit: self.pending_commit,
         }
     }
@@ -11,14 +11,17 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use core::codec::{Codec, DocValuesFormat, DocValuesProducer};
-use core::index::FieldInfos;
+use core::codec::{Codec, DocValuesFormat, DocValuesProducer, DocValuesProducerRef};
+use core::index::{BinaryDocValues, NumericDocValues, SortedDocValues};
+use core::index::{DocValuesType, FieldInfo, FieldInfos};
 use core::index::{SegmentCommitInfo, SegmentReadState};
+use core::index::{SortedNumericDocValues, SortedSetDocValues};
 use core::store::{Directory, IOContext};
-use core::util::to_base36;
-use error::ErrorKind::IllegalState;
+use core::util::{to_base36, BitsRef};
+use error::ErrorKind::{IllegalArgument, IllegalState};
 use error::Result;
 
+use std::collections::{BTreeMap, HashMap};
 use std::sync::Arc;
 
 /// Manage the `DocValuesProducer` held by `SegmentReader`.
@@ -67,3 +70,108 @@ impl SegmentDocValues {
         }
     }
 }
+
+/// A `DocValuesProducer` for a segment whose doc values were updated, each
+/// field being read from the doc values generation it was last written to.
+pub struct SegmentDocValuesProducer {
+    fields: HashMap<String, DocValuesProducerRef>,
+}
+
+impl SegmentDocValuesProducer {
+    /// Opens one producer per doc values generation of `infos`, the fields
+    /// which were never updated being read with `core_infos` from `dir`.
+    pub fn new<D: Directory, DW: Directory, C: Codec>(
+        si: &SegmentCommitInfo<D, C>,
+        dir: Arc<DW>,
+        core_infos: &Arc<FieldInfos>,
+        infos: &FieldInfos,
+    ) -> Result<SegmentDocValuesProducer> {
+        let mut fields = HashMap::new();
+        let mut base_producer: Option<DocValuesProducerRef> = None;
+        let mut updated_fields: BTreeMap<i64, Vec<FieldInfo>> = BTreeMap::new();
+        for fi in infos.by_number.values() {
+            if fi.doc_values_type == DocValuesType::Null {
+                continue;
+            }
+            if fi.dv_gen != -1 {
+                updated_fields
+                    .entry(fi.dv_gen)
+                    .or_insert_with(Vec::new)
+                    .push(fi.as_ref().clone());
+                continue;
+            }
+            if base_producer.is_none() {
+                let producer = SegmentDocValues::get_doc_values_producer(
+                    -1,
+                    si,
+                    Arc::clone(&dir),
+                    Arc::clone(core_infos),
+                )?;
+                base_producer = Some(Arc::from(producer));
+            }
+            let producer = base_producer.as_ref().unwrap();
+            fields.insert(fi.name.clone(), Arc::clone(producer));
+        }
+
+        for (gen, gen_infos) in updated_fields {
+            let names: Vec<String> = gen_infos.iter().map(|fi| fi.name.clone()).collect();
+            let gen_infos = Arc::new(FieldInfos::new(gen_infos)?);
+            let producer: DocValuesProducerRef = Arc::from(
+                SegmentDocValues::get_doc_values_producer(gen, si, Arc::clone(&dir), gen_infos)?,
+            );
+            for name in names {
+                fields.insert(name, Arc::clone(&producer));
+            }
+        }
+
+        Ok(SegmentDocValuesProducer { fields })
+    }
+
+    fn producer(&self, field: &FieldInfo) -> Result<&DocValuesProducerRef> {
+        match self.fields.get(&field.name) {
+            Some(producer) => Ok(producer),
+            None => bail!(IllegalArgument(format!("field {} has no doc values", field.name))),
+        }
+    }
+}
+
+impl DocValuesProducer for SegmentDocValuesProducer {
+    fn get_numeric(&self, field: &FieldInfo) -> Result<Arc<dyn NumericDocValues>> {
+        self.producer(field)?.get_numeric(field)
+    }
+
+    fn get_binary(&self, field: &FieldInfo) -> Result<Arc<dyn BinaryDocValues>> {
+        self.producer(field)?.get_binary(field)
+    }
+
+    fn get_sorted(&self, field: &FieldInfo) -> Result<Arc<dyn SortedDocValues>> {
+        self.producer(field)?.get_sorted(field)
+    }
+
+    fn get_sorted_numeric(&self, field: &FieldInfo) -> Result<Arc<dyn SortedNumericDocValues>> {
+        self.producer(field)?.get_sorted_numeric(field)
+    }
+
+    fn get_sorted_set(&self, field: &FieldInfo) -> Result<Arc<dyn SortedSetDocValues>> {
+        self.producer(field)?.get_sorted_set(field)
+    }
+
+    fn get_docs_with_field(&self, field: &FieldInfo) -> Result<BitsRef> {
+        self.producer(field)?.get_docs_with_field(field)
+    }
+
+    fn check_integrity(&self) -> Result<()> {
+        for producer in self.fields.values() {
+            producer.check_integrity()?;
+        }
+        Ok(())
+    }
+
+    fn get_merge_instance(&self) -> Result<Box<dyn DocValuesProducer>> {
+        let mut fields = HashMap::with_capacity(self.fields.len());
+        for (field, producer) in &self.fields {
+            fields.insert(field.clone(), Arc::from(producer.get_merge_instance()?));
+        }
+        Ok(Box::new(SegmentDocValuesProducer { fields }))
+    }
+}
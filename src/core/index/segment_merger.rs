@@ -18,7 +18,7 @@ use core::codec::{
 };
 use core::index::merge_state::MergeState;
 use core::index::{FieldInfosBuilder, FieldNumbersRef};
-use core::index::{SegmentInfo, SegmentReader, SegmentWriteState};
+use core::index::{SearchLeafReader, SegmentInfo, SegmentWriteState};
 use core::store::{Directory, IOContext};
 use error::ErrorKind::{IllegalArgument, IllegalState};
 use error::Result;
@@ -45,7 +45,7 @@ where
     <DW as Directory>::IndexOutput: 'static,
 {
     pub fn new(
-        readers: Vec<Arc<SegmentReader<D, C>>>,
+        readers: Vec<Arc<SearchLeafReader<C>>>,
         segment_info: &SegmentInfo<D, C>,
         directory: Arc<DW>,
         field_numbers: FieldNumbersRef,
@@ -83,7 +83,7 @@ where
         let num_merged = self.merge_fields()?;
         assert_eq!(num_merged, self.merge_state.segment_info().max_doc);
 
-        let segment_write_state = SegmentWriteState::new(
+        let mut segment_write_state = SegmentWriteState::new(
             Arc::clone(&self.directory),
             self.merge_state.segment_info().clone(),
             self.merge_state
@@ -96,35 +96,36 @@ where
             self.context.clone(),
             "".into(),
         );
-        self.merge_terms(&segment_write_state)?;
-
+        // the norms are merged first, the postings record them in their impacts
         if self
             .merge_state
             .merge_field_infos
             .as_ref()
             .unwrap()
-            .has_doc_values
+            .has_norms
         {
-            self.merge_doc_values(&segment_write_state)?;
+            self.merge_norms(&segment_write_state)?;
+            segment_write_state.load_norms()?;
         }
+        self.merge_terms(&segment_write_state)?;
+
         if self
             .merge_state
             .merge_field_infos
             .as_ref()
             .unwrap()
-            .has_point_values
+            .has_doc_values
         {
-            self.merge_points(&segment_write_state)?;
+            self.merge_doc_values(&segment_write_state)?;
         }
-
         if self
             .merge_state
             .merge_field_infos
             .as_ref()
             .unwrap()
-            .has_norms
+            .has_point_values
         {
-            self.merge_norms(&segment_write_state)?;
+            self.merge_points(&segment_write_state)?;
         }
         if self
             .merge_state
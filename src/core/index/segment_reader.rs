@@ -29,12 +29,17 @@ use core::{
     index::{
         leaf_reader::LeafReaderContext, BinaryDocValuesRef, CfsDirectory, DocValuesType, FieldInfo,
         FieldInfos, IndexReader, LeafReader, NumericDocValues, NumericDocValuesRef,
-        SegmentCommitInfo, SegmentCoreReaders, SegmentDocValues, SortedDocValuesRef,
-        SortedNumericDocValuesRef, SortedSetDocValuesRef, StoredFieldVisitor,
+        SegmentCommitInfo, SegmentCoreReaders, SegmentDocValues, SegmentDocValuesProducer,
+        SortedDocValuesRef, SortedNumericDocValuesRef, SortedSetDocValuesRef, StoredFieldVisitor,
     },
     search::sort::Sort,
     store::IOContext,
-    util::{external::deferred::Deferred, numeric::to_base36, BitsRef, DocId, MatchAllBits},
+    util::{
+        bit_set::{BitSet, FixedBitSet},
+        external::deferred::Deferred,
+        numeric::to_base36,
+        BitsRef, DocId, MatchAllBits,
+    },
 };
 use error::{ErrorKind::IllegalArgument, Result};
 
@@ -201,7 +206,7 @@ impl<D: Directory + 'static, C: Codec> SegmentReader<D, C> {
             Arc::clone(&core.core_field_infos)
         } else {
             let fis_format = codec.field_infos_format();
-            let segment_suffix = format!("{}", si.field_infos_gen());
+            let segment_suffix = to_base36(si.field_infos_gen() as u64);
             let field_infos = fis_format.read(
                 si.info.directory.as_ref(),
                 &si.info,
@@ -246,6 +251,60 @@ impl<D: Directory + 'static, C: Codec> SegmentReader<D, C> {
     pub fn leaf_context(&self) -> LeafReaderContext<C> {
         LeafReaderContext::new(self, self, 0, 0)
     }
+
+    /// Returns the docs soft deleted through the numeric doc values `field`,
+    /// that is the docs having a value for it, or None if the segment does
+    /// not have the field.
+    pub fn soft_deleted_docs(&self, field: &str) -> Result<Option<BitsRef>> {
+        match self.get_dv_field(field, DocValuesType::Numeric) {
+            Some(_) => Ok(Some(self.get_docs_with_field(field)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns a reader over the same segment whose live docs also exclude the
+    /// docs soft deleted through `field`, but the `retained` ones. Returns None
+    /// if none of the live docs is excluded.
+    pub fn with_soft_deletes(
+        &self,
+        field: &str,
+        retained: Option<&BitsRef>,
+    ) -> Result<Option<SegmentReader<D, C>>> {
+        let soft_deleted = match self.soft_deleted_docs(field)? {
+            Some(docs) => docs,
+            None => return Ok(None),
+        };
+        let max_doc = self.max_docs() as usize;
+        let mut live_docs = FixedBitSet::new(max_doc);
+        let mut num_docs = 0;
+        for doc in 0..max_doc {
+            if !self.live_docs.get(doc)? {
+                continue;
+            }
+            if soft_deleted.get(doc)? {
+                let retain = match retained {
+                    Some(retained) => retained.get(doc)?,
+                    None => false,
+                };
+                if !retain {
+                    continue;
+                }
+            }
+            live_docs.set(doc);
+            num_docs += 1;
+        }
+        if num_docs == self.num_docs {
+            return Ok(None);
+        }
+        let reader = Self::build_from(
+            Arc::clone(&self.si),
+            self,
+            Arc::new(live_docs),
+            num_docs,
+            self.is_nrt,
+        )?;
+        Ok(Some(reader))
+    }
 }
 
 impl<D: Directory + 'static, C: Codec> SegmentReader<D, C> {
@@ -263,7 +322,13 @@ impl<D: Directory + 'static, C: Codec> SegmentReader<D, C> {
         let doc_values_producer = if !field_infos.has_doc_values {
             ThreadLocal::new()
         } else if si.has_field_updates() {
-            unimplemented!()
+            let dv_producer: Arc<dyn DocValuesProducer> = Arc::new(
+                SegmentDocValuesProducer::new(si, dir, &core.core_field_infos, &field_infos)?,
+            );
+
+            let doc_values_producer = ThreadLocal::new();
+            doc_values_producer.get_or(|| Box::new(dv_producer));
+            doc_values_producer
         } else {
             // simple case, no DocValues updates
             let dv_producer =
@@ -279,7 +344,23 @@ impl<D: Directory + 'static, C: Codec> SegmentReader<D, C> {
     fn init_local_doc_values_producer(&self) -> Result<()> {
         if self.field_infos.has_doc_values {
             if self.si.has_field_updates() {
-                unimplemented!()
+                if self.doc_values_producer.get().is_some() {
+                    return Ok(());
+                }
+
+                let dir = match self.core.cfs_reader {
+                    Some(ref d) => Arc::clone(d),
+                    None => Arc::new(CfsDirectory::Raw(Arc::clone(&self.si.info.directory))),
+                };
+                let dv_producer: Arc<dyn DocValuesProducer> =
+                    Arc::new(SegmentDocValuesProducer::new(
+                        &self.si,
+                        dir,
+                        &self.core.core_field_infos,
+                        &self.field_infos,
+                    )?);
+
+                self.doc_values_producer.get_or(|| Box::new(dv_producer));
             } else {
                 if self.doc_values_producer.get().is_some() {
                     return Ok(());
@@ -383,6 +464,10 @@ where
         &self.si.info.name
     }
 
+    fn segment_id(&self) -> Option<&[u8]> {
+        Some(&self.si.info.id)
+    }
+
     fn term_vector(&self, doc_id: DocId) -> Result<Option<CodecTVFields<C>>> {
         self.check_bounds(doc_id);
         if let Some(ref reader) = self.core.term_vectors_reader {
@@ -35,6 +35,41 @@ pub trait SortedNumericDocValues: Send + Sync {
     fn get_numeric_doc_values(&self) -> Option<NumericDocValuesRef> {
         None
     }
+
+    /// Returns the smallest value of `doc`, or None if it has no values.
+    fn min(&self, doc: DocId) -> Result<Option<i64>> {
+        let ctx = self.set_document(None, doc)?;
+        if self.count(&ctx) == 0 {
+            return Ok(None);
+        }
+        // values are sorted in ascending order
+        self.value_at(&ctx, 0).map(Some)
+    }
+
+    /// Returns the largest value of `doc`, or None if it has no values.
+    fn max(&self, doc: DocId) -> Result<Option<i64>> {
+        let ctx = self.set_document(None, doc)?;
+        let count = self.count(&ctx);
+        if count == 0 {
+            return Ok(None);
+        }
+        self.value_at(&ctx, count - 1).map(Some)
+    }
+
+    /// Returns the sum of the values of `doc`, wrapping on overflow, or None
+    /// if it has no values.
+    fn sum(&self, doc: DocId) -> Result<Option<i64>> {
+        let ctx = self.set_document(None, doc)?;
+        let count = self.count(&ctx);
+        if count == 0 {
+            return Ok(None);
+        }
+        let mut sum = 0i64;
+        for i in 0..count {
+            sum = sum.wrapping_add(self.value_at(&ctx, i)?);
+        }
+        Ok(Some(sum))
+    }
 }
 
 pub type SortedNumericDocValuesRef = Arc<dyn SortedNumericDocValues>;
@@ -130,3 +165,40 @@ impl SortedNumericDocValues for TabledSortedNumericDocValues {
         (ctx.1 - ctx.0) as usize
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::doc::{NumericDocValuesField, SortedNumericDocValuesField};
+    use core::index::{Fieldable, IndexReader, IndexWriter, IndexWriterConfig, LeafReader};
+    use core::store::RAMDirectory;
+
+    #[test]
+    fn test_min_max_sum() {
+        let dir = Arc::new(RAMDirectory::default());
+        let writer = IndexWriter::new(dir, Arc::new(IndexWriterConfig::default())).unwrap();
+        let docs: [&[i64]; 3] = [&[3, -1, 5], &[], &[i64::max_value(), 1]];
+        for values in &docs {
+            let mut doc: Vec<Box<dyn Fieldable>> =
+                vec![Box::new(NumericDocValuesField::new("other", 1))];
+            for v in values.iter() {
+                doc.push(Box::new(SortedNumericDocValuesField::new("values", *v)));
+            }
+            writer.add_document(doc).unwrap();
+        }
+        let reader = writer.get_reader(true, false).unwrap();
+        let leaves = reader.leaves();
+        let doc_values = leaves[0].reader.get_sorted_numeric_doc_values("values").unwrap();
+
+        assert_eq!(doc_values.min(0).unwrap(), Some(-1));
+        assert_eq!(doc_values.max(0).unwrap(), Some(5));
+        assert_eq!(doc_values.sum(0).unwrap(), Some(7));
+        // no values rather than 0
+        assert_eq!(doc_values.min(1).unwrap(), None);
+        assert_eq!(doc_values.max(1).unwrap(), None);
+        assert_eq!(doc_values.sum(1).unwrap(), None);
+        assert_eq!(doc_values.sum(2).unwrap(), Some(i64::min_value()));
+
+        writer.close().unwrap();
+    }
+}
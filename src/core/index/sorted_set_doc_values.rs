@@ -54,6 +54,12 @@ pub trait SortedSetDocValues: Send + Sync {
     }
 
     fn term_iterator(&self) -> Result<DocValuesTermIterator>;
+
+    /// Returns these doc values as `RandomAccessOrds` if they support random
+    /// access to the ords of a doc.
+    fn random_access_ords(&self) -> Option<&dyn RandomAccessOrds> {
+        None
+    }
 }
 
 pub type SortedSetDocValuesRef = Arc<dyn SortedSetDocValues>;
@@ -157,6 +163,10 @@ impl SortedSetDocValues for AddressedRandomAccessOrds {
             }
         }
     }
+
+    fn random_access_ords(&self) -> Option<&dyn RandomAccessOrds> {
+        Some(self)
+    }
 }
 
 impl RandomAccessOrds for AddressedRandomAccessOrds {
@@ -330,6 +340,10 @@ impl SortedSetDocValues for TabledRandomAccessOrds {
             }
         }
     }
+
+    fn random_access_ords(&self) -> Option<&dyn RandomAccessOrds> {
+        Some(self)
+    }
 }
 
 impl RandomAccessOrds for TabledRandomAccessOrds {
@@ -26,7 +26,6 @@ use core::util::{BitsRef, DocId};
 use error::ErrorKind::IllegalArgument;
 use error::Result;
 
-use core::store::Directory;
 use std::cmp::Ordering;
 use std::collections::BinaryHeap;
 
@@ -46,6 +45,7 @@ impl Sorter {
         match sort {
             SortField::Simple(s) => s.field_type(),
             SortField::SortedNumeric(s) => s.numeric_type(),
+            SortField::SortedSet(_) => SortFieldType::String,
         }
     }
 
@@ -256,9 +256,9 @@ impl MultiSorter {
     /// to map each leaf's documents into the merged segment.  The documents for
     /// each incoming leaf reader must already be sorted by the same sort!
     /// Returns null if the merge sort is not needed (segments are already in index sort order).
-    pub fn sort<D: Directory, C: Codec>(
+    pub fn sort<C: Codec>(
         sort: &Sort,
-        readers: &[ReaderWrapperEnum<D, C>],
+        readers: &[ReaderWrapperEnum<C>],
     ) -> Result<Vec<LiveDocsDocMap>> {
         let fields = sort.get_sort();
 
@@ -333,8 +333,8 @@ impl MultiSorter {
 
     /// Returns {@code CrossReaderComparator} for the provided readers to represent
     /// the requested {@link SortField} sort order.
-    fn get_comparator<D: Directory, C: Codec>(
-        readers: &[ReaderWrapperEnum<D, C>],
+    fn get_comparator<C: Codec>(
+        readers: &[ReaderWrapperEnum<C>],
         sort_field: &SortField,
     ) -> Result<CrossReaderComparatorEnum> {
         let reverse = sort_field.is_reverse();
@@ -242,6 +242,53 @@ pub trait Terms {
     }
 }
 
+/// Cheap statistics of the terms of a field, read from the terms dictionary
+/// metadata without enumerating the terms, e.g. to bound the cost of a query
+/// expansion before doing it.
+///
+/// Just like the `Terms` measures they come from, these don't take deleted
+/// documents into account, and a measure which isn't stored by the codec is -1.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TermsStats {
+    /// Number of unique terms in the field.
+    pub num_terms: i64,
+    /// Sum of the doc freqs of all the terms in the field.
+    pub sum_doc_freq: i64,
+    /// Sum of the term freqs of all the terms in the field, -1 if the term
+    /// freqs are omitted.
+    pub sum_total_term_freq: i64,
+    /// Number of documents with at least one term in the field.
+    pub doc_count: i32,
+    /// Smallest term of the field, None if the field has no terms.
+    pub min_term: Option<Vec<u8>>,
+    /// Largest term of the field, None if the field has no terms.
+    pub max_term: Option<Vec<u8>>,
+}
+
+impl TermsStats {
+    pub fn from_terms<T: Terms + ?Sized>(terms: &T) -> Result<TermsStats> {
+        Ok(TermsStats {
+            num_terms: terms.size()?,
+            sum_doc_freq: terms.sum_doc_freq()?,
+            sum_total_term_freq: terms.sum_total_term_freq()?,
+            doc_count: terms.doc_count()?,
+            min_term: terms.min()?,
+            max_term: terms.max()?,
+        })
+    }
+
+    /// Returns the number of unique terms, or an upper bound of it if the
+    /// codec doesn't store it: every term has a doc freq of at least 1.
+    /// Returns -1 if neither measure is known.
+    pub fn estimated_num_terms(&self) -> i64 {
+        if self.num_terms >= 0 {
+            self.num_terms
+        } else {
+            self.sum_doc_freq
+        }
+    }
+}
+
 impl<T: Terms> Terms for Arc<T> {
     type Iterator = T::Iterator;
     fn iterator(&self) -> Result<Self::Iterator> {
@@ -596,3 +643,59 @@ where
         self.base_mut().terms.postings_with_flags(flags)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::analysis::whitespace_tokenizer::WhitespaceTokenizer;
+    use core::analysis::TokenStream;
+    use core::doc::{Field, FieldType, NumericDocValuesField};
+    use core::index::{Fieldable, IndexOptions, IndexReader, IndexWriter, IndexWriterConfig};
+    use core::index::LeafReader;
+    use core::store::RAMDirectory;
+
+    use std::io::Cursor;
+
+    #[test]
+    fn test_terms_stats() {
+        let dir = Arc::new(RAMDirectory::default());
+        let writer = IndexWriter::new(dir, Arc::new(IndexWriterConfig::default())).unwrap();
+        let mut field_type = FieldType::default();
+        field_type.index_options = IndexOptions::DocsAndFreqs;
+        for text in &["a b a", "b c", "d"] {
+            let tokens: Box<dyn TokenStream> = Box::new(WhitespaceTokenizer::new(Box::new(
+                Cursor::new(text.as_bytes().to_vec()),
+            )));
+            let doc: Vec<Box<dyn Fieldable>> = vec![Box::new(Field::new(
+                "body".into(),
+                field_type.clone(),
+                None,
+                Some(tokens),
+            ))];
+            writer.add_document(doc).unwrap();
+        }
+        // a doc without terms in the field
+        let doc: Vec<Box<dyn Fieldable>> = vec![Box::new(NumericDocValuesField::new("id", 1))];
+        writer.add_document(doc).unwrap();
+
+        let reader = writer.get_reader(true, false).unwrap();
+        let leaves = reader.leaves();
+        assert_eq!(leaves.len(), 1);
+        let stats = leaves[0].reader.terms_stats("body").unwrap().unwrap();
+        assert_eq!(
+            stats,
+            TermsStats {
+                num_terms: 4,
+                sum_doc_freq: 5,
+                sum_total_term_freq: 6,
+                doc_count: 3,
+                min_term: Some(b"a".to_vec()),
+                max_term: Some(b"d".to_vec()),
+            }
+        );
+        assert_eq!(stats.estimated_num_terms(), 4);
+        assert!(leaves[0].reader.terms_stats("missing").unwrap().is_none());
+
+        writer.close().unwrap();
+    }
+}
@@ -16,19 +16,24 @@ use core::{
     index::{
         bufferd_updates::{self, BufferedUpdates, FrozenBufferedUpdates},
         doc_consumer::{DefaultIndexingChain, DocConsumer},
-        doc_writer_delete_queue::{DeleteSlice, DocumentsWriterDeleteQueue},
+        doc_writer_delete_queue::{DeleteSlice, DeleteTerm, DocumentsWriterDeleteQueue},
         index_writer::{IndexWriterInner, INDEX_MAX_DOCS},
         index_writer_config::IndexWriterConfig,
         merge_policy::MergePolicy,
         merge_scheduler::MergeScheduler,
-        FieldInfos, FieldInfosBuilder, FieldNumbers, FieldNumbersRef, Fieldable, SegmentCommitInfo,
-        SegmentInfo, SegmentWriteState, Term,
+        merge_state::DocMap,
+        segment_merger::SegmentMerger,
+        sorter::Sorter,
+        FieldInfos, FieldInfosBuilder, FieldNumbers, FieldNumbersRef, Fieldable, SearchLeafReader,
+        SegmentCommitInfo, SegmentInfo, SegmentReader, SegmentWriteState,
     },
+    search::sort::Sort,
     store::{
-        Directory, FlushInfo, IOContext, LockValidatingDirectoryWrapper, TrackingDirectoryWrapper,
+        Directory, FlushInfo, IOContext, LockValidatingDirectoryWrapper, MergeInfo,
+        TrackingDirectoryWrapper,
     },
     util::{
-        bit_set::BitSet,
+        bit_set::{BitSet, FixedBitSet},
         byte_block_pool::DirectTrackingAllocator,
         int_block_pool::{IntAllocator, INT_BLOCK_SIZE},
         string_util::random_id,
@@ -50,6 +55,8 @@ pub struct DocState {
     // pub similarity: Option<Box<Similarity>>,
     pub doc_id: DocId,
     // pub doc: Vec<Box<dyn Fieldable>>,
+    // true if a field of the current doc hit its `max_field_length`
+    pub truncated: bool,
 }
 
 impl DocState {
@@ -57,10 +64,12 @@ impl DocState {
         DocState {
             doc_id: 0,
             // similarity: None,
+            truncated: false,
         }
     }
     pub fn clear(&mut self) {
         // self.doc = Vec::with_capacity(0);
+        self.truncated = false;
     }
 }
 
@@ -178,6 +187,10 @@ where
         self.index_writer_config.codec()
     }
 
+    pub fn index_sort(&self) -> Option<&Sort> {
+        self.index_writer_config.index_sort()
+    }
+
     pub fn bytes_used(&self) -> i64 {
         self.bytes_used.get() // + self.pending_updates.bytes_used.get()
     }
@@ -198,7 +211,7 @@ where
     pub fn update_document<F: Fieldable>(
         &mut self,
         mut doc: Vec<F>,
-        del_term: Option<Term>,
+        del_term: Option<DeleteTerm>,
     ) -> Result<u64> {
         // debug_assert!(self.inited);
         self.reserve_one_doc()?;
@@ -215,6 +228,9 @@ where
         let res = self
             .consumer
             .process_document(&mut self.doc_state, &mut doc);
+        if res.is_ok() && self.doc_state.truncated {
+            self.index_writer().inc_truncated_docs();
+        }
         self.doc_state.clear();
         if !res.is_ok() {
             // mark document as deleted
@@ -230,7 +246,7 @@ where
     pub fn update_documents<F: Fieldable>(
         &mut self,
         docs: Vec<Vec<F>>,
-        del_term: Option<Term>,
+        del_term: Option<DeleteTerm>,
     ) -> Result<u64> {
         // debug_assert!(self.inited);
         let mut doc_count = 0;
@@ -254,7 +270,7 @@ where
     fn do_update_documents<F: Fieldable>(
         &mut self,
         docs: Vec<Vec<F>>,
-        del_term: Option<Term>,
+        del_term: Option<DeleteTerm>,
         doc_count: &mut i32,
         all_docs_indexed: &mut bool,
     ) -> Result<u64> {
@@ -279,6 +295,9 @@ where
                 self.num_docs_in_ram += 1;
                 res?;
             }
+            if self.doc_state.truncated {
+                self.index_writer().inc_truncated_docs();
+            }
 
             self.num_docs_in_ram += 1;
         }
@@ -327,7 +346,7 @@ where
         // confounding exception).
     }
 
-    fn finish_document(&mut self, del_term: Option<Term>) -> Result<u64> {
+    fn finish_document(&mut self, del_term: Option<DeleteTerm>) -> Result<u64> {
         // here we actually finish the document in two steps:
         // 1. push the delete into the queue and update our slice
         // 2. increment the DWPT private document id.
@@ -446,6 +465,11 @@ where
         self.pending_updates.deleted_terms.clear();
         self.segment_info
             .set_files(&self.directory.create_files())?;
+        let sort_map = if self.index_sort().is_some() {
+            self.sort_flushed_segment(&mut flush_state)?
+        } else {
+            None
+        };
         let segment_info_per_commit = SegmentCommitInfo::new(
             self.segment_info.clone(),
             0,
@@ -457,7 +481,9 @@ where
         );
 
         let mut fs = {
-            let segment_deletes = if self.pending_updates.deleted_queries.is_empty() {
+            let segment_deletes = if self.pending_updates.deleted_queries.is_empty()
+                && self.pending_updates.numeric_updates.is_empty()
+            {
                 self.pending_updates.clear();
                 None
             } else {
@@ -469,6 +495,7 @@ where
                 segment_deletes,
                 Arc::new(flush_state.live_docs),
                 flush_state.del_count_on_flush,
+                sort_map,
             )
         };
         self.seal_flushed_segment(&mut fs)?;
@@ -480,6 +507,125 @@ where
         Ok(Some(fs))
     }
 
+    /// Rewrites the flushed segment with its docs in index sort order, by
+    /// merging it alone into a new segment. The files of the unsorted segment
+    /// are deleted once the flush is done, and the docs deleted on flush are
+    /// dropped by the merge. A segment already in sort order is kept as is.
+    ///
+    /// Returns the flush time doc id of each doc of the sorted segment, which
+    /// the doc id limits of its private deletes by query and doc values
+    /// updates apply to once it is published, `None` if it isn't rewritten.
+    fn sort_flushed_segment<DW: Directory + 'static>(
+        &mut self,
+        flush_state: &mut SegmentWriteState<D, DW, C>,
+    ) -> Result<Option<Arc<Vec<DocId>>>> {
+        let max_doc = self.num_docs_in_ram as DocId;
+        let del_count = flush_state.del_count_on_flush;
+        if del_count as DocId >= max_doc {
+            // all the docs are deleted, the segment will be dropped anyway
+            return Ok(None);
+        }
+
+        let unsorted_files = self.segment_info.files().clone();
+        let unsorted_info = Arc::new(SegmentCommitInfo::new(
+            self.segment_info.clone(),
+            0,
+            -1,
+            -1,
+            -1,
+            HashMap::new(),
+            HashSet::new(),
+        ));
+        let mut reader = SegmentReader::open(&unsorted_info, &IOContext::READ)?;
+        let index_sort = self.index_sort().unwrap().clone();
+        if Sorter::new(index_sort.clone())
+            .sort_leaf_reader(&reader.leaf_context())?
+            .is_none()
+        {
+            debug!("DWPT: segment '{}' is already sorted", self.segment_info.name);
+            self.segment_info.index_sort = Some(index_sort);
+            return Ok(None);
+        }
+        if del_count > 0 {
+            // the flush state keeps its live docs until the segment is sorted
+            let live_docs = FixedBitSet::copy_from(
+                flush_state.live_docs.bits.clone(),
+                flush_state.live_docs.num_bits,
+            )?;
+            reader = SegmentReader::build_from(
+                Arc::clone(&unsorted_info),
+                &reader,
+                Arc::new(live_docs),
+                max_doc - del_count as DocId,
+                false,
+            )?;
+        }
+
+        let writer = self.index_writer();
+        let sorted_info = SegmentInfo::new(
+            VERSION_LATEST.clone(),
+            &writer.new_segment_name(),
+            -1,
+            Arc::clone(&self.directory_orig),
+            false,
+            Some(Arc::clone(self.segment_info.codec())),
+            HashMap::new(),
+            random_id(),
+            HashMap::new(),
+            Some(index_sort),
+        )?;
+        let context = IOContext::Merge(MergeInfo::new(
+            max_doc as u32,
+            self.bytes_used() as u64,
+            false,
+            None,
+        ));
+        let reader: Arc<SearchLeafReader<C>> = Arc::new(reader);
+        let mut merger = SegmentMerger::new(
+            vec![reader],
+            &sorted_info,
+            Arc::clone(&self.directory),
+            FieldNumbersRef::new(Arc::clone(writer.global_field_numbers())),
+            context,
+        )?;
+        debug_assert!(merger.should_merge());
+        merger.merge()?;
+
+        let mut sort_map = vec![0; merger.merge_state.segment_info().max_doc() as usize];
+        for doc in 0..max_doc {
+            let sorted_doc = merger.merge_state.leaf_doc_maps[0].get(doc)?;
+            let new_doc = merger.merge_state.doc_maps[0].get(sorted_doc)?;
+            if new_doc >= 0 {
+                sort_map[new_doc as usize] = doc;
+            }
+        }
+
+        let sorted_files: HashSet<String> = self
+            .directory
+            .create_files()
+            .into_iter()
+            .filter(|f| !unsorted_files.contains(f))
+            .collect();
+        merger.merge_state.segment_info().set_files(&sorted_files)?;
+        flush_state.field_infos = merger
+            .merge_state
+            .merge_field_infos
+            .as_ref()
+            .unwrap()
+            .as_ref()
+            .clone();
+        if del_count > 0 {
+            // the docs deleted on flush are gone with the unsorted segment
+            self.pending_num_docs
+                .fetch_sub(i64::from(del_count), Ordering::AcqRel);
+            flush_state.live_docs = FixedBitSet::default();
+            flush_state.del_count_on_flush = 0;
+        }
+        self.segment_info = merger.merge_state.segment_info().clone();
+        self.files_to_delete.extend(unsorted_files);
+        Ok(Some(Arc::new(sort_map)))
+    }
+
     fn seal_flushed_segment(&mut self, flushed_segment: &mut FlushedSegment<D, C>) -> Result<()> {
         // set_diagnostics(&mut flushed_segment.segment_info.info, index_writer::SOURCE_FLUSH);
 
@@ -580,11 +726,16 @@ impl<D: Directory, C: Codec> FlushedSegment<D, C> {
         buffered_updates: Option<&mut BufferedUpdates<C>>,
         live_docs: BitsRef,
         del_count: u32,
+        sort_map: Option<Arc<Vec<DocId>>>,
     ) -> Self {
         let mut segment_updates = None;
         if let Some(b) = buffered_updates {
             if b.any() {
-                segment_updates = Some(FrozenBufferedUpdates::new(b, true));
+                let mut updates = FrozenBufferedUpdates::new(b, true);
+                if let Some(sort_map) = sort_map {
+                    updates.set_sort_map(sort_map);
+                }
+                segment_updates = Some(updates);
             }
         }
         FlushedSegment {
@@ -666,8 +817,7 @@ where
         self.aborted.write(true);
     }
 
-    #[allow(dead_code)]
-    fn clear_abort(&self) {
+    pub fn clear_abort(&self) {
         let _guard = self.inner.lock().unwrap();
         self.aborted.write(false);
         self.cond.notify_all();
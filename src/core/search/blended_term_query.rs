@@ -0,0 +1,406 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use error::ErrorKind::IllegalArgument;
+use error::Result;
+
+use std::fmt;
+
+use core::codec::Codec;
+use core::index::{LeafReaderContext, Term};
+use core::search::disjunction::DisjunctionMaxScorer;
+use core::search::explanation::Explanation;
+use core::search::searcher::SearchPlanBuilder;
+use core::search::statistics::{CollectionStatistics, TermStatistics};
+use core::search::term_query::{TermQuery, TermWeight};
+use core::search::{Query, Scorer, Weight};
+use core::util::DocId;
+
+pub const BLENDED_TERM: &str = "blended_term";
+
+/// How the statistics of the terms of a `BlendedTermQuery` are blended.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StatisticsBlend {
+    Max,
+    Sum,
+    Min,
+}
+
+impl StatisticsBlend {
+    /// Blends the given statistics, `values` must not be empty.
+    pub fn blend(self, values: &[i64]) -> i64 {
+        debug_assert!(!values.is_empty());
+        match self {
+            StatisticsBlend::Max => values.iter().cloned().max().unwrap_or(0),
+            StatisticsBlend::Sum => values.iter().sum(),
+            StatisticsBlend::Min => values.iter().cloned().min().unwrap_or(0),
+        }
+    }
+}
+
+/// A query matching a term in several fields, e.g. the same word in the
+/// `title` and the `body`, that scores each field with the same blended
+/// statistics.
+///
+/// The doc freq and the total term freq of each term are replaced by the
+/// blend of those of all the terms before the weights are computed, so
+/// that the idf is the same across fields and a doc matching in a field
+/// where the term is rare is not over-weighted. A doc is scored like by a
+/// `DisjunctionMaxQuery`: the score of its best field plus the scores of
+/// the other matched fields times the tie breaker multiplier, so that
+/// matching in several fields doesn't beat a better match in one field.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BlendedTermQuery {
+    pub terms: Vec<Term>,
+    pub blend: StatisticsBlend,
+    pub boost: f32,
+    pub tie_breaker_multiplier: f32,
+}
+
+impl BlendedTermQuery {
+    pub fn new(terms: Vec<Term>, blend: StatisticsBlend, boost: f32) -> Result<BlendedTermQuery> {
+        if terms.is_empty() {
+            bail!(IllegalArgument("blended term query needs at least one term".into()));
+        }
+        Ok(BlendedTermQuery {
+            terms,
+            blend,
+            boost,
+            tie_breaker_multiplier: 0.01,
+        })
+    }
+
+    /// Sets the weight of the other matched fields in the score of a doc,
+    /// in `[0, 1]`, 0.01 by default. With 1 the scores of the fields are
+    /// summed.
+    pub fn with_tie_breaker(mut self, tie_breaker_multiplier: f32) -> Result<BlendedTermQuery> {
+        if tie_breaker_multiplier < 0.0 || tie_breaker_multiplier > 1.0 {
+            bail!(IllegalArgument(format!(
+                "tie breaker multiplier must be in [0, 1], got {}",
+                tie_breaker_multiplier
+            )));
+        }
+        self.tie_breaker_multiplier = tie_breaker_multiplier;
+        Ok(self)
+    }
+
+    /// Returns the blended doc freq and total term freq of the terms found
+    /// in the index, `None` if none is. The doc freq never exceeds `max_doc`.
+    fn blend_statistics(&self, stats: &[TermStatistics], max_doc: i64) -> Option<(i64, i64)> {
+        // a missing term would make the min blend match nothing
+        let stats: Vec<&TermStatistics> = stats.iter().filter(|s| s.doc_freq > 0).collect();
+        if stats.is_empty() {
+            return None;
+        }
+        let doc_freqs: Vec<i64> = stats.iter().map(|s| s.doc_freq).collect();
+        let doc_freq = self.blend.blend(&doc_freqs).min(max_doc);
+        let total_term_freq = if stats.iter().any(|s| s.total_term_freq == -1) {
+            -1
+        } else {
+            let total_term_freqs: Vec<i64> = stats.iter().map(|s| s.total_term_freq).collect();
+            self.blend.blend(&total_term_freqs).max(doc_freq)
+        };
+        Some((doc_freq, total_term_freq))
+    }
+}
+
+impl<C: Codec> Query<C> for BlendedTermQuery {
+    fn create_weight(
+        &self,
+        searcher: &dyn SearchPlanBuilder<C>,
+        needs_scores: bool,
+    ) -> Result<Box<dyn Weight<C>>> {
+        let max_doc = i64::from(searcher.max_doc());
+        let mut term_contexts = Vec::with_capacity(self.terms.len());
+        let mut term_stats = Vec::with_capacity(self.terms.len());
+        for term in &self.terms {
+            let term_context = searcher.term_state(term)?;
+            term_stats.push(searcher.term_statistics(term.clone(), term_context.as_ref()));
+            term_contexts.push(term_context);
+        }
+        let blended = self.blend_statistics(&term_stats, max_doc);
+
+        let mut weights = Vec::with_capacity(self.terms.len());
+        for (term, term_context) in self.terms.iter().zip(term_contexts) {
+            let (term_stats, collection_stats) = match blended {
+                Some((doc_freq, total_term_freq)) if needs_scores => (
+                    TermStatistics::new(term.bytes.clone(), doc_freq, total_term_freq),
+                    searcher.collections_statistics(&term.field)?,
+                ),
+                _ => (
+                    TermStatistics::new(term.bytes.clone(), max_doc, -1),
+                    CollectionStatistics::new(term.field.clone(), max_doc, -1, -1, -1),
+                ),
+            };
+            let similarity = searcher.similarity(&term.field, needs_scores);
+            let sim_weight =
+                similarity.compute_weight(&collection_stats, &[term_stats], None, self.boost);
+            weights.push(TermWeight::new(
+                term.clone(),
+                term_context.term_states(),
+                self.boost,
+                similarity,
+                sim_weight,
+                needs_scores,
+            ));
+        }
+        Ok(Box::new(BlendedTermWeight {
+            weights,
+            blend: self.blend,
+            tie_breaker_multiplier: self.tie_breaker_multiplier,
+            needs_scores,
+        }))
+    }
+
+    fn extract_terms(&self) -> Vec<TermQuery> {
+        self.terms
+            .iter()
+            .map(|term| TermQuery::new(term.clone(), self.boost, None))
+            .collect()
+    }
+
+    fn query_type(&self) -> &'static str {
+        BLENDED_TERM
+    }
+
+    fn as_any(&self) -> &::std::any::Any {
+        self
+    }
+}
+
+impl fmt::Display for BlendedTermQuery {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let terms: Vec<String> = self
+            .terms
+            .iter()
+            .map(|t| format!("{}:{}", &t.field, t.text().unwrap_or_default()))
+            .collect();
+        write!(
+            f,
+            "BlendedTermQuery(terms: [{}], blend: {:?}, boost: {}, tie_breaker_multiplier: {})",
+            terms.join(", "),
+            self.blend,
+            self.boost,
+            self.tie_breaker_multiplier
+        )
+    }
+}
+
+struct BlendedTermWeight<C: Codec> {
+    weights: Vec<TermWeight<C>>,
+    blend: StatisticsBlend,
+    tie_breaker_multiplier: f32,
+    needs_scores: bool,
+}
+
+impl<C: Codec> Weight<C> for BlendedTermWeight<C> {
+    fn create_scorer(
+        &self,
+        reader_context: &LeafReaderContext<'_, C>,
+    ) -> Result<Option<Box<dyn Scorer>>> {
+        let mut scorers = Vec::with_capacity(self.weights.len());
+        for weight in &self.weights {
+            if let Some(scorer) = weight.create_scorer(reader_context)? {
+                scorers.push(scorer);
+            }
+        }
+        match scorers.len() {
+            0 => Ok(None),
+            1 => Ok(scorers.pop()),
+            _ => Ok(Some(Box::new(DisjunctionMaxScorer::new(
+                scorers,
+                self.tie_breaker_multiplier,
+            )))),
+        }
+    }
+
+    fn query_type(&self) -> &'static str {
+        BLENDED_TERM
+    }
+
+    fn normalize(&mut self, norm: f32, boost: f32) {
+        for weight in &mut self.weights {
+            weight.normalize(norm, boost)
+        }
+    }
+
+    fn value_for_normalization(&self) -> f32 {
+        let mut max = 0f32;
+        let mut sum = 0f32;
+        for weight in &self.weights {
+            let value = weight.value_for_normalization();
+            sum += value;
+            max = max.max(value);
+        }
+        max + (sum - max) * self.tie_breaker_multiplier
+    }
+
+    fn needs_scores(&self) -> bool {
+        self.needs_scores
+    }
+
+    fn explain(&self, reader: &LeafReaderContext<'_, C>, doc: DocId) -> Result<Explanation> {
+        let mut max = 0f32;
+        let mut sum = 0f32;
+        let mut subs = vec![];
+        for weight in &self.weights {
+            let explanation = weight.explain(reader, doc)?;
+            if explanation.is_match() {
+                sum += explanation.value();
+                max = max.max(explanation.value());
+                subs.push(explanation);
+            }
+        }
+        if subs.is_empty() {
+            Ok(Explanation::new(
+                false,
+                0f32,
+                "no matching term".to_string(),
+                vec![],
+            ))
+        } else {
+            Ok(Explanation::new(
+                true,
+                max + (sum - max) * self.tie_breaker_multiplier,
+                format!(
+                    "max plus {} times others of the {:?} blended fields:",
+                    self.tie_breaker_multiplier, self.blend
+                ),
+                subs,
+            ))
+        }
+    }
+}
+
+impl<C: Codec> fmt::Display for BlendedTermWeight<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let weights: Vec<String> = self.weights.iter().map(|w| format!("{}", w)).collect();
+        write!(
+            f,
+            "BlendedTermWeight(weights: [{}], blend: {:?}, tie_breaker_multiplier: {})",
+            weights.join(", "),
+            self.blend,
+            self.tie_breaker_multiplier
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::analysis::whitespace_tokenizer::WhitespaceTokenizer;
+    use core::analysis::TokenStream;
+    use core::doc::{Field, FieldType};
+    use core::index::{Fieldable, IndexOptions, IndexWriter, IndexWriterConfig};
+    use core::search::collector::top_docs::TopDocsCollector;
+    use core::search::searcher::{DefaultIndexSearcher, IndexSearcher};
+    use core::store::RAMDirectory;
+
+    use std::io::Cursor;
+    use std::sync::Arc;
+
+    fn stats(doc_freq: i64, total_term_freq: i64) -> TermStatistics {
+        TermStatistics::new(b"rucene".to_vec(), doc_freq, total_term_freq)
+    }
+
+    fn query(blend: StatisticsBlend) -> BlendedTermQuery {
+        let terms = vec![
+            Term::new("title".into(), b"rucene".to_vec()),
+            Term::new("body".into(), b"rucene".to_vec()),
+        ];
+        BlendedTermQuery::new(terms, blend, 1.0).unwrap()
+    }
+
+    #[test]
+    fn test_statistics_blend() {
+        let values = [3, 10, 5];
+        assert_eq!(StatisticsBlend::Max.blend(&values), 10);
+        assert_eq!(StatisticsBlend::Sum.blend(&values), 18);
+        assert_eq!(StatisticsBlend::Min.blend(&values), 3);
+    }
+
+    #[test]
+    fn test_blend_term_statistics() {
+        let term_stats = vec![stats(2, 3), stats(40, 90), stats(0, 0)];
+        let max = query(StatisticsBlend::Max);
+        assert_eq!(max.blend_statistics(&term_stats, 100), Some((40, 90)));
+        // the missing term is ignored
+        let min = query(StatisticsBlend::Min);
+        assert_eq!(min.blend_statistics(&term_stats, 100), Some((2, 3)));
+        // the doc freq is capped by max doc
+        let sum = query(StatisticsBlend::Sum);
+        assert_eq!(sum.blend_statistics(&term_stats, 30), Some((30, 93)));
+
+        let term_stats = vec![stats(2, -1), stats(4, 9)];
+        assert_eq!(max.blend_statistics(&term_stats, 100), Some((4, -1)));
+        assert_eq!(max.blend_statistics(&[stats(0, 0)], 100), None);
+
+        assert!(BlendedTermQuery::new(vec![], StatisticsBlend::Max, 1.0).is_err());
+    }
+
+    #[test]
+    fn test_blended_term_scores() {
+        let dir = Arc::new(RAMDirectory::default());
+        let writer = IndexWriter::new(dir, Arc::new(IndexWriterConfig::default())).unwrap();
+        let mut field_type = FieldType::default();
+        field_type.index_options = IndexOptions::DocsAndFreqs;
+        let field = |name: &str, text: &str| -> Box<dyn Fieldable> {
+            let tokens: Box<dyn TokenStream> = Box::new(WhitespaceTokenizer::new(Box::new(
+                Cursor::new(text.as_bytes().to_vec()),
+            )));
+            Box::new(Field::new(name.into(), field_type.clone(), None, Some(tokens)))
+        };
+        // a strong title match, then a weaker match in both fields
+        writer
+            .add_document(vec![field("title", "rucene rucene")])
+            .unwrap();
+        writer
+            .add_document(vec![field("title", "rucene foo"), field("body", "rucene foo")])
+            .unwrap();
+        writer
+            .add_document(vec![field("title", "foo bar"), field("body", "foo bar")])
+            .unwrap();
+        let reader = Arc::new(writer.get_reader(true, false).unwrap());
+        let searcher = DefaultIndexSearcher::new(reader);
+
+        let query = query(StatisticsBlend::Max);
+        let mut collector = TopDocsCollector::new(10);
+        searcher.search(&query, &mut collector).unwrap();
+        let top_docs = collector.top_docs();
+        let hits: Vec<(DocId, f32)> = top_docs
+            .score_docs()
+            .iter()
+            .map(|d| (d.doc_id(), d.score()))
+            .collect();
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0].0, 0);
+        assert_eq!(hits[1].0, 1);
+        assert!(hits[0].1 > hits[1].1);
+
+        // the best field plus the others times the tie breaker
+        let title_only = query.clone().with_tie_breaker(0.0).unwrap();
+        let mut collector = TopDocsCollector::new(10);
+        searcher.search(&title_only, &mut collector).unwrap();
+        let scores: Vec<f32> = collector
+            .top_docs()
+            .score_docs()
+            .iter()
+            .map(|d| d.score())
+            .collect();
+        assert!((scores[1] - hits[1].1).abs() < 0.01 * hits[1].1);
+        assert!(scores[1] <= hits[1].1);
+
+        assert!(query.clone().with_tie_breaker(1.5).is_err());
+        assert!(query.with_tie_breaker(-0.1).is_err());
+    }
+}
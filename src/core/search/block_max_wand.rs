@@ -0,0 +1,414 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::search::disjunction::DisjunctionSumScorer;
+use core::search::{DocIterator, Scorer, NO_MORE_DOCS};
+use core::util::DocId;
+use error::Result;
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+/// A disjunction scorer that sums the scores of its sub scorers, like
+/// `DisjunctionSumScorer`, but which skips the documents that can't be
+/// competitive once a minimum competitive score was set through
+/// `Scorer::set_min_competitive_score`.
+///
+/// Until then it simply iterates a `DisjunctionSumScorer`. Afterwards it
+/// uses the block-max WAND algorithm: the sub scorers are kept in a heap
+/// ordered by doc, and the first doc on which the sum of the max scores of the
+/// sub scorers positioned on or before it reaches the minimum competitive score
+/// is the *pivot*. Docs before the pivot are skipped. Then, if the sum of the
+/// max scores within the block of docs containing the pivot is still too low,
+/// the whole block is skipped.
+///
+/// Sub scorers must not support two phase iteration.
+pub struct BlockMaxWandScorer {
+    // used until a minimum competitive score is set
+    disjunction: Option<DisjunctionSumScorer<Box<dyn Scorer>>>,
+    scorers: Vec<Box<dyn Scorer>>,
+    // upper bound of the score of each sub scorer over the whole segment
+    max_scores: Vec<f32>,
+    // (doc, index in `scorers`) of each sub scorer, the smallest doc on top
+    heap: BinaryHeap<Reverse<(DocId, usize)>>,
+    // the entries popped from `heap` while looking for the next candidate
+    lead: Vec<(DocId, usize)>,
+    doc: DocId,
+    min_competitive_score: f32,
+    cost: usize,
+}
+
+impl BlockMaxWandScorer {
+    pub fn new(scorers: Vec<Box<dyn Scorer>>) -> Result<BlockMaxWandScorer> {
+        assert!(scorers.len() > 1);
+        debug_assert!(scorers.iter().all(|s| !s.support_two_phase()));
+
+        let cost = scorers.iter().map(|s| s.cost()).sum();
+        let num_scorers = scorers.len();
+        Ok(BlockMaxWandScorer {
+            disjunction: Some(DisjunctionSumScorer::new(scorers)),
+            scorers: Vec::with_capacity(num_scorers),
+            max_scores: Vec::with_capacity(num_scorers),
+            heap: BinaryHeap::with_capacity(num_scorers),
+            lead: Vec::with_capacity(num_scorers),
+            doc: -1,
+            min_competitive_score: 0f32,
+            cost,
+        })
+    }
+
+    /// Takes the sub scorers back from the plain disjunction, on the doc it
+    /// was positioned on.
+    fn switch_to_wand(&mut self) -> Result<()> {
+        if let Some(disjunction) = self.disjunction.take() {
+            self.doc = disjunction.doc_id();
+            self.scorers = disjunction.into_sub_scorers();
+            for (i, scorer) in self.scorers.iter_mut().enumerate() {
+                self.max_scores.push(scorer.max_score(NO_MORE_DOCS)?);
+                self.heap.push(Reverse((scorer.doc_id(), i)));
+            }
+        }
+        Ok(())
+    }
+
+    fn top_doc(&self) -> DocId {
+        self.heap.peek().map_or(NO_MORE_DOCS, |e| (e.0).0)
+    }
+
+    /// Advances the sub scorers positioned before `target`.
+    fn advance_head(&mut self, target: DocId) -> Result<()> {
+        loop {
+            let (doc, i) = match self.heap.peek() {
+                Some(&Reverse(entry)) => entry,
+                None => return Ok(()),
+            };
+            if doc >= target {
+                return Ok(());
+            }
+            self.heap.pop();
+            let doc = self.scorers[i].advance(target)?;
+            self.heap.push(Reverse((doc, i)));
+        }
+    }
+
+    /// Moves the sub scorers positioned on or before `up_to` from the heap to
+    /// `lead`.
+    fn pop_lead_up_to(&mut self, up_to: DocId) {
+        loop {
+            match self.heap.peek() {
+                Some(&Reverse((doc, _))) if doc <= up_to && doc != NO_MORE_DOCS => {}
+                _ => return,
+            }
+            if let Some(Reverse(entry)) = self.heap.pop() {
+                self.lead.push(entry);
+            }
+        }
+    }
+
+    fn push_back_lead(&mut self) {
+        for entry in self.lead.drain(..) {
+            self.heap.push(Reverse(entry));
+        }
+    }
+
+    /// Returns the first doc on or after `target` that may be competitive.
+    fn next_candidate(&mut self, target: DocId) -> Result<DocId> {
+        let mut target = target;
+        loop {
+            self.advance_head(target)?;
+
+            let first_doc = self.top_doc();
+            if first_doc == NO_MORE_DOCS || self.min_competitive_score <= 0f32 {
+                return Ok(first_doc);
+            }
+
+            let mut pivot = NO_MORE_DOCS;
+            let mut max_score_sum = 0f32;
+            while let Some(Reverse((doc, i))) = self.heap.pop() {
+                self.lead.push((doc, i));
+                if doc == NO_MORE_DOCS {
+                    break;
+                }
+                max_score_sum += self.max_scores[i];
+                if max_score_sum >= self.min_competitive_score {
+                    pivot = doc;
+                    break;
+                }
+            }
+            if pivot == NO_MORE_DOCS {
+                // even all the remaining scorers together can't be competitive
+                self.push_back_lead();
+                return Ok(NO_MORE_DOCS);
+            }
+            if first_doc < pivot {
+                // docs before the pivot can't be competitive
+                self.push_back_lead();
+                target = pivot;
+                continue;
+            }
+
+            // every scorer popped so far is on the pivot, check the max scores
+            // of the block of docs containing it
+            self.pop_lead_up_to(pivot);
+            let mut up_to = NO_MORE_DOCS;
+            for &(_, i) in &self.lead {
+                up_to = up_to.min(self.scorers[i].advance_shallow(pivot)?);
+            }
+            self.pop_lead_up_to(up_to);
+            let mut block_max_score = 0f32;
+            for &(_, i) in &self.lead {
+                block_max_score += self.scorers[i].max_score(up_to)?;
+            }
+            self.push_back_lead();
+            if block_max_score >= self.min_competitive_score {
+                return Ok(pivot);
+            }
+            if up_to == NO_MORE_DOCS {
+                return Ok(NO_MORE_DOCS);
+            }
+            target = up_to + 1;
+        }
+    }
+}
+
+impl Scorer for BlockMaxWandScorer {
+    fn score(&mut self) -> Result<f32> {
+        if let Some(ref mut disjunction) = self.disjunction {
+            return disjunction.score();
+        }
+        let mut score = 0f32;
+        for scorer in &mut self.scorers {
+            if scorer.doc_id() == self.doc {
+                score += scorer.score()?;
+            }
+        }
+        Ok(score)
+    }
+
+    fn set_min_competitive_score(&mut self, min_score: f32) {
+        if min_score > self.min_competitive_score {
+            self.min_competitive_score = min_score;
+        }
+    }
+
+    fn max_score(&mut self, up_to: DocId) -> Result<f32> {
+        self.switch_to_wand()?;
+        let mut max_score = 0f32;
+        for scorer in &mut self.scorers {
+            if scorer.doc_id() <= up_to {
+                max_score += scorer.max_score(up_to)?;
+            }
+        }
+        Ok(max_score)
+    }
+}
+
+impl DocIterator for BlockMaxWandScorer {
+    fn doc_id(&self) -> DocId {
+        match self.disjunction {
+            Some(ref disjunction) => disjunction.doc_id(),
+            None => self.doc,
+        }
+    }
+
+    fn next(&mut self) -> Result<DocId> {
+        if self.min_competitive_score > 0f32 {
+            self.switch_to_wand()?;
+        }
+        if let Some(ref mut disjunction) = self.disjunction {
+            return disjunction.next();
+        }
+        if self.doc != NO_MORE_DOCS {
+            let target = self.doc + 1;
+            self.doc = self.next_candidate(target)?;
+        }
+        Ok(self.doc)
+    }
+
+    fn advance(&mut self, target: DocId) -> Result<DocId> {
+        if self.min_competitive_score > 0f32 {
+            self.switch_to_wand()?;
+        }
+        if let Some(ref mut disjunction) = self.disjunction {
+            return disjunction.advance(target);
+        }
+        self.doc = self.next_candidate(target)?;
+        Ok(self.doc)
+    }
+
+    fn cost(&self) -> usize {
+        self.cost
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A scorer over a fixed list of (doc, score), whose blocks are made of
+    /// `block_size` consecutive docs of the list.
+    struct BlockScorer {
+        docs: Vec<(DocId, f32)>,
+        block_size: usize,
+        idx: Option<usize>,
+        doc: DocId,
+    }
+
+    impl BlockScorer {
+        fn new(docs: Vec<(DocId, f32)>, block_size: usize) -> Box<dyn Scorer> {
+            Box::new(BlockScorer {
+                docs,
+                block_size,
+                idx: None,
+                doc: -1,
+            })
+        }
+    }
+
+    impl Scorer for BlockScorer {
+        fn score(&mut self) -> Result<f32> {
+            Ok(self.docs[self.idx.unwrap()].1)
+        }
+
+        fn max_score(&mut self, up_to: DocId) -> Result<f32> {
+            Ok(self
+                .docs
+                .iter()
+                .filter(|&&(doc, _)| doc >= self.doc && doc <= up_to)
+                .map(|&(_, score)| score)
+                .fold(0f32, f32::max))
+        }
+
+        fn advance_shallow(&mut self, target: DocId) -> Result<DocId> {
+            if let Some(pos) = self.docs.iter().position(|&(doc, _)| doc >= target) {
+                let block_end = (pos / self.block_size + 1) * self.block_size - 1;
+                if block_end < self.docs.len() - 1 {
+                    return Ok(self.docs[block_end].0);
+                }
+            }
+            Ok(NO_MORE_DOCS)
+        }
+    }
+
+    impl DocIterator for BlockScorer {
+        fn doc_id(&self) -> DocId {
+            self.doc
+        }
+
+        fn next(&mut self) -> Result<DocId> {
+            let idx = self.idx.map_or(0, |i| i + 1);
+            self.idx = Some(idx);
+            self.doc = if idx < self.docs.len() {
+                self.docs[idx].0
+            } else {
+                NO_MORE_DOCS
+            };
+            Ok(self.doc)
+        }
+
+        fn advance(&mut self, target: DocId) -> Result<DocId> {
+            loop {
+                let doc = self.next()?;
+                if doc >= target {
+                    return Ok(doc);
+                }
+            }
+        }
+
+        fn cost(&self) -> usize {
+            self.docs.len()
+        }
+    }
+
+    fn collect(scorer: &mut BlockMaxWandScorer) -> Vec<(DocId, f32)> {
+        let mut hits = vec![];
+        loop {
+            let doc = scorer.next().unwrap();
+            if doc == NO_MORE_DOCS {
+                break;
+            }
+            hits.push((doc, scorer.score().unwrap()));
+        }
+        hits
+    }
+
+    #[test]
+    fn test_disjunction() {
+        let scorers = vec![
+            BlockScorer::new(vec![(1, 1.0), (3, 1.0), (5, 1.0)], 8),
+            BlockScorer::new(vec![(2, 2.0), (3, 2.0)], 8),
+        ];
+        let mut scorer = BlockMaxWandScorer::new(scorers).unwrap();
+        assert_eq!(scorer.cost(), 5);
+        assert_eq!(
+            collect(&mut scorer),
+            vec![(1, 1.0), (2, 2.0), (3, 3.0), (5, 1.0)]
+        );
+    }
+
+    #[test]
+    fn test_max_score_while_iterating() {
+        let scorers = vec![
+            BlockScorer::new(vec![(1, 1.0), (3, 1.0), (5, 1.0)], 8),
+            BlockScorer::new(vec![(2, 2.0), (3, 2.0)], 8),
+        ];
+        let mut scorer = BlockMaxWandScorer::new(scorers).unwrap();
+        assert_eq!(scorer.next().unwrap(), 1);
+        // takes the sub scorers back from the plain disjunction
+        assert!((scorer.max_score(3).unwrap() - 3.0).abs() < ::std::f32::EPSILON);
+        assert_eq!(scorer.doc_id(), 1);
+        assert_eq!(collect(&mut scorer), vec![(2, 2.0), (3, 3.0), (5, 1.0)]);
+    }
+
+    #[test]
+    fn test_skip_non_competitive_docs() {
+        let scorers = vec![
+            BlockScorer::new(vec![(1, 1.0), (2, 1.0), (3, 1.0), (4, 1.0), (5, 1.0)], 8),
+            BlockScorer::new(vec![(3, 2.0), (5, 2.0), (7, 2.0)], 8),
+        ];
+        let mut scorer = BlockMaxWandScorer::new(scorers).unwrap();
+        scorer.set_min_competitive_score(2.5);
+        assert_eq!(collect(&mut scorer), vec![(3, 3.0), (5, 3.0)]);
+    }
+
+    #[test]
+    fn test_skip_non_competitive_blocks() {
+        // the first block of each scorer has low scores
+        let scorers = vec![
+            BlockScorer::new(vec![(0, 0.5), (2, 0.5), (4, 3.0), (6, 3.0)], 2),
+            BlockScorer::new(vec![(1, 0.5), (2, 0.5), (4, 1.0), (5, 1.0)], 2),
+        ];
+        let mut scorer = BlockMaxWandScorer::new(scorers).unwrap();
+        scorer.set_min_competitive_score(2.0);
+        let hits = collect(&mut scorer);
+        assert_eq!(hits[0], (4, 4.0));
+        assert!(hits.iter().all(|&(doc, _)| doc >= 4));
+        assert!(hits.contains(&(6, 3.0)));
+    }
+
+    #[test]
+    fn test_set_min_competitive_score_while_iterating() {
+        let scorers = vec![
+            BlockScorer::new(vec![(1, 1.0), (2, 1.0), (3, 1.0), (4, 1.0)], 8),
+            BlockScorer::new(vec![(2, 1.5), (4, 1.5)], 8),
+        ];
+        let mut scorer = BlockMaxWandScorer::new(scorers).unwrap();
+        assert_eq!(scorer.next().unwrap(), 1);
+        scorer.set_min_competitive_score(2.0);
+        // no sub scorer can be competitive alone
+        assert_eq!(scorer.next().unwrap(), 2);
+        assert!((scorer.score().unwrap() - 2.5).abs() < ::std::f32::EPSILON);
+        assert_eq!(scorer.next().unwrap(), 4);
+        assert_eq!(scorer.next().unwrap(), NO_MORE_DOCS);
+    }
+}
@@ -11,7 +11,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use error::Result;
+use error::{ErrorKind::IllegalArgument, Result};
 use std::fmt;
 use std::sync::Arc;
 
@@ -43,9 +43,13 @@ lazy_static! {
 pub const DEFAULT_BM25_K1: f32 = 1.2;
 pub const DEFAULT_BM25_B: f32 = 0.75;
 
+#[derive(Clone, Copy, Debug)]
 pub struct BM25Similarity {
     k1: f32,
     b: f32,
+    // constant factor applied to the weight of every query, on top of the
+    // boost of the query itself
+    boost: f32,
 }
 
 impl Default for BM25Similarity {
@@ -56,7 +60,24 @@ impl Default for BM25Similarity {
 
 impl BM25Similarity {
     pub fn new(k1: f32, b: f32) -> BM25Similarity {
-        BM25Similarity { k1, b }
+        BM25Similarity { k1, b, boost: 1.0 }
+    }
+
+    /// Sets a constant boost multiplied into the score of every query scored
+    /// with this similarity, defaults to 1.0.
+    pub fn with_boost(mut self, boost: f32) -> Result<BM25Similarity> {
+        if !boost.is_finite() || boost < 0.0 {
+            bail!(IllegalArgument(format!(
+                "boost must be finite and non-negative, got {}",
+                boost
+            )));
+        }
+        self.boost = boost;
+        Ok(self)
+    }
+
+    pub fn boost(&self) -> f32 {
+        self.boost
     }
 
     fn sloppy_freq(distance: i32) -> f32 {
@@ -169,6 +190,7 @@ impl<C: Codec> Similarity<C> for BM25Similarity {
             cache,
             self.idf_explain(collection_stats, term_stats),
             BM25Similarity::avg_field_length(collection_stats),
+            self.boost,
             boost,
         ))
     }
@@ -176,7 +198,15 @@ impl<C: Codec> Similarity<C> for BM25Similarity {
 
 impl fmt::Display for BM25Similarity {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "BM25Similarity(k1: {}, b: {})", self.k1, self.b)
+        if (self.boost - 1.0f32).abs() > ::std::f32::EPSILON {
+            write!(
+                f,
+                "BM25Similarity(k1: {}, b: {}, boost: {})",
+                self.k1, self.b, self.boost
+            )
+        } else {
+            write!(f, "BM25Similarity(k1: {}, b: {})", self.k1, self.b)
+        }
     }
 }
 
@@ -217,6 +247,32 @@ impl SimScorer for BM25SimScorer {
     fn compute_slop_factor(&self, distance: i32) -> f32 {
         BM25Similarity::sloppy_freq(distance)
     }
+
+    /// `freq / (freq + norm)` never reaches 1, so the score is bounded by
+    /// `weight * (k1 + 1)`.
+    fn max_score(&self) -> f32 {
+        self.weight * (self.k1 + 1.0)
+    }
+
+    /// The score grows with the freq and decreases with the field length, so
+    /// it is bounded by the one of `max_freq` in the shortest field.
+    fn max_score_with_freq(&self, max_freq: f32) -> f32 {
+        let min_norm = if self.norms.is_some() {
+            self.cache.iter().cloned().fold(::std::f32::INFINITY, f32::min)
+        } else {
+            self.k1
+        };
+        self.weight * (self.k1 + 1.0) * max_freq / (max_freq + min_norm)
+    }
+
+    fn max_score_with_freq_and_norm(&self, max_freq: f32, norm: i64) -> f32 {
+        let norm = if self.norms.is_some() {
+            self.cache[(norm & 0xFF) as usize]
+        } else {
+            self.k1
+        };
+        self.weight * (self.k1 + 1.0) * max_freq / (max_freq + norm)
+    }
 }
 
 pub struct BM25SimWeight {
@@ -226,6 +282,9 @@ pub struct BM25SimWeight {
     idf: f32,
     field: String,
     cache: Arc<[f32; 256]>,
+    // the constant boost of the similarity
+    sim_boost: f32,
+    // the query boost times `sim_boost`
     boost: f32,
     weight: f32,
     idf_explanation: Explanation,
@@ -241,6 +300,7 @@ impl BM25SimWeight {
         cache: [f32; 256],
         idf_explanation: Explanation,
         avg_dl: f32,
+        sim_boost: f32,
         boost: f32,
     ) -> BM25SimWeight {
         let mut weight = BM25SimWeight {
@@ -249,6 +309,7 @@ impl BM25SimWeight {
             idf,
             field,
             cache: Arc::new(cache),
+            sim_boost,
             boost: 1.0,
             weight: 0.0,
             idf_explanation,
@@ -357,8 +418,8 @@ impl BM25SimWeight {
     }
 
     fn do_normalize(&mut self, boost: f32) {
-        self.boost = boost;
-        self.weight = self.idf * boost;
+        self.boost = boost * self.sim_boost;
+        self.weight = self.idf * self.boost;
     }
 }
 
@@ -391,6 +452,8 @@ impl<C: Codec> SimWeight<C> for BM25SimWeight {
 mod tests {
     use super::*;
     use core::index::tests::MockLeafReader;
+    use core::index::LeafReader;
+    use error::{Error, ErrorKind};
 
     // copy from Lucene TestBM25Similarity
     #[test]
@@ -459,4 +522,76 @@ mod tests {
 
         assert!(score1 > score2);
     }
+
+    #[test]
+    fn test_similarity_boost() {
+        let collection_stats = CollectionStatistics::new(String::from("world"), 32, 32, 120, -1);
+        let term_stats = vec![TermStatistics::new(Vec::new(), 1, -1)];
+        let leaf_reader = MockLeafReader::new(1);
+
+        let sim = BM25Similarity::default();
+        let sim_weight = sim.compute_weight(&collection_stats, &term_stats, None, 1.0f32);
+        let score = sim_weight.sim_scorer(&leaf_reader).unwrap().score(1, 10.0).unwrap();
+
+        let boosted_sim = BM25Similarity::default().with_boost(2.0).unwrap();
+        let boosted_weight =
+            boosted_sim.compute_weight(&collection_stats, &term_stats, None, 1.0f32);
+        let mut boosted_scorer = boosted_weight.sim_scorer(&leaf_reader).unwrap();
+        let boosted_score = boosted_scorer.score(1, 10.0).unwrap();
+        assert!((boosted_score - 2.0 * score).abs() < 1e-5);
+
+        // the boost of the similarity is kept when the query is normalized
+        let mut boosted_weight =
+            boosted_sim.compute_weight(&collection_stats, &term_stats, None, 1.0f32);
+        boosted_weight.normalize(1.0, 3.0);
+        let mut boosted_scorer = boosted_weight.sim_scorer(&leaf_reader).unwrap();
+        let boosted_score = boosted_scorer.score(1, 10.0).unwrap();
+        assert!((boosted_score - 6.0 * score).abs() < 1e-4);
+
+        for &boost in &[-1.0, ::std::f32::NAN, ::std::f32::INFINITY] {
+            match BM25Similarity::default().with_boost(boost) {
+                Err(Error(ErrorKind::IllegalArgument(_), _)) => {}
+                _ => panic!("boost {} should be rejected", boost),
+            }
+        }
+    }
+
+    #[test]
+    fn test_max_score_with_freq() {
+        let collection_stats = CollectionStatistics::new(String::from("world"), 32, 32, 120, -1);
+        let term_stats = vec![TermStatistics::new(Vec::new(), 1, -1)];
+        let leaf_reader = MockLeafReader::new(1);
+        let sim_weight =
+            BM25Similarity::default().compute_weight(&collection_stats, &term_stats, None, 1.0);
+        let mut sim_scorer = sim_weight.sim_scorer(&leaf_reader).unwrap();
+
+        let max_score = sim_scorer.max_score_with_freq(10.0);
+        assert!(max_score < sim_scorer.max_score());
+        assert!(sim_scorer.max_score_with_freq(1.0) < max_score);
+        for &doc in &[1, 2] {
+            for &freq in &[1.0, 5.0, 10.0] {
+                assert!(sim_scorer.score(doc, freq).unwrap() <= max_score);
+            }
+        }
+    }
+
+    #[test]
+    fn test_max_score_with_freq_and_norm() {
+        let collection_stats = CollectionStatistics::new(String::from("world"), 32, 32, 120, -1);
+        let term_stats = vec![TermStatistics::new(Vec::new(), 1, -1)];
+        let leaf_reader = MockLeafReader::new(1);
+        let sim_weight =
+            BM25Similarity::default().compute_weight(&collection_stats, &term_stats, None, 1.0);
+        let mut sim_scorer = sim_weight.sim_scorer(&leaf_reader).unwrap();
+        let norms = leaf_reader.norm_values("world").unwrap().unwrap();
+
+        for &doc in &[1, 2] {
+            let norm = norms.get(doc).unwrap();
+            let max_score = sim_scorer.max_score_with_freq_and_norm(10.0, norm);
+            assert!(max_score <= sim_scorer.max_score_with_freq(10.0));
+            // the bound of the norm of the doc is reached by its max freq
+            assert!((sim_scorer.score(doc, 10.0).unwrap() - max_score).abs() < 1e-5);
+            assert!(sim_scorer.score(doc, 5.0).unwrap() < max_score);
+        }
+    }
 }
@@ -16,21 +16,42 @@ use std::fmt;
 
 use core::codec::Codec;
 use core::index::LeafReaderContext;
-use core::search::conjunction::ConjunctionScorer;
+use core::search::block_max_wand::BlockMaxWandScorer;
+use core::search::bulk_scorer::BulkScorer;
+use core::search::conjunction::{ConjunctionBulkScorer, ConjunctionScorer};
 use core::search::disjunction::DisjunctionSumScorer;
 use core::search::explanation::Explanation;
-use core::search::match_all::ConstantScoreQuery;
+use core::search::match_all::{ConstantScoreQuery, ConstantScoreScorer};
+use core::search::min_should_match::MinShouldMatchSumScorer;
 use core::search::req_opt::ReqOptScorer;
+use core::search::req_penalty::ReqPenaltyScorer;
 use core::search::searcher::SearchPlanBuilder;
 use core::search::term_query::TermQuery;
 use core::search::{Query, Scorer, Weight};
 use core::util::DocId;
 use error::{ErrorKind::IllegalArgument, Result};
 
+/// A query matching documents based on the matches of its sub queries.
+///
+/// - `MUST` clauses must match and their scores are added to the score.
+/// - `FILTER` clauses must match but don't contribute to the score.
+/// - `SHOULD` clauses add their scores to the score when they match. By default
+/// at least one of them must match if there is no `MUST` or `FILTER` clause,
+/// and none otherwise, this can be changed with `build_with_min_should_match`.
+/// - `SHOULD_NOT` clauses multiply the score by a penalty when they match.
+///
+/// A boolean query used as a clause is scored as a whole, and its own clauses
+/// and minimum should match only apply within it. For example with
+/// `MUST(SHOULD(a, b)) + SHOULD(c)`, a doc must match `a` or `b`, and its score
+/// is the sum of the scores of those of `a`, `b` and `c` which match. To only
+/// use such a nested group to select docs, without it contributing to the
+/// score, add it as a `FILTER` clause instead.
 pub struct BooleanQuery<C: Codec> {
     must_queries: Vec<Box<dyn Query<C>>>,
     should_queries: Vec<Box<dyn Query<C>>>,
     filter_queries: Vec<Box<dyn Query<C>>>,
+    should_not_queries: Vec<Box<dyn Query<C>>>,
+    should_not_penalty: f32,
     minimum_should_match: i32,
 }
 
@@ -42,7 +63,67 @@ impl<C: Codec> BooleanQuery<C> {
         shoulds: Vec<Box<dyn Query<C>>>,
         filters: Vec<Box<dyn Query<C>>>,
     ) -> Result<Box<dyn Query<C>>> {
-        let minimum_should_match = if musts.is_empty() { 1 } else { 0 };
+        Self::build_with_should_not(musts, shoulds, filters, vec![], 1.0f32)
+    }
+
+    /// Build a boolean query with `SHOULD_NOT` clauses.
+    ///
+    /// Docs matching any of the `should_nots` clauses are not excluded from the
+    /// result, instead their score is multiplied by `should_not_penalty`, which
+    /// must be in range `[0, 1]`. `SHOULD_NOT` clauses never make a doc match
+    /// by themselves, so at least one of the other clauses must be present.
+    pub fn build_with_should_not(
+        musts: Vec<Box<dyn Query<C>>>,
+        shoulds: Vec<Box<dyn Query<C>>>,
+        filters: Vec<Box<dyn Query<C>>>,
+        should_nots: Vec<Box<dyn Query<C>>>,
+        should_not_penalty: f32,
+    ) -> Result<Box<dyn Query<C>>> {
+        Self::do_build(musts, shoulds, filters, should_nots, should_not_penalty, None)
+    }
+
+    /// Build a boolean query on which at least `minimum_should_match` of the
+    /// `shoulds` clauses must match, even if there are `MUST` or `FILTER`
+    /// clauses. A query with only `SHOULD` clauses always needs at least one
+    /// of them to match.
+    pub fn build_with_min_should_match(
+        musts: Vec<Box<dyn Query<C>>>,
+        shoulds: Vec<Box<dyn Query<C>>>,
+        filters: Vec<Box<dyn Query<C>>>,
+        minimum_should_match: i32,
+    ) -> Result<Box<dyn Query<C>>> {
+        if minimum_should_match < 0 || minimum_should_match as usize > shoulds.len() {
+            bail!(IllegalArgument(format!(
+                "boolean query minimum_should_match must be in [0, {}], got {}",
+                shoulds.len(),
+                minimum_should_match
+            )));
+        }
+        Self::do_build(
+            musts,
+            shoulds,
+            filters,
+            vec![],
+            1.0f32,
+            Some(minimum_should_match),
+        )
+    }
+
+    fn do_build(
+        musts: Vec<Box<dyn Query<C>>>,
+        shoulds: Vec<Box<dyn Query<C>>>,
+        filters: Vec<Box<dyn Query<C>>>,
+        should_nots: Vec<Box<dyn Query<C>>>,
+        should_not_penalty: f32,
+        minimum_should_match: Option<i32>,
+    ) -> Result<Box<dyn Query<C>>> {
+        let default_should_match = if musts.is_empty() && filters.is_empty() {
+            1
+        } else {
+            0
+        };
+        let minimum_should_match =
+            minimum_should_match.map_or(default_should_match, |m| m.max(default_should_match));
         let mut musts = musts;
         let mut shoulds = shoulds;
         let mut filters = filters;
@@ -51,7 +132,17 @@ impl<C: Codec> BooleanQuery<C> {
                 "boolean query should at least contain one inner query!".into()
             ));
         }
-        if musts.len() + shoulds.len() + filters.len() == 1 {
+        if !should_nots.is_empty() && !(should_not_penalty >= 0f32 && should_not_penalty <= 1f32)
+        {
+            bail!(IllegalArgument(format!(
+                "boolean query should_not penalty must be in [0, 1], got {}",
+                should_not_penalty
+            )));
+        }
+        if should_nots.is_empty()
+            && musts.len() + shoulds.len() + filters.len() == 1
+            && minimum_should_match <= 1
+        {
             let query = if musts.len() == 1 {
                 musts.remove(0)
             } else if shoulds.len() == 1 {
@@ -65,10 +156,20 @@ impl<C: Codec> BooleanQuery<C> {
             must_queries: musts,
             should_queries: shoulds,
             filter_queries: filters,
+            should_not_queries: should_nots,
+            should_not_penalty,
             minimum_should_match,
         }))
     }
 
+    pub fn must_queries(&self) -> &[Box<dyn Query<C>>] {
+        &self.must_queries
+    }
+
+    pub fn should_queries(&self) -> &[Box<dyn Query<C>>] {
+        &self.should_queries
+    }
+
     fn queries_to_str(&self, queries: &[Box<dyn Query<C>>]) -> String {
         let query_strs: Vec<String> = queries.iter().map(|q| format!("{}", q)).collect();
         query_strs.join(", ")
@@ -81,24 +182,33 @@ impl<C: Codec> Query<C> for BooleanQuery<C> {
         searcher: &dyn SearchPlanBuilder<C>,
         needs_scores: bool,
     ) -> Result<Box<dyn Weight<C>>> {
-        let mut must_weights =
-            Vec::with_capacity(self.must_queries.len() + self.filter_queries.len());
+        let mut must_weights = Vec::with_capacity(self.must_queries.len());
         for q in &self.must_queries {
             must_weights.push(searcher.create_weight(q.as_ref(), needs_scores)?);
         }
+        // filter clauses must match but never contribute to the score
+        let mut filter_weights = Vec::with_capacity(self.filter_queries.len());
         for q in &self.filter_queries {
-            must_weights.push(searcher.create_weight(q.as_ref(), false)?);
+            filter_weights.push(searcher.create_weight(q.as_ref(), false)?);
         }
         let mut should_weights = Vec::with_capacity(self.should_queries.len());
         for q in &self.should_queries {
             should_weights.push(searcher.create_weight(q.as_ref(), needs_scores)?);
         }
+        // should_not clauses only adjust scores, so they are useless if scores are not needed,
+        // and only their presence matters if they are.
+        let mut should_not_weights = Vec::with_capacity(self.should_not_queries.len());
+        if needs_scores {
+            for q in &self.should_not_queries {
+                should_not_weights.push(searcher.create_weight(q.as_ref(), false)?);
+            }
+        }
 
-        Ok(Box::new(BooleanWeight::new(
-            must_weights,
-            should_weights,
-            needs_scores,
-        )))
+        let mut weight =
+            BooleanWeight::new(must_weights, should_weights, filter_weights, needs_scores);
+        weight.set_should_not(should_not_weights, self.should_not_penalty);
+        weight.set_minimum_should_match(self.minimum_should_match);
+        Ok(Box::new(weight))
     }
 
     fn extract_terms(&self) -> Vec<TermQuery> {
@@ -116,6 +226,12 @@ impl<C: Codec> Query<C> for BooleanQuery<C> {
             }
         }
 
+        for query in &self.filter_queries {
+            for term_query in query.extract_terms() {
+                term_query_list.push(term_query);
+            }
+        }
+
         term_query_list
     }
 
@@ -133,10 +249,17 @@ impl<C: Codec> fmt::Display for BooleanQuery<C> {
         let must_str = self.queries_to_str(&self.must_queries);
         let should_str = self.queries_to_str(&self.should_queries);
         let filters_str = self.queries_to_str(&self.filter_queries);
+        let should_not_str = self.queries_to_str(&self.should_not_queries);
         write!(
             f,
-            "BooleanQuery(must: [{}], should: [{}], filters: [{}], match: {})",
-            must_str, should_str, filters_str, self.minimum_should_match
+            "BooleanQuery(must: [{}], should: [{}], filters: [{}], should_not: [{}], \
+             should_not_penalty: {}, match: {})",
+            must_str,
+            should_str,
+            filters_str,
+            should_not_str,
+            self.should_not_penalty,
+            self.minimum_should_match
         )
     }
 }
@@ -144,7 +267,9 @@ impl<C: Codec> fmt::Display for BooleanQuery<C> {
 pub struct BooleanWeight<C: Codec> {
     must_weights: Vec<Box<dyn Weight<C>>>,
     should_weights: Vec<Box<dyn Weight<C>>>,
-    #[allow(dead_code)]
+    filter_weights: Vec<Box<dyn Weight<C>>>,
+    should_not_weights: Vec<Box<dyn Weight<C>>>,
+    should_not_penalty: f32,
     minimum_should_match: i32,
     needs_scores: bool,
 }
@@ -153,17 +278,72 @@ impl<C: Codec> BooleanWeight<C> {
     pub fn new(
         musts: Vec<Box<dyn Weight<C>>>,
         shoulds: Vec<Box<dyn Weight<C>>>,
+        filters: Vec<Box<dyn Weight<C>>>,
         needs_scores: bool,
     ) -> BooleanWeight<C> {
-        let minimum_should_match = if musts.is_empty() { 1 } else { 0 };
+        let minimum_should_match = if musts.is_empty() && filters.is_empty() {
+            1
+        } else {
+            0
+        };
         BooleanWeight {
             must_weights: musts,
             should_weights: shoulds,
+            filter_weights: filters,
+            should_not_weights: vec![],
+            should_not_penalty: 1.0f32,
             minimum_should_match,
             needs_scores,
         }
     }
 
+    /// Set the `SHOULD_NOT` clauses whose matching docs are scored with `penalty`
+    pub fn set_should_not(&mut self, should_nots: Vec<Box<dyn Weight<C>>>, penalty: f32) {
+        self.should_not_weights = should_nots;
+        self.should_not_penalty = penalty;
+    }
+
+    /// Set the number of `SHOULD` clauses a doc must match, at least one of them
+    /// must always match if there is no `MUST` or `FILTER` clause.
+    pub fn set_minimum_should_match(&mut self, minimum_should_match: i32) {
+        debug_assert!(minimum_should_match >= 0);
+        if self.must_weights.is_empty() && self.filter_weights.is_empty() {
+            self.minimum_should_match = minimum_should_match.max(1);
+        } else {
+            self.minimum_should_match = minimum_should_match;
+        }
+    }
+
+    /// The scorers of the `MUST` and `FILTER` clauses, `None` if one of them
+    /// matches no doc of the segment.
+    fn required_scorers(
+        &self,
+        leaf_reader: &LeafReaderContext<'_, C>,
+    ) -> Result<Option<Vec<Box<dyn Scorer>>>> {
+        let mut scorers: Vec<Box<dyn Scorer>> =
+            Vec::with_capacity(self.must_weights.len() + self.filter_weights.len());
+        for weight in &self.must_weights {
+            if let Some(scorer) = weight.create_scorer(leaf_reader)? {
+                scorers.push(scorer);
+            } else {
+                return Ok(None);
+            }
+        }
+        for weight in &self.filter_weights {
+            if let Some(scorer) = weight.create_scorer(leaf_reader)? {
+                if self.needs_scores {
+                    // wrap it so the filter clause won't contribute to the score
+                    scorers.push(Box::new(ConstantScoreScorer::from_scorer(0f32, scorer)));
+                } else {
+                    scorers.push(scorer);
+                }
+            } else {
+                return Ok(None);
+            }
+        }
+        Ok(Some(scorers))
+    }
+
     fn weights_to_str(&self, weights: &[Box<dyn Weight<C>>]) -> String {
         let weight_strs: Vec<String> = weights.iter().map(|q| format!("{}", q)).collect();
         weight_strs.join(", ")
@@ -175,15 +355,12 @@ impl<C: Codec> Weight<C> for BooleanWeight<C> {
         &self,
         leaf_reader: &LeafReaderContext<'_, C>,
     ) -> Result<Option<Box<dyn Scorer>>> {
-        let must_scorer: Option<Box<dyn Scorer>> = if !self.must_weights.is_empty() {
-            let mut scorers = vec![];
-            for weight in &self.must_weights {
-                if let Some(scorer) = weight.create_scorer(leaf_reader)? {
-                    scorers.push(scorer);
-                } else {
-                    return Ok(None);
-                }
-            }
+        let has_required = !self.must_weights.is_empty() || !self.filter_weights.is_empty();
+        let must_scorer: Option<Box<dyn Scorer>> = if has_required {
+            let mut scorers = match self.required_scorers(leaf_reader)? {
+                Some(scorers) => scorers,
+                None => return Ok(None),
+            };
             if scorers.len() > 1 {
                 Some(Box::new(ConjunctionScorer::new(scorers)))
             } else {
@@ -192,6 +369,7 @@ impl<C: Codec> Weight<C> for BooleanWeight<C> {
         } else {
             None
         };
+        let minimum_should_match = self.minimum_should_match.max(0) as usize;
         let should_scorer: Option<Box<dyn Scorer>> = {
             let mut scorers = vec![];
             for weight in &self.should_weights {
@@ -199,25 +377,92 @@ impl<C: Codec> Weight<C> for BooleanWeight<C> {
                     scorers.push(scorer);
                 }
             }
+            if scorers.len() < minimum_should_match {
+                return Ok(None);
+            }
             match scorers.len() {
                 0 => None,
                 1 => Some(scorers.remove(0)),
-                _ => Some(Box::new(DisjunctionSumScorer::new(scorers))),
+                n if minimum_should_match == n => Some(Box::new(ConjunctionScorer::new(scorers))),
+                _ if minimum_should_match > 1 => Some(Box::new(MinShouldMatchSumScorer::new(
+                    scorers,
+                    minimum_should_match,
+                ))),
+                _ => {
+                    if !has_required
+                        && self.needs_scores
+                        && scorers.iter().all(|s| !s.support_two_phase())
+                    {
+                        // a pure disjunction iterates like a `DisjunctionSumScorer` until
+                        // a minimum competitive score lets it skip docs
+                        Some(Box::new(BlockMaxWandScorer::new(scorers)?))
+                    } else {
+                        Some(Box::new(DisjunctionSumScorer::new(scorers)))
+                    }
+                }
             }
         };
 
-        if let Some(must) = must_scorer {
+        let scorer: Option<Box<dyn Scorer>> = if let Some(must) = must_scorer {
             if let Some(should) = should_scorer {
-                Ok(Some(Box::new(ReqOptScorer::new(must, should))))
+                if minimum_should_match > 0 {
+                    // the should clauses are required too
+                    Some(Box::new(ConjunctionScorer::new(vec![must, should])))
+                } else {
+                    Some(Box::new(ReqOptScorer::new(must, should)))
+                }
             } else {
-                Ok(Some(must))
+                Some(must)
             }
         } else {
-            if let Some(should) = should_scorer {
-                Ok(Some(should))
+            should_scorer
+        };
+
+        if let Some(scorer) = scorer {
+            let should_not_scorer: Option<Box<dyn Scorer>> = {
+                let mut scorers = vec![];
+                for weight in &self.should_not_weights {
+                    if let Some(scorer) = weight.create_scorer(leaf_reader)? {
+                        scorers.push(scorer);
+                    }
+                }
+                match scorers.len() {
+                    0 => None,
+                    1 => Some(scorers.remove(0)),
+                    _ => Some(Box::new(DisjunctionSumScorer::new(scorers))),
+                }
+            };
+            if let Some(should_not) = should_not_scorer {
+                Ok(Some(Box::new(ReqPenaltyScorer::new(
+                    scorer,
+                    should_not,
+                    self.should_not_penalty,
+                ))))
             } else {
-                Ok(None)
+                Ok(Some(scorer))
             }
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Pure conjunctions are collected led by their rarest clause, the other
+    /// boolean queries by iterating over their scorer.
+    fn bulk_scorer(
+        &self,
+        leaf_reader: &LeafReaderContext<'_, C>,
+    ) -> Result<Option<Box<dyn BulkScorer>>> {
+        if !self.should_weights.is_empty()
+            || !self.should_not_weights.is_empty()
+            || self.minimum_should_match > 0
+            || self.must_weights.len() + self.filter_weights.len() < 2
+        {
+            return Ok(None);
+        }
+        // without a bulk scorer, `create_scorer` matches nothing either
+        match self.required_scorers(leaf_reader)? {
+            Some(scorers) => Ok(Some(Box::new(ConjunctionBulkScorer::new(scorers)))),
+            None => Ok(None),
         }
     }
 
@@ -257,6 +502,33 @@ impl<C: Codec> Weight<C> for BooleanWeight<C> {
         let mut should_match_count = 0;
 
         let mut subs: Vec<Explanation> = vec![];
+        for w in &self.filter_weights {
+            let e = w.explain(reader, doc)?;
+            max_coord += 1;
+
+            if e.is_match() {
+                coord += 1;
+                match_count += 1;
+                subs.push(Explanation::new(
+                    true,
+                    0.0f32,
+                    "match on required clause, product of:".to_string(),
+                    vec![
+                        Explanation::new(true, 0.0f32, "FILTER".to_string(), vec![]),
+                        e,
+                    ],
+                ));
+            } else {
+                fail = true;
+                subs.push(Explanation::new(
+                    false,
+                    0.0f32,
+                    format!("no match on required clause ({})", w),
+                    vec![e],
+                ));
+            }
+        }
+
         for w in &self.must_weights {
             let e = w.explain(reader, doc)?;
             max_coord += 1;
@@ -316,7 +588,29 @@ impl<C: Codec> Weight<C> for BooleanWeight<C> {
             ))
         } else {
             // we have a match
-            let result = Explanation::new(true, sum, "sum of:".to_string(), subs);
+            let mut result = Explanation::new(true, sum, "sum of:".to_string(), subs);
+
+            for w in &self.should_not_weights {
+                let e = w.explain(reader, doc)?;
+                if e.is_match() {
+                    result = Explanation::new(
+                        true,
+                        sum * self.should_not_penalty,
+                        "product of:".to_string(),
+                        vec![
+                            result,
+                            Explanation::new(
+                                true,
+                                self.should_not_penalty,
+                                format!("should_not penalty, matched ({})", w),
+                                vec![],
+                            ),
+                        ],
+                    );
+                    sum *= self.should_not_penalty;
+                    break;
+                }
+            }
 
             let coord_factor = 1.0f32;
             if coord_factor != 1.0f32 {
@@ -345,10 +639,216 @@ impl<C: Codec> fmt::Display for BooleanWeight<C> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let must_str = self.weights_to_str(&self.must_weights);
         let should_str = self.weights_to_str(&self.should_weights);
+        let filter_str = self.weights_to_str(&self.filter_weights);
+        let should_not_str = self.weights_to_str(&self.should_not_weights);
         write!(
             f,
-            "BooleanWeight(must: [{}], should: [{}], min match: {}, needs score: {})",
-            must_str, should_str, self.minimum_should_match, self.needs_scores
+            "BooleanWeight(must: [{}], should: [{}], filter: [{}], should_not: [{}], \
+             should_not_penalty: {}, min match: {}, needs score: {})",
+            must_str,
+            should_str,
+            filter_str,
+            should_not_str,
+            self.should_not_penalty,
+            self.minimum_should_match,
+            self.needs_scores
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::codec::tests::TestCodec;
+    use core::index::tests::*;
+    use core::index::IndexReader;
+    use core::search::tests::*;
+    use core::search::{DocIterator, NO_MORE_DOCS};
+
+    #[test]
+    fn test_filter_clause_not_scored() {
+        let leaf_reader = MockLeafReader::new(0);
+        let index_reader = MockIndexReader::new(vec![leaf_reader]);
+        let leaves = index_reader.leaves();
+
+        let must: Box<dyn Weight<TestCodec>> = Box::new(create_mock_weight(vec![1, 2, 3, 5]));
+        let filter: Box<dyn Weight<TestCodec>> = Box::new(create_mock_weight(vec![2, 3, 4, 5]));
+        let weight = BooleanWeight::new(vec![must], vec![], vec![filter], true);
+        let mut scorer = weight.create_scorer(&leaves[0]).unwrap().unwrap();
+
+        assert_eq!(scorer.next().unwrap(), 2);
+        assert!((scorer.score().unwrap() - 2.0).abs() < ::std::f32::EPSILON);
+
+        assert_eq!(scorer.next().unwrap(), 3);
+        assert!((scorer.score().unwrap() - 3.0).abs() < ::std::f32::EPSILON);
+
+        assert_eq!(scorer.next().unwrap(), 5);
+        assert!((scorer.score().unwrap() - 5.0).abs() < ::std::f32::EPSILON);
+
+        assert_eq!(scorer.next().unwrap(), NO_MORE_DOCS);
+    }
+
+    #[test]
+    fn test_only_filter_clauses() {
+        let leaf_reader = MockLeafReader::new(0);
+        let index_reader = MockIndexReader::new(vec![leaf_reader]);
+        let leaves = index_reader.leaves();
+
+        let filter1: Box<dyn Weight<TestCodec>> = Box::new(create_mock_weight(vec![1, 2, 4, 6]));
+        let filter2: Box<dyn Weight<TestCodec>> = Box::new(create_mock_weight(vec![2, 3, 4, 6]));
+        let weight = BooleanWeight::new(vec![], vec![], vec![filter1, filter2], true);
+        let mut scorer = weight.create_scorer(&leaves[0]).unwrap().unwrap();
+
+        let mut docs = vec![];
+        loop {
+            let doc = scorer.next().unwrap();
+            if doc == NO_MORE_DOCS {
+                break;
+            }
+            assert!(scorer.score().unwrap().abs() < ::std::f32::EPSILON);
+            docs.push(doc);
+        }
+        assert_eq!(docs, vec![2, 4, 6]);
+    }
+
+    fn collect_scores(scorer: &mut dyn Scorer) -> Vec<(DocId, f32)> {
+        let mut hits = vec![];
+        loop {
+            let doc = scorer.next().unwrap();
+            if doc == NO_MORE_DOCS {
+                break;
+            }
+            hits.push((doc, scorer.score().unwrap()));
+        }
+        hits
+    }
+
+    #[test]
+    fn test_conjunction_bulk_scorer() {
+        let leaf_reader = MockLeafReader::new(0);
+        let index_reader = MockIndexReader::new(vec![leaf_reader]);
+        let leaves = index_reader.leaves();
+
+        let must: Box<dyn Weight<TestCodec>> = Box::new(create_mock_weight(vec![1, 2, 3, 5, 8]));
+        let filter: Box<dyn Weight<TestCodec>> = Box::new(create_mock_weight(vec![2, 3, 4, 8]));
+        let weight = BooleanWeight::new(vec![must], vec![], vec![filter], true);
+        let mut bulk_scorer = weight.bulk_scorer(&leaves[0]).unwrap().unwrap();
+        assert_eq!(bulk_scorer.cost(), 4);
+
+        let mut hits = vec![];
+        bulk_scorer
+            .score(
+                &mut |doc: DocId, scorer: &mut dyn Scorer| -> Result<()> {
+                    hits.push((doc, scorer.score()?));
+                    Ok(())
+                },
+                None,
+                0,
+                NO_MORE_DOCS,
+            )
+            .unwrap();
+        // the same hits as the scorer, the filter clause isn't scored
+        let mut scorer = weight.create_scorer(&leaves[0]).unwrap().unwrap();
+        assert_eq!(hits, collect_scores(scorer.as_mut()));
+        assert_eq!(hits, vec![(2, 2.0), (3, 3.0), (8, 8.0)]);
+
+        // the other boolean queries are collected with their scorer
+        let a: Box<dyn Weight<TestCodec>> = Box::new(create_mock_weight(vec![1, 2]));
+        let b: Box<dyn Weight<TestCodec>> = Box::new(create_mock_weight(vec![2, 3]));
+        let weight = BooleanWeight::new(vec![a], vec![b], vec![], true);
+        assert!(weight.bulk_scorer(&leaves[0]).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_nested_should_in_must() {
+        let leaf_reader = MockLeafReader::new(0);
+        let index_reader = MockIndexReader::new(vec![leaf_reader]);
+        let leaves = index_reader.leaves();
+
+        // MUST(SHOULD(a, b)) + SHOULD(c)
+        let a: Box<dyn Weight<TestCodec>> = Box::new(create_mock_weight(vec![1, 2, 4]));
+        let b: Box<dyn Weight<TestCodec>> = Box::new(create_mock_weight(vec![2, 3]));
+        let c: Box<dyn Weight<TestCodec>> = Box::new(create_mock_weight(vec![2, 4, 5]));
+        let nested: Box<dyn Weight<TestCodec>> =
+            Box::new(BooleanWeight::new(vec![], vec![a, b], vec![], true));
+        let weight = BooleanWeight::new(vec![nested], vec![c], vec![], true);
+        let mut scorer = weight.create_scorer(&leaves[0]).unwrap().unwrap();
+
+        // the nested group must match, and contributes the sum of its matching
+        // clauses, `c` only adds to the score
+        assert_eq!(
+            collect_scores(scorer.as_mut()),
+            vec![(1, 1.0), (2, 6.0), (3, 3.0), (4, 8.0)]
+        );
+    }
+
+    #[test]
+    fn test_nested_min_should_match() {
+        let leaf_reader = MockLeafReader::new(0);
+        let index_reader = MockIndexReader::new(vec![leaf_reader]);
+        let leaves = index_reader.leaves();
+
+        // MUST(SHOULD(a, b, c), min_should_match = 2) + SHOULD(d)
+        let a: Box<dyn Weight<TestCodec>> = Box::new(create_mock_weight(vec![1, 2, 4, 6]));
+        let b: Box<dyn Weight<TestCodec>> = Box::new(create_mock_weight(vec![2, 3, 4]));
+        let c: Box<dyn Weight<TestCodec>> = Box::new(create_mock_weight(vec![4, 5, 6]));
+        let d: Box<dyn Weight<TestCodec>> = Box::new(create_mock_weight(vec![1, 6]));
+        let mut nested = BooleanWeight::new(vec![], vec![a, b, c], vec![], true);
+        nested.set_minimum_should_match(2);
+        let nested: Box<dyn Weight<TestCodec>> = Box::new(nested);
+        let weight = BooleanWeight::new(vec![nested], vec![d], vec![], true);
+        let mut scorer = weight.create_scorer(&leaves[0]).unwrap().unwrap();
+
+        assert_eq!(
+            collect_scores(scorer.as_mut()),
+            vec![(2, 4.0), (4, 12.0), (6, 18.0)]
+        );
+    }
+
+    #[test]
+    fn test_min_should_match_with_must() {
+        let leaf_reader = MockLeafReader::new(0);
+        let index_reader = MockIndexReader::new(vec![leaf_reader]);
+        let leaves = index_reader.leaves();
+
+        let must: Box<dyn Weight<TestCodec>> = Box::new(create_mock_weight(vec![1, 2, 3, 4]));
+        let a: Box<dyn Weight<TestCodec>> = Box::new(create_mock_weight(vec![2, 4]));
+        let b: Box<dyn Weight<TestCodec>> = Box::new(create_mock_weight(vec![3, 4]));
+        let mut weight = BooleanWeight::new(vec![must], vec![a, b], vec![], true);
+        weight.set_minimum_should_match(1);
+        let mut scorer = weight.create_scorer(&leaves[0]).unwrap().unwrap();
+
+        assert_eq!(
+            collect_scores(scorer.as_mut()),
+            vec![(2, 4.0), (3, 6.0), (4, 12.0)]
+        );
+    }
+
+    #[test]
+    fn test_explain() {
+        let leaf_reader = MockLeafReader::new(0);
+        let index_reader = MockIndexReader::new(vec![leaf_reader]);
+        let leaves = index_reader.leaves();
+
+        let must: Box<dyn Weight<TestCodec>> = Box::new(create_mock_weight(vec![1, 2, 3]));
+        let should: Box<dyn Weight<TestCodec>> = Box::new(create_mock_weight(vec![2, 4]));
+        let weight = BooleanWeight::new(vec![must], vec![should], vec![], true);
+
+        // the explanation sums the matching clauses, like the scorer
+        let mut scorer = weight.create_scorer(&leaves[0]).unwrap().unwrap();
+        assert_eq!(scorer.advance(2).unwrap(), 2);
+        let expl = weight.explain(&leaves[0], 2).unwrap();
+        assert!(expl.is_match());
+        assert!((expl.value() - scorer.score().unwrap()).abs() < ::std::f32::EPSILON);
+        assert_eq!(expl.details().len(), 2);
+
+        let expl = weight.explain(&leaves[0], 1).unwrap();
+        assert!(expl.is_match());
+        assert_eq!(expl.details().len(), 1);
+
+        // the required clause doesn't match
+        let expl = weight.explain(&leaves[0], 4).unwrap();
+        assert!(!expl.is_match());
+        assert!(!expl.details()[0].is_match());
+    }
+}
@@ -40,6 +40,14 @@ impl<C: Codec> BoostQuery<C> {
             Box::new(BoostQuery { query, boost })
         }
     }
+
+    pub fn query(&self) -> &dyn Query<C> {
+        self.query.as_ref()
+    }
+
+    pub fn boost(&self) -> f32 {
+        self.boost
+    }
 }
 
 impl<C: Codec> Query<C> for BoostQuery<C> {
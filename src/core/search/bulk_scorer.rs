@@ -12,18 +12,80 @@
 // limitations under the License.
 
 use core::search::collector::Collector;
-use core::search::{Scorer, NO_MORE_DOCS};
+use core::search::{DocIterator, Scorer, NO_MORE_DOCS};
 use core::util::Bits;
 use core::util::DocId;
+use error::ErrorKind::UnsupportedOperation;
 use error::Result;
 
-pub struct BulkScorer<'a, S: Scorer + ?Sized + 'a> {
+/// Scores the docs of a segment at once, which a `Weight` may provide to
+/// collect its matches faster than by iterating over its `Scorer`, e.g. by
+/// walking all the doc ids of a dense segment.
+///
+/// The matches are passed to `collector` along with a scorer positioned on
+/// them, which must only be used to get the score of the current doc.
+pub trait BulkScorer {
+    /// Collects the docs in `[min, max)` accepted by `accept_docs`, returns an
+    /// estimation of the next matching doc on or after `max`, with the same
+    /// contract as `DefaultBulkScorer::score`.
+    fn score(
+        &mut self,
+        collector: &mut dyn FnMut(DocId, &mut dyn Scorer) -> Result<()>,
+        accept_docs: Option<&dyn Bits>,
+        min: DocId,
+        max: DocId,
+    ) -> Result<DocId>;
+
+    /// The cost of scoring all the matching docs.
+    fn cost(&self) -> usize;
+}
+
+/// A scorer only holding the current doc and its score, passed to the
+/// collectors by the bulk scorers which don't iterate over a `Scorer`.
+pub struct ScoreAndDoc {
+    pub doc: DocId,
+    pub score: f32,
+}
+
+impl ScoreAndDoc {
+    pub fn new(score: f32) -> ScoreAndDoc {
+        ScoreAndDoc { doc: -1, score }
+    }
+}
+
+impl Scorer for ScoreAndDoc {
+    fn score(&mut self) -> Result<f32> {
+        Ok(self.score)
+    }
+}
+
+impl DocIterator for ScoreAndDoc {
+    fn doc_id(&self) -> DocId {
+        self.doc
+    }
+
+    fn next(&mut self) -> Result<DocId> {
+        bail!(UnsupportedOperation("ScoreAndDoc can't be iterated".into()))
+    }
+
+    fn advance(&mut self, _target: DocId) -> Result<DocId> {
+        bail!(UnsupportedOperation("ScoreAndDoc can't be iterated".into()))
+    }
+
+    fn cost(&self) -> usize {
+        1
+    }
+}
+
+/// Scores the docs of a segment by iterating over a `Scorer`, the bulk
+/// scorer used when the `Weight` doesn't provide its own.
+pub struct DefaultBulkScorer<'a, S: Scorer + ?Sized + 'a> {
     pub scorer: &'a mut S,
 }
 
-impl<'a, S: Scorer + ?Sized + 'a> BulkScorer<'a, S> {
-    pub fn new(scorer: &'a mut S) -> BulkScorer<'a, S> {
-        BulkScorer { scorer }
+impl<'a, S: Scorer + ?Sized + 'a> DefaultBulkScorer<'a, S> {
+    pub fn new(scorer: &'a mut S) -> DefaultBulkScorer<'a, S> {
+        DefaultBulkScorer { scorer }
     }
 
     /// Collects matching documents in a range and return an estimation of the
@@ -145,7 +207,7 @@ mod tests {
         let leaf_reader_context = index_reader.leaves();
         let mut top_collector = TopDocsCollector::new(3);
         {
-            let mut bulk_scorer = BulkScorer::new(&mut scorer_box);
+            let mut bulk_scorer = DefaultBulkScorer::new(&mut scorer_box);
             top_collector
                 .set_next_reader(&leaf_reader_context[0])
                 .unwrap();
@@ -0,0 +1,264 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::{BTreeMap, BinaryHeap, HashMap};
+
+use core::codec::Codec;
+use core::index::{DocValuesType, LeafReaderContext, SortedDocValuesRef};
+use core::search::collector::{Collector, NoParallelLeafCollector, SearchCollector};
+use core::search::top_docs::{ScoreDoc, ScoreDocHit, TopDocs, TopScoreDocs};
+use core::search::Scorer;
+use core::util::DocId;
+use error::{ErrorKind::IllegalArgument, ErrorKind::IllegalState, Result};
+
+/// A collector keeping the top hits by score, but at most
+/// `max_hits_per_key` hits sharing the same key, e.g. the same domain.
+///
+/// The key of a doc is read from a `SORTED` doc values field. Once the
+/// hits of a key are full, a new hit of this key can only displace the
+/// least competitive one of them, even if other keys have less competitive
+/// hits. The docs without key are not limited.
+pub struct DiversifiedTopDocsCollector {
+    field: String,
+    num_hits: usize,
+    max_hits_per_key: usize,
+    // the collected hits and the group of their key, the last hit is the
+    // least competitive one
+    hits: BTreeMap<ScoreDoc, Option<usize>>,
+    // the collected hits of each key, the top of each heap is the least
+    // competitive hit of its key
+    groups: Vec<BinaryHeap<ScoreDoc>>,
+    group_by_key: HashMap<Vec<u8>, usize>,
+    // the keys of the current segment and the groups of their ords
+    keys: Option<SortedDocValuesRef>,
+    group_by_ord: HashMap<i32, usize>,
+    doc_base: DocId,
+    total_hits: usize,
+}
+
+impl DiversifiedTopDocsCollector {
+    pub fn new(
+        field: String,
+        num_hits: usize,
+        max_hits_per_key: usize,
+    ) -> Result<DiversifiedTopDocsCollector> {
+        if max_hits_per_key == 0 {
+            bail!(IllegalArgument("max_hits_per_key must be > 0".into()));
+        }
+        Ok(DiversifiedTopDocsCollector {
+            field,
+            num_hits,
+            max_hits_per_key,
+            hits: BTreeMap::new(),
+            groups: vec![],
+            group_by_key: HashMap::new(),
+            keys: None,
+            group_by_ord: HashMap::new(),
+            doc_base: 0,
+            total_hits: 0,
+        })
+    }
+
+    /// Returns the top docs that were collected by this collector.
+    pub fn top_docs(&mut self) -> TopDocs {
+        let score_docs = self
+            .hits
+            .keys()
+            .map(|hit| ScoreDocHit::Score(hit.clone()))
+            .collect();
+        TopDocs::Score(TopScoreDocs::new(self.total_hits, score_docs))
+    }
+
+    fn group(&mut self, doc: DocId) -> Result<Option<usize>> {
+        let keys = match self.keys {
+            Some(ref keys) => keys,
+            None => return Ok(None),
+        };
+        let ord = keys.get_ord(doc)?;
+        if ord < 0 {
+            return Ok(None);
+        }
+        if let Some(group) = self.group_by_ord.get(&ord) {
+            return Ok(Some(*group));
+        }
+        // ords are per segment, the groups are shared by key
+        let key = keys.lookup_ord(ord)?;
+        let groups = &mut self.groups;
+        let group = *self.group_by_key.entry(key).or_insert_with(|| {
+            groups.push(BinaryHeap::new());
+            groups.len() - 1
+        });
+        self.group_by_ord.insert(ord, group);
+        Ok(Some(group))
+    }
+
+    fn add_hit(&mut self, hit: ScoreDoc, group: Option<usize>) {
+        if let Some(group) = group {
+            if self.groups[group].len() == self.max_hits_per_key {
+                // the group is full, only its weakest hit can be displaced
+                let mut weakest = self.groups[group].peek_mut().unwrap();
+                if hit < *weakest {
+                    self.hits.remove(&*weakest);
+                    *weakest = hit.clone();
+                    self.hits.insert(hit, Some(group));
+                }
+                return;
+            }
+        }
+
+        if self.hits.len() == self.num_hits {
+            let weakest = self.hits.keys().next_back().unwrap().clone();
+            if hit >= weakest {
+                return;
+            }
+            // the weakest hit overall is the weakest one of its group too
+            if let Some(weakest_group) = self.hits.remove(&weakest).unwrap() {
+                self.groups[weakest_group].pop();
+            }
+        }
+        if let Some(group) = group {
+            self.groups[group].push(hit.clone());
+        }
+        self.hits.insert(hit, group);
+    }
+}
+
+impl SearchCollector for DiversifiedTopDocsCollector {
+    type LC = NoParallelLeafCollector;
+
+    fn set_next_reader<C: Codec>(&mut self, reader: &LeafReaderContext<'_, C>) -> Result<()> {
+        self.doc_base = reader.doc_base;
+        self.group_by_ord.clear();
+        self.keys = match reader.reader.field_info(&self.field) {
+            Some(field_info) if field_info.doc_values_type == DocValuesType::Sorted => {
+                Some(reader.reader.get_sorted_doc_values(&self.field)?)
+            }
+            _ => None,
+        };
+        Ok(())
+    }
+
+    fn support_parallel(&self) -> bool {
+        false
+    }
+
+    fn leaf_collector<C: Codec>(
+        &mut self,
+        _reader: &LeafReaderContext<'_, C>,
+    ) -> Result<NoParallelLeafCollector> {
+        bail!(IllegalState(
+            "DiversifiedTopDocsCollector doesn't support parallel collection".into()
+        ))
+    }
+
+    fn finish_parallel(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl Collector for DiversifiedTopDocsCollector {
+    fn needs_scores(&self) -> bool {
+        true
+    }
+
+    fn collect<S: Scorer + ?Sized>(&mut self, doc: DocId, scorer: &mut S) -> Result<()> {
+        self.total_hits += 1;
+        if self.num_hits == 0 {
+            return Ok(());
+        }
+        let score = scorer.score()?;
+        let group = self.group(doc)?;
+        self.add_hit(ScoreDoc::new(doc + self.doc_base, score), group);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::doc::{Field, NumericDocValuesField, SORTED_DOC_VALUES_FIELD_TYPE};
+    use core::index::{Fieldable, IndexReader, IndexWriter, IndexWriterConfig};
+    use core::search::tests::*;
+    use core::search::{DocIterator, NO_MORE_DOCS};
+    use core::store::RAMDirectory;
+    use error::{Error, ErrorKind};
+
+    use std::sync::Arc;
+
+    fn collect(domains: &[&str], num_hits: usize, max_hits_per_key: usize) -> Vec<DocId> {
+        let dir = Arc::new(RAMDirectory::default());
+        let writer = IndexWriter::new(dir, Arc::new(IndexWriterConfig::default())).unwrap();
+        for domain in domains {
+            let field: Box<dyn Fieldable> = if domain.is_empty() {
+                Box::new(NumericDocValuesField::new("other", 1))
+            } else {
+                Box::new(Field::new_bytes(
+                    "domain".into(),
+                    domain.as_bytes().to_vec(),
+                    SORTED_DOC_VALUES_FIELD_TYPE,
+                ))
+            };
+            writer.add_document(vec![field]).unwrap();
+        }
+        let reader = writer.get_reader(true, false).unwrap();
+        let leaves = reader.leaves();
+
+        let mut collector =
+            DiversifiedTopDocsCollector::new("domain".into(), num_hits, max_hits_per_key).unwrap();
+        // the mock scorer scores each doc by its id
+        let mut scorer = create_mock_scorer((0..domains.len() as DocId).collect());
+        collector.set_next_reader(&leaves[0]).unwrap();
+        loop {
+            let doc = scorer.next().unwrap();
+            if doc == NO_MORE_DOCS {
+                break;
+            }
+            collector.collect(doc, &mut scorer).unwrap();
+        }
+        writer.close().unwrap();
+
+        let top_docs = collector.top_docs();
+        assert_eq!(top_docs.total_hits(), domains.len());
+        top_docs.score_docs().iter().map(|d| d.doc_id()).collect()
+    }
+
+    #[test]
+    fn test_max_hits_per_key() {
+        let domains = ["a", "b", "a", "a", "c", "a", "b", "a"];
+        assert_eq!(collect(&domains, 4, 2), vec![7, 6, 5, 4]);
+        assert_eq!(collect(&domains, 5, 1), vec![7, 6, 4]);
+        assert_eq!(collect(&domains, 3, 8), vec![7, 6, 5]);
+    }
+
+    #[test]
+    fn test_full_group_displaces_own_weakest() {
+        // once "a" holds 2 hits, the later "a" docs only replace the weakest
+        // "a" hit, never the "b" hit even though it scores less
+        let domains = ["b", "a", "a", "a", "a"];
+        assert_eq!(collect(&domains, 3, 2), vec![4, 3, 0]);
+    }
+
+    #[test]
+    fn test_docs_without_key_are_not_limited() {
+        let domains = ["a", "", "a", "", "a"];
+        assert_eq!(collect(&domains, 4, 1), vec![4, 3, 1]);
+    }
+
+    #[test]
+    fn test_invalid_max_hits_per_key() {
+        match DiversifiedTopDocsCollector::new("domain".into(), 10, 0) {
+            Err(Error(ErrorKind::IllegalArgument(_), _)) => {}
+            _ => panic!("max_hits_per_key 0 should be rejected"),
+        }
+    }
+}
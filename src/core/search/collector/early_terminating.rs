@@ -15,20 +15,46 @@ use core::codec::Codec;
 use core::index::LeafReaderContext;
 use core::search::collector;
 use core::search::collector::{Collector, ParallelLeafCollector, SearchCollector};
+use core::search::sort::Sort;
 use core::search::Scorer;
 use core::util::DocId;
 use error::{ErrorKind, Result};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
+/// Whether the docs of a segment sorted by `index_sort` are also sorted by
+/// `sort`, which holds when the fields of `sort` are a prefix of the ones of
+/// `index_sort`.
+pub fn can_early_terminate(sort: &Sort, index_sort: Option<&Sort>) -> bool {
+    match index_sort {
+        Some(index_sort) => {
+            let (fields, index_fields) = (sort.get_sort(), index_sort.get_sort());
+            fields.len() <= index_fields.len() && fields == &index_fields[..fields.len()]
+        }
+        None => false,
+    }
+}
+
+/// Stops collecting a segment once `num_docs_to_collect_per_reader` docs were
+/// collected, if the segment is sorted by `sort`: the following docs of the
+/// segment can't compete anymore. The segments sorted differently are fully
+/// collected.
+///
+/// It is meant to be chained before a collector sorting the hits by `sort`.
 pub struct EarlyTerminatingSortingCollector {
     pub early_terminated: Arc<AtomicBool>,
+    sort: Sort,
     num_docs_to_collect_per_reader: usize,
     num_docs_collected_per_reader: usize,
+    // whether the current segment is sorted by `sort`
+    segment_sorted: bool,
 }
 
 impl EarlyTerminatingSortingCollector {
-    pub fn new(num_docs_to_collect_per_reader: usize) -> EarlyTerminatingSortingCollector {
+    pub fn new(
+        sort: Sort,
+        num_docs_to_collect_per_reader: usize,
+    ) -> EarlyTerminatingSortingCollector {
         assert!(
             num_docs_to_collect_per_reader > 0,
             format!(
@@ -39,16 +65,19 @@ impl EarlyTerminatingSortingCollector {
 
         EarlyTerminatingSortingCollector {
             early_terminated: Arc::new(AtomicBool::new(false)),
+            sort,
             num_docs_to_collect_per_reader,
             num_docs_collected_per_reader: 0,
+            segment_sorted: false,
         }
     }
 }
 
 impl SearchCollector for EarlyTerminatingSortingCollector {
     type LC = EarlyTerminatingLeafCollector;
-    fn set_next_reader<C: Codec>(&mut self, _reader: &LeafReaderContext<'_, C>) -> Result<()> {
+    fn set_next_reader<C: Codec>(&mut self, reader: &LeafReaderContext<'_, C>) -> Result<()> {
         self.num_docs_collected_per_reader = 0;
+        self.segment_sorted = can_early_terminate(&self.sort, reader.reader.index_sort());
         Ok(())
     }
 
@@ -56,11 +85,12 @@ impl SearchCollector for EarlyTerminatingSortingCollector {
         true
     }
 
-    fn leaf_collector<C: Codec>(&mut self, _reader: &LeafReaderContext<'_, C>) -> Result<Self::LC> {
+    fn leaf_collector<C: Codec>(&mut self, reader: &LeafReaderContext<'_, C>) -> Result<Self::LC> {
         assert!(self.support_parallel());
         Ok(EarlyTerminatingLeafCollector::new(
             self.num_docs_to_collect_per_reader,
             Arc::clone(&self.early_terminated),
+            can_early_terminate(&self.sort, reader.reader.index_sort()),
         ))
     }
 
@@ -75,6 +105,9 @@ impl Collector for EarlyTerminatingSortingCollector {
     }
 
     fn collect<S: Scorer + ?Sized>(&mut self, _doc: DocId, _scorer: &mut S) -> Result<()> {
+        if !self.segment_sorted {
+            return Ok(());
+        }
         self.num_docs_collected_per_reader += 1;
 
         if self.num_docs_collected_per_reader > self.num_docs_to_collect_per_reader {
@@ -91,17 +124,20 @@ pub struct EarlyTerminatingLeafCollector {
     pub early_terminated: Arc<AtomicBool>,
     num_docs_to_collect: usize,
     num_docs_collected: usize,
+    segment_sorted: bool,
 }
 
 impl EarlyTerminatingLeafCollector {
     pub fn new(
         num_docs_to_collect: usize,
         early_terminated: Arc<AtomicBool>,
+        segment_sorted: bool,
     ) -> EarlyTerminatingLeafCollector {
         EarlyTerminatingLeafCollector {
             early_terminated,
             num_docs_to_collect,
             num_docs_collected: 0,
+            segment_sorted,
         }
     }
 }
@@ -118,6 +154,9 @@ impl Collector for EarlyTerminatingLeafCollector {
     }
 
     fn collect<S: Scorer + ?Sized>(&mut self, _doc: i32, _scorer: &mut S) -> Result<()> {
+        if !self.segment_sorted {
+            return Ok(());
+        }
         self.num_docs_collected += 1;
 
         if self.num_docs_collected > self.num_docs_to_collect {
@@ -0,0 +1,96 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::codec::Codec;
+use core::index::LeafReaderContext;
+use core::search::collector::{Collector, NoParallelLeafCollector, SearchCollector};
+use core::search::Scorer;
+use core::util::DocId;
+use error::{ErrorKind::IllegalState, Result};
+
+/// The docs of a segment matched by the query.
+pub struct MatchingDocs {
+    /// The ord of the segment in the searched reader.
+    pub ord: usize,
+    pub doc_base: DocId,
+    /// The matching docs, relative to the segment, in increasing order.
+    pub docs: Vec<DocId>,
+}
+
+/// A collector recording the matching docs of each segment, to compute
+/// facets once the search is done, e.g. with `SortedSetDocValuesFacetCounts`.
+///
+/// It is usually chained with the collector of the top docs so that both
+/// are computed in a single search.
+#[derive(Default)]
+pub struct FacetsCollector {
+    matching_docs: Vec<MatchingDocs>,
+}
+
+impl FacetsCollector {
+    pub fn new() -> FacetsCollector {
+        FacetsCollector::default()
+    }
+
+    pub fn matching_docs(&self) -> &[MatchingDocs] {
+        &self.matching_docs
+    }
+}
+
+impl SearchCollector for FacetsCollector {
+    type LC = NoParallelLeafCollector;
+
+    fn set_next_reader<C: Codec>(&mut self, reader: &LeafReaderContext<'_, C>) -> Result<()> {
+        self.matching_docs.push(MatchingDocs {
+            ord: reader.ord,
+            doc_base: reader.doc_base,
+            docs: vec![],
+        });
+        Ok(())
+    }
+
+    fn support_parallel(&self) -> bool {
+        false
+    }
+
+    fn leaf_collector<C: Codec>(
+        &mut self,
+        _reader: &LeafReaderContext<'_, C>,
+    ) -> Result<NoParallelLeafCollector> {
+        bail!(IllegalState(
+            "FacetsCollector doesn't support parallel collection".into()
+        ))
+    }
+
+    fn finish_parallel(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl Collector for FacetsCollector {
+    fn needs_scores(&self) -> bool {
+        false
+    }
+
+    fn collect<S: Scorer + ?Sized>(&mut self, doc: DocId, _scorer: &mut S) -> Result<()> {
+        match self.matching_docs.last_mut() {
+            Some(matching_docs) => {
+                matching_docs.docs.push(doc);
+                Ok(())
+            }
+            None => bail!(IllegalState(
+                "FacetsCollector::collect called before set_next_reader".into()
+            )),
+        }
+    }
+}
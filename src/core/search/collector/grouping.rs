@@ -0,0 +1,585 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::{BTreeSet, BinaryHeap, HashMap};
+use std::f32;
+
+use core::codec::Codec;
+use core::index::{DocValuesType, LeafReaderContext, SortedDocValuesRef};
+use core::search::collector::{Collector, NoParallelLeafCollector, SearchCollector};
+use core::search::search_group::{GroupDocs, SearchGroup, TopGroups};
+use core::search::top_docs::{ScoreDoc, ScoreDocHit, TopDocs, TopScoreDocs};
+use core::search::Scorer;
+use core::util::DocId;
+use error::{ErrorKind::IllegalState, Result};
+
+/// The group values of the docs of a segment, read from a `SORTED` doc
+/// values field.
+struct GroupValues {
+    field: String,
+    values: Option<SortedDocValuesRef>,
+}
+
+impl GroupValues {
+    fn new(field: String) -> GroupValues {
+        GroupValues {
+            field,
+            values: None,
+        }
+    }
+
+    fn set_next_reader<C: Codec>(&mut self, reader: &LeafReaderContext<'_, C>) -> Result<()> {
+        self.values = match reader.reader.field_info(&self.field) {
+            Some(field_info) if field_info.doc_values_type == DocValuesType::Sorted => {
+                Some(reader.reader.get_sorted_doc_values(&self.field)?)
+            }
+            _ => None,
+        };
+        Ok(())
+    }
+
+    /// Returns the ord of the group value of `doc` in the current segment,
+    /// -1 if it has none.
+    fn ord(&self, doc: DocId) -> Result<i32> {
+        match self.values {
+            Some(ref values) => values.get_ord(doc),
+            None => Ok(-1),
+        }
+    }
+
+    fn lookup_ord(&self, ord: i32) -> Result<Option<Vec<u8>>> {
+        match self.values {
+            Some(ref values) if ord >= 0 => values.lookup_ord(ord).map(Some),
+            _ => Ok(None),
+        }
+    }
+}
+
+/// The first pass of a grouping search, which finds the top groups of a
+/// `SORTED` doc values field, the groups being ordered by their best hit.
+///
+/// Only the top `top_n_groups` groups are kept while collecting: a group
+/// which falls out of them can't make it back with its previous hits, only
+/// with a better one.
+pub struct FirstPassGroupingCollector {
+    group_values: GroupValues,
+    top_n_groups: usize,
+    // the top groups, the last one is the least competitive
+    ordered_groups: BTreeSet<SearchGroup>,
+    groups: HashMap<Option<Vec<u8>>, SearchGroup>,
+    // the group values of the ords of the current segment
+    values_by_ord: HashMap<i32, Option<Vec<u8>>>,
+    doc_base: DocId,
+}
+
+impl FirstPassGroupingCollector {
+    pub fn new(group_field: String, top_n_groups: usize) -> FirstPassGroupingCollector {
+        FirstPassGroupingCollector {
+            group_values: GroupValues::new(group_field),
+            top_n_groups,
+            ordered_groups: BTreeSet::new(),
+            groups: HashMap::new(),
+            values_by_ord: HashMap::new(),
+            doc_base: 0,
+        }
+    }
+
+    /// Returns the top groups, skipping the first `group_offset` ones, to
+    /// pass to the `SecondPassGroupingCollector`.
+    pub fn top_groups(&self, group_offset: usize) -> Vec<SearchGroup> {
+        self.ordered_groups
+            .iter()
+            .skip(group_offset)
+            .cloned()
+            .collect()
+    }
+
+    fn group_value(&mut self, doc: DocId) -> Result<Option<Vec<u8>>> {
+        let ord = self.group_values.ord(doc)?;
+        if let Some(value) = self.values_by_ord.get(&ord) {
+            return Ok(value.clone());
+        }
+        let value = self.group_values.lookup_ord(ord)?;
+        self.values_by_ord.insert(ord, value.clone());
+        Ok(value)
+    }
+
+    fn add_hit(&mut self, hit: SearchGroup) {
+        if let Some(group) = self.groups.get_mut(&hit.group_value) {
+            if hit < *group {
+                self.ordered_groups.remove(&*group);
+                self.ordered_groups.insert(hit.clone());
+                *group = hit;
+            }
+            return;
+        }
+
+        if self.ordered_groups.len() == self.top_n_groups {
+            let weakest = self.ordered_groups.iter().next_back().unwrap().clone();
+            if hit >= weakest {
+                return;
+            }
+            self.ordered_groups.remove(&weakest);
+            self.groups.remove(&weakest.group_value);
+        }
+        self.ordered_groups.insert(hit.clone());
+        self.groups.insert(hit.group_value.clone(), hit);
+    }
+}
+
+impl SearchCollector for FirstPassGroupingCollector {
+    type LC = NoParallelLeafCollector;
+
+    fn set_next_reader<C: Codec>(&mut self, reader: &LeafReaderContext<'_, C>) -> Result<()> {
+        self.doc_base = reader.doc_base;
+        self.values_by_ord.clear();
+        self.group_values.set_next_reader(reader)
+    }
+
+    fn support_parallel(&self) -> bool {
+        false
+    }
+
+    fn leaf_collector<C: Codec>(
+        &mut self,
+        _reader: &LeafReaderContext<'_, C>,
+    ) -> Result<NoParallelLeafCollector> {
+        bail!(IllegalState(
+            "FirstPassGroupingCollector doesn't support parallel collection".into()
+        ))
+    }
+
+    fn finish_parallel(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl Collector for FirstPassGroupingCollector {
+    fn needs_scores(&self) -> bool {
+        true
+    }
+
+    fn collect<S: Scorer + ?Sized>(&mut self, doc: DocId, scorer: &mut S) -> Result<()> {
+        if self.top_n_groups == 0 {
+            return Ok(());
+        }
+        let score = scorer.score()?;
+        let group_value = self.group_value(doc)?;
+        self.add_hit(SearchGroup::new(group_value, doc + self.doc_base, score));
+        Ok(())
+    }
+}
+
+/// A collector keeping the best hit of each value of a `SORTED` doc values
+/// field, e.g. the best page of each site, and the top `num_hits` of these
+/// hits. Unlike a grouping search, it takes a single pass, but only keeps one
+/// hit per group.
+///
+/// The docs without value form a group of their own if `missing_as_group`,
+/// otherwise they are skipped, though still counted in the total hits.
+pub struct CollapsingTopDocsCollector {
+    groups: FirstPassGroupingCollector,
+    missing_as_group: bool,
+    total_hits: usize,
+}
+
+impl CollapsingTopDocsCollector {
+    pub fn new(
+        collapse_field: String,
+        num_hits: usize,
+        missing_as_group: bool,
+    ) -> CollapsingTopDocsCollector {
+        CollapsingTopDocsCollector {
+            groups: FirstPassGroupingCollector::new(collapse_field, num_hits),
+            missing_as_group,
+            total_hits: 0,
+        }
+    }
+
+    /// Returns the top groups, with the value of each group and its best
+    /// hit, by descending score of this hit.
+    pub fn top_groups(&self) -> Vec<SearchGroup> {
+        self.groups.top_groups(0)
+    }
+
+    /// Returns the best hit of each of the top groups.
+    pub fn top_docs(&self) -> TopDocs {
+        let score_docs = self
+            .groups
+            .ordered_groups
+            .iter()
+            .map(|group| ScoreDocHit::Score(ScoreDoc::new(group.top_doc, group.score)))
+            .collect();
+        TopDocs::Score(TopScoreDocs::new(self.total_hits, score_docs))
+    }
+}
+
+impl SearchCollector for CollapsingTopDocsCollector {
+    type LC = NoParallelLeafCollector;
+
+    fn set_next_reader<C: Codec>(&mut self, reader: &LeafReaderContext<'_, C>) -> Result<()> {
+        self.groups.set_next_reader(reader)
+    }
+
+    fn support_parallel(&self) -> bool {
+        false
+    }
+
+    fn leaf_collector<C: Codec>(
+        &mut self,
+        _reader: &LeafReaderContext<'_, C>,
+    ) -> Result<NoParallelLeafCollector> {
+        bail!(IllegalState(
+            "CollapsingTopDocsCollector doesn't support parallel collection".into()
+        ))
+    }
+
+    fn finish_parallel(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl Collector for CollapsingTopDocsCollector {
+    fn needs_scores(&self) -> bool {
+        true
+    }
+
+    fn collect<S: Scorer + ?Sized>(&mut self, doc: DocId, scorer: &mut S) -> Result<()> {
+        self.total_hits += 1;
+        if !self.missing_as_group && self.groups.group_values.ord(doc)? < 0 {
+            return Ok(());
+        }
+        self.groups.collect(doc, scorer)
+    }
+}
+
+struct GroupCollector {
+    group: SearchGroup,
+    // the top hits of the group, the top of the heap is the least competitive
+    pq: BinaryHeap<ScoreDoc>,
+    total_hits: usize,
+}
+
+/// The second pass of a grouping search, which collects the top hits of
+/// each of the groups found by the `FirstPassGroupingCollector`.
+pub struct SecondPassGroupingCollector {
+    group_values: GroupValues,
+    docs_per_group: usize,
+    groups: Vec<GroupCollector>,
+    group_index: HashMap<Option<Vec<u8>>, usize>,
+    // the groups of the ords of the current segment, `None` for the ords
+    // which are not in the top groups
+    index_by_ord: HashMap<i32, Option<usize>>,
+    doc_base: DocId,
+    total_hit_count: usize,
+    total_grouped_hit_count: usize,
+}
+
+impl SecondPassGroupingCollector {
+    pub fn new(
+        group_field: String,
+        groups: Vec<SearchGroup>,
+        docs_per_group: usize,
+    ) -> SecondPassGroupingCollector {
+        let mut group_index = HashMap::with_capacity(groups.len());
+        let groups: Vec<GroupCollector> = groups
+            .into_iter()
+            .enumerate()
+            .map(|(i, group)| {
+                group_index.insert(group.group_value.clone(), i);
+                GroupCollector {
+                    group,
+                    pq: BinaryHeap::with_capacity(docs_per_group),
+                    total_hits: 0,
+                }
+            })
+            .collect();
+        SecondPassGroupingCollector {
+            group_values: GroupValues::new(group_field),
+            docs_per_group,
+            groups,
+            group_index,
+            index_by_ord: HashMap::new(),
+            doc_base: 0,
+            total_hit_count: 0,
+            total_grouped_hit_count: 0,
+        }
+    }
+
+    /// Returns the top groups with their top hits.
+    pub fn top_groups(&mut self) -> TopGroups {
+        let groups = self
+            .groups
+            .iter()
+            .map(|g| {
+                // ascending order is by descending score
+                let score_docs = g.pq.clone().into_sorted_vec();
+                GroupDocs {
+                    group_value: g.group.group_value.clone(),
+                    max_score: score_docs.first().map_or(f32::NAN, |d| d.score),
+                    total_hits: g.total_hits,
+                    score_docs,
+                }
+            })
+            .collect();
+        TopGroups {
+            total_hit_count: self.total_hit_count,
+            total_grouped_hit_count: self.total_grouped_hit_count,
+            groups,
+        }
+    }
+
+    fn group_index(&mut self, doc: DocId) -> Result<Option<usize>> {
+        let ord = self.group_values.ord(doc)?;
+        if let Some(index) = self.index_by_ord.get(&ord) {
+            return Ok(*index);
+        }
+        let value = self.group_values.lookup_ord(ord)?;
+        let index = self.group_index.get(&value).cloned();
+        self.index_by_ord.insert(ord, index);
+        Ok(index)
+    }
+}
+
+impl SearchCollector for SecondPassGroupingCollector {
+    type LC = NoParallelLeafCollector;
+
+    fn set_next_reader<C: Codec>(&mut self, reader: &LeafReaderContext<'_, C>) -> Result<()> {
+        self.doc_base = reader.doc_base;
+        self.index_by_ord.clear();
+        self.group_values.set_next_reader(reader)
+    }
+
+    fn support_parallel(&self) -> bool {
+        false
+    }
+
+    fn leaf_collector<C: Codec>(
+        &mut self,
+        _reader: &LeafReaderContext<'_, C>,
+    ) -> Result<NoParallelLeafCollector> {
+        bail!(IllegalState(
+            "SecondPassGroupingCollector doesn't support parallel collection".into()
+        ))
+    }
+
+    fn finish_parallel(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl Collector for SecondPassGroupingCollector {
+    fn needs_scores(&self) -> bool {
+        true
+    }
+
+    fn collect<S: Scorer + ?Sized>(&mut self, doc: DocId, scorer: &mut S) -> Result<()> {
+        self.total_hit_count += 1;
+        let index = match self.group_index(doc)? {
+            Some(index) => index,
+            None => return Ok(()),
+        };
+        self.total_grouped_hit_count += 1;
+
+        let docs_per_group = self.docs_per_group;
+        let group = &mut self.groups[index];
+        group.total_hits += 1;
+        if docs_per_group == 0 {
+            return Ok(());
+        }
+        let hit = ScoreDoc::new(doc + self.doc_base, scorer.score()?);
+        if group.pq.len() < docs_per_group {
+            group.pq.push(hit);
+        } else if let Some(mut weakest) = group.pq.peek_mut() {
+            if hit < *weakest {
+                *weakest = hit;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::doc::{Field, NumericDocValuesField, SORTED_DOC_VALUES_FIELD_TYPE};
+    use core::index::{Fieldable, IndexReader, IndexWriter, IndexWriterConfig};
+    use core::search::{DocIterator, NO_MORE_DOCS};
+    use core::store::RAMDirectory;
+
+    use std::sync::Arc;
+
+    /// Visits all the docs of a segment with the given scores.
+    struct ScoresScorer {
+        doc: DocId,
+        scores: Vec<f32>,
+    }
+
+    impl Scorer for ScoresScorer {
+        fn score(&mut self) -> Result<f32> {
+            Ok(self.scores[self.doc as usize])
+        }
+    }
+
+    impl DocIterator for ScoresScorer {
+        fn doc_id(&self) -> DocId {
+            self.doc
+        }
+
+        fn next(&mut self) -> Result<DocId> {
+            let target = self.doc + 1;
+            self.advance(target)
+        }
+
+        fn advance(&mut self, target: DocId) -> Result<DocId> {
+            self.doc = if target as usize >= self.scores.len() {
+                NO_MORE_DOCS
+            } else {
+                target
+            };
+            Ok(self.doc)
+        }
+
+        fn cost(&self) -> usize {
+            self.scores.len()
+        }
+    }
+
+    fn collect<T: SearchCollector>(hits: &[(&str, f32)], collector: &mut T) {
+        let dir = Arc::new(RAMDirectory::default());
+        let writer = IndexWriter::new(dir, Arc::new(IndexWriterConfig::default())).unwrap();
+        for &(site, _) in hits {
+            let field: Box<dyn Fieldable> = if site.is_empty() {
+                Box::new(NumericDocValuesField::new("other", 1))
+            } else {
+                Box::new(Field::new_bytes(
+                    "site".into(),
+                    site.as_bytes().to_vec(),
+                    SORTED_DOC_VALUES_FIELD_TYPE,
+                ))
+            };
+            writer.add_document(vec![field]).unwrap();
+        }
+        let reader = writer.get_reader(true, false).unwrap();
+        let leaves = reader.leaves();
+
+        let mut scorer = ScoresScorer {
+            doc: -1,
+            scores: hits.iter().map(|h| h.1).collect(),
+        };
+        collector.set_next_reader(&leaves[0]).unwrap();
+        loop {
+            let doc = scorer.next().unwrap();
+            if doc == NO_MORE_DOCS {
+                break;
+            }
+            collector.collect(doc, &mut scorer).unwrap();
+        }
+        writer.close().unwrap();
+    }
+
+    fn group_value(value: &str) -> Option<Vec<u8>> {
+        if value.is_empty() {
+            None
+        } else {
+            Some(value.as_bytes().to_vec())
+        }
+    }
+
+    #[test]
+    fn test_grouping_search() {
+        let hits = [
+            ("a", 1.0),
+            ("b", 5.0),
+            ("a", 3.0),
+            ("c", 2.0),
+            ("", 4.0),
+            ("b", 0.5),
+            ("a", 2.5),
+            ("c", 0.1),
+        ];
+        let mut first_pass = FirstPassGroupingCollector::new("site".into(), 3);
+        collect(&hits, &mut first_pass);
+        let groups = first_pass.top_groups(0);
+        let values: Vec<Option<Vec<u8>>> = groups.iter().map(|g| g.group_value.clone()).collect();
+        // ordered by their best hit, "c" only scores 2.0
+        assert_eq!(
+            values,
+            vec![group_value("b"), group_value(""), group_value("a")]
+        );
+        assert_eq!(groups[0].top_doc, 1);
+        assert_eq!(groups[2].top_doc, 2);
+        assert_eq!(first_pass.top_groups(2).len(), 1);
+
+        let mut second_pass = SecondPassGroupingCollector::new("site".into(), groups, 2);
+        collect(&hits, &mut second_pass);
+        let top_groups = second_pass.top_groups();
+        assert_eq!(top_groups.total_hit_count, 8);
+        assert_eq!(top_groups.total_grouped_hit_count, 6);
+        assert_eq!(top_groups.groups.len(), 3);
+
+        let docs = |g: &GroupDocs| g.score_docs.iter().map(|d| d.doc).collect::<Vec<_>>();
+        assert_eq!(top_groups.groups[0].total_hits, 2);
+        assert_eq!(docs(&top_groups.groups[0]), vec![1, 5]);
+        assert!((top_groups.groups[0].max_score - 5.0).abs() < f32::EPSILON);
+        assert_eq!(docs(&top_groups.groups[1]), vec![4]);
+        // "a" has 3 hits, only the 2 best are kept
+        assert_eq!(top_groups.groups[2].total_hits, 3);
+        assert_eq!(docs(&top_groups.groups[2]), vec![2, 6]);
+    }
+
+    #[test]
+    fn test_collapsing_top_docs() {
+        let hits = [
+            ("a", 1.0),
+            ("b", 5.0),
+            ("a", 3.0),
+            ("", 4.0),
+            ("c", 2.0),
+            ("", 0.5),
+            ("b", 2.5),
+        ];
+        let mut collector = CollapsingTopDocsCollector::new("site".into(), 3, true);
+        collect(&hits, &mut collector);
+        let groups = collector.top_groups();
+        let values: Vec<Option<Vec<u8>>> = groups.iter().map(|g| g.group_value.clone()).collect();
+        assert_eq!(
+            values,
+            vec![group_value("b"), group_value(""), group_value("a")]
+        );
+        let top_docs = collector.top_docs();
+        assert_eq!(top_docs.total_hits(), 7);
+        let docs: Vec<DocId> = top_docs.score_docs().iter().map(|d| d.doc_id()).collect();
+        assert_eq!(docs, vec![1, 3, 2]);
+
+        // the docs without value are skipped
+        let mut collector = CollapsingTopDocsCollector::new("site".into(), 3, false);
+        collect(&hits, &mut collector);
+        let top_docs = collector.top_docs();
+        assert_eq!(top_docs.total_hits(), 7);
+        let docs: Vec<DocId> = top_docs.score_docs().iter().map(|d| d.doc_id()).collect();
+        assert_eq!(docs, vec![1, 2, 4]);
+    }
+
+    #[test]
+    fn test_evicted_group_comes_back_with_better_hit() {
+        let hits = [("a", 1.0), ("b", 2.0), ("c", 3.0), ("a", 4.0)];
+        let mut first_pass = FirstPassGroupingCollector::new("site".into(), 2);
+        collect(&hits, &mut first_pass);
+        let groups = first_pass.top_groups(0);
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].group_value, group_value("a"));
+        assert_eq!(groups[0].top_doc, 3);
+        assert_eq!(groups[1].group_value, group_value("c"));
+    }
+}
@@ -0,0 +1,183 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::codec::Codec;
+use core::index::LeafReaderContext;
+use core::search::collector;
+use core::search::collector::{Collector, ParallelLeafCollector, SearchCollector};
+use core::search::{Scorer, NO_MORE_DOCS};
+use core::util::DocId;
+use error::{ErrorKind, Result};
+
+/// Only passes the docs scoring at least `min_score` to the wrapped collector.
+///
+/// The rest of a segment is skipped as soon as the scorer's `max_score`
+/// bound for its remaining docs drops below `min_score`, so the scorers
+/// giving a tight bound, like the ones of queries sorting their docs by
+/// descending score, stop early.
+pub struct MinScoreCollector<T> {
+    collector: T,
+    min_score: f32,
+}
+
+impl<T> MinScoreCollector<T> {
+    pub fn new(collector: T, min_score: f32) -> MinScoreCollector<T> {
+        MinScoreCollector {
+            collector,
+            min_score,
+        }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.collector
+    }
+}
+
+impl<T: SearchCollector> SearchCollector for MinScoreCollector<T> {
+    type LC = MinScoreCollector<T::LC>;
+
+    fn set_next_reader<C: Codec>(&mut self, reader: &LeafReaderContext<'_, C>) -> Result<()> {
+        self.collector.set_next_reader(reader)
+    }
+
+    fn support_parallel(&self) -> bool {
+        self.collector.support_parallel()
+    }
+
+    fn leaf_collector<C: Codec>(
+        &mut self,
+        reader: &LeafReaderContext<'_, C>,
+    ) -> Result<MinScoreCollector<T::LC>> {
+        Ok(MinScoreCollector::new(
+            self.collector.leaf_collector(reader)?,
+            self.min_score,
+        ))
+    }
+
+    fn finish_parallel(&mut self) -> Result<()> {
+        self.collector.finish_parallel()
+    }
+}
+
+impl<T: Collector> Collector for MinScoreCollector<T> {
+    fn needs_scores(&self) -> bool {
+        true
+    }
+
+    fn collect<S: Scorer + ?Sized>(&mut self, doc: DocId, scorer: &mut S) -> Result<()> {
+        if scorer.max_score(NO_MORE_DOCS)? < self.min_score {
+            bail!(ErrorKind::Collector(
+                collector::ErrorKind::LeafCollectionTerminated,
+            ))
+        }
+        if scorer.score()? >= self.min_score {
+            self.collector.collect(doc, scorer)?;
+        }
+        Ok(())
+    }
+}
+
+impl<T: ParallelLeafCollector> ParallelLeafCollector for MinScoreCollector<T> {
+    fn finish_leaf(&mut self) -> Result<()> {
+        self.collector.finish_leaf()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::index::tests::*;
+    use core::index::IndexReader;
+    use core::search::collector::TopDocsCollector;
+    use core::search::DocIterator;
+    use error::Error;
+
+    /// Iterates over docs with given scores, bounding the score of the
+    /// remaining docs by the highest one.
+    struct ScoresScorer {
+        docs: Vec<(DocId, f32)>,
+        idx: usize,
+    }
+
+    impl Scorer for ScoresScorer {
+        fn score(&mut self) -> Result<f32> {
+            Ok(self.docs[self.idx].1)
+        }
+
+        fn max_score(&mut self, _up_to: DocId) -> Result<f32> {
+            Ok(self.docs[self.idx..]
+                .iter()
+                .map(|d| d.1)
+                .fold(0.0, f32::max))
+        }
+    }
+
+    impl DocIterator for ScoresScorer {
+        fn doc_id(&self) -> DocId {
+            if self.idx < self.docs.len() {
+                self.docs[self.idx].0
+            } else {
+                NO_MORE_DOCS
+            }
+        }
+
+        fn next(&mut self) -> Result<DocId> {
+            self.idx += 1;
+            Ok(self.doc_id())
+        }
+
+        fn advance(&mut self, target: DocId) -> Result<DocId> {
+            self.slow_advance(target)
+        }
+
+        fn cost(&self) -> usize {
+            self.docs.len()
+        }
+    }
+
+    #[test]
+    fn test_min_score_collector() {
+        let leaf_reader = MockLeafReader::new(0);
+        let index_reader = MockIndexReader::new(vec![leaf_reader]);
+        let leaves = index_reader.leaves();
+
+        let docs = vec![(1, 3.0), (2, 2.0), (3, 4.0), (4, 1.0), (5, 2.0)];
+        let mut scorer = ScoresScorer { docs, idx: 0 };
+        let mut min_score_collector = MinScoreCollector::new(TopDocsCollector::new(10), 2.5);
+        min_score_collector.set_next_reader(&leaves[0]).unwrap();
+
+        let mut doc = scorer.doc_id();
+        let mut terminated_at = None;
+        while doc != NO_MORE_DOCS {
+            match min_score_collector.collect(doc, &mut scorer) {
+                Ok(()) => {}
+                Err(Error(
+                    ErrorKind::Collector(collector::ErrorKind::LeafCollectionTerminated),
+                    _,
+                )) => {
+                    terminated_at = Some(doc);
+                    break;
+                }
+                Err(e) => panic!("unexpected error: {:?}", e),
+            }
+            doc = scorer.next().unwrap();
+        }
+        // the docs after 3 can't score 2.5
+        assert_eq!(terminated_at, Some(4));
+
+        let top_docs = min_score_collector.into_inner().top_docs();
+        assert_eq!(top_docs.total_hits(), 2);
+        let hits: Vec<DocId> = top_docs.score_docs().iter().map(|d| d.doc_id()).collect();
+        assert_eq!(hits, vec![3, 1]);
+    }
+}
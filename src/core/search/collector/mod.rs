@@ -21,15 +21,32 @@ use core::util::DocId;
 pub mod top_docs;
 pub use self::top_docs::TopDocsCollector;
 
+pub mod top_field;
+pub use self::top_field::TopFieldCollector;
+
 mod early_terminating;
 pub use self::early_terminating::EarlyTerminatingSortingCollector;
 
 mod timeout;
 pub use self::timeout::TimeoutCollector;
 
+mod min_score;
+pub use self::min_score::MinScoreCollector;
+
 mod chain;
 pub use self::chain::ChainedCollector;
 
+mod diversified;
+pub use self::diversified::DiversifiedTopDocsCollector;
+
+mod facets;
+pub use self::facets::{FacetsCollector, MatchingDocs};
+
+mod grouping;
+pub use self::grouping::{
+    CollapsingTopDocsCollector, FirstPassGroupingCollector, SecondPassGroupingCollector,
+};
+
 error_chain! {
     types {
         Error, ErrorKind, ResultExt;
@@ -44,6 +61,9 @@ error_chain! {
         CollectionFailed {
             description("Collection failed")
         }
+        CollectionTimeout {
+            description("Collection timed out")
+        }
     }
 }
 
@@ -131,3 +151,23 @@ impl<'a, T: Collector + 'a> Collector for &'a mut T {
 pub trait ParallelLeafCollector: Collector + Send + 'static {
     fn finish_leaf(&mut self) -> Result<()>;
 }
+
+/// The leaf collector of the collectors which can't collect in parallel,
+/// it can't be instantiated.
+pub enum NoParallelLeafCollector {}
+
+impl Collector for NoParallelLeafCollector {
+    fn needs_scores(&self) -> bool {
+        match *self {}
+    }
+
+    fn collect<S: Scorer + ?Sized>(&mut self, _doc: DocId, _scorer: &mut S) -> Result<()> {
+        match *self {}
+    }
+}
+
+impl ParallelLeafCollector for NoParallelLeafCollector {
+    fn finish_leaf(&mut self) -> Result<()> {
+        match *self {}
+    }
+}
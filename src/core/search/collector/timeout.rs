@@ -17,14 +17,31 @@ use core::search::collector;
 use core::search::collector::{Collector, ParallelLeafCollector, SearchCollector};
 use core::search::Scorer;
 use core::util::DocId;
-use error::{ErrorKind, Result};
+use error::{ErrorKind, ErrorKind::IllegalArgument, Result};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, SystemTime};
 
+/// A collector stopping the search once `timeout_duration` has elapsed since
+/// `start_time`, by returning a `CollectionTimeout` error from `collect`. The
+/// searcher then ends the search successfully, and `timed_out` tells whether
+/// the results are partial.
+///
+/// To limit the time of another collector, put this one first in a
+/// `ChainedCollector`: the doc on which the timeout is detected is then not
+/// passed to the other collector, and the docs collected before stay in it.
+///
+/// The clock is only read every `granularity` collected docs (every doc by
+/// default), so the search may run past the deadline by up to
+/// `granularity - 1` docs. The clock is always read on the first collected doc:
+/// if the deadline is already in the past when the search starts, the search
+/// fails on the first matching doc and nothing is collected.
 pub struct TimeoutCollector {
     timeout_duration: Duration,
     start_time: SystemTime,
+    granularity: usize,
+    // number of docs left to collect before the next clock read
+    count: usize,
     pub timeout: Arc<AtomicBool>,
 }
 
@@ -33,9 +50,26 @@ impl TimeoutCollector {
         TimeoutCollector {
             timeout_duration,
             start_time,
+            granularity: 1,
+            count: 0,
             timeout: Arc::new(AtomicBool::new(false)),
         }
     }
+
+    /// Sets the number of collected docs between two reads of the clock, must
+    /// be greater than 0.
+    pub fn with_granularity(mut self, granularity: usize) -> Result<TimeoutCollector> {
+        if granularity == 0 {
+            bail!(IllegalArgument("granularity must be greater than 0".into()));
+        }
+        self.granularity = granularity;
+        Ok(self)
+    }
+
+    /// Whether the deadline was passed during the search.
+    pub fn timed_out(&self) -> bool {
+        self.timeout.load(Ordering::Acquire)
+    }
 }
 
 impl SearchCollector for TimeoutCollector {
@@ -53,11 +87,13 @@ impl SearchCollector for TimeoutCollector {
         &mut self,
         _reader: &LeafReaderContext<'_, C>,
     ) -> Result<TimeoutLeafCollector> {
-        Ok(TimeoutLeafCollector::new(
+        let mut collector = TimeoutLeafCollector::new(
             self.timeout_duration,
             self.start_time,
             Arc::clone(&self.timeout),
-        ))
+        );
+        collector.granularity = self.granularity;
+        Ok(collector)
     }
 
     fn finish_parallel(&mut self) -> Result<()> {
@@ -71,20 +107,21 @@ impl Collector for TimeoutCollector {
     }
 
     fn collect<S: Scorer + ?Sized>(&mut self, _doc: DocId, _scorer: &mut S) -> Result<()> {
-        let now = SystemTime::now();
-        if self.start_time < now && now.duration_since(self.start_time)? >= self.timeout_duration {
-            self.timeout.store(true, Ordering::Release);
-            bail!(ErrorKind::Collector(
-                collector::ErrorKind::CollectionTerminated,
-            ))
-        }
-        Ok(())
+        check_timeout(
+            self.timeout_duration,
+            self.start_time,
+            self.granularity,
+            &mut self.count,
+            &self.timeout,
+        )
     }
 }
 
 pub struct TimeoutLeafCollector {
     timeout_duration: Duration,
     start_time: SystemTime,
+    granularity: usize,
+    count: usize,
     timeout: Arc<AtomicBool>,
 }
 
@@ -97,6 +134,8 @@ impl TimeoutLeafCollector {
         TimeoutLeafCollector {
             timeout_duration,
             start_time,
+            granularity: 1,
+            count: 0,
             timeout,
         }
     }
@@ -108,19 +147,135 @@ impl Collector for TimeoutLeafCollector {
     }
 
     fn collect<S: Scorer + ?Sized>(&mut self, _doc: i32, _scorer: &mut S) -> Result<()> {
+        check_timeout(
+            self.timeout_duration,
+            self.start_time,
+            self.granularity,
+            &mut self.count,
+            &self.timeout,
+        )
+    }
+}
+
+impl ParallelLeafCollector for TimeoutLeafCollector {
+    fn finish_leaf(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Reads the clock once every `granularity` calls, `count` being the number
+/// of calls left before the next read. Once the deadline is passed, all the
+/// collectors sharing `timeout` fail without reading the clock.
+fn check_timeout(
+    timeout_duration: Duration,
+    start_time: SystemTime,
+    granularity: usize,
+    count: &mut usize,
+    timeout: &AtomicBool,
+) -> Result<()> {
+    if *count == 0 {
         let now = SystemTime::now();
-        if self.start_time < now && now.duration_since(self.start_time)? >= self.timeout_duration {
-            self.timeout.store(true, Ordering::Release);
+        if timeout.load(Ordering::Acquire)
+            || (start_time < now && now.duration_since(start_time)? >= timeout_duration)
+        {
+            timeout.store(true, Ordering::Release);
             bail!(ErrorKind::Collector(
-                collector::ErrorKind::CollectionTerminated,
+                collector::ErrorKind::CollectionTimeout,
             ))
         }
-        Ok(())
+        *count = granularity;
     }
+    *count -= 1;
+    Ok(())
 }
 
-impl ParallelLeafCollector for TimeoutLeafCollector {
-    fn finish_leaf(&mut self) -> Result<()> {
-        Ok(())
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::index::tests::*;
+    use core::index::IndexReader;
+    use core::search::collector::TopDocsCollector;
+    use core::search::tests::*;
+    use core::search::{DocIterator, NO_MORE_DOCS};
+    use error::Error;
+
+    // collects like `ChainedCollector::new(timeout, collector)`
+    fn collect_all<T: SearchCollector>(
+        timeout: &mut TimeoutCollector,
+        collector: &mut T,
+        docs: Vec<DocId>,
+    ) -> Result<()> {
+        let mut scorer = create_mock_scorer(docs);
+        let leaf_reader = MockLeafReader::new(0);
+        let index_reader = MockIndexReader::new(vec![leaf_reader]);
+        let leaf_reader_context = index_reader.leaves();
+        timeout.set_next_reader(&leaf_reader_context[0])?;
+        collector.set_next_reader(&leaf_reader_context[0])?;
+        loop {
+            let doc = scorer.next()?;
+            if doc == NO_MORE_DOCS {
+                return Ok(());
+            }
+            timeout.collect(doc, &mut scorer)?;
+            collector.collect(doc, &mut scorer)?;
+        }
+    }
+
+    fn is_timeout(res: Result<()>) -> bool {
+        match res {
+            Err(Error(ErrorKind::Collector(collector::ErrorKind::CollectionTimeout), _)) => true,
+            _ => false,
+        }
+    }
+
+    #[test]
+    fn test_not_timed_out() {
+        let mut timeout = TimeoutCollector::new(Duration::from_secs(3600), SystemTime::now());
+        let mut collector = TopDocsCollector::new(10);
+        collect_all(&mut timeout, &mut collector, vec![1, 2, 3]).unwrap();
+        assert!(!timeout.timed_out());
+        assert_eq!(collector.top_docs().total_hits(), 3);
+    }
+
+    #[test]
+    fn test_deadline_in_the_past() {
+        let start_time = SystemTime::now() - Duration::from_secs(3600);
+        let mut timeout = TimeoutCollector::new(Duration::from_secs(1), start_time)
+            .with_granularity(4)
+            .unwrap();
+        let mut collector = TopDocsCollector::new(10);
+        assert!(is_timeout(collect_all(
+            &mut timeout,
+            &mut collector,
+            vec![1, 2, 3]
+        )));
+        assert!(timeout.timed_out());
+        assert_eq!(collector.top_docs().total_hits(), 0);
+    }
+
+    #[test]
+    fn test_partial_results() {
+        let mut timeout = TimeoutCollector::new(Duration::from_secs(3600), SystemTime::now())
+            .with_granularity(2)
+            .unwrap();
+        let mut collector = TopDocsCollector::new(10);
+        collect_all(&mut timeout, &mut collector, vec![1, 2, 3]).unwrap();
+
+        // pass the deadline without waiting for it
+        timeout.start_time = SystemTime::now() - Duration::from_secs(7200);
+        // the clock is only read again on the 2nd doc
+        assert!(is_timeout(collect_all(
+            &mut timeout,
+            &mut collector,
+            vec![4, 5, 6]
+        )));
+        assert!(timeout.timed_out());
+        assert_eq!(collector.top_docs().total_hits(), 4);
+    }
+
+    #[test]
+    fn test_invalid_granularity() {
+        let timeout = TimeoutCollector::new(Duration::from_secs(1), SystemTime::now());
+        assert!(timeout.with_granularity(0).is_err());
     }
 }
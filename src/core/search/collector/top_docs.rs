@@ -41,22 +41,55 @@ pub struct TopDocsCollector {
 
     cur_doc_base: DocId,
 
+    /// Once more than this many hits were counted, the current scorer is told
+    /// to skip the docs that can't make it into the queue.
+    total_hits_threshold: usize,
+
+    /// The min competitive score given to the current scorer, if any.
+    min_competitive_score: Option<f32>,
+
+    /// Only the hits sorting strictly after this one are collected, if set.
+    after: Option<ScoreDoc>,
+
     // TODO used for parallel collect, maybe should be move the new struct for parallel search
     channel: Option<(Sender<ScoreDoc>, Receiver<ScoreDoc>)>,
 }
 
 impl TopDocsCollector {
     pub fn new(estimated_hits: usize) -> TopDocsCollector {
+        Self::with_total_hits_threshold(estimated_hits, usize::MAX)
+    }
+
+    /// Creates a collector which counts hits accurately only up to
+    /// `total_hits_threshold`. Past that, scorers may skip non-competitive
+    /// docs, and the total hits of the returned `TopDocs` is a lower bound.
+    pub fn with_total_hits_threshold(
+        estimated_hits: usize,
+        total_hits_threshold: usize,
+    ) -> TopDocsCollector {
         let pq = ScoreDocPriorityQueue::with_capacity(estimated_hits);
         TopDocsCollector {
             pq,
             estimated_hits,
             total_hits: 0,
             cur_doc_base: 0,
+            total_hits_threshold,
+            min_competitive_score: None,
+            after: None,
             channel: None,
         }
     }
 
+    /// Creates a collector for the page of hits following `after`, which is
+    /// usually the last hit of the previous page: only the hits which sort
+    /// strictly after it, by descending score then ascending doc id, are
+    /// collected. The total hits still counts all the matching docs.
+    pub fn with_after(estimated_hits: usize, after: ScoreDoc) -> TopDocsCollector {
+        let mut collector = Self::new(estimated_hits);
+        collector.after = Some(after);
+        collector
+    }
+
     /// Returns the top docs that were collected by this collector.
     pub fn top_docs(&mut self) -> TopDocs {
         let size = self.total_hits.min(self.pq.len());
@@ -75,17 +108,39 @@ impl TopDocsCollector {
 
         self.total_hits += 1;
 
+        let score_doc = ScoreDoc::new(doc_id, score);
+        if let Some(ref after) = self.after {
+            if score_doc <= *after {
+                return;
+            }
+        }
+
         let at_capacity = self.pq.len() == self.estimated_hits;
 
         if !at_capacity {
-            let score_doc = ScoreDoc::new(doc_id, score);
             self.pq.push(score_doc);
         } else if let Some(mut doc) = self.pq.peek_mut() {
-            if doc.score < score {
+            // ties are broken by doc id rather than by collection order, so
+            // the hits don't depend on the order leaves are searched in parallel
+            if score_doc < *doc {
                 doc.reset(doc_id, score);
             }
         }
     }
+
+    /// Once the queue is full, docs which don't score more than the queue's
+    /// minimum won't be collected, so the scorer may skip them.
+    fn update_min_competitive_score<S: Scorer + ?Sized>(&mut self, scorer: &mut S) {
+        if self.total_hits <= self.total_hits_threshold || self.pq.len() < self.estimated_hits {
+            return;
+        }
+        if let Some(min_score) = self.pq.peek().map(|d| d.score) {
+            if self.min_competitive_score.map_or(true, |s| s < min_score) {
+                scorer.set_min_competitive_score(min_score);
+                self.min_competitive_score = Some(min_score);
+            }
+        }
+    }
 }
 
 impl SearchCollector for TopDocsCollector {
@@ -93,6 +148,7 @@ impl SearchCollector for TopDocsCollector {
 
     fn set_next_reader<C: Codec>(&mut self, reader: &LeafReaderContext<'_, C>) -> Result<()> {
         self.cur_doc_base = reader.doc_base;
+        self.min_competitive_score = None;
 
         Ok(())
     }
@@ -141,6 +197,7 @@ impl Collector for TopDocsCollector {
 
         let id = doc + self.cur_doc_base;
         self.add_doc(id, score);
+        self.update_min_competitive_score(scorer);
 
         Ok(())
     }
@@ -220,4 +277,145 @@ mod tests {
         assert_eq!(score_docs[1].doc_id(), 3);
         assert_eq!(score_docs[2].doc_id(), 3);
     }
+
+    fn collect_docs(collector: &mut TopDocsCollector, docs: Vec<DocId>) {
+        let mut scorer = create_mock_scorer(docs);
+        let leaf_reader = MockLeafReader::new(0);
+        let index_reader = MockIndexReader::new(vec![leaf_reader]);
+        let leaf_reader_context = index_reader.leaves();
+        collector.set_next_reader(&leaf_reader_context[0]).unwrap();
+        loop {
+            let doc = scorer.next().unwrap();
+            if doc == NO_MORE_DOCS {
+                break;
+            }
+            collector.collect(doc, &mut scorer).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_collect_after() {
+        // the mock scorer scores each doc by its id
+        let mut collector = TopDocsCollector::new(2);
+        collect_docs(&mut collector, vec![1, 2, 3, 4, 5]);
+        let page = collector.top_docs();
+        let ids: Vec<DocId> = page.score_docs().iter().map(|d| d.doc_id()).collect();
+        assert_eq!(ids, vec![5, 4]);
+
+        let last = page.score_docs()[1].clone();
+        let after = ScoreDoc::new(last.doc_id(), last.score());
+        let mut collector = TopDocsCollector::with_after(2, after);
+        collect_docs(&mut collector, vec![1, 2, 3, 4, 5]);
+        let page = collector.top_docs();
+        assert_eq!(page.total_hits(), 5);
+        let ids: Vec<DocId> = page.score_docs().iter().map(|d| d.doc_id()).collect();
+        assert_eq!(ids, vec![3, 2]);
+    }
+
+    #[test]
+    fn test_collect_after_ties() {
+        let mut collector = TopDocsCollector::with_after(10, ScoreDoc::new(4, 3.0));
+        collector.add_doc(2, 3.0);
+        collector.add_doc(4, 3.0);
+        collector.add_doc(5, 3.0);
+        collector.add_doc(1, 4.0);
+        collector.add_doc(7, 2.0);
+        collector.add_doc(6, 3.0);
+        let top_docs = collector.top_docs();
+        let hits: Vec<(DocId, f32)> = top_docs
+            .score_docs()
+            .iter()
+            .map(|d| (d.doc_id(), d.score()))
+            .collect();
+        assert_eq!(hits, vec![(5, 3.0), (6, 3.0), (7, 2.0)]);
+    }
+
+    #[test]
+    fn test_collect_ties_out_of_order() {
+        let mut collector = TopDocsCollector::new(2);
+        collector.add_doc(25, 5.0);
+        collector.add_doc(15, 5.0);
+        collector.add_doc(3, 1.0);
+        collector.add_doc(5, 5.0);
+        let top_docs = collector.top_docs();
+        let ids: Vec<DocId> = top_docs.score_docs().iter().map(|d| d.doc_id()).collect();
+        assert_eq!(ids, vec![5, 15]);
+    }
+
+    struct MinScoreRecordingScorer {
+        scorer: MockSimpleScorer<MockDocIterator>,
+        min_scores: Vec<f32>,
+    }
+
+    impl Scorer for MinScoreRecordingScorer {
+        fn score(&mut self) -> Result<f32> {
+            self.scorer.score()
+        }
+
+        fn set_min_competitive_score(&mut self, min_score: f32) {
+            self.min_scores.push(min_score);
+        }
+    }
+
+    impl DocIterator for MinScoreRecordingScorer {
+        fn doc_id(&self) -> DocId {
+            self.scorer.doc_id()
+        }
+
+        fn next(&mut self) -> Result<DocId> {
+            self.scorer.next()
+        }
+
+        fn advance(&mut self, target: DocId) -> Result<DocId> {
+            self.scorer.advance(target)
+        }
+
+        fn cost(&self) -> usize {
+            self.scorer.cost()
+        }
+    }
+
+    fn collect_all(collector: &mut TopDocsCollector, scorer: &mut MinScoreRecordingScorer) {
+        let leaf_reader = MockLeafReader::new(0);
+        let index_reader = MockIndexReader::new(vec![leaf_reader]);
+        let leaf_reader_context = index_reader.leaves();
+        collector.set_next_reader(&leaf_reader_context[0]).unwrap();
+        loop {
+            let doc = scorer.next().unwrap();
+            if doc == NO_MORE_DOCS {
+                break;
+            }
+            collector.collect(doc, scorer).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_set_min_competitive_score() {
+        let mut scorer = MinScoreRecordingScorer {
+            scorer: create_mock_scorer(vec![1, 2, 3, 4, 5]),
+            min_scores: vec![],
+        };
+        let mut collector = TopDocsCollector::with_total_hits_threshold(2, 0);
+        collect_all(&mut collector, &mut scorer);
+
+        // pushed each time the queue's minimum changes once it is full
+        assert_eq!(scorer.min_scores, vec![1.0, 2.0, 3.0, 4.0]);
+        let top_docs = collector.top_docs();
+        let score_docs = top_docs.score_docs();
+        assert_eq!(score_docs[0].doc_id(), 5);
+        assert_eq!(score_docs[1].doc_id(), 4);
+    }
+
+    #[test]
+    fn test_min_competitive_score_needs_threshold() {
+        let mut scorer = MinScoreRecordingScorer {
+            scorer: create_mock_scorer(vec![1, 2, 3, 4, 5]),
+            min_scores: vec![],
+        };
+        let mut collector = TopDocsCollector::new(2);
+        collect_all(&mut collector, &mut scorer);
+
+        assert!(scorer.min_scores.is_empty());
+        assert_eq!(collector.top_docs().total_hits(), 5);
+    }
 }
@@ -0,0 +1,374 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::cmp::Ordering;
+use std::f32;
+
+use core::codec::Codec;
+use core::index::LeafReaderContext;
+use core::search::collector::{Collector, NoParallelLeafCollector, SearchCollector};
+use core::search::field_comparator::{ComparatorValue, FieldComparator, FieldComparatorEnum};
+use core::search::sort::Sort;
+use core::search::sort_field::SortFieldType;
+use core::search::top_docs::{FieldDoc, ScoreDocHit, TopDocs, TopFieldDocs};
+use core::search::Scorer;
+use core::util::DocId;
+use error::{ErrorKind::IllegalState, Result};
+
+/// A collector keeping the top hits by a `Sort`.
+///
+/// The sort fields are compared in order, each one breaking the ties of the
+/// previous ones, and the hits which are still tied are sorted by ascending
+/// doc id. Scores are only computed if one of the sort fields is the score.
+pub struct TopFieldCollector {
+    sort: Sort,
+    comparators: Vec<FieldComparatorEnum>,
+    reverses: Vec<bool>,
+    num_hits: usize,
+    needs_scores: bool,
+    // global doc id and score of the hit in each slot
+    docs: Vec<DocId>,
+    scores: Vec<f32>,
+    // the used slots as a binary heap, the least competitive hit on top
+    heap: Vec<usize>,
+    total_hits: usize,
+    max_score: f32,
+    doc_base: DocId,
+}
+
+impl TopFieldCollector {
+    pub fn new(sort: Sort, num_hits: usize) -> TopFieldCollector {
+        let fields = sort.get_sort();
+        let comparators = fields
+            .iter()
+            .map(|f| f.get_comparator(num_hits, f.missing_value()))
+            .collect();
+        let reverses = fields.iter().map(|f| f.is_reverse()).collect();
+        let needs_scores = sort.needs_scores();
+        TopFieldCollector {
+            sort,
+            comparators,
+            reverses,
+            num_hits,
+            needs_scores,
+            docs: vec![0; num_hits],
+            scores: vec![f32::NAN; num_hits],
+            heap: Vec::with_capacity(num_hits),
+            total_hits: 0,
+            max_score: f32::NAN,
+            doc_base: 0,
+        }
+    }
+
+    /// Returns the top hits that were collected, as `FieldDoc`s carrying the
+    /// values they were sorted by.
+    pub fn top_docs(&mut self) -> TopDocs {
+        let mut slots = self.heap.clone();
+        slots.sort_by(|a, b| self.compare_slots(*a, *b));
+        let score_docs = slots
+            .into_iter()
+            .map(|slot| {
+                let fields = self.comparators.iter().map(|c| c.value(slot)).collect();
+                ScoreDocHit::Field(FieldDoc::new(self.docs[slot], self.scores[slot], fields))
+            })
+            .collect();
+        TopDocs::Field(TopFieldDocs {
+            total_hits: self.total_hits,
+            score_docs,
+            max_score: self.max_score,
+            fields: self.sort.get_sort().to_vec(),
+        })
+    }
+
+    /// `Ordering::Less` if the hit in `slot1` sorts before the one in `slot2`.
+    fn compare_slots(&self, slot1: usize, slot2: usize) -> Ordering {
+        for (comparator, reverse) in self.comparators.iter().zip(&self.reverses) {
+            let ord = comparator.compare(slot1, slot2);
+            if ord != Ordering::Equal {
+                return if *reverse { ord.reverse() } else { ord };
+            }
+        }
+        self.docs[slot1].cmp(&self.docs[slot2])
+    }
+
+    /// Whether the hit `doc` of the current segment sorts before the bottom
+    /// of the full queue.
+    fn is_competitive(&self, doc: DocId, score: f32) -> Result<bool> {
+        let bottom = self.heap[0];
+        for (comparator, reverse) in self.comparators.iter().zip(&self.reverses) {
+            // `Greater` if the bottom sorts after the hit
+            let ord = comparator.compare_bottom(comparator_value(comparator, doc, score))?;
+            let ord = if *reverse { ord.reverse() } else { ord };
+            if ord != Ordering::Equal {
+                return Ok(ord == Ordering::Greater);
+            }
+        }
+        Ok(doc + self.doc_base < self.docs[bottom])
+    }
+
+    /// Copies the hit `doc` of the current segment in `slot`.
+    fn copy(&mut self, slot: usize, doc: DocId, score: f32) -> Result<()> {
+        for comparator in &mut self.comparators {
+            let value = comparator_value(comparator, doc, score);
+            comparator.copy(slot, value)?;
+        }
+        self.docs[slot] = doc + self.doc_base;
+        self.scores[slot] = score;
+        Ok(())
+    }
+
+    fn set_bottom(&mut self) {
+        let bottom = self.heap[0];
+        for comparator in &mut self.comparators {
+            comparator.set_bottom(bottom);
+        }
+    }
+
+    fn sift_up(&mut self, mut i: usize) {
+        while i > 0 {
+            let parent = (i - 1) / 2;
+            if self.compare_slots(self.heap[i], self.heap[parent]) != Ordering::Greater {
+                break;
+            }
+            self.heap.swap(i, parent);
+            i = parent;
+        }
+    }
+
+    fn sift_down(&mut self, mut i: usize) {
+        let len = self.heap.len();
+        loop {
+            let mut largest = i;
+            for child in &[2 * i + 1, 2 * i + 2] {
+                if *child < len
+                    && self.compare_slots(self.heap[*child], self.heap[largest])
+                        == Ordering::Greater
+                {
+                    largest = *child;
+                }
+            }
+            if largest == i {
+                break;
+            }
+            self.heap.swap(i, largest);
+            i = largest;
+        }
+    }
+}
+
+impl SearchCollector for TopFieldCollector {
+    type LC = NoParallelLeafCollector;
+
+    fn set_next_reader<C: Codec>(&mut self, reader: &LeafReaderContext<'_, C>) -> Result<()> {
+        self.doc_base = reader.doc_base;
+        for comparator in &mut self.comparators {
+            comparator.get_information_from_reader(reader)?;
+        }
+        Ok(())
+    }
+
+    fn support_parallel(&self) -> bool {
+        false
+    }
+
+    fn leaf_collector<C: Codec>(
+        &mut self,
+        _reader: &LeafReaderContext<'_, C>,
+    ) -> Result<NoParallelLeafCollector> {
+        bail!(IllegalState(
+            "TopFieldCollector doesn't support parallel collection".into()
+        ))
+    }
+
+    fn finish_parallel(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl Collector for TopFieldCollector {
+    fn needs_scores(&self) -> bool {
+        self.needs_scores
+    }
+
+    fn collect<S: Scorer + ?Sized>(&mut self, doc: DocId, scorer: &mut S) -> Result<()> {
+        self.total_hits += 1;
+        if self.num_hits == 0 {
+            return Ok(());
+        }
+
+        let score = if self.needs_scores {
+            let score = scorer.score()?;
+            if self.max_score.is_nan() || score > self.max_score {
+                self.max_score = score;
+            }
+            score
+        } else {
+            f32::NAN
+        };
+        if self.heap.len() < self.num_hits {
+            let slot = self.heap.len();
+            self.copy(slot, doc, score)?;
+            self.heap.push(slot);
+            self.sift_up(slot);
+            if self.heap.len() == self.num_hits {
+                self.set_bottom();
+            }
+        } else if self.is_competitive(doc, score)? {
+            let slot = self.heap[0];
+            self.copy(slot, doc, score)?;
+            self.sift_down(0);
+            self.set_bottom();
+        }
+        Ok(())
+    }
+}
+
+/// The value `comparator` reads for the hit `doc` of the current segment.
+fn comparator_value(comparator: &FieldComparatorEnum, doc: DocId, score: f32) -> ComparatorValue {
+    if comparator.get_type() == SortFieldType::Score {
+        ComparatorValue::Score(score)
+    } else {
+        ComparatorValue::Doc(doc)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::doc::NumericDocValuesField;
+    use core::index::tests::*;
+    use core::index::{IndexReader, IndexWriter, IndexWriterConfig};
+    use core::search::match_all::MatchAllDocsQuery;
+    use core::search::searcher::{DefaultIndexSearcher, IndexSearcher};
+    use core::search::sort_field::{SimpleSortField, SortField, SortMissingOrder};
+    use core::search::tests::*;
+    use core::search::{DocIterator, NO_MORE_DOCS};
+    use core::store::RAMDirectory;
+    use std::sync::Arc;
+
+    fn collect(collector: &mut TopFieldCollector, docs: Vec<DocId>) {
+        let mut scorer = create_mock_scorer(docs);
+        let leaf_reader = MockLeafReader::new(0);
+        let index_reader = MockIndexReader::new(vec![leaf_reader]);
+        let leaf_reader_context = index_reader.leaves();
+        collector.set_next_reader(&leaf_reader_context[0]).unwrap();
+        loop {
+            let doc = scorer.next().unwrap();
+            if doc == NO_MORE_DOCS {
+                break;
+            }
+            collector.collect(doc, &mut scorer).unwrap();
+        }
+    }
+
+    fn doc_ids(top_docs: &TopDocs) -> Vec<DocId> {
+        top_docs.score_docs().iter().map(|d| d.doc_id()).collect()
+    }
+
+    #[test]
+    fn test_sort_by_score() {
+        // the mock scorer scores each doc by its id
+        let sort = Sort::new(vec![SortField::new_score()]);
+        let mut collector = TopFieldCollector::new(sort, 3);
+        assert!(collector.needs_scores());
+        collect(&mut collector, vec![1, 4, 2, 5, 3]);
+
+        let top_docs = collector.top_docs();
+        assert_eq!(top_docs.total_hits(), 5);
+        assert_eq!(doc_ids(&top_docs), vec![5, 4, 3]);
+        assert!((top_docs.score_docs()[0].score() - 5.0).abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn test_sort_by_doc() {
+        let sort = Sort::new(vec![SortField::Simple(SimpleSortField::new(
+            String::new(),
+            SortFieldType::Doc,
+            true,
+        ))]);
+        let mut collector = TopFieldCollector::new(sort, 2);
+        assert!(!collector.needs_scores());
+        collect(&mut collector, vec![1, 2, 3, 4, 5]);
+
+        let top_docs = collector.top_docs();
+        assert_eq!(doc_ids(&top_docs), vec![5, 4]);
+        assert!(top_docs.score_docs()[0].score().is_nan());
+    }
+
+    #[test]
+    fn test_ties_broken_by_next_field() {
+        let mut collector = TopFieldCollector::new(
+            Sort::new(vec![
+                SortField::new_score(),
+                SortField::Simple(SimpleSortField::new(
+                    String::new(),
+                    SortFieldType::Doc,
+                    true,
+                )),
+            ]),
+            3,
+        );
+        // all the docs of a mock scorer score differently, so force the ties
+        // through the slots directly
+        for (slot, (doc, score)) in [(1, 2.0), (3, 2.0), (2, 1.0)].iter().enumerate() {
+            collector.copy(slot, *doc, *score).unwrap();
+            collector.heap.push(slot);
+            collector.sift_up(slot);
+        }
+        let top_docs = collector.top_docs();
+        assert_eq!(doc_ids(&top_docs), vec![3, 1, 2]);
+    }
+
+    #[test]
+    fn test_missing_numeric_values() {
+        let dir = Arc::new(RAMDirectory::default());
+        let writer = IndexWriter::new(dir, Arc::new(IndexWriterConfig::default())).unwrap();
+        // the docs 1 and 3 have no price, and the docs 3 and 4 are in a
+        // second segment
+        let prices = [Some(3), None, Some(-1), None, Some(7)];
+        for (i, price) in prices.iter().enumerate() {
+            let mut doc = vec![NumericDocValuesField::new("id", i as i64)];
+            if let Some(price) = price {
+                doc.push(NumericDocValuesField::new("price", *price));
+            }
+            writer.add_document(doc).unwrap();
+            if i == 2 {
+                writer.commit().unwrap();
+            }
+        }
+        let reader = writer.get_reader(true, false).unwrap();
+        assert_eq!(reader.leaves().len(), 2);
+        let searcher = DefaultIndexSearcher::new(&reader);
+
+        let query = MatchAllDocsQuery::new();
+        let search = |reverse: bool, order: SortMissingOrder| {
+            let mut field = SortField::Simple(SimpleSortField::new(
+                "price".into(),
+                SortFieldType::Long,
+                reverse,
+            ));
+            field.set_missing_order(order).unwrap();
+            let top_docs = searcher
+                .search_with_sort(&query, 3, &Sort::new(vec![field]))
+                .unwrap();
+            assert_eq!(top_docs.total_hits(), 5);
+            doc_ids(&top_docs)
+        };
+        assert_eq!(search(false, SortMissingOrder::Last), vec![2, 0, 4]);
+        assert_eq!(search(false, SortMissingOrder::First), vec![1, 3, 2]);
+        assert_eq!(search(true, SortMissingOrder::Last), vec![4, 0, 2]);
+        assert_eq!(search(true, SortMissingOrder::First), vec![1, 3, 4]);
+
+        writer.close().unwrap();
+    }
+}
@@ -11,32 +11,51 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use core::search::bulk_scorer::BulkScorer;
 use core::search::{two_phase_next, DocIterator, Scorer, NO_MORE_DOCS};
-use core::util::DocId;
+use core::util::{Bits, DocId};
+use error::ErrorKind::UnsupportedOperation;
 use error::Result;
 
+use std::cmp::Ordering;
+
 pub struct ConjunctionScorer<T: Scorer> {
     lead1: T,
     lead2: T,
     others: Vec<T>,
     support_two_phase: bool,
     two_phase_match_cost: f32,
+    // indexes of the two phase scorers, `0` being `lead1`, `1` being `lead2`
+    // and `i + 2` being `others[i]`, by ascending match cost
+    two_phase_order: Vec<usize>,
 }
 
 impl<T: Scorer> ConjunctionScorer<T> {
     pub fn new(mut children: Vec<T>) -> ConjunctionScorer<T> {
         assert!(children.len() >= 2);
 
-        // Sort the scores the first time to allow the least cost DocIterator to
-        // lead the matching.
         let support_two_phase = children.iter().any(|scorer| scorer.support_two_phase());
         let two_phase_match_cost = if support_two_phase {
             children.iter().map(|s| s.match_cost()).sum()
         } else {
             0f32
         };
+        // Sort the scores the first time to allow the least cost DocIterator to
+        // lead the matching.
         children.sort_by(|a, b| a.cost().cmp(&b.cost()));
 
+        // Once all the approximations agree on a doc, the cheapest matches are
+        // checked first so that the expensive ones run as rarely as possible.
+        let mut two_phase_order: Vec<usize> = (0..children.len())
+            .filter(|&i| children[i].support_two_phase())
+            .collect();
+        two_phase_order.sort_by(|&a, &b| {
+            children[a]
+                .match_cost()
+                .partial_cmp(&children[b].match_cost())
+                .unwrap_or(Ordering::Equal)
+        });
+
         let others = children.drain(2..).collect();
 
         let lead2 = children.remove(1);
@@ -48,6 +67,15 @@ impl<T: Scorer> ConjunctionScorer<T> {
             others,
             support_two_phase,
             two_phase_match_cost,
+            two_phase_order,
+        }
+    }
+
+    fn scorer_mut(&mut self, index: usize) -> &mut T {
+        match index {
+            0 => &mut self.lead1,
+            1 => &mut self.lead2,
+            _ => &mut self.others[index - 2],
         }
     }
 
@@ -128,20 +156,13 @@ impl<T: Scorer> DocIterator for ConjunctionScorer<T> {
     }
 
     fn matches(&mut self) -> Result<bool> {
-        if !self.support_two_phase {
-            Ok(true)
-        } else if !self.lead1.matches()? || !self.lead2.matches()? {
-            Ok(false)
-        } else {
-            let mut res = true;
-            for s in &mut self.others {
-                if !s.matches()? {
-                    res = false;
-                    break;
-                }
+        for i in 0..self.two_phase_order.len() {
+            let index = self.two_phase_order[i];
+            if !self.scorer_mut(index).matches()? {
+                return Ok(false);
             }
-            Ok(res)
         }
+        Ok(true)
     }
 
     fn match_cost(&self) -> f32 {
@@ -159,11 +180,187 @@ impl<T: Scorer> DocIterator for ConjunctionScorer<T> {
     }
 }
 
+/// Collects the matches of a conjunction led by its rarest clause, the docs
+/// of the lead which aren't accepted are skipped before the other clauses
+/// are advanced to them.
+pub struct ConjunctionBulkScorer {
+    // by ascending cost, the first one leading the iteration
+    scorers: ClauseScorers,
+    // indexes of the two phase scorers by ascending match cost
+    two_phase_order: Vec<usize>,
+}
+
+impl ConjunctionBulkScorer {
+    pub fn new(mut scorers: Vec<Box<dyn Scorer>>) -> ConjunctionBulkScorer {
+        assert!(!scorers.is_empty());
+        scorers.sort_by(|a, b| a.cost().cmp(&b.cost()));
+        let mut two_phase_order: Vec<usize> = (0..scorers.len())
+            .filter(|&i| scorers[i].support_two_phase())
+            .collect();
+        two_phase_order.sort_by(|&a, &b| {
+            scorers[a]
+                .match_cost()
+                .partial_cmp(&scorers[b].match_cost())
+                .unwrap_or(Ordering::Equal)
+        });
+        ConjunctionBulkScorer {
+            scorers: ClauseScorers(scorers),
+            two_phase_order,
+        }
+    }
+
+    fn matches(&mut self) -> Result<bool> {
+        for &index in &self.two_phase_order {
+            if !self.scorers.0[index].matches()? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+}
+
+impl BulkScorer for ConjunctionBulkScorer {
+    fn score(
+        &mut self,
+        collector: &mut dyn FnMut(DocId, &mut dyn Scorer) -> Result<()>,
+        accept_docs: Option<&dyn Bits>,
+        min: DocId,
+        max: DocId,
+    ) -> Result<DocId> {
+        let mut doc = self.scorers.0[0].doc_id();
+        if doc < min {
+            doc = self.scorers.0[0].approximate_advance(min)?;
+        }
+        'lead: while doc < max {
+            if let Some(accept_docs) = accept_docs {
+                if !accept_docs.get(doc as usize)? {
+                    doc = self.scorers.0[0].approximate_next()?;
+                    continue;
+                }
+            }
+            for i in 1..self.scorers.0.len() {
+                let mut other_doc = self.scorers.0[i].doc_id();
+                if other_doc < doc {
+                    other_doc = self.scorers.0[i].approximate_advance(doc)?;
+                }
+                if other_doc > doc {
+                    doc = self.scorers.0[0].approximate_advance(other_doc)?;
+                    continue 'lead;
+                }
+            }
+            if self.matches()? {
+                collector(doc, &mut self.scorers)?;
+            }
+            doc = self.scorers.0[0].approximate_next()?;
+        }
+        Ok(doc)
+    }
+
+    fn cost(&self) -> usize {
+        self.scorers.0[0].cost()
+    }
+}
+
+/// The clauses of a `ConjunctionBulkScorer` positioned on a match, scoring
+/// the sum of their scores.
+struct ClauseScorers(Vec<Box<dyn Scorer>>);
+
+impl Scorer for ClauseScorers {
+    fn score(&mut self) -> Result<f32> {
+        let mut score = 0f32;
+        for scorer in &mut self.0 {
+            score += scorer.score()?;
+        }
+        Ok(score)
+    }
+}
+
+impl DocIterator for ClauseScorers {
+    fn doc_id(&self) -> DocId {
+        self.0[0].doc_id()
+    }
+
+    fn next(&mut self) -> Result<DocId> {
+        bail!(UnsupportedOperation(
+            "the clauses of a bulk scored conjunction can't be iterated".into()
+        ))
+    }
+
+    fn advance(&mut self, _target: DocId) -> Result<DocId> {
+        bail!(UnsupportedOperation(
+            "the clauses of a bulk scored conjunction can't be iterated".into()
+        ))
+    }
+
+    fn cost(&self) -> usize {
+        self.0[0].cost()
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
     use super::*;
     use core::search::tests::*;
+    use core::util::bit_set::{BitSet, FixedBitSet};
+
+    use std::sync::{Arc, Mutex};
+
+    /// A two phase scorer logging its name each time `matches` is called.
+    struct RecordingTwoPhaseScorer {
+        name: &'static str,
+        approximation: MockDocIterator,
+        rejected: Vec<DocId>,
+        match_cost: f32,
+        log: Arc<Mutex<Vec<&'static str>>>,
+    }
+
+    impl Scorer for RecordingTwoPhaseScorer {
+        fn score(&mut self) -> Result<f32> {
+            Ok(1f32)
+        }
+
+        fn support_two_phase(&self) -> bool {
+            true
+        }
+    }
+
+    impl DocIterator for RecordingTwoPhaseScorer {
+        fn doc_id(&self) -> DocId {
+            self.approximation.doc_id()
+        }
+
+        fn next(&mut self) -> Result<DocId> {
+            self.approximate_next()?;
+            two_phase_next(self)
+        }
+
+        fn advance(&mut self, target: DocId) -> Result<DocId> {
+            self.approximate_advance(target)?;
+            two_phase_next(self)
+        }
+
+        fn cost(&self) -> usize {
+            self.approximation.cost()
+        }
+
+        fn matches(&mut self) -> Result<bool> {
+            self.log.lock().unwrap().push(self.name);
+            Ok(!self.rejected.contains(&self.doc_id()))
+        }
+
+        fn match_cost(&self) -> f32 {
+            self.match_cost
+        }
+
+        fn approximate_next(&mut self) -> Result<DocId> {
+            self.approximation.next()
+        }
+
+        fn approximate_advance(&mut self, target: DocId) -> Result<DocId> {
+            self.approximation.advance(target)
+        }
+    }
 
     #[test]
     fn test_mock_doc_iterator_next() {
@@ -304,4 +501,86 @@ mod tests {
             vec![Box::new(s1), Box::new(s2), Box::new(s3), Box::new(s4)];
         ConjunctionScorer::new(scorers)
     }
+
+    #[test]
+    fn test_conjunction_two_phase_verification_order() {
+        let log = Arc::new(Mutex::new(vec![]));
+        // the cheapest approximation but the most expensive verification
+        let phrase = RecordingTwoPhaseScorer {
+            name: "phrase",
+            approximation: MockDocIterator::new(vec![2, 4, 6]),
+            rejected: vec![],
+            match_cost: 100f32,
+            log: Arc::clone(&log),
+        };
+        let range = RecordingTwoPhaseScorer {
+            name: "range",
+            approximation: MockDocIterator::new((0..10).collect()),
+            rejected: vec![4],
+            match_cost: 4f32,
+            log: Arc::clone(&log),
+        };
+        let term = create_mock_scorer((0..10).collect());
+
+        let scorers: Vec<Box<dyn Scorer>> = vec![Box::new(term), Box::new(range), Box::new(phrase)];
+        let mut conjunction = ConjunctionScorer::new(scorers);
+        assert!((conjunction.match_cost() - 104f32).abs() < ::std::f32::EPSILON);
+
+        assert_eq!(conjunction.next().unwrap(), 2);
+        assert_eq!(*log.lock().unwrap(), vec!["range", "phrase"]);
+
+        // the phrase is not verified once the range rejected the doc
+        log.lock().unwrap().clear();
+        assert_eq!(conjunction.next().unwrap(), 6);
+        assert_eq!(*log.lock().unwrap(), vec!["range", "range", "phrase"]);
+        assert_eq!(conjunction.next().unwrap(), NO_MORE_DOCS);
+    }
+
+    #[test]
+    fn test_conjunction_bulk_scorer() {
+        let create_bulk_scorer = || {
+            let s1 = create_mock_scorer(vec![1, 2, 3, 4, 5, 6, 7, 8]);
+            let s2 = create_mock_scorer(vec![2, 3, 5, 7, 8]);
+            let s3 = create_mock_two_phase_scorer(vec![1, 2, 3, 4, 5, 6, 7], vec![1, 4, 5]);
+            let s4 = create_mock_two_phase_scorer(vec![1, 2, 3, 4, 5, 6, 7], vec![2, 4]);
+            let scorers: Vec<Box<dyn Scorer>> =
+                vec![Box::new(s1), Box::new(s2), Box::new(s3), Box::new(s4)];
+            ConjunctionBulkScorer::new(scorers)
+        };
+        let mut collected = vec![];
+        {
+            let mut collect = |doc: DocId, scorer: &mut dyn Scorer| -> Result<()> {
+                assert_eq!(scorer.doc_id(), doc);
+                collected.push((doc, scorer.score()?));
+                Ok(())
+            };
+
+            let mut bulk_scorer = create_bulk_scorer();
+            assert_eq!(bulk_scorer.cost(), 5);
+            assert_eq!(
+                bulk_scorer.score(&mut collect, None, 0, NO_MORE_DOCS).unwrap(),
+                NO_MORE_DOCS
+            );
+
+            // the lead is only advanced to the accepted docs
+            let mut accept_docs = FixedBitSet::new(10);
+            for doc in &[2, 3, 5, 8] {
+                accept_docs.set(*doc);
+            }
+            let mut bulk_scorer = create_bulk_scorer();
+            assert_eq!(
+                bulk_scorer
+                    .score(&mut collect, Some(&accept_docs), 0, 6)
+                    .unwrap(),
+                7
+            );
+            assert_eq!(
+                bulk_scorer
+                    .score(&mut collect, Some(&accept_docs), 6, NO_MORE_DOCS)
+                    .unwrap(),
+                NO_MORE_DOCS
+            );
+        }
+        assert_eq!(collected, vec![(3, 12f32), (7, 28f32), (3, 12f32)]);
+    }
 }
@@ -331,6 +331,11 @@ impl<T: DocIterator> DisiPriorityQueue<T> {
     pub fn is_empty(&self) -> bool {
         self.size == 0
     }
+
+    /// Consumes the queue and returns the wrapped iterators, in the order they were pushed.
+    pub fn into_inner(self) -> Vec<T> {
+        self._buffer.into_iter().map(|w| w.scorer).collect()
+    }
 }
 
 impl<'a, T: DocIterator> IntoIterator for &'a DisiPriorityQueue<T> {
@@ -51,6 +51,11 @@ impl<T: Scorer> DisjunctionSumScorer<T> {
             two_phase_match_cost,
         }
     }
+
+    /// Consumes this scorer and returns its sub scorers, left on their current docs.
+    pub fn into_sub_scorers(self) -> Vec<T> {
+        self.sub_scorers.into_inner()
+    }
 }
 
 impl<T: Scorer> DisjunctionScorer for DisjunctionSumScorer<T> {
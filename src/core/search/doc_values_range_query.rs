@@ -0,0 +1,359 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::codec::Codec;
+use core::index::{DocValuesType, LeafReaderContext, NumericDocValuesRef, SortedNumericDocValuesRef};
+use core::search::explanation::Explanation;
+use core::search::match_all::AllDocsIterator;
+use core::search::searcher::SearchPlanBuilder;
+use core::search::term_query::TermQuery;
+use core::search::{two_phase_next, DocIterator, Query, Scorer, Weight};
+use core::util::{BitsRef, DocId};
+use error::{ErrorKind, Result};
+
+use std::fmt;
+
+pub const DOC_VALUES_RANGE: &str = "doc_values_range";
+
+// a bits lookup, a doc values lookup and two comparisons
+const NUMERIC_MATCH_COST: f32 = 4.0;
+// positioning on the doc, then a lookup and two comparisons per value
+const SORTED_NUMERIC_MATCH_COST: f32 = 8.0;
+
+/// A range query over a `NUMERIC` or `SORTED_NUMERIC` doc values field,
+/// both bounds being inclusive.
+///
+/// This query doesn't need the field to be indexed: every doc of a segment
+/// is a candidate and the doc values are checked in the second phase, so it
+/// is slow on its own and meant to be used as a filter in a conjunction with
+/// more selective clauses. A doc of a `SORTED_NUMERIC` field matches if any
+/// of its values is in range.
+pub struct DocValuesRangeQuery {
+    field: String,
+    lower: i64,
+    upper: i64,
+}
+
+impl DocValuesRangeQuery {
+    pub fn new(field: String, lower: i64, upper: i64) -> DocValuesRangeQuery {
+        DocValuesRangeQuery {
+            field,
+            lower,
+            upper,
+        }
+    }
+}
+
+impl<C: Codec> Query<C> for DocValuesRangeQuery {
+    fn create_weight(
+        &self,
+        _searcher: &dyn SearchPlanBuilder<C>,
+        _needs_scores: bool,
+    ) -> Result<Box<dyn Weight<C>>> {
+        Ok(Box::new(DocValuesRangeWeight::new(
+            self.field.clone(),
+            self.lower,
+            self.upper,
+        )))
+    }
+
+    fn extract_terms(&self) -> Vec<TermQuery> {
+        vec![]
+    }
+
+    fn query_type(&self) -> &'static str {
+        DOC_VALUES_RANGE
+    }
+
+    fn as_any(&self) -> &::std::any::Any {
+        self
+    }
+}
+
+impl fmt::Display for DocValuesRangeQuery {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "DocValuesRangeQuery(field: {}, lower: {}, upper: {})",
+            &self.field, self.lower, self.upper
+        )
+    }
+}
+
+struct DocValuesRangeWeight {
+    field: String,
+    lower: i64,
+    upper: i64,
+    weight: f32,
+    boost: f32,
+    norm: f32,
+}
+
+impl DocValuesRangeWeight {
+    fn new(field: String, lower: i64, upper: i64) -> DocValuesRangeWeight {
+        DocValuesRangeWeight {
+            field,
+            lower,
+            upper,
+            weight: 0f32,
+            boost: 1f32,
+            norm: 1f32,
+        }
+    }
+}
+
+impl<C: Codec> Weight<C> for DocValuesRangeWeight {
+    fn create_scorer(
+        &self,
+        leaf_reader_ctx: &LeafReaderContext<'_, C>,
+    ) -> Result<Option<Box<dyn Scorer>>> {
+        if self.lower > self.upper {
+            return Ok(None);
+        }
+        let leaf_reader = leaf_reader_ctx.reader;
+        let doc_values_type = match leaf_reader.field_info(&self.field) {
+            Some(field_info) => field_info.doc_values_type,
+            None => return Ok(None),
+        };
+        let values = match doc_values_type {
+            DocValuesType::Null => return Ok(None),
+            DocValuesType::Numeric => RangeDocValues::Numeric(
+                leaf_reader.get_numeric_doc_values(&self.field)?,
+                leaf_reader.get_docs_with_field(&self.field)?,
+            ),
+            DocValuesType::SortedNumeric => RangeDocValues::SortedNumeric(
+                leaf_reader.get_sorted_numeric_doc_values(&self.field)?,
+            ),
+            _ => bail!(ErrorKind::IllegalArgument(format!(
+                "field '{}' has doc values type {:?}, expected Numeric or SortedNumeric",
+                &self.field, doc_values_type
+            ))),
+        };
+        Ok(Some(Box::new(DocValuesRangeScorer {
+            approximation: AllDocsIterator::new(leaf_reader.max_doc()),
+            values,
+            lower: self.lower,
+            upper: self.upper,
+            score: self.weight,
+        })))
+    }
+
+    fn query_type(&self) -> &'static str {
+        DOC_VALUES_RANGE
+    }
+
+    fn normalize(&mut self, norm: f32, boost: f32) {
+        self.weight = norm * boost;
+        self.boost = boost;
+        self.norm = norm;
+    }
+
+    fn value_for_normalization(&self) -> f32 {
+        self.weight * self.weight
+    }
+
+    fn needs_scores(&self) -> bool {
+        false
+    }
+
+    fn explain(&self, reader: &LeafReaderContext<'_, C>, doc: DocId) -> Result<Explanation> {
+        let exists = if let Some(mut scorer) = self.create_scorer(reader)? {
+            scorer.advance(doc)? == doc
+        } else {
+            false
+        };
+
+        if exists {
+            Ok(Explanation::new(
+                true,
+                self.weight,
+                format!("{}, product of:", self),
+                vec![
+                    Explanation::new(true, self.boost, "boost".to_string(), vec![]),
+                    Explanation::new(true, self.norm, "queryNorm".to_string(), vec![]),
+                ],
+            ))
+        } else {
+            Ok(Explanation::new(
+                false,
+                0.0f32,
+                format!("{} doesn't match id {}", self, doc),
+                vec![],
+            ))
+        }
+    }
+}
+
+impl fmt::Display for DocValuesRangeWeight {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "DocValuesRangeWeight(field: {}, lower: {}, upper: {})",
+            &self.field, self.lower, self.upper
+        )
+    }
+}
+
+enum RangeDocValues {
+    Numeric(NumericDocValuesRef, BitsRef),
+    SortedNumeric(SortedNumericDocValuesRef),
+}
+
+/// A two phase scorer over all the docs of a segment, the doc values of
+/// the approximated doc are only read in `matches`.
+struct DocValuesRangeScorer {
+    approximation: AllDocsIterator,
+    values: RangeDocValues,
+    lower: i64,
+    upper: i64,
+    score: f32,
+}
+
+impl Scorer for DocValuesRangeScorer {
+    fn score(&mut self) -> Result<f32> {
+        Ok(self.score)
+    }
+
+    fn support_two_phase(&self) -> bool {
+        true
+    }
+}
+
+impl DocIterator for DocValuesRangeScorer {
+    fn doc_id(&self) -> DocId {
+        self.approximation.doc_id()
+    }
+
+    fn next(&mut self) -> Result<DocId> {
+        self.approximate_next()?;
+        two_phase_next(self)
+    }
+
+    fn advance(&mut self, target: DocId) -> Result<DocId> {
+        self.approximate_advance(target)?;
+        two_phase_next(self)
+    }
+
+    fn cost(&self) -> usize {
+        self.approximation.cost()
+    }
+
+    fn matches(&mut self) -> Result<bool> {
+        let doc = self.approximation.doc_id();
+        match self.values {
+            RangeDocValues::Numeric(ref values, ref docs_with_field) => {
+                let value = values.get(doc)?;
+                // a missing value reads as 0, only check the bits when needed
+                Ok(value >= self.lower
+                    && value <= self.upper
+                    && docs_with_field.get(doc as usize)?)
+            }
+            RangeDocValues::SortedNumeric(ref values) => {
+                let ctx = values.set_document(None, doc)?;
+                for i in 0..values.count(&ctx) {
+                    // values are sorted in ascending order
+                    let value = values.value_at(&ctx, i)?;
+                    if value > self.upper {
+                        break;
+                    }
+                    if value >= self.lower {
+                        return Ok(true);
+                    }
+                }
+                Ok(false)
+            }
+        }
+    }
+
+    fn match_cost(&self) -> f32 {
+        match self.values {
+            RangeDocValues::Numeric(..) => NUMERIC_MATCH_COST,
+            RangeDocValues::SortedNumeric(..) => SORTED_NUMERIC_MATCH_COST,
+        }
+    }
+
+    fn approximate_next(&mut self) -> Result<DocId> {
+        self.approximation.next()
+    }
+
+    fn approximate_advance(&mut self, target: DocId) -> Result<DocId> {
+        self.approximation.advance(target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::doc::{NumericDocValuesField, SortedNumericDocValuesField};
+    use core::index::{Fieldable, IndexReader, IndexWriter, IndexWriterConfig};
+    use core::search::NO_MORE_DOCS;
+    use core::store::RAMDirectory;
+
+    use std::sync::Arc;
+
+    fn search(docs: Vec<Vec<Box<dyn Fieldable>>>, lower: i64, upper: i64) -> Vec<DocId> {
+        let dir = Arc::new(RAMDirectory::default());
+        let writer = IndexWriter::new(dir, Arc::new(IndexWriterConfig::default())).unwrap();
+        for doc in docs {
+            writer.add_document(doc).unwrap();
+        }
+        let reader = writer.get_reader(true, false).unwrap();
+
+        let weight = DocValuesRangeWeight::new("value".into(), lower, upper);
+        let mut result = vec![];
+        for leaf in reader.leaves() {
+            if let Some(mut scorer) = weight.create_scorer(&leaf).unwrap() {
+                assert!(scorer.support_two_phase());
+                assert!(scorer.match_cost() > 1.0);
+                loop {
+                    let doc = scorer.next().unwrap();
+                    if doc == NO_MORE_DOCS {
+                        break;
+                    }
+                    result.push(leaf.doc_base + doc);
+                }
+            }
+        }
+        writer.close().unwrap();
+        result
+    }
+
+    #[test]
+    fn test_numeric_range() {
+        let docs: Vec<Vec<Box<dyn Fieldable>>> = (0..20)
+            .map(|i| {
+                // every 5th doc has no value, which must not match a range containing 0
+                let field = if i % 5 == 0 { "other" } else { "value" };
+                let doc: Vec<Box<dyn Fieldable>> =
+                    vec![Box::new(NumericDocValuesField::new(field, i64::from(i) - 10))];
+                doc
+            })
+            .collect();
+        assert_eq!(search(docs, -3, 2), vec![7, 8, 9, 11, 12]);
+    }
+
+    #[test]
+    fn test_sorted_numeric_range() {
+        let docs: Vec<Vec<Box<dyn Fieldable>>> = (0..10)
+            .map(|i| {
+                let doc: Vec<Box<dyn Fieldable>> = vec![
+                    Box::new(SortedNumericDocValuesField::new("value", i)),
+                    Box::new(SortedNumericDocValuesField::new("value", i * 10)),
+                ];
+                doc
+            })
+            .collect();
+        // matched by either of their values
+        assert_eq!(search(docs, 8, 20), vec![1, 2, 8, 9]);
+    }
+}
@@ -0,0 +1,326 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::codec::Codec;
+use core::index::{DocValuesType, LeafReaderContext, SortedSetDocValuesRef, NO_MORE_ORDS};
+use core::search::collector::{
+    Collector, FacetsCollector, NoParallelLeafCollector, SearchCollector, TopDocsCollector,
+};
+use core::search::facets::SortedSetDocValuesFacetCounts;
+use core::search::searcher::IndexSearcher;
+use core::search::top_docs::TopDocs;
+use core::search::{Query, Scorer};
+use core::util::DocId;
+use error::{ErrorKind::IllegalState, Result};
+
+/// A drill down on a facet dimension: the docs whose `SORTED_SET` doc values
+/// field `dim` has any of `values`.
+struct DrillDownDim {
+    dim: String,
+    values: Vec<Vec<u8>>,
+}
+
+/// Searches a base query restricted by drill downs on facet dimensions, and
+/// collects the facets of each drill down dimension as if its own drill down
+/// was not applied, so that the sibling values of a selected one keep their
+/// counts.
+///
+/// The values of a dimension are OR'ed, the dimensions are AND'ed.
+///
+/// This is done in a single pass: the base query is run without the drill
+/// downs and each hit is checked against all the dimensions. A hit matching
+/// all of them is a drill down hit, a hit missing only one dimension is a
+/// sideways hit of this dimension, the others are dropped. The alternative
+/// is a search per dimension with the drill downs of the other dimensions
+/// as filters, which is cheaper when the base query matches many docs and
+/// the drill downs are selective, but costs `N + 1` searches for `N`
+/// dimensions.
+#[derive(Default)]
+pub struct DrillSideways {
+    dims: Vec<DrillDownDim>,
+}
+
+impl DrillSideways {
+    pub fn new() -> DrillSideways {
+        DrillSideways::default()
+    }
+
+    /// Restricts the hits to the docs with `value` in the `dim` field, or any
+    /// other value previously added to this dimension.
+    pub fn add(&mut self, dim: &str, value: &[u8]) {
+        if let Some(drill_down) = self.dims.iter_mut().find(|d| d.dim == dim) {
+            drill_down.values.push(value.to_vec());
+            return;
+        }
+        self.dims.push(DrillDownDim {
+            dim: dim.to_string(),
+            values: vec![value.to_vec()],
+        });
+    }
+
+    /// Searches the `top_n` drill down hits of `query` and collects the
+    /// facets of all the dimensions.
+    pub fn search<C, S>(
+        &self,
+        searcher: &S,
+        query: &dyn Query<C>,
+        top_n: usize,
+    ) -> Result<DrillSidewaysResult>
+    where
+        C: Codec,
+        S: IndexSearcher<C>,
+    {
+        let mut collector = DrillSidewaysCollector {
+            dims: &self.dims,
+            top_docs: TopDocsCollector::new(top_n),
+            drill_down: FacetsCollector::new(),
+            sideways: self.dims.iter().map(|_| FacetsCollector::new()).collect(),
+            segment_dims: vec![],
+        };
+        searcher.search(query, &mut collector)?;
+
+        Ok(DrillSidewaysResult {
+            top_docs: collector.top_docs.top_docs(),
+            drill_down: collector.drill_down,
+            sideways: self
+                .dims
+                .iter()
+                .map(|d| d.dim.clone())
+                .zip(collector.sideways)
+                .collect(),
+        })
+    }
+}
+
+/// The result of a `DrillSideways` search.
+pub struct DrillSidewaysResult {
+    /// The top hits matching all the drill downs.
+    pub top_docs: TopDocs,
+    /// The hits matching all the drill downs.
+    pub drill_down: FacetsCollector,
+    /// The hits matching the drill downs of all the dimensions but one, by
+    /// dimension.
+    pub sideways: Vec<(String, FacetsCollector)>,
+}
+
+impl DrillSidewaysResult {
+    /// Returns the hits to count the facets of `dim` on: its sideways hits
+    /// if it was drilled down, else the drill down hits.
+    pub fn facets(&self, dim: &str) -> &FacetsCollector {
+        self.sideways
+            .iter()
+            .find(|s| s.0 == dim)
+            .map_or(&self.drill_down, |s| &s.1)
+    }
+
+    /// Counts the values of the `dim` field, `leaves` must be the leaves of
+    /// the searched reader.
+    pub fn facet_counts<C: Codec>(
+        &self,
+        dim: &str,
+        leaves: &[LeafReaderContext<'_, C>],
+    ) -> Result<SortedSetDocValuesFacetCounts> {
+        SortedSetDocValuesFacetCounts::new(dim.to_string(), leaves, self.facets(dim))
+    }
+}
+
+struct DrillSidewaysCollector<'a> {
+    dims: &'a [DrillDownDim],
+    top_docs: TopDocsCollector,
+    drill_down: FacetsCollector,
+    // the sideways hits of each dim, which include the drill down hits
+    sideways: Vec<FacetsCollector>,
+    // the doc values of each dim in the current segment and the ords of its
+    // values, `None` if no doc of the segment can match the dim
+    segment_dims: Vec<Option<(SortedSetDocValuesRef, Vec<i64>)>>,
+}
+
+impl<'a> DrillSidewaysCollector<'a> {
+    fn matches_dim(&self, index: usize, doc: DocId) -> Result<bool> {
+        if let Some((ref doc_values, ref ords)) = self.segment_dims[index] {
+            let mut ctx = doc_values.set_document(doc)?;
+            loop {
+                let ord = doc_values.next_ord(&mut ctx)?;
+                if ord == NO_MORE_ORDS {
+                    break;
+                }
+                if ords.contains(&ord) {
+                    return Ok(true);
+                }
+            }
+        }
+        Ok(false)
+    }
+}
+
+impl<'a> SearchCollector for DrillSidewaysCollector<'a> {
+    type LC = NoParallelLeafCollector;
+
+    fn set_next_reader<C: Codec>(&mut self, reader: &LeafReaderContext<'_, C>) -> Result<()> {
+        self.top_docs.set_next_reader(reader)?;
+        self.drill_down.set_next_reader(reader)?;
+        for sideways in &mut self.sideways {
+            sideways.set_next_reader(reader)?;
+        }
+
+        self.segment_dims.clear();
+        for drill_down in self.dims {
+            let segment_dim = match reader.reader.field_info(&drill_down.dim) {
+                Some(field_info) if field_info.doc_values_type == DocValuesType::SortedSet => {
+                    let doc_values = reader.reader.get_sorted_set_doc_values(&drill_down.dim)?;
+                    let mut ords = Vec::with_capacity(drill_down.values.len());
+                    for value in &drill_down.values {
+                        let ord = doc_values.lookup_term(value)?;
+                        if ord >= 0 {
+                            ords.push(ord);
+                        }
+                    }
+                    if ords.is_empty() {
+                        None
+                    } else {
+                        Some((doc_values, ords))
+                    }
+                }
+                _ => None,
+            };
+            self.segment_dims.push(segment_dim);
+        }
+        Ok(())
+    }
+
+    fn support_parallel(&self) -> bool {
+        false
+    }
+
+    fn leaf_collector<C: Codec>(
+        &mut self,
+        _reader: &LeafReaderContext<'_, C>,
+    ) -> Result<NoParallelLeafCollector> {
+        bail!(IllegalState(
+            "DrillSidewaysCollector doesn't support parallel collection".into()
+        ))
+    }
+
+    fn finish_parallel(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> Collector for DrillSidewaysCollector<'a> {
+    fn needs_scores(&self) -> bool {
+        true
+    }
+
+    fn collect<S: Scorer + ?Sized>(&mut self, doc: DocId, scorer: &mut S) -> Result<()> {
+        let mut missed_dim = None;
+        for i in 0..self.dims.len() {
+            if !self.matches_dim(i, doc)? {
+                if missed_dim.is_some() {
+                    // misses two dims, neither a drill down nor a sideways hit
+                    return Ok(());
+                }
+                missed_dim = Some(i);
+            }
+        }
+
+        match missed_dim {
+            Some(i) => self.sideways[i].collect(doc, scorer),
+            None => {
+                self.top_docs.collect(doc, scorer)?;
+                self.drill_down.collect(doc, scorer)?;
+                for sideways in &mut self.sideways {
+                    sideways.collect(doc, scorer)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::doc::SortedSetDocValuesField;
+    use core::index::{Fieldable, IndexReader, IndexWriter, IndexWriterConfig};
+    use core::search::facets::LabelAndValue;
+    use core::search::match_all::MatchAllDocsQuery;
+    use core::search::searcher::DefaultIndexSearcher;
+    use core::store::RAMDirectory;
+
+    use std::sync::Arc;
+
+    fn label(label: &str, value: usize) -> LabelAndValue {
+        LabelAndValue::new(label.as_bytes().to_vec(), value)
+    }
+
+    #[test]
+    fn test_drill_sideways() {
+        let dir = Arc::new(RAMDirectory::default());
+        let writer = IndexWriter::new(dir, Arc::new(IndexWriterConfig::default())).unwrap();
+        let docs = [
+            ("red", "L", "a"),
+            ("red", "M", "b"),
+            ("blue", "L", "a"),
+            ("blue", "M", "a"),
+            ("red", "L", "b"),
+            ("green", "S", ""),
+        ];
+        for &(color, size, brand) in &docs {
+            let mut doc: Vec<Box<dyn Fieldable>> = vec![
+                Box::new(SortedSetDocValuesField::new("color", color.as_bytes())),
+                Box::new(SortedSetDocValuesField::new("size", size.as_bytes())),
+            ];
+            if !brand.is_empty() {
+                doc.push(Box::new(SortedSetDocValuesField::new("brand", brand.as_bytes())));
+            }
+            writer.add_document(doc).unwrap();
+        }
+        let reader = Arc::new(writer.get_reader(true, false).unwrap());
+        let leaves = reader.leaves();
+        let searcher = DefaultIndexSearcher::new(Arc::clone(&reader));
+        let query = MatchAllDocsQuery::new();
+
+        let mut drill_sideways = DrillSideways::new();
+        drill_sideways.add("color", b"red");
+        drill_sideways.add("size", b"L");
+        let result = drill_sideways.search(&searcher, &query, 10).unwrap();
+
+        let mut hits: Vec<DocId> = result
+            .top_docs
+            .score_docs()
+            .iter()
+            .map(|d| d.doc_id())
+            .collect();
+        hits.sort();
+        assert_eq!(hits, vec![0, 4]);
+
+        // the colors of the docs of size L
+        let colors = result.facet_counts("color", &leaves).unwrap();
+        assert_eq!(colors.top_children(10), vec![label("red", 2), label("blue", 1)]);
+        // the sizes of the red docs
+        let sizes = result.facet_counts("size", &leaves).unwrap();
+        assert_eq!(sizes.top_children(10), vec![label("L", 2), label("M", 1)]);
+        // a dimension without drill down is counted on the drill down hits
+        let brands = result.facet_counts("brand", &leaves).unwrap();
+        assert_eq!(brands.top_children(10), vec![label("a", 1), label("b", 1)]);
+
+        // the values of a dimension are OR'ed
+        drill_sideways.add("color", b"blue");
+        let result = drill_sideways.search(&searcher, &query, 10).unwrap();
+        assert_eq!(result.top_docs.total_hits(), 3);
+        let sizes = result.facet_counts("size", &leaves).unwrap();
+        assert_eq!(sizes.top_children(10), vec![label("L", 3), label("M", 2)]);
+
+        writer.close().unwrap();
+    }
+}
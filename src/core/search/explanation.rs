@@ -11,7 +11,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Explanation {
     is_match: bool,
     value: f32,
@@ -75,17 +75,167 @@ impl Explanation {
 
         return buffer;
     }
+}
 
-    pub fn clone(&self) -> Explanation {
-        let mut details: Vec<Explanation> = vec![];
-        for detail in &self.details {
-            details.push(detail.clone());
+/// The difference between two explanations of the score of the same doc, e.g.
+/// for two versions of a query.
+///
+/// The details of both explanations are aligned by description, a detail
+/// which has no counterpart with the same description in the other
+/// explanation only has a value on its side.
+#[derive(Serialize, Deserialize)]
+pub struct ExplanationDiff {
+    description: String,
+    left: Option<f32>,
+    right: Option<f32>,
+    details: Vec<ExplanationDiff>,
+}
+
+impl ExplanationDiff {
+    pub fn new(left: &Explanation, right: &Explanation) -> ExplanationDiff {
+        let description = if left.description == right.description {
+            left.description()
+        } else {
+            format!("{} <> {}", left.description, right.description)
+        };
+        ExplanationDiff {
+            description,
+            left: Some(left.value),
+            right: Some(right.value),
+            details: Self::diff_details(&left.details, &right.details),
         }
-        Explanation {
-            is_match: self.is_match,
-            value: self.value(),
-            description: self.description(),
-            details,
+    }
+
+    fn one_side(explanation: &Explanation, is_left: bool) -> ExplanationDiff {
+        let value = Some(explanation.value);
+        ExplanationDiff {
+            description: explanation.description(),
+            left: if is_left { value } else { None },
+            right: if is_left { None } else { value },
+            details: explanation
+                .details
+                .iter()
+                .map(|d| Self::one_side(d, is_left))
+                .collect(),
+        }
+    }
+
+    fn diff_details(left: &[Explanation], right: &[Explanation]) -> Vec<ExplanationDiff> {
+        let mut right_used = vec![false; right.len()];
+        let mut details = Vec::with_capacity(left.len().max(right.len()));
+        for l in left {
+            let matched = right
+                .iter()
+                .enumerate()
+                .position(|(i, r)| !right_used[i] && r.description == l.description);
+            if let Some(i) = matched {
+                right_used[i] = true;
+                details.push(ExplanationDiff::new(l, &right[i]));
+            } else {
+                details.push(Self::one_side(l, true));
+            }
+        }
+        for (r, used) in right.iter().zip(right_used) {
+            if !used {
+                details.push(Self::one_side(r, false));
+            }
+        }
+        details
+    }
+
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    /// The value in the first explanation, `None` if it has no such component.
+    pub fn left(&self) -> Option<f32> {
+        self.left
+    }
+
+    /// The value in the second explanation, `None` if it has no such component.
+    pub fn right(&self) -> Option<f32> {
+        self.right
+    }
+
+    pub fn details(&self) -> &[ExplanationDiff] {
+        &self.details
+    }
+
+    /// Whether this component or any of its sub components differs between
+    /// the two explanations.
+    pub fn is_different(&self) -> bool {
+        self.left != self.right || self.details.iter().any(|d| d.is_different())
+    }
+
+    /// Renders the diff as a tree like `Explanation::to_string`, the lines of
+    /// the components whose value differs start with a `*`.
+    pub fn to_string(&self, depth: i32) -> String {
+        let mut buffer = String::from("");
+
+        buffer.push_str(if self.left != self.right { "* " } else { "  " });
+        for _i in 0..depth {
+            buffer.push_str("  ");
         }
+
+        let value = |v: Option<f32>| v.map_or_else(|| "-".to_string(), |v| v.to_string());
+        buffer.push_str(&format!(
+            "{} | {} = {}\n",
+            value(self.left),
+            value(self.right),
+            self.description
+        ));
+
+        for detail in &self.details {
+            buffer.push_str(&detail.to_string(depth + 1))
+        }
+
+        buffer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expl(value: f32, description: &str, details: Vec<Explanation>) -> Explanation {
+        Explanation::new(true, value, description.to_string(), details)
+    }
+
+    #[test]
+    fn test_diff_aligns_details_by_description() {
+        let left = expl(
+            3.0,
+            "sum of:",
+            vec![expl(1.0, "weight(title:a)", vec![]), expl(2.0, "weight(body:b)", vec![])],
+        );
+        let right = expl(
+            4.5,
+            "sum of:",
+            vec![expl(2.5, "weight(body:b)", vec![]), expl(2.0, "weight(body:c)", vec![])],
+        );
+
+        let diff = ExplanationDiff::new(&left, &right);
+        assert!(diff.is_different());
+        assert_eq!(diff.description(), "sum of:");
+        assert_eq!(diff.details().len(), 3);
+
+        let details = diff.details();
+        assert_eq!(details[0].description(), "weight(title:a)");
+        assert_eq!((details[0].left(), details[0].right()), (Some(1.0), None));
+        assert_eq!(details[1].description(), "weight(body:b)");
+        assert_eq!((details[1].left(), details[1].right()), (Some(2.0), Some(2.5)));
+        assert_eq!(details[2].description(), "weight(body:c)");
+        assert_eq!((details[2].left(), details[2].right()), (None, Some(2.0)));
+    }
+
+    #[test]
+    fn test_same_explanations() {
+        let left = expl(1.0, "sum of:", vec![expl(1.0, "weight(title:a)", vec![])]);
+        let diff = ExplanationDiff::new(&left, &left.clone());
+        assert!(!diff.is_different());
+        assert_eq!(
+            diff.to_string(0),
+            "  1 | 1 = sum of:\n    1 | 1 = weight(title:a)\n"
+        );
     }
 }
@@ -0,0 +1,320 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+
+use core::codec::Codec;
+use core::index::{
+    DocValuesType, LeafReaderContext, OrdinalMap, SortedSetDocValues, SortedSetDocValuesRef,
+    NO_MORE_ORDS,
+};
+use core::search::collector::{FacetsCollector, MatchingDocs};
+use error::ErrorKind::IllegalArgument;
+use error::Result;
+
+/// A facet label with its count.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LabelAndValue {
+    pub label: Vec<u8>,
+    pub value: usize,
+}
+
+impl LabelAndValue {
+    pub fn new(label: Vec<u8>, value: usize) -> LabelAndValue {
+        LabelAndValue { label, value }
+    }
+}
+
+/// Counts the values of a `SORTED_SET` doc values field over the docs
+/// recorded by a `FacetsCollector`, each value of a doc being counted once.
+///
+/// Ords are counted per segment, then the counts are merged either by label,
+/// or by global ord with an `OrdinalMap` of the field built once per reader,
+/// so that each label with a count is only looked up once.
+pub struct SortedSetDocValuesFacetCounts {
+    // the labels with a count, in increasing order
+    counts: Vec<LabelAndValue>,
+}
+
+impl SortedSetDocValuesFacetCounts {
+    /// Counts the values of `field` for the matching docs of `collector`,
+    /// `leaves` must be the leaves of the searched reader.
+    ///
+    /// The counts of the segments are merged by label, the labels with a
+    /// count being looked up in each segment they occur in.
+    pub fn new<C: Codec>(
+        field: String,
+        leaves: &[LeafReaderContext<'_, C>],
+        collector: &FacetsCollector,
+    ) -> Result<SortedSetDocValuesFacetCounts> {
+        Self::check_leaves(leaves, collector)?;
+        let doc_values = Self::segment_doc_values(&field, leaves)?;
+        let mut label_counts = BTreeMap::new();
+        for matching_docs in collector.matching_docs() {
+            if matching_docs.docs.is_empty() {
+                continue;
+            }
+            if let Some(ref doc_values) = doc_values[matching_docs.ord] {
+                let ord_counts = Self::count_segment(doc_values.as_ref(), matching_docs)?;
+                for (ord, &count) in ord_counts.iter().enumerate() {
+                    if count > 0 {
+                        let label = doc_values.lookup_ord(ord as i64)?;
+                        *label_counts.entry(label).or_insert(0) += count;
+                    }
+                }
+            }
+        }
+        let counts = label_counts
+            .into_iter()
+            .map(|(label, count)| LabelAndValue::new(label, count))
+            .collect();
+        Ok(SortedSetDocValuesFacetCounts { counts })
+    }
+
+    /// Like `new`, with the `OrdinalMap` of `field` over `leaves`, the counts
+    /// of the segments being merged by global ord.
+    ///
+    /// Building the map reads the whole term dictionary of `field` in every
+    /// segment, so it should be built once per reader by
+    /// `OrdinalMap::build_sorted_set` and shared by the facet calls on that
+    /// reader, which then look up each label with a count only once.
+    pub fn with_ordinal_map<C: Codec>(
+        field: &str,
+        leaves: &[LeafReaderContext<'_, C>],
+        ordinal_map: &OrdinalMap,
+        collector: &FacetsCollector,
+    ) -> Result<SortedSetDocValuesFacetCounts> {
+        if ordinal_map.segment_count() != leaves.len() {
+            bail!(IllegalArgument(format!(
+                "the ordinal map is over {} segments, not the {} leaves",
+                ordinal_map.segment_count(),
+                leaves.len()
+            )));
+        }
+        Self::check_leaves(leaves, collector)?;
+        let doc_values = Self::segment_doc_values(field, leaves)?;
+
+        let mut global_counts = vec![0usize; ordinal_map.value_count() as usize];
+        for matching_docs in collector.matching_docs() {
+            if matching_docs.docs.is_empty() {
+                continue;
+            }
+            if let Some(ref doc_values) = doc_values[matching_docs.ord] {
+                let ord_counts = Self::count_segment(doc_values.as_ref(), matching_docs)?;
+                let global_ords = ordinal_map.get_global_ords(matching_docs.ord);
+                for (ord, &count) in ord_counts.iter().enumerate() {
+                    if count > 0 {
+                        global_counts[global_ords.get64(ord as i64)? as usize] += count;
+                    }
+                }
+            }
+        }
+
+        let mut counts = vec![];
+        for (global_ord, &count) in global_counts.iter().enumerate() {
+            if count > 0 {
+                let global_ord = global_ord as i64;
+                let segment = ordinal_map.first_segment_number(global_ord) as usize;
+                let segment_ord = ordinal_map.first_segment_ord(global_ord);
+                let label = doc_values[segment].as_ref().unwrap().lookup_ord(segment_ord)?;
+                counts.push(LabelAndValue::new(label, count));
+            }
+        }
+        Ok(SortedSetDocValuesFacetCounts { counts })
+    }
+
+    // fails if `collector` recorded docs of a segment missing from `leaves`,
+    // as when it collected the docs of another reader
+    fn check_leaves<C: Codec>(
+        leaves: &[LeafReaderContext<'_, C>],
+        collector: &FacetsCollector,
+    ) -> Result<()> {
+        for matching_docs in collector.matching_docs() {
+            if matching_docs.ord >= leaves.len() {
+                bail!(IllegalArgument(format!(
+                    "the docs of segment {} were collected, but there are only {} leaves",
+                    matching_docs.ord,
+                    leaves.len()
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    // the doc values of `field` of each leaf, `None` if it has none
+    fn segment_doc_values<C: Codec>(
+        field: &str,
+        leaves: &[LeafReaderContext<'_, C>],
+    ) -> Result<Vec<Option<SortedSetDocValuesRef>>> {
+        let mut doc_values = Vec::with_capacity(leaves.len());
+        for leaf in leaves {
+            doc_values.push(match leaf.reader.field_info(field) {
+                Some(fi) if fi.doc_values_type == DocValuesType::SortedSet => {
+                    Some(leaf.reader.get_sorted_set_doc_values(field)?)
+                }
+                _ => None,
+            });
+        }
+        Ok(doc_values)
+    }
+
+    fn count_segment(
+        doc_values: &dyn SortedSetDocValues,
+        matching_docs: &MatchingDocs,
+    ) -> Result<Vec<usize>> {
+        let mut ord_counts = vec![0usize; doc_values.get_value_count()];
+        if let Some(ords) = doc_values.random_access_ords() {
+            for &doc in &matching_docs.docs {
+                let ctx = ords.set_document(doc)?;
+                for i in 0..ords.cardinality(&ctx) {
+                    ord_counts[ords.ord_at(&ctx, i)? as usize] += 1;
+                }
+            }
+        } else {
+            for &doc in &matching_docs.docs {
+                let mut ctx = doc_values.set_document(doc)?;
+                loop {
+                    let ord = doc_values.next_ord(&mut ctx)?;
+                    if ord == NO_MORE_ORDS {
+                        break;
+                    }
+                    ord_counts[ord as usize] += 1;
+                }
+            }
+        }
+        Ok(ord_counts)
+    }
+
+    /// Returns the count of `label`, 0 if no matching doc has it.
+    pub fn specific_value(&self, label: &[u8]) -> usize {
+        match self.counts.binary_search_by(|c| c.label.as_slice().cmp(label)) {
+            Ok(i) => self.counts[i].value,
+            Err(_) => 0,
+        }
+    }
+
+    /// Returns the `top_n` labels with the highest counts, by descending
+    /// count then ascending label.
+    pub fn top_children(&self, top_n: usize) -> Vec<LabelAndValue> {
+        let mut children = self.counts.clone();
+        children.sort_by(|a, b| match b.value.cmp(&a.value) {
+            Ordering::Equal => a.label.cmp(&b.label),
+            ord => ord,
+        });
+        children.truncate(top_n);
+        children
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::doc::{NumericDocValuesField, SortedSetDocValuesField};
+    use core::index::{Fieldable, IndexReader, IndexWriter, IndexWriterConfig};
+    use core::search::collector::top_docs::TopDocsCollector;
+    use core::search::collector::ChainedCollector;
+    use core::search::doc_values_range_query::DocValuesRangeQuery;
+    use core::search::searcher::{DefaultIndexSearcher, IndexSearcher};
+    use core::store::RAMDirectory;
+    use error::{Error, ErrorKind};
+
+    use std::sync::Arc;
+
+    #[test]
+    fn test_sorted_set_facet_counts() {
+        let dir = Arc::new(RAMDirectory::default());
+        let writer = IndexWriter::new(dir, Arc::new(IndexWriterConfig::default())).unwrap();
+        let docs: [&[&str]; 6] = [
+            &["book", "sale"],
+            &["music"],
+            &["book"],
+            &["book", "music", "sale"],
+            &[],
+            &["video"],
+        ];
+        for (i, categories) in docs.iter().enumerate() {
+            let mut doc: Vec<Box<dyn Fieldable>> =
+                vec![Box::new(NumericDocValuesField::new("price", i as i64))];
+            for category in categories.iter() {
+                doc.push(Box::new(SortedSetDocValuesField::new(
+                    "category",
+                    category.as_bytes(),
+                )));
+            }
+            writer.add_document(doc).unwrap();
+            // two segments, whose ords differ
+            if i == 2 {
+                writer.commit().unwrap();
+            }
+        }
+        let reader = Arc::new(writer.get_reader(true, false).unwrap());
+        let leaves = reader.leaves();
+        assert_eq!(leaves.len(), 2);
+
+        // all the docs but the video one
+        let query = DocValuesRangeQuery::new("price".into(), 0, 4);
+        let mut top_collector = TopDocsCollector::new(10);
+        let mut facets_collector = FacetsCollector::new();
+        {
+            let searcher = DefaultIndexSearcher::new(Arc::clone(&reader));
+            let mut collector = ChainedCollector::new(&mut top_collector, &mut facets_collector);
+            searcher.search(&query, &mut collector).unwrap();
+
+            let facet_counts =
+                SortedSetDocValuesFacetCounts::new("category".into(), &leaves, &facets_collector)
+                    .unwrap();
+            assert_eq!(
+                facet_counts.top_children(2),
+                vec![
+                    LabelAndValue::new(b"book".to_vec(), 3),
+                    LabelAndValue::new(b"music".to_vec(), 2),
+                ]
+            );
+            assert_eq!(facet_counts.top_children(10).len(), 3);
+            assert_eq!(facet_counts.specific_value(b"sale"), 2);
+            assert_eq!(facet_counts.specific_value(b"video"), 0);
+
+            // the union of the terms of the segments
+            let ordinal_map = OrdinalMap::build_sorted_set(&leaves, "category").unwrap();
+            assert_eq!(ordinal_map.value_count(), 4);
+            // the ords of the second segment: book, music, sale, video
+            let global_ords: Vec<i64> = (0..4)
+                .map(|ord| ordinal_map.global_ord(1, ord).unwrap())
+                .collect();
+            assert_eq!(global_ords, vec![0, 1, 2, 3]);
+            // and of the first one: book, music, sale
+            assert_eq!(ordinal_map.global_ord(0, 2).unwrap(), 2);
+            let reused = SortedSetDocValuesFacetCounts::with_ordinal_map(
+                "category",
+                &leaves,
+                &ordinal_map,
+                &facets_collector,
+            )
+            .unwrap();
+            assert_eq!(reused.top_children(10), facet_counts.top_children(10));
+
+            // the leaves of another reader are rejected
+            match SortedSetDocValuesFacetCounts::new(
+                "category".into(),
+                &leaves[..1],
+                &facets_collector,
+            ) {
+                Err(Error(ErrorKind::IllegalArgument(_), _)) => {}
+                _ => panic!("the leaves should be rejected"),
+            }
+        }
+        assert_eq!(top_collector.top_docs().total_hits(), 5);
+        writer.close().unwrap();
+    }
+}
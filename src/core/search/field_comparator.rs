@@ -12,14 +12,16 @@
 // limitations under the License.
 
 use core::index::{LeafReaderContext, NumericDocValuesRef, SearchLeafReader};
-use core::search::sort_field::{SortFieldType, SortedWrapperDocValuesSource};
+use core::index::{OrdinalMap, SortedSetDocValuesRef, NO_MORE_ORDS};
+use core::search::sort_field::{SortFieldType, SortedSetSelectorType, SortedWrapperDocValuesSource};
 use core::util::bits::BitsRef;
-use core::util::{DocId, VariantValue};
-use error::Result;
+use core::util::{DocId, LongValues, VariantValue};
+use error::{ErrorKind::IllegalState, Result};
 
 use core::codec::Codec;
 use std::cmp::Ordering;
 use std::fmt;
+use std::sync::Arc;
 
 #[derive(Copy, Clone, Debug)]
 pub enum ComparatorValue {
@@ -122,6 +124,7 @@ pub enum FieldComparatorEnum {
     Doc(DocComparator),
     NumericDV(NumericDocValuesComparator<DefaultDocValuesSource>),
     SortedNumericDV(NumericDocValuesComparator<SortedWrapperDocValuesSource>),
+    TermOrdVal(TermOrdValComparator),
 }
 
 impl FieldComparator for FieldComparatorEnum {
@@ -131,6 +134,7 @@ impl FieldComparator for FieldComparatorEnum {
             FieldComparatorEnum::Doc(c) => c.compare(slot1, slot2),
             FieldComparatorEnum::NumericDV(c) => c.compare(slot1, slot2),
             FieldComparatorEnum::SortedNumericDV(c) => c.compare(slot1, slot2),
+            FieldComparatorEnum::TermOrdVal(c) => c.compare(slot1, slot2),
         }
     }
 
@@ -140,6 +144,7 @@ impl FieldComparator for FieldComparatorEnum {
             FieldComparatorEnum::Doc(c) => c.value(slot),
             FieldComparatorEnum::NumericDV(c) => c.value(slot),
             FieldComparatorEnum::SortedNumericDV(c) => c.value(slot),
+            FieldComparatorEnum::TermOrdVal(c) => c.value(slot),
         }
     }
 
@@ -149,6 +154,7 @@ impl FieldComparator for FieldComparatorEnum {
             FieldComparatorEnum::Doc(c) => c.set_bottom(slot),
             FieldComparatorEnum::NumericDV(c) => c.set_bottom(slot),
             FieldComparatorEnum::SortedNumericDV(c) => c.set_bottom(slot),
+            FieldComparatorEnum::TermOrdVal(c) => c.set_bottom(slot),
         }
     }
 
@@ -158,6 +164,7 @@ impl FieldComparator for FieldComparatorEnum {
             FieldComparatorEnum::Doc(c) => c.compare_bottom(value),
             FieldComparatorEnum::NumericDV(c) => c.compare_bottom(value),
             FieldComparatorEnum::SortedNumericDV(c) => c.compare_bottom(value),
+            FieldComparatorEnum::TermOrdVal(c) => c.compare_bottom(value),
         }
     }
 
@@ -167,6 +174,7 @@ impl FieldComparator for FieldComparatorEnum {
             FieldComparatorEnum::Doc(c) => c.copy(slot, value),
             FieldComparatorEnum::NumericDV(c) => c.copy(slot, value),
             FieldComparatorEnum::SortedNumericDV(c) => c.copy(slot, value),
+            FieldComparatorEnum::TermOrdVal(c) => c.copy(slot, value),
         }
     }
 
@@ -179,6 +187,7 @@ impl FieldComparator for FieldComparatorEnum {
             FieldComparatorEnum::Doc(c) => c.get_information_from_reader(reader),
             FieldComparatorEnum::NumericDV(c) => c.get_information_from_reader(reader),
             FieldComparatorEnum::SortedNumericDV(c) => c.get_information_from_reader(reader),
+            FieldComparatorEnum::TermOrdVal(c) => c.get_information_from_reader(reader),
         }
     }
 
@@ -188,6 +197,7 @@ impl FieldComparator for FieldComparatorEnum {
             FieldComparatorEnum::Doc(c) => c.get_type(),
             FieldComparatorEnum::NumericDV(c) => c.get_type(),
             FieldComparatorEnum::SortedNumericDV(c) => c.get_type(),
+            FieldComparatorEnum::TermOrdVal(c) => c.get_type(),
         }
     }
 }
@@ -199,6 +209,7 @@ impl fmt::Display for FieldComparatorEnum {
             FieldComparatorEnum::Doc(c) => write!(f, "FieldComparatorEnum({})", c),
             FieldComparatorEnum::NumericDV(c) => write!(f, "FieldComparatorEnum({})", c),
             FieldComparatorEnum::SortedNumericDV(c) => write!(f, "FieldComparatorEnum({})", c),
+            FieldComparatorEnum::TermOrdVal(c) => write!(f, "FieldComparatorEnum({})", c),
         }
     }
 }
@@ -302,7 +313,7 @@ impl FieldComparator for DocComparator {
 
     fn compare_bottom(&self, value: ComparatorValue) -> Result<Ordering> {
         debug_assert!(value.is_doc());
-        Ok(self.bottom.cmp(&value.doc()))
+        Ok(self.bottom.cmp(&(value.doc() + self.doc_base)))
     }
 
     fn copy(&mut self, slot: usize, value: ComparatorValue) -> Result<()> {
@@ -402,7 +413,7 @@ impl<T: DocValuesSource> FieldComparator for NumericDocValuesComparator<T> {
         let doc_id = value.doc();
         let value = self.get_doc_value(doc_id)?;
         if let Some(ref bits) = self.docs_with_fields {
-            if value.is_zero() && bits.get(doc_id as usize)? {
+            if value.is_zero() && !bits.get(doc_id as usize)? {
                 return Ok(self.bottom.cmp(self.missing_value.as_ref().unwrap()));
             }
         }
@@ -414,7 +425,7 @@ impl<T: DocValuesSource> FieldComparator for NumericDocValuesComparator<T> {
         let doc_id = value.doc();
         let mut value = self.get_doc_value(doc_id)?;
         if let Some(ref bits) = self.docs_with_fields {
-            if value.is_zero() && bits.get(doc_id as usize)? {
+            if value.is_zero() && !bits.get(doc_id as usize)? {
                 value = self.missing_value.as_ref().unwrap().clone();
             }
         }
@@ -455,6 +466,269 @@ impl<T: DocValuesSource> fmt::Display for NumericDocValuesComparator<T> {
     }
 }
 
+/// Sorts by the terms of a `SortedSetDocValues` field, one term of each doc
+/// being selected by a `SortedSetSelectorType`.
+///
+/// The hits of a segment are compared by ordinal. The term of each hit is
+/// kept too, so that the hits of different segments are compared by term, and
+/// the ordinal of the bottom is looked up again in each new segment. With the
+/// `OrdinalMap` of the field, all the hits are compared by global ordinal.
+pub struct TermOrdValComparator {
+    field: String,
+    selector: SortedSetSelectorType,
+    // the ordinal and term of the hit in each slot, and the generation of the
+    // segment the ordinal is from. The term is `None` for the docs without value
+    ords: Vec<i64>,
+    values: Vec<Option<Vec<u8>>>,
+    read_gens: Vec<usize>,
+    current_read_gen: usize,
+    doc_values: Option<SortedSetDocValuesRef>,
+    bottom_slot: Option<usize>,
+    // the ordinal of the bottom in the current segment, or of the greatest term
+    // less than the bottom if `bottom_same_reader` is false. `None` if the term
+    // of the bottom couldn't be looked up, the hits are then compared by term
+    bottom_ord: Option<i64>,
+    bottom_same_reader: bool,
+    missing_last: bool,
+    missing_ord: i64,
+    ordinal_map: Option<Arc<OrdinalMap>>,
+    // the global ordinals of the ordinals of the current segment
+    global_ords: Option<Arc<dyn LongValues>>,
+}
+
+impl TermOrdValComparator {
+    pub fn new(
+        num_hits: usize,
+        field: String,
+        selector: SortedSetSelectorType,
+        missing_last: bool,
+    ) -> TermOrdValComparator {
+        TermOrdValComparator {
+            field,
+            selector,
+            ords: vec![0; num_hits],
+            values: vec![None; num_hits],
+            read_gens: vec![0; num_hits],
+            current_read_gen: 0,
+            doc_values: None,
+            bottom_slot: None,
+            bottom_ord: None,
+            bottom_same_reader: false,
+            missing_last,
+            missing_ord: if missing_last { i64::max_value() } else { -1 },
+            ordinal_map: None,
+            global_ords: None,
+        }
+    }
+
+    /// Compares the hits by their global ordinals in `ordinal_map`, which must
+    /// be the map of the field over the leaves of the searched reader.
+    pub fn with_ordinal_map(mut self, ordinal_map: Arc<OrdinalMap>) -> TermOrdValComparator {
+        self.ordinal_map = Some(ordinal_map);
+        self
+    }
+
+    /// Maps an ordinal of the current segment to its global ordinal, if there
+    /// is an ordinal map.
+    fn global_ord(&self, segment_ord: i64) -> Result<i64> {
+        match self.global_ords {
+            Some(ref global_ords) if segment_ord != self.missing_ord => {
+                global_ords.get64(segment_ord)
+            }
+            _ => Ok(segment_ord),
+        }
+    }
+
+    /// Returns the ordinal selected for the doc in the current segment,
+    /// `missing_ord` if it has none.
+    fn segment_ord(&self, doc: DocId) -> Result<i64> {
+        let doc_values = self.doc_values.as_ref().unwrap();
+        let mut ctx = doc_values.set_document(doc)?;
+        let ord = match self.selector {
+            SortedSetSelectorType::Min => doc_values.next_ord(&mut ctx)?,
+            SortedSetSelectorType::Max => {
+                let mut max = NO_MORE_ORDS;
+                loop {
+                    let ord = doc_values.next_ord(&mut ctx)?;
+                    if ord == NO_MORE_ORDS {
+                        break;
+                    }
+                    max = ord;
+                }
+                max
+            }
+            SortedSetSelectorType::MiddleMin | SortedSetSelectorType::MiddleMax => {
+                let mut ords = Vec::new();
+                loop {
+                    let ord = doc_values.next_ord(&mut ctx)?;
+                    if ord == NO_MORE_ORDS {
+                        break;
+                    }
+                    ords.push(ord);
+                }
+                if ords.is_empty() {
+                    NO_MORE_ORDS
+                } else if self.selector == SortedSetSelectorType::MiddleMin {
+                    ords[(ords.len() - 1) / 2]
+                } else {
+                    ords[ords.len() / 2]
+                }
+            }
+        };
+        Ok(if ord == NO_MORE_ORDS {
+            self.missing_ord
+        } else {
+            ord
+        })
+    }
+
+    fn compare_values(&self, value1: &Option<Vec<u8>>, value2: &Option<Vec<u8>>) -> Ordering {
+        match (value1, value2) {
+            (Some(v1), Some(v2)) => v1.cmp(v2),
+            (None, None) => Ordering::Equal,
+            (None, Some(_)) if self.missing_last => Ordering::Greater,
+            (None, Some(_)) => Ordering::Less,
+            (Some(_), None) if self.missing_last => Ordering::Less,
+            (Some(_), None) => Ordering::Greater,
+        }
+    }
+
+    fn set_doc_values(&mut self, segment: usize, doc_values: SortedSetDocValuesRef) -> Result<()> {
+        self.doc_values = Some(doc_values);
+        if let Some(ref ordinal_map) = self.ordinal_map {
+            if segment >= ordinal_map.segment_count() {
+                bail!(IllegalState(format!(
+                    "the ordinal map of field '{}' has no segment {}",
+                    self.field, segment
+                )));
+            }
+            self.global_ords = Some(ordinal_map.get_global_ords(segment));
+        }
+        // the global ordinals of all the segments are comparable
+        if self.ordinal_map.is_none() || self.current_read_gen == 0 {
+            self.current_read_gen += 1;
+        }
+        if let Some(slot) = self.bottom_slot {
+            self.resolve_bottom(slot)?;
+        }
+        Ok(())
+    }
+
+    /// Looks up the ordinal of the bottom in the current segment.
+    fn resolve_bottom(&mut self, slot: usize) -> Result<()> {
+        self.bottom_ord = None;
+        if self.read_gens[slot] == self.current_read_gen {
+            self.bottom_ord = Some(self.ords[slot]);
+            self.bottom_same_reader = true;
+            return Ok(());
+        }
+
+        let index = match self.values[slot] {
+            Some(ref value) => match self.doc_values {
+                Some(ref doc_values) => Some(doc_values.lookup_term(value)?),
+                None => bail!(IllegalState(format!(
+                    "no doc values of field '{}' to look up the bottom in",
+                    self.field
+                ))),
+            },
+            None => None,
+        };
+        match index {
+            Some(index) if index < 0 => {
+                // not in this segment, the term would have the ordinal `-index - 1`
+                self.bottom_ord = Some(-index - 2);
+                self.bottom_same_reader = false;
+            }
+            _ => {
+                let ord = index.unwrap_or(self.missing_ord);
+                self.bottom_ord = Some(ord);
+                self.bottom_same_reader = true;
+                self.ords[slot] = ord;
+                self.read_gens[slot] = self.current_read_gen;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FieldComparator for TermOrdValComparator {
+    fn compare(&self, slot1: usize, slot2: usize) -> Ordering {
+        if self.read_gens[slot1] == self.read_gens[slot2] {
+            self.ords[slot1].cmp(&self.ords[slot2])
+        } else {
+            self.compare_values(&self.values[slot1], &self.values[slot2])
+        }
+    }
+
+    /// The term of the slot, empty for the docs without value.
+    fn value(&self, slot: usize) -> VariantValue {
+        VariantValue::Binary(self.values[slot].clone().unwrap_or_default())
+    }
+
+    fn set_bottom(&mut self, slot: usize) {
+        self.bottom_slot = Some(slot);
+        // if the term of the bottom can't be looked up, `bottom_ord` is left
+        // unset and the hits are compared to the bottom by term
+        let _ = self.resolve_bottom(slot);
+    }
+
+    fn compare_bottom(&self, value: ComparatorValue) -> Result<Ordering> {
+        debug_assert!(value.is_doc());
+        let segment_ord = self.segment_ord(value.doc())?;
+        let ord = self.global_ord(segment_ord)?;
+        match self.bottom_ord {
+            Some(bottom_ord) if self.bottom_same_reader => Ok(bottom_ord.cmp(&ord)),
+            // the bottom sorts after all the terms up to `bottom_ord`
+            Some(bottom_ord) if bottom_ord >= ord => Ok(Ordering::Greater),
+            Some(_) => Ok(Ordering::Less),
+            None => {
+                let value = if segment_ord == self.missing_ord {
+                    None
+                } else {
+                    Some(self.doc_values.as_ref().unwrap().lookup_ord(segment_ord)?)
+                };
+                let bottom_slot = self.bottom_slot.unwrap();
+                Ok(self.compare_values(&self.values[bottom_slot], &value))
+            }
+        }
+    }
+
+    fn copy(&mut self, slot: usize, value: ComparatorValue) -> Result<()> {
+        debug_assert!(value.is_doc());
+        let segment_ord = self.segment_ord(value.doc())?;
+        self.values[slot] = if segment_ord == self.missing_ord {
+            None
+        } else {
+            Some(self.doc_values.as_ref().unwrap().lookup_ord(segment_ord)?)
+        };
+        self.ords[slot] = self.global_ord(segment_ord)?;
+        self.read_gens[slot] = self.current_read_gen;
+        Ok(())
+    }
+
+    fn get_information_from_reader<C: Codec>(
+        &mut self,
+        reader: &LeafReaderContext<'_, C>,
+    ) -> Result<()> {
+        let doc_values = reader.reader.get_sorted_set_doc_values(&self.field)?;
+        self.set_doc_values(reader.ord, doc_values)
+    }
+
+    fn get_type(&self) -> SortFieldType {
+        SortFieldType::String
+    }
+}
+
+impl fmt::Display for TermOrdValComparator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "TermOrdValComparator(field: {}, selector: {:?}, bottom: {:?})",
+            self.field, self.selector, self.bottom_ord
+        )
+    }
+}
+
 pub trait DocValuesSource {
     fn numeric_doc_values<C: Codec>(
         &self,
@@ -492,8 +766,122 @@ impl DocValuesSource for DefaultDocValuesSource {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use core::codec::tests::TestCodec;
     use core::index::tests::*;
-    use core::index::IndexReader;
+    use core::index::{DocValuesTermIterator, SortedSetDocValues, SortedSetDocValuesContext};
+    use core::index::{IndexReader, NumericDocValues, NumericDocValuesContext};
+    use core::search::sort_field::{SimpleSortField, SortField, SortMissingOrder};
+    use core::util::bit_set::{BitSet, FixedBitSet};
+    use std::sync::Arc;
+
+    struct MockNumericDocValues {
+        values: Vec<i64>,
+    }
+
+    impl NumericDocValues for MockNumericDocValues {
+        fn get_with_ctx(
+            &self,
+            ctx: NumericDocValuesContext,
+            doc_id: DocId,
+        ) -> Result<(i64, NumericDocValuesContext)> {
+            Ok((self.values[doc_id as usize], ctx))
+        }
+    }
+
+    /// The value of each doc, `None` if the doc has no value.
+    struct MockDocValuesSource {
+        values: Vec<Option<i64>>,
+    }
+
+    impl DocValuesSource for MockDocValuesSource {
+        fn numeric_doc_values<C: Codec>(
+            &self,
+            _reader: &SearchLeafReader<C>,
+            _field: &str,
+        ) -> Result<NumericDocValuesRef> {
+            let values = self.values.iter().map(|v| v.unwrap_or(0)).collect();
+            Ok(Arc::new(MockNumericDocValues { values }))
+        }
+
+        fn docs_with_fields<C: Codec>(
+            &self,
+            _reader: &SearchLeafReader<C>,
+            _field: &str,
+        ) -> Result<BitsRef> {
+            let mut bits = FixedBitSet::new(self.values.len());
+            for (doc, value) in self.values.iter().enumerate() {
+                if value.is_some() {
+                    bits.set(doc);
+                }
+            }
+            Ok(Arc::new(bits))
+        }
+    }
+
+    /// Sorts the docs 0 to 4 of a `price` field, returns the docs in sort
+    /// order and whether doc 1, which has no value, is competitive against
+    /// doc 4 as the bottom.
+    fn sort_prices(reverse: bool, order: SortMissingOrder) -> (Vec<DocId>, bool) {
+        let mut sort_field = SortField::Simple(SimpleSortField::new(
+            "price".to_string(),
+            SortFieldType::Long,
+            reverse,
+        ));
+        sort_field.set_missing_order(order).unwrap();
+        let mut comparator = NumericDocValuesComparator::new(
+            5,
+            "price".to_string(),
+            SortFieldType::Long,
+            sort_field.missing_value().cloned(),
+            MockDocValuesSource {
+                values: vec![Some(3), None, Some(-1), None, Some(7)],
+            },
+        );
+
+        let leaf_reader = MockLeafReader::new(0);
+        let index_reader = MockIndexReader::new(vec![leaf_reader]);
+        let leaf_reader_context = index_reader.leaves();
+        comparator
+            .get_information_from_reader(&leaf_reader_context[0])
+            .unwrap();
+        for doc in 0..5 {
+            comparator.copy(doc, ComparatorValue::Doc(doc as DocId)).unwrap();
+        }
+
+        let mut slots: Vec<usize> = (0..5).collect();
+        slots.sort_by(|a, b| {
+            let ord = comparator.compare(*a, *b);
+            let ord = if reverse { ord.reverse() } else { ord };
+            ord.then(a.cmp(b))
+        });
+
+        comparator.set_bottom(4);
+        let ord = comparator.compare_bottom(ComparatorValue::Doc(1)).unwrap();
+        let ord = if reverse { ord.reverse() } else { ord };
+
+        let docs = slots.into_iter().map(|slot| slot as DocId).collect();
+        (docs, ord == Ordering::Greater)
+    }
+
+    #[test]
+    fn test_numeric_comparator_missing_values() {
+        assert_eq!(
+            sort_prices(false, SortMissingOrder::Last),
+            (vec![2, 0, 4, 1, 3], false)
+        );
+        assert_eq!(
+            sort_prices(false, SortMissingOrder::First),
+            (vec![1, 3, 2, 0, 4], true)
+        );
+        assert_eq!(
+            sort_prices(true, SortMissingOrder::Last),
+            (vec![4, 0, 2, 1, 3], false)
+        );
+        assert_eq!(
+            sort_prices(true, SortMissingOrder::First),
+            (vec![1, 3, 4, 0, 2], true)
+        );
+    }
 
     #[test]
     fn test_relevance_comparator() {
@@ -547,4 +935,133 @@ mod tests {
             Ordering::Greater
         );
     }
+
+    #[test]
+    fn test_doc_comparator_doc_base() {
+        let mut comparator = DocComparator::new(2);
+        let index_reader = MockIndexReader::new(vec![MockLeafReader::new(0)]);
+        let leaf_reader = MockLeafReader::new(10);
+        // the second segment of an index, starting at doc 10
+        let leaf_reader_context = LeafReaderContext::new(
+            &index_reader,
+            &leaf_reader as &SearchLeafReader<TestCodec>,
+            1,
+            10,
+        );
+        comparator
+            .get_information_from_reader(&leaf_reader_context)
+            .unwrap();
+        comparator.copy(0, ComparatorValue::Doc(1)).unwrap();
+        comparator.copy(1, ComparatorValue::Doc(3)).unwrap();
+        assert_eq!(comparator.value(0), VariantValue::Int(11));
+
+        // the bottom is compared with the global doc id of the hit
+        comparator.set_bottom(0);
+        assert_eq!(
+            comparator.compare_bottom(ComparatorValue::Doc(2)).unwrap(),
+            Ordering::Less
+        );
+        assert_eq!(
+            comparator.compare_bottom(ComparatorValue::Doc(0)).unwrap(),
+            Ordering::Greater
+        );
+    }
+
+    /// A segment with the given sorted terms, and the ordinals of each doc.
+    struct MockSortedSetDocValues {
+        terms: Vec<&'static str>,
+        doc_ords: Vec<Vec<i64>>,
+    }
+
+    impl SortedSetDocValues for MockSortedSetDocValues {
+        fn set_document(&self, doc: DocId) -> Result<SortedSetDocValuesContext> {
+            Ok((i64::from(doc), 0, 0))
+        }
+
+        fn next_ord(&self, ctx: &mut SortedSetDocValuesContext) -> Result<i64> {
+            let ords = &self.doc_ords[ctx.0 as usize];
+            if ctx.1 as usize == ords.len() {
+                return Ok(NO_MORE_ORDS);
+            }
+            ctx.1 += 1;
+            Ok(ords[ctx.1 as usize - 1])
+        }
+
+        fn lookup_ord(&self, ord: i64) -> Result<Vec<u8>> {
+            Ok(self.terms[ord as usize].as_bytes().to_vec())
+        }
+
+        fn get_value_count(&self) -> usize {
+            self.terms.len()
+        }
+
+        fn term_iterator(&self) -> Result<DocValuesTermIterator> {
+            Ok(DocValuesTermIterator::empty())
+        }
+    }
+
+    fn segments() -> Vec<SortedSetDocValuesRef> {
+        vec![
+            Arc::new(MockSortedSetDocValues {
+                terms: vec!["b", "d", "f"],
+                doc_ords: vec![vec![0, 2], vec![], vec![1]],
+            }),
+            Arc::new(MockSortedSetDocValues {
+                terms: vec!["a", "c", "d"],
+                doc_ords: vec![vec![1], vec![0, 2], vec![]],
+            }),
+        ]
+    }
+
+    /// Copies the 3 docs of each segment in the slots 0 to 5, returns the
+    /// slots in sort order.
+    fn sort_segments(comparator: &mut TermOrdValComparator) -> Vec<usize> {
+        for (i, doc_values) in segments().into_iter().enumerate() {
+            comparator.set_doc_values(i, doc_values).unwrap();
+            for doc in 0..3 {
+                comparator.copy(i * 3 + doc, ComparatorValue::Doc(doc as DocId)).unwrap();
+            }
+        }
+        let mut slots: Vec<usize> = (0..6).collect();
+        slots.sort_by(|a, b| comparator.compare(*a, *b).then(a.cmp(b)));
+        slots
+    }
+
+    #[test]
+    fn test_term_ord_val_comparator() {
+        let mut comparator =
+            TermOrdValComparator::new(6, "tags".into(), SortedSetSelectorType::Min, true);
+        assert_eq!(sort_segments(&mut comparator), vec![4, 0, 3, 2, 1, 5]);
+        assert_eq!(comparator.value(0), VariantValue::Binary(b"b".to_vec()));
+
+        // the docs of both segments with the term "d" are tied
+        let mut comparator =
+            TermOrdValComparator::new(6, "tags".into(), SortedSetSelectorType::Max, false);
+        assert_eq!(sort_segments(&mut comparator), vec![1, 5, 3, 2, 4, 0]);
+    }
+
+    #[test]
+    fn test_term_ord_val_comparator_bottom() {
+        let mut comparator =
+            TermOrdValComparator::new(3, "tags".into(), SortedSetSelectorType::Min, true);
+        let mut segments = segments().into_iter();
+        comparator.set_doc_values(0, segments.next().unwrap()).unwrap();
+        for doc in 0..3 {
+            comparator.copy(doc, ComparatorValue::Doc(doc as DocId)).unwrap();
+        }
+        // "d" is in the next segment too
+        comparator.set_bottom(2);
+        comparator.set_doc_values(1, segments.next().unwrap()).unwrap();
+        let compare_bottom = |c: &TermOrdValComparator, doc| {
+            c.compare_bottom(ComparatorValue::Doc(doc)).unwrap()
+        };
+        assert_eq!(compare_bottom(&comparator, 0), Ordering::Greater);
+        assert_eq!(compare_bottom(&comparator, 2), Ordering::Less);
+
+        // "b" isn't, it sorts between "a" and "c"
+        comparator.set_bottom(0);
+        assert_eq!(compare_bottom(&comparator, 1), Ordering::Greater);
+        assert_eq!(compare_bottom(&comparator, 0), Ordering::Less);
+        assert_eq!(compare_bottom(&comparator, 2), Ordering::Less);
+    }
 }
@@ -218,4 +218,16 @@ impl Scorer for FilterScorer {
     fn score_feature(&mut self) -> Result<Vec<FeatureResult>> {
         self.scorer.score_feature()
     }
+
+    fn set_min_competitive_score(&mut self, min_score: f32) {
+        self.scorer.set_min_competitive_score(min_score)
+    }
+
+    fn max_score(&mut self, up_to: DocId) -> Result<f32> {
+        self.scorer.max_score(up_to)
+    }
+
+    fn advance_shallow(&mut self, target: DocId) -> Result<DocId> {
+        self.scorer.advance_shallow(target)
+    }
 }
@@ -0,0 +1,384 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::codec::Codec;
+use core::index::LeafReaderContext;
+use core::search::conjunction::ConjunctionScorer;
+use core::search::explanation::Explanation;
+use core::search::match_all::ConstantScoreScorer;
+use core::search::searcher::SearchPlanBuilder;
+use core::search::term_query::TermQuery;
+use core::search::{two_phase_next, DocIdSet, DocIterator, FeatureResult};
+use core::search::{Query, Scorer, Weight};
+use core::util::context::IndexedContext;
+use core::util::doc_id_set::DocIdSetEnum;
+use core::util::{BitsRef, DocId};
+use error::Result;
+
+use std::fmt;
+use std::sync::Arc;
+
+const FILTERED_QUERY: &str = "filtered_query";
+
+/// A non scoring restriction of the docs matched by a query, computed per
+/// segment.
+pub trait Filter<C: Codec>: fmt::Display + Send + Sync {
+    /// Returns the accepted docs of the segment, `None` if no doc is accepted.
+    fn doc_id_set(&self, reader: &LeafReaderContext<'_, C>) -> Result<Option<DocIdSetEnum>>;
+}
+
+/// How a `FilteredQuery` intersects the query with the filter.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FilterStrategy {
+    /// Check the filter's bits when they are available and the filter is
+    /// not more selective than the query, leap frog otherwise.
+    Auto,
+    /// Always advance the query and the filter iterators in turn, the
+    /// cheaper one leading.
+    LeapFrog,
+    /// Check the filter's bits for each doc matched by the query whenever
+    /// the filter provides random access.
+    RandomAccess,
+}
+
+/// A query whose matches are restricted to the docs accepted by a `Filter`.
+///
+/// The filter never contributes to the score. When the filter's
+/// `DocIdSet` provides random access `bits()`, the query leads and the
+/// bits are checked in the second phase of the returned scorer, so that
+/// `BulkScorer` gates the docs with them along with the live docs. For
+/// selective filters, the query and the filter iterators are intersected
+/// instead, the filter leading.
+pub struct FilteredQuery<C: Codec> {
+    query: Box<dyn Query<C>>,
+    filter: Arc<dyn Filter<C>>,
+    strategy: FilterStrategy,
+}
+
+impl<C: Codec> FilteredQuery<C> {
+    pub fn build(query: Box<dyn Query<C>>, filter: Arc<dyn Filter<C>>) -> Box<dyn Query<C>> {
+        Self::build_with_strategy(query, filter, FilterStrategy::Auto)
+    }
+
+    pub fn build_with_strategy(
+        query: Box<dyn Query<C>>,
+        filter: Arc<dyn Filter<C>>,
+        strategy: FilterStrategy,
+    ) -> Box<dyn Query<C>> {
+        Box::new(FilteredQuery {
+            query,
+            filter,
+            strategy,
+        })
+    }
+}
+
+impl<C: Codec> Query<C> for FilteredQuery<C> {
+    fn create_weight(
+        &self,
+        searcher: &dyn SearchPlanBuilder<C>,
+        needs_scores: bool,
+    ) -> Result<Box<dyn Weight<C>>> {
+        Ok(Box::new(FilteredWeight {
+            weight: self.query.create_weight(searcher, needs_scores)?,
+            filter: Arc::clone(&self.filter),
+            strategy: self.strategy,
+        }))
+    }
+
+    fn extract_terms(&self) -> Vec<TermQuery> {
+        self.query.extract_terms()
+    }
+
+    fn query_type(&self) -> &'static str {
+        FILTERED_QUERY
+    }
+
+    fn as_any(&self) -> &::std::any::Any {
+        self
+    }
+}
+
+impl<C: Codec> fmt::Display for FilteredQuery<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "FilteredQuery(query: {}, filter: {}, strategy: {:?})",
+            &self.query, &self.filter, self.strategy
+        )
+    }
+}
+
+struct FilteredWeight<C: Codec> {
+    weight: Box<dyn Weight<C>>,
+    filter: Arc<dyn Filter<C>>,
+    strategy: FilterStrategy,
+}
+
+impl<C: Codec> FilteredWeight<C> {
+    fn accepts(&self, reader: &LeafReaderContext<'_, C>, doc: DocId) -> Result<bool> {
+        if let Some(doc_id_set) = self.filter.doc_id_set(reader)? {
+            if let Some(bits) = doc_id_set.bits()? {
+                return bits.get(doc as usize);
+            }
+            if let Some(mut iterator) = doc_id_set.iterator()? {
+                return Ok(iterator.advance(doc)? == doc);
+            }
+        }
+        Ok(false)
+    }
+}
+
+impl<C: Codec> Weight<C> for FilteredWeight<C> {
+    fn create_scorer(
+        &self,
+        reader_context: &LeafReaderContext<'_, C>,
+    ) -> Result<Option<Box<dyn Scorer>>> {
+        let doc_id_set = match self.filter.doc_id_set(reader_context)? {
+            Some(doc_id_set) => doc_id_set,
+            None => return Ok(None),
+        };
+        let filter_iterator = match doc_id_set.iterator()? {
+            Some(iterator) => iterator,
+            None => return Ok(None),
+        };
+        let scorer = match self.weight.create_scorer(reader_context)? {
+            Some(scorer) => scorer,
+            None => return Ok(None),
+        };
+
+        if self.strategy != FilterStrategy::LeapFrog {
+            if let Some(bits) = doc_id_set.bits()? {
+                // with a selective filter, leading with its iterator skips more docs
+                if self.strategy == FilterStrategy::RandomAccess
+                    || filter_iterator.cost() >= scorer.cost()
+                {
+                    return Ok(Some(Box::new(RandomAccessFilterScorer { scorer, bits })));
+                }
+            }
+        }
+
+        let cost = filter_iterator.cost();
+        let filter_scorer: Box<dyn Scorer> =
+            Box::new(ConstantScoreScorer::new(0f32, filter_iterator, cost));
+        Ok(Some(Box::new(ConjunctionScorer::new(vec![scorer, filter_scorer]))))
+    }
+
+    fn query_type(&self) -> &'static str {
+        FILTERED_QUERY
+    }
+
+    fn normalize(&mut self, norm: f32, boost: f32) {
+        self.weight.normalize(norm, boost)
+    }
+
+    fn value_for_normalization(&self) -> f32 {
+        self.weight.value_for_normalization()
+    }
+
+    fn needs_scores(&self) -> bool {
+        self.weight.needs_scores()
+    }
+
+    fn explain(&self, reader: &LeafReaderContext<'_, C>, doc: DocId) -> Result<Explanation> {
+        let explanation = self.weight.explain(reader, doc)?;
+        if !explanation.is_match() || self.accepts(reader, doc)? {
+            Ok(explanation)
+        } else {
+            Ok(Explanation::new(
+                false,
+                0.0f32,
+                format!("failure to match filter: {}", &self.filter),
+                vec![explanation],
+            ))
+        }
+    }
+}
+
+impl<C: Codec> fmt::Display for FilteredWeight<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "FilteredWeight(weight: {}, filter: {}, strategy: {:?})",
+            &self.weight, &self.filter, self.strategy
+        )
+    }
+}
+
+/// Iterates over the query's docs, those rejected by the filter's bits
+/// are dropped in the second phase.
+struct RandomAccessFilterScorer {
+    scorer: Box<dyn Scorer>,
+    bits: BitsRef,
+}
+
+impl DocIterator for RandomAccessFilterScorer {
+    fn doc_id(&self) -> DocId {
+        self.scorer.doc_id()
+    }
+
+    fn next(&mut self) -> Result<DocId> {
+        self.approximate_next()?;
+        two_phase_next(self)
+    }
+
+    fn advance(&mut self, target: DocId) -> Result<DocId> {
+        self.approximate_advance(target)?;
+        two_phase_next(self)
+    }
+
+    fn cost(&self) -> usize {
+        self.scorer.cost()
+    }
+
+    fn matches(&mut self) -> Result<bool> {
+        let doc = self.doc_id();
+        Ok(self.bits.get(doc as usize)? && self.scorer.matches()?)
+    }
+
+    fn match_cost(&self) -> f32 {
+        // a bits lookup
+        1f32 + self.scorer.match_cost()
+    }
+
+    fn approximate_next(&mut self) -> Result<DocId> {
+        self.scorer.approximate_next()
+    }
+
+    fn approximate_advance(&mut self, target: DocId) -> Result<DocId> {
+        self.scorer.approximate_advance(target)
+    }
+}
+
+impl Scorer for RandomAccessFilterScorer {
+    fn score(&mut self) -> Result<f32> {
+        self.scorer.score()
+    }
+
+    fn support_two_phase(&self) -> bool {
+        true
+    }
+
+    fn score_context(&mut self) -> Result<IndexedContext> {
+        self.scorer.score_context()
+    }
+
+    fn score_feature(&mut self) -> Result<Vec<FeatureResult>> {
+        self.scorer.score_feature()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::codec::tests::TestCodec;
+    use core::index::tests::*;
+    use core::index::IndexReader;
+    use core::search::tests::*;
+    use core::search::NO_MORE_DOCS;
+    use core::util::bit_set::{BitSet, FixedBitSet};
+    use core::util::doc_id_set::{BitDocIdSet, IntArrayDocIdSet};
+
+    struct MockFilter {
+        docs: Vec<DocId>,
+        random_access: bool,
+    }
+
+    impl Filter<TestCodec> for MockFilter {
+        fn doc_id_set(
+            &self,
+            _reader: &LeafReaderContext<'_, TestCodec>,
+        ) -> Result<Option<DocIdSetEnum>> {
+            if self.random_access {
+                let mut bits = FixedBitSet::new(100);
+                for &doc in &self.docs {
+                    bits.set(doc as usize);
+                }
+                let set = BitDocIdSet::new(Arc::new(bits), self.docs.len());
+                Ok(Some(DocIdSetEnum::BitDocId(set)))
+            } else {
+                let mut docs = self.docs.clone();
+                docs.push(NO_MORE_DOCS);
+                let set = IntArrayDocIdSet::new(docs, self.docs.len());
+                Ok(Some(DocIdSetEnum::IntArray(set)))
+            }
+        }
+    }
+
+    impl fmt::Display for MockFilter {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "MockFilter({:?})", &self.docs)
+        }
+    }
+
+    fn filtered_docs(
+        query_docs: Vec<DocId>,
+        filter: MockFilter,
+        strategy: FilterStrategy,
+    ) -> (Vec<DocId>, bool) {
+        let weight = FilteredWeight {
+            weight: Box::new(create_mock_weight(query_docs)),
+            filter: Arc::new(filter),
+            strategy,
+        };
+        let leaf_reader = MockLeafReader::new(0);
+        let index_reader = MockIndexReader::new(vec![leaf_reader]);
+        let leaves = index_reader.leaves();
+        let mut scorer = weight.create_scorer(&leaves[0]).unwrap().unwrap();
+
+        let mut docs = vec![];
+        loop {
+            let doc = scorer.next().unwrap();
+            if doc == NO_MORE_DOCS {
+                break;
+            }
+            docs.push(doc);
+        }
+        (docs, scorer.support_two_phase())
+    }
+
+    #[test]
+    fn test_filtered_query_strategies() {
+        let query_docs = vec![1, 3, 5, 7, 9];
+        let filter_docs = vec![0, 1, 2, 3, 4, 5, 6];
+
+        for &strategy in &[
+            FilterStrategy::Auto,
+            FilterStrategy::LeapFrog,
+            FilterStrategy::RandomAccess,
+        ] {
+            for &random_access in &[true, false] {
+                let filter = MockFilter {
+                    docs: filter_docs.clone(),
+                    random_access,
+                };
+                let (docs, two_phase) = filtered_docs(query_docs.clone(), filter, strategy);
+                assert_eq!(docs, vec![1, 3, 5]);
+                // the bits are only used when the filter provides them
+                assert_eq!(
+                    two_phase,
+                    random_access && strategy != FilterStrategy::LeapFrog
+                );
+            }
+        }
+
+        // a filter more selective than the query leads the intersection
+        let filter = MockFilter {
+            docs: vec![7],
+            random_access: true,
+        };
+        let (docs, two_phase) = filtered_docs(query_docs, filter, FilterStrategy::Auto);
+        assert_eq!(docs, vec![7]);
+        assert!(!two_phase);
+    }
+}
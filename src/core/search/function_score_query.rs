@@ -0,0 +1,335 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fmt;
+use std::sync::Arc;
+
+use core::codec::Codec;
+use core::index::LeafReaderContext;
+use core::search::explanation::Explanation;
+use core::search::searcher::SearchPlanBuilder;
+use core::search::term_query::TermQuery;
+use core::search::value_source::{FunctionValues, ValueSourceRef};
+use core::search::{DocIterator, Query, Scorer, Weight};
+use core::util::DocId;
+use error::Result;
+
+pub const FUNCTION_SCORE: &str = "function_score";
+
+/// How the score of the base query and the function value are combined.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CombineMode {
+    Multiply,
+    Sum,
+    /// the function value replaces the score of the base query
+    Replace,
+}
+
+impl CombineMode {
+    pub fn combine(self, score: f32, value: f32) -> f32 {
+        match self {
+            CombineMode::Multiply => score * value,
+            CombineMode::Sum => score + value,
+            CombineMode::Replace => value,
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            CombineMode::Multiply => "product of:",
+            CombineMode::Sum => "sum of:",
+            CombineMode::Replace => "function value, base score ignored:",
+        }
+    }
+}
+
+/// A query matching the docs of a base query whose score is combined with
+/// the value of a `ValueSource` for each doc, e.g. `score * ln(1 + popularity)`
+/// with a `NumericDocValuesSource` on the `popularity` field.
+pub struct FunctionScoreQuery<C: Codec> {
+    query: Box<dyn Query<C>>,
+    source: ValueSourceRef<C>,
+    mode: CombineMode,
+}
+
+impl<C: Codec> FunctionScoreQuery<C> {
+    pub fn new(
+        query: Box<dyn Query<C>>,
+        source: ValueSourceRef<C>,
+        mode: CombineMode,
+    ) -> FunctionScoreQuery<C> {
+        FunctionScoreQuery {
+            query,
+            source,
+            mode,
+        }
+    }
+}
+
+impl<C: Codec> Query<C> for FunctionScoreQuery<C> {
+    fn create_weight(
+        &self,
+        searcher: &dyn SearchPlanBuilder<C>,
+        needs_scores: bool,
+    ) -> Result<Box<dyn Weight<C>>> {
+        if !needs_scores {
+            return searcher.create_weight(self.query.as_ref(), false);
+        }
+        // the base score is useless when it is replaced
+        let sub_needs_scores = self.mode != CombineMode::Replace;
+        let weight = searcher.create_weight(self.query.as_ref(), sub_needs_scores)?;
+        Ok(Box::new(FunctionScoreWeight {
+            weight,
+            source: Arc::clone(&self.source),
+            mode: self.mode,
+        }))
+    }
+
+    fn extract_terms(&self) -> Vec<TermQuery> {
+        self.query.extract_terms()
+    }
+
+    fn query_type(&self) -> &'static str {
+        FUNCTION_SCORE
+    }
+
+    fn as_any(&self) -> &::std::any::Any {
+        self
+    }
+}
+
+impl<C: Codec> fmt::Display for FunctionScoreQuery<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "FunctionScoreQuery(query: {}, source: {}, mode: {:?})",
+            &self.query, &self.source, self.mode
+        )
+    }
+}
+
+struct FunctionScoreWeight<C: Codec> {
+    weight: Box<dyn Weight<C>>,
+    source: ValueSourceRef<C>,
+    mode: CombineMode,
+}
+
+impl<C: Codec> Weight<C> for FunctionScoreWeight<C> {
+    fn create_scorer(
+        &self,
+        reader_context: &LeafReaderContext<'_, C>,
+    ) -> Result<Option<Box<dyn Scorer>>> {
+        if let Some(scorer) = self.weight.create_scorer(reader_context)? {
+            Ok(Some(Box::new(FunctionScoreScorer {
+                scorer,
+                values: self.source.get_values(reader_context)?,
+                mode: self.mode,
+            })))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn query_type(&self) -> &'static str {
+        FUNCTION_SCORE
+    }
+
+    fn actual_query_type(&self) -> &'static str {
+        self.weight.query_type()
+    }
+
+    fn normalize(&mut self, norm: f32, boost: f32) {
+        self.weight.normalize(norm, boost)
+    }
+
+    fn value_for_normalization(&self) -> f32 {
+        self.weight.value_for_normalization()
+    }
+
+    fn needs_scores(&self) -> bool {
+        true
+    }
+
+    fn explain(&self, reader: &LeafReaderContext<'_, C>, doc: DocId) -> Result<Explanation> {
+        let sub_explanation = self.weight.explain(reader, doc)?;
+        if !sub_explanation.is_match() {
+            return Ok(sub_explanation);
+        }
+        let value = self.source.get_values(reader)?.float_val(doc)?;
+        let score = self.mode.combine(sub_explanation.value(), value);
+        Ok(Explanation::new(
+            true,
+            score,
+            format!("{}, {}", self, self.mode.description()),
+            vec![
+                sub_explanation,
+                Explanation::new(true, value, format!("{}", &self.source), vec![]),
+            ],
+        ))
+    }
+}
+
+impl<C: Codec> fmt::Display for FunctionScoreWeight<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "FunctionScoreWeight(weight: {}, source: {}, mode: {:?})",
+            &self.weight, &self.source, self.mode
+        )
+    }
+}
+
+struct FunctionScoreScorer {
+    scorer: Box<dyn Scorer>,
+    values: Box<dyn FunctionValues>,
+    mode: CombineMode,
+}
+
+impl Scorer for FunctionScoreScorer {
+    fn score(&mut self) -> Result<f32> {
+        let value = self.values.float_val(self.scorer.doc_id())?;
+        if self.mode == CombineMode::Replace {
+            Ok(value)
+        } else {
+            let score = self.scorer.score()?;
+            Ok(self.mode.combine(score, value))
+        }
+    }
+
+    fn support_two_phase(&self) -> bool {
+        self.scorer.support_two_phase()
+    }
+}
+
+impl DocIterator for FunctionScoreScorer {
+    fn doc_id(&self) -> DocId {
+        self.scorer.doc_id()
+    }
+
+    fn next(&mut self) -> Result<DocId> {
+        self.scorer.next()
+    }
+
+    fn advance(&mut self, target: DocId) -> Result<DocId> {
+        self.scorer.advance(target)
+    }
+
+    fn cost(&self) -> usize {
+        self.scorer.cost()
+    }
+
+    fn matches(&mut self) -> Result<bool> {
+        self.scorer.matches()
+    }
+
+    fn match_cost(&self) -> f32 {
+        self.scorer.match_cost()
+    }
+
+    fn approximate_next(&mut self) -> Result<DocId> {
+        self.scorer.approximate_next()
+    }
+
+    fn approximate_advance(&mut self, target: DocId) -> Result<DocId> {
+        self.scorer.approximate_advance(target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::doc::NumericDocValuesField;
+    use core::index::{Fieldable, IndexWriter, IndexWriterConfig};
+    use core::search::collector::top_docs::TopDocsCollector;
+    use core::search::match_all::MatchAllDocsQuery;
+    use core::search::searcher::{DefaultIndexSearcher, IndexSearcher};
+    use core::search::value_source::{NumericDocValuesSource, ValueModifier};
+    use core::store::RAMDirectory;
+
+    fn search(mode: CombineMode) -> Vec<(DocId, f32)> {
+        let dir = Arc::new(RAMDirectory::default());
+        let writer = IndexWriter::new(dir, Arc::new(IndexWriterConfig::default())).unwrap();
+        for popularity in &[3i64, 0, 99, 7] {
+            let doc: Vec<Box<dyn Fieldable>> =
+                vec![Box::new(NumericDocValuesField::new("popularity", *popularity))];
+            writer.add_document(doc).unwrap();
+        }
+        // a doc without popularity reads as 0
+        let doc: Vec<Box<dyn Fieldable>> = vec![Box::new(NumericDocValuesField::new("other", 5))];
+        writer.add_document(doc).unwrap();
+        let reader = writer.get_reader(true, false).unwrap();
+
+        let source = NumericDocValuesSource::new("popularity".into(), ValueModifier::Log1p);
+        let query = FunctionScoreQuery::new(
+            Box::new(MatchAllDocsQuery::with_boost(2.0)),
+            Arc::new(source),
+            mode,
+        );
+        let mut collector = TopDocsCollector::new(10);
+        {
+            let searcher = DefaultIndexSearcher::new(Arc::new(reader));
+            searcher.search(&query, &mut collector).unwrap();
+        }
+        writer.close().unwrap();
+
+        collector
+            .top_docs()
+            .score_docs()
+            .iter()
+            .map(|d| (d.doc_id(), d.score()))
+            .collect()
+    }
+
+    fn assert_scores(actual: &[(DocId, f32)], expected: &[(DocId, f32)]) {
+        assert_eq!(actual.len(), expected.len());
+        for (a, e) in actual.iter().zip(expected) {
+            assert_eq!(a.0, e.0);
+            assert!((a.1 - e.1).abs() < 1e-5, "{:?} != {:?}", a, e);
+        }
+    }
+
+    #[test]
+    fn test_function_score_modes() {
+        let log = |v: f32| v.ln_1p();
+        assert_scores(
+            &search(CombineMode::Multiply),
+            &[
+                (2, 2.0 * log(99.0)),
+                (3, 2.0 * log(7.0)),
+                (0, 2.0 * log(3.0)),
+                (1, 0.0),
+                (4, 0.0),
+            ],
+        );
+        assert_scores(
+            &search(CombineMode::Sum),
+            &[
+                (2, 2.0 + log(99.0)),
+                (3, 2.0 + log(7.0)),
+                (0, 2.0 + log(3.0)),
+                (1, 2.0),
+                (4, 2.0),
+            ],
+        );
+        assert_scores(
+            &search(CombineMode::Replace),
+            &[
+                (2, log(99.0)),
+                (3, log(7.0)),
+                (0, log(3.0)),
+                (1, 0.0),
+                (4, 0.0),
+            ],
+        );
+    }
+}
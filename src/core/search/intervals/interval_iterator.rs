@@ -0,0 +1,644 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::cmp::max;
+
+use core::search::posting_iterator::PostingIterator;
+use core::search::{DocIterator, NO_MORE_DOCS};
+use core::util::DocId;
+
+use error::Result;
+
+pub const NO_MORE_INTERVALS: i32 = i32::max_value();
+
+/// Iterates over the intervals of positions of the current doc.
+///
+/// The doc iteration is only an approximation: a doc may have no interval
+/// at all, which is only known once `next_interval` is called. The intervals
+/// are enumerated by increasing start position.
+pub trait IntervalIterator: DocIterator {
+    /// Returns the start position of the current interval, -1 when
+    /// `next_interval` was not yet called on the current doc, and
+    /// `NO_MORE_INTERVALS` after the last interval of the current doc.
+    fn start(&self) -> i32;
+
+    /// Returns the end position of the current interval, inclusive.
+    fn end(&self) -> i32;
+
+    /// Returns the number of positions of the current interval which are not
+    /// covered by the intervals it is made of.
+    fn gaps(&self) -> i32;
+
+    /// Returns the number of positions of the current interval.
+    fn width(&self) -> i32 {
+        self.end() - self.start() + 1
+    }
+
+    /// Advances to the next interval of the current doc and returns its start
+    /// position, or `NO_MORE_INTERVALS`.
+    fn next_interval(&mut self) -> Result<i32>;
+}
+
+/// The positions of a term, each one being an interval of width 1.
+pub struct TermIntervalIterator<P: PostingIterator> {
+    postings: P,
+    remaining: i32,
+    position: i32,
+}
+
+impl<P: PostingIterator> TermIntervalIterator<P> {
+    pub fn new(postings: P) -> TermIntervalIterator<P> {
+        TermIntervalIterator {
+            postings,
+            remaining: 0,
+            position: -1,
+        }
+    }
+
+    fn reset(&mut self, doc: DocId) -> Result<DocId> {
+        self.position = -1;
+        self.remaining = if doc == NO_MORE_DOCS {
+            0
+        } else {
+            self.postings.freq()?
+        };
+        Ok(doc)
+    }
+}
+
+impl<P: PostingIterator> DocIterator for TermIntervalIterator<P> {
+    fn doc_id(&self) -> DocId {
+        self.postings.doc_id()
+    }
+
+    fn next(&mut self) -> Result<DocId> {
+        let doc = self.postings.next()?;
+        self.reset(doc)
+    }
+
+    fn advance(&mut self, target: DocId) -> Result<DocId> {
+        let doc = self.postings.advance(target)?;
+        self.reset(doc)
+    }
+
+    fn cost(&self) -> usize {
+        self.postings.cost()
+    }
+}
+
+impl<P: PostingIterator> IntervalIterator for TermIntervalIterator<P> {
+    fn start(&self) -> i32 {
+        self.position
+    }
+
+    fn end(&self) -> i32 {
+        self.position
+    }
+
+    fn gaps(&self) -> i32 {
+        0
+    }
+
+    fn next_interval(&mut self) -> Result<i32> {
+        if self.remaining == 0 {
+            self.position = NO_MORE_INTERVALS;
+        } else {
+            self.remaining -= 1;
+            self.position = self.postings.next_position()?;
+        }
+        Ok(self.position)
+    }
+}
+
+/// Moves sub iterators to the docs they all contain.
+struct IntervalsConjunction {
+    subs: Vec<Box<dyn IntervalIterator>>,
+    doc: DocId,
+}
+
+impl IntervalsConjunction {
+    fn new(subs: Vec<Box<dyn IntervalIterator>>) -> IntervalsConjunction {
+        assert!(!subs.is_empty());
+        IntervalsConjunction { subs, doc: -1 }
+    }
+
+    fn next(&mut self) -> Result<DocId> {
+        let target = self.subs[0].next()?;
+        self.align(target)
+    }
+
+    fn advance(&mut self, target: DocId) -> Result<DocId> {
+        let target = self.subs[0].advance(target)?;
+        self.align(target)
+    }
+
+    fn align(&mut self, mut target: DocId) -> Result<DocId> {
+        'align: while target != NO_MORE_DOCS {
+            for sub in &mut self.subs {
+                let mut doc = sub.doc_id();
+                if doc < target {
+                    doc = sub.advance(target)?;
+                }
+                if doc > target {
+                    target = doc;
+                    continue 'align;
+                }
+            }
+            break;
+        }
+        self.doc = target;
+        Ok(target)
+    }
+
+    fn cost(&self) -> usize {
+        self.subs.iter().map(|sub| sub.cost()).min().unwrap()
+    }
+
+    // the number of positions of the current interval not covered by the
+    // current intervals of the sub iterators, the positions where sub
+    // intervals overlap are only covered once
+    fn gaps(&self, start: i32, end: i32) -> i32 {
+        let mut intervals: Vec<(i32, i32)> =
+            self.subs.iter().map(|sub| (sub.start(), sub.end())).collect();
+        intervals.sort();
+        let mut covered = 0;
+        let mut uncovered_from = start;
+        for (sub_start, sub_end) in intervals {
+            let from = max(sub_start, uncovered_from);
+            if sub_end >= from {
+                covered += sub_end - from + 1;
+                uncovered_from = sub_end + 1;
+            }
+        }
+        end - start + 1 - covered
+    }
+}
+
+/// The minimal intervals containing an interval of each sub iterator, in
+/// order and without overlap.
+pub struct OrderedIntervalIterator {
+    conjunction: IntervalsConjunction,
+    start: i32,
+    end: i32,
+    gaps: i32,
+}
+
+impl OrderedIntervalIterator {
+    pub fn new(subs: Vec<Box<dyn IntervalIterator>>) -> OrderedIntervalIterator {
+        OrderedIntervalIterator {
+            conjunction: IntervalsConjunction::new(subs),
+            start: -1,
+            end: -1,
+            gaps: -1,
+        }
+    }
+
+    fn reset(&mut self, doc: DocId) -> Result<DocId> {
+        self.start = -1;
+        self.end = -1;
+        self.gaps = -1;
+        if doc != NO_MORE_DOCS {
+            self.conjunction.subs[0].next_interval()?;
+        }
+        Ok(doc)
+    }
+}
+
+impl DocIterator for OrderedIntervalIterator {
+    fn doc_id(&self) -> DocId {
+        self.conjunction.doc
+    }
+
+    fn next(&mut self) -> Result<DocId> {
+        let doc = self.conjunction.next()?;
+        self.reset(doc)
+    }
+
+    fn advance(&mut self, target: DocId) -> Result<DocId> {
+        let doc = self.conjunction.advance(target)?;
+        self.reset(doc)
+    }
+
+    fn cost(&self) -> usize {
+        self.conjunction.cost()
+    }
+}
+
+impl IntervalIterator for OrderedIntervalIterator {
+    fn start(&self) -> i32 {
+        self.start
+    }
+
+    fn end(&self) -> i32 {
+        self.end
+    }
+
+    fn gaps(&self) -> i32 {
+        self.gaps
+    }
+
+    fn next_interval(&mut self) -> Result<i32> {
+        self.start = NO_MORE_INTERVALS;
+        self.end = NO_MORE_INTERVALS;
+        self.gaps = NO_MORE_INTERVALS;
+        let n = self.conjunction.subs.len();
+        // a match ending after the last start of the found match contains it
+        let mut last_start = NO_MORE_INTERVALS;
+        let mut minimizing = false;
+        let mut i = 1;
+        loop {
+            // moves each sub iterator after the end of the previous one
+            loop {
+                let prev_end = self.conjunction.subs[i - 1].end();
+                if prev_end >= last_start {
+                    return Ok(self.start);
+                }
+                if i == n {
+                    break;
+                }
+                let sub = &mut self.conjunction.subs[i];
+                if minimizing && sub.start() > prev_end {
+                    // the following ones are still after their previous one
+                    break;
+                }
+                loop {
+                    if sub.end() >= last_start || sub.next_interval()? == NO_MORE_INTERVALS {
+                        return Ok(self.start);
+                    }
+                    if sub.start() > prev_end {
+                        break;
+                    }
+                }
+                i += 1;
+            }
+
+            self.start = self.conjunction.subs[0].start();
+            if self.start == NO_MORE_INTERVALS {
+                self.end = NO_MORE_INTERVALS;
+                return Ok(NO_MORE_INTERVALS);
+            }
+            self.end = self.conjunction.subs[n - 1].end();
+            self.gaps = self.conjunction.gaps(self.start, self.end);
+
+            // then looks for a narrower match starting after this one
+            last_start = self.conjunction.subs[n - 1].start();
+            i = 1;
+            if self.conjunction.subs[0].next_interval()? == NO_MORE_INTERVALS {
+                return Ok(self.start);
+            }
+            minimizing = true;
+        }
+    }
+}
+
+/// The minimal intervals containing an interval of each sub iterator, in
+/// any order.
+pub struct UnorderedIntervalIterator {
+    conjunction: IntervalsConjunction,
+    // the sub iterators positioned on an interval, the top one has the lowest
+    // start, then the highest end
+    queue: Vec<usize>,
+    // the highest end of the sub iterators in the queue
+    queue_end: i32,
+    start: i32,
+    end: i32,
+    gaps: i32,
+}
+
+impl UnorderedIntervalIterator {
+    pub fn new(subs: Vec<Box<dyn IntervalIterator>>) -> UnorderedIntervalIterator {
+        let queue = Vec::with_capacity(subs.len());
+        UnorderedIntervalIterator {
+            conjunction: IntervalsConjunction::new(subs),
+            queue,
+            queue_end: -1,
+            start: -1,
+            end: -1,
+            gaps: -1,
+        }
+    }
+
+    fn reset(&mut self, doc: DocId) -> Result<DocId> {
+        self.queue.clear();
+        self.queue_end = -1;
+        self.start = -1;
+        self.end = -1;
+        self.gaps = -1;
+        if doc != NO_MORE_DOCS {
+            for i in 0..self.conjunction.subs.len() {
+                if self.conjunction.subs[i].next_interval()? == NO_MORE_INTERVALS {
+                    break;
+                }
+                self.push(i);
+            }
+        }
+        Ok(doc)
+    }
+
+    fn is_full(&self) -> bool {
+        self.queue.len() == self.conjunction.subs.len()
+    }
+
+    // the queue is tiny, a linear scan is enough
+    fn top_index(&self) -> usize {
+        let subs = &self.conjunction.subs;
+        let mut top = 0;
+        for i in 1..self.queue.len() {
+            let (sub, top_sub) = (&subs[self.queue[i]], &subs[self.queue[top]]);
+            if sub.start() < top_sub.start()
+                || (sub.start() == top_sub.start() && sub.end() > top_sub.end())
+            {
+                top = i;
+            }
+        }
+        top
+    }
+
+    fn top(&self) -> &dyn IntervalIterator {
+        self.conjunction.subs[self.queue[self.top_index()]].as_ref()
+    }
+
+    fn push(&mut self, sub: usize) {
+        self.queue.push(sub);
+        let end = self.conjunction.subs[sub].end();
+        if end > self.queue_end {
+            self.queue_end = end;
+        }
+    }
+
+    // pops the top sub iterator, and pushes it back if it has another interval
+    fn advance_top(&mut self) -> Result<()> {
+        let top_index = self.top_index();
+        let sub = self.queue.swap_remove(top_index);
+        if self.conjunction.subs[sub].next_interval()? != NO_MORE_INTERVALS {
+            self.push(sub);
+        }
+        Ok(())
+    }
+}
+
+impl DocIterator for UnorderedIntervalIterator {
+    fn doc_id(&self) -> DocId {
+        self.conjunction.doc
+    }
+
+    fn next(&mut self) -> Result<DocId> {
+        let doc = self.conjunction.next()?;
+        self.reset(doc)
+    }
+
+    fn advance(&mut self, target: DocId) -> Result<DocId> {
+        let doc = self.conjunction.advance(target)?;
+        self.reset(doc)
+    }
+
+    fn cost(&self) -> usize {
+        self.conjunction.cost()
+    }
+}
+
+impl IntervalIterator for UnorderedIntervalIterator {
+    fn start(&self) -> i32 {
+        self.start
+    }
+
+    fn end(&self) -> i32 {
+        self.end
+    }
+
+    fn gaps(&self) -> i32 {
+        self.gaps
+    }
+
+    fn next_interval(&mut self) -> Result<i32> {
+        // first, moves past the current match
+        while self.is_full() && self.top().start() == self.start {
+            self.advance_top()?;
+        }
+        if !self.is_full() {
+            self.start = NO_MORE_INTERVALS;
+            self.end = NO_MORE_INTERVALS;
+            return Ok(NO_MORE_INTERVALS);
+        }
+
+        // then, narrows it while its end doesn't change
+        loop {
+            self.start = self.top().start();
+            self.end = self.queue_end;
+            self.gaps = self.conjunction.gaps(self.start, self.end);
+            if self.top().end() == self.end {
+                return Ok(self.start);
+            }
+            self.advance_top()?;
+            if !self.is_full() || self.end != self.queue_end {
+                return Ok(self.start);
+            }
+        }
+    }
+}
+
+/// A condition on the intervals of a `FilteredIntervalIterator`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IntervalFilter {
+    /// Accepts the intervals of at most this width.
+    MaxWidth(i32),
+    /// Accepts the intervals with at most this number of gaps.
+    MaxGaps(i32),
+}
+
+impl IntervalFilter {
+    fn accept(&self, intervals: &dyn IntervalIterator) -> bool {
+        match *self {
+            IntervalFilter::MaxWidth(width) => intervals.width() <= width,
+            IntervalFilter::MaxGaps(gaps) => intervals.gaps() <= gaps,
+        }
+    }
+}
+
+/// The intervals of a sub iterator which are accepted by a filter.
+pub struct FilteredIntervalIterator {
+    intervals: Box<dyn IntervalIterator>,
+    filter: IntervalFilter,
+}
+
+impl FilteredIntervalIterator {
+    pub fn new(
+        intervals: Box<dyn IntervalIterator>,
+        filter: IntervalFilter,
+    ) -> FilteredIntervalIterator {
+        FilteredIntervalIterator { intervals, filter }
+    }
+}
+
+impl DocIterator for FilteredIntervalIterator {
+    fn doc_id(&self) -> DocId {
+        self.intervals.doc_id()
+    }
+
+    fn next(&mut self) -> Result<DocId> {
+        self.intervals.next()
+    }
+
+    fn advance(&mut self, target: DocId) -> Result<DocId> {
+        self.intervals.advance(target)
+    }
+
+    fn cost(&self) -> usize {
+        self.intervals.cost()
+    }
+}
+
+impl IntervalIterator for FilteredIntervalIterator {
+    fn start(&self) -> i32 {
+        self.intervals.start()
+    }
+
+    fn end(&self) -> i32 {
+        self.intervals.end()
+    }
+
+    fn gaps(&self) -> i32 {
+        self.intervals.gaps()
+    }
+
+    fn next_interval(&mut self) -> Result<i32> {
+        loop {
+            let start = self.intervals.next_interval()?;
+            if start == NO_MORE_INTERVALS || self.filter.accept(self.intervals.as_ref()) {
+                return Ok(start);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::search::spans::tests::MockPostings;
+
+    fn term(docs: Vec<(DocId, Vec<i32>)>) -> Box<dyn IntervalIterator> {
+        Box::new(TermIntervalIterator::new(MockPostings::new(docs)))
+    }
+
+    fn a() -> Box<dyn IntervalIterator> {
+        term(vec![(0, vec![0, 3]), (1, vec![2]), (2, vec![0, 5]), (3, vec![0, 1])])
+    }
+
+    fn b() -> Box<dyn IntervalIterator> {
+        term(vec![(0, vec![1, 4]), (1, vec![0]), (2, vec![3, 7]), (3, vec![2])])
+    }
+
+    fn c() -> Box<dyn IntervalIterator> {
+        term(vec![(0, vec![2]), (2, vec![1, 6])])
+    }
+
+    fn ordered(subs: Vec<Box<dyn IntervalIterator>>) -> Box<dyn IntervalIterator> {
+        Box::new(OrderedIntervalIterator::new(subs))
+    }
+
+    fn unordered(subs: Vec<Box<dyn IntervalIterator>>) -> Box<dyn IntervalIterator> {
+        Box::new(UnorderedIntervalIterator::new(subs))
+    }
+
+    fn filtered(
+        intervals: Box<dyn IntervalIterator>,
+        filter: IntervalFilter,
+    ) -> Box<dyn IntervalIterator> {
+        Box::new(FilteredIntervalIterator::new(intervals, filter))
+    }
+
+    // returns the (doc, start, end, gaps) of each interval
+    fn collect(mut intervals: Box<dyn IntervalIterator>) -> Vec<(DocId, i32, i32, i32)> {
+        let mut res = vec![];
+        loop {
+            let doc = intervals.next().unwrap();
+            if doc == NO_MORE_DOCS {
+                break;
+            }
+            while intervals.next_interval().unwrap() != NO_MORE_INTERVALS {
+                res.push((doc, intervals.start(), intervals.end(), intervals.gaps()));
+            }
+        }
+        res
+    }
+
+    #[test]
+    fn test_ordered_intervals() {
+        // in doc 3 only the narrowest interval is kept
+        assert_eq!(
+            collect(ordered(vec![a(), b()])),
+            vec![(0, 0, 1, 0), (0, 3, 4, 0), (2, 0, 3, 2), (2, 5, 7, 1), (3, 1, 2, 0)]
+        );
+    }
+
+    #[test]
+    fn test_unordered_intervals() {
+        assert_eq!(
+            collect(unordered(vec![a(), b()])),
+            vec![
+                (0, 0, 1, 0),
+                (0, 1, 3, 1),
+                (0, 3, 4, 0),
+                (1, 0, 2, 1),
+                (2, 0, 3, 2),
+                (2, 3, 5, 1),
+                (2, 5, 7, 1),
+                (3, 1, 2, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_filtered_intervals() {
+        assert_eq!(
+            collect(filtered(ordered(vec![a(), b()]), IntervalFilter::MaxGaps(0))),
+            vec![(0, 0, 1, 0), (0, 3, 4, 0), (3, 1, 2, 0)]
+        );
+        assert_eq!(
+            collect(filtered(ordered(vec![a(), b()]), IntervalFilter::MaxWidth(3))),
+            vec![(0, 0, 1, 0), (0, 3, 4, 0), (2, 5, 7, 1), (3, 1, 2, 0)]
+        );
+        assert_eq!(
+            collect(filtered(unordered(vec![a(), b()]), IntervalFilter::MaxWidth(2))),
+            vec![(0, 0, 1, 0), (0, 3, 4, 0), (3, 1, 2, 0)]
+        );
+    }
+
+    #[test]
+    fn test_nested_intervals() {
+        // the gaps of the sub intervals are not counted
+        assert_eq!(
+            collect(ordered(vec![ordered(vec![a(), b()]), c()])),
+            vec![(0, 0, 2, 0), (2, 0, 6, 2)]
+        );
+        assert_eq!(
+            collect(filtered(
+                ordered(vec![ordered(vec![a(), b()]), c()]),
+                IntervalFilter::MaxGaps(0),
+            )),
+            vec![(0, 0, 2, 0)]
+        );
+        assert_eq!(
+            collect(unordered(vec![a(), ordered(vec![b(), c()])])),
+            vec![(0, 0, 2, 0), (0, 1, 3, 0), (2, 3, 6, 0)]
+        );
+        // the positions where sub intervals overlap are only counted once
+        assert_eq!(
+            collect(filtered(
+                unordered(vec![a(), ordered(vec![b(), c()])]),
+                IntervalFilter::MaxGaps(0),
+            )),
+            vec![(0, 0, 2, 0), (0, 1, 3, 0), (2, 3, 6, 0)]
+        );
+    }
+}
@@ -0,0 +1,361 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::codec::{Codec, CodecTermState};
+use core::index::{LeafReaderContext, Term, TermContext};
+use core::search::explanation::Explanation;
+use core::search::intervals::interval_iterator::{IntervalIterator, NO_MORE_INTERVALS};
+use core::search::intervals::intervals_source::IntervalsSource;
+use core::search::searcher::SearchPlanBuilder;
+use core::search::spans::span::build_sim_weight;
+use core::search::term_query::TermQuery;
+use core::search::{DocIterator, Query, Scorer, SimScorer, SimWeight, Weight, NO_MORE_DOCS};
+use core::util::DocId;
+
+use error::Result;
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+const INTERVAL_QUERY: &str = "interval";
+
+/// Matches the docs of `field` with an interval of `source`, see `Intervals`
+/// to build the sources.
+///
+/// The narrower the narrowest interval of a doc, the higher its score.
+pub struct IntervalQuery {
+    field: String,
+    source: IntervalsSource,
+}
+
+impl IntervalQuery {
+    pub fn new(field: String, source: IntervalsSource) -> IntervalQuery {
+        IntervalQuery { field, source }
+    }
+
+    fn terms(&self) -> Vec<Term> {
+        let mut terms = vec![];
+        self.source.extract_terms(&self.field, &mut terms);
+        terms
+    }
+}
+
+impl<C: Codec> Query<C> for IntervalQuery {
+    fn create_weight(
+        &self,
+        searcher: &dyn SearchPlanBuilder<C>,
+        needs_scores: bool,
+    ) -> Result<Box<dyn Weight<C>>> {
+        let mut term_contexts = HashMap::new();
+        for term in self.terms() {
+            let term_context = searcher.term_state(&term)?;
+            term_contexts.insert(term, term_context);
+        }
+        let sim_weight = if needs_scores {
+            build_sim_weight(&self.field, searcher, term_contexts.clone(), None)?
+        } else {
+            None
+        };
+        Ok(Box::new(IntervalWeight {
+            field: self.field.clone(),
+            source: self.source.clone(),
+            term_contexts,
+            sim_weight,
+            needs_scores,
+        }))
+    }
+
+    fn extract_terms(&self) -> Vec<TermQuery> {
+        self.terms()
+            .into_iter()
+            .map(|term| TermQuery::new(term, 1.0f32, None))
+            .collect()
+    }
+
+    fn query_type(&self) -> &'static str {
+        INTERVAL_QUERY
+    }
+
+    fn as_any(&self) -> &::std::any::Any {
+        self
+    }
+}
+
+impl fmt::Display for IntervalQuery {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "IntervalQuery(field: {}, source: {})", self.field, self.source)
+    }
+}
+
+struct IntervalWeight<C: Codec> {
+    field: String,
+    source: IntervalsSource,
+    term_contexts: HashMap<Term, Arc<TermContext<CodecTermState<C>>>>,
+    sim_weight: Option<Box<dyn SimWeight<C>>>,
+    needs_scores: bool,
+}
+
+impl<C: Codec> IntervalWeight<C> {
+    fn interval_scorer(&self, reader: &LeafReaderContext<'_, C>) -> Result<Option<IntervalScorer>> {
+        let intervals = match self
+            .source
+            .intervals(&self.field, reader, &self.term_contexts)?
+        {
+            Some(intervals) => intervals,
+            None => return Ok(None),
+        };
+        let sim_scorer = match self.sim_weight {
+            Some(ref sim_weight) => Some(sim_weight.sim_scorer(reader.reader)?),
+            None => None,
+        };
+        Ok(Some(IntervalScorer::new(intervals, sim_scorer)))
+    }
+}
+
+impl<C: Codec> Weight<C> for IntervalWeight<C> {
+    fn create_scorer(&self, reader: &LeafReaderContext<'_, C>) -> Result<Option<Box<dyn Scorer>>> {
+        match self.interval_scorer(reader)? {
+            Some(scorer) => Ok(Some(Box::new(scorer))),
+            None => Ok(None),
+        }
+    }
+
+    fn query_type(&self) -> &'static str {
+        INTERVAL_QUERY
+    }
+
+    fn normalize(&mut self, norm: f32, boost: f32) {
+        if let Some(ref mut sim_weight) = self.sim_weight {
+            sim_weight.normalize(norm, boost)
+        }
+    }
+
+    fn value_for_normalization(&self) -> f32 {
+        match self.sim_weight {
+            Some(ref sim_weight) => sim_weight.get_value_for_normalization(),
+            None => 1.0,
+        }
+    }
+
+    fn needs_scores(&self) -> bool {
+        self.needs_scores
+    }
+
+    fn explain(&self, reader: &LeafReaderContext<'_, C>, doc: DocId) -> Result<Explanation> {
+        if let Some(mut scorer) = self.interval_scorer(reader)? {
+            if scorer.advance(doc)? == doc {
+                if let Some(ref sim_weight) = self.sim_weight {
+                    scorer.ensure_freq()?;
+                    let freq = scorer.freq;
+                    let freq_expl =
+                        Explanation::new(true, freq, format!("intervalFreq={}", freq), vec![]);
+                    let score_expl = sim_weight.explain(reader.reader, doc, freq_expl)?;
+
+                    return Ok(Explanation::new(
+                        true,
+                        score_expl.value(),
+                        format!("weight({} in {}), result of:", self, doc),
+                        vec![score_expl],
+                    ));
+                }
+            }
+        }
+
+        Ok(Explanation::new(
+            false,
+            0.0f32,
+            "no matching interval".to_string(),
+            vec![],
+        ))
+    }
+}
+
+impl<C: Codec> fmt::Display for IntervalWeight<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "IntervalWeight(field: {}, source: {})", self.field, self.source)
+    }
+}
+
+/// Scores the docs with an interval by the width of their narrowest one.
+///
+/// The intervals are only known once the doc is positioned, so the doc
+/// iteration is an approximation checked by `matches`, which reads the first
+/// interval.
+struct IntervalScorer {
+    intervals: Box<dyn IntervalIterator>,
+    sim_scorer: Option<Box<dyn SimScorer>>,
+    /// `1 / width` of the narrowest interval of the last scored doc
+    freq: f32,
+    last_scored_doc: DocId,
+}
+
+impl IntervalScorer {
+    fn new(intervals: Box<dyn IntervalIterator>, sim_scorer: Option<Box<dyn SimScorer>>) -> Self {
+        IntervalScorer {
+            intervals,
+            sim_scorer,
+            freq: 0.0,
+            last_scored_doc: -1,
+        }
+    }
+
+    fn ensure_freq(&mut self) -> Result<()> {
+        let doc = self.intervals.doc_id();
+        if self.last_scored_doc != doc {
+            // the first interval was read when the doc was matched
+            let mut min_width = self.intervals.width();
+            while self.intervals.next_interval()? != NO_MORE_INTERVALS {
+                min_width = min_width.min(self.intervals.width());
+            }
+            self.freq = 1.0 / min_width as f32;
+            self.last_scored_doc = doc;
+        }
+        Ok(())
+    }
+
+    fn next_match(&mut self, mut doc: DocId) -> Result<DocId> {
+        while doc != NO_MORE_DOCS && !self.matches()? {
+            doc = self.intervals.next()?;
+        }
+        Ok(doc)
+    }
+}
+
+impl DocIterator for IntervalScorer {
+    fn doc_id(&self) -> DocId {
+        self.intervals.doc_id()
+    }
+
+    fn next(&mut self) -> Result<DocId> {
+        let doc = self.intervals.next()?;
+        self.next_match(doc)
+    }
+
+    fn advance(&mut self, target: DocId) -> Result<DocId> {
+        let doc = self.intervals.advance(target)?;
+        self.next_match(doc)
+    }
+
+    fn cost(&self) -> usize {
+        self.intervals.cost()
+    }
+
+    fn matches(&mut self) -> Result<bool> {
+        Ok(self.intervals.next_interval()? != NO_MORE_INTERVALS)
+    }
+
+    fn approximate_next(&mut self) -> Result<DocId> {
+        self.intervals.next()
+    }
+
+    fn approximate_advance(&mut self, target: DocId) -> Result<DocId> {
+        self.intervals.advance(target)
+    }
+}
+
+impl Scorer for IntervalScorer {
+    fn score(&mut self) -> Result<f32> {
+        self.ensure_freq()?;
+        let doc = self.intervals.doc_id();
+        match self.sim_scorer {
+            Some(ref mut sim_scorer) => sim_scorer.score(doc, self.freq),
+            None => Ok(self.freq),
+        }
+    }
+
+    fn support_two_phase(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::analysis::whitespace_tokenizer::WhitespaceTokenizer;
+    use core::analysis::TokenStream;
+    use core::doc::{Field, FieldType};
+    use core::index::{Fieldable, IndexOptions, IndexReader, IndexWriter, IndexWriterConfig};
+    use core::search::collector::top_docs::TopDocsCollector;
+    use core::search::intervals::intervals_source::Intervals;
+    use core::search::searcher::{DefaultIndexSearcher, IndexSearcher};
+    use core::store::RAMDirectory;
+
+    use std::io::Cursor;
+
+    #[test]
+    fn test_interval_query() {
+        let dir = Arc::new(RAMDirectory::default());
+        let writer = IndexWriter::new(dir, Arc::new(IndexWriterConfig::default())).unwrap();
+        let mut field_type = FieldType::default();
+        field_type.index_options = IndexOptions::DocsAndFreqsAndPositions;
+        let texts = [
+            "quick brown fox",
+            "quick red fox",
+            "brown quick fox",
+            "the fox is quick and brown",
+        ];
+        for text in &texts {
+            let tokens: Box<dyn TokenStream> = Box::new(WhitespaceTokenizer::new(Box::new(
+                Cursor::new(text.as_bytes().to_vec()),
+            )));
+            let doc: Vec<Box<dyn Fieldable>> = vec![Box::new(Field::new(
+                "body".into(),
+                field_type.clone(),
+                None,
+                Some(tokens),
+            ))];
+            writer.add_document(doc).unwrap();
+        }
+        let reader = Arc::new(writer.get_reader(true, false).unwrap());
+        let searcher = DefaultIndexSearcher::new(Arc::clone(&reader));
+
+        let search = |source: IntervalsSource| {
+            let query = IntervalQuery::new("body".into(), source);
+            let mut collector = TopDocsCollector::new(10);
+            searcher.search(&query, &mut collector).unwrap();
+            collector
+                .top_docs()
+                .score_docs()
+                .iter()
+                .map(|d| d.doc_id())
+                .collect::<Vec<DocId>>()
+        };
+
+        let quick_fox = || vec![Intervals::term("quick"), Intervals::term("fox")];
+        // the narrowest interval ranks first
+        let hits = search(Intervals::ordered(quick_fox()));
+        assert_eq!(hits.len(), 3);
+        assert_eq!(hits[0], 2);
+        let hits = search(Intervals::unordered(quick_fox()));
+        assert_eq!(hits.len(), 4);
+        assert_eq!(hits[3], 3);
+
+        let mut hits = search(Intervals::max_width(3, Intervals::ordered(quick_fox())));
+        hits.sort();
+        assert_eq!(hits, vec![0, 1, 2]);
+        let hits = search(Intervals::max_gaps(0, Intervals::ordered(quick_fox())));
+        assert_eq!(hits, vec![2]);
+
+        // "brown" then "quick fox" within 3 positions
+        let nested = Intervals::ordered(vec![
+            Intervals::term("brown"),
+            Intervals::max_width(3, Intervals::unordered(quick_fox())),
+        ]);
+        assert_eq!(search(nested), vec![2]);
+        let missing = Intervals::ordered(vec![Intervals::term("quick"), Intervals::term("dog")]);
+        assert!(search(missing).is_empty());
+
+        writer.close().unwrap();
+    }
+}
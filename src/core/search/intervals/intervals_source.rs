@@ -0,0 +1,209 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::codec::{Codec, CodecTermState};
+use core::index::{LeafReaderContext, Term, TermContext, TermIterator, Terms};
+use core::search::intervals::interval_iterator::{
+    FilteredIntervalIterator, IntervalFilter, IntervalIterator, OrderedIntervalIterator,
+    TermIntervalIterator, UnorderedIntervalIterator,
+};
+use core::search::posting_iterator::PostingIteratorFlags;
+
+use error::{ErrorKind, Result};
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+/// Describes how to build the `IntervalIterator` of a field, see `Intervals`
+/// for the constructors.
+#[derive(Debug, Clone)]
+pub enum IntervalsSource {
+    /// The positions of a term.
+    Term(Vec<u8>),
+    /// The minimal intervals containing an interval of each source, in order.
+    Ordered(Vec<IntervalsSource>),
+    /// The minimal intervals containing an interval of each source.
+    Unordered(Vec<IntervalsSource>),
+    /// The intervals of a source accepted by a filter.
+    Filtered(Box<IntervalsSource>, IntervalFilter),
+}
+
+impl IntervalsSource {
+    /// Returns the intervals of `field` in the segment of `reader`, `None` if
+    /// no doc of the segment can match.
+    ///
+    /// `term_contexts` must contain the terms extracted from this source.
+    #[allow(implicit_hasher)]
+    pub fn intervals<C: Codec>(
+        &self,
+        field: &str,
+        reader: &LeafReaderContext<'_, C>,
+        term_contexts: &HashMap<Term, Arc<TermContext<CodecTermState<C>>>>,
+    ) -> Result<Option<Box<dyn IntervalIterator>>> {
+        match self {
+            IntervalsSource::Term(bytes) => {
+                Self::term_intervals(field, bytes, reader, term_contexts)
+            }
+            IntervalsSource::Ordered(sources) => {
+                match Self::sub_intervals(sources, field, reader, term_contexts)? {
+                    Some(subs) => Ok(Some(Box::new(OrderedIntervalIterator::new(subs)))),
+                    None => Ok(None),
+                }
+            }
+            IntervalsSource::Unordered(sources) => {
+                match Self::sub_intervals(sources, field, reader, term_contexts)? {
+                    Some(subs) => Ok(Some(Box::new(UnorderedIntervalIterator::new(subs)))),
+                    None => Ok(None),
+                }
+            }
+            IntervalsSource::Filtered(source, filter) => {
+                match source.intervals(field, reader, term_contexts)? {
+                    Some(intervals) => Ok(Some(Box::new(FilteredIntervalIterator::new(
+                        intervals, *filter,
+                    )))),
+                    None => Ok(None),
+                }
+            }
+        }
+    }
+
+    fn term_intervals<C: Codec>(
+        field: &str,
+        bytes: &[u8],
+        reader: &LeafReaderContext<'_, C>,
+        term_contexts: &HashMap<Term, Arc<TermContext<CodecTermState<C>>>>,
+    ) -> Result<Option<Box<dyn IntervalIterator>>> {
+        let term = Term::new(field.to_string(), bytes.to_vec());
+        let state = match term_contexts
+            .get(&term)
+            .and_then(|ctx| ctx.get_term_state(reader))
+        {
+            Some(state) => state,
+            None => return Ok(None),
+        };
+        if let Some(terms) = reader.reader.terms(field)? {
+            if !terms.has_positions()? {
+                bail!(ErrorKind::IllegalState(format!(
+                    "field '{}' was indexed without position data; cannot run IntervalQuery",
+                    field
+                )));
+            }
+            let mut terms_iter = terms.iterator()?;
+            terms_iter.seek_exact_state(bytes, state)?;
+            let postings = terms_iter.postings_with_flags(PostingIteratorFlags::POSITIONS)?;
+            return Ok(Some(Box::new(TermIntervalIterator::new(postings))));
+        }
+        Ok(None)
+    }
+
+    fn sub_intervals<C: Codec>(
+        sources: &[IntervalsSource],
+        field: &str,
+        reader: &LeafReaderContext<'_, C>,
+        term_contexts: &HashMap<Term, Arc<TermContext<CodecTermState<C>>>>,
+    ) -> Result<Option<Vec<Box<dyn IntervalIterator>>>> {
+        let mut subs = Vec::with_capacity(sources.len());
+        for source in sources {
+            match source.intervals(field, reader, term_contexts)? {
+                Some(intervals) => subs.push(intervals),
+                None => return Ok(None),
+            }
+        }
+        Ok(Some(subs))
+    }
+
+    /// Adds the terms of this source to `terms`.
+    pub fn extract_terms(&self, field: &str, terms: &mut Vec<Term>) {
+        match self {
+            IntervalsSource::Term(bytes) => {
+                let term = Term::new(field.to_string(), bytes.clone());
+                if !terms.contains(&term) {
+                    terms.push(term);
+                }
+            }
+            IntervalsSource::Ordered(sources) | IntervalsSource::Unordered(sources) => {
+                for source in sources {
+                    source.extract_terms(field, terms);
+                }
+            }
+            IntervalsSource::Filtered(source, _) => source.extract_terms(field, terms),
+        }
+    }
+}
+
+impl fmt::Display for IntervalsSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (name, sources) = match self {
+            IntervalsSource::Term(bytes) => {
+                return write!(f, "{}", String::from_utf8_lossy(bytes));
+            }
+            IntervalsSource::Filtered(source, IntervalFilter::MaxWidth(width)) => {
+                return write!(f, "MAXWIDTH/{}({})", width, source);
+            }
+            IntervalsSource::Filtered(source, IntervalFilter::MaxGaps(gaps)) => {
+                return write!(f, "MAXGAPS/{}({})", gaps, source);
+            }
+            IntervalsSource::Ordered(sources) => ("ORDERED", sources),
+            IntervalsSource::Unordered(sources) => ("UNORDERED", sources),
+        };
+        write!(f, "{}(", name)?;
+        for (i, source) in sources.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", source)?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// Constructors of `IntervalsSource`.
+pub struct Intervals;
+
+impl Intervals {
+    /// The positions of `term`.
+    pub fn term(term: &str) -> IntervalsSource {
+        IntervalsSource::Term(term.as_bytes().to_vec())
+    }
+
+    /// The intervals containing an interval of each source, in order and
+    /// without overlap.
+    pub fn ordered(sources: Vec<IntervalsSource>) -> IntervalsSource {
+        assert!(!sources.is_empty());
+        if sources.len() == 1 {
+            return sources.into_iter().next().unwrap();
+        }
+        IntervalsSource::Ordered(sources)
+    }
+
+    /// The intervals containing an interval of each source, in any order.
+    pub fn unordered(sources: Vec<IntervalsSource>) -> IntervalsSource {
+        assert!(!sources.is_empty());
+        if sources.len() == 1 {
+            return sources.into_iter().next().unwrap();
+        }
+        IntervalsSource::Unordered(sources)
+    }
+
+    /// The intervals of `source` spanning at most `width` positions.
+    pub fn max_width(width: i32, source: IntervalsSource) -> IntervalsSource {
+        IntervalsSource::Filtered(Box::new(source), IntervalFilter::MaxWidth(width))
+    }
+
+    /// The intervals of `source` with at most `gaps` positions not covered by
+    /// the intervals they are made of.
+    pub fn max_gaps(gaps: i32, source: IntervalsSource) -> IntervalsSource {
+        IntervalsSource::Filtered(Box::new(source), IntervalFilter::MaxGaps(gaps))
+    }
+}
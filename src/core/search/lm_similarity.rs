@@ -0,0 +1,296 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use error::{ErrorKind::IllegalArgument, Result};
+use std::fmt;
+
+use core::codec::Codec;
+use core::index::{NumericDocValues, SearchLeafReader};
+use core::search::explanation::Explanation;
+use core::search::statistics::{CollectionStatistics, TermStatistics};
+use core::search::{SimScorer, SimWeight, Similarity};
+use core::util::small_float::SmallFloat;
+use core::util::{DocId, KeyedContext};
+
+lazy_static! {
+    // decodes the norms written by `BM25Similarity::compute_norm` to the field length
+    static ref LENGTH_TABLE: [f32; 256] = {
+        let mut length_table: [f32; 256] = [0f32; 256];
+        for (i, length) in length_table.iter_mut().enumerate().skip(1) {
+            let f = SmallFloat::byte315_to_float(i as u8);
+            *length = 1f32 / (f * f);
+        }
+        length_table[0] = ::std::f32::MAX;
+        length_table
+    };
+}
+
+pub const DEFAULT_LM_DIRICHLET_MU: f32 = 2000.0;
+
+/// Language model similarity with Bayesian smoothing using Dirichlet priors.
+/// From Chengxiang Zhai and John Lafferty. 2001. A study of smoothing methods
+/// for language models applied to Ad Hoc information retrieval. In Proceedings
+/// of the 24th annual international ACM SIGIR conference on Research and
+/// development in information retrieval (SIGIR '01). ACM, New York, NY, USA,
+/// 334-342.
+///
+/// The score of a term is
+/// `boost * (ln(1 + freq / (mu * p(t|C))) + ln(mu / (docLen + mu)))`, where
+/// `p(t|C) = (totalTermFreq + 1) / (sumTotalTermFreq + 1)` is the probability
+/// of the term in the collection. Like Lucene, negative scores are clamped to
+/// 0, which happens for docs where the term is less frequent than in the
+/// collection.
+///
+/// The doc length is decoded from the norms written at index time, so this
+/// similarity can score an index written for `BM25Similarity`.
+#[derive(Clone, Copy, Debug)]
+pub struct LMDirichletSimilarity {
+    mu: f32,
+}
+
+impl Default for LMDirichletSimilarity {
+    fn default() -> Self {
+        LMDirichletSimilarity {
+            mu: DEFAULT_LM_DIRICHLET_MU,
+        }
+    }
+}
+
+impl LMDirichletSimilarity {
+    pub fn new(mu: f32) -> Result<LMDirichletSimilarity> {
+        if !mu.is_finite() || mu <= 0.0 {
+            bail!(IllegalArgument(format!("mu must be positive, got {}", mu)));
+        }
+        Ok(LMDirichletSimilarity { mu })
+    }
+
+    pub fn mu(&self) -> f32 {
+        self.mu
+    }
+
+    fn sloppy_freq(distance: i32) -> f32 {
+        1.0 / (distance as f32 + 1.0)
+    }
+
+    /// Returns `p(t|C)`, which is never 0 nor infinite: the counts are add-one
+    /// smoothed, and the stats which aren't stored by the codec count as 0.
+    fn collection_probability(
+        collection_stats: &CollectionStatistics,
+        term_stats: &TermStatistics,
+    ) -> f32 {
+        let total_term_freq = if term_stats.total_term_freq >= 0 {
+            term_stats.total_term_freq
+        } else {
+            term_stats.doc_freq.max(0)
+        };
+        let sum_total_term_freq = collection_stats.sum_total_term_freq.max(total_term_freq);
+        ((total_term_freq as f64 + 1.0) / (sum_total_term_freq as f64 + 1.0)) as f32
+    }
+
+    fn score_term(&self, freq: f32, doc_len: f32, collection_probability: f32) -> f32 {
+        let score = (1.0 + freq / (self.mu * collection_probability)).ln()
+            + (self.mu / (doc_len + self.mu)).ln();
+        if score > 0.0 {
+            score
+        } else {
+            0.0
+        }
+    }
+}
+
+impl<C: Codec> Similarity<C> for LMDirichletSimilarity {
+    fn compute_weight(
+        &self,
+        collection_stats: &CollectionStatistics,
+        term_stats: &[TermStatistics],
+        _context: Option<&KeyedContext>,
+        boost: f32,
+    ) -> Box<dyn SimWeight<C>> {
+        let collection_probabilities = term_stats
+            .iter()
+            .map(|s| LMDirichletSimilarity::collection_probability(collection_stats, s))
+            .collect();
+        Box::new(LMDirichletSimWeight {
+            similarity: *self,
+            field: collection_stats.field.clone(),
+            collection_probabilities,
+            boost,
+        })
+    }
+}
+
+impl fmt::Display for LMDirichletSimilarity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "LMDirichletSimilarity(mu: {})", self.mu)
+    }
+}
+
+pub struct LMDirichletSimScorer {
+    similarity: LMDirichletSimilarity,
+    collection_probabilities: Vec<f32>,
+    boost: f32,
+    norms: Option<Box<dyn NumericDocValues>>,
+}
+
+impl SimScorer for LMDirichletSimScorer {
+    fn score(&mut self, doc: DocId, freq: f32) -> Result<f32> {
+        let doc_len = match self.norms {
+            Some(ref mut norms) => LENGTH_TABLE[(norms.get(doc)? & 0xFF) as usize],
+            None => 1.0,
+        };
+        let mut score = 0f32;
+        for p in &self.collection_probabilities {
+            score += self.similarity.score_term(freq, doc_len, *p);
+        }
+        Ok(self.boost * score)
+    }
+
+    fn compute_slop_factor(&self, distance: i32) -> f32 {
+        LMDirichletSimilarity::sloppy_freq(distance)
+    }
+}
+
+pub struct LMDirichletSimWeight {
+    similarity: LMDirichletSimilarity,
+    field: String,
+    // `p(t|C)` of each term of the query
+    collection_probabilities: Vec<f32>,
+    boost: f32,
+}
+
+impl<C: Codec> SimWeight<C> for LMDirichletSimWeight {
+    fn get_value_for_normalization(&self) -> f32 {
+        self.boost * self.boost
+    }
+
+    fn normalize(&mut self, _query_norm: f32, boost: f32) {
+        self.boost = boost;
+    }
+
+    fn sim_scorer(&self, reader: &SearchLeafReader<C>) -> Result<Box<dyn SimScorer>> {
+        let norms = reader.norm_values(&self.field)?;
+        Ok(Box::new(LMDirichletSimScorer {
+            similarity: self.similarity,
+            collection_probabilities: self.collection_probabilities.clone(),
+            boost: self.boost,
+            norms,
+        }))
+    }
+
+    fn explain(
+        &self,
+        reader: &SearchLeafReader<C>,
+        doc: DocId,
+        freq: Explanation,
+    ) -> Result<Explanation> {
+        let doc_len = match reader.norm_values(&self.field)? {
+            Some(norms) => LENGTH_TABLE[(norms.get(doc)? & 0xFF) as usize],
+            None => 1.0,
+        };
+        let freq_value = freq.value();
+        let mu = self.similarity.mu;
+
+        let mut details = Vec::with_capacity(self.collection_probabilities.len());
+        let mut total = 0f32;
+        for p in &self.collection_probabilities {
+            let score = self.boost * self.similarity.score_term(freq_value, doc_len, *p);
+            total += score;
+            details.push(Explanation::new(
+                true,
+                score,
+                format!(
+                    "score(freq={}, docLen={}, mu={}, p(t|C)={})",
+                    freq_value, doc_len, mu, p
+                ),
+                vec![],
+            ));
+        }
+
+        let mut subs = vec![freq];
+        if (self.boost - 1.0f32).abs() > ::std::f32::EPSILON {
+            subs.push(Explanation::new(true, self.boost, "boost".to_string(), vec![]));
+        }
+        subs.extend(details);
+        Ok(Explanation::new(
+            true,
+            total,
+            format!("score(doc={},freq={}), LMDirichlet, sum of:", doc, freq_value),
+            subs,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::codec::tests::TestCodec;
+    use core::index::tests::MockLeafReader;
+    use error::{Error, ErrorKind};
+
+    #[test]
+    fn test_collection_probability() {
+        let term_stats = TermStatistics::new(Vec::new(), 3, 9);
+        let collection_stats = CollectionStatistics::new(String::from("world"), 32, 32, 99, 50);
+        let p = LMDirichletSimilarity::collection_probability(&collection_stats, &term_stats);
+        assert!((p - 0.1).abs() < ::std::f32::EPSILON);
+
+        // zero collection length and zero collection frequency
+        let term_stats = TermStatistics::new(Vec::new(), 0, 0);
+        let collection_stats = CollectionStatistics::new(String::from("world"), 32, 0, 0, 0);
+        let p = LMDirichletSimilarity::collection_probability(&collection_stats, &term_stats);
+        assert!((p - 1.0).abs() < ::std::f32::EPSILON);
+
+        // stats not stored by the codec
+        let term_stats = TermStatistics::new(Vec::new(), 4, -1);
+        let collection_stats = CollectionStatistics::new(String::from("world"), 32, -1, -1, -1);
+        let p = LMDirichletSimilarity::collection_probability(&collection_stats, &term_stats);
+        assert!(p.is_finite() && p > 0.0);
+    }
+
+    #[test]
+    fn test_lm_dirichlet_similarity() {
+        let collection_stats =
+            CollectionStatistics::new(String::from("world"), 32, 32, 100_000, -1);
+        let term_stats = vec![TermStatistics::new(Vec::new(), 1, 10)];
+        let sim_weight: Box<dyn SimWeight<TestCodec>> = LMDirichletSimilarity::default()
+            .compute_weight(&collection_stats, &term_stats, None, 1.0f32);
+
+        let leaf_reader = MockLeafReader::new(1);
+        let mut sim_scorer = sim_weight.sim_scorer(&leaf_reader).unwrap();
+
+        // same field length
+        let score1 = sim_scorer.score(1, 10.0).unwrap();
+        let score2 = sim_scorer.score(1, 5.0).unwrap();
+        assert!(score1 > score2);
+        assert!(score2 > 0.0);
+
+        // same term_freq, doc 1 has a shorter field than doc 2
+        let score1 = sim_scorer.score(1, 10.0).unwrap();
+        let score2 = sim_scorer.score(2, 10.0).unwrap();
+        assert!(score1 > score2);
+
+        // scores are never negative
+        assert!(sim_scorer.score(1, 0.0).unwrap() >= 0.0);
+    }
+
+    #[test]
+    fn test_invalid_mu() {
+        assert!((LMDirichletSimilarity::new(1000.0).unwrap().mu() - 1000.0).abs() < 1e-3);
+        for &mu in &[0.0, -1.0, ::std::f32::NAN, ::std::f32::INFINITY] {
+            match LMDirichletSimilarity::new(mu) {
+                Err(Error(ErrorKind::IllegalArgument(_), _)) => {}
+                _ => panic!("mu {} should be rejected", mu),
+            }
+        }
+    }
+}
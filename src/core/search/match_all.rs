@@ -13,18 +13,43 @@
 
 use core::codec::Codec;
 use core::index::LeafReaderContext;
+use core::search::bulk_scorer::{BulkScorer, ScoreAndDoc};
 use core::search::explanation::Explanation;
 use core::search::searcher::SearchPlanBuilder;
 use core::search::term_query::TermQuery;
 use core::search::two_phase_next;
-use core::search::{DocIterator, Query, Scorer, Weight, NO_MORE_DOCS};
-use core::util::DocId;
+use core::search::{DocIdSet, DocIterator, Query, Scorer, Weight, NO_MORE_DOCS};
+use core::util::{Bits, BitsRef, DocId};
 use error::Result;
 use std::fmt;
 
 pub const MATCH_ALL: &str = "match_all";
 
-pub struct MatchAllDocsQuery;
+/// A query matching all the docs, each with the same constant score,
+/// `boost` by default.
+pub struct MatchAllDocsQuery {
+    boost: f32,
+}
+
+impl MatchAllDocsQuery {
+    pub fn new() -> MatchAllDocsQuery {
+        Self::with_boost(1f32)
+    }
+
+    pub fn with_boost(boost: f32) -> MatchAllDocsQuery {
+        MatchAllDocsQuery { boost }
+    }
+
+    pub fn boost(&self) -> f32 {
+        self.boost
+    }
+}
+
+impl Default for MatchAllDocsQuery {
+    fn default() -> Self {
+        MatchAllDocsQuery::new()
+    }
+}
 
 impl<C: Codec> Query<C> for MatchAllDocsQuery {
     fn create_weight(
@@ -32,7 +57,7 @@ impl<C: Codec> Query<C> for MatchAllDocsQuery {
         _searcher: &dyn SearchPlanBuilder<C>,
         _needs_scores: bool,
     ) -> Result<Box<dyn Weight<C>>> {
-        Ok(Box::new(MatchAllDocsWeight::default()))
+        Ok(Box::new(MatchAllDocsWeight::new(self.boost)))
     }
 
     fn extract_terms(&self) -> Vec<TermQuery> {
@@ -50,19 +75,25 @@ impl<C: Codec> Query<C> for MatchAllDocsQuery {
 
 impl fmt::Display for MatchAllDocsQuery {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "MatchAllDocsQuery()")
+        write!(f, "MatchAllDocsQuery(boost: {})", self.boost)
     }
 }
 
+// Like `ConstantScoreWeight`, the weight starts at the boost, so that a match-all
+// scores its boost without normalization and its value for normalization is the
+// squared boost. As it does not need scores, a boolean query leaves it out of
+// its own value for normalization.
 struct MatchAllDocsWeight {
+    boost: f32,
     weight: f32,
     norm: f32,
 }
 
-impl Default for MatchAllDocsWeight {
-    fn default() -> Self {
+impl MatchAllDocsWeight {
+    fn new(boost: f32) -> Self {
         MatchAllDocsWeight {
-            weight: 0f32,
+            boost,
+            weight: boost,
             norm: 1f32,
         }
     }
@@ -74,10 +105,20 @@ impl<C: Codec> Weight<C> for MatchAllDocsWeight {
         leaf_reader: &LeafReaderContext<'_, C>,
     ) -> Result<Option<Box<dyn Scorer>>> {
         let max_doc = leaf_reader.reader.max_doc();
-        Ok(Some(Box::new(ConstantScoreScorer {
+        Ok(Some(Box::new(ConstantScoreScorer::new(
+            self.weight,
+            AllDocsIterator::new(max_doc),
+            max_doc as usize,
+        ))))
+    }
+
+    fn bulk_scorer(
+        &self,
+        leaf_reader: &LeafReaderContext<'_, C>,
+    ) -> Result<Option<Box<dyn BulkScorer>>> {
+        Ok(Some(Box::new(MatchAllBulkScorer {
             score: self.weight,
-            iterator: AllDocsIterator::new(max_doc),
-            cost: max_doc as usize,
+            max_doc: leaf_reader.reader.max_doc(),
         })))
     }
 
@@ -87,7 +128,7 @@ impl<C: Codec> Weight<C> for MatchAllDocsWeight {
 
     fn normalize(&mut self, norm: f32, boost: f32) {
         self.norm = norm;
-        self.weight = norm * boost;
+        self.weight = norm * boost * self.boost;
     }
 
     fn value_for_normalization(&self) -> f32 {
@@ -104,7 +145,7 @@ impl<C: Codec> Weight<C> for MatchAllDocsWeight {
             self.weight,
             format!("{}, product of:", self),
             vec![
-                Explanation::new(true, self.weight, "boost".to_string(), vec![]),
+                Explanation::new(true, self.weight / self.norm, "boost".to_string(), vec![]),
                 Explanation::new(true, self.norm, "queryNorm".to_string(), vec![]),
             ],
         ))
@@ -113,7 +154,45 @@ impl<C: Codec> Weight<C> for MatchAllDocsWeight {
 
 impl fmt::Display for MatchAllDocsWeight {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "MatchAllDocsWeight()")
+        write!(f, "MatchAllDocsWeight(boost: {})", self.boost)
+    }
+}
+
+/// Collects all the docs of a segment by walking over their ids, checking
+/// the accepted docs directly instead of iterating over a scorer.
+struct MatchAllBulkScorer {
+    score: f32,
+    max_doc: DocId,
+}
+
+impl BulkScorer for MatchAllBulkScorer {
+    fn score(
+        &mut self,
+        collector: &mut dyn FnMut(DocId, &mut dyn Scorer) -> Result<()>,
+        accept_docs: Option<&dyn Bits>,
+        min: DocId,
+        max: DocId,
+    ) -> Result<DocId> {
+        let max = max.min(self.max_doc);
+        let mut scorer = ScoreAndDoc::new(self.score);
+        if let Some(accept_docs) = accept_docs {
+            for doc in min..max {
+                if accept_docs.get(doc as usize)? {
+                    scorer.doc = doc;
+                    collector(doc, &mut scorer)?;
+                }
+            }
+        } else {
+            for doc in min..max {
+                scorer.doc = doc;
+                collector(doc, &mut scorer)?;
+            }
+        }
+        Ok(if max == self.max_doc { NO_MORE_DOCS } else { max })
+    }
+
+    fn cost(&self) -> usize {
+        self.max_doc as usize
     }
 }
 
@@ -121,6 +200,7 @@ pub struct ConstantScoreScorer<T: DocIterator> {
     score: f32,
     iterator: T,
     cost: usize,
+    support_two_phase: bool,
 }
 
 impl<T: DocIterator> ConstantScoreScorer<T> {
@@ -129,6 +209,42 @@ impl<T: DocIterator> ConstantScoreScorer<T> {
             score,
             iterator,
             cost,
+            support_two_phase: false,
+        }
+    }
+
+}
+
+impl<T: DocIterator> ConstantScoreScorer<DocIdSetIterator<T>> {
+    /// Iterates over the docs of `set`, costing as much as the set, `None` if
+    /// the set matches no doc. The docs are looked up in the bits of a set
+    /// with random access, see `DocIdSetIterator`.
+    pub fn from_doc_id_set<S: DocIdSet<Iter = T>>(
+        score: f32,
+        set: &S,
+        max_doc: DocId,
+    ) -> Result<Option<ConstantScoreScorer<DocIdSetIterator<T>>>> {
+        match DocIdSetIterator::new(set, max_doc)? {
+            Some(iterator) => {
+                let cost = iterator.cost();
+                Ok(Some(ConstantScoreScorer::new(score, iterator, cost)))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+impl ConstantScoreScorer<Box<dyn Scorer>> {
+    /// Wrap a `Scorer` whose score should be ignored, the *two phase iteration*
+    /// of the inner scorer is kept.
+    pub fn from_scorer(score: f32, scorer: Box<dyn Scorer>) -> Self {
+        let cost = scorer.cost();
+        let support_two_phase = scorer.support_two_phase();
+        ConstantScoreScorer {
+            score,
+            iterator: scorer,
+            cost,
+            support_two_phase,
         }
     }
 }
@@ -137,6 +253,10 @@ impl<T: DocIterator> Scorer for ConstantScoreScorer<T> {
     fn score(&mut self) -> Result<f32> {
         Ok(self.score)
     }
+
+    fn support_two_phase(&self) -> bool {
+        self.support_two_phase
+    }
 }
 
 impl<T: DocIterator> DocIterator for ConstantScoreScorer<T> {
@@ -160,6 +280,14 @@ impl<T: DocIterator> DocIterator for ConstantScoreScorer<T> {
         self.cost
     }
 
+    fn matches(&mut self) -> Result<bool> {
+        self.iterator.matches()
+    }
+
+    fn match_cost(&self) -> f32 {
+        self.iterator.match_cost()
+    }
+
     fn approximate_next(&mut self) -> Result<DocId> {
         self.iterator.approximate_next()
     }
@@ -169,6 +297,65 @@ impl<T: DocIterator> DocIterator for ConstantScoreScorer<T> {
     }
 }
 
+/// Iterates over the docs of a `DocIdSet`.
+///
+/// When the set has random access `bits`, a target is first looked up in the
+/// bits, so that advancing to a matching doc, as the sub iterators of a
+/// conjunction mostly do, doesn't search the set.
+pub struct DocIdSetIterator<T: DocIterator> {
+    iterator: T,
+    bits: Option<BitsRef>,
+    max_doc: DocId,
+    doc: DocId,
+}
+
+impl<T: DocIterator> DocIdSetIterator<T> {
+    /// `None` if the set matches no doc.
+    pub fn new<S: DocIdSet<Iter = T>>(set: &S, max_doc: DocId) -> Result<Option<Self>> {
+        match set.iterator()? {
+            Some(iterator) => Ok(Some(DocIdSetIterator {
+                iterator,
+                bits: set.bits()?,
+                max_doc,
+                doc: -1,
+            })),
+            None => Ok(None),
+        }
+    }
+}
+
+impl<T: DocIterator> DocIterator for DocIdSetIterator<T> {
+    fn doc_id(&self) -> DocId {
+        self.doc
+    }
+
+    fn next(&mut self) -> Result<DocId> {
+        let target = self.doc + 1;
+        self.advance(target)
+    }
+
+    fn advance(&mut self, target: DocId) -> Result<DocId> {
+        // the set's iterator stays behind the docs found in the bits, it may
+        // also be on the first match from `target` on already
+        let in_bits = match self.bits {
+            Some(ref bits) if target < self.max_doc => bits.get(target as usize)?,
+            _ => false,
+        };
+        self.doc = if in_bits {
+            target
+        } else if self.iterator.doc_id() >= target {
+            self.iterator.doc_id()
+        } else {
+            self.iterator.advance(target)?
+        };
+        Ok(self.doc)
+    }
+
+    fn cost(&self) -> usize {
+        self.iterator.cost()
+    }
+}
+
 pub struct AllDocsIterator {
     doc: DocId,
     max_doc: DocId,
@@ -288,17 +475,28 @@ impl<C: Codec> ConstantScoreWeight<C> {
 impl<C: Codec> Weight<C> for ConstantScoreWeight<C> {
     fn create_scorer(&self, reader: &LeafReaderContext<'_, C>) -> Result<Option<Box<dyn Scorer>>> {
         if let Some(inner_scorer) = self.sub_weight.create_scorer(reader)? {
-            let cost = inner_scorer.cost();
-            Ok(Some(Box::new(ConstantScoreScorer {
-                score: self.query_weight,
-                iterator: inner_scorer,
-                cost,
-            })))
+            Ok(Some(Box::new(ConstantScoreScorer::from_scorer(
+                self.query_weight,
+                inner_scorer,
+            ))))
         } else {
             Ok(None)
         }
     }
 
+    fn bulk_scorer(
+        &self,
+        reader: &LeafReaderContext<'_, C>,
+    ) -> Result<Option<Box<dyn BulkScorer>>> {
+        match self.sub_weight.bulk_scorer(reader)? {
+            Some(bulk_scorer) => Ok(Some(Box::new(ConstantScoreBulkScorer {
+                bulk_scorer,
+                score: self.query_weight,
+            }))),
+            None => Ok(None),
+        }
+    }
+
     fn query_type(&self) -> &'static str {
         CONSTANT
     }
@@ -348,6 +546,38 @@ impl<C: Codec> Weight<C> for ConstantScoreWeight<C> {
     }
 }
 
+/// Collects the matches of the bulk scorer of the wrapped query with a
+/// constant score.
+struct ConstantScoreBulkScorer {
+    bulk_scorer: Box<dyn BulkScorer>,
+    score: f32,
+}
+
+impl BulkScorer for ConstantScoreBulkScorer {
+    fn score(
+        &mut self,
+        collector: &mut dyn FnMut(DocId, &mut dyn Scorer) -> Result<()>,
+        accept_docs: Option<&dyn Bits>,
+        min: DocId,
+        max: DocId,
+    ) -> Result<DocId> {
+        let mut scorer = ScoreAndDoc::new(self.score);
+        self.bulk_scorer.score(
+            &mut |doc: DocId, _inner: &mut dyn Scorer| {
+                scorer.doc = doc;
+                collector(doc, &mut scorer)
+            },
+            accept_docs,
+            min,
+            max,
+        )
+    }
+
+    fn cost(&self) -> usize {
+        self.bulk_scorer.cost()
+    }
+}
+
 impl<C: Codec> fmt::Display for ConstantScoreWeight<C> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
@@ -357,3 +587,178 @@ impl<C: Codec> fmt::Display for ConstantScoreWeight<C> {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::doc::NumericDocValuesField;
+    use core::codec::CodecEnum;
+    use core::index::{Fieldable, IndexReader, IndexWriter, IndexWriterConfig};
+    use core::search::boolean_query::BooleanQuery;
+    use core::search::bulk_scorer::DefaultBulkScorer;
+    use core::search::collector::top_docs::TopDocsCollector;
+    use core::search::collector::SearchCollector;
+    use core::search::searcher::DefaultIndexSearcher;
+    use core::store::RAMDirectory;
+    use core::util::bit_set::{BitSet, FixedBitSet};
+    use core::util::doc_id_set::{BitDocIdSet, IntArrayDocIdSet};
+
+    use std::sync::Arc;
+
+    #[test]
+    fn test_match_all_boost() {
+        let dir = Arc::new(RAMDirectory::default());
+        let writer = IndexWriter::new(dir, Arc::new(IndexWriterConfig::default())).unwrap();
+        for i in 0..10 {
+            let doc: Vec<Box<dyn Fieldable>> = vec![Box::new(NumericDocValuesField::new("id", i))];
+            writer.add_document(doc).unwrap();
+        }
+        let reader = writer.get_reader(true, false).unwrap();
+        let leaves = reader.leaves();
+        assert_eq!(leaves.len(), 1);
+
+        let weight = MatchAllDocsWeight::new(2.5);
+        let mut scorer = weight.create_scorer(&leaves[0]).unwrap().unwrap();
+        assert_eq!(scorer.cost(), 10);
+        assert_eq!(scorer.next().unwrap(), 0);
+        assert!((scorer.score().unwrap() - 2.5).abs() < ::std::f32::EPSILON);
+
+        // only the accepted docs are collected
+        let mut accept_docs = FixedBitSet::new(10);
+        accept_docs.set(3);
+        accept_docs.set(7);
+        let mut scorer = weight.create_scorer(&leaves[0]).unwrap().unwrap();
+        let mut collector = TopDocsCollector::new(10);
+        collector.set_next_reader(&leaves[0]).unwrap();
+        DefaultBulkScorer::new(scorer.as_mut())
+            .score(&mut collector, Some(&accept_docs), 0, NO_MORE_DOCS)
+            .unwrap();
+        let top_docs = collector.top_docs();
+        assert_eq!(top_docs.total_hits(), 2);
+        for score_doc in top_docs.score_docs() {
+            assert!(score_doc.doc_id() == 3 || score_doc.doc_id() == 7);
+            assert!((score_doc.score() - 2.5).abs() < ::std::f32::EPSILON);
+        }
+        writer.close().unwrap();
+    }
+
+    #[test]
+    fn test_match_all_value_for_normalization() {
+        let weight: Box<dyn Weight<CodecEnum>> = Box::new(MatchAllDocsWeight::new(1.0));
+        assert!((weight.value_for_normalization() - 1.0).abs() < ::std::f32::EPSILON);
+        let mut weight: Box<dyn Weight<CodecEnum>> = Box::new(MatchAllDocsWeight::new(2.0));
+        assert!((weight.value_for_normalization() - 4.0).abs() < ::std::f32::EPSILON);
+        weight.normalize(0.5, 1.0);
+        assert!((weight.value_for_normalization() - 1.0).abs() < ::std::f32::EPSILON);
+
+        // the match-all clauses do not change the norms of a boolean query
+        let dir = Arc::new(RAMDirectory::default());
+        let writer = IndexWriter::new(dir, Arc::new(IndexWriterConfig::default())).unwrap();
+        let doc: Vec<Box<dyn Fieldable>> = vec![Box::new(NumericDocValuesField::new("id", 1))];
+        writer.add_document(doc).unwrap();
+        let reader = Arc::new(writer.get_reader(true, false).unwrap());
+        let searcher = DefaultIndexSearcher::new(reader);
+        let musts: Vec<Box<dyn Query<CodecEnum>>> = vec![
+            Box::new(MatchAllDocsQuery::new()),
+            Box::new(MatchAllDocsQuery::with_boost(3.0)),
+        ];
+        let query = BooleanQuery::build(musts, vec![], vec![]).unwrap();
+        let weight = query.create_weight(&searcher, true).unwrap();
+        assert!(weight.value_for_normalization().abs() < ::std::f32::EPSILON);
+        writer.close().unwrap();
+    }
+
+    #[test]
+    fn test_constant_score_scorer_from_doc_id_set() {
+        let mut bits = FixedBitSet::new(10);
+        bits.set(2);
+        bits.set(5);
+        let set = BitDocIdSet::new(Arc::new(bits), 2);
+        let mut scorer = ConstantScoreScorer::from_doc_id_set(1.5, &set, 10)
+            .unwrap()
+            .unwrap();
+        assert_eq!(scorer.cost(), 2);
+        assert_eq!(scorer.next().unwrap(), 2);
+        assert!((scorer.score().unwrap() - 1.5).abs() < ::std::f32::EPSILON);
+        assert_eq!(scorer.advance(3).unwrap(), 5);
+        assert_eq!(scorer.next().unwrap(), NO_MORE_DOCS);
+
+        let set = IntArrayDocIdSet::new(vec![4, NO_MORE_DOCS], 1);
+        let mut scorer = ConstantScoreScorer::from_doc_id_set(1.0, &set, 10)
+            .unwrap()
+            .unwrap();
+        assert_eq!(scorer.cost(), 1);
+        assert_eq!(scorer.next().unwrap(), 4);
+        assert_eq!(scorer.next().unwrap(), NO_MORE_DOCS);
+    }
+
+    #[test]
+    fn test_constant_score_scorer_random_access() {
+        let mut bits = FixedBitSet::new(10);
+        bits.set(2);
+        bits.set(5);
+        bits.set(8);
+        let set = BitDocIdSet::new(Arc::new(bits), 3);
+        let mut scorer = ConstantScoreScorer::from_doc_id_set(1.0, &set, 10)
+            .unwrap()
+            .unwrap();
+        // a matching target is found in the bits
+        assert_eq!(scorer.advance(5).unwrap(), 5);
+        assert_eq!(scorer.next().unwrap(), 8);
+        assert_eq!(scorer.next().unwrap(), NO_MORE_DOCS);
+
+        let mut scorer = ConstantScoreScorer::from_doc_id_set(1.0, &set, 10)
+            .unwrap()
+            .unwrap();
+        assert_eq!(scorer.next().unwrap(), 2);
+        assert_eq!(scorer.advance(4).unwrap(), 5);
+        assert_eq!(scorer.advance(8).unwrap(), 8);
+        assert_eq!(scorer.advance(9).unwrap(), NO_MORE_DOCS);
+
+        let set = BitDocIdSet::new(Arc::new(FixedBitSet::new(10)), 0);
+        let mut scorer = ConstantScoreScorer::from_doc_id_set(1.0, &set, 10)
+            .unwrap()
+            .unwrap();
+        assert_eq!(scorer.advance(3).unwrap(), NO_MORE_DOCS);
+    }
+
+    #[test]
+    fn test_match_all_bulk_scorer() {
+        let dir = Arc::new(RAMDirectory::default());
+        let writer = IndexWriter::new(dir, Arc::new(IndexWriterConfig::default())).unwrap();
+        for i in 0..10 {
+            let doc: Vec<Box<dyn Fieldable>> = vec![Box::new(NumericDocValuesField::new("id", i))];
+            writer.add_document(doc).unwrap();
+        }
+        let reader = writer.get_reader(true, false).unwrap();
+        let leaves = reader.leaves();
+
+        let weight = MatchAllDocsWeight::new(2.5);
+        let mut bulk_scorer = weight.bulk_scorer(&leaves[0]).unwrap().unwrap();
+        assert_eq!(bulk_scorer.cost(), 10);
+
+        let mut accept_docs = FixedBitSet::new(10);
+        for doc in &[1, 3, 4, 8] {
+            accept_docs.set(*doc);
+        }
+        let mut collected = vec![];
+        let mut collect = |doc: DocId, scorer: &mut dyn Scorer| -> Result<()> {
+            assert_eq!(scorer.doc_id(), doc);
+            assert!((scorer.score()? - 2.5).abs() < ::std::f32::EPSILON);
+            collected.push(doc);
+            Ok(())
+        };
+        let next = bulk_scorer
+            .score(&mut collect, Some(&accept_docs), 2, 6)
+            .unwrap();
+        assert_eq!(next, 6);
+        let next = bulk_scorer
+            .score(&mut collect, Some(&accept_docs), 6, NO_MORE_DOCS)
+            .unwrap();
+        assert_eq!(next, NO_MORE_DOCS);
+        let next = bulk_scorer.score(&mut collect, None, 0, 2).unwrap();
+        assert_eq!(next, 2);
+        assert_eq!(collected, vec![3, 4, 8, 0, 1]);
+        writer.close().unwrap();
+    }
+}
@@ -48,6 +48,18 @@ impl<S: Scorer> Scorer for MinScoreScorer<S> {
     fn support_two_phase(&self) -> bool {
         true
     }
+
+    fn set_min_competitive_score(&mut self, min_score: f32) {
+        self.origin.set_min_competitive_score(min_score)
+    }
+
+    fn max_score(&mut self, up_to: DocId) -> Result<f32> {
+        self.origin.max_score(up_to)
+    }
+
+    fn advance_shallow(&mut self, target: DocId) -> Result<DocId> {
+        self.origin.advance_shallow(target)
+    }
 }
 
 impl<S: Scorer> DocIterator for MinScoreScorer<S> {
@@ -0,0 +1,130 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::search::{DocIterator, Scorer, NO_MORE_DOCS};
+use core::util::DocId;
+use error::Result;
+
+/// A scorer matching the docs on which at least `minimum_should_match` of its
+/// sub scorers match, the score is the sum of the scores of the matching sub
+/// scorers.
+pub struct MinShouldMatchSumScorer<T: Scorer> {
+    scorers: Vec<T>,
+    minimum_should_match: usize,
+    doc: DocId,
+    cost: usize,
+}
+
+impl<T: Scorer> MinShouldMatchSumScorer<T> {
+    pub fn new(scorers: Vec<T>, minimum_should_match: usize) -> MinShouldMatchSumScorer<T> {
+        assert!(minimum_should_match > 0 && minimum_should_match <= scorers.len());
+
+        // a match needs at least one of the `n - minimum_should_match + 1` least
+        // costly scorers to match
+        let mut costs: Vec<usize> = scorers.iter().map(|s| s.cost()).collect();
+        costs.sort();
+        let cost = costs
+            .iter()
+            .take(scorers.len() - minimum_should_match + 1)
+            .sum();
+
+        MinShouldMatchSumScorer {
+            scorers,
+            minimum_should_match,
+            doc: -1,
+            cost,
+        }
+    }
+
+    fn do_next(&mut self, target: DocId) -> Result<DocId> {
+        let mut target = target;
+        let mut docs = Vec::with_capacity(self.scorers.len());
+        loop {
+            docs.clear();
+            for scorer in &mut self.scorers {
+                if scorer.doc_id() < target {
+                    scorer.advance(target)?;
+                }
+                docs.push(scorer.doc_id());
+            }
+            docs.sort();
+
+            // no doc before the `minimum_should_match`-th smallest doc can match
+            let candidate = docs[self.minimum_should_match - 1];
+            if candidate == NO_MORE_DOCS || docs[0] == candidate {
+                return Ok(candidate);
+            }
+            target = candidate;
+        }
+    }
+}
+
+impl<T: Scorer> Scorer for MinShouldMatchSumScorer<T> {
+    fn score(&mut self) -> Result<f32> {
+        let mut score = 0f32;
+        for scorer in &mut self.scorers {
+            if scorer.doc_id() == self.doc {
+                score += scorer.score()?;
+            }
+        }
+        Ok(score)
+    }
+}
+
+impl<T: Scorer> DocIterator for MinShouldMatchSumScorer<T> {
+    fn doc_id(&self) -> DocId {
+        self.doc
+    }
+
+    fn next(&mut self) -> Result<DocId> {
+        if self.doc != NO_MORE_DOCS {
+            let target = self.doc + 1;
+            self.doc = self.do_next(target)?;
+        }
+        Ok(self.doc)
+    }
+
+    fn advance(&mut self, target: DocId) -> Result<DocId> {
+        self.doc = self.do_next(target)?;
+        Ok(self.doc)
+    }
+
+    fn cost(&self) -> usize {
+        self.cost
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::search::tests::*;
+
+    #[test]
+    fn test_min_should_match() {
+        let s1 = create_mock_scorer(vec![1, 2, 4, 7, 9]);
+        let s2 = create_mock_scorer(vec![2, 3, 4, 8, 9]);
+        let s3 = create_mock_scorer(vec![4, 5, 7, 9]);
+        let mut scorer = MinShouldMatchSumScorer::new(vec![s1, s2, s3], 2);
+        assert_eq!(scorer.cost(), 9);
+
+        assert_eq!(scorer.next().unwrap(), 2);
+        assert!((scorer.score().unwrap() - 4.0).abs() < ::std::f32::EPSILON);
+        assert_eq!(scorer.next().unwrap(), 4);
+        assert!((scorer.score().unwrap() - 12.0).abs() < ::std::f32::EPSILON);
+        assert_eq!(scorer.advance(5).unwrap(), 7);
+        assert!((scorer.score().unwrap() - 14.0).abs() < ::std::f32::EPSILON);
+        assert_eq!(scorer.next().unwrap(), 9);
+        assert!((scorer.score().unwrap() - 27.0).abs() < ::std::f32::EPSILON);
+        assert_eq!(scorer.next().unwrap(), NO_MORE_DOCS);
+    }
+}
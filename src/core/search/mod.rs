@@ -16,26 +16,33 @@ use std::collections::hash_map::DefaultHasher;
 use std::collections::HashMap;
 use std::fmt;
 use std::fmt::Display;
+use std::f32;
 use std::hash::{Hash, Hasher};
 use std::i32;
 
 use core::codec::Codec;
 use core::index::{LeafReaderContext, SearchLeafReader};
+use core::search::bulk_scorer::BulkScorer;
 use core::search::explanation::Explanation;
 use core::search::searcher::{IndexSearcher, SearchPlanBuilder};
 use core::search::statistics::CollectionStatistics;
 use core::search::statistics::TermStatistics;
 use core::search::term_query::TermQuery;
 use core::search::top_docs::TopDocs;
-use core::util::{DocId, IndexedContext, KeyedContext, VariantValue};
+use core::util::{BitsRef, DocId, IndexedContext, KeyedContext, VariantValue};
 use error::Result;
 
 pub mod collector;
 pub mod conjunction;
 pub mod disjunction;
+pub mod drill_sideways;
+pub mod facets;
 pub mod filter_query;
+pub mod filtered_query;
+pub mod intervals;
 pub mod match_all;
 pub mod min_score;
+pub mod min_should_match;
 pub mod point_range;
 pub mod posting_iterator;
 pub mod spans;
@@ -44,18 +51,30 @@ pub mod bulk_scorer;
 pub mod disi;
 pub mod field_comparator;
 pub mod req_opt;
+pub mod req_penalty;
 pub mod rescorer;
 pub mod search_group;
 pub mod sort;
 pub mod sort_field;
+pub mod spell_checker;
+pub mod suggester;
 pub mod top_docs;
 pub mod util;
+pub mod value_source;
 
 // Queries
+pub mod blended_term_query;
+pub mod block_max_wand;
 pub mod boolean_query;
 pub mod boost;
+pub mod doc_values_range_query;
+pub mod function_score_query;
+pub mod multi_term_query;
 pub mod phrase_query;
+pub mod position_range_query;
 pub mod query_string;
+pub mod regexp_query;
+pub mod synonym_query;
 pub mod term_query;
 
 // Scorers
@@ -63,6 +82,9 @@ pub mod term_scorer;
 
 // Similarities
 pub mod bm25_similarity;
+pub mod lm_similarity;
+pub mod per_field_similarity;
+pub mod tfidf_similarity;
 
 // IndexSearcher
 pub mod searcher;
@@ -244,6 +266,42 @@ pub trait Scorer: DocIterator {
     fn score_feature(&mut self) -> Result<Vec<FeatureResult>> {
         unimplemented!()
     }
+
+    /// Optional method: tell the scorer that its iterator may safely ignore
+    /// all documents whose score is less than the given `min_score`.
+    /// This is a no-op by default.
+    ///
+    /// This method may only be called from collectors that don't need the
+    /// exact total hit count.
+    fn set_min_competitive_score(&mut self, _min_score: f32) {}
+
+    /// Returns an upper bound of the score of all documents from the current
+    /// doc up to `up_to` (inclusive), or `f32::INFINITY` if no bound is known.
+    fn max_score(&mut self, _up_to: DocId) -> Result<f32> {
+        Ok(f32::INFINITY)
+    }
+
+    /// Returns the last doc id of the block of docs that contains `target`,
+    /// among which `max_score` can give a tighter bound. Unlike `advance`,
+    /// this doesn't move the iterator. Returns `NO_MORE_DOCS` by default,
+    /// i.e. the whole segment is a single block.
+    fn advance_shallow(&mut self, _target: DocId) -> Result<DocId> {
+        Ok(NO_MORE_DOCS)
+    }
+
+    /// Returns the two-phase view of this scorer if it supports two-phase
+    /// iteration, `None` otherwise. A trait object can be viewed with
+    /// `TwoPhaseIterator::new` instead.
+    fn two_phase(&mut self) -> Option<TwoPhaseIterator<'_, Self>>
+    where
+        Self: Sized,
+    {
+        if self.support_two_phase() {
+            Some(TwoPhaseIterator::new(self))
+        } else {
+            None
+        }
+    }
 }
 
 impl Scorer for Box<dyn Scorer> {
@@ -262,6 +320,18 @@ impl Scorer for Box<dyn Scorer> {
     fn score_feature(&mut self) -> Result<Vec<FeatureResult>> {
         (**self).score_feature()
     }
+
+    fn set_min_competitive_score(&mut self, min_score: f32) {
+        (**self).set_min_competitive_score(min_score)
+    }
+
+    fn max_score(&mut self, up_to: DocId) -> Result<f32> {
+        (**self).max_score(up_to)
+    }
+
+    fn advance_shallow(&mut self, target: DocId) -> Result<DocId> {
+        (**self).advance_shallow(target)
+    }
 }
 
 impl DocIterator for Box<dyn Scorer> {
@@ -302,6 +372,72 @@ impl DocIterator for Box<dyn Scorer> {
     }
 }
 
+/// A two-phase view of a scorer: its `approximation` iterates over a superset
+/// of the matching docs, and `matches` tells whether the doc the approximation
+/// is on really matches, which may be costly. Conjunctions can intersect the
+/// approximations of their sub scorers, and only check the matches of the docs
+/// all the approximations agree on, the lowest `match_cost` first.
+///
+/// This is a view over the two-phase methods of `DocIterator`: moving the
+/// approximation moves the wrapped scorer.
+pub struct TwoPhaseIterator<'a, S: DocIterator + ?Sized + 'a> {
+    scorer: &'a mut S,
+}
+
+impl<'a, S: DocIterator + ?Sized + 'a> TwoPhaseIterator<'a, S> {
+    pub fn new(scorer: &'a mut S) -> TwoPhaseIterator<'a, S> {
+        TwoPhaseIterator { scorer }
+    }
+
+    /// Returns the iterator over the approximate matches.
+    pub fn approximation(&mut self) -> Approximation<'_, S> {
+        Approximation {
+            scorer: &mut *self.scorer,
+        }
+    }
+
+    /// Whether the doc the approximation is on matches, see `DocIterator::matches`.
+    pub fn matches(&mut self) -> Result<bool> {
+        self.scorer.matches()
+    }
+
+    pub fn match_cost(&self) -> f32 {
+        self.scorer.match_cost()
+    }
+
+    /// Moves the approximation to the next doc that matches.
+    pub fn next_match(&mut self) -> Result<DocId> {
+        let mut doc = self.scorer.approximate_next()?;
+        while doc != NO_MORE_DOCS && !self.scorer.matches()? {
+            doc = self.scorer.approximate_next()?;
+        }
+        Ok(doc)
+    }
+}
+
+/// The approximation of a `TwoPhaseIterator`.
+pub struct Approximation<'a, S: DocIterator + ?Sized + 'a> {
+    scorer: &'a mut S,
+}
+
+impl<'a, S: DocIterator + ?Sized + 'a> DocIterator for Approximation<'a, S> {
+    fn doc_id(&self) -> DocId {
+        self.scorer.doc_id()
+    }
+
+    fn next(&mut self) -> Result<DocId> {
+        self.scorer.approximate_next()
+    }
+
+    fn advance(&mut self, target: DocId) -> Result<DocId> {
+        self.scorer.approximate_advance(target)
+    }
+
+    fn cost(&self) -> usize {
+        self.scorer.cost()
+    }
+}
+
 // helper function for doc iterator support two phase
 pub fn two_phase_next(scorer: &mut dyn Scorer) -> Result<DocId> {
     let mut doc = scorer.doc_id();
@@ -343,6 +479,16 @@ pub trait Query<C: Codec>: Display {
 pub trait Weight<C: Codec>: Display {
     fn create_scorer(&self, reader: &LeafReaderContext<'_, C>) -> Result<Option<Box<dyn Scorer>>>;
 
+    /// A `BulkScorer` collecting the matches of the segment faster than by
+    /// iterating over the scorer, `None` to collect them with the scorer of
+    /// `create_scorer`.
+    fn bulk_scorer(
+        &self,
+        _reader: &LeafReaderContext<'_, C>,
+    ) -> Result<Option<Box<dyn BulkScorer>>> {
+        Ok(None)
+    }
+
     fn hash_code(&self) -> u32 {
         let key = format!("{}", self);
         let mut hasher = DefaultHasher::new();
@@ -481,6 +627,25 @@ pub trait SimScorer: Send {
     /// Computes the amount of a sloppy phrase match, based on an edit distance.
     fn compute_slop_factor(&self, distance: i32) -> f32;
 
+    /// Returns an upper bound of the score of any document, whatever its
+    /// freq and norm, or `f32::INFINITY` if the score is unbounded.
+    fn max_score(&self) -> f32 {
+        f32::INFINITY
+    }
+
+    /// Returns an upper bound of the score of the documents whose freq is at
+    /// most `max_freq`, whatever their norm. Defaults to `max_score`.
+    fn max_score_with_freq(&self, _max_freq: f32) -> f32 {
+        self.max_score()
+    }
+
+    /// Returns an upper bound of the score of the documents whose freq is at
+    /// most `max_freq` and whose norm is `norm`, as read from the norms of
+    /// the field. Defaults to `max_score_with_freq`.
+    fn max_score_with_freq_and_norm(&self, max_freq: f32, _norm: i64) -> f32 {
+        self.max_score_with_freq(max_freq)
+    }
+
     // Calculate a scoring factor based on the data in the payload.
     // fn compute_payload_factor(&self, doc: DocId, start: i32, end: i32, payload: &Payload);
 }
@@ -635,27 +800,28 @@ pub trait DocIdSet: Send + Sync {
     /// are no docs that match.
     fn iterator(&self) -> Result<Option<Self::Iter>>;
 
-    // Optionally provides `Bits` interface for random access
-    // to matching documents.
-    // None, if this `DocIdSet` does not support random access.
-    // In contrast to #iterator(), a return value of None
-    // *does not* imply that no documents match the filter!
-    // The default implementation does not provide random access, so you
-    // only need to implement this method if your DocIdSet can
-    // guarantee random access to every docid in O(1) time without
-    // external disk access (as `Bits` interface cannot return
-    // IOError. This is generally true for bit sets
-    // like `FixedBitSet`, which return
-    // itself if they are used as `DocIdSet`.
-    //    fn bits(&self) -> Result<Option<ImmutableBitSetRef>> {
-    //        Ok(None)
-    //    }
+    /// Optionally provides a `Bits` interface for random access
+    /// to matching documents.
+    /// None, if this `DocIdSet` does not support random access.
+    /// In contrast to #iterator(), a return value of None
+    /// *does not* imply that no documents match the filter!
+    /// The default implementation does not provide random access, so you
+    /// only need to implement this method if your DocIdSet can
+    /// guarantee random access to every docid in O(1) time without
+    /// external disk access. This is generally true for bit sets
+    /// like `FixedBitSet`, which return itself if they are used as `DocIdSet`.
+    fn bits(&self) -> Result<Option<BitsRef>> {
+        Ok(None)
+    }
 }
 
 #[cfg(test)]
 pub mod tests {
     use super::*;
 
+    use rand::prng::XorShiftRng;
+    use rand::{Rng, SeedableRng};
+
     pub struct MockDocIterator {
         doc_ids: Vec<DocId>,
         current_doc_id: DocId,
@@ -784,8 +950,12 @@ pub mod tests {
             false
         }
 
-        fn explain(&self, _reader: &LeafReaderContext<'_, C>, _doc: DocId) -> Result<Explanation> {
-            unimplemented!()
+        fn explain(&self, _reader: &LeafReaderContext<'_, C>, doc: DocId) -> Result<Explanation> {
+            if self.docs.contains(&doc) {
+                Ok(Explanation::new(true, doc as f32, "mock".to_string(), vec![]))
+            } else {
+                Ok(Explanation::new(false, 0.0, "no mock match".to_string(), vec![]))
+            }
         }
     }
 
@@ -893,6 +1063,168 @@ pub mod tests {
         MockTwoPhaseScorer::new(all_docs, invalid_docs)
     }
 
+    /// How the docs of a `SyntheticDocIterator` are spread over `[0, max_doc)`.
+    #[derive(Clone, Copy, Debug)]
+    pub enum DocDistribution {
+        /// Every doc matches with probability `density`.
+        Uniform { density: f64 },
+        /// Runs of `cluster_size` consecutive docs, so that about `density`
+        /// of the docs match overall.
+        Clustered { density: f64, cluster_size: usize },
+        /// Doc `d` matches with probability `1 / (d + 1)^exponent`, so the
+        /// matches get sparser towards the end of the doc id space.
+        PowerLaw { exponent: f64 },
+    }
+
+    /// A `DocIterator` over generated doc ids, to benchmark or stress the
+    /// scorers under a controlled density without building an index.
+    ///
+    /// The docs are generated up front from `seed`, so `cost` is exact and the
+    /// same seed always gives the same docs. `reset` rewinds the iterator, so
+    /// it can be replayed any number of times.
+    pub struct SyntheticDocIterator {
+        doc_ids: Vec<DocId>,
+        // index of the next doc to return in `doc_ids`
+        upto: usize,
+        doc: DocId,
+    }
+
+    impl SyntheticDocIterator {
+        pub fn new(
+            distribution: DocDistribution,
+            max_doc: DocId,
+            seed: u64,
+        ) -> SyntheticDocIterator {
+            assert!(max_doc >= 0);
+            // `from_seed` needs 16 bytes, and the second half keeps them from being all zeros
+            let mut bytes = [0u8; 16];
+            bytes[..8].copy_from_slice(&seed.to_le_bytes());
+            bytes[8..].copy_from_slice(&(!seed).to_le_bytes());
+            let mut rng = XorShiftRng::from_seed(bytes);
+            let mut doc_ids = Vec::new();
+            match distribution {
+                DocDistribution::Uniform { density } => {
+                    for doc in 0..max_doc {
+                        if rng.gen::<f64>() < density {
+                            doc_ids.push(doc);
+                        }
+                    }
+                }
+                DocDistribution::Clustered {
+                    density,
+                    cluster_size,
+                } => {
+                    assert!(cluster_size > 0);
+                    let start_probability = density / cluster_size as f64;
+                    let mut doc = 0;
+                    while doc < max_doc {
+                        if rng.gen::<f64>() < start_probability {
+                            let end = max_doc.min(doc + cluster_size as DocId);
+                            doc_ids.extend(doc..end);
+                            doc = end;
+                        } else {
+                            doc += 1;
+                        }
+                    }
+                }
+                DocDistribution::PowerLaw { exponent } => {
+                    for doc in 0..max_doc {
+                        if rng.gen::<f64>() < (f64::from(doc) + 1.0).powf(-exponent) {
+                            doc_ids.push(doc);
+                        }
+                    }
+                }
+            }
+
+            SyntheticDocIterator {
+                doc_ids,
+                upto: 0,
+                doc: -1,
+            }
+        }
+
+        pub fn doc_ids(&self) -> &[DocId] {
+            &self.doc_ids
+        }
+
+        /// Rewinds the iterator before its first doc.
+        pub fn reset(&mut self) {
+            self.upto = 0;
+            self.doc = -1;
+        }
+
+        fn doc_at(&mut self, index: usize) -> DocId {
+            if index < self.doc_ids.len() {
+                self.doc = self.doc_ids[index];
+                self.upto = index + 1;
+            } else {
+                self.doc = NO_MORE_DOCS;
+                self.upto = self.doc_ids.len();
+            }
+            self.doc
+        }
+    }
+
+    impl DocIterator for SyntheticDocIterator {
+        fn doc_id(&self) -> DocId {
+            self.doc
+        }
+
+        fn next(&mut self) -> Result<DocId> {
+            let upto = self.upto;
+            Ok(self.doc_at(upto))
+        }
+
+        fn advance(&mut self, target: DocId) -> Result<DocId> {
+            let upto = self.upto;
+            let index = match self.doc_ids[upto..].binary_search(&target) {
+                Ok(i) | Err(i) => upto + i,
+            };
+            Ok(self.doc_at(index))
+        }
+
+        fn cost(&self) -> usize {
+            self.doc_ids.len()
+        }
+    }
+
+    #[test]
+    fn test_synthetic_doc_iterator() {
+        let distributions = [
+            DocDistribution::Uniform { density: 0.1 },
+            DocDistribution::Clustered {
+                density: 0.1,
+                cluster_size: 16,
+            },
+            DocDistribution::PowerLaw { exponent: 0.5 },
+        ];
+        for distribution in &distributions {
+            let mut iter = SyntheticDocIterator::new(*distribution, 10_000, 42);
+            let docs = iter.doc_ids().to_vec();
+            assert!(docs.windows(2).all(|w| w[0] < w[1]));
+            assert!(docs.iter().all(|d| *d >= 0 && *d < 10_000));
+
+            let mut count = 0;
+            while iter.next().unwrap() != NO_MORE_DOCS {
+                assert_eq!(iter.doc_id(), docs[count]);
+                count += 1;
+            }
+            assert_eq!(count, iter.cost());
+
+            iter.reset();
+            let target = docs[docs.len() / 2];
+            assert_eq!(iter.advance(target).unwrap(), target);
+            assert_eq!(iter.advance(target + 1).unwrap(), docs[docs.len() / 2 + 1]);
+            assert_eq!(iter.advance(10_000).unwrap(), NO_MORE_DOCS);
+        }
+
+        // about 10% of the docs match, and the same seed gives the same docs
+        let iter = SyntheticDocIterator::new(distributions[0], 10_000, 7);
+        assert!(iter.cost() > 800 && iter.cost() < 1200);
+        let other = SyntheticDocIterator::new(distributions[0], 10_000, 7);
+        assert_eq!(iter.doc_ids(), other.doc_ids());
+    }
+
     #[test]
     fn test_mock_two_phase_scorer() {
         let mut scorer =
@@ -913,4 +1245,26 @@ pub mod tests {
         assert_eq!(scorer.advance(9).unwrap(), 10);
         assert!(scorer.matches().unwrap());
     }
+
+    #[test]
+    fn test_two_phase_iterator() {
+        let mut scorer = create_mock_two_phase_scorer(vec![1, 2, 3, 5, 8], vec![2, 5]);
+        {
+            let mut two_phase = scorer.two_phase().unwrap();
+            assert!((two_phase.match_cost() - 1.0).abs() < f32::EPSILON);
+            assert_eq!(two_phase.approximation().cost(), 5);
+
+            assert_eq!(two_phase.approximation().next().unwrap(), 1);
+            assert!(two_phase.matches().unwrap());
+            assert_eq!(two_phase.approximation().advance(2).unwrap(), 2);
+            assert!(!two_phase.matches().unwrap());
+
+            assert_eq!(two_phase.next_match().unwrap(), 3);
+            assert_eq!(two_phase.next_match().unwrap(), 8);
+            assert_eq!(two_phase.next_match().unwrap(), NO_MORE_DOCS);
+        }
+        assert_eq!(scorer.doc_id(), NO_MORE_DOCS);
+
+        assert!(create_mock_scorer(vec![1, 2]).two_phase().is_none());
+    }
 }
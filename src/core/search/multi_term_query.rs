@@ -0,0 +1,773 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::any::Any;
+use std::cmp::{min, Ordering};
+use std::collections::BinaryHeap;
+use std::fmt;
+
+use core::codec::Codec;
+use core::index::{LeafReaderContext, Term, TermIterator, Terms};
+use core::search::boolean_query::BooleanQuery;
+use core::search::explanation::Explanation;
+use core::search::match_all::ConstantScoreScorer;
+use core::search::posting_iterator::PostingIteratorFlags;
+use core::search::searcher::SearchPlanBuilder;
+use core::search::term_query::TermQuery;
+use core::search::{DocIterator, Query, Scorer, Weight};
+use core::util::{DocId, DocIdSetBuilder};
+
+use error::ErrorKind::IllegalArgument;
+use error::Result;
+
+pub const MULTI_TERM: &str = "multi_term";
+
+/// Default number of terms a `MultiTermQuery` expands to at most.
+pub const DEFAULT_MAX_EXPANSIONS: usize = 50;
+
+/// The greatest number of edits a fuzzy query may allow.
+pub const MAX_EDITS: usize = 2;
+
+/// Which of the matching terms a `MultiTermQuery` keeps when there are more
+/// than `max_expansions` of them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExpansionOrder {
+    /// The terms with the highest doc freq, ties broken by distance.
+    DocFreq,
+    /// The closest terms to the query text, ties broken by doc freq. The
+    /// distance of a prefix query term is the number of bytes after the prefix.
+    Distance,
+}
+
+#[derive(Clone, Debug)]
+enum TermMatcher {
+    Prefix,
+    Fuzzy { text: Vec<char>, max_edits: usize },
+}
+
+/// A term a `MultiTermQuery` expanded to.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct ExpandedTerm {
+    pub(crate) term: Vec<u8>,
+    pub(crate) doc_freq: i32,
+    pub(crate) distance: usize,
+}
+
+/// A query matching the terms of a field which start with a prefix, or which
+/// are within a number of edits of a text, rewritten into a disjunction of
+/// term queries. When no score is needed, the docs of the terms are rather
+/// gathered into a doc id set per segment.
+///
+/// At most `max_expansions` terms are searched. If more terms match, the ones
+/// searched are picked by `ExpansionOrder` as the terms are enumerated, a
+/// warning is logged, and `MultiTermWeight::is_truncated` tells that the
+/// weight was narrowed.
+///
+/// The fuzzy terms are found by computing the edit distance of all the terms
+/// which start with the first `prefix_length` chars of the text, so a non-zero
+/// prefix length makes the expansion much cheaper on large fields.
+pub struct MultiTermQuery {
+    field: String,
+    // only the terms which start with it are candidates
+    prefix: Vec<u8>,
+    matcher: TermMatcher,
+    max_expansions: usize,
+    order: ExpansionOrder,
+    boost: f32,
+}
+
+impl MultiTermQuery {
+    pub fn prefix(field: String, prefix: Vec<u8>) -> MultiTermQuery {
+        MultiTermQuery {
+            field,
+            prefix,
+            matcher: TermMatcher::Prefix,
+            max_expansions: DEFAULT_MAX_EXPANSIONS,
+            order: ExpansionOrder::DocFreq,
+            boost: 1.0f32,
+        }
+    }
+
+    pub fn fuzzy(field: String, text: &str, max_edits: usize) -> Result<MultiTermQuery> {
+        if max_edits > MAX_EDITS {
+            bail!(IllegalArgument(format!(
+                "fuzzy query max_edits must be in [0, {}], got {}",
+                MAX_EDITS, max_edits
+            )));
+        }
+        Ok(MultiTermQuery {
+            field,
+            prefix: Vec::new(),
+            matcher: TermMatcher::Fuzzy {
+                text: text.chars().collect(),
+                max_edits,
+            },
+            max_expansions: DEFAULT_MAX_EXPANSIONS,
+            order: ExpansionOrder::Distance,
+            boost: 1.0f32,
+        })
+    }
+
+    /// Only expands a fuzzy query to the terms which share the first
+    /// `prefix_length` chars of its text. No-op for a prefix query.
+    pub fn with_prefix_length(mut self, prefix_length: usize) -> MultiTermQuery {
+        if let TermMatcher::Fuzzy { ref text, .. } = self.matcher {
+            let prefix: String = text[..min(prefix_length, text.len())].iter().collect();
+            self.prefix = prefix.into_bytes();
+        }
+        self
+    }
+
+    pub fn with_max_expansions(mut self, max_expansions: usize) -> Result<MultiTermQuery> {
+        if max_expansions == 0 {
+            bail!(IllegalArgument("max_expansions must be positive".into()));
+        }
+        self.max_expansions = max_expansions;
+        Ok(self)
+    }
+
+    pub fn with_expansion_order(mut self, order: ExpansionOrder) -> MultiTermQuery {
+        self.order = order;
+        self
+    }
+
+    pub fn with_boost(mut self, boost: f32) -> MultiTermQuery {
+        self.boost = boost;
+        self
+    }
+
+    /// Returns the distance of a term starting with `prefix` to the query, or
+    /// `None` if the term doesn't match.
+    fn distance(&self, term: &[u8]) -> Option<usize> {
+        match self.matcher {
+            TermMatcher::Prefix => Some(term.len() - self.prefix.len()),
+            TermMatcher::Fuzzy {
+                ref text,
+                max_edits,
+            } => {
+                let term: Vec<char> = ::std::str::from_utf8(term).ok()?.chars().collect();
+                edit_distance(text, &term, max_edits)
+            }
+        }
+    }
+
+    /// Calls `visitor` with each term matching the query, in order, and its
+    /// doc freq and distance, until it returns false.
+    pub(crate) fn visit_matching_terms<C: Codec, F>(
+        &self,
+        searcher: &dyn SearchPlanBuilder<C>,
+        mut visitor: F,
+    ) -> Result<()>
+    where
+        F: FnMut(&[u8], i32, usize) -> bool,
+    {
+        searcher.visit_terms_with_prefix(
+            &self.field,
+            &self.prefix,
+            &mut |term: &[u8], doc_freq: i32| -> Result<bool> {
+                Ok(match self.distance(term) {
+                    Some(distance) => visitor(term, doc_freq, distance),
+                    None => true,
+                })
+            },
+        )
+    }
+
+    /// Returns the terms to search, in order, and whether some of the matching
+    /// terms were dropped.
+    fn expand<C: Codec>(
+        &self,
+        searcher: &dyn SearchPlanBuilder<C>,
+    ) -> Result<(Vec<ExpandedTerm>, bool)> {
+        let mut selector = TermSelector::new(self.max_expansions, self.order);
+        self.visit_matching_terms(searcher, |term, doc_freq, distance| {
+            selector.add(term, doc_freq, distance);
+            true
+        })?;
+        let truncated = selector.is_truncated();
+        if truncated {
+            warn!(
+                "{} matches {} terms, only the {} first by {:?} are searched",
+                self,
+                selector.total(),
+                self.max_expansions,
+                self.order
+            );
+        }
+        Ok((selector.into_terms(), truncated))
+    }
+}
+
+/// Keeps the first `max_terms` terms by `ExpansionOrder` of the terms it is
+/// given, without holding the others.
+pub(crate) struct TermSelector {
+    max_terms: usize,
+    order: ExpansionOrder,
+    // the worst of the kept terms on top
+    heap: BinaryHeap<RankedTerm>,
+    total: usize,
+}
+
+impl TermSelector {
+    pub(crate) fn new(max_terms: usize, order: ExpansionOrder) -> TermSelector {
+        TermSelector {
+            max_terms,
+            order,
+            heap: BinaryHeap::with_capacity(max_terms.min(1024)),
+            total: 0,
+        }
+    }
+
+    pub(crate) fn add(&mut self, term: &[u8], doc_freq: i32, distance: usize) {
+        self.total += 1;
+        if self.heap.len() >= self.max_terms {
+            match self.heap.peek() {
+                Some(worst)
+                    if compare_terms(self.order, term, doc_freq, distance, &worst.term)
+                        == Ordering::Less => {}
+                _ => return,
+            }
+            self.heap.pop();
+        }
+        self.heap.push(RankedTerm {
+            term: ExpandedTerm {
+                term: term.to_vec(),
+                doc_freq,
+                distance,
+            },
+            order: self.order,
+        });
+    }
+
+    /// The number of terms given so far.
+    pub(crate) fn total(&self) -> usize {
+        self.total
+    }
+
+    /// Whether some of the terms given were dropped.
+    pub(crate) fn is_truncated(&self) -> bool {
+        self.total > self.heap.len()
+    }
+
+    /// Returns the kept terms, in term order.
+    pub(crate) fn into_terms(self) -> Vec<ExpandedTerm> {
+        let mut terms: Vec<ExpandedTerm> = self.heap.into_iter().map(|t| t.term).collect();
+        terms.sort_by(|a, b| a.term.cmp(&b.term));
+        terms
+    }
+}
+
+/// Compares a term to `other` by `order`, the better term first.
+fn compare_terms(
+    order: ExpansionOrder,
+    term: &[u8],
+    doc_freq: i32,
+    distance: usize,
+    other: &ExpandedTerm,
+) -> Ordering {
+    let by_doc_freq = other.doc_freq.cmp(&doc_freq);
+    let by_distance = distance.cmp(&other.distance);
+    let ord = match order {
+        ExpansionOrder::DocFreq => by_doc_freq.then(by_distance),
+        ExpansionOrder::Distance => by_distance.then(by_doc_freq),
+    };
+    ord.then_with(|| term.cmp(&other.term[..]))
+}
+
+struct RankedTerm {
+    term: ExpandedTerm,
+    order: ExpansionOrder,
+}
+
+impl Ord for RankedTerm {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let term = &self.term;
+        compare_terms(self.order, &term.term, term.doc_freq, term.distance, &other.term)
+    }
+}
+
+impl PartialOrd for RankedTerm {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Eq for RankedTerm {}
+
+impl PartialEq for RankedTerm {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+/// Returns the Levenshtein distance of `a` and `b`, or `None` if it is
+/// greater than `max_edits`.
+fn edit_distance(a: &[char], b: &[char], max_edits: usize) -> Option<usize> {
+    let len_diff = if a.len() > b.len() {
+        a.len() - b.len()
+    } else {
+        b.len() - a.len()
+    };
+    if len_diff > max_edits {
+        return None;
+    }
+
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut curr = vec![0; b.len() + 1];
+    for (i, ca) in a.iter().enumerate() {
+        curr[0] = i + 1;
+        let mut row_min = curr[0];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = prev[j] + if ca == cb { 0 } else { 1 };
+            curr[j + 1] = min(substitution, min(prev[j + 1], curr[j]) + 1);
+            row_min = min(row_min, curr[j + 1]);
+        }
+        // the distance never decreases from one row to the next
+        if row_min > max_edits {
+            return None;
+        }
+        ::std::mem::swap(&mut prev, &mut curr);
+    }
+    if prev[b.len()] <= max_edits {
+        Some(prev[b.len()])
+    } else {
+        None
+    }
+}
+
+impl<C: Codec> Query<C> for MultiTermQuery {
+    fn create_weight(
+        &self,
+        searcher: &dyn SearchPlanBuilder<C>,
+        needs_scores: bool,
+    ) -> Result<Box<dyn Weight<C>>> {
+        let (expanded, truncated) = self.expand(searcher)?;
+        let terms: Vec<Vec<u8>> = expanded.into_iter().map(|t| t.term).collect();
+        // without scores the postings of the terms are merged into a doc id set
+        // per segment, there is no need for the term weights and their stats
+        let weight = if terms.is_empty() || !needs_scores {
+            None
+        } else {
+            let shoulds = terms
+                .iter()
+                .map(|t| {
+                    let term = Term::new(self.field.clone(), t.clone());
+                    Box::new(TermQuery::new(term, self.boost, None)) as Box<dyn Query<C>>
+                })
+                .collect();
+            let query = BooleanQuery::build(vec![], shoulds, vec![])?;
+            Some(query.create_weight(searcher, needs_scores)?)
+        };
+        Ok(Box::new(MultiTermWeight {
+            weight,
+            field: self.field.clone(),
+            terms,
+            truncated,
+            needs_scores,
+            query: format!("{}", self),
+        }))
+    }
+
+    fn extract_terms(&self) -> Vec<TermQuery> {
+        vec![]
+    }
+
+    fn query_type(&self) -> &'static str {
+        MULTI_TERM
+    }
+
+    fn as_any(&self) -> &Any {
+        self
+    }
+}
+
+impl fmt::Display for MultiTermQuery {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.matcher {
+            TermMatcher::Prefix => write!(
+                f,
+                "PrefixQuery(field: {}, prefix: {}, boost: {})",
+                self.field,
+                String::from_utf8_lossy(&self.prefix),
+                self.boost
+            ),
+            TermMatcher::Fuzzy {
+                ref text,
+                max_edits,
+            } => write!(
+                f,
+                "FuzzyQuery(field: {}, text: {}, max_edits: {}, prefix_length: {}, boost: {})",
+                self.field,
+                text.iter().collect::<String>(),
+                max_edits,
+                String::from_utf8_lossy(&self.prefix).chars().count(),
+                self.boost
+            ),
+        }
+    }
+}
+
+pub struct MultiTermWeight<C: Codec> {
+    // `None` if no term matched or no score is needed
+    weight: Option<Box<dyn Weight<C>>>,
+    field: String,
+    // the expanded terms
+    terms: Vec<Vec<u8>>,
+    truncated: bool,
+    needs_scores: bool,
+    query: String,
+}
+
+impl<C: Codec> MultiTermWeight<C> {
+    /// Whether some of the matching terms were dropped.
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /// Matches the docs of any of the terms with a constant score, the
+    /// postings of the terms are merged up front into a doc id set.
+    fn doc_id_set_scorer(
+        &self,
+        leaf_reader: &LeafReaderContext<'_, C>,
+    ) -> Result<Option<Box<dyn Scorer>>> {
+        let reader = leaf_reader.reader;
+        let terms = match reader.terms(&self.field)? {
+            Some(terms) => terms,
+            None => return Ok(None),
+        };
+        let mut builder = DocIdSetBuilder::from_terms(reader.max_doc(), &terms)?;
+        let mut terms_iter = terms.iterator()?;
+        for term in &self.terms {
+            if terms_iter.seek_exact(term)? {
+                let mut postings = terms_iter.postings_with_flags(PostingIteratorFlags::NONE)?;
+                builder.add(&mut postings)?;
+            }
+        }
+        let set = builder.build();
+        match ConstantScoreScorer::from_doc_id_set(0f32, &set, reader.max_doc())? {
+            Some(scorer) => Ok(Some(Box::new(scorer))),
+            None => Ok(None),
+        }
+    }
+}
+
+impl<C: Codec> Weight<C> for MultiTermWeight<C> {
+    fn create_scorer(
+        &self,
+        leaf_reader: &LeafReaderContext<'_, C>,
+    ) -> Result<Option<Box<dyn Scorer>>> {
+        match self.weight {
+            Some(ref weight) => weight.create_scorer(leaf_reader),
+            None if self.terms.is_empty() => Ok(None),
+            None => self.doc_id_set_scorer(leaf_reader),
+        }
+    }
+
+    fn query_type(&self) -> &'static str {
+        MULTI_TERM
+    }
+
+    fn actual_query_type(&self) -> &'static str {
+        match self.weight {
+            Some(ref weight) => weight.query_type(),
+            None => MULTI_TERM,
+        }
+    }
+
+    fn normalize(&mut self, norm: f32, boost: f32) {
+        if let Some(ref mut weight) = self.weight {
+            weight.normalize(norm, boost)
+        }
+    }
+
+    fn value_for_normalization(&self) -> f32 {
+        match self.weight {
+            Some(ref weight) => weight.value_for_normalization(),
+            None => 1.0f32,
+        }
+    }
+
+    fn needs_scores(&self) -> bool {
+        self.needs_scores
+    }
+
+    fn explain(&self, reader: &LeafReaderContext<'_, C>, doc: DocId) -> Result<Explanation> {
+        let weight = match self.weight {
+            Some(ref weight) => weight,
+            None if !self.terms.is_empty() => {
+                let is_match = match self.doc_id_set_scorer(reader)? {
+                    Some(mut scorer) => scorer.advance(doc)? == doc,
+                    None => false,
+                };
+                return Ok(Explanation::new(
+                    is_match,
+                    0.0f32,
+                    format!("{}, without scores", self.query),
+                    vec![],
+                ));
+            }
+            None => {
+                return Ok(Explanation::new(
+                    false,
+                    0.0f32,
+                    format!("{} matches no term", self.query),
+                    vec![],
+                ));
+            }
+        };
+        let explanation = weight.explain(reader, doc)?;
+        if self.truncated {
+            Ok(Explanation::new(
+                explanation.is_match(),
+                explanation.value(),
+                format!("{}, expansion truncated, of:", self.query),
+                vec![explanation],
+            ))
+        } else {
+            Ok(explanation)
+        }
+    }
+}
+
+impl<C: Codec> fmt::Display for MultiTermWeight<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.weight {
+            Some(ref weight) => write!(
+                f,
+                "MultiTermWeight(query: {}, weight: {}, truncated: {})",
+                self.query, weight, self.truncated
+            ),
+            None if !self.terms.is_empty() => write!(
+                f,
+                "MultiTermWeight(query: {}, terms: {}, truncated: {})",
+                self.query,
+                self.terms.len(),
+                self.truncated
+            ),
+            None => write!(f, "MultiTermWeight(query: {}, no term)", self.query),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::analysis::whitespace_tokenizer::WhitespaceTokenizer;
+    use core::analysis::TokenStream;
+    use core::codec::CodecEnum;
+    use core::doc::{Field, FieldType};
+    use core::index::{Fieldable, IndexOptions, IndexReader, IndexWriter, IndexWriterConfig};
+    use core::search::boolean_query::BOOLEAN;
+    use core::search::searcher::DefaultIndexSearcher;
+    use core::search::{DocIterator, NO_MORE_DOCS};
+    use core::store::RAMDirectory;
+
+    use std::io::Cursor;
+    use std::sync::Arc;
+
+    fn chars(s: &str) -> Vec<char> {
+        s.chars().collect()
+    }
+
+    fn expanded(term: &str, doc_freq: i32, distance: usize) -> ExpandedTerm {
+        ExpandedTerm {
+            term: term.as_bytes().to_vec(),
+            doc_freq,
+            distance,
+        }
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance(&chars("lucene"), &chars("lucene"), 0), Some(0));
+        assert_eq!(edit_distance(&chars("lucene"), &chars("lucine"), 2), Some(1));
+        assert_eq!(edit_distance(&chars("lucene"), &chars("luce"), 2), Some(2));
+        assert_eq!(edit_distance(&chars("lucene"), &chars("ulcene"), 2), Some(2));
+        assert_eq!(edit_distance(&chars("lucene"), &chars("luc"), 2), None);
+        assert_eq!(edit_distance(&chars("lucene"), &chars("rucene"), 0), None);
+        assert_eq!(edit_distance(&chars("über"), &chars("uber"), 1), Some(1));
+    }
+
+    #[test]
+    fn test_distance() {
+        let query = MultiTermQuery::prefix("title".into(), b"luc".to_vec());
+        assert_eq!(query.distance(b"lucene"), Some(3));
+
+        let query = MultiTermQuery::fuzzy("title".into(), "lucene", 1)
+            .unwrap()
+            .with_prefix_length(2);
+        assert_eq!(query.prefix, b"lu".to_vec());
+        assert_eq!(query.distance(b"lucine"), Some(1));
+        assert_eq!(query.distance(b"lucid"), None);
+        assert!(MultiTermQuery::fuzzy("title".into(), "lucene", 3).is_err());
+    }
+
+    #[test]
+    fn test_term_selector() {
+        let terms = vec![
+            expanded("a", 1, 0),
+            expanded("b", 10, 2),
+            expanded("c", 5, 1),
+            expanded("d", 5, 2),
+        ];
+        let select = |max_terms: usize, order: ExpansionOrder| {
+            let mut selector = TermSelector::new(max_terms, order);
+            for t in &terms {
+                selector.add(&t.term, t.doc_freq, t.distance);
+            }
+            assert_eq!(selector.total(), terms.len());
+            (selector.is_truncated(), selector.into_terms())
+        };
+
+        assert_eq!(select(4, ExpansionOrder::DocFreq), (false, terms.clone()));
+        assert_eq!(
+            select(2, ExpansionOrder::DocFreq),
+            (true, vec![terms[1].clone(), terms[2].clone()])
+        );
+        assert_eq!(
+            select(3, ExpansionOrder::Distance),
+            (true, vec![terms[0].clone(), terms[1].clone(), terms[2].clone()])
+        );
+    }
+
+    #[test]
+    fn test_expand_terms() {
+        let dir = Arc::new(RAMDirectory::default());
+        let writer = IndexWriter::new(dir, Arc::new(IndexWriterConfig::default())).unwrap();
+        let mut field_type = FieldType::default();
+        field_type.index_options = IndexOptions::DocsAndFreqs;
+        let texts = [
+            "lucene",
+            "lucene search",
+            "lucene index",
+            "lucine",
+            "lucid",
+            "lucent",
+            "lucent light",
+            "rucene",
+        ];
+        for text in &texts {
+            let tokens: Box<dyn TokenStream> = Box::new(WhitespaceTokenizer::new(Box::new(
+                Cursor::new(text.as_bytes().to_vec()),
+            )));
+            let doc: Vec<Box<dyn Fieldable>> = vec![Box::new(Field::new(
+                "body".into(),
+                field_type.clone(),
+                None,
+                Some(tokens),
+            ))];
+            writer.add_document(doc).unwrap();
+        }
+        let reader = Arc::new(writer.get_reader(true, false).unwrap());
+        let searcher = DefaultIndexSearcher::new(Arc::clone(&reader));
+        let leaves = reader.leaves();
+        let expand = |query: &MultiTermQuery| {
+            let (terms, truncated) = query.expand::<CodecEnum>(&searcher).unwrap();
+            let terms: Vec<String> = terms
+                .into_iter()
+                .map(|t| String::from_utf8(t.term).unwrap())
+                .collect();
+            (terms, truncated)
+        };
+
+        let query = MultiTermQuery::prefix("body".into(), b"luc".to_vec());
+        assert!(query.with_max_expansions(0).is_err());
+
+        // the most frequent terms
+        let query = MultiTermQuery::prefix("body".into(), b"luc".to_vec())
+            .with_max_expansions(2)
+            .unwrap();
+        assert_eq!(
+            expand(&query),
+            (vec!["lucene".to_string(), "lucent".to_string()], true)
+        );
+
+        // without a prefix all the terms are enumerated, the closest are kept
+        let query = MultiTermQuery::fuzzy("body".into(), "lucene", 1).unwrap();
+        let (terms, truncated) = expand(&query);
+        assert_eq!(terms, vec!["lucene", "lucent", "lucine", "rucene"]);
+        assert!(!truncated);
+        let query = query.with_max_expansions(2).unwrap();
+        assert_eq!(
+            expand(&query),
+            (vec!["lucene".to_string(), "lucent".to_string()], true)
+        );
+
+        // the truncation is reported by each weight
+        let weight = searcher.create_weight(&query, false).unwrap();
+        let mut scorer = weight.create_scorer(&leaves[0]).unwrap().unwrap();
+        let mut docs = vec![];
+        while scorer.next().unwrap() != NO_MORE_DOCS {
+            docs.push(scorer.doc_id());
+        }
+        assert_eq!(docs, vec![0, 1, 2, 5, 6]);
+        let weight = searcher.create_weight(&query, true).unwrap();
+        let explanation = weight.explain(&leaves[0], 0).unwrap();
+        assert!(explanation.description().contains("expansion truncated"));
+
+        writer.close().unwrap();
+    }
+
+    #[test]
+    fn test_constant_score_without_scores() {
+        let dir = Arc::new(RAMDirectory::default());
+        let writer = IndexWriter::new(dir, Arc::new(IndexWriterConfig::default())).unwrap();
+        let mut field_type = FieldType::default();
+        field_type.index_options = IndexOptions::DocsAndFreqs;
+        for text in &["apple", "apricot banana", "banana", "application apple"] {
+            let tokens: Box<dyn TokenStream> = Box::new(WhitespaceTokenizer::new(Box::new(
+                Cursor::new(text.as_bytes().to_vec()),
+            )));
+            let doc: Vec<Box<dyn Fieldable>> = vec![Box::new(Field::new(
+                "body".into(),
+                field_type.clone(),
+                None,
+                Some(tokens),
+            ))];
+            writer.add_document(doc).unwrap();
+        }
+        let reader = Arc::new(writer.get_reader(true, false).unwrap());
+        let searcher = DefaultIndexSearcher::new(Arc::clone(&reader));
+        let leaves = reader.leaves();
+        assert_eq!(leaves.len(), 1);
+
+        let query = MultiTermQuery::prefix("body".into(), b"ap".to_vec());
+        // the boolean weight, with its term stats, is only built for scores
+        let weight: Box<dyn Weight<CodecEnum>> = query.create_weight(&searcher, false).unwrap();
+        assert_eq!(weight.actual_query_type(), MULTI_TERM);
+        assert!(weight.explain(&leaves[0], 3).unwrap().is_match());
+        assert!(!weight.explain(&leaves[0], 2).unwrap().is_match());
+        let weight: Box<dyn Weight<CodecEnum>> = query.create_weight(&searcher, true).unwrap();
+        assert_eq!(weight.actual_query_type(), BOOLEAN);
+
+        for &needs_scores in &[true, false] {
+            let weight = searcher.create_weight(&query, needs_scores).unwrap();
+            let mut scorer = weight.create_scorer(&leaves[0]).unwrap().unwrap();
+            let mut docs = vec![];
+            loop {
+                let doc = scorer.next().unwrap();
+                if doc == NO_MORE_DOCS {
+                    break;
+                }
+                docs.push(doc);
+            }
+            assert_eq!(docs, vec![0, 1, 3]);
+        }
+
+        let query = MultiTermQuery::prefix("body".into(), b"cherry".to_vec());
+        let weight = searcher.create_weight(&query, false).unwrap();
+        assert!(weight.create_scorer(&leaves[0]).unwrap().is_none());
+
+        writer.close().unwrap();
+    }
+}
@@ -0,0 +1,114 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::codec::Codec;
+use core::search::searcher::SimilarityEnum;
+use core::search::statistics::{CollectionStatistics, TermStatistics};
+use core::search::{SimWeight, Similarity, SimilarityProducer};
+use core::util::KeyedContext;
+
+use std::collections::HashMap;
+use std::fmt;
+
+/// A similarity picking a different scoring model per field.
+///
+/// Fields without a specific similarity are scored with the default one. Use
+/// it as the `SimilarityProducer` of `DefaultIndexSearcher::with_similarity` so
+/// that the term weights of each field are created with its own similarity.
+#[derive(Clone, Debug, Default)]
+pub struct PerFieldSimilarityWrapper {
+    default: SimilarityEnum,
+    per_field: HashMap<String, SimilarityEnum>,
+}
+
+impl PerFieldSimilarityWrapper {
+    pub fn new(default: SimilarityEnum) -> PerFieldSimilarityWrapper {
+        PerFieldSimilarityWrapper {
+            default,
+            per_field: HashMap::new(),
+        }
+    }
+
+    /// Scores `field` with `similarity` instead of the default similarity.
+    pub fn with_field_similarity(
+        mut self,
+        field: &str,
+        similarity: SimilarityEnum,
+    ) -> PerFieldSimilarityWrapper {
+        self.per_field.insert(field.to_string(), similarity);
+        self
+    }
+
+    /// Returns the similarity used to score `field`.
+    pub fn get(&self, field: &str) -> &SimilarityEnum {
+        self.per_field.get(field).unwrap_or(&self.default)
+    }
+}
+
+impl<C: Codec> Similarity<C> for PerFieldSimilarityWrapper {
+    fn compute_weight(
+        &self,
+        collection_stats: &CollectionStatistics,
+        term_stats: &[TermStatistics],
+        context: Option<&KeyedContext>,
+        boost: f32,
+    ) -> Box<dyn SimWeight<C>> {
+        self.get(&collection_stats.field)
+            .compute_weight(collection_stats, term_stats, context, boost)
+    }
+}
+
+impl<C: Codec> SimilarityProducer<C> for PerFieldSimilarityWrapper {
+    fn create(&self, field: &str) -> Box<dyn Similarity<C>> {
+        Box::new(*self.get(field))
+    }
+}
+
+impl fmt::Display for PerFieldSimilarityWrapper {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "PerFieldSimilarityWrapper(default: {})", self.default)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::codec::tests::TestCodec;
+    use core::search::bm25_similarity::BM25Similarity;
+    use core::search::tfidf_similarity::TFIDFSimilarity;
+
+    #[test]
+    fn test_per_field_similarity() {
+        let wrapper = PerFieldSimilarityWrapper::new(SimilarityEnum::BM25(
+            BM25Similarity::default(),
+        ))
+        .with_field_similarity("title", SimilarityEnum::TFIDF(TFIDFSimilarity::new()));
+
+        let title: Box<dyn Similarity<TestCodec>> = wrapper.create("title");
+        assert_eq!(title.to_string(), "TFIDFSimilarity");
+        let body: Box<dyn Similarity<TestCodec>> = wrapper.create("body");
+        assert_eq!(body.to_string(), BM25Similarity::default().to_string());
+
+        let term_stats = vec![TermStatistics::new(Vec::new(), 1, -1)];
+        let title_stats = CollectionStatistics::new(String::from("title"), 32, 32, 120, -1);
+        let weight: Box<dyn SimWeight<TestCodec>> =
+            wrapper.compute_weight(&title_stats, &term_stats, None, 1.0);
+        let idf = TFIDFSimilarity::idf(1, 32);
+        assert!((weight.get_value_for_normalization() - idf * idf).abs() < 1e-5);
+
+        let body_stats = CollectionStatistics::new(String::from("body"), 32, 32, 120, -1);
+        let weight: Box<dyn SimWeight<TestCodec>> =
+            wrapper.compute_weight(&body_stats, &term_stats, None, 1.0);
+        assert!((weight.get_value_for_normalization() - idf * idf).abs() > 1e-3);
+    }
+}
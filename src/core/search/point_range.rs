@@ -12,6 +12,7 @@
 // limitations under the License.
 
 use error::{ErrorKind, Result};
+use std::cmp::min;
 use std::fmt;
 
 use core::codec::Codec;
@@ -19,10 +20,10 @@ use core::doc::{DoublePoint, FloatPoint, IntPoint, LongPoint};
 use core::index::{IntersectVisitor, PointValues, Relation};
 use core::index::{LeafReader, LeafReaderContext};
 use core::search::explanation::Explanation;
-use core::search::match_all::{AllDocsIterator, ConstantScoreScorer};
+use core::search::match_all::{AllDocsIterator, ConstantScoreScorer, DocIdSetIterator};
 use core::search::searcher::SearchPlanBuilder;
 use core::search::term_query::TermQuery;
-use core::search::{DocIdSet, Query, Scorer, Weight};
+use core::search::{Query, Scorer, Weight};
 use core::search::{DocIterator, EmptyDocIterator};
 use core::util::doc_id_set::{DocIdSetDocIterEnum, DocIdSetEnum};
 use core::util::{DocId, DocIdSetBuilder};
@@ -173,6 +174,7 @@ struct PointRangeWeight {
     upper_point: Vec<u8>,
     value_type: PointValueType,
     weight: f32,
+    boost: f32,
     norm: f32,
 }
 
@@ -193,18 +195,68 @@ impl PointRangeWeight {
             upper_point,
             value_type,
             weight: 0f32,
+            boost: 1f32,
             norm: 1f32,
         }
     }
 
-    fn build_matching_doc_set<R: LeafReader + ?Sized>(
+    /// Returns whether the query range contains every value of the field
+    /// indexed in this segment.
+    fn covers_all_values(&self, values: &impl PointValues) -> Result<bool> {
+        let field_packed_lower = values.min_packed_value(&self.field)?;
+        let field_packed_upper = values.max_packed_value(&self.field)?;
+
+        for i in 0..self.num_dims {
+            let offset = i * self.bytes_per_dim;
+            let end = offset + self.bytes_per_dim;
+            if self.lower_point[offset..end] > field_packed_lower[offset..end]
+                || self.upper_point[offset..end] < field_packed_upper[offset..end]
+            {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Returns an iterator over the matching docs of one segment.
+    ///
+    /// Only docs that have a value for the field are ever visited, so for sparse
+    /// fields the work done is bounded by the field's doc count, not `max_doc`.
+    fn matching_docs(
+        &self,
+        max_doc: DocId,
+        values: &impl PointValues,
+    ) -> Result<PointDocIterEnum> {
+        let doc_count = values.doc_count(&self.field)?;
+        if doc_count <= 0 {
+            return Ok(PointDocIterEnum::None(EmptyDocIterator::default()));
+        }
+
+        let covers_all_values = self.covers_all_values(values)?;
+        if covers_all_values && doc_count == max_doc {
+            return Ok(PointDocIterEnum::All(AllDocsIterator::new(max_doc)));
+        }
+
+        let doc_set = self.build_matching_doc_set(max_doc, values, covers_all_values)?;
+        if let Some(iter) = DocIdSetIterator::new(&doc_set, max_doc)? {
+            Ok(PointDocIterEnum::DocSet(iter))
+        } else {
+            Ok(PointDocIterEnum::None(EmptyDocIterator::default()))
+        }
+    }
+
+    /// Collects the matching docs from the BKD tree. When `all_values_match` is
+    /// set, the field is sparse but every value is in range, so docs are
+    /// collected as they come without comparing their values.
+    fn build_matching_doc_set(
         &self,
-        reader: &R,
+        max_doc: DocId,
         values: &impl PointValues,
+        all_values_match: bool,
     ) -> Result<DocIdSetEnum> {
-        let mut result = DocIdSetBuilder::from_values(reader.max_doc(), values, &self.field)?;
+        let mut result = DocIdSetBuilder::from_values(max_doc, values, &self.field)?;
         {
-            let mut visitor = PointRangeIntersectVisitor::new(&mut result, self);
+            let mut visitor = PointRangeIntersectVisitor::new(&mut result, self, all_values_match);
             values.intersect(&self.field, &mut visitor)?;
         }
 
@@ -234,37 +286,9 @@ impl<C: Codec> Weight<C> for PointRangeWeight {
                     )));
                 }
 
-                let mut all_docs_match = false;
-                if values.doc_count(&self.field)? == leaf_reader.max_doc() {
-                    let field_packed_lower = values.min_packed_value(&self.field)?;
-                    let field_packed_upper = values.max_packed_value(&self.field)?;
-
-                    all_docs_match = true;
-                    for i in 0..self.num_dims {
-                        let offset = i * self.bytes_per_dim;
-                        let end = offset + self.bytes_per_dim;
-                        if self.lower_point[offset..end] > field_packed_lower[offset..end]
-                            || self.upper_point[offset..end] < field_packed_upper[offset..end]
-                        {
-                            all_docs_match = false;
-                            break;
-                        }
-                    }
-                }
-
-                let iterator = if all_docs_match {
-                    PointDocIterEnum::All(AllDocsIterator::new(leaf_reader.max_doc()))
-                } else {
-                    if let Some(iter) = self
-                        .build_matching_doc_set(leaf_reader, values)?
-                        .iterator()?
-                    {
-                        PointDocIterEnum::DocSet(iter)
-                    } else {
-                        PointDocIterEnum::None(EmptyDocIterator::default())
-                    }
-                };
-                let cost = iterator.cost();
+                let iterator = self.matching_docs(leaf_reader.max_doc(), values)?;
+                // a sparse field never matches more docs than it has values for
+                let cost = min(iterator.cost(), values.doc_count(&self.field)?.max(0) as usize);
                 return Ok(Some(Box::new(ConstantScoreScorer::new(
                     self.weight,
                     iterator,
@@ -281,6 +305,7 @@ impl<C: Codec> Weight<C> for PointRangeWeight {
 
     fn normalize(&mut self, norm: f32, boost: f32) {
         self.weight = norm * boost;
+        self.boost = boost;
         self.norm = norm;
     }
 
@@ -292,8 +317,31 @@ impl<C: Codec> Weight<C> for PointRangeWeight {
         false
     }
 
-    fn explain(&self, _reader: &LeafReaderContext<'_, C>, _doc: DocId) -> Result<Explanation> {
-        unimplemented!()
+    fn explain(&self, reader: &LeafReaderContext<'_, C>, doc: DocId) -> Result<Explanation> {
+        let exists = if let Some(mut scorer) = self.create_scorer(reader)? {
+            scorer.advance(doc)? == doc
+        } else {
+            false
+        };
+
+        if exists {
+            Ok(Explanation::new(
+                true,
+                self.weight,
+                format!("{}, product of:", self),
+                vec![
+                    Explanation::new(true, self.boost, "boost".to_string(), vec![]),
+                    Explanation::new(true, self.norm, "queryNorm".to_string(), vec![]),
+                ],
+            ))
+        } else {
+            Ok(Explanation::new(
+                false,
+                0.0f32,
+                format!("{} doesn't match id {}", self, doc),
+                vec![],
+            ))
+        }
     }
 }
 
@@ -318,16 +366,19 @@ impl fmt::Display for PointRangeWeight {
 struct PointRangeIntersectVisitor<'a> {
     doc_id_set_builder: &'a mut DocIdSetBuilder,
     weight: &'a PointRangeWeight,
+    all_values_match: bool,
 }
 
 impl<'a> PointRangeIntersectVisitor<'a> {
     pub fn new(
         doc_id_set_builder: &'a mut DocIdSetBuilder,
         weight: &'a PointRangeWeight,
+        all_values_match: bool,
     ) -> PointRangeIntersectVisitor<'a> {
         PointRangeIntersectVisitor {
             doc_id_set_builder,
             weight,
+            all_values_match,
         }
     }
 }
@@ -339,6 +390,10 @@ impl<'a> IntersectVisitor for PointRangeIntersectVisitor<'a> {
     }
 
     fn visit_by_packed_value(&mut self, doc_id: DocId, packed_value: &[u8]) -> Result<()> {
+        if self.all_values_match {
+            self.doc_id_set_builder.add_doc(doc_id);
+            return Ok(());
+        }
         let bytes = self.weight.bytes_per_dim;
         for dim in 0..self.weight.num_dims {
             let offset = dim * bytes;
@@ -355,18 +410,20 @@ impl<'a> IntersectVisitor for PointRangeIntersectVisitor<'a> {
     }
 
     fn compare(&self, min_packed_value: &[u8], max_packed_value: &[u8]) -> Relation {
+        if self.all_values_match {
+            return Relation::CellInsideQuery;
+        }
         let mut crosses = false;
         let bytes = self.weight.bytes_per_dim;
         for dim in 0..self.weight.num_dims {
             let offset = dim * bytes;
             let end = offset + bytes;
             if min_packed_value[offset..end] > self.weight.upper_point[offset..end]
-                || max_packed_value[offset..end] < self.weight.lower_point[offset..]
+                || max_packed_value[offset..end] < self.weight.lower_point[offset..end]
             {
                 return Relation::CellOutsideQuery;
             }
 
-            let end = offset + bytes;
             crosses |= min_packed_value[offset..end] < self.weight.lower_point[offset..end]
                 || max_packed_value[offset..end] > self.weight.upper_point[offset..end];
         }
@@ -384,7 +441,7 @@ impl<'a> IntersectVisitor for PointRangeIntersectVisitor<'a> {
 }
 
 enum PointDocIterEnum {
-    DocSet(DocIdSetDocIterEnum),
+    DocSet(DocIdSetIterator<DocIdSetDocIterEnum>),
     All(AllDocsIterator),
     None(EmptyDocIterator),
 }
@@ -462,3 +519,271 @@ impl DocIterator for PointDocIterEnum {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::search::NO_MORE_DOCS;
+
+    use std::any::Any;
+    use std::cell::Cell;
+
+    const BLOCK_SIZE: usize = 16;
+
+    /// A single-dimension long field, stored as leaf blocks sorted by value.
+    struct MockPointValues {
+        points: Vec<(DocId, i64)>,
+        visited: Cell<usize>,
+    }
+
+    impl MockPointValues {
+        fn new(points: Vec<(DocId, i64)>) -> MockPointValues {
+            MockPointValues {
+                points,
+                visited: Cell::new(0),
+            }
+        }
+    }
+
+    impl PointValues for MockPointValues {
+        fn intersect(&self, _field_name: &str, visitor: &mut impl IntersectVisitor) -> Result<()> {
+            for block in self.points.chunks(BLOCK_SIZE) {
+                let min = LongPoint::pack(&[block[0].1]);
+                let max = LongPoint::pack(&[block[block.len() - 1].1]);
+                match visitor.compare(&min, &max) {
+                    Relation::CellOutsideQuery => {}
+                    Relation::CellInsideQuery => {
+                        visitor.grow(block.len());
+                        for &(doc, _) in block {
+                            self.visited.set(self.visited.get() + 1);
+                            visitor.visit(doc)?;
+                        }
+                    }
+                    Relation::CellCrossesQuery => {
+                        visitor.grow(block.len());
+                        for &(doc, value) in block {
+                            self.visited.set(self.visited.get() + 1);
+                            visitor.visit_by_packed_value(doc, &LongPoint::pack(&[value]))?;
+                        }
+                    }
+                }
+            }
+            Ok(())
+        }
+
+        fn min_packed_value(&self, _field_name: &str) -> Result<Vec<u8>> {
+            Ok(LongPoint::pack(&[self.points[0].1]))
+        }
+
+        fn max_packed_value(&self, _field_name: &str) -> Result<Vec<u8>> {
+            Ok(LongPoint::pack(&[self.points[self.points.len() - 1].1]))
+        }
+
+        fn num_dimensions(&self, _field_name: &str) -> Result<usize> {
+            Ok(1)
+        }
+
+        fn bytes_per_dimension(&self, _field_name: &str) -> Result<usize> {
+            Ok(8)
+        }
+
+        fn size(&self, _field_name: &str) -> Result<i64> {
+            Ok(self.points.len() as i64)
+        }
+
+        fn doc_count(&self, _field_name: &str) -> Result<i32> {
+            Ok(self.points.len() as i32)
+        }
+
+        fn as_any(&self) -> &Any {
+            self
+        }
+    }
+
+    fn create_weight(lower: i64, upper: i64) -> PointRangeWeight {
+        PointRangeWeight::new(
+            "price".into(),
+            1,
+            8,
+            LongPoint::pack(&[lower]),
+            LongPoint::pack(&[upper]),
+            PointValueType::Long,
+        )
+    }
+
+    /// 10000 docs, of which every 100th has the value `doc / 100`.
+    fn create_sparse_values() -> MockPointValues {
+        MockPointValues::new((0..100).map(|i| (i * 100, i64::from(i))).collect())
+    }
+
+    fn collect(iter: &mut PointDocIterEnum) -> Vec<DocId> {
+        let mut docs = vec![];
+        loop {
+            let doc = iter.next().unwrap();
+            if doc == NO_MORE_DOCS {
+                break;
+            }
+            docs.push(doc);
+        }
+        docs
+    }
+
+    #[test]
+    fn test_sparse_field_all_values_match() {
+        let values = create_sparse_values();
+        let weight = create_weight(i64::min_value(), i64::max_value());
+
+        let mut iter = weight.matching_docs(10000, &values).unwrap();
+        assert!(iter.cost() <= 100);
+        let docs = collect(&mut iter);
+        assert_eq!(docs.len(), 100);
+        assert_eq!(docs[0], 0);
+        assert_eq!(docs[99], 9900);
+        // only the docs with the field are visited
+        assert_eq!(values.visited.get(), 100);
+    }
+
+    #[test]
+    fn test_sparse_field_range() {
+        let values = create_sparse_values();
+        let weight = create_weight(20, 29);
+
+        let mut iter = weight.matching_docs(10000, &values).unwrap();
+        assert!(iter.cost() <= 100);
+        let docs = collect(&mut iter);
+        assert_eq!(docs, (20..30).map(|i| i * 100).collect::<Vec<_>>());
+        // blocks outside the range are skipped
+        assert!(values.visited.get() <= 2 * BLOCK_SIZE);
+    }
+
+    #[test]
+    fn test_dense_field_all_values_match() {
+        let values = MockPointValues::new((0..100).map(|i| (i, i64::from(i))).collect());
+        let weight = create_weight(-1, 100);
+
+        let mut iter = weight.matching_docs(100, &values).unwrap();
+        assert_eq!(collect(&mut iter).len(), 100);
+        // no need to look at the tree when every doc matches
+        assert_eq!(values.visited.get(), 0);
+    }
+
+    #[test]
+    fn test_dense_field_range_advance() {
+        let values = MockPointValues::new((0..100).map(|i| (i, i64::from(i))).collect());
+        let weight = create_weight(10, 89);
+
+        let mut iter = weight.matching_docs(100, &values).unwrap();
+        assert_eq!(iter.advance(5).unwrap(), 10);
+        assert_eq!(iter.advance(50).unwrap(), 50);
+        assert_eq!(iter.next().unwrap(), 51);
+        assert_eq!(iter.advance(90).unwrap(), NO_MORE_DOCS);
+    }
+
+    /// A two-dimension int field, stored as leaf blocks in doc order.
+    struct MockPointValues2D {
+        points: Vec<(DocId, [i32; 2])>,
+        inside_cells: Cell<usize>,
+    }
+
+    impl MockPointValues2D {
+        fn bounds(points: &[(DocId, [i32; 2])]) -> (Vec<u8>, Vec<u8>) {
+            let mut min = [i32::max_value(); 2];
+            let mut max = [i32::min_value(); 2];
+            for &(_, point) in points {
+                for dim in 0..2 {
+                    min[dim] = min[dim].min(point[dim]);
+                    max[dim] = max[dim].max(point[dim]);
+                }
+            }
+            (IntPoint::pack(&min), IntPoint::pack(&max))
+        }
+    }
+
+    impl PointValues for MockPointValues2D {
+        fn intersect(&self, _field_name: &str, visitor: &mut impl IntersectVisitor) -> Result<()> {
+            for block in self.points.chunks(BLOCK_SIZE) {
+                let (min, max) = MockPointValues2D::bounds(block);
+                match visitor.compare(&min, &max) {
+                    Relation::CellOutsideQuery => {}
+                    Relation::CellInsideQuery => {
+                        self.inside_cells.set(self.inside_cells.get() + 1);
+                        visitor.grow(block.len());
+                        for &(doc, _) in block {
+                            visitor.visit(doc)?;
+                        }
+                    }
+                    Relation::CellCrossesQuery => {
+                        visitor.grow(block.len());
+                        for &(doc, point) in block {
+                            visitor.visit_by_packed_value(doc, &IntPoint::pack(&point))?;
+                        }
+                    }
+                }
+            }
+            Ok(())
+        }
+
+        fn min_packed_value(&self, _field_name: &str) -> Result<Vec<u8>> {
+            Ok(MockPointValues2D::bounds(&self.points).0)
+        }
+
+        fn max_packed_value(&self, _field_name: &str) -> Result<Vec<u8>> {
+            Ok(MockPointValues2D::bounds(&self.points).1)
+        }
+
+        fn num_dimensions(&self, _field_name: &str) -> Result<usize> {
+            Ok(2)
+        }
+
+        fn bytes_per_dimension(&self, _field_name: &str) -> Result<usize> {
+            Ok(4)
+        }
+
+        fn size(&self, _field_name: &str) -> Result<i64> {
+            Ok(self.points.len() as i64)
+        }
+
+        fn doc_count(&self, _field_name: &str) -> Result<i32> {
+            Ok(self.points.len() as i32)
+        }
+
+        fn as_any(&self) -> &Any {
+            self
+        }
+    }
+
+    #[test]
+    fn test_two_dims_range() {
+        // a 10x10 grid, doc `x * 10 + y` at point (x, y)
+        let values = MockPointValues2D {
+            points: (0..100).map(|i| (i, [i / 10, i % 10])).collect(),
+            inside_cells: Cell::new(0),
+        };
+        let weight = PointRangeWeight::new(
+            "loc".into(),
+            2,
+            4,
+            IntPoint::pack(&[3, 0]),
+            IntPoint::pack(&[6, 9]),
+            PointValueType::Integer,
+        );
+        let mut iter = weight.matching_docs(100, &values).unwrap();
+        assert_eq!(collect(&mut iter), (30..70).collect::<Vec<_>>());
+        // docs 32..47 and 48..63 cover full rows, accepted without comparing values
+        assert_eq!(values.inside_cells.get(), 2);
+
+        let weight = PointRangeWeight::new(
+            "loc".into(),
+            2,
+            4,
+            IntPoint::pack(&[3, 2]),
+            IntPoint::pack(&[5, 7]),
+            PointValueType::Integer,
+        );
+        let mut iter = weight.matching_docs(100, &values).unwrap();
+        let expected: Vec<DocId> = (0..100)
+            .filter(|i| i / 10 >= 3 && i / 10 <= 5 && i % 10 >= 2 && i % 10 <= 7)
+            .collect();
+        assert_eq!(collect(&mut iter), expected);
+    }
+}
@@ -0,0 +1,406 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use error::ErrorKind::IllegalArgument;
+use error::Result;
+
+use std::collections::HashMap;
+use std::fmt;
+
+use core::codec::{Codec, CodecPostingIterator, CodecTermState};
+use core::index::{LeafReaderContext, Term, Terms};
+use core::search::explanation::Explanation;
+use core::search::phrase_query::{TERM_OPS_PER_POS, TERM_POSNS_SEEK_OPS_PER_DOC};
+use core::search::posting_iterator::{PostingIterator, PostingIteratorFlags};
+use core::search::searcher::SearchPlanBuilder;
+use core::search::statistics::{CollectionStatistics, TermStatistics};
+use core::search::term_query::TermQuery;
+use core::search::{
+    two_phase_next, DocIterator, Query, Scorer, SimScorer, SimWeight, Similarity, Weight,
+};
+use core::util::DocId;
+
+pub const POSITION_RANGE: &str = "position_range";
+
+/// A query that matches the documents containing `term` at a position within
+/// `[min_pos, max_pos]`, e.g. to only match a term in the leading tokens of a
+/// field. The field must be indexed with positions.
+///
+/// Documents are scored like by a `TermQuery`, with the number of occurrences
+/// of the term within the range as term freq.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PositionRangeQuery {
+    pub term: Term,
+    pub min_pos: i32,
+    pub max_pos: i32,
+    pub boost: f32,
+}
+
+impl PositionRangeQuery {
+    pub fn new(term: Term, min_pos: i32, max_pos: i32, boost: f32) -> Result<PositionRangeQuery> {
+        if min_pos < 0 || min_pos > max_pos {
+            bail!(IllegalArgument(format!(
+                "invalid position range: [{}, {}]",
+                min_pos, max_pos
+            )));
+        }
+        Ok(PositionRangeQuery {
+            term,
+            min_pos,
+            max_pos,
+            boost,
+        })
+    }
+}
+
+impl<C: Codec> Query<C> for PositionRangeQuery {
+    fn create_weight(
+        &self,
+        searcher: &dyn SearchPlanBuilder<C>,
+        needs_scores: bool,
+    ) -> Result<Box<dyn Weight<C>>> {
+        let term_context = searcher.term_state(&self.term)?;
+        let max_doc = i64::from(searcher.max_doc());
+        let term_stats = searcher.term_statistics(self.term.clone(), term_context.as_ref());
+        let collection_stats = if needs_scores {
+            searcher.collections_statistics(&self.term.field)?
+        } else {
+            CollectionStatistics::new(self.term.field.clone(), max_doc, -1, -1, -1)
+        };
+
+        // positions of each matching doc are scanned until past `max_pos`
+        let exp_occurrences_in_matching_doc = if term_stats.total_term_freq < term_stats.doc_freq
+        {
+            1.0f32
+        } else {
+            term_stats.total_term_freq as f32 / term_stats.doc_freq as f32
+        };
+        let match_cost = TERM_POSNS_SEEK_OPS_PER_DOC as f32
+            + exp_occurrences_in_matching_doc * TERM_OPS_PER_POS as f32;
+
+        let term_stats = if needs_scores {
+            term_stats
+        } else {
+            TermStatistics::new(self.term.bytes.clone(), max_doc, -1)
+        };
+        let similarity = searcher.similarity(&self.term.field, needs_scores);
+        let sim_weight =
+            similarity.compute_weight(&collection_stats, &[term_stats], None, self.boost);
+        Ok(Box::new(PositionRangeWeight {
+            term: self.term.clone(),
+            min_pos: self.min_pos,
+            max_pos: self.max_pos,
+            boost: self.boost,
+            similarity,
+            sim_weight,
+            needs_scores,
+            term_states: term_context.term_states(),
+            match_cost,
+        }))
+    }
+
+    fn extract_terms(&self) -> Vec<TermQuery> {
+        vec![TermQuery::new(self.term.clone(), self.boost, None)]
+    }
+
+    fn query_type(&self) -> &'static str {
+        POSITION_RANGE
+    }
+
+    fn as_any(&self) -> &::std::any::Any {
+        self
+    }
+}
+
+impl fmt::Display for PositionRangeQuery {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "PositionRangeQuery(field: {}, term: {}, positions: [{}, {}], boost: {})",
+            &self.term.field(),
+            &self.term.text().unwrap(),
+            self.min_pos,
+            self.max_pos,
+            self.boost
+        )
+    }
+}
+
+struct PositionRangeWeight<C: Codec> {
+    term: Term,
+    min_pos: i32,
+    max_pos: i32,
+    boost: f32,
+    similarity: Box<dyn Similarity<C>>,
+    sim_weight: Box<dyn SimWeight<C>>,
+    needs_scores: bool,
+    term_states: HashMap<DocId, CodecTermState<C>>,
+    match_cost: f32,
+}
+
+impl<C: Codec> PositionRangeWeight<C> {
+    fn create_postings_iterator(
+        &self,
+        reader: &LeafReaderContext<'_, C>,
+    ) -> Result<Option<CodecPostingIterator<C>>> {
+        if let Some(terms) = reader.reader.terms(&self.term.field)? {
+            if !terms.has_positions()? {
+                bail!(IllegalArgument(format!(
+                    "field {} was indexed without position data; cannot run \
+                     PositionRangeQuery (term={})",
+                    &self.term.field,
+                    &self.term.text()?
+                )));
+            }
+        } else {
+            return Ok(None);
+        }
+
+        if let Some(state) = self.term_states.get(&reader.doc_base) {
+            let flags = i32::from(PostingIteratorFlags::POSITIONS);
+            reader.reader.postings_from_state(&self.term, &state, flags)
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+impl<C: Codec> Weight<C> for PositionRangeWeight<C> {
+    fn create_scorer(
+        &self,
+        reader_context: &LeafReaderContext<'_, C>,
+    ) -> Result<Option<Box<dyn Scorer>>> {
+        if let Some(postings) = self.create_postings_iterator(reader_context)? {
+            let sim_scorer = self.sim_weight.sim_scorer(reader_context.reader)?;
+            Ok(Some(Box::new(PositionRangeScorer::new(
+                sim_scorer,
+                postings,
+                self.min_pos,
+                self.max_pos,
+                self.match_cost,
+            ))))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn query_type(&self) -> &'static str {
+        POSITION_RANGE
+    }
+
+    fn normalize(&mut self, norm: f32, boost: f32) {
+        self.sim_weight.normalize(norm, boost)
+    }
+
+    fn value_for_normalization(&self) -> f32 {
+        self.sim_weight.get_value_for_normalization()
+    }
+
+    fn needs_scores(&self) -> bool {
+        self.needs_scores
+    }
+
+    fn explain(&self, reader: &LeafReaderContext<'_, C>, doc: DocId) -> Result<Explanation> {
+        if let Some(postings) = self.create_postings_iterator(reader)? {
+            let sim_scorer = self.sim_weight.sim_scorer(reader.reader)?;
+            let mut scorer = PositionRangeScorer::new(
+                sim_scorer,
+                postings,
+                self.min_pos,
+                self.max_pos,
+                self.match_cost,
+            );
+            if scorer.approximate_advance(doc)? == doc && scorer.matches()? {
+                let freq = scorer.freq as f32;
+                let freq_expl = Explanation::new(
+                    true,
+                    freq,
+                    format!(
+                        "termFreq within positions [{}, {}]={}",
+                        self.min_pos, self.max_pos, freq
+                    ),
+                    vec![],
+                );
+                let score_expl = self.sim_weight.explain(reader.reader, doc, freq_expl)?;
+
+                return Ok(Explanation::new(
+                    true,
+                    score_expl.value(),
+                    format!(
+                        "weight({} in {}) [{}], result of:",
+                        self, doc, self.similarity
+                    ),
+                    vec![score_expl],
+                ));
+            }
+        }
+        Ok(Explanation::new(
+            false,
+            0f32,
+            "no matching term within position range".to_string(),
+            vec![],
+        ))
+    }
+}
+
+impl<C: Codec> fmt::Display for PositionRangeWeight<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "PositionRangeWeight(field: {}, term: {}, positions: [{}, {}], boost: {}, \
+             similarity: {}, need_score: {})",
+            &self.term.field(),
+            &self.term.text().unwrap(),
+            self.min_pos,
+            self.max_pos,
+            self.boost,
+            &self.similarity,
+            self.needs_scores
+        )
+    }
+}
+
+/// Scorer of `PositionRangeQuery`, the postings are the approximation and
+/// `matches` scans the positions of the current doc.
+pub struct PositionRangeScorer<T: PostingIterator> {
+    sim_scorer: Box<dyn SimScorer>,
+    postings: T,
+    min_pos: i32,
+    max_pos: i32,
+    match_cost: f32,
+    // number of positions of the current doc within the range
+    freq: i32,
+}
+
+impl<T: PostingIterator> PositionRangeScorer<T> {
+    pub fn new(
+        sim_scorer: Box<dyn SimScorer>,
+        postings: T,
+        min_pos: i32,
+        max_pos: i32,
+        match_cost: f32,
+    ) -> Self {
+        PositionRangeScorer {
+            sim_scorer,
+            postings,
+            min_pos,
+            max_pos,
+            match_cost,
+            freq: 0,
+        }
+    }
+}
+
+impl<T: PostingIterator> Scorer for PositionRangeScorer<T> {
+    fn score(&mut self) -> Result<f32> {
+        let doc_id = self.postings.doc_id();
+        self.sim_scorer.score(doc_id, self.freq as f32)
+    }
+
+    fn support_two_phase(&self) -> bool {
+        true
+    }
+}
+
+impl<T: PostingIterator> DocIterator for PositionRangeScorer<T> {
+    fn doc_id(&self) -> DocId {
+        self.postings.doc_id()
+    }
+
+    fn next(&mut self) -> Result<DocId> {
+        self.approximate_next()?;
+        two_phase_next(self)
+    }
+
+    fn advance(&mut self, target: DocId) -> Result<DocId> {
+        self.approximate_advance(target)?;
+        two_phase_next(self)
+    }
+
+    fn cost(&self) -> usize {
+        self.postings.cost()
+    }
+
+    fn matches(&mut self) -> Result<bool> {
+        self.freq = 0;
+        for _ in 0..self.postings.freq()? {
+            let pos = self.postings.next_position()?;
+            if pos > self.max_pos {
+                break;
+            }
+            if pos >= self.min_pos {
+                self.freq += 1;
+            }
+        }
+        Ok(self.freq > 0)
+    }
+
+    fn match_cost(&self) -> f32 {
+        self.match_cost
+    }
+
+    fn approximate_next(&mut self) -> Result<DocId> {
+        self.postings.next()
+    }
+
+    fn approximate_advance(&mut self, target: DocId) -> Result<DocId> {
+        self.postings.advance(target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::search::spans::tests::MockPostings;
+    use core::search::NO_MORE_DOCS;
+
+    struct FreqSimScorer;
+
+    impl SimScorer for FreqSimScorer {
+        fn score(&mut self, _doc: DocId, freq: f32) -> Result<f32> {
+            Ok(freq)
+        }
+
+        fn compute_slop_factor(&self, _distance: i32) -> f32 {
+            1.0
+        }
+    }
+
+    #[test]
+    fn test_invalid_range() {
+        let term = Term::new("title".into(), b"rucene".to_vec());
+        assert!(PositionRangeQuery::new(term.clone(), -1, 3, 1.0).is_err());
+        assert!(PositionRangeQuery::new(term.clone(), 4, 3, 1.0).is_err());
+        assert!(PositionRangeQuery::new(term, 3, 3, 1.0).is_ok());
+    }
+
+    #[test]
+    fn test_position_range_scorer() {
+        let postings = MockPostings::new(vec![
+            (1, vec![0, 7]),
+            (3, vec![12, 20]),
+            (4, vec![2, 3, 4, 60]),
+            (8, vec![50]),
+            (9, vec![9, 10]),
+        ]);
+        let mut scorer = PositionRangeScorer::new(Box::new(FreqSimScorer), postings, 2, 10, 1.0);
+
+        assert_eq!(scorer.next().unwrap(), 1);
+        assert!((scorer.score().unwrap() - 1.0).abs() < ::std::f32::EPSILON);
+        assert_eq!(scorer.next().unwrap(), 4);
+        assert!((scorer.score().unwrap() - 3.0).abs() < ::std::f32::EPSILON);
+        assert_eq!(scorer.advance(5).unwrap(), 9);
+        assert!((scorer.score().unwrap() - 2.0).abs() < ::std::f32::EPSILON);
+        assert_eq!(scorer.next().unwrap(), NO_MORE_DOCS);
+    }
+}
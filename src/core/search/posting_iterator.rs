@@ -47,6 +47,21 @@ impl PostingIteratorFlags {
     }
 }
 
+/// A competitive (freq, norm) pair of the docs of a range, read from the
+/// skip data of the postings: every doc of the range has a lower freq than
+/// the one of the impact of its norm.
+///
+/// A range has one impact per distinct norm of its docs, so that the bound
+/// of the score of the range doesn't depend on whether the score grows or
+/// decreases with the norm, see `SimScorer::max_score_with_freq_and_norm`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Impact {
+    /// the highest freq of the term among the docs of the range with `norm`
+    pub freq: i32,
+    /// the norm of the docs, 0 if the field has no norms
+    pub norm: i64,
+}
+
 pub trait PostingIterator: DocIterator {
     /// Returns term frequency in the current document, or 1 if the field was
     /// indexed with {@link IndexOptions::Docs}. Do not call this before
@@ -75,6 +90,21 @@ pub trait PostingIterator: DocIterator {
     /// (neither members of the returned BytesRef nor bytes
     /// in the byte[]). */
     fn payload(&self) -> Result<Payload>;
+
+    /// Loads the impacts of the docs from `target` on, without decoding the
+    /// docs nor moving the current doc. The next move must then be an
+    /// `advance` to at least `target`. This is a no-op by default.
+    fn advance_shallow(&mut self, _target: DocId) -> Result<()> {
+        Ok(())
+    }
+
+    /// Returns the last doc, inclusive, of the `level`-th narrowest range of
+    /// docs containing the last skipped to target, and the impacts of the
+    /// docs of the range sorted by norm, `None` past the known levels. No
+    /// impact is known by default.
+    fn impacts(&self, _level: usize) -> Option<(DocId, &[Impact])> {
+        None
+    }
 }
 
 #[derive(Clone)]
@@ -17,20 +17,23 @@ use std::fmt;
 use std::hash::{Hash, Hasher};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
 
-use core::index::LeafReaderContext;
-use core::search::bulk_scorer::BulkScorer;
-use core::search::cache_policy::QueryCachingPolicy;
+use core::index::{IndexReader, LeafReaderContext};
+use core::search::bulk_scorer::{BulkScorer, DefaultBulkScorer};
+use core::search::cache_policy::{AlwaysCacheQueryCachingPolicy, QueryCachingPolicy};
 use core::search::collector::Collector;
 use core::search::explanation::Explanation;
 use core::search::lru_cache::LRUCache;
 use core::search::match_all::ConstantScoreScorer;
-use core::search::{two_phase_next, DocIdSet, DocIterator, Scorer, Weight, NO_MORE_DOCS};
-use core::util::bit_set::{BitSet, FixedBitSet, ImmutableBitSet};
-use core::util::bit_util::UnsignedShift;
-use core::util::doc_id_set::{BitDocIdSet, BitSetIterator, DocIdSetDocIterEnum, DocIdSetEnum};
+use core::search::searcher::IndexSearcher;
+use core::search::{two_phase_next, DocIdSet, DocIterator, Query, Scorer, Weight, NO_MORE_DOCS};
+use core::util::bit_set::{BitSet, FixedBitSet};
+use core::util::doc_id_set::{
+    BitDocIdSet, BitSetIterator, RoaringDocIdSet, RoaringDocIdSetBuilder, RoaringDocIterator,
+};
 use core::util::external::deferred::Deferred;
-use core::util::{Bits, DocId};
+use core::util::DocId;
 
 use core::codec::Codec;
 use error::Result;
@@ -118,6 +121,7 @@ impl<C: Codec> QueryCache<C> for NoCacheQueryCache {
 struct LeafCache {
     _key: String,
     leaf_cache: HashMap<String, CacheDocIdSetEnum>,
+    ram_bytes_used: usize,
 }
 
 impl LeafCache {
@@ -125,6 +129,7 @@ impl LeafCache {
         LeafCache {
             _key,
             leaf_cache: HashMap::new(),
+            ram_bytes_used: 0,
         }
     }
 
@@ -135,14 +140,31 @@ impl LeafCache {
         }
     }
 
-    pub fn put_if_absent(&mut self, query_key: &str, set: CacheDocIdSetEnum) {
-        if !self.leaf_cache.contains_key(query_key) {
-            self.leaf_cache.insert(query_key.to_string(), set);
+    /// Returns the number of bytes added to the cache.
+    pub fn put_if_absent(&mut self, query_key: &str, set: CacheDocIdSetEnum) -> usize {
+        if self.leaf_cache.contains_key(query_key) {
+            return 0;
+        }
+        let ram_bytes_used = set.ram_bytes_used();
+        self.leaf_cache.insert(query_key.to_string(), set);
+        self.ram_bytes_used += ram_bytes_used;
+        ram_bytes_used
+    }
+
+    /// Returns the number of bytes released from the cache.
+    pub fn remove(&mut self, query_key: &str) -> usize {
+        match self.leaf_cache.remove(query_key) {
+            Some(set) => {
+                let ram_bytes_used = set.ram_bytes_used();
+                self.ram_bytes_used -= ram_bytes_used;
+                ram_bytes_used
+            }
+            None => 0,
         }
     }
 
-    pub fn remove(&mut self, query_key: &str) {
-        self.leaf_cache.remove(query_key);
+    pub fn contains(&self, query_key: &str) -> bool {
+        self.leaf_cache.contains_key(query_key)
     }
 }
 
@@ -155,8 +177,14 @@ struct CacheData {
     // mostRecentlyUsedQueries. This is why write operations are performed under a lock
     // pub most_recently_used_queries: HashSet<Query>,
     pub cache: HashMap<String, LeafCache>,
+    // number of cache hits of each query of `unique_queries`, used to find the
+    // hot queries to warm on new segments
+    hit_counts: HashMap<String, u64>,
+    // memory used by the cached doc id sets of all the leaves
+    ram_bytes_used: usize,
 
     max_size: usize,
+    max_ram_bytes_used: usize,
     min_size: i32,
     min_size_ratio: f32,
 }
@@ -173,9 +201,10 @@ impl CacheData {
         }
     }
 
-    /// Whether evictions are required.
-    fn requires_eviction(&self) -> Result<bool> {
-        Ok(self.unique_queries.len() >= self.max_size)
+    /// Whether evictions are required to cache a new set of `extra_ram_bytes`.
+    fn requires_eviction(&self, extra_ram_bytes: usize) -> Result<bool> {
+        Ok(self.unique_queries.len() >= self.max_size
+            || self.ram_bytes_used + extra_ram_bytes > self.max_ram_bytes_used)
     }
 
     fn get<C: Codec>(
@@ -183,13 +212,46 @@ impl CacheData {
         query_key: &str,
         leaf_reader: &LeafReaderContext<'_, C>,
     ) -> Result<Option<CachedDocIdSetIterEnum>> {
+        let mut set = None;
         if let Some(leaf_cache) = self.cache.get(leaf_reader.reader.core_cache_key()) {
             if let Some(singleton) = self.unique_queries.get(&query_key.to_string()) {
                 // this get call moves the query to the most-recently-used position
-                return leaf_cache.get(singleton);
+                set = leaf_cache.get(singleton)?;
             }
         }
-        Ok(None)
+        if set.is_some() {
+            self.on_hit(query_key);
+        }
+        Ok(set)
+    }
+
+    fn contains<C: Codec>(&self, query_key: &str, leaf_reader: &LeafReaderContext<'_, C>) -> bool {
+        self.cache
+            .get(leaf_reader.reader.core_cache_key())
+            .map_or(false, |leaf_cache| leaf_cache.contains(query_key))
+    }
+
+    fn on_hit(&mut self, query_key: &str) {
+        if let Some(count) = self.hit_counts.get_mut(query_key) {
+            *count += 1;
+        }
+    }
+
+    fn on_query_cache(&mut self, query_key: &str) {
+        if !self.hit_counts.contains_key(query_key) {
+            self.hit_counts.insert(query_key.to_string(), 0);
+        }
+    }
+
+    /// Returns the keys of at most `n` cached queries, the most hit first.
+    fn hot_queries(&self, n: usize) -> Vec<String> {
+        let mut queries: Vec<(&String, &u64)> = self.hit_counts.iter().collect();
+        queries.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+        queries
+            .into_iter()
+            .take(n)
+            .map(|(key, _)| key.clone())
+            .collect()
     }
 
     // return true if new LeafCache is added to process core reader drop listener
@@ -199,7 +261,12 @@ impl CacheData {
         leaf_reader: &LeafReaderContext<'_, C>,
         set: CacheDocIdSetEnum,
     ) -> Result<bool> {
-        self.evict_if_necessary()?;
+        let set_ram_bytes = set.ram_bytes_used();
+        if set_ram_bytes > self.max_ram_bytes_used {
+            // would evict everything else and still not fit
+            return Ok(false);
+        }
+        self.evict_if_necessary(set_ram_bytes)?;
 
         let query_key = if self.unique_queries.contains_key(&query_key.to_string()) {
             self.unique_queries
@@ -211,6 +278,7 @@ impl CacheData {
                 .insert(query_key.to_string(), query_key.to_string());
             query_key.to_string()
         };
+        self.on_query_cache(&query_key);
 
         let key = leaf_reader.reader.core_cache_key();
         let new_entry = if self.cache.contains_key(key) {
@@ -223,16 +291,16 @@ impl CacheData {
 
         {
             let leaf_cache = self.cache.get_mut(key).unwrap();
-            leaf_cache.put_if_absent(&query_key, set);
+            self.ram_bytes_used += leaf_cache.put_if_absent(&query_key, set);
         }
 
         Ok(new_entry)
     }
 
-    fn evict_if_necessary(&mut self) -> Result<()> {
-        if self.requires_eviction()? {
+    fn evict_if_necessary(&mut self, extra_ram_bytes: usize) -> Result<()> {
+        if self.requires_eviction(extra_ram_bytes)? {
             loop {
-                if !self.requires_eviction()? {
+                if !self.requires_eviction(extra_ram_bytes)? {
                     break;
                 }
 
@@ -252,8 +320,16 @@ impl CacheData {
     }
 
     fn on_eviction(&mut self, query_key: &str) {
+        self.hit_counts.remove(query_key);
         for leaf_cache in self.cache.values_mut() {
-            leaf_cache.remove(query_key);
+            self.ram_bytes_used -= leaf_cache.remove(query_key);
+        }
+    }
+
+    /// Drops the cached sets of a closed segment core.
+    fn on_core_closed(&mut self, core_key: &str) {
+        if let Some(leaf_cache) = self.cache.remove(core_key) {
+            self.ram_bytes_used -= leaf_cache.ram_bytes_used;
         }
     }
 }
@@ -263,12 +339,22 @@ pub struct LRUQueryCache {
 }
 
 impl LRUQueryCache {
+    /// Creates a cache of at most `max_size` queries, without memory bound.
     pub fn new(max_size: usize) -> LRUQueryCache {
-        // let max_size = 10;
+        LRUQueryCache::with_max_ram_bytes_used(max_size, usize::max_value())
+    }
+
+    /// Creates a cache of at most `max_size` queries whose cached sets use at
+    /// most `max_ram_bytes_used` bytes, evicting the least recently used
+    /// queries first.
+    pub fn with_max_ram_bytes_used(max_size: usize, max_ram_bytes_used: usize) -> LRUQueryCache {
         let cache_data = CacheData {
             unique_queries: LRUCache::with_capacity(max_size),
             cache: HashMap::new(),
+            hit_counts: HashMap::new(),
+            ram_bytes_used: 0,
             max_size,
+            max_ram_bytes_used,
             min_size: 10000,
             min_size_ratio: 0.03f32,
         };
@@ -277,6 +363,90 @@ impl LRUQueryCache {
             cache_data: Arc::new(RwLock::new(cache_data)),
         }
     }
+
+    /// Approximate memory usage of the cached doc id sets in bytes.
+    pub fn ram_bytes_used(&self) -> Result<usize> {
+        Ok(self.cache_data.read()?.ram_bytes_used)
+    }
+
+    /// Returns the keys of the `n` most hit queries of this cache.
+    pub fn hot_queries(&self, n: usize) -> Result<Vec<String>> {
+        Ok(self.cache_data.read()?.hot_queries(n))
+    }
+
+    /// Creates a warmer caching the hot queries of this cache on the new
+    /// segments of a reopened reader, see `QueryCacheWarmer`.
+    pub fn warmer<C: Codec>(
+        &self,
+        max_queries: usize,
+        time_budget: Duration,
+    ) -> QueryCacheWarmer<C> {
+        QueryCacheWarmer {
+            cache_data: Arc::clone(&self.cache_data),
+            queries: Vec::new(),
+            max_queries,
+            time_budget,
+        }
+    }
+}
+
+/// Caches the hot filters of a `LRUQueryCache` on the segments of a new
+/// searcher, so that the first searches after a reopen don't pay for
+/// computing them on the new segments. It is typically run by the
+/// `SearcherFactory` of a `SearcherManager`, with a searcher sharing the
+/// query cache of the previous ones.
+///
+/// The cache only keeps the keys of the queries, so the filters which may
+/// be warmed have to be registered with `add_query`. Only the registered
+/// queries which are among the `max_queries` most hit queries of the cache
+/// are warmed, the hottest first, and warming stops once `time_budget` is
+/// spent. The segments on which a query is already cached are skipped.
+pub struct QueryCacheWarmer<C: Codec> {
+    cache_data: Arc<RwLock<CacheData>>,
+    queries: Vec<Box<dyn Query<C>>>,
+    max_queries: usize,
+    time_budget: Duration,
+}
+
+impl<C: Codec> QueryCacheWarmer<C> {
+    pub fn add_query(&mut self, query: Box<dyn Query<C>>) {
+        self.queries.push(query);
+    }
+
+    /// Warms the cache for the segments of `searcher`, returns the number of
+    /// segments a query was cached on.
+    pub fn warm<S: IndexSearcher<C>>(&self, searcher: &S) -> Result<usize> {
+        let deadline = Instant::now() + self.time_budget;
+        let hot_queries = self.cache_data.read()?.hot_queries(self.max_queries);
+        let policy: Arc<QueryCachingPolicy<C>> = Arc::new(AlwaysCacheQueryCachingPolicy);
+
+        let mut weights = Vec::with_capacity(hot_queries.len());
+        for query in &self.queries {
+            let weight = CachingWrapperWeight::new(
+                Arc::clone(&self.cache_data),
+                query.create_weight(searcher, false)?,
+                Arc::clone(&policy),
+            );
+            if let Some(rank) = hot_queries.iter().position(|k| *k == weight.query_key) {
+                weights.push((rank, weight));
+            }
+        }
+        weights.sort_by_key(|&(rank, _)| rank);
+
+        let leaves = searcher.reader().leaves();
+        let mut warmed = 0;
+        for (_, weight) in &weights {
+            for leaf in &leaves {
+                if Instant::now() >= deadline {
+                    return Ok(warmed);
+                }
+                if weight.warm(leaf)? {
+                    warmed += 1;
+                }
+            }
+        }
+        Ok(warmed)
+    }
 }
 
 impl<C: Codec> QueryCache<C> for LRUQueryCache {
@@ -332,7 +502,7 @@ impl<C: Codec> CachingWrapperWeight<C> {
     //
     fn cache_impl<'a, S>(
         &self,
-        scorer: &mut BulkScorer<'a, S>,
+        scorer: &mut DefaultBulkScorer<'a, S>,
         max_doc: i32,
     ) -> Result<CacheDocIdSetEnum>
     where
@@ -353,7 +523,7 @@ impl<C: Codec> CachingWrapperWeight<C> {
 
     fn cache_into_bitset<'a, S>(
         &self,
-        scorer: &mut BulkScorer<'a, S>,
+        scorer: &mut DefaultBulkScorer<'a, S>,
         max_doc: i32,
     ) -> Result<BitDocIdSet<FixedBitSet>>
     where
@@ -375,7 +545,7 @@ impl<C: Codec> CachingWrapperWeight<C> {
 
     fn cache_into_roaring_docid_set<'a, S>(
         &self,
-        scorer: &mut BulkScorer<S>,
+        scorer: &mut DefaultBulkScorer<S>,
         max_doc: i32,
     ) -> Result<RoaringDocIdSet>
     where
@@ -395,6 +565,19 @@ impl<C: Codec> CachingWrapperWeight<C> {
         self.cache_data.read()?.test(leaf_reader)
     }
 
+    /// Caches the query on the segment if it is eligible for caching and the
+    /// query isn't cached on it yet, returns whether it was cached.
+    fn warm(&self, leaf_reader: &LeafReaderContext<'_, C>) -> Result<bool> {
+        {
+            let cache_data = self.cache_data.read()?;
+            if !cache_data.test(leaf_reader)? || cache_data.contains(&self.query_key, leaf_reader) {
+                return Ok(false);
+            }
+        }
+        self.cache(leaf_reader, &self.query_key)?;
+        Ok(true)
+    }
+
     fn cache(
         &self,
         leaf_reader: &LeafReaderContext<'_, C>,
@@ -402,7 +585,7 @@ impl<C: Codec> CachingWrapperWeight<C> {
     ) -> Result<Option<CachedDocIdSetIterEnum>> {
         match self.weight.create_scorer(leaf_reader)? {
             Some(mut scorer) => {
-                let mut bulk_scorer = BulkScorer::new(scorer.as_mut());
+                let mut bulk_scorer = DefaultBulkScorer::new(scorer.as_mut());
                 let max_doc = leaf_reader.reader.max_doc();
 
                 let doc_id_set = self.cache_impl(&mut bulk_scorer, max_doc)?;
@@ -419,7 +602,7 @@ impl<C: Codec> CachingWrapperWeight<C> {
                         .reader
                         .add_core_drop_listener(Deferred::new(move || {
                             let core_key = key;
-                            cache_data.write().unwrap().cache.remove(&core_key);
+                            cache_data.write().unwrap().on_core_closed(&core_key);
                         }))
                 }
 
@@ -475,6 +658,23 @@ impl<C: Codec> Weight<C> for CachingWrapperWeight<C> {
         }
     }
 
+    fn bulk_scorer(
+        &self,
+        leaf_reader: &LeafReaderContext<'_, C>,
+    ) -> Result<Option<Box<dyn BulkScorer>>> {
+        if !self.used.compare_and_swap(false, true, Ordering::AcqRel) {
+            self.policy.on_use(self)
+        }
+
+        // the segments not eligible for caching keep the bulk scorer of the
+        // wrapped weight, the others go through `create_scorer`
+        if self.should_cache(leaf_reader)? {
+            Ok(None)
+        } else {
+            self.weight.bulk_scorer(leaf_reader)
+        }
+    }
+
     fn hash_code(&self) -> u32 {
         self.hash_code
     }
@@ -569,473 +769,18 @@ impl Collector for DocIdSetLeafCollector {
     }
 }
 
-// Number of documents in a block
-static BLOCK_SIZE: usize = 1 << 16;
-// The maximum length for an array, beyond that point we switch to a bitset
-static MAX_ARRAY_LENGTH: usize = 1 << 12;
-
-///
-// {@link DocIdSet} implementation inspired from http://roaringbitmap.org/
-//
-// The space is divided into blocks of 2^16 bits and each block is encoded
-// independently. In each block, if less than 2^12 bits are set, then
-// documents are simply stored in a short[]. If more than 2^16-2^12 bits are
-// set, then the inverse of the set is encoded in a simple short[]. Otherwise
-// a {@link FixedBitSet} is used.
-//
-// @lucene.internal
-//
-struct RoaringDocIdSet {
-    doc_id_sets: Arc<[Option<DocIdSetEnum>]>,
-    cardinality: usize,
-}
-
-impl RoaringDocIdSet {
-    fn new(doc_id_sets: Vec<Option<DocIdSetEnum>>, cardinality: usize) -> RoaringDocIdSet {
-        RoaringDocIdSet {
-            doc_id_sets: Arc::from(doc_id_sets.into_boxed_slice()),
-            cardinality,
-        }
-    }
-}
-
-struct RoaringDocIdSetBuilder {
-    doc_id_sets: Vec<Option<DocIdSetEnum>>,
-    cardinality: usize,
-
-    max_doc: i32,
-    last_doc_id: DocId,
-    current_block: i32,
-    current_block_cardinality: usize,
-
-    // We start by filling the buffer and when it's full we copy the content of
-    // the buffer to the FixedBitSet and put further documents in that bitset
-    buffer: Vec<u16>,
-    dense_buffer: Option<Box<FixedBitSet>>,
-}
-
-impl RoaringDocIdSetBuilder {
-    fn new(max_doc: i32) -> RoaringDocIdSetBuilder {
-        let length = (max_doc + (1 << 16) - 1).unsigned_shift(16);
-        let mut doc_id_sets = Vec::with_capacity(length as usize);
-        for _ in 0..length {
-            doc_id_sets.push(None);
-        }
-
-        RoaringDocIdSetBuilder {
-            doc_id_sets,
-            cardinality: 0,
-            max_doc,
-            last_doc_id: -1,
-            current_block: -1,
-            current_block_cardinality: 0,
-            buffer: vec![0u16; MAX_ARRAY_LENGTH as usize],
-            dense_buffer: None,
-        }
-    }
-
-    fn flush(&mut self) {
-        assert!(self.current_block_cardinality <= BLOCK_SIZE);
-
-        let current_block = self.current_block;
-        let current_block_cardinality = self.current_block_cardinality;
-
-        if current_block_cardinality <= MAX_ARRAY_LENGTH {
-            // Use sparse encoding
-            assert!(self.dense_buffer.is_none());
-            if current_block_cardinality > 0 {
-                let mut docs: Vec<u16> = vec![0u16; current_block_cardinality];
-                docs.copy_from_slice(&self.buffer[0..current_block_cardinality]);
-
-                self.doc_id_sets[current_block as usize] = Some(DocIdSetEnum::ShortArray(
-                    ShortArrayDocIdSet::new(docs, current_block_cardinality),
-                ));
-            }
-        } else {
-            assert!(self.dense_buffer.is_some());
-            assert_eq!(
-                self.dense_buffer.as_mut().unwrap().cardinality(),
-                self.current_block_cardinality
-            );
-
-            if self.dense_buffer.as_mut().unwrap().len() == BLOCK_SIZE as usize
-                && BLOCK_SIZE - self.current_block_cardinality < MAX_ARRAY_LENGTH
-            {
-                let dense_buffer = self.dense_buffer.as_mut().unwrap();
-                // Doc ids are very dense, inverse the encoding
-                let mut exclude_docs =
-                    vec![0u16; (BLOCK_SIZE - self.current_block_cardinality) as usize];
-                let num_bits = dense_buffer.num_bits;
-                dense_buffer.flip(0, num_bits);
-
-                let mut exclude_doc = -1;
-                unsafe {
-                    let ptr = exclude_docs.as_mut_ptr();
-                    for i in 0..exclude_docs.len() {
-                        exclude_doc = dense_buffer.next_set_bit((exclude_doc + 1) as usize);
-                        debug_assert_ne!(exclude_doc, NO_MORE_DOCS);
-                        *ptr.offset(i as isize) = exclude_doc as u16;
-                    }
-                }
-
-                assert!(
-                    exclude_doc as usize + 1 == dense_buffer.len()
-                        || dense_buffer.next_set_bit((exclude_doc + 1) as usize) == NO_MORE_DOCS
-                );
-
-                let length = exclude_docs.len();
-                self.doc_id_sets[self.current_block as usize] =
-                    Some(DocIdSetEnum::NotDocId(NotDocIdSet::new(
-                        ShortArrayDocIdSet::new(exclude_docs, length),
-                        BLOCK_SIZE as i32,
-                    )));
-            } else {
-                // Neither sparse nor super dense, use a fixed bit set
-                let dense_buf = self.dense_buffer.take().unwrap();
-                self.doc_id_sets[self.current_block as usize] =
-                    Some(DocIdSetEnum::BitDocId(BitDocIdSet::new(
-                        Arc::from(dense_buf),
-                        self.current_block_cardinality as usize,
-                    )));
-            }
-        }
-
-        self.cardinality += self.current_block_cardinality;
-        self.dense_buffer = None;
-        self.current_block_cardinality = 0;
-    }
-
-    ///
-    // Add a new doc-id to this builder.
-    // NOTE: doc ids must be added in order.
-    //
-    pub fn add_doc(&mut self, doc_id: i32) -> Result<()> {
-        if doc_id < self.last_doc_id {
-            bail!(
-                "Doc ids must be added in-order, got {} which is <= lastDocID={}",
-                doc_id,
-                self.last_doc_id
-            );
-        }
-
-        let block = doc_id.unsigned_shift(16);
-        if block != self.current_block {
-            // we went to a different block, let's flush what we buffered and start from fresh
-            self.flush();
-            self.current_block = block;
-        }
-
-        if self.current_block_cardinality < MAX_ARRAY_LENGTH {
-            unsafe {
-                *self
-                    .buffer
-                    .as_mut_ptr()
-                    .offset(self.current_block_cardinality as isize) = doc_id as u16
-            };
-        } else {
-            if self.dense_buffer.is_none() {
-                // the buffer is full, let's move to a fixed bit set
-                let num_bits = (1i32 << 16).min(self.max_doc - (block << 16));
-                let mut fixed_bit_set = Box::new(FixedBitSet::new(num_bits as usize));
-                for doc in &self.buffer {
-                    fixed_bit_set.set(*doc as usize);
-                }
-
-                self.dense_buffer = Some(fixed_bit_set);
-            }
-
-            self.dense_buffer
-                .as_mut()
-                .unwrap()
-                .set((doc_id & 0xFFFF) as usize);
-        }
-
-        self.last_doc_id = doc_id;
-        self.current_block_cardinality += 1;
-
-        Ok(())
-    }
-
-    pub fn build(mut self) -> RoaringDocIdSet {
-        self.flush();
-        RoaringDocIdSet::new(self.doc_id_sets, self.cardinality)
-    }
-}
-
-impl DocIdSet for RoaringDocIdSet {
-    type Iter = RoaringDocIterator;
-    fn iterator(&self) -> Result<Option<Self::Iter>> {
-        if self.cardinality == 0 {
-            Ok(None)
-        } else {
-            Ok(Some(RoaringDocIterator::new(
-                self.doc_id_sets.clone(),
-                self.cardinality,
-            )))
-        }
-    }
-}
-
-struct RoaringDocIterator {
-    doc_id_sets: Arc<[Option<DocIdSetEnum>]>,
-    doc: DocId,
-    block: i32,
-    cardinality: usize,
-    sub: Option<DocIdSetDocIterEnum>,
-}
-
-impl RoaringDocIterator {
-    fn new(doc_id_sets: Arc<[Option<DocIdSetEnum>]>, cardinality: usize) -> Self {
-        RoaringDocIterator {
-            doc_id_sets,
-            doc: -1,
-            block: -1,
-            cardinality,
-            // init as stub
-            sub: Some(DocIdSetDocIterEnum::default()),
-        }
-    }
-
-    fn first_doc_from_next_block(&mut self) -> Result<(DocId)> {
-        loop {
-            self.block += 1;
-            if self.block as usize >= self.doc_id_sets.len() {
-                self.sub = None;
-                self.doc = NO_MORE_DOCS;
-
-                return Ok(self.doc);
-            } else if self.doc_id_sets[self.block as usize].is_some() {
-                self.sub = self.doc_id_sets[self.block as usize]
-                    .as_ref()
-                    .unwrap()
-                    .iterator()?;
-                let sub_next = self.sub.as_mut().unwrap().next()?;
-                debug_assert_ne!(sub_next, NO_MORE_DOCS);
-
-                self.doc = (self.block << 16) | sub_next;
-                return Ok(self.doc);
-            }
-        }
-    }
-}
-
-impl DocIterator for RoaringDocIterator {
-    fn doc_id(&self) -> DocId {
-        self.doc
-    }
-
-    fn next(&mut self) -> Result<DocId> {
-        let sub_next = self.sub.as_mut().unwrap().next()?;
-        if sub_next == NO_MORE_DOCS {
-            return self.first_doc_from_next_block();
-        }
-
-        self.doc = (self.block << 16) | sub_next;
-        Ok(self.doc)
-    }
-
-    fn advance(&mut self, target: DocId) -> Result<DocId> {
-        let target_block = target.unsigned_shift(16);
-
-        if target_block != self.block {
-            self.block = target_block;
-            if self.block as usize > self.doc_id_sets.len() {
-                self.sub = None;
-                self.doc = NO_MORE_DOCS;
-
-                return Ok(self.doc);
-            }
-
-            if self.doc_id_sets[self.block as usize].is_none() {
-                return self.first_doc_from_next_block();
-            }
-
-            self.sub = self.doc_id_sets[self.block as usize]
-                .as_ref()
-                .unwrap()
-                .iterator()?;
-        }
-
-        let sub_next = self.sub.as_mut().unwrap().advance(target & 0xFFFF)?;
-        if sub_next == NO_MORE_DOCS {
-            return self.first_doc_from_next_block();
-        }
-
-        self.doc = (self.block << 16) | sub_next;
-        Ok(self.doc)
-    }
-
-    fn cost(&self) -> usize {
-        self.cardinality as usize
-    }
-}
-
-pub struct ShortArrayDocIdSet {
-    docs: Arc<Vec<u16>>,
-    length: usize,
-}
-
-impl ShortArrayDocIdSet {
-    pub fn new(docs: Vec<u16>, length: usize) -> ShortArrayDocIdSet {
-        ShortArrayDocIdSet {
-            docs: Arc::new(docs),
-            length,
-        }
-    }
-}
-
-impl DocIdSet for ShortArrayDocIdSet {
-    type Iter = ShortArrayDocIterator;
-    fn iterator(&self) -> Result<Option<Self::Iter>> {
-        Ok(Some(ShortArrayDocIterator::new(
-            self.docs.clone(),
-            self.length,
-        )))
-    }
-}
-
-pub struct ShortArrayDocIterator {
-    docs: Arc<Vec<u16>>,
-    length: usize,
-    i: i32,
-    doc: DocId,
-}
-
-impl ShortArrayDocIterator {
-    pub fn new(docs: Arc<Vec<u16>>, length: usize) -> ShortArrayDocIterator {
-        ShortArrayDocIterator {
-            docs,
-            length,
-            i: -1,
-            doc: -1,
-        }
-    }
-}
-
-impl DocIterator for ShortArrayDocIterator {
-    fn doc_id(&self) -> DocId {
-        self.doc
-    }
-
-    fn next(&mut self) -> Result<DocId> {
-        self.i += 1;
-        if self.i as usize >= self.length {
-            Ok(NO_MORE_DOCS)
-        } else {
-            self.doc = i32::from(unsafe { *self.docs.as_ptr().offset(self.i as isize) });
-            Ok(self.doc)
-        }
-    }
-
-    fn advance(&mut self, target: DocId) -> Result<DocId> {
-        self.doc = if (self.i + 1) as usize >= self.length {
-            NO_MORE_DOCS
-        } else {
-            let adv = match self.docs[(self.i + 1) as usize..self.length]
-                .binary_search(&(target as u16))
-            {
-                Ok(x) => x,
-                Err(e) => e,
-            };
-            self.i += (adv + 1) as i32;
-            if self.i < self.length as i32 {
-                i32::from(unsafe { *self.docs.as_ptr().offset(self.i as isize) })
-            } else {
-                NO_MORE_DOCS
-            }
-        };
-
-        Ok(self.doc)
-    }
-
-    fn cost(&self) -> usize {
-        self.length
-    }
-}
-
-pub struct NotDocIdSet<T: DocIdSet> {
-    set: T,
-    max_doc: i32,
-}
-
-impl<T: DocIdSet> NotDocIdSet<T> {
-    pub fn new(set: T, max_doc: i32) -> NotDocIdSet<T> {
-        NotDocIdSet { set, max_doc }
-    }
-}
-
-impl<T: DocIdSet> DocIdSet for NotDocIdSet<T> {
-    type Iter = NotDocIterator<T::Iter>;
-    fn iterator(&self) -> Result<Option<Self::Iter>> {
-        match self.set.iterator()? {
-            Some(iter) => Ok(Some(NotDocIterator::new(iter, self.max_doc))),
-            _ => Ok(None),
-        }
-    }
-    //    fn bits(&self) -> Result<Option<ImmutableBitSetRef>> {
-    //        self.set.bits()
-    //    }
-}
-
-pub struct NotDocIterator<DI: DocIterator> {
-    max_doc: i32,
-    doc: DocId,
-    next_skipped_doc: i32,
-    iterator: DI,
-}
-
-impl<DI: DocIterator> NotDocIterator<DI> {
-    pub fn new(iterator: DI, max_doc: i32) -> Self {
-        NotDocIterator {
-            max_doc,
-            doc: -1,
-            next_skipped_doc: -1,
-            iterator,
-        }
-    }
+enum CacheDocIdSetEnum {
+    Bit(BitDocIdSet<FixedBitSet>),
+    Roaring(RoaringDocIdSet),
 }
 
-impl<DI: DocIterator> DocIterator for NotDocIterator<DI> {
-    fn doc_id(&self) -> DocId {
-        self.doc
-    }
-
-    fn next(&mut self) -> Result<DocId> {
-        let adv = self.doc + 1;
-        self.advance(adv)
-    }
-
-    fn advance(&mut self, target: DocId) -> Result<DocId> {
-        self.doc = target;
-
-        if self.doc > self.next_skipped_doc {
-            self.next_skipped_doc = self.iterator.advance(self.doc)?;
-        }
-
-        loop {
-            if self.doc >= self.max_doc {
-                self.doc = NO_MORE_DOCS;
-                return Ok(self.doc);
-            }
-
-            debug_assert!(self.doc <= self.next_skipped_doc);
-            if self.doc != self.next_skipped_doc {
-                return Ok(self.doc);
-            }
-
-            self.doc += 1;
-            self.next_skipped_doc = self.iterator.next()?;
+impl CacheDocIdSetEnum {
+    fn ram_bytes_used(&self) -> usize {
+        match self {
+            CacheDocIdSetEnum::Bit(s) => s.ram_bytes_used(),
+            CacheDocIdSetEnum::Roaring(s) => s.ram_bytes_used(),
         }
     }
-
-    fn cost(&self) -> usize {
-        self.max_doc as usize
-    }
-}
-
-enum CacheDocIdSetEnum {
-    Bit(BitDocIdSet<FixedBitSet>),
-    Roaring(RoaringDocIdSet),
 }
 
 impl DocIdSet for CacheDocIdSetEnum {
@@ -1130,3 +875,175 @@ impl DocIterator for CachedDocIdSetIterEnum {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::analysis::whitespace_tokenizer::WhitespaceTokenizer;
+    use core::analysis::TokenStream;
+    use core::doc::{Field, FieldType, NumericDocValuesField};
+    use core::index::{Fieldable, IndexOptions, IndexWriter, IndexWriterConfig, Term};
+    use core::search::searcher::DefaultIndexSearcher;
+    use core::search::term_query::TermQuery;
+    use core::store::RAMDirectory;
+
+    use std::io::Cursor;
+
+    #[test]
+    fn test_hot_queries() {
+        let cache = LRUQueryCache::new(2);
+        {
+            let mut cache_data = cache.cache_data.write().unwrap();
+            for key in &["a", "b"] {
+                cache_data
+                    .unique_queries
+                    .insert(key.to_string(), key.to_string());
+                cache_data.on_query_cache(key);
+            }
+            cache_data.on_hit("b");
+            cache_data.on_hit("b");
+            cache_data.on_hit("a");
+            // not cached
+            cache_data.on_hit("c");
+        }
+        assert_eq!(cache.hot_queries(3).unwrap(), vec!["b", "a"]);
+        assert_eq!(cache.hot_queries(1).unwrap(), vec!["b"]);
+
+        // the least recently used query is evicted with its hits
+        {
+            let mut cache_data = cache.cache_data.write().unwrap();
+            cache_data.evict_if_necessary(0).unwrap();
+            cache_data
+                .unique_queries
+                .insert("c".to_string(), "c".to_string());
+            cache_data.on_query_cache("c");
+        }
+        assert_eq!(cache.hot_queries(3).unwrap(), vec!["b", "c"]);
+    }
+
+    #[test]
+    fn test_max_ram_bytes_used() {
+        let dir = Arc::new(RAMDirectory::default());
+        let writer = IndexWriter::new(dir, Arc::new(IndexWriterConfig::default())).unwrap();
+        let doc: Vec<Box<dyn Fieldable>> = vec![Box::new(NumericDocValuesField::new("price", 1))];
+        writer.add_document(doc).unwrap();
+        let reader = writer.get_reader(true, false).unwrap();
+        let leaves = reader.leaves();
+
+        let bit_set = |num_words: usize| {
+            let bits = FixedBitSet::new(num_words * 64);
+            CacheDocIdSetEnum::Bit(BitDocIdSet::new(Arc::new(bits), 0))
+        };
+        let cache = LRUQueryCache::with_max_ram_bytes_used(10, 200);
+        {
+            let mut cache_data = cache.cache_data.write().unwrap();
+            for key in &["a", "b", "c"] {
+                cache_data.put_if_absent(key, &leaves[0], bit_set(10)).unwrap();
+            }
+            // the least recently used query made room for the last one
+            assert!(!cache_data.contains("a", &leaves[0]));
+            assert!(cache_data.contains("b", &leaves[0]));
+            assert!(cache_data.contains("c", &leaves[0]));
+
+            // larger than the whole cache
+            assert!(!cache_data.put_if_absent("d", &leaves[0], bit_set(30)).unwrap());
+            assert!(!cache_data.contains("d", &leaves[0]));
+        }
+        assert_eq!(cache.ram_bytes_used().unwrap(), 160);
+
+        cache
+            .cache_data
+            .write()
+            .unwrap()
+            .on_core_closed(leaves[0].reader.core_cache_key());
+        assert_eq!(cache.ram_bytes_used().unwrap(), 0);
+
+        writer.close().unwrap();
+    }
+
+    #[test]
+    fn test_warm_reopened_reader() {
+        let dir = Arc::new(RAMDirectory::default());
+        let writer = IndexWriter::new(dir, Arc::new(IndexWriterConfig::default())).unwrap();
+        let mut field_type = FieldType::default();
+        field_type.index_options = IndexOptions::Docs;
+        let add_docs = |num_docs: usize| {
+            for i in 0..num_docs {
+                let text = if i % 2 == 0 { "a" } else { "b" };
+                let tokens: Box<dyn TokenStream> = Box::new(WhitespaceTokenizer::new(Box::new(
+                    Cursor::new(text.as_bytes().to_vec()),
+                )));
+                let doc: Vec<Box<dyn Fieldable>> = vec![Box::new(Field::new(
+                    "body".into(),
+                    field_type.clone(),
+                    None,
+                    Some(tokens),
+                ))];
+                writer.add_document(doc).unwrap();
+            }
+        };
+        let query = |text: &str| -> Box<dyn Query<_>> {
+            let term = Term::new("body".into(), text.as_bytes().to_vec());
+            Box::new(TermQuery::new(term, 1.0, None))
+        };
+
+        let cache = LRUQueryCache::new(10);
+        // cache the queries on the small test segments too
+        cache.cache_data.write().unwrap().min_size = 0;
+        let policy: Arc<QueryCachingPolicy<_>> = Arc::new(AlwaysCacheQueryCachingPolicy);
+
+        add_docs(10);
+        let reader1 = writer.get_reader(true, false).unwrap();
+        let searcher1 = DefaultIndexSearcher::new(&reader1);
+        let leaves1 = reader1.leaves();
+        // both queries are cached on the first segment, only "a" is hit
+        let weight = |text: &str| {
+            let weight = query(text).create_weight(&searcher1, false).unwrap();
+            CachingWrapperWeight::new(Arc::clone(&cache.cache_data), weight, Arc::clone(&policy))
+        };
+        let (hot, cold) = (weight("a"), weight("b"));
+        assert!(hot.warm(&leaves1[0]).unwrap());
+        assert!(cold.warm(&leaves1[0]).unwrap());
+        for _ in 0..3 {
+            let cached = cache.cache_data.write().unwrap().get(&hot.query_key, &leaves1[0]);
+            assert!(cached.unwrap().is_some());
+        }
+        assert_eq!(cache.hot_queries(1).unwrap(), vec![hot.query_key.clone()]);
+
+        add_docs(6);
+        let reader2 = reader1.open_if_changed(None).unwrap().unwrap();
+        let searcher2 = DefaultIndexSearcher::new(&reader2);
+        let leaves2 = reader2.leaves();
+        assert_eq!(leaves2.len(), 2);
+
+        // only the hot query is cached, on the new segment only
+        let mut warmer = cache.warmer(1, Duration::from_secs(60));
+        warmer.add_query(query("a"));
+        warmer.add_query(query("b"));
+        assert_eq!(warmer.warm(&searcher2).unwrap(), 1);
+        {
+            let cache_data = cache.cache_data.read().unwrap();
+            assert!(cache_data.contains(&hot.query_key, &leaves2[0]));
+            assert!(cache_data.contains(&hot.query_key, &leaves2[1]));
+            assert!(!cache_data.contains(&cold.query_key, &leaves2[1]));
+        }
+        // the cached set matches the docs of the query on the new segment
+        let cached = cache
+            .cache_data
+            .write()
+            .unwrap()
+            .get(&hot.query_key, &leaves2[1])
+            .unwrap();
+        let mut iter = cached.unwrap();
+        let mut docs = Vec::new();
+        while iter.next().unwrap() != NO_MORE_DOCS {
+            docs.push(iter.doc_id());
+        }
+        assert_eq!(docs, vec![0, 2, 4]);
+
+        // nothing is left to warm
+        assert_eq!(warmer.warm(&searcher2).unwrap(), 0);
+
+        writer.close().unwrap();
+    }
+}
@@ -0,0 +1,294 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::any::Any;
+use std::fmt;
+use std::sync::Arc;
+
+use core::codec::Codec;
+use core::index::{LeafReaderContext, SeekStatus, TermIterator, Terms};
+use core::search::explanation::Explanation;
+use core::search::match_all::ConstantScoreScorer;
+use core::search::posting_iterator::PostingIteratorFlags;
+use core::search::searcher::SearchPlanBuilder;
+use core::search::term_query::TermQuery;
+use core::search::{DocIterator, Query, Scorer, Weight};
+use core::util::automaton::Automaton;
+use core::util::{DocId, DocIdSetBuilder};
+
+use error::Result;
+
+pub const REGEXP: &str = "regexp";
+
+/// Matches the docs of the terms of a field which are entirely matched by a
+/// regular expression, with a constant score. See `Automaton::from_regexp`
+/// for the supported syntax.
+///
+/// The expression is compiled once into a DFA, which then drives the seeks of
+/// the term dictionary of each segment, so only the terms which may match are
+/// read. A leading wildcard like `.*foo` still reads every term.
+pub struct RegexpQuery {
+    field: String,
+    regexp: String,
+    automaton: Arc<Automaton>,
+    boost: f32,
+}
+
+impl RegexpQuery {
+    pub fn new(field: String, regexp: &str) -> Result<RegexpQuery> {
+        Ok(RegexpQuery {
+            field,
+            regexp: regexp.to_string(),
+            automaton: Arc::new(Automaton::from_regexp(regexp)?),
+            boost: 1.0f32,
+        })
+    }
+
+    pub fn with_boost(mut self, boost: f32) -> RegexpQuery {
+        self.boost = boost;
+        self
+    }
+}
+
+impl<C: Codec> Query<C> for RegexpQuery {
+    fn create_weight(
+        &self,
+        _searcher: &dyn SearchPlanBuilder<C>,
+        _needs_scores: bool,
+    ) -> Result<Box<dyn Weight<C>>> {
+        Ok(Box::new(RegexpWeight {
+            field: self.field.clone(),
+            automaton: Arc::clone(&self.automaton),
+            boost: self.boost,
+            weight: self.boost,
+            norm: 1.0f32,
+            query: format!("{}", self),
+        }))
+    }
+
+    fn extract_terms(&self) -> Vec<TermQuery> {
+        vec![]
+    }
+
+    fn query_type(&self) -> &'static str {
+        REGEXP
+    }
+
+    fn as_any(&self) -> &Any {
+        self
+    }
+}
+
+impl fmt::Display for RegexpQuery {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "RegexpQuery(field: {}, regexp: {}, boost: {})",
+            self.field, self.regexp, self.boost
+        )
+    }
+}
+
+/// Calls `on_match` with `terms` positioned on each term accepted by
+/// `automaton`, in order.
+///
+/// After each term the automaton gives the smallest string which may still
+/// match: the next term is used if it isn't smaller, otherwise the terms are
+/// sought to that string.
+fn intersect<T, F>(automaton: &Automaton, terms: &mut T, mut on_match: F) -> Result<()>
+where
+    T: TermIterator,
+    F: FnMut(&mut T) -> Result<()>,
+{
+    let target = match automaton.first_candidate() {
+        Some(target) => target,
+        None => return Ok(()),
+    };
+    let mut status = terms.seek_ceil(target.as_bytes())?;
+    while status != SeekStatus::End {
+        let term = terms.term()?.to_vec();
+        if automaton.run(&term) {
+            on_match(terms)?;
+        }
+        let target = match ::std::str::from_utf8(&term) {
+            Ok(term) => match automaton.next_candidate(term) {
+                Some(target) => Some(target),
+                None => return Ok(()),
+            },
+            // the terms which aren't UTF-8 are skipped one by one
+            Err(_) => None,
+        };
+        status = match terms.next()? {
+            Some(ref next) if target.as_ref().map_or(true, |t| next[..] >= *t.as_bytes()) => {
+                SeekStatus::NotFound
+            }
+            Some(_) => terms.seek_ceil(target.unwrap().as_bytes())?,
+            None => SeekStatus::End,
+        };
+    }
+    Ok(())
+}
+
+struct RegexpWeight {
+    field: String,
+    automaton: Arc<Automaton>,
+    boost: f32,
+    weight: f32,
+    norm: f32,
+    query: String,
+}
+
+impl<C: Codec> Weight<C> for RegexpWeight {
+    fn create_scorer(
+        &self,
+        leaf_reader: &LeafReaderContext<'_, C>,
+    ) -> Result<Option<Box<dyn Scorer>>> {
+        let reader = leaf_reader.reader;
+        let terms = match reader.terms(&self.field)? {
+            Some(terms) => terms,
+            None => return Ok(None),
+        };
+        let mut builder = DocIdSetBuilder::from_terms(reader.max_doc(), &terms)?;
+        let mut terms_iter = terms.iterator()?;
+        intersect(&self.automaton, &mut terms_iter, |terms_iter| {
+            let mut postings = terms_iter.postings_with_flags(PostingIteratorFlags::NONE)?;
+            builder.add(&mut postings)
+        })?;
+        let set = builder.build();
+        match ConstantScoreScorer::from_doc_id_set(self.weight, &set, reader.max_doc())? {
+            Some(scorer) => Ok(Some(Box::new(scorer))),
+            None => Ok(None),
+        }
+    }
+
+    fn query_type(&self) -> &'static str {
+        REGEXP
+    }
+
+    fn normalize(&mut self, norm: f32, boost: f32) {
+        self.norm = norm;
+        self.weight = norm * boost * self.boost;
+    }
+
+    fn value_for_normalization(&self) -> f32 {
+        self.weight * self.weight
+    }
+
+    fn needs_scores(&self) -> bool {
+        false
+    }
+
+    fn explain(&self, reader: &LeafReaderContext<'_, C>, doc: DocId) -> Result<Explanation> {
+        let matched = match self.create_scorer(reader)? {
+            Some(mut scorer) => scorer.advance(doc)? == doc,
+            None => false,
+        };
+        if !matched {
+            return Ok(Explanation::new(
+                false,
+                0.0f32,
+                format!("{} doesn't match doc {}", self.query, doc),
+                vec![],
+            ));
+        }
+        Ok(Explanation::new(
+            true,
+            self.weight,
+            format!("{}, product of:", self.query),
+            vec![
+                Explanation::new(true, self.weight / self.norm, "boost".to_string(), vec![]),
+                Explanation::new(true, self.norm, "queryNorm".to_string(), vec![]),
+            ],
+        ))
+    }
+}
+
+impl fmt::Display for RegexpWeight {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "RegexpWeight(query: {})", self.query)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::analysis::whitespace_tokenizer::WhitespaceTokenizer;
+    use core::analysis::TokenStream;
+    use core::doc::{Field, FieldType};
+    use core::index::{Fieldable, IndexOptions, IndexReader, IndexWriter, IndexWriterConfig};
+    use core::search::searcher::{DefaultIndexSearcher, IndexSearcher};
+    use core::store::RAMDirectory;
+
+    use std::io::Cursor;
+
+    #[test]
+    fn test_regexp_query() {
+        let dir = Arc::new(RAMDirectory::default());
+        let writer = IndexWriter::new(dir, Arc::new(IndexWriterConfig::default())).unwrap();
+        let mut field_type = FieldType::default();
+        field_type.index_options = IndexOptions::DocsAndFreqs;
+        // 3000 terms over 1000 docs
+        for i in 0..1000 {
+            let text = format!("a{:03} b{:03} c{:03}", i, (i * 7) % 1000, 999 - i);
+            let tokens: Box<dyn TokenStream> = Box::new(WhitespaceTokenizer::new(Box::new(
+                Cursor::new(text.into_bytes()),
+            )));
+            let doc: Vec<Box<dyn Fieldable>> = vec![Box::new(Field::new(
+                "body".into(),
+                field_type.clone(),
+                None,
+                Some(tokens),
+            ))];
+            writer.add_document(doc).unwrap();
+        }
+        let reader = Arc::new(writer.get_reader(true, false).unwrap());
+        let searcher = DefaultIndexSearcher::new(Arc::clone(&reader));
+        let count = |regexp: &str| {
+            let query = RegexpQuery::new("body".into(), regexp).unwrap();
+            searcher.count(&query).unwrap()
+        };
+
+        assert_eq!(count("a.*"), 1000);
+        assert_eq!(count("a00[0-9]"), 10);
+        // anchored: matches whole terms only
+        assert_eq!(count("a00"), 0);
+        assert_eq!(count("00.*"), 0);
+        assert_eq!(count("a12[^0-4]"), 5);
+        assert_eq!(count("(a|c)99[89]"), 4);
+        assert_eq!(count("b0{2}7|a5{3}"), 2);
+        assert_eq!(count("[ab]1.3"), 20);
+        assert_eq!(count("d.*"), 0);
+        assert!(RegexpQuery::new("body".into(), "a(0").is_err());
+
+        // only the matching terms are visited, in order
+        let leaves = reader.leaves();
+        let automaton = Automaton::from_regexp("[bc]9(1|8)[05]").unwrap();
+        let mut matched = vec![];
+        for leaf in &leaves {
+            let terms = leaf.reader.terms("body").unwrap().unwrap();
+            let mut terms_iter = terms.iterator().unwrap();
+            intersect(&automaton, &mut terms_iter, |terms_iter| {
+                matched.push(String::from_utf8(terms_iter.term()?.to_vec()).unwrap());
+                Ok(())
+            })
+            .unwrap();
+        }
+        matched.sort();
+        assert_eq!(
+            matched,
+            vec!["b910", "b915", "b980", "b985", "c910", "c915", "c980", "c985"]
+        );
+
+        writer.close().unwrap();
+    }
+}
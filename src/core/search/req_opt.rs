@@ -17,9 +17,15 @@ use error::Result;
 
 /// A Scorer for queries with a required part and an optional part.
 /// Delays `advance()` on the optional part until a `score()` is needed.
+///
+/// The score of the required part is cached for the current doc, so that
+/// scoring the same doc again, e.g. from several collectors, doesn't
+/// recompute it.
 pub struct ReqOptScorer {
     req_scorer: Box<dyn Scorer>,
     opt_scorer: Box<dyn Scorer>,
+    req_score_doc: DocId,
+    req_score: f32,
 }
 
 impl ReqOptScorer {
@@ -27,6 +33,8 @@ impl ReqOptScorer {
         ReqOptScorer {
             req_scorer,
             opt_scorer,
+            req_score_doc: -1,
+            req_score: 0f32,
         }
     }
 }
@@ -34,7 +42,11 @@ impl ReqOptScorer {
 impl Scorer for ReqOptScorer {
     fn score(&mut self) -> Result<f32> {
         let current_doc = self.req_scorer.doc_id();
-        let mut score = self.req_scorer.score()?;
+        if self.req_score_doc != current_doc {
+            self.req_score = self.req_scorer.score()?;
+            self.req_score_doc = current_doc;
+        }
+        let mut score = self.req_score;
 
         let mut opt_doc = self.opt_scorer.doc_id();
         if opt_doc < current_doc {
@@ -94,6 +106,40 @@ mod tests {
     use core::search::tests::*;
     use core::search::*;
 
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    /// Counts the calls to `score`.
+    struct CountingScorer {
+        scorer: Box<dyn Scorer>,
+        score_count: Arc<AtomicUsize>,
+    }
+
+    impl Scorer for CountingScorer {
+        fn score(&mut self) -> Result<f32> {
+            self.score_count.fetch_add(1, Ordering::SeqCst);
+            self.scorer.score()
+        }
+    }
+
+    impl DocIterator for CountingScorer {
+        fn doc_id(&self) -> DocId {
+            self.scorer.doc_id()
+        }
+
+        fn next(&mut self) -> Result<DocId> {
+            self.scorer.next()
+        }
+
+        fn advance(&mut self, target: DocId) -> Result<DocId> {
+            self.scorer.advance(target)
+        }
+
+        fn cost(&self) -> usize {
+            self.scorer.cost()
+        }
+    }
+
     #[test]
     fn test_score() {
         let s1 = create_mock_scorer(vec![1, 2, 3, 4, 5]);
@@ -118,4 +164,28 @@ mod tests {
 
         assert_eq!(scorer.next().unwrap(), NO_MORE_DOCS);
     }
+
+    #[test]
+    fn test_required_score_cached() {
+        let score_count = Arc::new(AtomicUsize::new(0));
+        let req_scorer = CountingScorer {
+            scorer: Box::new(create_mock_scorer(vec![1, 2, 3, 4])),
+            score_count: Arc::clone(&score_count),
+        };
+        let opt_scorer = create_mock_scorer(vec![2, 4, 6]);
+        let mut scorer = ReqOptScorer::new(Box::new(req_scorer), Box::new(opt_scorer));
+
+        let mut collected = 0;
+        let mut scores = vec![];
+        while scorer.next().unwrap() != NO_MORE_DOCS {
+            collected += 1;
+            // e.g. a top docs collector and a max score collector
+            let score = scorer.score().unwrap();
+            assert!((scorer.score().unwrap() - score).abs() < ::std::f32::EPSILON);
+            scores.push(score);
+        }
+        assert_eq!(collected, 4);
+        assert_eq!(scores, vec![1.0, 4.0, 3.0, 8.0]);
+        assert_eq!(score_count.load(Ordering::SeqCst), collected);
+    }
 }
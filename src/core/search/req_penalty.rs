@@ -0,0 +1,145 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::search::{DocIterator, Scorer};
+use core::util::DocId;
+use error::Result;
+
+/// A Scorer for queries with a required part and a penalized part.
+///
+/// Unlike an exclusion, docs matching the penalized part are still returned,
+/// but their score is multiplied by `penalty`. The penalized scorer is only
+/// advanced when a `score()` is needed.
+pub struct ReqPenaltyScorer {
+    req_scorer: Box<dyn Scorer>,
+    penalty_scorer: Box<dyn Scorer>,
+    penalty: f32,
+}
+
+impl ReqPenaltyScorer {
+    pub fn new(
+        req_scorer: Box<dyn Scorer>,
+        penalty_scorer: Box<dyn Scorer>,
+        penalty: f32,
+    ) -> ReqPenaltyScorer {
+        ReqPenaltyScorer {
+            req_scorer,
+            penalty_scorer,
+            penalty,
+        }
+    }
+}
+
+impl Scorer for ReqPenaltyScorer {
+    fn score(&mut self) -> Result<f32> {
+        let current_doc = self.req_scorer.doc_id();
+        let score = self.req_scorer.score()?;
+
+        let mut penalty_doc = self.penalty_scorer.doc_id();
+        if penalty_doc < current_doc {
+            penalty_doc = self.penalty_scorer.advance(current_doc)?;
+        }
+
+        if penalty_doc == current_doc {
+            Ok(score * self.penalty)
+        } else {
+            Ok(score)
+        }
+    }
+
+    fn support_two_phase(&self) -> bool {
+        self.req_scorer.support_two_phase()
+    }
+}
+
+impl DocIterator for ReqPenaltyScorer {
+    fn doc_id(&self) -> DocId {
+        self.req_scorer.doc_id()
+    }
+
+    fn next(&mut self) -> Result<DocId> {
+        self.req_scorer.next()
+    }
+
+    fn advance(&mut self, target: DocId) -> Result<DocId> {
+        self.req_scorer.advance(target)
+    }
+
+    fn cost(&self) -> usize {
+        self.req_scorer.cost()
+    }
+
+    fn matches(&mut self) -> Result<bool> {
+        self.req_scorer.matches()
+    }
+
+    fn match_cost(&self) -> f32 {
+        self.req_scorer.match_cost()
+    }
+
+    fn approximate_next(&mut self) -> Result<DocId> {
+        self.req_scorer.approximate_next()
+    }
+
+    fn approximate_advance(&mut self, target: DocId) -> Result<DocId> {
+        self.req_scorer.approximate_advance(target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::search::tests::*;
+    use core::search::*;
+
+    #[test]
+    fn test_score() {
+        let req: Box<dyn Scorer> = Box::new(create_mock_scorer(vec![1, 2, 3, 4, 5]));
+        let penalty: Box<dyn Scorer> = Box::new(create_mock_scorer(vec![2, 4, 6]));
+        let mut scorer = ReqPenaltyScorer::new(req, penalty, 0.5);
+
+        assert_eq!(scorer.next().unwrap(), 1);
+        assert!((scorer.score().unwrap() - 1.0).abs() < ::std::f32::EPSILON);
+
+        assert_eq!(scorer.next().unwrap(), 2);
+        assert!((scorer.score().unwrap() - 1.0).abs() < ::std::f32::EPSILON);
+
+        assert_eq!(scorer.next().unwrap(), 3);
+        assert!((scorer.score().unwrap() - 3.0).abs() < ::std::f32::EPSILON);
+
+        assert_eq!(scorer.advance(4).unwrap(), 4);
+        assert!((scorer.score().unwrap() - 2.0).abs() < ::std::f32::EPSILON);
+
+        assert_eq!(scorer.next().unwrap(), 5);
+        assert!((scorer.score().unwrap() - 5.0).abs() < ::std::f32::EPSILON);
+
+        assert_eq!(scorer.next().unwrap(), NO_MORE_DOCS);
+    }
+
+    #[test]
+    fn test_two_phase_penalty() {
+        let req: Box<dyn Scorer> = Box::new(create_mock_scorer(vec![1, 2, 3]));
+        let penalty: Box<dyn Scorer> =
+            Box::new(create_mock_two_phase_scorer(vec![1, 2, 3], vec![1, 3]));
+        let mut scorer = ReqPenaltyScorer::new(req, penalty, 0.25);
+
+        assert_eq!(scorer.next().unwrap(), 1);
+        assert!((scorer.score().unwrap() - 1.0).abs() < ::std::f32::EPSILON);
+
+        assert_eq!(scorer.next().unwrap(), 2);
+        assert!((scorer.score().unwrap() - 0.5).abs() < ::std::f32::EPSILON);
+
+        assert_eq!(scorer.next().unwrap(), 3);
+        assert!((scorer.score().unwrap() - 3.0).abs() < ::std::f32::EPSILON);
+    }
+}
@@ -27,6 +27,13 @@ use core::search::{BatchScorer, RescoreRequest, Rescorer, Weight};
 use core::util::DocId;
 use core::util::{IndexedContext, VariantValue};
 
+/// A `Rescorer` re-running the `RescoreRequest` query over the first
+/// `window_size` hits.
+///
+/// The hits of the window are re-sorted by their scores combined with
+/// `RescoreMode`, the first pass score weighted by `query_weight` and the
+/// second pass one by `rescore_weight`. The hits after the window keep their
+/// order and scores.
 #[derive(Default)]
 pub struct QueryRescorer;
 
@@ -273,12 +280,9 @@ impl QueryRescorer {
         }
     }
 
-    fn combine_docs<C: Codec>(
-        &self,
-        docs: &mut TopDocs,
-        resorted: Vec<ScoreDocHit>,
-        ctx: &RescoreRequest<C>,
-    ) {
+    /// Replaces the first hits of `docs` by the `resorted` window, the hits
+    /// after the window keep their order and scores.
+    fn combine_docs(&self, docs: &mut TopDocs, resorted: Vec<ScoreDocHit>) {
         let rescore_len = resorted.len();
         let mut resorted = resorted;
         // used for collapsing top docs
@@ -293,14 +297,6 @@ impl QueryRescorer {
             for i in 0..rescore_len {
                 hits[rescore_len - 1 - i] = resorted.pop().unwrap();
             }
-            if hits.len() > rescore_len {
-                for hit in hits.iter_mut().skip(rescore_len) {
-                    // TODO: shouldn't this be up to the ScoreMode?  I.e., we should just invoke
-                    // ScoreMode.combine, passing 0.0f for the secondary score?
-                    let score = hit.score();
-                    hit.set_score(score * ctx.query_weight);
-                }
-            }
         }
 
         // adjust collapse_values for collapse top docs after rescore
@@ -491,7 +487,7 @@ impl Rescorer for QueryRescorer {
         }
 
         let rescore_hits = self.query_rescore(searcher, rescore_req, top_docs)?;
-        self.combine_docs(top_docs, rescore_hits, rescore_req);
+        self.combine_docs(top_docs, rescore_hits);
 
         Ok(())
     }
@@ -546,3 +542,56 @@ impl Rescorer for QueryRescorer {
         self.explain_es(searcher, req, first, doc)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::codec::tests::TestCodec;
+    use core::search::match_all::MatchAllDocsQuery;
+    use core::search::top_docs::{ScoreDoc, TopScoreDocs};
+    use core::search::RescoreMode;
+
+    fn hits(docs: &[(DocId, f32)]) -> Vec<ScoreDocHit> {
+        docs.iter()
+            .map(|&(doc, score)| ScoreDocHit::Score(ScoreDoc::new(doc, score)))
+            .collect()
+    }
+
+    fn doc_scores(top_docs: &TopDocs) -> Vec<(DocId, f32)> {
+        top_docs
+            .score_docs()
+            .iter()
+            .map(|d| (d.doc_id(), d.score()))
+            .collect()
+    }
+
+    #[test]
+    fn test_combine_score() {
+        let req: RescoreRequest<TestCodec> = RescoreRequest::new(
+            Box::new(MatchAllDocsQuery::default()),
+            0.5,
+            2.0,
+            RescoreMode::Total,
+            10,
+            false,
+        );
+        let rescorer = QueryRescorer;
+        assert!((rescorer.combine_score(&req, 4.0, true, 3.0) - 8.0).abs() < ::std::f32::EPSILON);
+        assert!((rescorer.combine_score(&req, 4.0, false, 0.0) - 2.0).abs() < ::std::f32::EPSILON);
+    }
+
+    #[test]
+    fn test_combine_docs_keeps_hits_after_window() {
+        let mut top_docs = TopDocs::Score(TopScoreDocs::new(
+            4,
+            hits(&[(3, 4.0), (1, 3.0), (0, 2.0), (2, 1.0)]),
+        ));
+        // the window of the two first hits, rescored and sorted
+        let resorted = hits(&[(1, 9.0), (3, 5.0)]);
+        QueryRescorer.combine_docs(&mut top_docs, resorted);
+        assert_eq!(
+            doc_scores(&top_docs),
+            vec![(1, 9.0), (3, 5.0), (0, 2.0), (2, 1.0)]
+        );
+    }
+}
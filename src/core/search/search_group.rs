@@ -16,6 +16,7 @@ use std::hash::{Hash, Hasher};
 
 use core::search::field_comparator::ComparatorValue;
 use core::search::sort_field::SortFieldType;
+use core::search::top_docs::ScoreDoc;
 use core::util::DocId;
 use core::util::VariantValue;
 
@@ -159,6 +160,75 @@ impl PartialEq for CollectedSearchGroup {
     }
 }
 
+/// A group found by the `FirstPassGroupingCollector`, with the best hit of
+/// the group. The docs without group value are grouped under `None`.
+///
+/// Groups are ordered by the descending score of their best hit, then by
+/// the ascending doc id of this hit.
+#[derive(Debug, Clone)]
+pub struct SearchGroup {
+    pub group_value: Option<Vec<u8>>,
+    pub top_doc: DocId,
+    pub score: f32,
+}
+
+impl SearchGroup {
+    pub fn new(group_value: Option<Vec<u8>>, top_doc: DocId, score: f32) -> SearchGroup {
+        SearchGroup {
+            group_value,
+            top_doc,
+            score,
+        }
+    }
+
+    fn top_hit(&self) -> ScoreDoc {
+        ScoreDoc::new(self.top_doc, self.score)
+    }
+}
+
+impl Ord for SearchGroup {
+    fn cmp(&self, other: &SearchGroup) -> Ordering {
+        self.top_hit().cmp(&other.top_hit())
+    }
+}
+
+impl PartialOrd for SearchGroup {
+    fn partial_cmp(&self, other: &SearchGroup) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for SearchGroup {
+    fn eq(&self, other: &SearchGroup) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for SearchGroup {}
+
+/// The top hits of a group, collected by the `SecondPassGroupingCollector`.
+#[derive(Debug, Clone)]
+pub struct GroupDocs {
+    pub group_value: Option<Vec<u8>>,
+    /// The score of the best hit of the group, `NaN` if no hit was kept.
+    pub max_score: f32,
+    /// The number of hits of the group.
+    pub total_hits: usize,
+    /// The top hits of the group, by descending score.
+    pub score_docs: Vec<ScoreDoc>,
+}
+
+/// The result of a grouping search: the top groups, in the order of the
+/// first pass, with their top hits.
+#[derive(Debug, Clone)]
+pub struct TopGroups {
+    /// The number of hits of the query.
+    pub total_hit_count: usize,
+    /// The number of hits which belong to one of the top groups.
+    pub total_grouped_hit_count: usize,
+    pub groups: Vec<GroupDocs>,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -295,4 +365,16 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn test_compare_search_group() {
+        let best = SearchGroup::new(Some(b"a".to_vec()), 3, 2.0);
+        let worse = SearchGroup::new(Some(b"b".to_vec()), 1, 1.0);
+        let tie = SearchGroup::new(None, 5, 2.0);
+
+        assert_eq!(best.cmp(&worse), Ordering::Less);
+        // ties are broken by the doc id of the best hit
+        assert_eq!(best.cmp(&tie), Ordering::Less);
+        assert_eq!(tie.cmp(&worse), Ordering::Less);
+    }
 }
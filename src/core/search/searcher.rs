@@ -21,17 +21,24 @@ use crossbeam::channel::{unbounded, Receiver, Sender};
 use core::codec::{Codec, CodecTermState};
 use core::index::LeafReaderContext;
 use core::index::{get_terms, IndexReader, SearchLeafReader};
-use core::index::{Term, TermContext, Terms};
+use core::index::{SeekStatus, Term, TermContext, TermIterator, Terms};
 use core::search::bm25_similarity::BM25Similarity;
-use core::search::bulk_scorer::BulkScorer;
+use core::search::bulk_scorer::{BulkScorer, DefaultBulkScorer, ScoreAndDoc};
 use core::search::cache_policy::{QueryCachingPolicy, UsageTrackingQueryCachingPolicy};
-use core::search::collector::{self, Collector, ParallelLeafCollector, SearchCollector};
-use core::search::explanation::Explanation;
-use core::search::match_all::{ConstantScoreQuery, MatchAllDocsQuery};
+use core::search::collector::{
+    self, Collector, ParallelLeafCollector, SearchCollector, TopDocsCollector, TopFieldCollector,
+};
+use core::search::explanation::{Explanation, ExplanationDiff};
+use core::search::lm_similarity::LMDirichletSimilarity;
+use core::search::match_all::{ConstantScoreQuery, ConstantScoreScorer, MatchAllDocsQuery};
 use core::search::query_cache::{LRUQueryCache, QueryCache};
+use core::search::rescorer::QueryRescorer;
+use core::search::sort::Sort;
 use core::search::statistics::{CollectionStatistics, TermStatistics};
 use core::search::term_query::TermQuery;
-use core::search::{Query, Scorer, Weight, NO_MORE_DOCS};
+use core::search::tfidf_similarity::TFIDFSimilarity;
+use core::search::top_docs::{ScoreDoc, TopDocs};
+use core::search::{Query, RescoreRequest, Rescorer, Scorer, Weight, NO_MORE_DOCS};
 use core::search::{SimScorer, SimWeight, Similarity, SimilarityProducer};
 use core::util::bits::Bits;
 use core::util::thread_pool::{DefaultContext, ThreadPool, ThreadPoolBuilder};
@@ -69,6 +76,74 @@ impl<C: Codec> SimilarityProducer<C> for DefaultSimilarityProducer {
     }
 }
 
+/// The similarities which can be picked for a whole searcher, e.g. to compare
+/// the ranking of BM25, of the classic TF-IDF model and of a language model on
+/// the same index.
+#[derive(Clone, Copy, Debug)]
+pub enum SimilarityEnum {
+    BM25(BM25Similarity),
+    TFIDF(TFIDFSimilarity),
+    LMDirichlet(LMDirichletSimilarity),
+}
+
+impl Default for SimilarityEnum {
+    fn default() -> Self {
+        SimilarityEnum::BM25(BM25Similarity::default())
+    }
+}
+
+impl<C: Codec> Similarity<C> for SimilarityEnum {
+    fn compute_weight(
+        &self,
+        collection_stats: &CollectionStatistics,
+        term_stats: &[TermStatistics],
+        context: Option<&KeyedContext>,
+        boost: f32,
+    ) -> Box<dyn SimWeight<C>> {
+        match self {
+            SimilarityEnum::BM25(s) => {
+                s.compute_weight(collection_stats, term_stats, context, boost)
+            }
+            SimilarityEnum::TFIDF(s) => {
+                s.compute_weight(collection_stats, term_stats, context, boost)
+            }
+            SimilarityEnum::LMDirichlet(s) => {
+                s.compute_weight(collection_stats, term_stats, context, boost)
+            }
+        }
+    }
+
+    fn query_norm(&self, value_for_normalization: f32, context: Option<&KeyedContext>) -> f32 {
+        match self {
+            SimilarityEnum::BM25(s) => {
+                Similarity::<C>::query_norm(s, value_for_normalization, context)
+            }
+            SimilarityEnum::TFIDF(s) => {
+                Similarity::<C>::query_norm(s, value_for_normalization, context)
+            }
+            SimilarityEnum::LMDirichlet(s) => {
+                Similarity::<C>::query_norm(s, value_for_normalization, context)
+            }
+        }
+    }
+}
+
+impl fmt::Display for SimilarityEnum {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SimilarityEnum::BM25(s) => write!(f, "{}", s),
+            SimilarityEnum::TFIDF(s) => write!(f, "{}", s),
+            SimilarityEnum::LMDirichlet(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+impl<C: Codec> SimilarityProducer<C> for SimilarityEnum {
+    fn create(&self, _field: &str) -> Box<dyn Similarity<C>> {
+        Box::new(*self)
+    }
+}
+
 pub struct NonScoringSimilarity;
 
 impl<C: Codec> Similarity<C> for NonScoringSimilarity {
@@ -115,6 +190,17 @@ impl SimScorer for NonScoringSimScorer {
     }
 }
 
+/// Whether the hits of a search are scored.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScoreMode {
+    /// The hits are scored if the collector needs their scores.
+    Complete,
+    /// The query is only used as a filter: the whole query tree is weighted
+    /// without scores, so no similarity scores a hit, and the collector gets
+    /// a score of 0 for each hit.
+    None,
+}
+
 pub trait IndexSearcher<C: Codec>: SearchPlanBuilder<C> {
     type Reader: IndexReader<Codec = C> + ?Sized;
     fn reader(&self) -> &Self::Reader;
@@ -123,13 +209,89 @@ pub trait IndexSearcher<C: Codec>: SearchPlanBuilder<C> {
     where
         S: SearchCollector + ?Sized;
 
+    /// Like `search`, but with `ScoreMode::None` the hits are not scored,
+    /// even if `collector` needs scores.
+    fn search_with_score_mode<S>(
+        &self,
+        query: &dyn Query<C>,
+        collector: &mut S,
+        score_mode: ScoreMode,
+    ) -> Result<()>
+    where
+        S: SearchCollector + ?Sized;
+
     fn search_parallel<S>(&self, query: &dyn Query<C>, collector: &mut S) -> Result<()>
     where
         S: SearchCollector + ?Sized;
 
+    /// Like `search_parallel`, but with `ScoreMode::None` the hits are not
+    /// scored, even if `collector` needs scores.
+    fn search_parallel_with_score_mode<S>(
+        &self,
+        query: &dyn Query<C>,
+        collector: &mut S,
+        score_mode: ScoreMode,
+    ) -> Result<()>
+    where
+        S: SearchCollector + ?Sized;
+
     fn count(&self, query: &dyn Query<C>) -> Result<i32>;
 
     fn explain(&self, query: &dyn Query<C>, doc: DocId) -> Result<Explanation>;
+
+    /// Finds the top `n` hits for `query` which sort strictly after `after`,
+    /// usually the last hit of the previous page, for deep pagination.
+    fn search_after(&self, query: &dyn Query<C>, after: &ScoreDoc, n: usize) -> Result<TopDocs> {
+        let mut collector = TopDocsCollector::with_after(n, after.clone());
+        self.search(query, &mut collector)?;
+        Ok(collector.top_docs())
+    }
+
+    /// Finds the top `n` hits for `query` sorted by `sort`, instead of by
+    /// descending score.
+    fn search_with_sort(&self, query: &dyn Query<C>, n: usize, sort: &Sort) -> Result<TopDocs> {
+        let mut collector = TopFieldCollector::new(sort.clone(), n);
+        self.search(query, &mut collector)?;
+        Ok(collector.top_docs())
+    }
+
+    /// Explains the score of `doc` for both queries, and aligns the two
+    /// explanations to show which score components differ.
+    fn explain_diff(
+        &self,
+        query1: &dyn Query<C>,
+        query2: &dyn Query<C>,
+        doc: DocId,
+    ) -> Result<ExplanationDiff> {
+        let left = self.explain(query1, doc)?;
+        let right = self.explain(query2, doc)?;
+        Ok(ExplanationDiff::new(&left, &right))
+    }
+
+    /// Rescores `top_docs` in place with each of `requests` in turn, each pass
+    /// seeing the hits as reordered by the previous ones.
+    ///
+    /// The window sizes must not increase from one request to the next, as a
+    /// pass can't rank the hits which were not ranked by the previous one.
+    fn rescore(&self, top_docs: &mut TopDocs, requests: &[RescoreRequest<C>]) -> Result<()>
+    where
+        Self: Sized,
+    {
+        for pair in requests.windows(2) {
+            if pair[1].window_size > pair[0].window_size {
+                bail!(ErrorKind::IllegalArgument(format!(
+                    "rescore window sizes must not increase, got {} after {}",
+                    pair[1].window_size, pair[0].window_size
+                )));
+            }
+        }
+
+        let rescorer = QueryRescorer::default();
+        for req in requests {
+            rescorer.rescore(self, req, top_docs)?;
+        }
+        Ok(())
+    }
 }
 
 pub trait SearchPlanBuilder<C: Codec> {
@@ -163,6 +325,15 @@ pub trait SearchPlanBuilder<C: Codec> {
     ) -> TermStatistics;
 
     fn collections_statistics(&self, field: &str) -> Result<CollectionStatistics>;
+
+    /// Calls `visitor` with the terms of `field` which start with `prefix`, in
+    /// order, and their doc freq in the whole index, until it returns false.
+    fn visit_terms_with_prefix(
+        &self,
+        field: &str,
+        prefix: &[u8],
+        visitor: &mut dyn FnMut(&[u8], i32) -> Result<bool>,
+    ) -> Result<()>;
 }
 
 pub struct DefaultIndexSearcher<
@@ -178,6 +349,8 @@ pub struct DefaultIndexSearcher<
     collection_statistics: RwLock<HashMap<String, CollectionStatistics>>,
     term_contexts: RwLock<HashMap<String, Arc<TermContext<CodecTermState<C>>>>>,
     thread_pool: Option<Arc<ThreadPool<DefaultContext>>>,
+    // max number of tasks a parallel search is split into
+    max_parallelism: usize,
 }
 
 impl<C: Codec, R: IndexReader<Codec = C> + ?Sized, IR: Deref<Target = R>>
@@ -204,6 +377,7 @@ where
             collection_statistics: RwLock::new(HashMap::new()),
             term_contexts: RwLock::new(HashMap::new()),
             thread_pool: None,
+            max_parallelism: usize::max_value(),
         }
     }
 
@@ -217,10 +391,18 @@ where
         }
     }
 
+    /// Runs the parallel searches on `pool`, which may be shared with other
+    /// searchers. Without a pool, searches run on the calling thread.
     pub fn set_thread_pool(&mut self, pool: Arc<ThreadPool<DefaultContext>>) {
         self.thread_pool = Some(pool);
     }
 
+    /// Caps the number of tasks each parallel search is split into, the leaves
+    /// being grouped into tasks of balanced cost.
+    pub fn set_max_parallelism(&mut self, max_parallelism: usize) {
+        self.max_parallelism = max_parallelism.max(1);
+    }
+
     pub fn set_query_cache(&mut self, cache: Arc<dyn QueryCache<C>>) {
         self.query_cache = cache;
     }
@@ -229,22 +411,62 @@ where
         self.cache_policy = cache_policy;
     }
 
+    /// Returns false if the collection was terminated, in which case the
+    /// following leaves must not be searched.
     fn do_search<S: Scorer + ?Sized, T: Collector + ?Sized, B: Bits + ?Sized>(
         scorer: &mut S,
         collector: &mut T,
         live_docs: &B,
-    ) -> Result<()> {
-        let mut bulk_scorer = BulkScorer::new(scorer);
-        match bulk_scorer.score(collector, Some(live_docs), 0, NO_MORE_DOCS) {
-            Err(Error(ErrorKind::Collector(collector::ErrorKind::CollectionTerminated), _)) => {
+    ) -> Result<bool> {
+        let mut bulk_scorer = DefaultBulkScorer::new(scorer);
+        Self::continue_search(bulk_scorer.score(collector, Some(live_docs), 0, NO_MORE_DOCS))
+    }
+
+    /// Like `do_search`, with the bulk scorer provided by the weight, the hits
+    /// are collected with a score of 0 if `score_mode` is `ScoreMode::None`.
+    fn do_bulk_search<T: Collector + ?Sized>(
+        bulk_scorer: &mut dyn BulkScorer,
+        collector: &mut T,
+        live_docs: &dyn Bits,
+        score_mode: ScoreMode,
+    ) -> Result<bool> {
+        let result = match score_mode {
+            ScoreMode::Complete => bulk_scorer.score(
+                &mut |doc: DocId, scorer: &mut dyn Scorer| collector.collect(doc, scorer),
+                Some(live_docs),
+                0,
+                NO_MORE_DOCS,
+            ),
+            ScoreMode::None => {
+                let mut no_score = ScoreAndDoc::new(0f32);
+                bulk_scorer.score(
+                    &mut |doc: DocId, _scorer: &mut dyn Scorer| {
+                        no_score.doc = doc;
+                        collector.collect(doc, &mut no_score)
+                    },
+                    Some(live_docs),
+                    0,
+                    NO_MORE_DOCS,
+                )
+            }
+        };
+        Self::continue_search(result)
+    }
+
+    /// Whether the search goes on with the following leaves after collecting
+    /// a leaf with `result`.
+    fn continue_search(result: Result<DocId>) -> Result<bool> {
+        match result {
+            Err(Error(ErrorKind::Collector(collector::ErrorKind::CollectionTerminated), _))
+            | Err(Error(ErrorKind::Collector(collector::ErrorKind::CollectionTimeout), _)) => {
                 // Collection was terminated prematurely
-                Ok(())
+                Ok(false)
             }
             Err(Error(ErrorKind::Collector(collector::ErrorKind::LeafCollectionTerminated), _))
             | Ok(_) => {
                 // Leaf collection was terminated prematurely,
                 // continue with the following leaf
-                Ok(())
+                Ok(true)
             }
             Err(e) => {
                 // something goes wrong, stop search and return error!
@@ -254,6 +476,30 @@ where
     }
 }
 
+/// Leaves whose total cost is below this are searched in a single task, as
+/// scheduling them separately would cost more than it saves.
+const MIN_TASK_COST: usize = 10_000;
+
+/// Splits the leaves of the given costs into at most `max_tasks` groups of
+/// balanced total cost, returns the indexes of the leaves of each group.
+fn group_leaves(costs: &[usize], max_tasks: usize) -> Vec<Vec<usize>> {
+    let total_cost: usize = costs.iter().sum();
+    let num_tasks = (total_cost / MIN_TASK_COST)
+        .max(1)
+        .min(max_tasks)
+        .min(costs.len());
+    let mut order: Vec<usize> = (0..costs.len()).collect();
+    // the most costly leaves first, each to the least loaded task
+    order.sort_by(|&a, &b| costs[b].cmp(&costs[a]).then(a.cmp(&b)));
+    let mut tasks = vec![(0usize, Vec::new()); num_tasks];
+    for i in order {
+        let task = tasks.iter_mut().min_by_key(|task| task.0).unwrap();
+        task.0 += costs[i];
+        task.1.push(i);
+    }
+    tasks.into_iter().map(|task| task.1).collect()
+}
+
 impl<C, R, IR, SP> IndexSearcher<C> for DefaultIndexSearcher<C, R, IR, SP>
 where
     C: Codec,
@@ -272,23 +518,61 @@ where
     where
         S: SearchCollector + ?Sized,
     {
-        let weight = self.create_weight(query, collector.needs_scores())?;
+        self.search_with_score_mode(query, collector, ScoreMode::Complete)
+    }
+
+    fn search_with_score_mode<S>(
+        &self,
+        query: &dyn Query<C>,
+        collector: &mut S,
+        score_mode: ScoreMode,
+    ) -> Result<()>
+    where
+        S: SearchCollector + ?Sized,
+    {
+        let needs_scores = score_mode == ScoreMode::Complete && collector.needs_scores();
+        let weight = self.create_weight(query, needs_scores)?;
 
         for reader in self.reader.leaves() {
-            if let Some(mut scorer) = weight.create_scorer(&reader)? {
-                // some in running segment maybe wrong, just skip it!
-                // TODO maybe we should matching more specific error type
-                if let Err(e) = collector.set_next_reader(&reader) {
-                    error!(
-                        "set next reader for leaf {} failed!, {:?}",
-                        reader.reader.name(),
-                        e
-                    );
-                    continue;
+            // the bulk scorer of the weight is preferred to wrapping its scorer
+            let bulk_scorer = weight.bulk_scorer(&reader)?;
+            let scorer = match bulk_scorer {
+                Some(_) => None,
+                None => match weight.create_scorer(&reader)? {
+                    Some(scorer) => Some(scorer),
+                    None => continue,
+                },
+            };
+            // some in running segment maybe wrong, just skip it!
+            // TODO maybe we should matching more specific error type
+            if let Err(e) = collector.set_next_reader(&reader) {
+                error!(
+                    "set next reader for leaf {} failed!, {:?}",
+                    reader.reader.name(),
+                    e
+                );
+                continue;
+            }
+            let live_docs = reader.reader.live_docs();
+
+            let more = match (bulk_scorer, scorer) {
+                (Some(mut bulk_scorer), _) => Self::do_bulk_search(
+                    bulk_scorer.as_mut(),
+                    collector,
+                    live_docs.as_ref(),
+                    score_mode,
+                )?,
+                (None, Some(scorer)) => {
+                    let mut scorer: Box<dyn Scorer> = match score_mode {
+                        ScoreMode::Complete => scorer,
+                        ScoreMode::None => Box::new(ConstantScoreScorer::from_scorer(0f32, scorer)),
+                    };
+                    Self::do_search(scorer.as_mut(), collector, live_docs.as_ref())?
                 }
-                let live_docs = reader.reader.live_docs();
-
-                Self::do_search(&mut *scorer, collector, live_docs.as_ref())?;
+                (None, None) => unreachable!(),
+            };
+            if !more {
+                break;
             }
         }
 
@@ -296,40 +580,42 @@ where
     }
 
     fn search_parallel<S>(&self, query: &dyn Query<C>, collector: &mut S) -> Result<()>
+    where
+        S: SearchCollector + ?Sized,
+    {
+        self.search_parallel_with_score_mode(query, collector, ScoreMode::Complete)
+    }
+
+    fn search_parallel_with_score_mode<S>(
+        &self,
+        query: &dyn Query<C>,
+        collector: &mut S,
+        score_mode: ScoreMode,
+    ) -> Result<()>
     where
         S: SearchCollector + ?Sized,
     {
         if collector.support_parallel() && self.reader.leaves().len() > 1 {
             if let Some(ref thread_pool) = self.thread_pool {
-                let weight = self.create_weight(query, collector.needs_scores())?;
+                let needs_scores = score_mode == ScoreMode::Complete && collector.needs_scores();
+                let weight = self.create_weight(query, needs_scores)?;
 
-                for (_ord, reader) in self.reader.leaves().iter().enumerate() {
+                let mut leaves = Vec::new();
+                for reader in &self.reader.leaves() {
                     if let Some(scorer) = weight.create_scorer(reader)? {
+                        let scorer: Box<dyn Scorer> = match score_mode {
+                            ScoreMode::Complete => scorer,
+                            ScoreMode::None => {
+                                Box::new(ConstantScoreScorer::from_scorer(0f32, scorer))
+                            }
+                        };
                         match collector.leaf_collector(reader) {
                             Ok(leaf_collector) => {
-                                let live_docs = reader.reader.live_docs();
-                                thread_pool.execute(move |_ctx| {
-                                    let mut collector = leaf_collector;
-                                    let mut scorer = scorer;
-                                    if let Err(e) = Self::do_search(
-                                        scorer.as_mut(),
-                                        &mut collector,
-                                        live_docs.as_ref(),
-                                    ) {
-                                        error!(
-                                            "do search parallel failed by '{:?}', may return \
-                                             partial result",
-                                            e
-                                        );
-                                    }
-                                    if let Err(e) = collector.finish_leaf() {
-                                        error!(
-                                            "finish search parallel failed by '{:?}', may return \
-                                             partial result",
-                                            e
-                                        );
-                                    }
-                                })
+                                leaves.push(Some((
+                                    scorer,
+                                    leaf_collector,
+                                    reader.reader.live_docs(),
+                                )));
                             }
                             Err(e) => {
                                 error!(
@@ -341,10 +627,47 @@ where
                         }
                     }
                 }
+
+                let costs: Vec<usize> = leaves
+                    .iter()
+                    .map(|leaf| leaf.as_ref().unwrap().0.cost())
+                    .collect();
+                for group in group_leaves(&costs, self.max_parallelism) {
+                    let task: Vec<_> = group
+                        .into_iter()
+                        .map(|i| leaves[i].take().unwrap())
+                        .collect();
+                    thread_pool.execute(move |_ctx| {
+                        let mut terminated = false;
+                        for (mut scorer, mut collector, live_docs) in task {
+                            if !terminated {
+                                match Self::do_search(
+                                    scorer.as_mut(),
+                                    &mut collector,
+                                    live_docs.as_ref(),
+                                ) {
+                                    Ok(more) => terminated = !more,
+                                    Err(e) => error!(
+                                        "do search parallel failed by '{:?}', may return \
+                                         partial result",
+                                        e
+                                    ),
+                                }
+                            }
+                            if let Err(e) = collector.finish_leaf() {
+                                error!(
+                                    "finish search parallel failed by '{:?}', may return \
+                                     partial result",
+                                    e
+                                );
+                            }
+                        }
+                    })
+                }
                 return collector.finish_parallel();
             }
         }
-        self.search(query, collector)
+        self.search_with_score_mode(query, collector, score_mode)
     }
 
     fn count(&self, query: &dyn Query<C>) -> Result<i32> {
@@ -513,6 +836,33 @@ where
         statistics.insert(field.into(), stat);
         Ok(statistics[field].clone())
     }
+
+    fn visit_terms_with_prefix(
+        &self,
+        field: &str,
+        prefix: &[u8],
+        visitor: &mut dyn FnMut(&[u8], i32) -> Result<bool>,
+    ) -> Result<()> {
+        if let Some(field_terms) = get_terms(&*self.reader, field)? {
+            let mut iter = field_terms.iterator()?;
+            if iter.seek_ceil(prefix)? == SeekStatus::End {
+                return Ok(());
+            }
+            loop {
+                let doc_freq = iter.doc_freq()?;
+                {
+                    let term = iter.term()?;
+                    if !term.starts_with(prefix) || !visitor(term, doc_freq)? {
+                        break;
+                    }
+                }
+                if iter.next()?.is_none() {
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
 }
 
 struct TotalHitCountCollector {
@@ -613,15 +963,28 @@ impl ParallelLeafCollector for TotalHitsCountLeafCollector {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use core::analysis::whitespace_tokenizer::WhitespaceTokenizer;
+    use core::analysis::TokenStream;
     use core::codec::tests::TestCodec;
+    use core::doc::{Field, FieldType, NumericDocValuesField};
     use core::index::tests::*;
+    use core::index::{Fieldable, IndexOptions, IndexWriter, IndexWriterConfig};
+    use core::search::boolean_query::BooleanQuery;
     use core::search::collector::top_docs::*;
     use core::search::collector::*;
+    use core::search::disjunction::DisjunctionMaxQuery;
+    use core::search::sort::Sort;
+    use core::search::sort_field::{SimpleSortField, SortField, SortFieldType};
     use core::search::term_query::TermQuery;
     use core::search::tests::*;
     use core::search::*;
+    use core::search::top_docs::{ScoreDocHit, TopScoreDocs};
+    use core::store::RAMDirectory;
     use core::util::DocId;
-    use std::sync::atomic::Ordering;
+    use error::ErrorKind;
+    use std::io::Cursor;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::{Duration, SystemTime};
 
     pub const MOCK_QUERY: &str = "mock";
 
@@ -663,11 +1026,48 @@ mod tests {
         }
     }
 
+    fn index_sort() -> Sort {
+        Sort::new(vec![SortField::Simple(SimpleSortField::new(
+            "test".into(),
+            SortFieldType::Long,
+            false,
+        ))])
+    }
+
+    // searches 5 docs in each of 3 segments, returns the total hits and
+    // whether the collection was early terminated
+    fn early_terminating_search(sort: Sort, segments_sorted: bool) -> (usize, bool) {
+        let leaf_readers = [0, 10, 20].iter().map(|&doc_base| {
+            let leaf_reader = MockLeafReader::new(doc_base);
+            if segments_sorted {
+                leaf_reader.with_index_sort(index_sort())
+            } else {
+                leaf_reader
+            }
+        });
+        let index_reader: Arc<dyn IndexReader<Codec = TestCodec>> =
+            Arc::new(MockIndexReader::new(leaf_readers.collect()));
+
+        let mut top_collector = TopDocsCollector::new(3);
+        let mut early_terminating_collector = EarlyTerminatingSortingCollector::new(sort, 3);
+        {
+            let mut chained_collector =
+                ChainedCollector::new(&mut early_terminating_collector, &mut top_collector);
+            let query = MockQuery::new(vec![1, 5, 3, 4, 2]);
+            let searcher = DefaultIndexSearcher::new(index_reader);
+            searcher.search(&query, &mut chained_collector).unwrap();
+        }
+        let early_terminated = early_terminating_collector
+            .early_terminated
+            .load(Ordering::Acquire);
+        (top_collector.top_docs().total_hits(), early_terminated)
+    }
+
     #[test]
     fn test_early_terminating_search() {
-        let leaf_reader1 = MockLeafReader::new(0);
-        let leaf_reader2 = MockLeafReader::new(10);
-        let leaf_reader3 = MockLeafReader::new(20);
+        let leaf_reader1 = MockLeafReader::new(0).with_index_sort(index_sort());
+        let leaf_reader2 = MockLeafReader::new(10).with_index_sort(index_sort());
+        let leaf_reader3 = MockLeafReader::new(20).with_index_sort(index_sort());
         let index_reader: Arc<dyn IndexReader<Codec = TestCodec>> =
             Arc::new(MockIndexReader::new(vec![
                 leaf_reader1,
@@ -677,7 +1077,8 @@ mod tests {
 
         let mut top_collector = TopDocsCollector::new(3);
         {
-            let mut early_terminating_collector = EarlyTerminatingSortingCollector::new(3);
+            let mut early_terminating_collector =
+                EarlyTerminatingSortingCollector::new(index_sort(), 3);
             {
                 let mut chained_collector =
                     ChainedCollector::new(&mut early_terminating_collector, &mut top_collector);
@@ -705,4 +1106,347 @@ mod tests {
         assert!((score_docs[1].score() - 5f32) < ::std::f32::EPSILON);
         assert!((score_docs[2].score() - 5f32) < ::std::f32::EPSILON);
     }
+
+    /// Counts the collected docs, terminating the collection at `terminate_at`.
+    struct TerminatingCollector {
+        terminate_at: usize,
+        count: usize,
+    }
+
+    impl SearchCollector for TerminatingCollector {
+        type LC = NoParallelLeafCollector;
+
+        fn set_next_reader<C: Codec>(&mut self, _reader: &LeafReaderContext<'_, C>) -> Result<()> {
+            Ok(())
+        }
+
+        fn support_parallel(&self) -> bool {
+            false
+        }
+
+        fn leaf_collector<C: Codec>(
+            &mut self,
+            _reader: &LeafReaderContext<'_, C>,
+        ) -> Result<Self::LC> {
+            unreachable!()
+        }
+
+        fn finish_parallel(&mut self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Collector for TerminatingCollector {
+        fn needs_scores(&self) -> bool {
+            false
+        }
+
+        fn collect<S: Scorer + ?Sized>(&mut self, _doc: DocId, _scorer: &mut S) -> Result<()> {
+            self.count += 1;
+            if self.count == self.terminate_at {
+                bail!(ErrorKind::Collector(
+                    collector::ErrorKind::CollectionTerminated,
+                ))
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_collection_terminated() {
+        let leaf_readers = vec![
+            MockLeafReader::new(0),
+            MockLeafReader::new(10),
+            MockLeafReader::new(20),
+        ];
+        let index_reader: Arc<dyn IndexReader<Codec = TestCodec>> =
+            Arc::new(MockIndexReader::new(leaf_readers));
+        let searcher = DefaultIndexSearcher::new(index_reader);
+        let query = MockQuery::new(vec![1, 5, 3, 4, 2]);
+
+        // the following leaves are not searched
+        let mut collector = TerminatingCollector {
+            terminate_at: 7,
+            count: 0,
+        };
+        searcher.search(&query, &mut collector).unwrap();
+        assert_eq!(collector.count, 7);
+    }
+
+    #[test]
+    fn test_collection_timeout() {
+        let leaf_readers = vec![
+            MockLeafReader::new(0),
+            MockLeafReader::new(10),
+            MockLeafReader::new(20),
+        ];
+        let index_reader: Arc<dyn IndexReader<Codec = TestCodec>> =
+            Arc::new(MockIndexReader::new(leaf_readers));
+        let searcher = DefaultIndexSearcher::new(index_reader);
+        let query = MockQuery::new(vec![1, 5, 3, 4, 2]);
+
+        let mut top_collector = TopDocsCollector::new(10);
+        let mut timeout = TimeoutCollector::new(Duration::from_secs(3600), SystemTime::now());
+        {
+            let mut collector = ChainedCollector::new(&mut top_collector, &mut timeout);
+            searcher.search(&query, &mut collector).unwrap();
+        }
+        assert!(!timeout.timed_out());
+        assert_eq!(top_collector.top_docs().total_hits(), 15);
+
+        // the search stops on the first doc past the deadline, which is only
+        // collected by the collectors before the timeout one
+        let mut top_collector = TopDocsCollector::new(10);
+        let start_time = SystemTime::now() - Duration::from_secs(3600);
+        let mut timeout = TimeoutCollector::new(Duration::from_secs(1), start_time);
+        {
+            let mut collector = ChainedCollector::new(&mut top_collector, &mut timeout);
+            searcher.search(&query, &mut collector).unwrap();
+        }
+        assert!(timeout.timed_out());
+        assert_eq!(top_collector.top_docs().total_hits(), 1);
+    }
+
+    #[test]
+    fn test_parallel_search_ties() {
+        let leaf_readers = vec![
+            MockLeafReader::new(0),
+            MockLeafReader::new(10),
+            MockLeafReader::new(20),
+        ];
+        let index_reader: Arc<dyn IndexReader<Codec = TestCodec>> =
+            Arc::new(MockIndexReader::new(leaf_readers));
+        let mut searcher = DefaultIndexSearcher::new(index_reader);
+        searcher.with_thread_pool(3);
+        let query = MockQuery::new(vec![1, 5, 3, 4, 2]);
+
+        // docs 5, 15 and 25 tie with the top score, whichever leaf finishes first
+        for _ in 0..10 {
+            let mut collector = TopDocsCollector::new(2);
+            searcher.search_parallel(&query, &mut collector).unwrap();
+            let top_docs = collector.top_docs();
+            assert_eq!(top_docs.total_hits(), 15);
+            let hits: Vec<DocId> = top_docs.score_docs().iter().map(|d| d.doc_id()).collect();
+            assert_eq!(hits, vec![5, 15]);
+        }
+    }
+
+    #[test]
+    fn test_group_leaves() {
+        let costs = [100_000, 5, 3, 50_000, 60_000];
+        assert_eq!(group_leaves(&costs, 2), vec![vec![0, 1, 2], vec![4, 3]]);
+        assert_eq!(group_leaves(&costs, 10).len(), 5);
+        // too cheap to be worth several tasks
+        assert_eq!(group_leaves(&[5, 3, 8], 10), vec![vec![2, 0, 1]]);
+        assert!(group_leaves(&[], 10).is_empty());
+    }
+
+    #[test]
+    fn test_early_terminating_sort_mismatch() {
+        // a prefix of the index sort
+        assert_eq!(early_terminating_search(index_sort(), true), (9, true));
+        // the segments are not sorted
+        assert_eq!(early_terminating_search(index_sort(), false), (15, false));
+        // the segments are sorted by another field
+        let sort = Sort::new(vec![SortField::Simple(SimpleSortField::new(
+            "test_2".into(),
+            SortFieldType::Long,
+            false,
+        ))]);
+        assert_eq!(early_terminating_search(sort, true), (15, false));
+        // in the reverse order
+        let sort = Sort::new(vec![SortField::Simple(SimpleSortField::new(
+            "test".into(),
+            SortFieldType::Long,
+            true,
+        ))]);
+        assert_eq!(early_terminating_search(sort, true), (15, false));
+    }
+
+    /// Counts the sim weights it creates and the hits scored by its sim
+    /// scorers.
+    #[derive(Clone, Default)]
+    struct CountingSimilarity {
+        weights: Arc<AtomicUsize>,
+        scored: Arc<AtomicUsize>,
+    }
+
+    impl<C: Codec> SimilarityProducer<C> for CountingSimilarity {
+        fn create(&self, _field: &str) -> Box<dyn Similarity<C>> {
+            Box::new(self.clone())
+        }
+    }
+
+    impl<C: Codec> Similarity<C> for CountingSimilarity {
+        fn compute_weight(
+            &self,
+            _collection_stats: &CollectionStatistics,
+            _term_stats: &[TermStatistics],
+            _context: Option<&KeyedContext>,
+            _boost: f32,
+        ) -> Box<dyn SimWeight<C>> {
+            self.weights.fetch_add(1, Ordering::SeqCst);
+            Box::new(self.clone())
+        }
+    }
+
+    impl<C: Codec> SimWeight<C> for CountingSimilarity {
+        fn get_value_for_normalization(&self) -> f32 {
+            1.0f32
+        }
+
+        fn normalize(&mut self, _query_norm: f32, _boost: f32) {}
+
+        fn sim_scorer(&self, _reader: &SearchLeafReader<C>) -> Result<Box<dyn SimScorer>> {
+            Ok(Box::new(self.clone()))
+        }
+    }
+
+    impl SimScorer for CountingSimilarity {
+        fn score(&mut self, _doc: DocId, freq: f32) -> Result<f32> {
+            self.scored.fetch_add(1, Ordering::SeqCst);
+            Ok(freq)
+        }
+
+        fn compute_slop_factor(&self, _distance: i32) -> f32 {
+            1.0f32
+        }
+    }
+
+    impl fmt::Display for CountingSimilarity {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "counting")
+        }
+    }
+
+    #[test]
+    fn test_score_mode_none() {
+        let dir = Arc::new(RAMDirectory::default());
+        let writer = IndexWriter::new(dir, Arc::new(IndexWriterConfig::default())).unwrap();
+        let mut field_type = FieldType::default();
+        field_type.index_options = IndexOptions::DocsAndFreqs;
+        // two segments, for the parallel search
+        for (i, text) in ["a b", "a c", "b c d", "a b c d", "d"].iter().enumerate() {
+            let tokens: Box<dyn TokenStream> = Box::new(WhitespaceTokenizer::new(Box::new(
+                Cursor::new(text.as_bytes().to_vec()),
+            )));
+            let doc: Vec<Box<dyn Fieldable>> = vec![Box::new(Field::new(
+                "body".into(),
+                field_type.clone(),
+                None,
+                Some(tokens),
+            ))];
+            writer.add_document(doc).unwrap();
+            if i == 2 {
+                writer.commit().unwrap();
+            }
+        }
+        let reader = writer.get_reader(true, false).unwrap();
+        assert_eq!(reader.leaves().len(), 2);
+        let similarity = CountingSimilarity::default();
+        let mut searcher = DefaultIndexSearcher::with_similarity(&reader, similarity.clone());
+        searcher.with_thread_pool(2);
+
+        let term = |text: &str| -> Box<dyn Query<TestCodec>> {
+            Box::new(TermQuery::new(
+                Term::new("body".into(), text.as_bytes().to_vec()),
+                1.0,
+                None,
+            ))
+        };
+        // a conjunction, a disjunction and a dis max nested in a boolean query
+        let query = BooleanQuery::build(
+            vec![BooleanQuery::build(vec![term("a"), term("b")], vec![], vec![]).unwrap()],
+            vec![
+                BooleanQuery::build(vec![], vec![term("c"), term("d")], vec![]).unwrap(),
+                DisjunctionMaxQuery::build(vec![term("c"), term("d")], 0.1).unwrap(),
+            ],
+            vec![],
+        )
+        .unwrap();
+
+        // no part of the weight tree is built to score, so the similarity
+        // creates no sim weight at all
+        for parallel in &[false, true] {
+            let mut collector = TopDocsCollector::new(10);
+            if *parallel {
+                searcher
+                    .search_parallel_with_score_mode(
+                        query.as_ref(),
+                        &mut collector,
+                        ScoreMode::None,
+                    )
+                    .unwrap();
+            } else {
+                searcher
+                    .search_with_score_mode(query.as_ref(), &mut collector, ScoreMode::None)
+                    .unwrap();
+            }
+            assert_eq!(similarity.weights.load(Ordering::SeqCst), 0);
+            assert_eq!(similarity.scored.load(Ordering::SeqCst), 0);
+            let top_docs = collector.top_docs();
+            assert_eq!(top_docs.total_hits(), 2);
+            for hit in top_docs.score_docs() {
+                assert!(hit.score().abs() < ::std::f32::EPSILON);
+            }
+        }
+
+        // the similarity does score the hits otherwise
+        let mut collector = TopDocsCollector::new(10);
+        searcher.search(query.as_ref(), &mut collector).unwrap();
+        assert!(similarity.weights.load(Ordering::SeqCst) > 0);
+        assert!(similarity.scored.load(Ordering::SeqCst) > 0);
+        assert_eq!(collector.top_docs().total_hits(), 2);
+
+        writer.close().unwrap();
+    }
+
+    #[test]
+    fn test_chained_rescore() {
+        let dir = Arc::new(RAMDirectory::default());
+        let writer = IndexWriter::new(dir, Arc::new(IndexWriterConfig::default())).unwrap();
+        for i in 0..4 {
+            writer
+                .add_document(vec![NumericDocValuesField::new("id", i)])
+                .unwrap();
+        }
+        let reader = writer.get_reader(true, false).unwrap();
+        let searcher = DefaultIndexSearcher::new(&reader);
+
+        // the mock queries score their docs with the doc id
+        let request = |docs: Vec<DocId>, window_size: usize| {
+            RescoreRequest::new(
+                Box::new(MockQuery::new(docs)),
+                1.0,
+                10.0,
+                RescoreMode::Total,
+                window_size,
+                false,
+            )
+        };
+        let hits = [(0, 4.0), (1, 3.0), (2, 2.0), (3, 1.0)]
+            .iter()
+            .map(|&(doc, score)| ScoreDocHit::Score(ScoreDoc::new(doc, score)))
+            .collect();
+        let mut top_docs = TopDocs::Score(TopScoreDocs::new(4, hits));
+        // the first pass moves the docs 3 and 2 to the top, so that the window
+        // of the second pass is made of them rather than of the docs 0 and 1
+        let requests = vec![request(vec![2, 3], 4), request(vec![1, 2], 2)];
+        searcher.rescore(&mut top_docs, &requests).unwrap();
+        let doc_scores: Vec<(DocId, f32)> = top_docs
+            .score_docs()
+            .iter()
+            .map(|d| (d.doc_id(), d.score()))
+            .collect();
+        assert_eq!(
+            doc_scores,
+            vec![(2, 42.0), (3, 31.0), (0, 4.0), (1, 3.0)]
+        );
+
+        // the windows can't grow from one pass to the next
+        let requests = vec![request(vec![2, 3], 2), request(vec![1, 2], 4)];
+        assert!(searcher.rescore(&mut top_docs, &requests).is_err());
+
+        writer.close().unwrap();
+    }
 }
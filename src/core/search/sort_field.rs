@@ -13,7 +13,7 @@
 
 use core::codec::Codec;
 use core::index::{
-    NumericDocValues, NumericDocValuesContext, NumericDocValuesRef, SearchLeafReader,
+    NumericDocValues, NumericDocValuesContext, NumericDocValuesRef, OrdinalMap, SearchLeafReader,
     SortedNumericDocValuesRef,
 };
 use core::search::field_comparator::*;
@@ -25,6 +25,7 @@ use error::ErrorKind::IllegalArgument;
 use error::Result;
 
 use std::sync::Arc;
+use std::{f32, f64};
 
 #[derive(PartialEq, Debug, Clone, Copy, Eq)]
 pub enum SortFieldType {
@@ -60,11 +61,18 @@ pub enum SortFieldMissingValue {
     StringFirst,
 }
 
+/// Where the docs without a value for a numeric sort field are sorted.
+#[derive(PartialEq, Debug, Clone, Copy, Eq)]
+pub enum SortMissingOrder {
+    First,
+    Last,
+}
+
 #[derive(Clone, Eq, PartialEq, Debug)]
 pub enum SortField {
     Simple(SimpleSortField),
     SortedNumeric(SortedNumericSortField),
-    // SortedSet(SortedSetSortField),
+    SortedSet(SortedSetSortField),
 }
 
 impl SortField {
@@ -76,6 +84,7 @@ impl SortField {
         match self {
             SortField::Simple(s) => &s.field,
             SortField::SortedNumeric(s) => &s.raw_field.field,
+            SortField::SortedSet(s) => &s.raw_field.field,
         }
     }
 
@@ -83,6 +92,7 @@ impl SortField {
         match self {
             SortField::Simple(s) => s.field_type,
             SortField::SortedNumeric(s) => s.raw_field.field_type,
+            SortField::SortedSet(s) => s.raw_field.field_type,
         }
     }
 
@@ -90,6 +100,7 @@ impl SortField {
         match self {
             SortField::Simple(s) => s.is_reverse,
             SortField::SortedNumeric(s) => s.raw_field.is_reverse,
+            SortField::SortedSet(s) => s.raw_field.is_reverse,
         }
     }
 
@@ -97,6 +108,7 @@ impl SortField {
         match self {
             SortField::Simple(s) => s.missing_value.as_ref(),
             SortField::SortedNumeric(s) => s.raw_field.missing_value.as_ref(),
+            SortField::SortedSet(s) => s.raw_field.missing_value.as_ref(),
         }
     }
 
@@ -104,10 +116,14 @@ impl SortField {
         match self {
             SortField::Simple(s) => s.needs_scores(),
             SortField::SortedNumeric(s) => s.raw_field.needs_scores(),
+            SortField::SortedSet(s) => s.raw_field.needs_scores(),
         }
     }
 
-    pub fn set_missing_value(&mut self, value: Option<VariantValue>) {
+    /// Sets the value of the docs without a value for this field. A sorted set
+    /// field has no such value, where its docs without value sort is set by
+    /// `set_missing_order` instead.
+    pub fn set_missing_value(&mut self, value: Option<VariantValue>) -> Result<()> {
         match self {
             SortField::Simple(s) => {
                 s.missing_value = value;
@@ -115,7 +131,47 @@ impl SortField {
             SortField::SortedNumeric(s) => {
                 s.raw_field.missing_value = value;
             }
+            SortField::SortedSet(s) => bail!(IllegalArgument(format!(
+                "sorted set sort field '{}' has no missing value, set its missing order",
+                s.raw_field.field
+            ))),
         }
+        Ok(())
+    }
+
+    /// Sorts the docs without a value for this field first or last, whatever
+    /// the direction of the sort. Only numeric and sorted set fields support it.
+    pub fn set_missing_order(&mut self, order: SortMissingOrder) -> Result<()> {
+        // the missing value sorts first in ascending order
+        let lowest = (order == SortMissingOrder::First) != self.is_reverse();
+        let numeric_type = match self {
+            SortField::Simple(s) => s.field_type,
+            SortField::SortedNumeric(s) => s.real_type,
+            SortField::SortedSet(s) => {
+                s.missing_value = if lowest {
+                    SortFieldMissingValue::StringFirst
+                } else {
+                    SortFieldMissingValue::StringLast
+                };
+                return Ok(());
+            }
+        };
+        let value = match numeric_type {
+            SortFieldType::Int if lowest => VariantValue::Int(i32::min_value()),
+            SortFieldType::Int => VariantValue::Int(i32::max_value()),
+            SortFieldType::Long if lowest => VariantValue::Long(i64::min_value()),
+            SortFieldType::Long => VariantValue::Long(i64::max_value()),
+            SortFieldType::Float if lowest => VariantValue::Float(f32::NEG_INFINITY),
+            SortFieldType::Float => VariantValue::Float(f32::INFINITY),
+            SortFieldType::Double if lowest => VariantValue::Double(f64::NEG_INFINITY),
+            SortFieldType::Double => VariantValue::Double(f64::INFINITY),
+            _ => bail!(IllegalArgument(format!(
+                "sort field '{}' of type {:?} has no missing order",
+                self.field(),
+                numeric_type
+            ))),
+        };
+        self.set_missing_value(Some(value))
     }
 
     pub fn get_comparator(
@@ -126,6 +182,7 @@ impl SortField {
         match self {
             SortField::Simple(s) => s.get_comparator(num_hits, missing_value),
             SortField::SortedNumeric(s) => s.get_comparator(num_hits, missing_value),
+            SortField::SortedSet(s) => s.get_comparator(num_hits),
         }
     }
 }
@@ -423,12 +480,7 @@ impl NumericDocValues for SortedNumAsNumDocValuesMin {
         _ctx: NumericDocValuesContext,
         doc_id: i32,
     ) -> Result<(i64, NumericDocValuesContext)> {
-        let ctx = self.doc_values.set_document(None, doc_id)?;
-        if self.doc_values.count(&ctx) == 0 {
-            Ok((0, None))
-        } else {
-            Ok((self.doc_values.value_at(&ctx, 0)?, None))
-        }
+        Ok((self.doc_values.min(doc_id)?.unwrap_or(0), None))
     }
 }
 
@@ -448,13 +500,7 @@ impl NumericDocValues for SortedNumAsNumDocValuesMax {
         _ctx: NumericDocValuesContext,
         doc_id: i32,
     ) -> Result<(i64, NumericDocValuesContext)> {
-        let ctx = self.doc_values.set_document(None, doc_id)?;
-        let count = self.doc_values.count(&ctx);
-        if count == 0 {
-            Ok((0, None))
-        } else {
-            Ok((self.doc_values.value_at(&ctx, count - 1)?, None))
-        }
+        Ok((self.doc_values.max(doc_id)?.unwrap_or(0), None))
     }
 }
 
@@ -464,22 +510,107 @@ impl NumericDocValues for SortedNumAsNumDocValuesMax {
 /// this technique "selects" a value as the representative sort value for the document.
 ///
 /// By default, the minimum value in the set is selected as the sort value, but
-/// this can be customized. Selectors other than the default do have some limitations
-/// to ensure that all selections happen in constant-time for performance.
+/// this can be customized. The ords of the doc are read one by one to select
+/// the middle ones, so the middle selectors are slower on docs with many values.
 ///
 /// Like sorting by string, this also supports sorting missing values as first or last,
-/// via {@link #setMissingValue(Object)}.
-/// @see SortedSetSelector
-/// TODO, may implement later
-#[allow(dead_code)]
-struct SortedSetSortField {
+/// via `set_missing_value`, the docs without value sort first by default.
+///
+/// The hits of different segments are compared by term, unless the `OrdinalMap`
+/// of the field over the searched reader is set with `set_ordinal_map`.
+#[derive(Clone, Debug)]
+pub struct SortedSetSortField {
     selector: SortedSetSelectorType,
     raw_field: SimpleSortField,
+    missing_value: SortFieldMissingValue,
+    ordinal_map: Option<Arc<OrdinalMap>>,
+}
+
+impl SortedSetSortField {
+    pub fn with_field(field: String, reverse: bool) -> Self {
+        Self::new(field, reverse, SortedSetSelectorType::Min)
+    }
+
+    pub fn new(field: String, reverse: bool, selector: SortedSetSelectorType) -> Self {
+        SortedSetSortField {
+            selector,
+            raw_field: SimpleSortField::new(field, SortFieldType::Custom, reverse),
+            missing_value: SortFieldMissingValue::StringFirst,
+            ordinal_map: None,
+        }
+    }
+
+    pub fn selector(&self) -> SortedSetSelectorType {
+        self.selector
+    }
+
+    pub fn missing_value(&self) -> SortFieldMissingValue {
+        self.missing_value
+    }
+
+    /// Where the docs without value sort in ascending order.
+    pub fn set_missing_value(&mut self, missing_value: SortFieldMissingValue) {
+        self.missing_value = missing_value;
+    }
+
+    /// Compares the hits by global ordinal with `ordinal_map`, the map of the
+    /// field built by `OrdinalMap::build_sorted_set` over the leaves of the
+    /// searched reader, which saves looking up the terms in each segment.
+    pub fn set_ordinal_map(&mut self, ordinal_map: Arc<OrdinalMap>) {
+        self.ordinal_map = Some(ordinal_map);
+    }
+
+    pub fn ordinal_map(&self) -> Option<&Arc<OrdinalMap>> {
+        self.ordinal_map.as_ref()
+    }
+
+    pub fn get_comparator(&self, num_hits: usize) -> FieldComparatorEnum {
+        let comparator = TermOrdValComparator::new(
+            num_hits,
+            self.raw_field.field.clone(),
+            self.selector,
+            self.missing_value == SortFieldMissingValue::StringLast,
+        );
+        match self.ordinal_map {
+            Some(ref ordinal_map) => FieldComparatorEnum::TermOrdVal(
+                comparator.with_ordinal_map(Arc::clone(ordinal_map)),
+            ),
+            None => FieldComparatorEnum::TermOrdVal(comparator),
+        }
+    }
+
+    #[inline]
+    pub fn raw_field(&self) -> &SimpleSortField {
+        &self.raw_field
+    }
+}
+
+impl PartialEq for SortedSetSortField {
+    fn eq(&self, other: &SortedSetSortField) -> bool {
+        let same_map = match (&self.ordinal_map, &other.ordinal_map) {
+            (Some(m1), Some(m2)) => Arc::ptr_eq(m1, m2),
+            (None, None) => true,
+            _ => false,
+        };
+        self.selector == other.selector
+            && self.raw_field == other.raw_field
+            && self.missing_value == other.missing_value
+            && same_map
+    }
 }
 
+impl Eq for SortedSetSortField {}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use core::doc::{NumericDocValuesField, SortedSetDocValuesField};
+    use core::index::{Fieldable, IndexReader, IndexWriter, IndexWriterConfig};
+    use core::search::match_all::MatchAllDocsQuery;
+    use core::search::searcher::{DefaultIndexSearcher, IndexSearcher};
+    use core::search::sort::Sort;
+    use core::store::RAMDirectory;
+    use core::util::DocId;
 
     #[test]
     fn test_sort_field_with_score_type() {
@@ -506,4 +637,84 @@ mod tests {
         assert_eq!(SortFieldType::Doc, sort_field.field_type());
         assert_eq!(true, sort_field.is_reverse());
     }
+
+    #[test]
+    fn test_set_missing_order() {
+        let mut sort_field =
+            SortField::Simple(SimpleSortField::new("test".into(), SortFieldType::Long, false));
+        sort_field.set_missing_order(SortMissingOrder::Last).unwrap();
+        assert_eq!(
+            sort_field.missing_value(),
+            Some(&VariantValue::Long(i64::max_value()))
+        );
+
+        // a reversed sort puts the lowest values last
+        let mut sort_field = SortField::SortedNumeric(SortedNumericSortField::with_default_selector(
+            "test".into(),
+            SortFieldType::Int,
+            true,
+        ));
+        sort_field.set_missing_order(SortMissingOrder::Last).unwrap();
+        assert_eq!(
+            sort_field.missing_value(),
+            Some(&VariantValue::Int(i32::min_value()))
+        );
+
+        let field = SortedSetSortField::with_field("test".into(), true);
+        let mut sort_field = SortField::SortedSet(field);
+        sort_field.set_missing_order(SortMissingOrder::Last).unwrap();
+        if let SortField::SortedSet(ref s) = sort_field {
+            assert_eq!(s.missing_value(), SortFieldMissingValue::StringFirst);
+        }
+        // the generic missing value would be ignored by the comparator
+        let value = Some(VariantValue::VString("z".into()));
+        assert!(sort_field.set_missing_value(value).is_err());
+        assert_eq!(sort_field.missing_value(), None);
+        if let SortField::SortedSet(ref s) = sort_field {
+            assert_eq!(s.missing_value(), SortFieldMissingValue::StringFirst);
+        }
+
+        let mut sort_field = SortField::new_score();
+        assert!(sort_field.set_missing_order(SortMissingOrder::First).is_err());
+    }
+
+    #[test]
+    fn test_sorted_set_sort_with_ordinal_map() {
+        let dir = Arc::new(RAMDirectory::default());
+        let writer = IndexWriter::new(dir, Arc::new(IndexWriterConfig::default())).unwrap();
+        let docs: [&[&str]; 6] = [&["d", "f"], &["b"], &[], &["e"], &["a", "c"], &["c", "g"]];
+        for (i, tags) in docs.iter().enumerate() {
+            let mut doc: Vec<Box<dyn Fieldable>> =
+                vec![Box::new(NumericDocValuesField::new("id", i as i64))];
+            for tag in tags.iter() {
+                doc.push(Box::new(SortedSetDocValuesField::new("tags", tag.as_bytes())));
+            }
+            writer.add_document(doc).unwrap();
+            // two segments, whose ords differ
+            if i == 2 {
+                writer.commit().unwrap();
+            }
+        }
+        let reader = Arc::new(writer.get_reader(true, false).unwrap());
+        let searcher = DefaultIndexSearcher::new(Arc::clone(&reader));
+        let sorted = |field: &SortedSetSortField, n: usize| -> Vec<DocId> {
+            let sort = Sort::new(vec![SortField::SortedSet(field.clone())]);
+            let top_docs = searcher
+                .search_with_sort(&MatchAllDocsQuery::new(), n, &sort)
+                .unwrap();
+            top_docs.score_docs().iter().map(|d| d.doc_id()).collect()
+        };
+
+        let mut field = SortedSetSortField::with_field("tags".into(), false);
+        assert_eq!(sorted(&field, 10), vec![2, 4, 1, 5, 0, 3]);
+        assert_eq!(sorted(&field, 3), vec![2, 4, 1]);
+
+        let ordinal_map = OrdinalMap::build_sorted_set(&reader.leaves(), "tags").unwrap();
+        field.set_ordinal_map(Arc::new(ordinal_map));
+        assert_eq!(sorted(&field, 10), vec![2, 4, 1, 5, 0, 3]);
+        assert_eq!(sorted(&field, 3), vec![2, 4, 1]);
+        assert_ne!(field, SortedSetSortField::with_field("tags".into(), false));
+
+        writer.close().unwrap();
+    }
 }
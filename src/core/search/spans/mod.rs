@@ -15,5 +15,112 @@
 pub mod span;
 pub mod span_boost;
 pub mod span_near;
+pub mod span_not;
 pub mod span_or;
 pub mod span_term;
+
+#[cfg(test)]
+pub mod tests {
+    use core::index::Term;
+    use core::search::posting_iterator::PostingIterator;
+    use core::search::spans::span::{Spans, SpansEnum, NO_MORE_POSITIONS};
+    use core::search::spans::span_term::TermSpans;
+    use core::search::{DocIterator, Payload, NO_MORE_DOCS};
+    use core::util::DocId;
+    use error::Result;
+
+    /// The postings of a term, with the positions of each doc.
+    pub struct MockPostings {
+        docs: Vec<(DocId, Vec<i32>)>,
+        idx: usize,
+        doc: DocId,
+        pos_idx: usize,
+    }
+
+    impl MockPostings {
+        pub fn new(docs: Vec<(DocId, Vec<i32>)>) -> MockPostings {
+            MockPostings {
+                docs,
+                idx: 0,
+                doc: -1,
+                pos_idx: 0,
+            }
+        }
+    }
+
+    impl DocIterator for MockPostings {
+        fn doc_id(&self) -> DocId {
+            self.doc
+        }
+
+        fn next(&mut self) -> Result<DocId> {
+            if self.doc != -1 {
+                self.idx += 1;
+            }
+            self.pos_idx = 0;
+            self.doc = if self.idx < self.docs.len() {
+                self.docs[self.idx].0
+            } else {
+                NO_MORE_DOCS
+            };
+            Ok(self.doc)
+        }
+
+        fn advance(&mut self, target: DocId) -> Result<DocId> {
+            loop {
+                let doc = self.next()?;
+                if doc >= target {
+                    return Ok(doc);
+                }
+            }
+        }
+
+        fn cost(&self) -> usize {
+            self.docs.len()
+        }
+    }
+
+    impl PostingIterator for MockPostings {
+        fn freq(&self) -> Result<i32> {
+            Ok(self.docs[self.idx].1.len() as i32)
+        }
+
+        fn next_position(&mut self) -> Result<i32> {
+            let pos = self.docs[self.idx].1[self.pos_idx];
+            self.pos_idx += 1;
+            Ok(pos)
+        }
+
+        fn start_offset(&self) -> Result<i32> {
+            Ok(-1)
+        }
+
+        fn end_offset(&self) -> Result<i32> {
+            Ok(-1)
+        }
+
+        fn payload(&self) -> Result<Payload> {
+            Ok(Payload::new())
+        }
+    }
+
+    pub fn term_spans(text: &str, docs: Vec<(DocId, Vec<i32>)>) -> SpansEnum<MockPostings> {
+        let term = Term::new("body".into(), text.as_bytes().to_vec());
+        SpansEnum::Term(TermSpans::new(MockPostings::new(docs), term, 1.0))
+    }
+
+    /// Returns the (doc, start, end) of each match of `spans`.
+    pub fn collect_matches<S: Spans>(spans: &mut S) -> Vec<(DocId, i32, i32)> {
+        let mut matches = vec![];
+        loop {
+            let doc = spans.next().unwrap();
+            if doc == NO_MORE_DOCS {
+                break;
+            }
+            while spans.next_start_position().unwrap() != NO_MORE_POSITIONS {
+                matches.push((doc, spans.start_position(), spans.end_position()));
+            }
+        }
+        matches
+    }
+}
@@ -23,6 +23,7 @@ use core::search::spans::span_near::{
     GapSpans, NearSpansOrdered, NearSpansUnordered, SpanGapQuery, SpanGapWeight, SpanNearQuery,
     SpanNearWeight,
 };
+use core::search::spans::span_not::{NotSpans, SpanNotQuery, SpanNotWeight};
 use core::search::spans::span_or::{SpanOrQuery, SpanOrSpans, SpanOrWeight};
 use core::search::spans::span_term::{SpanTermQuery, SpanTermWeight, TermSpans};
 use core::search::term_query::TermQuery;
@@ -68,6 +69,7 @@ pub enum SpanQueryEnum {
     Gap(SpanGapQuery),
     Or(SpanOrQuery),
     Near(SpanNearQuery),
+    Not(SpanNotQuery),
     Boost(SpanBoostQuery),
 }
 
@@ -84,6 +86,7 @@ impl<C: Codec> SpanQuery<C> for SpanQueryEnum {
             SpanQueryEnum::Gap(q) => SpanWeightEnum::Gap(q.span_weight(searcher, needs_scores)?),
             SpanQueryEnum::Or(q) => SpanWeightEnum::Or(q.span_weight(searcher, needs_scores)?),
             SpanQueryEnum::Near(q) => SpanWeightEnum::Near(q.span_weight(searcher, needs_scores)?),
+            SpanQueryEnum::Not(q) => SpanWeightEnum::Not(q.span_weight(searcher, needs_scores)?),
             SpanQueryEnum::Boost(q) => q.span_weight(searcher, needs_scores)?,
         };
         Ok(weight)
@@ -95,6 +98,7 @@ impl<C: Codec> SpanQuery<C> for SpanQueryEnum {
             SpanQueryEnum::Gap(q) => SpanQuery::<C>::field(q),
             SpanQueryEnum::Or(q) => SpanQuery::<C>::field(q),
             SpanQueryEnum::Near(q) => SpanQuery::<C>::field(q),
+            SpanQueryEnum::Not(q) => SpanQuery::<C>::field(q),
             SpanQueryEnum::Boost(q) => SpanQuery::<C>::field(q),
         }
     }
@@ -105,6 +109,7 @@ impl<C: Codec> SpanQuery<C> for SpanQueryEnum {
             SpanQueryEnum::Gap(q) => SpanQuery::<C>::ctx(q),
             SpanQueryEnum::Or(q) => SpanQuery::<C>::ctx(q),
             SpanQueryEnum::Near(q) => SpanQuery::<C>::ctx(q),
+            SpanQueryEnum::Not(q) => SpanQuery::<C>::ctx(q),
             SpanQueryEnum::Boost(q) => SpanQuery::<C>::ctx(q),
         }
     }
@@ -121,6 +126,7 @@ impl<C: Codec> Query<C> for SpanQueryEnum {
             SpanQueryEnum::Gap(q) => q.create_weight(searcher, needs_scores),
             SpanQueryEnum::Or(q) => q.create_weight(searcher, needs_scores),
             SpanQueryEnum::Near(q) => q.create_weight(searcher, needs_scores),
+            SpanQueryEnum::Not(q) => q.create_weight(searcher, needs_scores),
             SpanQueryEnum::Boost(q) => q.create_weight(searcher, needs_scores),
         }
     }
@@ -131,6 +137,7 @@ impl<C: Codec> Query<C> for SpanQueryEnum {
             SpanQueryEnum::Gap(q) => Query::<C>::extract_terms(q),
             SpanQueryEnum::Or(q) => Query::<C>::extract_terms(q),
             SpanQueryEnum::Near(q) => Query::<C>::extract_terms(q),
+            SpanQueryEnum::Not(q) => Query::<C>::extract_terms(q),
             SpanQueryEnum::Boost(q) => Query::<C>::extract_terms(q),
         }
     }
@@ -145,6 +152,7 @@ impl<C: Codec> Query<C> for SpanQueryEnum {
             SpanQueryEnum::Gap(q) => Query::<C>::as_any(q),
             SpanQueryEnum::Or(q) => Query::<C>::as_any(q),
             SpanQueryEnum::Near(q) => Query::<C>::as_any(q),
+            SpanQueryEnum::Not(q) => Query::<C>::as_any(q),
             SpanQueryEnum::Boost(q) => Query::<C>::as_any(q),
         }
     }
@@ -157,6 +165,7 @@ impl fmt::Display for SpanQueryEnum {
             SpanQueryEnum::Gap(q) => write!(f, "SpanQueryEnum({})", q),
             SpanQueryEnum::Or(q) => write!(f, "SpanQueryEnum({})", q),
             SpanQueryEnum::Near(q) => write!(f, "SpanQueryEnum({})", q),
+            SpanQueryEnum::Not(q) => write!(f, "SpanQueryEnum({})", q),
             SpanQueryEnum::Boost(q) => write!(f, "SpanQueryEnum({})", q),
         }
     }
@@ -232,6 +241,7 @@ pub enum SpansEnum<P: PostingIterator> {
     Gap(GapSpans),
     NearOrdered(NearSpansOrdered<P>),
     NearUnordered(Box<NearSpansUnordered<P>>),
+    Not(NotSpans<P>),
     Or(SpanOrSpans<P>),
     Term(TermSpans<P>),
 }
@@ -243,6 +253,7 @@ impl<P: PostingIterator> Spans for SpansEnum<P> {
             SpansEnum::NearOrdered(s) => s.next_start_position(),
             SpansEnum::NearUnordered(s) => s.next_start_position(),
             SpansEnum::Or(s) => s.next_start_position(),
+            SpansEnum::Not(s) => s.next_start_position(),
             SpansEnum::Term(s) => s.next_start_position(),
         }
     }
@@ -253,6 +264,7 @@ impl<P: PostingIterator> Spans for SpansEnum<P> {
             SpansEnum::NearOrdered(s) => s.start_position(),
             SpansEnum::NearUnordered(s) => s.start_position(),
             SpansEnum::Or(s) => s.start_position(),
+            SpansEnum::Not(s) => s.start_position(),
             SpansEnum::Term(s) => s.start_position(),
         }
     }
@@ -263,6 +275,7 @@ impl<P: PostingIterator> Spans for SpansEnum<P> {
             SpansEnum::NearOrdered(s) => s.end_position(),
             SpansEnum::NearUnordered(s) => s.end_position(),
             SpansEnum::Or(s) => s.end_position(),
+            SpansEnum::Not(s) => s.end_position(),
             SpansEnum::Term(s) => s.end_position(),
         }
     }
@@ -273,6 +286,7 @@ impl<P: PostingIterator> Spans for SpansEnum<P> {
             SpansEnum::NearOrdered(s) => s.width(),
             SpansEnum::NearUnordered(s) => s.width(),
             SpansEnum::Or(s) => s.width(),
+            SpansEnum::Not(s) => s.width(),
             SpansEnum::Term(s) => s.width(),
         }
     }
@@ -283,6 +297,7 @@ impl<P: PostingIterator> Spans for SpansEnum<P> {
             SpansEnum::NearOrdered(s) => s.collect(collector),
             SpansEnum::NearUnordered(s) => s.collect(collector),
             SpansEnum::Or(s) => s.collect(collector),
+            SpansEnum::Not(s) => s.collect(collector),
             SpansEnum::Term(s) => s.collect(collector),
         }
     }
@@ -293,6 +308,7 @@ impl<P: PostingIterator> Spans for SpansEnum<P> {
             SpansEnum::NearOrdered(s) => s.positions_cost(),
             SpansEnum::NearUnordered(s) => s.positions_cost(),
             SpansEnum::Or(s) => s.positions_cost(),
+            SpansEnum::Not(s) => s.positions_cost(),
             SpansEnum::Term(s) => s.positions_cost(),
         }
     }
@@ -303,6 +319,7 @@ impl<P: PostingIterator> Spans for SpansEnum<P> {
             SpansEnum::NearOrdered(s) => s.do_start_current_doc(),
             SpansEnum::NearUnordered(s) => s.do_start_current_doc(),
             SpansEnum::Or(s) => s.do_start_current_doc(),
+            SpansEnum::Not(s) => s.do_start_current_doc(),
             SpansEnum::Term(s) => s.do_start_current_doc(),
         }
     }
@@ -313,6 +330,7 @@ impl<P: PostingIterator> Spans for SpansEnum<P> {
             SpansEnum::NearOrdered(s) => s.do_current_spans(),
             SpansEnum::NearUnordered(s) => s.do_current_spans(),
             SpansEnum::Or(s) => s.do_current_spans(),
+            SpansEnum::Not(s) => s.do_current_spans(),
             SpansEnum::Term(s) => s.do_current_spans(),
         }
     }
@@ -323,6 +341,7 @@ impl<P: PostingIterator> Spans for SpansEnum<P> {
             SpansEnum::NearOrdered(s) => s.support_two_phase(),
             SpansEnum::NearUnordered(s) => s.support_two_phase(),
             SpansEnum::Or(s) => s.support_two_phase(),
+            SpansEnum::Not(s) => s.support_two_phase(),
             SpansEnum::Term(s) => s.support_two_phase(),
         }
     }
@@ -333,6 +352,7 @@ impl<P: PostingIterator> Spans for SpansEnum<P> {
             SpansEnum::NearOrdered(s) => s.advance_position(position),
             SpansEnum::NearUnordered(s) => s.advance_position(position),
             SpansEnum::Or(s) => s.advance_position(position),
+            SpansEnum::Not(s) => s.advance_position(position),
             SpansEnum::Term(s) => s.advance_position(position),
         }
     }
@@ -345,6 +365,7 @@ impl<P: PostingIterator> DocIterator for SpansEnum<P> {
             SpansEnum::NearOrdered(s) => s.doc_id(),
             SpansEnum::NearUnordered(s) => s.doc_id(),
             SpansEnum::Or(s) => s.doc_id(),
+            SpansEnum::Not(s) => s.doc_id(),
             SpansEnum::Term(s) => s.doc_id(),
         }
     }
@@ -355,6 +376,7 @@ impl<P: PostingIterator> DocIterator for SpansEnum<P> {
             SpansEnum::NearOrdered(s) => s.next(),
             SpansEnum::NearUnordered(s) => s.next(),
             SpansEnum::Or(s) => s.next(),
+            SpansEnum::Not(s) => s.next(),
             SpansEnum::Term(s) => s.next(),
         }
     }
@@ -365,6 +387,7 @@ impl<P: PostingIterator> DocIterator for SpansEnum<P> {
             SpansEnum::NearOrdered(s) => s.advance(target),
             SpansEnum::NearUnordered(s) => s.advance(target),
             SpansEnum::Or(s) => s.advance(target),
+            SpansEnum::Not(s) => s.advance(target),
             SpansEnum::Term(s) => s.advance(target),
         }
     }
@@ -375,6 +398,7 @@ impl<P: PostingIterator> DocIterator for SpansEnum<P> {
             SpansEnum::NearOrdered(s) => s.slow_advance(target),
             SpansEnum::NearUnordered(s) => s.slow_advance(target),
             SpansEnum::Or(s) => s.slow_advance(target),
+            SpansEnum::Not(s) => s.slow_advance(target),
             SpansEnum::Term(s) => s.slow_advance(target),
         }
     }
@@ -385,6 +409,7 @@ impl<P: PostingIterator> DocIterator for SpansEnum<P> {
             SpansEnum::NearOrdered(s) => s.cost(),
             SpansEnum::NearUnordered(s) => s.cost(),
             SpansEnum::Or(s) => s.cost(),
+            SpansEnum::Not(s) => s.cost(),
             SpansEnum::Term(s) => s.cost(),
         }
     }
@@ -395,6 +420,7 @@ impl<P: PostingIterator> DocIterator for SpansEnum<P> {
             SpansEnum::NearOrdered(s) => s.matches(),
             SpansEnum::NearUnordered(s) => s.matches(),
             SpansEnum::Or(s) => s.matches(),
+            SpansEnum::Not(s) => s.matches(),
             SpansEnum::Term(s) => s.matches(),
         }
     }
@@ -405,6 +431,7 @@ impl<P: PostingIterator> DocIterator for SpansEnum<P> {
             SpansEnum::NearOrdered(s) => s.match_cost(),
             SpansEnum::NearUnordered(s) => s.match_cost(),
             SpansEnum::Or(s) => s.match_cost(),
+            SpansEnum::Not(s) => s.match_cost(),
             SpansEnum::Term(s) => s.match_cost(),
         }
     }
@@ -415,6 +442,7 @@ impl<P: PostingIterator> DocIterator for SpansEnum<P> {
             SpansEnum::NearOrdered(s) => s.approximate_next(),
             SpansEnum::NearUnordered(s) => s.approximate_next(),
             SpansEnum::Or(s) => s.approximate_next(),
+            SpansEnum::Not(s) => s.approximate_next(),
             SpansEnum::Term(s) => s.approximate_next(),
         }
     }
@@ -425,6 +453,7 @@ impl<P: PostingIterator> DocIterator for SpansEnum<P> {
             SpansEnum::NearOrdered(s) => s.approximate_advance(target),
             SpansEnum::NearUnordered(s) => s.approximate_advance(target),
             SpansEnum::Or(s) => s.approximate_advance(target),
+            SpansEnum::Not(s) => s.approximate_advance(target),
             SpansEnum::Term(s) => s.approximate_advance(target),
         }
     }
@@ -693,6 +722,7 @@ pub enum SpanWeightEnum<C: Codec> {
     Gap(SpanGapWeight<C>),
     Boost(SpanBoostWeight<C>),
     Near(SpanNearWeight<C>),
+    Not(SpanNotWeight<C>),
     Or(SpanOrWeight<C>),
 }
 
@@ -703,6 +733,7 @@ impl<C: Codec> SpanWeight<C> for SpanWeightEnum<C> {
             SpanWeightEnum::Gap(w) => w.sim_weight(),
             SpanWeightEnum::Or(w) => w.sim_weight(),
             SpanWeightEnum::Near(w) => w.sim_weight(),
+            SpanWeightEnum::Not(w) => w.sim_weight(),
             SpanWeightEnum::Boost(w) => w.sim_weight(),
         }
     }
@@ -713,6 +744,7 @@ impl<C: Codec> SpanWeight<C> for SpanWeightEnum<C> {
             SpanWeightEnum::Gap(w) => w.sim_weight_mut(),
             SpanWeightEnum::Or(w) => w.sim_weight_mut(),
             SpanWeightEnum::Near(w) => w.sim_weight_mut(),
+            SpanWeightEnum::Not(w) => w.sim_weight_mut(),
             SpanWeightEnum::Boost(w) => w.sim_weight_mut(),
         }
     }
@@ -727,6 +759,7 @@ impl<C: Codec> SpanWeight<C> for SpanWeightEnum<C> {
             SpanWeightEnum::Gap(w) => w.get_spans(reader, required_postings),
             SpanWeightEnum::Or(w) => w.get_spans(reader, required_postings),
             SpanWeightEnum::Near(w) => w.get_spans(reader, required_postings),
+            SpanWeightEnum::Not(w) => w.get_spans(reader, required_postings),
             SpanWeightEnum::Boost(w) => w.get_spans(reader, required_postings),
         }
     }
@@ -740,6 +773,7 @@ impl<C: Codec> SpanWeight<C> for SpanWeightEnum<C> {
             SpanWeightEnum::Gap(w) => w.extract_term_contexts(contexts),
             SpanWeightEnum::Or(w) => w.extract_term_contexts(contexts),
             SpanWeightEnum::Near(w) => w.extract_term_contexts(contexts),
+            SpanWeightEnum::Not(w) => w.extract_term_contexts(contexts),
             SpanWeightEnum::Boost(w) => w.extract_term_contexts(contexts),
         }
     }
@@ -750,6 +784,7 @@ impl<C: Codec> SpanWeight<C> for SpanWeightEnum<C> {
             SpanWeightEnum::Gap(w) => w.do_create_scorer(ctx),
             SpanWeightEnum::Or(w) => w.do_create_scorer(ctx),
             SpanWeightEnum::Near(w) => w.do_create_scorer(ctx),
+            SpanWeightEnum::Not(w) => w.do_create_scorer(ctx),
             SpanWeightEnum::Boost(w) => w.do_create_scorer(ctx),
         }
     }
@@ -760,6 +795,7 @@ impl<C: Codec> SpanWeight<C> for SpanWeightEnum<C> {
             SpanWeightEnum::Gap(w) => w.do_value_for_normalization(),
             SpanWeightEnum::Or(w) => w.do_value_for_normalization(),
             SpanWeightEnum::Near(w) => w.do_value_for_normalization(),
+            SpanWeightEnum::Not(w) => w.do_value_for_normalization(),
             SpanWeightEnum::Boost(w) => w.do_value_for_normalization(),
         }
     }
@@ -770,6 +806,7 @@ impl<C: Codec> SpanWeight<C> for SpanWeightEnum<C> {
             SpanWeightEnum::Gap(w) => w.do_normalize(query_norm, boost),
             SpanWeightEnum::Or(w) => w.do_normalize(query_norm, boost),
             SpanWeightEnum::Near(w) => w.do_normalize(query_norm, boost),
+            SpanWeightEnum::Not(w) => w.do_normalize(query_norm, boost),
             SpanWeightEnum::Boost(w) => w.do_normalize(query_norm, boost),
         }
     }
@@ -780,6 +817,7 @@ impl<C: Codec> SpanWeight<C> for SpanWeightEnum<C> {
             SpanWeightEnum::Gap(w) => w.sim_scorer(reader),
             SpanWeightEnum::Or(w) => w.sim_scorer(reader),
             SpanWeightEnum::Near(w) => w.sim_scorer(reader),
+            SpanWeightEnum::Not(w) => w.sim_scorer(reader),
             SpanWeightEnum::Boost(w) => w.sim_scorer(reader),
         }
     }
@@ -790,6 +828,7 @@ impl<C: Codec> SpanWeight<C> for SpanWeightEnum<C> {
             SpanWeightEnum::Gap(w) => w.explain_span(reader, doc),
             SpanWeightEnum::Or(w) => w.explain_span(reader, doc),
             SpanWeightEnum::Near(w) => w.explain_span(reader, doc),
+            SpanWeightEnum::Not(w) => w.explain_span(reader, doc),
             SpanWeightEnum::Boost(w) => w.explain_span(reader, doc),
         }
     }
@@ -805,6 +844,7 @@ impl<C: Codec> Weight<C> for SpanWeightEnum<C> {
             SpanWeightEnum::Gap(w) => w.create_scorer(leaf_reader),
             SpanWeightEnum::Or(w) => w.create_scorer(leaf_reader),
             SpanWeightEnum::Near(w) => w.create_scorer(leaf_reader),
+            SpanWeightEnum::Not(w) => w.create_scorer(leaf_reader),
             SpanWeightEnum::Boost(w) => w.create_scorer(leaf_reader),
         }
     }
@@ -815,6 +855,7 @@ impl<C: Codec> Weight<C> for SpanWeightEnum<C> {
             SpanWeightEnum::Gap(w) => w.hash_code(),
             SpanWeightEnum::Or(w) => w.hash_code(),
             SpanWeightEnum::Near(w) => w.hash_code(),
+            SpanWeightEnum::Not(w) => w.hash_code(),
             SpanWeightEnum::Boost(w) => w.hash_code(),
         }
     }
@@ -829,6 +870,7 @@ impl<C: Codec> Weight<C> for SpanWeightEnum<C> {
             SpanWeightEnum::Gap(w) => w.actual_query_type(),
             SpanWeightEnum::Or(w) => w.actual_query_type(),
             SpanWeightEnum::Near(w) => w.actual_query_type(),
+            SpanWeightEnum::Not(w) => w.actual_query_type(),
             SpanWeightEnum::Boost(w) => w.actual_query_type(),
         }
     }
@@ -839,6 +881,7 @@ impl<C: Codec> Weight<C> for SpanWeightEnum<C> {
             SpanWeightEnum::Gap(w) => w.normalize(norm, boost),
             SpanWeightEnum::Or(w) => w.normalize(norm, boost),
             SpanWeightEnum::Near(w) => w.normalize(norm, boost),
+            SpanWeightEnum::Not(w) => w.normalize(norm, boost),
             SpanWeightEnum::Boost(w) => w.normalize(norm, boost),
         }
     }
@@ -849,6 +892,7 @@ impl<C: Codec> Weight<C> for SpanWeightEnum<C> {
             SpanWeightEnum::Gap(w) => w.value_for_normalization(),
             SpanWeightEnum::Or(w) => w.value_for_normalization(),
             SpanWeightEnum::Near(w) => w.value_for_normalization(),
+            SpanWeightEnum::Not(w) => w.value_for_normalization(),
             SpanWeightEnum::Boost(w) => w.value_for_normalization(),
         }
     }
@@ -859,6 +903,7 @@ impl<C: Codec> Weight<C> for SpanWeightEnum<C> {
             SpanWeightEnum::Gap(w) => w.needs_scores(),
             SpanWeightEnum::Or(w) => w.needs_scores(),
             SpanWeightEnum::Near(w) => w.needs_scores(),
+            SpanWeightEnum::Not(w) => w.needs_scores(),
             SpanWeightEnum::Boost(w) => w.needs_scores(),
         }
     }
@@ -869,6 +914,7 @@ impl<C: Codec> Weight<C> for SpanWeightEnum<C> {
             SpanWeightEnum::Gap(w) => w.explain(reader, doc),
             SpanWeightEnum::Or(w) => w.explain(reader, doc),
             SpanWeightEnum::Near(w) => w.explain(reader, doc),
+            SpanWeightEnum::Not(w) => w.explain(reader, doc),
             SpanWeightEnum::Boost(w) => w.explain(reader, doc),
         }
     }
@@ -881,6 +927,7 @@ impl<C: Codec> fmt::Display for SpanWeightEnum<C> {
             SpanWeightEnum::Gap(w) => write!(f, "SpanWeightEnum({})", w),
             SpanWeightEnum::Or(w) => write!(f, "SpanWeightEnum({})", w),
             SpanWeightEnum::Near(w) => write!(f, "SpanWeightEnum({})", w),
+            SpanWeightEnum::Not(w) => write!(f, "SpanWeightEnum({})", w),
             SpanWeightEnum::Boost(w) => write!(f, "SpanWeightEnum({})", w),
         }
     }
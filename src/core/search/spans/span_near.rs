@@ -1057,3 +1057,31 @@ impl DocIterator for GapSpans {
         0
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::search::spans::tests::*;
+
+    fn near_ordered_matches(slop: i32) -> Vec<(DocId, i32, i32)> {
+        let a = term_spans(
+            "a",
+            vec![(0, vec![0]), (1, vec![3]), (2, vec![0, 5]), (3, vec![2])],
+        );
+        let b = term_spans(
+            "b",
+            vec![(0, vec![1]), (1, vec![1]), (2, vec![7]), (4, vec![0])],
+        );
+        let mut spans = NearSpansOrdered::new(slop, vec![a, b]).unwrap();
+        collect_matches(&mut spans)
+    }
+
+    #[test]
+    fn test_near_spans_ordered() {
+        // "b" must follow "a", in doc 1 it precedes it
+        assert_eq!(near_ordered_matches(0), vec![(0, 0, 2)]);
+        // in doc 2 the second "a" is one position away from "b"
+        assert_eq!(near_ordered_matches(1), vec![(0, 0, 2), (2, 5, 8)]);
+        assert_eq!(near_ordered_matches(6), vec![(0, 0, 2), (2, 0, 8), (2, 5, 8)]);
+    }
+}
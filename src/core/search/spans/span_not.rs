@@ -0,0 +1,428 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::codec::{Codec, CodecEnum, CodecPostingIterator, CodecTermState};
+use core::index::{LeafReaderContext, Term, TermContext};
+use core::search::explanation::Explanation;
+use core::search::posting_iterator::PostingIterator;
+use core::search::searcher::SearchPlanBuilder;
+use core::search::spans::span::{
+    build_sim_weight, PostingsFlag, SpanQueryEnum, SpanWeightEnum, SpansEnum, NO_MORE_POSITIONS,
+};
+use core::search::spans::span::{SpanCollector, SpanQuery, SpanWeight, Spans};
+use core::search::term_query::TermQuery;
+use core::search::{DocIterator, Query, Scorer, SimWeight, Weight, NO_MORE_DOCS};
+use core::util::{DocId, KeyedContext};
+
+use error::{ErrorKind, Result};
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+const SPAN_NOT_QUERY: &str = "span_not";
+
+/// Removes the matches of `include` which overlap a match of `exclude`.
+///
+/// A match of `include` is also removed when a match of `exclude` ends less
+/// than `pre` positions before its start, or starts less than `post`
+/// positions after its end.
+pub struct SpanNotQuery {
+    include: Box<SpanQueryEnum>,
+    exclude: Box<SpanQueryEnum>,
+    pre: i32,
+    post: i32,
+}
+
+impl SpanNotQuery {
+    pub fn new(
+        include: SpanQueryEnum,
+        exclude: SpanQueryEnum,
+        pre: i32,
+        post: i32,
+    ) -> Result<Self> {
+        if SpanQuery::<CodecEnum>::field(&include) != SpanQuery::<CodecEnum>::field(&exclude) {
+            bail!(ErrorKind::IllegalArgument(
+                "Clauses must have same field.".into()
+            ));
+        }
+        Ok(SpanNotQuery {
+            include: Box::new(include),
+            exclude: Box::new(exclude),
+            pre,
+            post,
+        })
+    }
+
+    fn span_not_weight<C: Codec>(
+        &self,
+        searcher: &dyn SearchPlanBuilder<C>,
+        needs_scores: bool,
+    ) -> Result<SpanNotWeight<C>> {
+        // only the include clause is scored
+        let include_weight = self.include.span_weight(searcher, false)?;
+        let exclude_weight = self.exclude.span_weight(searcher, false)?;
+        let mut term_contexts = HashMap::new();
+        if needs_scores {
+            include_weight.extract_term_contexts(&mut term_contexts);
+        }
+        SpanNotWeight::new(self, include_weight, exclude_weight, searcher, term_contexts)
+    }
+}
+
+impl<C: Codec> SpanQuery<C> for SpanNotQuery {
+    type Weight = SpanNotWeight<C>;
+
+    fn span_weight(
+        &self,
+        searcher: &dyn SearchPlanBuilder<C>,
+        needs_scores: bool,
+    ) -> Result<Self::Weight> {
+        self.span_not_weight(searcher, needs_scores)
+    }
+
+    fn field(&self) -> &str {
+        SpanQuery::<C>::field(self.include.as_ref())
+    }
+
+    fn ctx(&self) -> Option<KeyedContext> {
+        SpanQuery::<C>::ctx(self.include.as_ref())
+    }
+}
+
+impl<C: Codec> Query<C> for SpanNotQuery {
+    fn create_weight(
+        &self,
+        searcher: &dyn SearchPlanBuilder<C>,
+        needs_scores: bool,
+    ) -> Result<Box<dyn Weight<C>>> {
+        Ok(Box::new(self.span_not_weight(searcher, needs_scores)?))
+    }
+
+    fn extract_terms(&self) -> Vec<TermQuery> {
+        Query::<C>::extract_terms(self.include.as_ref())
+    }
+
+    fn query_type(&self) -> &'static str {
+        SPAN_NOT_QUERY
+    }
+
+    fn as_any(&self) -> &::std::any::Any {
+        self
+    }
+}
+
+impl fmt::Display for SpanNotQuery {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "SpanNotQuery(include: {}, exclude: {}, pre: {}, post: {})",
+            &self.include, &self.exclude, self.pre, self.post
+        )
+    }
+}
+
+pub struct SpanNotWeight<C: Codec> {
+    sim_weight: Option<Box<dyn SimWeight<C>>>,
+    include_weight: Box<SpanWeightEnum<C>>,
+    exclude_weight: Box<SpanWeightEnum<C>>,
+    pre: i32,
+    post: i32,
+}
+
+impl<C: Codec> SpanNotWeight<C> {
+    pub fn new<IS: SearchPlanBuilder<C> + ?Sized>(
+        query: &SpanNotQuery,
+        include_weight: SpanWeightEnum<C>,
+        exclude_weight: SpanWeightEnum<C>,
+        searcher: &IS,
+        terms: HashMap<Term, Arc<TermContext<CodecTermState<C>>>>,
+    ) -> Result<Self> {
+        let sim_weight = build_sim_weight(
+            SpanQuery::<C>::field(query),
+            searcher,
+            terms,
+            SpanQuery::<C>::ctx(query),
+        )?;
+        Ok(SpanNotWeight {
+            sim_weight,
+            include_weight: Box::new(include_weight),
+            exclude_weight: Box::new(exclude_weight),
+            pre: query.pre,
+            post: query.post,
+        })
+    }
+}
+
+impl<C: Codec> SpanWeight<C> for SpanNotWeight<C> {
+    fn sim_weight(&self) -> Option<&SimWeight<C>> {
+        self.sim_weight.as_ref().map(|x| &**x)
+    }
+
+    fn sim_weight_mut(&mut self) -> Option<&mut SimWeight<C>> {
+        if let Some(ref mut sim_weight) = self.sim_weight {
+            Some(sim_weight.as_mut())
+        } else {
+            None
+        }
+    }
+
+    fn get_spans(
+        &self,
+        reader: &LeafReaderContext<'_, C>,
+        required_postings: &PostingsFlag,
+    ) -> Result<Option<SpansEnum<CodecPostingIterator<C>>>> {
+        let include = match self.include_weight.get_spans(reader, required_postings)? {
+            Some(spans) => spans,
+            None => return Ok(None),
+        };
+        match self.exclude_weight.get_spans(reader, required_postings)? {
+            Some(exclude) => Ok(Some(SpansEnum::Not(NotSpans::new(
+                include, exclude, self.pre, self.post,
+            )))),
+            None => Ok(Some(include)),
+        }
+    }
+
+    fn extract_term_contexts(
+        &self,
+        contexts: &mut HashMap<Term, Arc<TermContext<CodecTermState<C>>>>,
+    ) {
+        self.include_weight.extract_term_contexts(contexts)
+    }
+}
+
+impl<C: Codec> Weight<C> for SpanNotWeight<C> {
+    fn create_scorer(&self, ctx: &LeafReaderContext<'_, C>) -> Result<Option<Box<dyn Scorer>>> {
+        self.do_create_scorer(ctx)
+    }
+
+    fn query_type(&self) -> &'static str {
+        SPAN_NOT_QUERY
+    }
+
+    fn normalize(&mut self, norm: f32, boost: f32) {
+        self.do_normalize(norm, boost)
+    }
+
+    fn value_for_normalization(&self) -> f32 {
+        self.do_value_for_normalization()
+    }
+
+    fn needs_scores(&self) -> bool {
+        true
+    }
+
+    fn explain(&self, reader: &LeafReaderContext<'_, C>, doc: DocId) -> Result<Explanation> {
+        self.explain_span(reader, doc)
+    }
+}
+
+impl<C: Codec> fmt::Display for SpanNotWeight<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "SpanNotWeight(include: {}, exclude: {}, pre: {}, post: {})",
+            &self.include_weight, &self.exclude_weight, self.pre, self.post
+        )
+    }
+}
+
+/// The spans of `include` which are not close to a span of `exclude`.
+///
+/// The exclude spans only move forward: the include spans are in increasing
+/// start position order, so the exclude spans ending before the current one
+/// can't remove the next ones either.
+pub struct NotSpans<P: PostingIterator> {
+    include: Box<SpansEnum<P>>,
+    exclude: Box<SpansEnum<P>>,
+    pre: i32,
+    post: i32,
+    start_position: i32,
+    // the first accepted position of the current doc was already read by
+    // the doc iteration
+    first_in_current_doc: bool,
+}
+
+impl<P: PostingIterator> NotSpans<P> {
+    pub fn new(include: SpansEnum<P>, exclude: SpansEnum<P>, pre: i32, post: i32) -> Self {
+        NotSpans {
+            include: Box::new(include),
+            exclude: Box::new(exclude),
+            pre,
+            post,
+            start_position: -1,
+            first_in_current_doc: false,
+        }
+    }
+
+    /// Whether the current include span is far enough from the exclude spans.
+    fn accept(&mut self) -> Result<bool> {
+        let doc = self.include.doc_id();
+        if doc > self.exclude.doc_id() {
+            self.exclude.advance(doc)?;
+        }
+        if doc != self.exclude.doc_id() {
+            return Ok(true);
+        }
+
+        if self.exclude.start_position() == -1 {
+            self.exclude.next_start_position()?;
+        }
+        while self.exclude.end_position() <= self.include.start_position() - self.pre {
+            if self.exclude.next_start_position()? == NO_MORE_POSITIONS {
+                return Ok(true);
+            }
+        }
+        Ok(self.exclude.start_position() == NO_MORE_POSITIONS
+            || self.include.end_position() + self.post <= self.exclude.start_position())
+    }
+
+    fn current_doc_matches(&mut self) -> Result<bool> {
+        self.first_in_current_doc = false;
+        loop {
+            self.start_position = self.include.next_start_position()?;
+            if self.start_position == NO_MORE_POSITIONS {
+                return Ok(false);
+            }
+            if self.accept()? {
+                self.first_in_current_doc = true;
+                return Ok(true);
+            }
+        }
+    }
+
+    fn next_to_match_doc(&mut self, mut doc: DocId) -> Result<DocId> {
+        while doc != NO_MORE_DOCS && !self.current_doc_matches()? {
+            doc = self.include.next()?;
+        }
+        Ok(doc)
+    }
+}
+
+impl<P: PostingIterator> Spans for NotSpans<P> {
+    fn next_start_position(&mut self) -> Result<i32> {
+        if self.first_in_current_doc {
+            self.first_in_current_doc = false;
+            return Ok(self.start_position);
+        }
+        loop {
+            self.start_position = self.include.next_start_position()?;
+            if self.start_position == NO_MORE_POSITIONS || self.accept()? {
+                return Ok(self.start_position);
+            }
+        }
+    }
+
+    fn start_position(&self) -> i32 {
+        if self.first_in_current_doc {
+            -1
+        } else {
+            self.start_position
+        }
+    }
+
+    fn end_position(&self) -> i32 {
+        if self.first_in_current_doc {
+            -1
+        } else if self.start_position == NO_MORE_POSITIONS {
+            NO_MORE_POSITIONS
+        } else {
+            self.include.end_position()
+        }
+    }
+
+    fn width(&self) -> i32 {
+        self.include.width()
+    }
+
+    fn collect(&mut self, collector: &mut impl SpanCollector) -> Result<()> {
+        self.include.collect(collector)
+    }
+
+    fn positions_cost(&self) -> f32 {
+        self.include.positions_cost()
+    }
+
+    fn support_two_phase(&self) -> bool {
+        false
+    }
+}
+
+impl<P: PostingIterator> DocIterator for NotSpans<P> {
+    fn doc_id(&self) -> DocId {
+        self.include.doc_id()
+    }
+
+    fn next(&mut self) -> Result<DocId> {
+        let doc = self.include.next()?;
+        self.next_to_match_doc(doc)
+    }
+
+    fn advance(&mut self, target: DocId) -> Result<DocId> {
+        let doc = self.include.advance(target)?;
+        self.next_to_match_doc(doc)
+    }
+
+    fn cost(&self) -> usize {
+        self.include.cost()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::search::spans::span_near::NearSpansOrdered;
+    use core::search::spans::tests::*;
+
+    fn not_matches(pre: i32, post: i32) -> Vec<(DocId, i32, i32)> {
+        let include = term_spans(
+            "x",
+            vec![(0, vec![0, 4]), (1, vec![2]), (2, vec![1, 3, 6]), (3, vec![5])],
+        );
+        let exclude = term_spans("z", vec![(0, vec![4]), (2, vec![2]), (4, vec![0])]);
+        let mut spans = NotSpans::new(include, exclude, pre, post);
+        collect_matches(&mut spans)
+    }
+
+    #[test]
+    fn test_not_spans() {
+        // only the overlapping spans are removed, the adjacent ones are kept
+        assert_eq!(
+            not_matches(0, 0),
+            vec![(0, 0, 1), (1, 2, 3), (2, 1, 2), (2, 3, 4), (2, 6, 7), (3, 5, 6)]
+        );
+        // "x" right after "z"
+        assert_eq!(
+            not_matches(1, 0),
+            vec![(0, 0, 1), (1, 2, 3), (2, 1, 2), (2, 6, 7), (3, 5, 6)]
+        );
+        // "x" right before "z"
+        assert_eq!(
+            not_matches(0, 1),
+            vec![(0, 0, 1), (1, 2, 3), (2, 3, 4), (2, 6, 7), (3, 5, 6)]
+        );
+        assert_eq!(not_matches(4, 4), vec![(1, 2, 3), (3, 5, 6)]);
+    }
+
+    #[test]
+    fn test_not_spans_near() {
+        // "x y" but not when "z" intervenes
+        let x = term_spans("x", vec![(0, vec![0]), (1, vec![0]), (2, vec![0, 5])]);
+        let y = term_spans("y", vec![(0, vec![2]), (1, vec![2]), (2, vec![3, 7])]);
+        let near = NearSpansOrdered::new(1, vec![x, y]).unwrap();
+        let z = term_spans("z", vec![(0, vec![1]), (2, vec![1])]);
+        let mut spans = NotSpans::new(SpansEnum::NearOrdered(near), z, 0, 0);
+        assert_eq!(collect_matches(&mut spans), vec![(1, 0, 3), (2, 5, 8)]);
+    }
+}
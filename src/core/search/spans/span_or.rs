@@ -512,18 +512,26 @@ impl<P: PostingIterator> SpansElement<P> {
     }
 }
 
+impl<P: PostingIterator> SpansElement<P> {
+    fn positions(&self) -> (i32, i32) {
+        let spans = self.spans();
+        (spans.start_position(), spans.end_position())
+    }
+}
+
 impl<P: PostingIterator> Eq for SpansElement<P> {}
 
 impl<P: PostingIterator> PartialEq for SpansElement<P> {
     fn eq(&self, other: &SpansElement<P>) -> bool {
-        self.spans().doc_id().eq(&other.spans().doc_id())
+        self.positions() == other.positions()
     }
 }
 
 impl<P: PostingIterator> Ord for SpansElement<P> {
     fn cmp(&self, other: &Self) -> Ordering {
-        // reversed order by doc id for BinaryHeap
-        other.spans().doc_id().cmp(&self.spans().doc_id())
+        // all the spans are on the same doc, reversed order by start position
+        // then end position for BinaryHeap
+        other.positions().cmp(&self.positions())
     }
 }
 
@@ -532,3 +540,43 @@ impl<P: PostingIterator> PartialOrd for SpansElement<P> {
         Some(self.cmp(other))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::search::spans::span_near::NearSpansOrdered;
+    use core::search::spans::tests::*;
+
+    #[test]
+    fn test_span_or_spans() {
+        let a = vec![(0, vec![1, 4]), (1, vec![0]), (3, vec![2])];
+        let b = vec![(0, vec![0, 4]), (2, vec![5]), (3, vec![3])];
+        let near = NearSpansOrdered::new(
+            0,
+            vec![term_spans("a", a.clone()), term_spans("b", b.clone())],
+        )
+        .unwrap();
+        let sub_spans = vec![
+            term_spans("a", a),
+            term_spans("b", b),
+            SpansEnum::NearOrdered(near),
+        ];
+        let mut spans = SpanOrSpans::new(DisiPriorityQueue::new(sub_spans), BinaryHeap::new());
+
+        // sorted by start then end position, the identical spans are kept
+        assert_eq!(
+            collect_matches(&mut spans),
+            vec![
+                (0, 0, 1),
+                (0, 1, 2),
+                (0, 4, 5),
+                (0, 4, 5),
+                (1, 0, 1),
+                (2, 5, 6),
+                (3, 2, 3),
+                (3, 2, 4),
+                (3, 3, 4),
+            ]
+        );
+    }
+}
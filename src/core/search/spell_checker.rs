@@ -0,0 +1,231 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use core::codec::Codec;
+use core::index::Term;
+use core::search::multi_term_query::{MultiTermQuery, MAX_EDITS};
+use core::search::searcher::IndexSearcher;
+
+use error::ErrorKind::IllegalArgument;
+use error::Result;
+
+use std::cmp::{min, Ordering};
+
+pub const DEFAULT_MIN_PREFIX: usize = 1;
+pub const DEFAULT_MAX_INSPECTIONS: usize = 5;
+
+/// A term suggested by a `DirectSpellChecker`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SuggestWord {
+    pub term: String,
+    /// The number of edits from the misspelled term.
+    pub edits: usize,
+    pub doc_freq: i64,
+    /// `1 - edits / length`, the length being the one of the shortest of the
+    /// suggested and misspelled terms, in chars.
+    pub score: f32,
+}
+
+/// Suggests the terms of a field close to a misspelled one, read straight
+/// from the term dictionary of the index so no separate dictionary has to
+/// be built.
+///
+/// The candidates are the terms within `max_edits` of the misspelled one which
+/// share its first `min_prefix` chars, found like the terms of a fuzzy
+/// `MultiTermQuery`. The term dictionary is enumerated until `max_inspections`
+/// candidates per wanted suggestion are found, and these candidates are then
+/// ranked by score, and by doc freq for equal scores.
+pub struct DirectSpellChecker {
+    max_edits: usize,
+    min_prefix: usize,
+    max_inspections: usize,
+}
+
+impl Default for DirectSpellChecker {
+    fn default() -> Self {
+        DirectSpellChecker {
+            max_edits: MAX_EDITS,
+            min_prefix: DEFAULT_MIN_PREFIX,
+            max_inspections: DEFAULT_MAX_INSPECTIONS,
+        }
+    }
+}
+
+impl DirectSpellChecker {
+    pub fn with_max_edits(mut self, max_edits: usize) -> Result<DirectSpellChecker> {
+        if max_edits == 0 || max_edits > MAX_EDITS {
+            bail!(IllegalArgument(format!(
+                "max_edits must be in [1, {}], got {}",
+                MAX_EDITS, max_edits
+            )));
+        }
+        self.max_edits = max_edits;
+        Ok(self)
+    }
+
+    /// A prefix of 1 avoids reading the whole term dictionary, misspellings
+    /// are rarely on the first char.
+    pub fn with_min_prefix(mut self, min_prefix: usize) -> DirectSpellChecker {
+        self.min_prefix = min_prefix;
+        self
+    }
+
+    pub fn with_max_inspections(mut self, max_inspections: usize) -> Result<DirectSpellChecker> {
+        if max_inspections == 0 {
+            bail!(IllegalArgument("max_inspections must be positive".into()));
+        }
+        self.max_inspections = max_inspections;
+        Ok(self)
+    }
+
+    /// Returns at most `num_suggestions` terms of the field of `term` close to
+    /// it, the best first. The term itself is never suggested.
+    pub fn suggest<C: Codec, IS: IndexSearcher<C>>(
+        &self,
+        searcher: &IS,
+        term: &Term,
+        num_suggestions: usize,
+    ) -> Result<Vec<SuggestWord>> {
+        let text = term.text()?;
+        let text_len = text.chars().count();
+        if num_suggestions == 0 || text_len == 0 {
+            return Ok(vec![]);
+        }
+        let query = MultiTermQuery::fuzzy(term.field().to_string(), &text, self.max_edits)?
+            .with_prefix_length(self.min_prefix);
+        let max_inspections = num_suggestions.saturating_mul(self.max_inspections);
+        let mut suggestions = Vec::with_capacity(min(max_inspections, 1024));
+        query.visit_matching_terms::<C, _>(searcher, |candidate, doc_freq, distance| {
+            if distance > 0 {
+                // the fuzzy matching only accepts UTF-8 terms
+                let suggested = String::from_utf8_lossy(candidate).into_owned();
+                let length = min(text_len, suggested.chars().count());
+                suggestions.push(SuggestWord {
+                    term: suggested,
+                    edits: distance,
+                    doc_freq: i64::from(doc_freq),
+                    score: 1.0 - distance as f32 / length as f32,
+                });
+            }
+            suggestions.len() < max_inspections
+        })?;
+        suggestions.sort_by(|a, b| {
+            b.score
+                .partial_cmp(&a.score)
+                .unwrap_or(Ordering::Equal)
+                .then(b.doc_freq.cmp(&a.doc_freq))
+                .then_with(|| a.term.cmp(&b.term))
+        });
+        suggestions.truncate(num_suggestions);
+        Ok(suggestions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::analysis::whitespace_tokenizer::WhitespaceTokenizer;
+    use core::analysis::TokenStream;
+    use core::doc::{Field, FieldType};
+    use core::index::{Fieldable, IndexOptions, IndexWriter, IndexWriterConfig};
+    use core::search::searcher::DefaultIndexSearcher;
+    use core::store::RAMDirectory;
+
+    use std::io::Cursor;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_suggest() {
+        let dir = Arc::new(RAMDirectory::default());
+        let writer = IndexWriter::new(dir, Arc::new(IndexWriterConfig::default())).unwrap();
+        let mut field_type = FieldType::default();
+        field_type.index_options = IndexOptions::DocsAndFreqs;
+        let texts = [
+            "lucene search",
+            "lucene index",
+            "lucene",
+            "lucent light",
+            "lucent",
+            "lucine",
+            "licene",
+            "rucene",
+        ];
+        for text in &texts {
+            let tokens: Box<dyn TokenStream> = Box::new(WhitespaceTokenizer::new(Box::new(
+                Cursor::new(text.as_bytes().to_vec()),
+            )));
+            let doc: Vec<Box<dyn Fieldable>> = vec![Box::new(Field::new(
+                "body".into(),
+                field_type.clone(),
+                None,
+                Some(tokens),
+            ))];
+            writer.add_document(doc).unwrap();
+        }
+        let reader = Arc::new(writer.get_reader(true, false).unwrap());
+        let searcher = DefaultIndexSearcher::new(Arc::clone(&reader));
+        let suggest = |checker: &DirectSpellChecker, text: &str, num_suggestions: usize| {
+            let term = Term::new("body".into(), text.as_bytes().to_vec());
+            checker
+                .suggest(&searcher, &term, num_suggestions)
+                .unwrap()
+                .into_iter()
+                .map(|s| (s.term, s.edits, s.doc_freq))
+                .collect::<Vec<(String, usize, i64)>>()
+        };
+        let owned = |words: &[(&str, usize, i64)]| {
+            words
+                .iter()
+                .map(|&(t, e, f)| (t.to_string(), e, f))
+                .collect::<Vec<(String, usize, i64)>>()
+        };
+
+        // the fewer edits first, then the more frequent, "rucene" lacks the prefix
+        let checker = DirectSpellChecker::default();
+        assert_eq!(
+            suggest(&checker, "lucenr", 3),
+            owned(&[("lucene", 1, 3), ("lucent", 1, 2), ("licene", 2, 1)])
+        );
+        let checker = DirectSpellChecker::default().with_max_edits(1).unwrap();
+        assert_eq!(
+            suggest(&checker, "lucenr", 3),
+            owned(&[("lucene", 1, 3), ("lucent", 1, 2)])
+        );
+        // the term itself is not suggested
+        assert_eq!(
+            suggest(&checker, "lucene", 5),
+            owned(&[("lucent", 1, 2), ("licene", 1, 1), ("lucine", 1, 1)])
+        );
+        let checker = DirectSpellChecker::default().with_min_prefix(0);
+        assert_eq!(
+            suggest(&checker, "rucenr", 2),
+            owned(&[("rucene", 1, 1), ("lucene", 2, 3)])
+        );
+        // the enumeration stops at the first candidate, in term order
+        let checker = DirectSpellChecker::default()
+            .with_max_inspections(1)
+            .unwrap();
+        assert_eq!(suggest(&checker, "licenr", 1), owned(&[("licene", 1, 1)]));
+        assert_eq!(suggest(&checker, "lucenr", 1), owned(&[("licene", 2, 1)]));
+        assert!(suggest(&checker, "xyz", 3).is_empty());
+
+        assert!(DirectSpellChecker::default().with_max_edits(0).is_err());
+        assert!(DirectSpellChecker::default().with_max_edits(3).is_err());
+        assert!(DirectSpellChecker::default()
+            .with_max_inspections(0)
+            .is_err());
+
+        writer.close().unwrap();
+    }
+}
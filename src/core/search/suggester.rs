@@ -0,0 +1,306 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use core::util::fst::fst_builder::FstBuilder;
+use core::util::fst::{Arc as FstArc, InputType, Output, FST};
+use core::util::fst::{PositiveIntOutput, PositiveIntOutputFactory};
+use core::util::ints_ref::{to_ints_ref, IntsRefBuilder};
+
+use error::ErrorKind::IllegalArgument;
+use error::Result;
+
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashSet};
+
+/// Ends the text of the keys, before the 4 bytes of the ord of their entry.
+const SEPARATOR: u8 = 0;
+
+/// A surface form to complete, with its weight and payload.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Suggestion {
+    pub surface_form: String,
+    pub weight: i64,
+    pub payload: Vec<u8>,
+}
+
+/// Completes a prefix into the highest weighted surface forms starting with
+/// it, ignoring case. When built with `infix`, a surface form also completes
+/// the prefixes of each of its words, so "york" completes "new york".
+///
+/// The keys are the lowercased surface forms, or their suffixes starting at
+/// each word, in an FST whose outputs are costs, `i64::MAX - weight`. The FST
+/// pushes the smallest cost below each arc onto it, so a lookup follows the
+/// cheapest arcs first and only reads the arcs on the way to the best
+/// completions, however many keys start with the prefix.
+pub struct Suggester {
+    // `None` if there is no entry
+    fst: Option<FST<PositiveIntOutputFactory>>,
+    // by ord
+    entries: Vec<Suggestion>,
+}
+
+impl Suggester {
+    /// Builds a suggester from `(surface_form, weight, payload)` entries. The
+    /// weights must not be negative, and the surface forms must not contain
+    /// `\0`.
+    pub fn build<I>(entries: I, infix: bool) -> Result<Suggester>
+    where
+        I: IntoIterator<Item = (String, i64, Vec<u8>)>,
+    {
+        let mut suggestions = vec![];
+        let mut keys: Vec<(Vec<u8>, u64)> = vec![];
+        for (surface_form, weight, payload) in entries {
+            if weight < 0 {
+                bail!(IllegalArgument(format!(
+                    "weight of '{}' must not be negative, got {}",
+                    surface_form, weight
+                )));
+            }
+            if surface_form.as_bytes().contains(&SEPARATOR) {
+                bail!(IllegalArgument(format!(
+                    "surface form '{}' must not contain \\0",
+                    surface_form.escape_default()
+                )));
+            }
+            if suggestions.len() > u32::max_value() as usize {
+                bail!(IllegalArgument("too many suggester entries".into()));
+            }
+            let ord = suggestions.len() as u32;
+            let cost = (i64::max_value() - weight) as u64;
+            for start in word_starts(&surface_form, infix) {
+                let mut key = surface_form[start..].to_lowercase().into_bytes();
+                key.push(SEPARATOR);
+                key.extend_from_slice(&ord.to_be_bytes());
+                keys.push((key, cost));
+            }
+            suggestions.push(Suggestion {
+                surface_form,
+                weight,
+                payload,
+            });
+        }
+        // the keys are unique thanks to the ords
+        keys.sort_unstable();
+
+        let mut builder = FstBuilder::new(InputType::Byte1, PositiveIntOutputFactory::default());
+        builder.init();
+        let mut scratch = IntsRefBuilder::new();
+        for (key, cost) in &keys {
+            builder.add(to_ints_ref(key, &mut scratch), PositiveIntOutput::new(*cost))?;
+        }
+        Ok(Suggester {
+            fst: builder.finish()?,
+            entries: suggestions,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns at most `num` entries completing `prefix`, by descending weight
+    /// and ascending key for equal weights.
+    pub fn lookup(&self, prefix: &str, num: usize) -> Result<Vec<&Suggestion>> {
+        let fst = match self.fst {
+            Some(ref fst) => fst,
+            None => return Ok(vec![]),
+        };
+        let prefix = prefix.to_lowercase().into_bytes();
+        let mut reader = fst.bytes_reader();
+        let mut arc = fst.root_arc();
+        let mut cost = 0;
+        for &b in &prefix {
+            arc = match fst.find_target_arc(i32::from(b), &arc, &mut reader)? {
+                Some(arc) => arc,
+                None => return Ok(vec![]),
+            };
+            cost += output_cost(&arc.output);
+        }
+
+        // best first: the cost of a path is a lower bound of the cost of the
+        // keys below it, and the exact cost of its key if it is final
+        let mut queue = BinaryHeap::new();
+        queue.push(Reverse(Path {
+            cost,
+            key: prefix,
+            arc,
+        }));
+        let mut results = Vec::with_capacity(num);
+        // with infix keys an entry may be reached several times
+        let mut seen = HashSet::new();
+        while results.len() < num {
+            let path = match queue.pop() {
+                Some(Reverse(path)) => path,
+                None => break,
+            };
+            if path.arc.is_final() {
+                // a key can't be the prefix of another one, all end with an ord
+                let ord = path.key[path.key.len() - 4..]
+                    .iter()
+                    .fold(0usize, |ord, &b| ord << 8 | b as usize);
+                if seen.insert(ord) {
+                    results.push(&self.entries[ord]);
+                }
+                continue;
+            }
+            let mut child = fst.read_first_real_arc(path.arc.target, &mut reader)?;
+            loop {
+                let mut key = path.key.clone();
+                key.push(child.label as u8);
+                let mut cost = path.cost + output_cost(&child.output);
+                if child.is_final() {
+                    cost += output_cost(&child.next_final_output);
+                }
+                let is_last = child.is_last();
+                queue.push(Reverse(Path {
+                    cost,
+                    key,
+                    arc: child.clone(),
+                }));
+                if is_last {
+                    break;
+                }
+                fst.read_next_real_arc(&mut child, &mut reader)?;
+            }
+        }
+        Ok(results)
+    }
+}
+
+/// The byte offsets at which the keys of `surface_form` start.
+fn word_starts(surface_form: &str, infix: bool) -> Vec<usize> {
+    let mut starts = vec![0];
+    if infix {
+        let mut after_whitespace = false;
+        for (i, c) in surface_form.char_indices() {
+            if after_whitespace && !c.is_whitespace() {
+                starts.push(i);
+            }
+            after_whitespace = c.is_whitespace();
+        }
+    }
+    starts
+}
+
+fn output_cost(output: &Option<PositiveIntOutput>) -> u64 {
+    output.as_ref().map_or(0, |o| o.value())
+}
+
+/// A path of the FST from the root, ordered by cost then key.
+struct Path {
+    cost: u64,
+    key: Vec<u8>,
+    arc: FstArc<PositiveIntOutput>,
+}
+
+impl PartialEq for Path {
+    fn eq(&self, other: &Path) -> bool {
+        self.cost == other.cost && self.key == other.key
+    }
+}
+
+impl Eq for Path {}
+
+impl PartialOrd for Path {
+    fn partial_cmp(&self, other: &Path) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Path {
+    fn cmp(&self, other: &Path) -> Ordering {
+        self.cost
+            .cmp(&other.cost)
+            .then_with(|| self.key.cmp(&other.key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(surface_form: &str, weight: i64) -> (String, i64, Vec<u8>) {
+        (surface_form.to_string(), weight, vec![])
+    }
+
+    fn lookup(suggester: &Suggester, prefix: &str, num: usize) -> Vec<String> {
+        suggester
+            .lookup(prefix, num)
+            .unwrap()
+            .into_iter()
+            .map(|s| s.surface_form.clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_lookup() {
+        let entries = || {
+            vec![
+                ("new york".to_string(), 10, b"ny".to_vec()),
+                entry("new jersey", 5),
+                entry("newark", 8),
+                entry("york", 3),
+                entry("New Orleans", 7),
+                entry("boston", 20),
+                entry("york new york", 1),
+            ]
+        };
+        let suggester = Suggester::build(entries(), false).unwrap();
+        assert_eq!(suggester.len(), 7);
+        assert_eq!(
+            lookup(&suggester, "new", 10),
+            vec!["new york", "newark", "New Orleans", "new jersey"]
+        );
+        assert_eq!(lookup(&suggester, "NEW", 2), vec!["new york", "newark"]);
+        let results = suggester.lookup("new y", 10).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].payload, b"ny".to_vec());
+        assert_eq!(lookup(&suggester, "yo", 10), vec!["york", "york new york"]);
+        assert_eq!(lookup(&suggester, "", 1), vec!["boston"]);
+        assert!(lookup(&suggester, "x", 10).is_empty());
+
+        // each entry once, though "york new york" has two words matching
+        let suggester = Suggester::build(entries(), true).unwrap();
+        assert_eq!(
+            lookup(&suggester, "yo", 10),
+            vec!["new york", "york", "york new york"]
+        );
+        assert_eq!(lookup(&suggester, "or", 10), vec!["New Orleans"]);
+
+        assert!(Suggester::build(vec![entry("a", -1)], false).is_err());
+        assert!(Suggester::build(vec![entry("a\0b", 1)], false).is_err());
+        let empty = Suggester::build(vec![], true).unwrap();
+        assert!(empty.is_empty());
+        assert!(lookup(&empty, "a", 10).is_empty());
+    }
+
+    #[test]
+    fn test_lookup_many() {
+        let entries: Vec<(String, i64, Vec<u8>)> = (0..5000)
+            .map(|i| entry(&format!("term{}", i), (i * 7919) % 1000))
+            .collect();
+        let suggester = Suggester::build(entries.clone(), false).unwrap();
+        for prefix in &["term", "term1", "term42", "term4999"] {
+            let mut expected: Vec<&(String, i64, Vec<u8>)> =
+                entries.iter().filter(|e| e.0.starts_with(*prefix)).collect();
+            expected.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+            let expected: Vec<String> = expected.iter().take(8).map(|e| e.0.clone()).collect();
+            assert_eq!(lookup(&suggester, prefix, 8), expected);
+        }
+    }
+}
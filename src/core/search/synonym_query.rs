@@ -0,0 +1,413 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use error::ErrorKind::IllegalArgument;
+use error::Result;
+
+use std::collections::HashMap;
+use std::fmt;
+
+use core::codec::{Codec, CodecPostingIterator, CodecTermState};
+use core::index::{LeafReaderContext, Term};
+use core::search::disi::DisiPriorityQueue;
+use core::search::disjunction::DisjunctionScorer;
+use core::search::explanation::Explanation;
+use core::search::posting_iterator::{PostingIterator, PostingIteratorFlags};
+use core::search::searcher::SearchPlanBuilder;
+use core::search::statistics::{CollectionStatistics, TermStatistics};
+use core::search::term_query::TermQuery;
+use core::search::{DocIterator, Query, Scorer, SimScorer, SimWeight, Similarity, Weight};
+use core::util::DocId;
+
+pub const SYNONYM: &str = "synonym";
+
+/// A query that treats several terms of the same field as one, e.g. the
+/// synonyms a term was expanded to at query time.
+///
+/// Documents are scored like by a `TermQuery`, with the sum of the freqs of
+/// all the terms as term freq, and the statistics of a pseudo term whose doc
+/// freq is the max of the terms' doc freqs and whose total term freq is the
+/// sum of theirs. So unlike a disjunction of the terms, a rare synonym
+/// doesn't outweigh a common one.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SynonymQuery {
+    pub terms: Vec<Term>,
+    pub boost: f32,
+}
+
+impl SynonymQuery {
+    pub fn new(mut terms: Vec<Term>, boost: f32) -> Result<SynonymQuery> {
+        if terms.is_empty() {
+            bail!(IllegalArgument("synonym query needs at least one term".into()));
+        }
+        if terms.iter().any(|t| t.field != terms[0].field) {
+            bail!(IllegalArgument("synonyms must be across the same field".into()));
+        }
+        terms.sort_by(|a, b| a.bytes.cmp(&b.bytes));
+        terms.dedup();
+        Ok(SynonymQuery { terms, boost })
+    }
+
+    pub fn field(&self) -> &str {
+        &self.terms[0].field
+    }
+}
+
+impl<C: Codec> Query<C> for SynonymQuery {
+    fn create_weight(
+        &self,
+        searcher: &dyn SearchPlanBuilder<C>,
+        needs_scores: bool,
+    ) -> Result<Box<dyn Weight<C>>> {
+        let max_doc = i64::from(searcher.max_doc());
+        let mut term_states = Vec::with_capacity(self.terms.len());
+        let mut doc_freq = 0i64;
+        let mut total_term_freq = 0i64;
+        for term in &self.terms {
+            let term_context = searcher.term_state(term)?;
+            let term_stats = searcher.term_statistics(term.clone(), term_context.as_ref());
+            doc_freq = doc_freq.max(term_stats.doc_freq);
+            if total_term_freq == -1 || term_stats.total_term_freq == -1 {
+                total_term_freq = -1;
+            } else {
+                total_term_freq += term_stats.total_term_freq;
+            }
+            term_states.push(term_context.term_states());
+        }
+
+        let pseudo_term = self.terms[0].bytes.clone();
+        let (term_stats, collection_stats) = if needs_scores {
+            (
+                TermStatistics::new(pseudo_term, doc_freq, total_term_freq),
+                searcher.collections_statistics(self.field())?,
+            )
+        } else {
+            (
+                TermStatistics::new(pseudo_term, max_doc, -1),
+                CollectionStatistics::new(self.field().to_string(), max_doc, -1, -1, -1),
+            )
+        };
+        let similarity = searcher.similarity(self.field(), needs_scores);
+        let sim_weight =
+            similarity.compute_weight(&collection_stats, &[term_stats], None, self.boost);
+        Ok(Box::new(SynonymWeight {
+            terms: self.terms.clone(),
+            term_states,
+            similarity,
+            sim_weight,
+            needs_scores,
+        }))
+    }
+
+    fn extract_terms(&self) -> Vec<TermQuery> {
+        self.terms
+            .iter()
+            .map(|term| TermQuery::new(term.clone(), self.boost, None))
+            .collect()
+    }
+
+    fn query_type(&self) -> &'static str {
+        SYNONYM
+    }
+
+    fn as_any(&self) -> &::std::any::Any {
+        self
+    }
+}
+
+impl fmt::Display for SynonymQuery {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let terms: Vec<String> = self
+            .terms
+            .iter()
+            .map(|t| t.text().unwrap_or_default())
+            .collect();
+        write!(
+            f,
+            "SynonymQuery(field: {}, terms: [{}], boost: {})",
+            self.field(),
+            terms.join(", "),
+            self.boost
+        )
+    }
+}
+
+struct SynonymWeight<C: Codec> {
+    terms: Vec<Term>,
+    // the term states of `terms[i]` by segment doc base
+    term_states: Vec<HashMap<DocId, CodecTermState<C>>>,
+    similarity: Box<dyn Similarity<C>>,
+    sim_weight: Box<dyn SimWeight<C>>,
+    needs_scores: bool,
+}
+
+impl<C: Codec> SynonymWeight<C> {
+    fn create_postings_iterators(
+        &self,
+        reader: &LeafReaderContext<'_, C>,
+    ) -> Result<Vec<CodecPostingIterator<C>>> {
+        let flags = if self.needs_scores {
+            i32::from(PostingIteratorFlags::FREQS)
+        } else {
+            i32::from(PostingIteratorFlags::NONE)
+        };
+        let mut iterators = Vec::with_capacity(self.terms.len());
+        for (term, term_states) in self.terms.iter().zip(&self.term_states) {
+            if let Some(state) = term_states.get(&reader.doc_base) {
+                if let Some(postings) = reader.reader.postings_from_state(term, state, flags)? {
+                    iterators.push(postings);
+                }
+            }
+        }
+        Ok(iterators)
+    }
+}
+
+impl<C: Codec> Weight<C> for SynonymWeight<C> {
+    fn create_scorer(
+        &self,
+        reader_context: &LeafReaderContext<'_, C>,
+    ) -> Result<Option<Box<dyn Scorer>>> {
+        let postings = self.create_postings_iterators(reader_context)?;
+        if postings.is_empty() {
+            return Ok(None);
+        }
+        let sim_scorer = self.sim_weight.sim_scorer(reader_context.reader)?;
+        Ok(Some(Box::new(SynonymScorer::new(sim_scorer, postings))))
+    }
+
+    fn query_type(&self) -> &'static str {
+        SYNONYM
+    }
+
+    fn normalize(&mut self, norm: f32, boost: f32) {
+        self.sim_weight.normalize(norm, boost)
+    }
+
+    fn value_for_normalization(&self) -> f32 {
+        self.sim_weight.get_value_for_normalization()
+    }
+
+    fn needs_scores(&self) -> bool {
+        self.needs_scores
+    }
+
+    fn explain(&self, reader: &LeafReaderContext<'_, C>, doc: DocId) -> Result<Explanation> {
+        let mut freq = 0;
+        for mut postings in self.create_postings_iterators(reader)? {
+            if postings.advance(doc)? == doc {
+                freq += postings.freq()?;
+            }
+        }
+        if freq > 0 {
+            let freq = freq as f32;
+            let freq_expl = Explanation::new(true, freq, format!("termFreq={}", freq), vec![]);
+            let score_expl = self.sim_weight.explain(reader.reader, doc, freq_expl)?;
+
+            Ok(Explanation::new(
+                true,
+                score_expl.value(),
+                format!(
+                    "weight({} in {}) [{}], result of:",
+                    self, doc, self.similarity
+                ),
+                vec![score_expl],
+            ))
+        } else {
+            Ok(Explanation::new(
+                false,
+                0f32,
+                "no matching term".to_string(),
+                vec![],
+            ))
+        }
+    }
+}
+
+impl<C: Codec> fmt::Display for SynonymWeight<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let terms: Vec<String> = self
+            .terms
+            .iter()
+            .map(|t| t.text().unwrap_or_default())
+            .collect();
+        write!(
+            f,
+            "SynonymWeight(field: {}, terms: [{}], similarity: {}, need_score: {})",
+            &self.terms[0].field,
+            terms.join(", "),
+            &self.similarity,
+            self.needs_scores
+        )
+    }
+}
+
+/// The postings of one of the synonyms, whose "score" is its freq in the
+/// current doc.
+struct SynonymPostings<T: PostingIterator> {
+    postings: T,
+}
+
+impl<T: PostingIterator> Scorer for SynonymPostings<T> {
+    fn score(&mut self) -> Result<f32> {
+        Ok(self.postings.freq()? as f32)
+    }
+}
+
+impl<T: PostingIterator> DocIterator for SynonymPostings<T> {
+    fn doc_id(&self) -> DocId {
+        self.postings.doc_id()
+    }
+
+    fn next(&mut self) -> Result<DocId> {
+        self.postings.next()
+    }
+
+    fn advance(&mut self, target: DocId) -> Result<DocId> {
+        self.postings.advance(target)
+    }
+
+    fn cost(&self) -> usize {
+        self.postings.cost()
+    }
+}
+
+/// Iterates over the union of the synonyms' postings, scoring each doc once
+/// with the summed freq of the synonyms it contains.
+struct SynonymScorer<T: PostingIterator> {
+    sim_scorer: Box<dyn SimScorer>,
+    sub_scorers: DisiPriorityQueue<SynonymPostings<T>>,
+    cost: usize,
+}
+
+impl<T: PostingIterator> SynonymScorer<T> {
+    fn new(sim_scorer: Box<dyn SimScorer>, postings: Vec<T>) -> SynonymScorer<T> {
+        let cost = postings.iter().map(|p| p.cost()).sum();
+        let sub_scorers = postings
+            .into_iter()
+            .map(|postings| SynonymPostings { postings })
+            .collect();
+        SynonymScorer {
+            sim_scorer,
+            sub_scorers: DisiPriorityQueue::new(sub_scorers),
+            cost,
+        }
+    }
+
+    fn freq(&mut self) -> Result<i32> {
+        let mut freq = 0;
+        self.foreach_top_scorer(|scorer| {
+            freq += scorer.inner().postings.freq()?;
+            Ok(true)
+        })?;
+        Ok(freq)
+    }
+}
+
+impl<T: PostingIterator> DisjunctionScorer for SynonymScorer<T> {
+    type Scorer = SynonymPostings<T>;
+
+    fn sub_scorers(&self) -> &DisiPriorityQueue<SynonymPostings<T>> {
+        &self.sub_scorers
+    }
+
+    fn sub_scorers_mut(&mut self) -> &mut DisiPriorityQueue<SynonymPostings<T>> {
+        &mut self.sub_scorers
+    }
+
+    fn two_phase_match_cost(&self) -> f32 {
+        0f32
+    }
+
+    fn get_cost(&self) -> usize {
+        self.cost
+    }
+
+    fn support_two_phase_iter(&self) -> bool {
+        false
+    }
+}
+
+impl<T: PostingIterator> Scorer for SynonymScorer<T> {
+    fn score(&mut self) -> Result<f32> {
+        let doc = self.doc_id();
+        let freq = self.freq()?;
+        self.sim_scorer.score(doc, freq as f32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::codec::CodecEnum;
+    use core::search::spans::tests::MockPostings;
+    use core::search::NO_MORE_DOCS;
+
+    // the postings of a term with the given freq in each doc
+    fn postings(freqs: &[(DocId, i32)]) -> MockPostings {
+        MockPostings::new(
+            freqs
+                .iter()
+                .map(|&(doc, freq)| (doc, (0..freq).collect()))
+                .collect(),
+        )
+    }
+
+    struct FreqSimScorer;
+
+    impl SimScorer for FreqSimScorer {
+        fn score(&mut self, _doc: DocId, freq: f32) -> Result<f32> {
+            Ok(freq)
+        }
+
+        fn compute_slop_factor(&self, _distance: i32) -> f32 {
+            1.0
+        }
+    }
+
+    #[test]
+    fn test_synonym_query_terms() {
+        let term = |text: &str| Term::new("body".into(), text.as_bytes().to_vec());
+        let query =
+            SynonymQuery::new(vec![term("quick"), term("fast"), term("quick")], 2.0).unwrap();
+        assert_eq!(query.terms, vec![term("fast"), term("quick")]);
+
+        let terms = <SynonymQuery as Query<CodecEnum>>::extract_terms(&query);
+        assert_eq!(terms.len(), 2);
+        assert_eq!(terms[1].term, term("quick"));
+        assert!((terms[1].boost - 2.0).abs() < ::std::f32::EPSILON);
+
+        assert!(SynonymQuery::new(vec![], 1.0).is_err());
+        let title = Term::new("title".into(), b"fast".to_vec());
+        assert!(SynonymQuery::new(vec![term("quick"), title], 1.0).is_err());
+    }
+
+    #[test]
+    fn test_synonym_scorer_sums_freqs() {
+        let quick = postings(&[(1, 2), (3, 1), (7, 4)]);
+        let fast = postings(&[(3, 3), (5, 1), (7, 1)]);
+        let speedy = postings(&[(7, 2)]);
+        let mut scorer = SynonymScorer::new(Box::new(FreqSimScorer), vec![quick, fast, speedy]);
+        assert_eq!(scorer.cost(), 7);
+
+        let mut docs = vec![];
+        loop {
+            let doc = scorer.next().unwrap();
+            if doc == NO_MORE_DOCS {
+                break;
+            }
+            docs.push((doc, scorer.score().unwrap() as i32));
+        }
+        assert_eq!(docs, vec![(1, 2), (3, 4), (5, 1), (7, 7)]);
+    }
+}
@@ -17,7 +17,7 @@ use std::collections::HashMap;
 use std::fmt;
 
 use core::codec::{Codec, CodecPostingIterator, CodecTermState};
-use core::index::{LeafReaderContext, Term};
+use core::index::{LeafReaderContext, Term, TermIterator, Terms};
 use core::search::explanation::Explanation;
 use core::search::posting_iterator::{PostingIterator, PostingIteratorFlags};
 use core::search::searcher::SearchPlanBuilder;
@@ -158,9 +158,24 @@ impl<C: Codec> Weight<C> for TermWeight<C> {
             PostingIteratorFlags::NONE
         };
 
-        if let Some(postings) = self.create_postings_iterator(reader_context, i32::from(flags))? {
+        let state = match self.term_states.get(&reader_context.doc_base) {
+            Some(state) => state,
+            None => return Ok(None),
+        };
+        if let Some(terms) = reader_context.reader.terms(&self.term.field)? {
+            let mut terms_iter = terms.iterator()?;
+            terms_iter.seek_exact_state(&self.term.bytes, state)?;
+            // each of the other docs of the term holds at least one occurrence
+            let total_term_freq = terms_iter.total_term_freq()?;
+            let max_freq = if total_term_freq < 0 {
+                // the freqs are not indexed
+                1
+            } else {
+                (total_term_freq - i64::from(terms_iter.doc_freq()?) + 1) as i32
+            };
+            let postings = terms_iter.postings_with_flags(flags)?;
             Ok(Some(Box::new(TermScorer::new(
-                sim_scorer, postings, self.boost,
+                sim_scorer, postings, self.boost, max_freq,
             ))))
         } else {
             Ok(None)
@@ -15,6 +15,7 @@ use core::search::posting_iterator::PostingIterator;
 use core::search::DocIterator;
 use core::search::Scorer;
 use core::search::SimScorer;
+use core::search::NO_MORE_DOCS;
 use core::util::DocId;
 use error::Result;
 
@@ -22,14 +23,26 @@ pub struct TermScorer<T: PostingIterator> {
     sim_scorer: Box<dyn SimScorer>,
     postings_iterator: T,
     boost: f32,
+    // the bound of the scores of the segment, from the highest freq the term
+    // may have in a doc
+    max_score: f32,
 }
 
 impl<T: PostingIterator> TermScorer<T> {
-    pub fn new(sim_scorer: Box<dyn SimScorer>, postings_iterator: T, boost: f32) -> Self {
+    /// `max_freq` is an upper bound of the freq of the term in the docs of
+    /// the segment.
+    pub fn new(
+        sim_scorer: Box<dyn SimScorer>,
+        postings_iterator: T,
+        boost: f32,
+        max_freq: i32,
+    ) -> Self {
+        let max_score = sim_scorer.max_score_with_freq(max_freq as f32);
         TermScorer {
             sim_scorer,
             postings_iterator,
             boost,
+            max_score,
         }
     }
 
@@ -49,6 +62,30 @@ impl<T: PostingIterator> Scorer for TermScorer<T> {
         self.boost;
         Ok(self.sim_scorer.score(doc_id, freq as f32)?)
     }
+
+    fn max_score(&mut self, up_to: DocId) -> Result<f32> {
+        // the narrowest range of the postings' impacts covering `up_to`
+        let mut level = 0;
+        while let Some((doc_id_up_to, impacts)) = self.postings_iterator.impacts(level) {
+            if doc_id_up_to >= up_to {
+                let sim_scorer = &self.sim_scorer;
+                return Ok(impacts
+                    .iter()
+                    .map(|i| sim_scorer.max_score_with_freq_and_norm(i.freq as f32, i.norm))
+                    .fold(0.0, f32::max));
+            }
+            level += 1;
+        }
+        Ok(self.max_score)
+    }
+
+    fn advance_shallow(&mut self, target: DocId) -> Result<DocId> {
+        self.postings_iterator.advance_shallow(target)?;
+        match self.postings_iterator.impacts(0) {
+            Some((doc_id_up_to, _)) => Ok(doc_id_up_to),
+            None => Ok(NO_MORE_DOCS),
+        }
+    }
 }
 
 impl<T: PostingIterator> DocIterator for TermScorer<T> {
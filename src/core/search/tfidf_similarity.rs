@@ -0,0 +1,332 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use error::Result;
+use std::fmt;
+
+use core::codec::Codec;
+use core::index::field_info::FieldInvertState;
+use core::index::{NumericDocValues, SearchLeafReader};
+use core::search::explanation::Explanation;
+use core::search::statistics::{CollectionStatistics, TermStatistics};
+use core::search::{SimScorer, SimWeight, Similarity};
+use core::util::small_float::SmallFloat;
+use core::util::{DocId, KeyedContext};
+
+lazy_static! {
+    static ref NORM_TABLE: [f32; 256] = {
+        let mut norm_table: [f32; 256] = [0f32; 256];
+        for (i, norm) in norm_table.iter_mut().enumerate() {
+            *norm = SmallFloat::byte315_to_float(i as u8);
+        }
+        norm_table
+    };
+}
+
+/// The classic vector space model similarity of Lucene, also known as
+/// `ClassicSimilarity`, where:
+///
+/// - `tf = sqrt(freq)`
+/// - `idf = 1 + ln(docCount / (docFreq + 1))`
+/// - the norm is `boost / sqrt(numTerms)`, encoded into a single byte with
+/// `SmallFloat::float_to_byte315`.
+///
+/// The norms are encoded exactly like `BM25Similarity` does at index time, so
+/// both similarities can score the same index.
+///
+/// The score of a doc is `tf * idf^2 * boost * norm * queryNorm`, `queryNorm`
+/// is only applied if the searcher normalizes the weights.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TFIDFSimilarity;
+
+impl TFIDFSimilarity {
+    pub fn new() -> TFIDFSimilarity {
+        TFIDFSimilarity {}
+    }
+
+    pub fn tf(freq: f32) -> f32 {
+        freq.sqrt()
+    }
+
+    pub fn idf(doc_freq: i64, doc_count: i64) -> f32 {
+        ((doc_count as f64 / (doc_freq + 1) as f64).ln() + 1.0) as f32
+    }
+
+    fn sloppy_freq(distance: i32) -> f32 {
+        1.0 / (distance as f32 + 1.0)
+    }
+
+    pub fn compute_norm(state: &FieldInvertState) -> i64 {
+        let num_terms = state.length - state.num_overlap;
+        TFIDFSimilarity::encode_norm_value(state.boost, num_terms) as i64
+    }
+
+    pub fn encode_norm_value(boost: f32, field_length: i32) -> u8 {
+        SmallFloat::float_to_byte315(boost / (field_length as f32).sqrt())
+    }
+
+    #[inline]
+    fn decode_norm_value(b: usize) -> f32 {
+        NORM_TABLE[b]
+    }
+
+    fn idf_explain(
+        collection_stats: &CollectionStatistics,
+        term_stats: &[TermStatistics],
+    ) -> Explanation {
+        let doc_count = if collection_stats.doc_count == -1 {
+            collection_stats.max_doc
+        } else {
+            collection_stats.doc_count
+        };
+
+        let mut idf_total = 0f32;
+        let mut details: Vec<Explanation> = vec![];
+        for stat in term_stats {
+            let idf = TFIDFSimilarity::idf(stat.doc_freq, doc_count);
+            idf_total += idf;
+            details.push(Explanation::new(
+                true,
+                idf,
+                format!("idf(docFreq={}, docCount={})", stat.doc_freq, doc_count),
+                vec![],
+            ));
+        }
+
+        if details.len() == 1 {
+            details.remove(0)
+        } else {
+            Explanation::new(true, idf_total, "idf(), sum of:".to_string(), details)
+        }
+    }
+}
+
+impl<C: Codec> Similarity<C> for TFIDFSimilarity {
+    fn compute_weight(
+        &self,
+        collection_stats: &CollectionStatistics,
+        term_stats: &[TermStatistics],
+        _context: Option<&KeyedContext>,
+        boost: f32,
+    ) -> Box<dyn SimWeight<C>> {
+        Box::new(TFIDFSimWeight::new(
+            collection_stats.field.clone(),
+            TFIDFSimilarity::idf_explain(collection_stats, term_stats),
+            boost,
+        ))
+    }
+
+    fn query_norm(&self, value_for_normalization: f32, _context: Option<&KeyedContext>) -> f32 {
+        1.0 / value_for_normalization.sqrt()
+    }
+}
+
+impl fmt::Display for TFIDFSimilarity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "TFIDFSimilarity")
+    }
+}
+
+pub struct TFIDFSimScorer {
+    weight_value: f32,
+    norms: Option<Box<dyn NumericDocValues>>,
+}
+
+impl SimScorer for TFIDFSimScorer {
+    fn score(&mut self, doc: DocId, freq: f32) -> Result<f32> {
+        let raw = TFIDFSimilarity::tf(freq) * self.weight_value;
+        if let Some(ref mut norms) = self.norms {
+            let norm = (norms.get(doc)? & 0xFF) as usize;
+            Ok(raw * TFIDFSimilarity::decode_norm_value(norm))
+        } else {
+            Ok(raw)
+        }
+    }
+
+    fn compute_slop_factor(&self, distance: i32) -> f32 {
+        TFIDFSimilarity::sloppy_freq(distance)
+    }
+}
+
+pub struct TFIDFSimWeight {
+    field: String,
+    idf: Explanation,
+    boost: f32,
+    query_norm: f32,
+    query_weight: f32,
+    value: f32,
+}
+
+impl TFIDFSimWeight {
+    fn new(field: String, idf: Explanation, boost: f32) -> TFIDFSimWeight {
+        let mut weight = TFIDFSimWeight {
+            field,
+            idf,
+            boost: 1.0,
+            query_norm: 1.0,
+            query_weight: 0.0,
+            value: 0.0,
+        };
+        weight.do_normalize(1.0, boost);
+        weight
+    }
+
+    fn do_normalize(&mut self, query_norm: f32, boost: f32) {
+        self.boost = boost;
+        self.query_norm = query_norm;
+        self.query_weight = query_norm * boost * self.idf.value();
+        self.value = self.query_weight * self.idf.value();
+    }
+
+    fn explain_score(
+        &self,
+        doc: DocId,
+        freq: Explanation,
+        norms: Option<Box<dyn NumericDocValues>>,
+    ) -> Result<Explanation> {
+        let mut query_subs = vec![];
+        if (self.boost - 1.0f32).abs() > ::std::f32::EPSILON {
+            query_subs.push(Explanation::new(true, self.boost, "boost".to_string(), vec![]));
+        }
+        query_subs.push(self.idf.clone());
+        query_subs.push(Explanation::new(
+            true,
+            self.query_norm,
+            "queryNorm".to_string(),
+            vec![],
+        ));
+        let query_expl = Explanation::new(
+            true,
+            self.boost * self.idf.value() * self.query_norm,
+            "queryWeight, product of:".to_string(),
+            query_subs,
+        );
+
+        let freq_value = freq.value();
+        let tf_expl = Explanation::new(
+            true,
+            TFIDFSimilarity::tf(freq_value),
+            format!("tf(freq={}), with freq of:", freq_value),
+            vec![freq],
+        );
+        let field_norm = match norms {
+            Some(n) => TFIDFSimilarity::decode_norm_value((n.get(doc)? & 0xFF) as usize),
+            None => 1.0f32,
+        };
+        let field_norm_expl = Explanation::new(
+            true,
+            field_norm,
+            format!("fieldNorm(doc={})", doc),
+            vec![],
+        );
+        let field_expl = Explanation::new(
+            true,
+            tf_expl.value() * self.idf.value() * field_norm,
+            format!("fieldWeight in {}, product of:", doc),
+            vec![tf_expl, self.idf.clone(), field_norm_expl],
+        );
+
+        if (query_expl.value() - 1.0f32).abs() < ::std::f32::EPSILON {
+            return Ok(field_expl);
+        }
+        Ok(Explanation::new(
+            true,
+            query_expl.value() * field_expl.value(),
+            format!("score(doc={},freq={}), product of:", doc, freq_value),
+            vec![query_expl, field_expl],
+        ))
+    }
+}
+
+impl<C: Codec> SimWeight<C> for TFIDFSimWeight {
+    fn get_value_for_normalization(&self) -> f32 {
+        self.query_weight * self.query_weight
+    }
+
+    fn normalize(&mut self, query_norm: f32, boost: f32) {
+        self.do_normalize(query_norm, boost)
+    }
+
+    fn sim_scorer(&self, reader: &SearchLeafReader<C>) -> Result<Box<dyn SimScorer>> {
+        let norms = reader.norm_values(&self.field)?;
+        Ok(Box::new(TFIDFSimScorer {
+            weight_value: self.value,
+            norms,
+        }))
+    }
+
+    fn explain(
+        &self,
+        reader: &SearchLeafReader<C>,
+        doc: DocId,
+        freq: Explanation,
+    ) -> Result<Explanation> {
+        let norms = reader.norm_values(&self.field)?;
+        self.explain_score(doc, freq, norms)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::codec::tests::TestCodec;
+    use core::index::tests::MockLeafReader;
+    use core::search::bm25_similarity::BM25Similarity;
+
+    #[test]
+    fn test_tf_idf() {
+        assert!((TFIDFSimilarity::tf(4.0) - 2.0).abs() < ::std::f32::EPSILON);
+        assert!((TFIDFSimilarity::idf(9, 10) - 1.0).abs() < ::std::f32::EPSILON);
+        assert!(
+            (TFIDFSimilarity::idf(1, 20) - (1.0 + 10f32.ln())).abs() < ::std::f32::EPSILON * 4.0
+        );
+    }
+
+    #[test]
+    fn test_norms_compatible_with_bm25() {
+        for len in &[1, 4, 16, 100, 1000] {
+            assert_eq!(
+                TFIDFSimilarity::encode_norm_value(1.0, *len),
+                BM25Similarity::encode_norm_value(1.0, *len)
+            );
+        }
+        let norm = TFIDFSimilarity::encode_norm_value(1.0, 4) as usize;
+        assert!((TFIDFSimilarity::decode_norm_value(norm) - 0.5).abs() < ::std::f32::EPSILON);
+        assert!(TFIDFSimilarity::decode_norm_value(0).abs() < ::std::f32::EPSILON);
+    }
+
+    #[test]
+    fn test_tfidf_similarity() {
+        let collection_stats = CollectionStatistics::new(String::from("world"), 32, 32, 120, -1);
+        let term_stats = vec![TermStatistics::new(Vec::new(), 1, -1)];
+        let sim_weight: Box<dyn SimWeight<TestCodec>> =
+            TFIDFSimilarity::new().compute_weight(&collection_stats, &term_stats, None, 1.0f32);
+
+        // idf = 1 + ln(32 / 2)
+        let idf = 1.0 + 16f32.ln();
+        assert!((sim_weight.get_value_for_normalization() - idf * idf).abs() < 1e-5);
+
+        let leaf_reader = MockLeafReader::new(1);
+        let mut sim_scorer = sim_weight.sim_scorer(&leaf_reader).unwrap();
+
+        // same field length
+        let score1 = sim_scorer.score(1, 100.0).unwrap();
+        let score2 = sim_scorer.score(1, 25.0).unwrap();
+        assert!((score1 - 2.0 * score2).abs() < 1e-5);
+
+        // same term_freq, doc 1 has a shorter field than doc 2
+        let score1 = sim_scorer.score(1, 10.0).unwrap();
+        let score2 = sim_scorer.score(2, 10.0).unwrap();
+        assert!(score1 > score2);
+    }
+}
@@ -45,9 +45,11 @@ impl ScoreDoc {
     }
 }
 
+/// Hits are ordered by descending score, then ascending doc id, so the
+/// greatest hit is the least competitive one.
 impl Ord for ScoreDoc {
     fn cmp(&self, other: &Self) -> Ordering {
-        self.score.partial_cmp(&other.score).unwrap()
+        self.partial_cmp(other).unwrap()
     }
 }
 
@@ -55,13 +57,13 @@ impl PartialOrd for ScoreDoc {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         self.score
             .partial_cmp(&other.score)
-            .map(|ord| ord.reverse())
+            .map(|ord| ord.reverse().then(self.doc.cmp(&other.doc)))
     }
 }
 
 impl PartialEq for ScoreDoc {
     fn eq(&self, other: &Self) -> bool {
-        self == other
+        self.partial_cmp(other) == Some(Ordering::Equal)
     }
 }
 
@@ -0,0 +1,416 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fmt;
+use std::fmt::Display;
+use std::sync::Arc;
+
+use core::codec::Codec;
+use core::index::{
+    DocValuesType, EmptyNumericDocValues, EmptySortedNumericDocValues, LeafReaderContext,
+    NumericDocValuesRef, SortedNumericDocValuesRef,
+};
+use core::search::sort_field::SortedNumericSelectorType;
+use core::util::DocId;
+use error::Result;
+
+/// A source of per doc values, read segment by segment, e.g. to compute
+/// the score of a `FunctionScoreQuery`.
+pub trait ValueSource<C: Codec>: Display + Send + Sync {
+    /// Returns the values of the docs of the given segment.
+    fn get_values(&self, reader: &LeafReaderContext<'_, C>) -> Result<Box<dyn FunctionValues>>;
+}
+
+pub type ValueSourceRef<C> = Arc<dyn ValueSource<C>>;
+
+/// The values of a `ValueSource` for the docs of a segment, docs must be
+/// visited in increasing order.
+pub trait FunctionValues {
+    fn double_val(&mut self, doc: DocId) -> Result<f64>;
+
+    fn float_val(&mut self, doc: DocId) -> Result<f32> {
+        self.double_val(doc).map(|v| v as f32)
+    }
+}
+
+/// A function applied to the raw values of a doc values source.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ValueModifier {
+    None,
+    /// `ln(1 + value)`, negative values are treated as 0
+    Log1p,
+    /// `sqrt(value)`, negative values are treated as 0
+    Sqrt,
+}
+
+impl ValueModifier {
+    pub fn apply(self, value: f64) -> f64 {
+        match self {
+            ValueModifier::None => value,
+            ValueModifier::Log1p => value.max(0.0).ln_1p(),
+            ValueModifier::Sqrt => value.max(0.0).sqrt(),
+        }
+    }
+}
+
+/// A `ValueSource` reading the `NUMERIC` doc values of a field, a doc
+/// without value reads as 0, as do all the docs of a segment where the
+/// field has no numeric doc values.
+pub struct NumericDocValuesSource {
+    field: String,
+    modifier: ValueModifier,
+}
+
+impl NumericDocValuesSource {
+    pub fn new(field: String, modifier: ValueModifier) -> NumericDocValuesSource {
+        NumericDocValuesSource { field, modifier }
+    }
+}
+
+impl<C: Codec> ValueSource<C> for NumericDocValuesSource {
+    fn get_values(&self, reader: &LeafReaderContext<'_, C>) -> Result<Box<dyn FunctionValues>> {
+        let doc_values: NumericDocValuesRef = match reader.reader.field_info(&self.field) {
+            Some(field_info) if field_info.doc_values_type == DocValuesType::Numeric => {
+                reader.reader.get_numeric_doc_values(&self.field)?
+            }
+            _ => Arc::new(EmptyNumericDocValues::default()),
+        };
+        Ok(Box::new(NumericFunctionValues {
+            doc_values,
+            modifier: self.modifier,
+        }))
+    }
+}
+
+impl fmt::Display for NumericDocValuesSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "NumericDocValuesSource(field: {}, modifier: {:?})",
+            &self.field, self.modifier
+        )
+    }
+}
+
+struct NumericFunctionValues {
+    doc_values: NumericDocValuesRef,
+    modifier: ValueModifier,
+}
+
+impl FunctionValues for NumericFunctionValues {
+    fn double_val(&mut self, doc: DocId) -> Result<f64> {
+        let value = self.doc_values.get(doc)?;
+        Ok(self.modifier.apply(value as f64))
+    }
+}
+
+/// A `ValueSource` reading the `SORTED_NUMERIC` doc values of a field, the
+/// selector picks the min or the max of the values of a doc, a doc without
+/// value reads as 0.
+pub struct SortedNumericDocValuesSource {
+    field: String,
+    selector: SortedNumericSelectorType,
+    modifier: ValueModifier,
+}
+
+impl SortedNumericDocValuesSource {
+    pub fn new(
+        field: String,
+        selector: SortedNumericSelectorType,
+        modifier: ValueModifier,
+    ) -> SortedNumericDocValuesSource {
+        SortedNumericDocValuesSource {
+            field,
+            selector,
+            modifier,
+        }
+    }
+}
+
+impl<C: Codec> ValueSource<C> for SortedNumericDocValuesSource {
+    fn get_values(&self, reader: &LeafReaderContext<'_, C>) -> Result<Box<dyn FunctionValues>> {
+        let doc_values: SortedNumericDocValuesRef = match reader.reader.field_info(&self.field) {
+            Some(field_info) if field_info.doc_values_type == DocValuesType::SortedNumeric => {
+                reader.reader.get_sorted_numeric_doc_values(&self.field)?
+            }
+            _ => Arc::new(EmptySortedNumericDocValues),
+        };
+        Ok(Box::new(SortedNumericFunctionValues {
+            doc_values,
+            selector: self.selector,
+            modifier: self.modifier,
+        }))
+    }
+}
+
+impl fmt::Display for SortedNumericDocValuesSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "SortedNumericDocValuesSource(field: {}, selector: {:?}, modifier: {:?})",
+            &self.field, self.selector, self.modifier
+        )
+    }
+}
+
+struct SortedNumericFunctionValues {
+    doc_values: SortedNumericDocValuesRef,
+    selector: SortedNumericSelectorType,
+    modifier: ValueModifier,
+}
+
+impl FunctionValues for SortedNumericFunctionValues {
+    fn double_val(&mut self, doc: DocId) -> Result<f64> {
+        let value = match self.selector {
+            SortedNumericSelectorType::Min => self.doc_values.min(doc)?,
+            SortedNumericSelectorType::Max => self.doc_values.max(doc)?,
+        };
+        Ok(self.modifier.apply(value.unwrap_or(0) as f64))
+    }
+}
+
+/// A `ValueSource` with the same value for all the docs.
+pub struct ConstValueSource {
+    value: f64,
+}
+
+impl ConstValueSource {
+    pub fn new(value: f64) -> ConstValueSource {
+        ConstValueSource { value }
+    }
+}
+
+impl<C: Codec> ValueSource<C> for ConstValueSource {
+    fn get_values(&self, _reader: &LeafReaderContext<'_, C>) -> Result<Box<dyn FunctionValues>> {
+        Ok(Box::new(ConstFunctionValues(self.value)))
+    }
+}
+
+impl fmt::Display for ConstValueSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ConstValueSource({})", self.value)
+    }
+}
+
+struct ConstFunctionValues(f64);
+
+impl FunctionValues for ConstFunctionValues {
+    fn double_val(&mut self, _doc: DocId) -> Result<f64> {
+        Ok(self.0)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum MultiFunction {
+    Sum,
+    Product,
+}
+
+impl MultiFunction {
+    fn name(self) -> &'static str {
+        match self {
+            MultiFunction::Sum => "sum",
+            MultiFunction::Product => "product",
+        }
+    }
+}
+
+/// A `ValueSource` combining the values of several sources.
+pub struct MultiValueSource<C: Codec> {
+    sources: Vec<ValueSourceRef<C>>,
+    function: MultiFunction,
+}
+
+impl<C: Codec> MultiValueSource<C> {
+    /// The sum of the values of `sources`, 0 if empty.
+    pub fn sum(sources: Vec<ValueSourceRef<C>>) -> MultiValueSource<C> {
+        MultiValueSource {
+            sources,
+            function: MultiFunction::Sum,
+        }
+    }
+
+    /// The product of the values of `sources`, 1 if empty.
+    pub fn product(sources: Vec<ValueSourceRef<C>>) -> MultiValueSource<C> {
+        MultiValueSource {
+            sources,
+            function: MultiFunction::Product,
+        }
+    }
+}
+
+impl<C: Codec> ValueSource<C> for MultiValueSource<C> {
+    fn get_values(&self, reader: &LeafReaderContext<'_, C>) -> Result<Box<dyn FunctionValues>> {
+        let mut values = Vec::with_capacity(self.sources.len());
+        for source in &self.sources {
+            values.push(source.get_values(reader)?);
+        }
+        Ok(Box::new(MultiFunctionValues {
+            values,
+            function: self.function,
+        }))
+    }
+}
+
+impl<C: Codec> fmt::Display for MultiValueSource<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let sources: Vec<String> = self.sources.iter().map(|s| format!("{}", s)).collect();
+        write!(f, "{}({})", self.function.name(), sources.join(", "))
+    }
+}
+
+struct MultiFunctionValues {
+    values: Vec<Box<dyn FunctionValues>>,
+    function: MultiFunction,
+}
+
+impl FunctionValues for MultiFunctionValues {
+    fn double_val(&mut self, doc: DocId) -> Result<f64> {
+        let mut result = match self.function {
+            MultiFunction::Sum => 0.0,
+            MultiFunction::Product => 1.0,
+        };
+        for values in &mut self.values {
+            let value = values.double_val(doc)?;
+            match self.function {
+                MultiFunction::Sum => result += value,
+                MultiFunction::Product => result *= value,
+            }
+        }
+        Ok(result)
+    }
+}
+
+/// A `ValueSource` multiplying the values of another source by a constant
+/// factor.
+pub struct ScaleValueSource<C: Codec> {
+    source: ValueSourceRef<C>,
+    scale: f64,
+}
+
+impl<C: Codec> ScaleValueSource<C> {
+    pub fn new(source: ValueSourceRef<C>, scale: f64) -> ScaleValueSource<C> {
+        ScaleValueSource { source, scale }
+    }
+}
+
+impl<C: Codec> ValueSource<C> for ScaleValueSource<C> {
+    fn get_values(&self, reader: &LeafReaderContext<'_, C>) -> Result<Box<dyn FunctionValues>> {
+        Ok(Box::new(ScaleFunctionValues {
+            values: self.source.get_values(reader)?,
+            scale: self.scale,
+        }))
+    }
+}
+
+impl<C: Codec> fmt::Display for ScaleValueSource<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "scale({}, {})", &self.source, self.scale)
+    }
+}
+
+struct ScaleFunctionValues {
+    values: Box<dyn FunctionValues>,
+    scale: f64,
+}
+
+impl FunctionValues for ScaleFunctionValues {
+    fn double_val(&mut self, doc: DocId) -> Result<f64> {
+        Ok(self.values.double_val(doc)? * self.scale)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::codec::CodecEnum;
+    use core::doc::{NumericDocValuesField, SortedNumericDocValuesField};
+    use core::index::{Fieldable, IndexReader, IndexWriter, IndexWriterConfig};
+    use core::store::RAMDirectory;
+
+    fn assert_values<C: Codec>(
+        source: &dyn ValueSource<C>,
+        reader: &LeafReaderContext<'_, C>,
+        expected: &[f64],
+    ) {
+        let mut values = source.get_values(reader).unwrap();
+        for (doc, value) in expected.iter().enumerate() {
+            let actual = values.double_val(doc as DocId).unwrap();
+            assert!((actual - value).abs() < 1e-9, "{} != {}", actual, value);
+        }
+    }
+
+    #[test]
+    fn test_value_sources() {
+        let dir = Arc::new(RAMDirectory::default());
+        let writer = IndexWriter::new(dir, Arc::new(IndexWriterConfig::default())).unwrap();
+        for i in 0..4i64 {
+            let mut doc: Vec<Box<dyn Fieldable>> = vec![
+                Box::new(SortedNumericDocValuesField::new("multi", i)),
+                Box::new(SortedNumericDocValuesField::new("multi", 10 - i)),
+            ];
+            // the last doc has no price
+            if i < 3 {
+                doc.push(Box::new(NumericDocValuesField::new("price", i * 3)));
+            }
+            writer.add_document(doc).unwrap();
+        }
+        let reader = writer.get_reader(true, false).unwrap();
+        let leaves = reader.leaves();
+        assert_eq!(leaves.len(), 1);
+        let leaf = &leaves[0];
+
+        let price: ValueSourceRef<CodecEnum> = Arc::new(NumericDocValuesSource::new(
+            "price".into(),
+            ValueModifier::None,
+        ));
+        assert_values(price.as_ref(), leaf, &[0.0, 3.0, 6.0, 0.0]);
+        let sqrt = NumericDocValuesSource::new("price".into(), ValueModifier::Sqrt);
+        assert_values(&sqrt, leaf, &[0.0, 3f64.sqrt(), 6f64.sqrt(), 0.0]);
+        // a field without numeric doc values reads as 0
+        let missing = NumericDocValuesSource::new("multi".into(), ValueModifier::None);
+        assert_values(&missing, leaf, &[0.0; 4]);
+
+        let min = SortedNumericDocValuesSource::new(
+            "multi".into(),
+            SortedNumericSelectorType::Min,
+            ValueModifier::None,
+        );
+        assert_values(&min, leaf, &[0.0, 1.0, 2.0, 3.0]);
+        let max: ValueSourceRef<CodecEnum> = Arc::new(SortedNumericDocValuesSource::new(
+            "multi".into(),
+            SortedNumericSelectorType::Max,
+            ValueModifier::None,
+        ));
+        assert_values(max.as_ref(), leaf, &[10.0, 9.0, 8.0, 7.0]);
+
+        let one: ValueSourceRef<CodecEnum> = Arc::new(ConstValueSource::new(1.0));
+        assert_values(one.as_ref(), leaf, &[1.0; 4]);
+        let sum = MultiValueSource::sum(vec![Arc::clone(&price), Arc::clone(&max), one]);
+        assert_values(&sum, leaf, &[11.0, 13.0, 15.0, 8.0]);
+        let product = MultiValueSource::product(vec![Arc::clone(&price), max]);
+        assert_values(&product, leaf, &[0.0, 27.0, 48.0, 0.0]);
+        let scale = ScaleValueSource::new(price, 0.5);
+        assert_values(&scale, leaf, &[0.0, 1.5, 3.0, 0.0]);
+
+        writer.close().unwrap();
+    }
+
+    #[test]
+    fn test_value_modifier() {
+        assert!((ValueModifier::None.apply(-4.0) + 4.0).abs() < 1e-9);
+        assert!((ValueModifier::Sqrt.apply(16.0) - 4.0).abs() < 1e-9);
+        assert!(ValueModifier::Sqrt.apply(-16.0).abs() < 1e-9);
+        assert!(ValueModifier::Log1p.apply(-3.0).abs() < 1e-9);
+    }
+}
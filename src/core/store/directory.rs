@@ -28,6 +28,15 @@ pub enum IOContext {
     Merge(MergeInfo),
 }
 
+/// The kind of an `IOContext`, without the flush or merge details.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum IOContextType {
+    Read,
+    Default,
+    Flush,
+    Merge,
+}
+
 impl IOContext {
     pub const READ: IOContext = IOContext::Read(false);
     pub const READ_ONCE: IOContext = IOContext::Read(true);
@@ -37,6 +46,15 @@ impl IOContext {
             _ => false,
         }
     }
+
+    pub fn context_type(&self) -> IOContextType {
+        match self {
+            IOContext::Read(_) => IOContextType::Read,
+            IOContext::Default => IOContextType::Default,
+            IOContext::Flush(_) => IOContextType::Flush,
+            IOContext::Merge(_) => IOContextType::Merge,
+        }
+    }
 }
 
 pub trait Directory: fmt::Display {
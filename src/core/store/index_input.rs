@@ -12,9 +12,12 @@
 // limitations under the License.
 
 use core::store::DataInput;
-use core::store::RandomAccessInput;
+use core::store::{RandomAccessInput, RateLimiter};
 use error::Result;
 
+use std::io::{self, Read};
+use std::sync::Arc;
+
 pub trait IndexInput: DataInput + Send + Sync {
     fn clone(&self) -> Result<Box<dyn IndexInput>>;
 
@@ -37,3 +40,85 @@ pub trait IndexInput: DataInput + Send + Sync {
         false
     }
 }
+
+/// An `IndexInput` pausing on the given `RateLimiter` as the bytes are read.
+pub struct RateLimitIndexInput<RL: RateLimiter + ?Sized + 'static> {
+    delegate: Box<dyn IndexInput>,
+    rate_limiter: Arc<RL>,
+    /// How many bytes we've read since we last called rateLimiter.pause.
+    bytes_since_last_pause: usize,
+    current_min_pause_check_bytes: usize,
+}
+
+impl<RL: RateLimiter + ?Sized + 'static> RateLimitIndexInput<RL> {
+    pub fn new(rate_limiter: Arc<RL>, delegate: Box<dyn IndexInput>) -> Self {
+        let current_min_pause_check_bytes = rate_limiter.min_pause_check_bytes() as usize;
+        RateLimitIndexInput {
+            delegate,
+            rate_limiter,
+            bytes_since_last_pause: 0,
+            current_min_pause_check_bytes,
+        }
+    }
+
+    fn check_rate(&mut self) -> Result<()> {
+        if self.bytes_since_last_pause > self.current_min_pause_check_bytes {
+            self.rate_limiter
+                .pause(self.bytes_since_last_pause as u64)?;
+            self.bytes_since_last_pause = 0;
+            self.current_min_pause_check_bytes = self.rate_limiter.min_pause_check_bytes() as usize;
+        }
+        Ok(())
+    }
+}
+
+impl<RL: RateLimiter + ?Sized + 'static> IndexInput for RateLimitIndexInput<RL> {
+    fn clone(&self) -> Result<Box<dyn IndexInput>> {
+        Ok(Box::new(Self::new(
+            Arc::clone(&self.rate_limiter),
+            self.delegate.clone()?,
+        )))
+    }
+
+    fn file_pointer(&self) -> i64 {
+        self.delegate.file_pointer()
+    }
+
+    fn seek(&mut self, pos: i64) -> Result<()> {
+        self.delegate.seek(pos)
+    }
+
+    fn len(&self) -> u64 {
+        self.delegate.len()
+    }
+
+    fn name(&self) -> &str {
+        self.delegate.name()
+    }
+
+    fn random_access_slice(&self, offset: i64, length: i64) -> Result<Box<dyn RandomAccessInput>> {
+        self.delegate.random_access_slice(offset, length)
+    }
+
+    fn slice(&self, description: &str, offset: i64, length: i64) -> Result<Box<dyn IndexInput>> {
+        let slice = self.delegate.slice(description, offset, length)?;
+        Ok(Box::new(Self::new(Arc::clone(&self.rate_limiter), slice)))
+    }
+
+    fn is_buffered(&self) -> bool {
+        self.delegate.is_buffered()
+    }
+}
+
+impl<RL: RateLimiter + ?Sized + 'static> DataInput for RateLimitIndexInput<RL> {}
+
+impl<RL: RateLimiter + ?Sized + 'static> Read for RateLimitIndexInput<RL> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let count = self.delegate.read(buf)?;
+        self.bytes_since_last_pause += count;
+        if let Err(e) = self.check_rate() {
+            return Err(io::Error::new(io::ErrorKind::Other, e.to_string()));
+        }
+        Ok(count)
+    }
+}
@@ -13,7 +13,8 @@
 
 use core::store::Directory;
 
-use error::{ErrorKind::AlreadyClosed, Result};
+use error::ErrorKind::{AlreadyClosed, IllegalState};
+use error::Result;
 
 use std::collections::HashSet;
 use std::fs;
@@ -172,3 +173,56 @@ impl LockFactory for NativeFSLockFactory {
         ))
     }
 }
+
+/// A lock only valid inside a single process, obtained from a
+/// `SingleInstanceLockFactory`.
+pub struct SingleInstanceLock {
+    lock_name: String,
+    locks: Arc<Mutex<HashSet<String>>>,
+}
+
+impl Lock for SingleInstanceLock {
+    fn close(&self) -> Result<()> {
+        if !self.locks.lock()?.remove(&self.lock_name) {
+            bail!(AlreadyClosed(format!(
+                "Lock was already released: {}",
+                self.lock_name
+            )));
+        }
+        Ok(())
+    }
+
+    fn ensure_valid(&self) -> Result<()> {
+        if !self.locks.lock()?.contains(&self.lock_name) {
+            bail!(AlreadyClosed(format!(
+                "Lock instance was invalidated from map: {}",
+                self.lock_name
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Implements `LockFactory` for a single in-process instance, meaning all
+/// locking will take place through this one instance. Only use this when
+/// the index is never shared across processes, e.g. for a `RAMDirectory`.
+#[derive(Default)]
+pub struct SingleInstanceLockFactory {
+    locks: Arc<Mutex<HashSet<String>>>,
+}
+
+impl LockFactory for SingleInstanceLockFactory {
+    type LK = SingleInstanceLock;
+    fn obtain_lock<D: Directory>(&self, _dir: &D, lock_name: &str) -> Result<Self::LK> {
+        if !self.locks.lock()?.insert(lock_name.to_string()) {
+            bail!(IllegalState(format!(
+                "lock instance already obtained: {}",
+                lock_name
+            )));
+        }
+        Ok(SingleInstanceLock {
+            lock_name: lock_name.to_string(),
+            locks: Arc::clone(&self.locks),
+        })
+    }
+}
@@ -14,6 +14,8 @@
 use std::collections::hash_map::Entry as HashMapEntry;
 use std::collections::{HashMap, HashSet};
 use std::fmt;
+#[cfg(unix)]
+use std::io;
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex, Weak};
 
@@ -21,7 +23,7 @@ use memmap::Mmap;
 
 use core::store::fs_index_output::FSIndexOutput;
 use core::store::lock::LockFactory;
-use core::store::{Directory, FSDirectory, IOContext};
+use core::store::{Directory, FSDirectory, IOContext, IOContextType};
 use core::store::{IndexInput, MmapIndexInput, ReadOnlySource};
 use error::Result;
 
@@ -105,10 +107,45 @@ impl MmapCache {
     }
 }
 
+/// Access pattern hint given to the kernel for the mapped files, see
+/// `madvise(2)`. This is a no-op on the platforms without `madvise`, and a
+/// failing `madvise` is logged without failing the opening of the input.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum MmapAdvice {
+    Normal,
+    Sequential,
+    Random,
+    WillNeed,
+}
+
+impl MmapAdvice {
+    #[cfg(unix)]
+    fn advise(self, mmap: &Mmap) -> Result<()> {
+        let advice = match self {
+            MmapAdvice::Normal => libc::MADV_NORMAL,
+            MmapAdvice::Sequential => libc::MADV_SEQUENTIAL,
+            MmapAdvice::Random => libc::MADV_RANDOM,
+            MmapAdvice::WillNeed => libc::MADV_WILLNEED,
+        };
+        let ret = unsafe { libc::madvise(mmap.as_ptr() as *mut libc::c_void, mmap.len(), advice) };
+        if ret != 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+        Ok(())
+    }
+
+    #[cfg(not(unix))]
+    fn advise(self, _mmap: &Mmap) -> Result<()> {
+        Ok(())
+    }
+}
+
 pub struct MmapDirectory<LF: LockFactory> {
     directory: FSDirectory<LF>,
     pub preload: bool,
     mmap_cache: Arc<Mutex<MmapCache>>,
+    advice: Option<MmapAdvice>,
+    context_advice: HashMap<IOContextType, MmapAdvice>,
 }
 
 impl<LF: LockFactory> MmapDirectory<LF> {
@@ -122,8 +159,32 @@ impl<LF: LockFactory> MmapDirectory<LF> {
             directory,
             preload: false,
             mmap_cache: Arc::new(Mutex::new(MmapCache::default())),
+            advice: None,
+            context_advice: HashMap::new(),
         })
     }
+
+    /// Sets the advice given for the files opened under any `IOContext`
+    /// without a specific advice. No advice is given by default.
+    pub fn set_advice(&mut self, advice: MmapAdvice) {
+        self.advice = Some(advice);
+    }
+
+    /// Sets the advice given for the files opened under the given kind of
+    /// `IOContext`, e.g. `Sequential` for the merges.
+    ///
+    /// NOTE: a file is mapped once and shared by all its inputs, so the
+    /// advice of the last opened input applies to all of them.
+    pub fn set_context_advice(&mut self, context: IOContextType, advice: MmapAdvice) {
+        self.context_advice.insert(context, advice);
+    }
+
+    fn advice(&self, ctx: &IOContext) -> Option<MmapAdvice> {
+        self.context_advice
+            .get(&ctx.context_type())
+            .cloned()
+            .or(self.advice)
+    }
 }
 
 impl<LF: LockFactory> Directory for MmapDirectory<LF> {
@@ -143,11 +204,17 @@ impl<LF: LockFactory> Directory for MmapDirectory<LF> {
         self.directory.create_output(name, context)
     }
 
-    fn open_input(&self, name: &str, _ctx: &IOContext) -> Result<Box<dyn IndexInput>> {
+    fn open_input(&self, name: &str, ctx: &IOContext) -> Result<Box<dyn IndexInput>> {
         let full_path = self.directory.resolve(name);
         let mut mmap_cache = self.mmap_cache.lock()?;
-        let boxed = mmap_cache
-            .get_mmap(&full_path)?
+        let mmap = mmap_cache.get_mmap(&full_path)?;
+        if let (Some(mmap), Some(advice)) = (mmap.as_ref(), self.advice(ctx)) {
+            // the advice is only a hint, reading works without it
+            if let Err(e) = advice.advise(mmap) {
+                warn!("MmapDirectory: madvise {:?} of '{}' failed: {:?}", advice, name, e);
+            }
+        }
+        let boxed = mmap
             .map(ReadOnlySource::from)
             .map(MmapIndexInput::from)
             .unwrap();
@@ -197,3 +264,31 @@ impl<LF: LockFactory> fmt::Display for MmapDirectory<LF> {
         write!(f, "MmapDirectory({})", self.directory)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::store::tests::TempDir;
+    use core::store::{DataInput, DataOutput, MergeInfo, NativeFSLockFactory};
+
+    #[test]
+    fn test_mmap_directory_advice() {
+        let temp_dir = TempDir::new("rucene_mmap_advice");
+        let mut dir =
+            MmapDirectory::new(&temp_dir.path(), NativeFSLockFactory::default(), 0).unwrap();
+        dir.set_advice(MmapAdvice::Random);
+        dir.set_context_advice(IOContextType::Merge, MmapAdvice::Sequential);
+        {
+            let mut out = dir.create_output("test", &IOContext::Default).unwrap();
+            out.write_long(567_890).unwrap();
+        }
+
+        let merge_ctx = IOContext::Merge(MergeInfo::new(1, 8, false, None));
+        assert_eq!(dir.advice(&merge_ctx), Some(MmapAdvice::Sequential));
+        assert_eq!(dir.advice(&IOContext::READ), Some(MmapAdvice::Random));
+        let mut input = dir.open_input("test", &merge_ctx).unwrap();
+        assert_eq!(input.read_long().unwrap(), 567_890);
+        let mut input = dir.open_input("test", &IOContext::READ).unwrap();
+        assert_eq!(input.read_long().unwrap(), 567_890);
+    }
+}
@@ -313,6 +313,24 @@ impl RandomAccessInput for MmapIndexInput {
         Ok((i64::from(RandomAccessInput::read_int(self, pos)?) << 32)
             | (i64::from(RandomAccessInput::read_int(self, pos + 4)?) & 0xffff_ffff))
     }
+
+    fn slice(&self, offset: i64, length: i64) -> Result<Box<dyn RandomAccessInput>> {
+        Ok(Box::new(self.slice_impl("RandomAccessSlice", offset, length)?))
+    }
+
+    fn read_bytes(&self, pos: i64, buf: &mut [u8]) -> Result<()> {
+        if pos < 0 || pos as u64 + buf.len() as u64 > self.len() {
+            bail!(IllegalArgument(format!(
+                "invalid range, expecting 0 < pos + len <= {}, got: ({}, {})",
+                self.len(),
+                pos,
+                buf.len()
+            )));
+        }
+        let start = pos as usize;
+        buf.copy_from_slice(&self.slice[start..start + buf.len()]);
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -337,7 +355,7 @@ mod tests {
         fsout.flush().unwrap();
 
         let mmap_input = MmapIndexInput::new(name).unwrap();
-        let mut slice = mmap_input.slice("from3", 3, 13).unwrap();
+        let mut slice = IndexInput::slice(&mmap_input, "from3", 3, 13).unwrap();
         assert_eq!(slice.read_long().unwrap(), 567_890_i64);
         assert_eq!(slice.read_int().unwrap(), 1_234_567_i32);
         assert!(slice.read_int().is_err());
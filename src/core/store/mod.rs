@@ -77,9 +77,15 @@ pub use self::tracking_directory_wrapper::*;
 mod ram_output;
 pub use self::ram_output::*;
 
+mod ram_directory;
+pub use self::ram_directory::*;
+
 mod rate_limiter;
 pub use self::rate_limiter::*;
 
+mod rate_limited_directory_wrapper;
+pub use self::rate_limited_directory_wrapper::*;
+
 #[derive(PartialEq, Eq, Hash, Clone, Copy)]
 pub struct FlushInfo {
     num_docs: u32,
@@ -118,3 +124,43 @@ impl MergeInfo {
         }
     }
 }
+
+#[cfg(test)]
+pub mod tests {
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use std::process;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static TEMP_DIR_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    /// A unique directory under the system temp dir, removed with its content
+    /// on drop, even if the test panics.
+    pub struct TempDir {
+        path: PathBuf,
+    }
+
+    impl TempDir {
+        pub fn new(prefix: &str) -> TempDir {
+            let name = format!(
+                "{}_{}_{}",
+                prefix,
+                process::id(),
+                TEMP_DIR_COUNTER.fetch_add(1, Ordering::SeqCst)
+            );
+            TempDir {
+                path: ::std::env::temp_dir().join(name),
+            }
+        }
+
+        pub fn path(&self) -> &Path {
+            &self.path
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.path);
+        }
+    }
+}
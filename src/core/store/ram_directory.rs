@@ -0,0 +1,511 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::index::segment_file_name;
+use core::store::{DataInput, DataOutput, Directory, IOContext};
+use core::store::{IndexInput, IndexOutput, RandomAccessInput};
+use core::store::{LockFactory, SingleInstanceLockFactory};
+use core::util::to_base36;
+use error::ErrorKind::IllegalArgument;
+use error::Result;
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::mem;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
+
+use flate2::Crc;
+
+type RAMFiles = Arc<RwLock<HashMap<String, Arc<Vec<u8>>>>>;
+
+/// A memory-resident `Directory` implementation, files are kept as byte
+/// buffers and never touch the filesystem.
+///
+/// The content of an output is published when the output is dropped, so
+/// the inputs always read a consistent snapshot of a closed output. A file
+/// deleted while its output is still open is discarded. Files are never
+/// overwritten: creating an output for an existing file fails with an
+/// `AlreadyExists` I/O error.
+pub struct RAMDirectory {
+    files: RAMFiles,
+    next_temp_file_counter: AtomicUsize,
+    lock_factory: SingleInstanceLockFactory,
+}
+
+impl Default for RAMDirectory {
+    fn default() -> Self {
+        RAMDirectory {
+            files: Arc::new(RwLock::new(HashMap::new())),
+            next_temp_file_counter: AtomicUsize::new(0),
+            lock_factory: SingleInstanceLockFactory::default(),
+        }
+    }
+}
+
+impl RAMDirectory {
+    /// Returns the total bytes of all the published files.
+    pub fn ram_bytes_used(&self) -> Result<usize> {
+        Ok(self.files.read()?.values().map(|f| f.len()).sum())
+    }
+
+    fn file(&self, name: &str) -> Result<Arc<Vec<u8>>> {
+        match self.files.read()?.get(name) {
+            Some(file) => Ok(Arc::clone(file)),
+            None => bail!(IllegalArgument(format!("file {} does not exist", name))),
+        }
+    }
+
+    fn new_output(&self, name: String) -> Result<RAMIndexOutput> {
+        {
+            let mut files = self.files.write()?;
+            if files.contains_key(&name) {
+                bail!(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    format!("file {} already exists", name)
+                ));
+            }
+            files.insert(name.clone(), Arc::new(Vec::new()));
+        }
+        Ok(RAMIndexOutput::new(name, Arc::clone(&self.files)))
+    }
+}
+
+impl Directory for RAMDirectory {
+    type LK = <SingleInstanceLockFactory as LockFactory>::LK;
+    type IndexOutput = RAMIndexOutput;
+    type TempOutput = RAMIndexOutput;
+
+    fn list_all(&self) -> Result<Vec<String>> {
+        let mut names: Vec<String> = self.files.read()?.keys().cloned().collect();
+        names.sort();
+        Ok(names)
+    }
+
+    fn file_length(&self, name: &str) -> Result<i64> {
+        Ok(self.file(name)?.len() as i64)
+    }
+
+    fn create_output(&self, name: &str, _context: &IOContext) -> Result<Self::IndexOutput> {
+        self.new_output(name.to_string())
+    }
+
+    fn open_input(&self, name: &str, _ctx: &IOContext) -> Result<Box<dyn IndexInput>> {
+        Ok(Box::new(RAMIndexInput::new(name, self.file(name)?)))
+    }
+
+    fn obtain_lock(&self, name: &str) -> Result<Self::LK> {
+        self.lock_factory.obtain_lock(self, name)
+    }
+
+    fn create_temp_output(
+        &self,
+        prefix: &str,
+        suffix: &str,
+        _ctx: &IOContext,
+    ) -> Result<Self::TempOutput> {
+        loop {
+            let name = segment_file_name(
+                prefix,
+                &format!(
+                    "{}_{}",
+                    suffix,
+                    to_base36(self.next_temp_file_counter.fetch_add(1, Ordering::AcqRel) as u64)
+                ),
+                "tmp",
+            );
+            if !self.files.read()?.contains_key(&name) {
+                return self.new_output(name);
+            }
+        }
+    }
+
+    fn delete_file(&self, name: &str) -> Result<()> {
+        if self.files.write()?.remove(name).is_none() {
+            bail!(IllegalArgument(format!("file {} does not exist", name)));
+        }
+        Ok(())
+    }
+
+    fn sync(&self, _names: &HashSet<String>) -> Result<()> {
+        Ok(())
+    }
+
+    fn sync_meta_data(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn rename(&self, source: &str, dest: &str) -> Result<()> {
+        let mut files = self.files.write()?;
+        match files.remove(source) {
+            Some(file) => {
+                files.insert(dest.to_string(), file);
+                Ok(())
+            }
+            None => bail!(IllegalArgument(format!("file {} does not exist", source))),
+        }
+    }
+}
+
+impl fmt::Display for RAMDirectory {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "RAMDirectory@{:p}", self)
+    }
+}
+
+/// The `IndexOutput` of a `RAMDirectory`, the written bytes are only
+/// visible to the directory once the output is dropped.
+pub struct RAMIndexOutput {
+    name: String,
+    buffer: Vec<u8>,
+    crc: Crc,
+    files: RAMFiles,
+}
+
+impl RAMIndexOutput {
+    fn new(name: String, files: RAMFiles) -> Self {
+        RAMIndexOutput {
+            name,
+            buffer: Vec::new(),
+            crc: Crc::new(),
+            files,
+        }
+    }
+}
+
+impl Drop for RAMIndexOutput {
+    fn drop(&mut self) {
+        let buffer = mem::replace(&mut self.buffer, Vec::new());
+        match self.files.write() {
+            Ok(mut files) => {
+                // the file was deleted or renamed away while being written
+                if let Some(file) = files.get_mut(&self.name) {
+                    *file = Arc::new(buffer);
+                }
+            }
+            Err(e) => error!("Oops, failed to publish {}, errmsg: {}", self.name, e),
+        }
+    }
+}
+
+impl Write for RAMIndexOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        self.crc.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl DataOutput for RAMIndexOutput {}
+
+impl IndexOutput for RAMIndexOutput {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn file_pointer(&self) -> i64 {
+        self.buffer.len() as i64
+    }
+
+    fn checksum(&self) -> Result<i64> {
+        Ok((self.crc.sum() as i64) & 0xffff_ffffi64)
+    }
+}
+
+/// The `IndexInput` of a `RAMDirectory`, reading a snapshot of the file
+/// shared with all its clones and slices.
+#[derive(Clone)]
+pub struct RAMIndexInput {
+    name: String,
+    file: Arc<Vec<u8>>,
+    start: usize,
+    end: usize,
+    position: usize,
+}
+
+impl RAMIndexInput {
+    fn new(name: &str, file: Arc<Vec<u8>>) -> Self {
+        let end = file.len();
+        RAMIndexInput {
+            name: name.to_string(),
+            file,
+            start: 0,
+            end,
+            position: 0,
+        }
+    }
+
+    fn slice_impl(&self, description: &str, offset: i64, length: i64) -> Result<Self> {
+        let total_len = self.len() as i64;
+        if offset < 0 || length < 0 || offset + length > total_len {
+            bail!(IllegalArgument(format!(
+                "Illegal (offset, length) slice: ({}, {}) for file of length: {}",
+                offset, length, total_len
+            )));
+        }
+        let start = self.start + offset as usize;
+        Ok(RAMIndexInput {
+            name: description.to_string(),
+            file: Arc::clone(&self.file),
+            start,
+            end: start + length as usize,
+            position: 0,
+        })
+    }
+}
+
+impl IndexInput for RAMIndexInput {
+    fn clone(&self) -> Result<Box<dyn IndexInput>> {
+        Ok(Box::new(Clone::clone(self)))
+    }
+
+    fn file_pointer(&self) -> i64 {
+        self.position as i64
+    }
+
+    fn seek(&mut self, pos: i64) -> Result<()> {
+        if pos < 0 || pos as u64 > self.len() {
+            bail!(IllegalArgument(format!(
+                "invalid position, expecting 0 < pos < {}, got: {}",
+                self.len(),
+                pos
+            )));
+        }
+        self.position = pos as usize;
+        Ok(())
+    }
+
+    fn len(&self) -> u64 {
+        (self.end - self.start) as u64
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn random_access_slice(&self, offset: i64, length: i64) -> Result<Box<dyn RandomAccessInput>> {
+        Ok(Box::new(self.slice_impl("RandomAccessSlice", offset, length)?))
+    }
+
+    fn slice(&self, description: &str, offset: i64, length: i64) -> Result<Box<dyn IndexInput>> {
+        Ok(Box::new(self.slice_impl(description, offset, length)?))
+    }
+}
+
+impl DataInput for RAMIndexInput {}
+
+impl Read for RAMIndexInput {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut remaining = &self.file[self.start + self.position..self.end];
+        let count = remaining.read(buf)?;
+        self.position += count;
+        Ok(count)
+    }
+}
+
+impl RandomAccessInput for RAMIndexInput {
+    fn read_byte(&self, pos: i64) -> Result<u8> {
+        if pos < 0 || pos as u64 >= self.len() {
+            bail!(IllegalArgument(format!(
+                "invalid position, expecting 0 < pos < {}, got: {}",
+                self.len(),
+                pos
+            )));
+        }
+        Ok(self.file[self.start + pos as usize])
+    }
+
+    fn read_short(&self, pos: i64) -> Result<i16> {
+        Ok(
+            ((i16::from(RandomAccessInput::read_byte(self, pos)?) & 0xff) << 8)
+                | (i16::from(RandomAccessInput::read_byte(self, pos + 1)?) & 0xff),
+        )
+    }
+
+    fn read_int(&self, pos: i64) -> Result<i32> {
+        Ok(
+            ((i32::from(RandomAccessInput::read_byte(self, pos)?) & 0xff) << 24)
+                | ((i32::from(RandomAccessInput::read_byte(self, pos + 1)?) & 0xff) << 16)
+                | ((i32::from(RandomAccessInput::read_byte(self, pos + 2)?) & 0xff) << 8)
+                | (i32::from(RandomAccessInput::read_byte(self, pos + 3)?) & 0xff),
+        )
+    }
+
+    fn read_long(&self, pos: i64) -> Result<i64> {
+        Ok((i64::from(RandomAccessInput::read_int(self, pos)?) << 32)
+            | (i64::from(RandomAccessInput::read_int(self, pos + 4)?) & 0xffff_ffff))
+    }
+
+    fn slice(&self, offset: i64, length: i64) -> Result<Box<dyn RandomAccessInput>> {
+        Ok(Box::new(self.slice_impl("RandomAccessSlice", offset, length)?))
+    }
+
+    fn read_bytes(&self, pos: i64, buf: &mut [u8]) -> Result<()> {
+        if pos < 0 || pos as u64 + buf.len() as u64 > self.len() {
+            bail!(IllegalArgument(format!(
+                "invalid range, expecting 0 < pos + len <= {}, got: ({}, {})",
+                self.len(),
+                pos,
+                buf.len()
+            )));
+        }
+        let start = self.start + pos as usize;
+        buf.copy_from_slice(&self.file[start..start + buf.len()]);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::codec::codec_util;
+    use core::doc::NumericDocValuesField;
+    use core::index::{IndexReader, IndexWriter, IndexWriterConfig};
+    use error::{Error, ErrorKind};
+
+    #[test]
+    fn test_ram_directory_read_write() {
+        let dir = RAMDirectory::default();
+        {
+            let mut out = dir.create_output("test", &IOContext::Default).unwrap();
+            codec_util::write_header(&mut out, "RAMTest", 1).unwrap();
+            out.write_short(0x7F_i16).unwrap();
+            out.write_long(567_890).unwrap();
+            codec_util::write_footer(&mut out).unwrap();
+            // nothing is visible before the output is closed
+            assert_eq!(dir.file_length("test").unwrap(), 0);
+        }
+        assert_eq!(dir.list_all().unwrap(), vec!["test".to_string()]);
+
+        let mut input = dir.open_checksum_input("test", &IOContext::READ).unwrap();
+        codec_util::check_header(&mut input, "RAMTest", 1, 1).unwrap();
+        assert_eq!(input.read_short().unwrap(), 0x7F_i16);
+        assert_eq!(input.read_long().unwrap(), 567_890);
+        codec_util::check_footer(&mut input).unwrap();
+
+        let input = dir.open_input("test", &IOContext::READ).unwrap();
+        // magic, the codec name with its length and the version
+        let header_len = 4 + 1 + 7 + 4;
+        let random_input = input.random_access_slice(header_len, 10).unwrap();
+        assert_eq!(random_input.read_short(0).unwrap(), 0x7F_i16);
+        assert_eq!(random_input.read_long(2).unwrap(), 567_890);
+        assert!(random_input.read_int(8).is_err());
+    }
+
+    #[test]
+    fn test_ram_directory_no_overwrite() {
+        let dir = RAMDirectory::default();
+        {
+            let mut out = dir.create_output("test", &IOContext::Default).unwrap();
+            out.write_long(567_890).unwrap();
+        }
+        match dir.create_output("test", &IOContext::Default) {
+            Err(Error(ErrorKind::IoError(ref e), _)) => {
+                assert_eq!(e.kind(), io::ErrorKind::AlreadyExists)
+            }
+            _ => panic!("an existing file must not be overwritten"),
+        }
+        let mut input = dir.open_input("test", &IOContext::READ).unwrap();
+        assert_eq!(input.read_long().unwrap(), 567_890);
+
+        // the file can be created again once deleted
+        dir.delete_file("test").unwrap();
+        assert!(dir.create_output("test", &IOContext::Default).is_ok());
+    }
+
+    #[test]
+    fn test_ram_random_access_input() {
+        let dir = RAMDirectory::default();
+        {
+            let mut out = dir.create_output("test", &IOContext::Default).unwrap();
+            out.write_byte(b'a').unwrap();
+            out.write_int(1.5f32.to_bits() as i32).unwrap();
+            out.write_long((-0.25f64).to_bits() as i64).unwrap();
+            out.write_bytes(b"rucene", 0, 6).unwrap();
+        }
+        let input = dir.open_input("test", &IOContext::READ).unwrap();
+        let random_input = input.random_access_slice(1, 18).unwrap();
+        assert_eq!(random_input.read_float(0).unwrap(), 1.5);
+        assert_eq!(random_input.read_double(4).unwrap(), -0.25);
+        let mut buf = [0u8; 6];
+        random_input.read_bytes(12, &mut buf).unwrap();
+        assert_eq!(&buf, b"rucene");
+        assert!(random_input.read_bytes(13, &mut buf).is_err());
+
+        // positions of a slice are relative to its offset
+        let slice = random_input.slice(12, 6).unwrap();
+        assert_eq!(slice.read_byte(0).unwrap(), b'r');
+        assert_eq!(slice.read_byte(5).unwrap(), b'e');
+        assert!(slice.read_byte(6).is_err());
+        let slice = slice.slice(2, 2).unwrap();
+        assert_eq!(slice.read_short(0).unwrap(), i16::from_be_bytes(*b"ce"));
+        assert!(slice.read_short(1).is_err());
+        assert!(random_input.slice(12, 7).is_err());
+    }
+
+    #[test]
+    fn test_ram_directory_snapshot() {
+        let dir = RAMDirectory::default();
+        {
+            let mut out = dir.create_output("a", &IOContext::Default).unwrap();
+            out.write_int(1).unwrap();
+        }
+        let mut input = dir.open_input("a", &IOContext::READ).unwrap();
+        {
+            let mut out = dir.create_output("a", &IOContext::Default).unwrap();
+            out.write_long(2).unwrap();
+        }
+        // the opened input still reads the old content
+        assert_eq!(input.len(), 4);
+        assert_eq!(input.read_int().unwrap(), 1);
+        assert_eq!(dir.file_length("a").unwrap(), 8);
+
+        dir.rename("a", "b").unwrap();
+        assert!(dir.open_input("a", &IOContext::READ).is_err());
+        assert_eq!(dir.file_length("b").unwrap(), 8);
+
+        // an output deleted before it is closed is discarded
+        let mut out = dir.create_output("c", &IOContext::Default).unwrap();
+        out.write_int(3).unwrap();
+        dir.delete_file("c").unwrap();
+        drop(out);
+        assert_eq!(dir.list_all().unwrap(), vec!["b".to_string()]);
+
+        dir.delete_file("b").unwrap();
+        assert!(dir.delete_file("b").is_err());
+        assert!(dir.list_all().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_index_writer_on_ram_directory() {
+        let dir = Arc::new(RAMDirectory::default());
+        let writer = IndexWriter::new(Arc::clone(&dir), Arc::new(IndexWriterConfig::default()))
+            .unwrap();
+        for i in 0..10 {
+            writer
+                .add_document(vec![NumericDocValuesField::new("id", i)])
+                .unwrap();
+        }
+        writer.commit().unwrap();
+        assert!(dir.list_all().unwrap().contains(&"segments_1".to_string()));
+
+        let reader = writer.get_reader(true, false).unwrap();
+        assert_eq!(reader.num_docs(), 10);
+        writer.close().unwrap();
+    }
+}
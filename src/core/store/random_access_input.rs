@@ -18,4 +18,29 @@ pub trait RandomAccessInput: Send + Sync {
     fn read_short(&self, pos: i64) -> Result<i16>;
     fn read_int(&self, pos: i64) -> Result<i32>;
     fn read_long(&self, pos: i64) -> Result<i64>;
+
+    /// Returns a view of `length` bytes starting at `offset`, its positions
+    /// are relative to `offset` and reading outside of it fails.
+    fn slice(&self, offset: i64, length: i64) -> Result<Box<dyn RandomAccessInput>>;
+
+    /// Reads the `f32` whose bits are stored as an int at the given position.
+    fn read_float(&self, pos: i64) -> Result<f32> {
+        Ok(f32::from_bits(self.read_int(pos)? as u32))
+    }
+
+    /// Reads the `f64` whose bits are stored as a long at the given position.
+    fn read_double(&self, pos: i64) -> Result<f64> {
+        Ok(f64::from_bits(self.read_long(pos)? as u64))
+    }
+
+    /// Reads `buf.len()` bytes starting at the given position into `buf`.
+    ///
+    /// The default implementation reads byte by byte, implementations backed
+    /// by a contiguous buffer should copy it at once.
+    fn read_bytes(&self, pos: i64, buf: &mut [u8]) -> Result<()> {
+        for (i, b) in buf.iter_mut().enumerate() {
+            *b = self.read_byte(pos + i as i64)?;
+        }
+        Ok(())
+    }
 }
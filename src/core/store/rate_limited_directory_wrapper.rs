@@ -0,0 +1,195 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::store::{Directory, IOContext, IOContextType, IndexInput};
+use core::store::{RateLimitIndexInput, RateLimitIndexOutput};
+use core::store::{RateLimiter, SimpleRateLimiter};
+use error::Result;
+
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::Arc;
+
+/// A `Directory` wrapper throttling the bytes written, and optionally read,
+/// under a given `IOContextType`, e.g. to keep the merges from saturating
+/// the disk.
+///
+/// The rate can be changed at any time through `set_max_write_mb_per_sec`
+/// or the shared `SimpleRateLimiter`, the outputs already opened pick up
+/// the new rate on their next pause.
+pub struct RateLimitedDirectoryWrapper<D: Directory> {
+    dir: Arc<D>,
+    context: IOContextType,
+    rate_limiter: Arc<SimpleRateLimiter>,
+    // used by the outputs of the other contexts, never pauses
+    unlimited: Arc<SimpleRateLimiter>,
+    throttle_reads: bool,
+}
+
+impl<D: Directory> RateLimitedDirectoryWrapper<D> {
+    pub fn new(dir: Arc<D>, context: IOContextType, rate_limiter: Arc<SimpleRateLimiter>) -> Self {
+        RateLimitedDirectoryWrapper {
+            dir,
+            context,
+            rate_limiter,
+            unlimited: Arc::new(SimpleRateLimiter::unlimited()),
+            throttle_reads: false,
+        }
+    }
+
+    /// Also throttles the inputs opened under the rate limited context.
+    pub fn set_throttle_reads(&mut self, throttle_reads: bool) {
+        self.throttle_reads = throttle_reads;
+    }
+
+    pub fn set_max_write_mb_per_sec(&self, mb_per_sec: f64) -> Result<()> {
+        self.rate_limiter.set_mb_per_sec(mb_per_sec)
+    }
+
+    pub fn max_write_mb_per_sec(&self) -> f64 {
+        self.rate_limiter.mb_per_sec()
+    }
+
+    pub fn rate_limiter(&self) -> &Arc<SimpleRateLimiter> {
+        &self.rate_limiter
+    }
+
+    fn rate_limiter_for(&self, ctx: &IOContext) -> Arc<SimpleRateLimiter> {
+        if ctx.context_type() == self.context {
+            Arc::clone(&self.rate_limiter)
+        } else {
+            Arc::clone(&self.unlimited)
+        }
+    }
+}
+
+impl<D: Directory> Directory for RateLimitedDirectoryWrapper<D> {
+    type LK = D::LK;
+    type IndexOutput = RateLimitIndexOutput<D::IndexOutput, SimpleRateLimiter>;
+    type TempOutput = RateLimitIndexOutput<D::TempOutput, SimpleRateLimiter>;
+
+    fn list_all(&self) -> Result<Vec<String>> {
+        self.dir.list_all()
+    }
+
+    fn file_length(&self, name: &str) -> Result<i64> {
+        self.dir.file_length(name)
+    }
+
+    fn create_output(&self, name: &str, context: &IOContext) -> Result<Self::IndexOutput> {
+        let index_output = self.dir.create_output(name, context)?;
+        Ok(RateLimitIndexOutput::new(
+            self.rate_limiter_for(context),
+            index_output,
+        ))
+    }
+
+    fn open_input(&self, name: &str, ctx: &IOContext) -> Result<Box<dyn IndexInput>> {
+        let input = self.dir.open_input(name, ctx)?;
+        if self.throttle_reads && ctx.context_type() == self.context {
+            Ok(Box::new(RateLimitIndexInput::new(
+                Arc::clone(&self.rate_limiter),
+                input,
+            )))
+        } else {
+            Ok(input)
+        }
+    }
+
+    fn obtain_lock(&self, name: &str) -> Result<Self::LK> {
+        self.dir.obtain_lock(name)
+    }
+
+    fn create_temp_output(
+        &self,
+        prefix: &str,
+        suffix: &str,
+        ctx: &IOContext,
+    ) -> Result<Self::TempOutput> {
+        let temp_output = self.dir.create_temp_output(prefix, suffix, ctx)?;
+        Ok(RateLimitIndexOutput::new(
+            self.rate_limiter_for(ctx),
+            temp_output,
+        ))
+    }
+
+    fn delete_file(&self, name: &str) -> Result<()> {
+        self.dir.delete_file(name)
+    }
+
+    fn sync(&self, name: &HashSet<String>) -> Result<()> {
+        self.dir.sync(name)
+    }
+
+    fn sync_meta_data(&self) -> Result<()> {
+        self.dir.sync_meta_data()
+    }
+
+    fn rename(&self, source: &str, dest: &str) -> Result<()> {
+        self.dir.rename(source, dest)
+    }
+}
+
+impl<D: Directory> fmt::Display for RateLimitedDirectoryWrapper<D> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "RateLimitedDirectoryWrapper({})", self.dir.as_ref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::store::{DataInput, DataOutput, MergeInfo, RAMDirectory};
+    use std::time::{Duration, SystemTime};
+
+    #[test]
+    fn test_rate_limited_directory_wrapper() {
+        let rate_limiter = Arc::new(SimpleRateLimiter::unlimited());
+        let mut dir = RateLimitedDirectoryWrapper::new(
+            Arc::new(RAMDirectory::default()),
+            IOContextType::Merge,
+            Arc::clone(&rate_limiter),
+        );
+        dir.set_throttle_reads(true);
+        let bytes = vec![0u8; 1 << 20];
+        let merge_ctx = IOContext::Merge(MergeInfo::new(1, 1 << 20, false, None));
+
+        assert!(dir.set_max_write_mb_per_sec(0.0).is_err());
+        dir.set_max_write_mb_per_sec(5.0).unwrap();
+        assert_eq!(rate_limiter.mb_per_sec(), 5.0);
+
+        // the other contexts are never throttled
+        assert!(dir
+            .rate_limiter_for(&IOContext::Default)
+            .mb_per_sec()
+            .is_infinite());
+        {
+            let mut out = dir.create_output("flush", &IOContext::Default).unwrap();
+            out.write_bytes(&bytes, 0, bytes.len()).unwrap();
+        }
+
+        // 1MB at 5MB/s is 200ms
+        let start = SystemTime::now();
+        {
+            let mut out = dir.create_output("merge", &merge_ctx).unwrap();
+            out.write_bytes(&bytes, 0, bytes.len()).unwrap();
+        }
+        assert!(start.elapsed().unwrap() >= Duration::from_millis(150));
+
+        let start = SystemTime::now();
+        let mut input = dir.open_input("merge", &merge_ctx).unwrap();
+        let mut read = vec![0u8; bytes.len()];
+        input.read_bytes(&mut read, 0, bytes.len()).unwrap();
+        assert!(start.elapsed().unwrap() >= Duration::from_millis(150));
+    }
+}
@@ -11,9 +11,12 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use error::Result;
+use error::{ErrorKind::IllegalArgument, Result};
 
-use std::sync::Arc;
+use std::f64;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
 use std::time::{Duration, SystemTime};
 
 /// Abstract base class to rate limit IO.  Typically implementations are
@@ -24,7 +27,7 @@ use std::time::{Duration, SystemTime};
 
 pub trait RateLimiter: Sync + Send {
     /// Sets an updated MB per second rate limit.
-    fn set_mb_per_sec(&self, mb_per_sec: f64);
+    fn set_mb_per_sec(&self, mb_per_sec: f64) -> Result<()>;
 
     /// The current MB per second rate limit.
     fn mb_per_sec(&self) -> f64;
@@ -40,8 +43,8 @@ pub trait RateLimiter: Sync + Send {
 }
 
 impl RateLimiter for Arc<RateLimiter> {
-    fn set_mb_per_sec(&self, mb_per_sec: f64) {
-        (**self).set_mb_per_sec(mb_per_sec);
+    fn set_mb_per_sec(&self, mb_per_sec: f64) -> Result<()> {
+        (**self).set_mb_per_sec(mb_per_sec)
     }
 
     fn mb_per_sec(&self) -> f64 {
@@ -57,37 +60,110 @@ impl RateLimiter for Arc<RateLimiter> {
     }
 }
 
+const MIN_PAUSE_CHECK_MSEC: f64 = 5.0;
+
 /// Simple class to rate limit IO.
+///
+/// The rate is kept in an atomic so it can be changed while the outputs
+/// using this limiter are being written.
 pub struct SimpleRateLimiter {
-    _mb_per_sec: f64,
-    _min_pause_check_bytes: u64,
-    _last_ns: SystemTime,
+    // bits of the f64 MB per second rate
+    mb_per_sec: AtomicU64,
+    min_pause_check_bytes: AtomicU64,
+    last_time: Mutex<SystemTime>,
 }
 
 impl SimpleRateLimiter {
-    pub fn new(mb_per_sec: f64) -> Self {
+    pub fn new(mb_per_sec: f64) -> Result<Self> {
+        let limiter = Self::unlimited();
+        limiter.set_mb_per_sec(mb_per_sec)?;
+        Ok(limiter)
+    }
+
+    /// A limiter that never pauses, until a rate is set.
+    pub fn unlimited() -> Self {
         SimpleRateLimiter {
-            _mb_per_sec: mb_per_sec,
-            _min_pause_check_bytes: 0,
-            _last_ns: SystemTime::now(),
+            mb_per_sec: AtomicU64::new(f64::INFINITY.to_bits()),
+            min_pause_check_bytes: AtomicU64::new(u64::max_value()),
+            last_time: Mutex::new(SystemTime::now()),
         }
     }
 }
 
 impl RateLimiter for SimpleRateLimiter {
-    fn set_mb_per_sec(&self, _mb_per_sec: f64) {
-        unimplemented!()
+    fn set_mb_per_sec(&self, mb_per_sec: f64) -> Result<()> {
+        if mb_per_sec.is_nan() || mb_per_sec <= 0.0 {
+            bail!(IllegalArgument(format!(
+                "mb_per_sec must be positive; got: {}",
+                mb_per_sec
+            )));
+        }
+        self.mb_per_sec
+            .store(mb_per_sec.to_bits(), Ordering::Release);
+        let check_value = MIN_PAUSE_CHECK_MSEC / 1000.0 * mb_per_sec * 1024.0 * 1024.0;
+        let check_bytes = if f64::is_infinite(check_value) {
+            u64::max_value()
+        } else {
+            check_value as u64
+        };
+        self.min_pause_check_bytes
+            .store(check_bytes, Ordering::Release);
+        Ok(())
     }
 
     fn mb_per_sec(&self) -> f64 {
-        unimplemented!()
+        f64::from_bits(self.mb_per_sec.load(Ordering::Acquire))
     }
 
-    fn pause(&self, _bytes: u64) -> Result<Duration> {
-        unimplemented!()
+    fn pause(&self, bytes: u64) -> Result<Duration> {
+        let mb_per_sec = self.mb_per_sec();
+        if f64::is_infinite(mb_per_sec) {
+            return Ok(Duration::default());
+        }
+        let seconds_to_pause = bytes as f64 / 1024.0 / 1024.0 / mb_per_sec;
+        let start = SystemTime::now();
+        // reserve our time slot under the lock, but sleep outside of it so
+        // that the concurrent callers are queued one after the other
+        let target_time = {
+            let mut last_time = self.last_time.lock()?;
+            if *last_time < start {
+                *last_time = start;
+            }
+            *last_time += Duration::from_nanos((seconds_to_pause * 1_000_000_000.0) as u64);
+            *last_time
+        };
+        if let Ok(pause_dur) = target_time.duration_since(start) {
+            thread::sleep(pause_dur);
+            Ok(pause_dur)
+        } else {
+            Ok(Duration::default())
+        }
     }
 
     fn min_pause_check_bytes(&self) -> u64 {
-        unimplemented!()
+        self.min_pause_check_bytes.load(Ordering::Acquire)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_simple_rate_limiter() {
+        let limiter = SimpleRateLimiter::new(f64::INFINITY).unwrap();
+        assert_eq!(limiter.min_pause_check_bytes(), u64::max_value());
+        assert_eq!(limiter.pause(1 << 30).unwrap(), Duration::default());
+
+        assert!(SimpleRateLimiter::new(0.0).is_err());
+        assert!(limiter.set_mb_per_sec(-1.0).is_err());
+        assert!(limiter.set_mb_per_sec(::std::f64::NAN).is_err());
+        assert!(limiter.mb_per_sec().is_infinite());
+
+        // 1MB at 10MB/s is 100ms
+        limiter.set_mb_per_sec(10.0).unwrap();
+        assert_eq!(limiter.mb_per_sec(), 10.0);
+        assert_eq!(limiter.min_pause_check_bytes(), 52_428);
+        assert_eq!(limiter.pause(1 << 20).unwrap(), Duration::from_millis(100));
     }
 }
@@ -0,0 +1,692 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::{HashMap, HashSet};
+
+use error::{ErrorKind, Result};
+
+/// Max number of states a regular expression may be determinized to.
+pub const MAX_DETERMINIZED_STATES: usize = 10_000;
+
+// max number of states of the nondeterministic automaton built before
+// determinizing, which bounds the expansion of the repeats
+const MAX_NFA_STATES: usize = 100_000;
+
+const MAX_CHAR: u32 = 0x0010_FFFF;
+const MIN_SURROGATE: u32 = 0xD800;
+const MAX_SURROGATE: u32 = 0xDFFF;
+
+/// A deterministic finite automaton over unicode chars, accepting whole
+/// strings: a regular expression matches a term only if it matches all of it.
+///
+/// The transitions of a state are labeled by sorted, disjoint ranges of
+/// chars, and only lead to states from which an accept state is reachable.
+/// As UTF-8 preserves the order of the chars, the automaton can drive the
+/// seeks of a `TermIterator`, see `next_candidate`.
+#[derive(Debug)]
+pub struct Automaton {
+    // (min, max, dest) per state, the start state is 0
+    transitions: Vec<Vec<(u32, u32, usize)>>,
+    accept: Vec<bool>,
+}
+
+impl Automaton {
+    /// Compiles a regular expression, with the syntax of Lucene's `RegExp`
+    /// but without its optional operators:
+    ///
+    /// * `.` matches any char, `\` escapes the next char,
+    /// * `[abc]`, `[a-z]` and `[^a-z]` match a char in, or not in, a class,
+    /// * `"..."` matches a literal string, `()` the empty string,
+    /// * `(...)` groups, `|` unions and juxtaposition concatenates,
+    /// * `?`, `*`, `+`, `{n}`, `{n,}` and `{n,m}` repeat.
+    ///
+    /// The complement `~`, intersection `&`, any string `@`, empty language
+    /// `#` and numerical interval `<n-m>` operators are rejected, as are the
+    /// expressions determinized to more than `MAX_DETERMINIZED_STATES` and the
+    /// repeats of more than `MAX_DETERMINIZED_STATES` times.
+    pub fn from_regexp(regexp: &str) -> Result<Automaton> {
+        let node = Parser::new(regexp).parse()?;
+        let mut nfa = Nfa::default();
+        let start = nfa.new_state()?;
+        let accept = nfa.build(&node, start)?;
+        nfa.determinize(accept)
+    }
+
+    fn new(transitions: Vec<Vec<(u32, u32, usize)>>, accept: Vec<bool>) -> Automaton {
+        // the states from which an accept state is reachable
+        let mut incoming = vec![vec![]; accept.len()];
+        for (from, trans) in transitions.iter().enumerate() {
+            for &(_, _, to) in trans {
+                incoming[to].push(from);
+            }
+        }
+        let mut live = accept.clone();
+        let mut stack: Vec<usize> = (0..accept.len()).filter(|&s| accept[s]).collect();
+        while let Some(state) = stack.pop() {
+            for &from in &incoming[state] {
+                if !live[from] {
+                    live[from] = true;
+                    stack.push(from);
+                }
+            }
+        }
+
+        let transitions = transitions
+            .into_iter()
+            .map(|trans| {
+                let mut merged: Vec<(u32, u32, usize)> = Vec::with_capacity(trans.len());
+                for (min, max, to) in trans.into_iter().filter(|t| live[t.2]) {
+                    match merged.last_mut() {
+                        Some(last) if last.2 == to && last.1 + 1 == min => last.1 = max,
+                        _ => merged.push((min, max, to)),
+                    }
+                }
+                merged
+            })
+            .collect();
+        let mut automaton = Automaton { transitions, accept };
+        if !live[0] {
+            // matches nothing
+            automaton.transitions = vec![vec![]];
+            automaton.accept = vec![false];
+        }
+        automaton
+    }
+
+    fn step(&self, state: usize, c: u32) -> Option<usize> {
+        let trans = &self.transitions[state];
+        match trans.binary_search_by(|t| t.0.cmp(&c)) {
+            Ok(i) => Some(trans[i].2),
+            Err(0) => None,
+            Err(i) if trans[i - 1].1 >= c => Some(trans[i - 1].2),
+            Err(_) => None,
+        }
+    }
+
+    /// Whether this automaton accepts the whole `term`, which never matches
+    /// if it isn't valid UTF-8.
+    pub fn run(&self, term: &[u8]) -> bool {
+        let term = match ::std::str::from_utf8(term) {
+            Ok(term) => term,
+            Err(_) => return false,
+        };
+        let mut state = 0;
+        for c in term.chars() {
+            match self.step(state, c as u32) {
+                Some(next) => state = next,
+                None => return false,
+            }
+        }
+        self.accept[state]
+    }
+
+    /// Returns a lower bound of the accepted strings, `None` if this
+    /// automaton accepts nothing.
+    pub fn first_candidate(&self) -> Option<String> {
+        if self.accept[0] {
+            return Some(String::new());
+        }
+        self.transitions[0].first()?;
+        let mut candidate = vec![];
+        self.extend_min(0, &mut candidate);
+        Some(chars_to_string(&candidate))
+    }
+
+    /// Returns a string greater than `term` which is a lower bound of the
+    /// accepted strings greater than `term`, `None` if there is no such
+    /// accepted string. Seeking the terms to the candidates skips the ranges
+    /// of terms which can't match.
+    pub fn next_candidate(&self, term: &str) -> Option<String> {
+        let term: Vec<u32> = term.chars().map(|c| c as u32).collect();
+        let mut states = vec![0];
+        for &c in &term {
+            match self.step(states[states.len() - 1], c) {
+                Some(next) => states.push(next),
+                None => break,
+            }
+        }
+
+        let mut pos = states.len() - 1;
+        if pos == term.len() {
+            // the smallest extension of the term
+            if let Some(&(min, max, to)) = self.transitions[states[pos]].first() {
+                let mut candidate = term.clone();
+                let label = valid_char(min);
+                if label <= max {
+                    candidate.push(label);
+                    self.extend_min(to, &mut candidate);
+                    return Some(chars_to_string(&candidate));
+                }
+            }
+            if pos == 0 {
+                return None;
+            }
+            pos -= 1;
+        }
+
+        // the smallest greater char at the last possible position
+        loop {
+            let c = term[pos];
+            for &(min, max, to) in &self.transitions[states[pos]] {
+                let label = valid_char(min.max(c + 1));
+                if label <= max {
+                    let mut candidate = term[..pos].to_vec();
+                    candidate.push(label);
+                    self.extend_min(to, &mut candidate);
+                    return Some(chars_to_string(&candidate));
+                }
+            }
+            if pos == 0 {
+                return None;
+            }
+            pos -= 1;
+        }
+    }
+
+    /// Appends the smallest chars leading from `state` to an accept state,
+    /// stopping early on loops, which the result is still a lower bound of.
+    fn extend_min(&self, mut state: usize, candidate: &mut Vec<u32>) {
+        let mut visited = HashSet::new();
+        while !self.accept[state] && visited.insert(state) {
+            // a live state which doesn't accept has transitions
+            let (min, max, to) = self.transitions[state][0];
+            let label = valid_char(min);
+            if label > max {
+                break;
+            }
+            candidate.push(label);
+            state = to;
+        }
+    }
+}
+
+/// Skips the surrogates, which are not chars.
+fn valid_char(c: u32) -> u32 {
+    if c >= MIN_SURROGATE && c <= MAX_SURROGATE {
+        MAX_SURROGATE + 1
+    } else {
+        c
+    }
+}
+
+fn chars_to_string(chars: &[u32]) -> String {
+    chars
+        .iter()
+        .map(|&c| ::std::char::from_u32(c).unwrap())
+        .collect()
+}
+
+#[derive(Debug)]
+enum Node {
+    Empty,
+    // sorted, disjoint ranges of chars
+    Chars(Vec<(u32, u32)>),
+    Concat(Vec<Node>),
+    Union(Vec<Node>),
+    Repeat(Box<Node>, usize, Option<usize>),
+}
+
+struct Parser<'a> {
+    regexp: &'a str,
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(regexp: &'a str) -> Parser<'a> {
+        Parser {
+            regexp,
+            chars: regexp.chars().collect(),
+            pos: 0,
+        }
+    }
+
+    fn parse(&mut self) -> Result<Node> {
+        let node = self.parse_union()?;
+        if let Some(c) = self.peek() {
+            return self.error(&format!("unexpected '{}'", c));
+        }
+        Ok(node)
+    }
+
+    fn error<T>(&self, msg: &str) -> Result<T> {
+        bail!(ErrorKind::IllegalArgument(format!(
+            "invalid regexp '{}' at position {}: {}",
+            self.regexp, self.pos, msg
+        )))
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).cloned()
+    }
+
+    fn next_char(&mut self) -> Result<char> {
+        match self.peek() {
+            Some(c) => {
+                self.pos += 1;
+                Ok(c)
+            }
+            None => self.error("unexpected end"),
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<()> {
+        if self.peek() == Some(expected) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            self.error(&format!("expected '{}'", expected))
+        }
+    }
+
+    fn parse_union(&mut self) -> Result<Node> {
+        let mut alternatives = vec![self.parse_concat()?];
+        while self.peek() == Some('|') {
+            self.pos += 1;
+            alternatives.push(self.parse_concat()?);
+        }
+        Ok(if alternatives.len() == 1 {
+            alternatives.pop().unwrap()
+        } else {
+            Node::Union(alternatives)
+        })
+    }
+
+    fn parse_concat(&mut self) -> Result<Node> {
+        let mut nodes = vec![];
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            nodes.push(self.parse_repeat()?);
+        }
+        Ok(match nodes.len() {
+            0 => Node::Empty,
+            1 => nodes.pop().unwrap(),
+            _ => Node::Concat(nodes),
+        })
+    }
+
+    fn parse_repeat(&mut self) -> Result<Node> {
+        let mut node = self.parse_simple()?;
+        loop {
+            let (min, max) = match self.peek() {
+                Some('?') => (0, Some(1)),
+                Some('*') => (0, None),
+                Some('+') => (1, None),
+                Some('{') => {
+                    self.pos += 1;
+                    let min = self.parse_number()?;
+                    let max = if self.peek() == Some(',') {
+                        self.pos += 1;
+                        if self.peek() == Some('}') {
+                            None
+                        } else {
+                            Some(self.parse_number()?)
+                        }
+                    } else {
+                        Some(min)
+                    };
+                    if self.peek() != Some('}') {
+                        return self.error("expected '}'");
+                    }
+                    if max.map_or(false, |max| max < min) {
+                        return self.error("the max repetitions are less than the min");
+                    }
+                    (min, max)
+                }
+                _ => return Ok(node),
+            };
+            self.pos += 1;
+            node = Node::Repeat(Box::new(node), min, max);
+        }
+    }
+
+    fn parse_number(&mut self) -> Result<usize> {
+        let start = self.pos;
+        while self.peek().map_or(false, |c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        let digits: String = self.chars[start..self.pos].iter().collect();
+        match digits.parse() {
+            Ok(n) if n > MAX_DETERMINIZED_STATES => self.error(&format!(
+                "the repetitions exceed the max of {}",
+                MAX_DETERMINIZED_STATES
+            )),
+            Ok(n) => Ok(n),
+            Err(_) => self.error("expected a number"),
+        }
+    }
+
+    fn parse_simple(&mut self) -> Result<Node> {
+        let c = self.next_char()?;
+        match c {
+            '.' => Ok(Node::Chars(vec![(0, MAX_CHAR)])),
+            '[' => self.parse_class(),
+            '(' => {
+                if self.peek() == Some(')') {
+                    self.pos += 1;
+                    return Ok(Node::Empty);
+                }
+                let node = self.parse_union()?;
+                self.expect(')')?;
+                Ok(node)
+            }
+            '"' => {
+                let mut nodes = vec![];
+                loop {
+                    match self.next_char()? {
+                        '"' => break,
+                        c => nodes.push(Node::Chars(vec![(c as u32, c as u32)])),
+                    }
+                }
+                Ok(Node::Concat(nodes))
+            }
+            '\\' => {
+                let c = self.next_char()?;
+                Ok(Node::Chars(vec![(c as u32, c as u32)]))
+            }
+            '?' | '*' | '+' | '{' => {
+                self.pos -= 1;
+                self.error("nothing to repeat")
+            }
+            '~' | '&' | '@' | '#' | '<' | '>' => {
+                self.pos -= 1;
+                self.error(&format!("unsupported operator '{}'", c))
+            }
+            c => Ok(Node::Chars(vec![(c as u32, c as u32)])),
+        }
+    }
+
+    fn parse_class_char(&mut self) -> Result<u32> {
+        match self.next_char()? {
+            '\\' => Ok(self.next_char()? as u32),
+            c => Ok(c as u32),
+        }
+    }
+
+    fn parse_class(&mut self) -> Result<Node> {
+        let negate = self.peek() == Some('^');
+        if negate {
+            self.pos += 1;
+        }
+        let mut ranges = vec![];
+        while self.peek() != Some(']') {
+            let min = self.parse_class_char()?;
+            let max = if self.peek() == Some('-') && self.chars.get(self.pos + 1) != Some(&']') {
+                self.pos += 1;
+                let max = self.parse_class_char()?;
+                if max < min {
+                    return self.error("invalid char range");
+                }
+                max
+            } else {
+                min
+            };
+            ranges.push((min, max));
+        }
+        self.pos += 1;
+        if ranges.is_empty() {
+            return self.error("empty char class");
+        }
+
+        ranges.sort();
+        let mut merged: Vec<(u32, u32)> = vec![];
+        for (min, max) in ranges {
+            match merged.last_mut() {
+                Some(last) if min <= last.1 + 1 => last.1 = last.1.max(max),
+                _ => merged.push((min, max)),
+            }
+        }
+        if negate {
+            let mut complement = vec![];
+            let mut next = 0;
+            for (min, max) in merged {
+                if min > next {
+                    complement.push((next, min - 1));
+                }
+                next = max + 1;
+            }
+            if next <= MAX_CHAR {
+                complement.push((next, MAX_CHAR));
+            }
+            merged = complement;
+        }
+        Ok(Node::Chars(merged))
+    }
+}
+
+/// A nondeterministic automaton with epsilon transitions.
+#[derive(Default)]
+struct Nfa {
+    transitions: Vec<Vec<(u32, u32, usize)>>,
+    epsilons: Vec<Vec<usize>>,
+}
+
+impl Nfa {
+    fn new_state(&mut self) -> Result<usize> {
+        if self.transitions.len() >= MAX_NFA_STATES {
+            bail!(ErrorKind::IllegalArgument(format!(
+                "regexp expands to more than {} states",
+                MAX_NFA_STATES
+            )));
+        }
+        self.transitions.push(vec![]);
+        self.epsilons.push(vec![]);
+        Ok(self.transitions.len() - 1)
+    }
+
+    /// Adds the states matching `node` from `from`, returns the end state.
+    fn build(&mut self, node: &Node, from: usize) -> Result<usize> {
+        Ok(match node {
+            Node::Empty => from,
+            Node::Chars(ranges) => {
+                let to = self.new_state()?;
+                for &(min, max) in ranges {
+                    self.transitions[from].push((min, max, to));
+                }
+                to
+            }
+            Node::Concat(nodes) => {
+                let mut state = from;
+                for node in nodes {
+                    state = self.build(node, state)?;
+                }
+                state
+            }
+            Node::Union(nodes) => {
+                let to = self.new_state()?;
+                for node in nodes {
+                    let start = self.new_state()?;
+                    self.epsilons[from].push(start);
+                    let end = self.build(node, start)?;
+                    self.epsilons[end].push(to);
+                }
+                to
+            }
+            Node::Repeat(node, min, max) => {
+                let mut state = from;
+                for _ in 0..*min {
+                    state = self.build(node, state)?;
+                }
+                match max {
+                    None => {
+                        let start = self.new_state()?;
+                        self.epsilons[state].push(start);
+                        let end = self.build(node, start)?;
+                        self.epsilons[end].push(start);
+                        start
+                    }
+                    Some(max) => {
+                        for _ in *min..*max {
+                            let to = self.new_state()?;
+                            self.epsilons[state].push(to);
+                            let start = self.new_state()?;
+                            self.epsilons[state].push(start);
+                            let end = self.build(node, start)?;
+                            self.epsilons[end].push(to);
+                            state = to;
+                        }
+                        state
+                    }
+                }
+            }
+        })
+    }
+
+    /// The sorted states reachable from `states` by epsilon transitions.
+    fn closure(&self, mut states: Vec<usize>) -> Vec<usize> {
+        let mut seen: HashSet<usize> = states.iter().cloned().collect();
+        let mut stack = states.clone();
+        while let Some(state) = stack.pop() {
+            for &next in &self.epsilons[state] {
+                if seen.insert(next) {
+                    states.push(next);
+                    stack.push(next);
+                }
+            }
+        }
+        states.sort();
+        states.dedup();
+        states
+    }
+
+    fn determinize(&self, accept: usize) -> Result<Automaton> {
+        let start = self.closure(vec![0]);
+        let mut ids = HashMap::new();
+        ids.insert(start.clone(), 0);
+        let mut sets = vec![start];
+        let mut transitions = vec![];
+        let mut accepts = vec![];
+
+        let mut i = 0;
+        while i < sets.len() {
+            let set = sets[i].clone();
+            accepts.push(set.binary_search(&accept).is_ok());
+
+            // the chars at which the reachable states may change
+            let mut points = vec![];
+            for &state in &set {
+                for &(min, max, _) in &self.transitions[state] {
+                    points.push(min);
+                    points.push(max + 1);
+                }
+            }
+            points.sort();
+            points.dedup();
+
+            let mut trans: Vec<(u32, u32, usize)> = vec![];
+            for window in points.windows(2) {
+                let (min, max) = (window[0], window[1] - 1);
+                let targets: Vec<usize> = set
+                    .iter()
+                    .flat_map(|&state| self.transitions[state].iter())
+                    .filter(|t| t.0 <= min && min <= t.1)
+                    .map(|t| t.2)
+                    .collect();
+                if targets.is_empty() {
+                    continue;
+                }
+                let target = self.closure(targets);
+                let id = match ids.get(&target) {
+                    Some(&id) => id,
+                    None => {
+                        if sets.len() >= MAX_DETERMINIZED_STATES {
+                            bail!(ErrorKind::IllegalArgument(format!(
+                                "regexp is too complex, it needs more than {} states",
+                                MAX_DETERMINIZED_STATES
+                            )));
+                        }
+                        sets.push(target.clone());
+                        ids.insert(target, sets.len() - 1);
+                        sets.len() - 1
+                    }
+                };
+                match trans.last_mut() {
+                    Some(last) if last.2 == id && last.1 + 1 == min => last.1 = max,
+                    _ => trans.push((min, max, id)),
+                }
+            }
+            transitions.push(trans);
+            i += 1;
+        }
+        Ok(Automaton::new(transitions, accepts))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(regexp: &str, term: &str) -> bool {
+        Automaton::from_regexp(regexp).unwrap().run(term.as_bytes())
+    }
+
+    #[test]
+    fn test_run() {
+        assert!(matches("abc", "abc"));
+        // anchored on both ends
+        assert!(!matches("abc", "abcd"));
+        assert!(!matches("bc", "abc"));
+        assert!(matches("a.c", "aéc"));
+        assert!(matches("a[b-d]+", "abdc"));
+        assert!(!matches("a[^b-d]", "ac"));
+        assert!(matches("a[^b-d]", "ae"));
+        assert!(matches("(ab|cd)*e?", ""));
+        assert!(matches("(ab|cd)*e?", "abcdabe"));
+        assert!(!matches("(ab|cd)*e?", "abc"));
+        assert!(matches("a{2,3}", "aaa"));
+        assert!(!matches("a{2,3}", "aaaa"));
+        assert!(matches("a{2,}", "aaaa"));
+        assert!(!matches("a{2}", "a"));
+        assert!(matches("\"a.b\"\\*", "a.b*"));
+        assert!(!matches("\"a.b\"", "axb"));
+        assert!(matches("a()b", "ab"));
+        assert!(!Automaton::from_regexp("a.").unwrap().run(&[b'a', 0xFF]));
+    }
+
+    #[test]
+    fn test_invalid_regexp() {
+        for regexp in &["a(b", "a)", "[ab", "[]", "[z-a]", "*a", "a{2,1}", "a~b", "<1-5>", "a\\"] {
+            assert!(Automaton::from_regexp(regexp).is_err(), "{}", regexp);
+        }
+        assert!(Automaton::from_regexp("[a-z]{1,10}[a-z]{1,10}[a-z]{1,10}").is_ok());
+        // huge repeats are rejected before being expanded
+        assert!(Automaton::from_regexp("a{1000000000}").is_err());
+        assert!(Automaton::from_regexp("a{1,1000000000}").is_err());
+        assert!(Automaton::from_regexp("((a{1000}){1000}){1000}").is_err());
+    }
+
+    #[test]
+    fn test_next_candidate() {
+        let automaton = Automaton::from_regexp("a[bc]d|x").unwrap();
+        assert_eq!(automaton.first_candidate(), Some("abd".to_string()));
+        assert_eq!(automaton.next_candidate("a"), Some("abd".to_string()));
+        assert_eq!(automaton.next_candidate("abd"), Some("acd".to_string()));
+        assert_eq!(automaton.next_candidate("abz"), Some("acd".to_string()));
+        assert_eq!(automaton.next_candidate("acd"), Some("x".to_string()));
+        assert_eq!(automaton.next_candidate("b"), Some("x".to_string()));
+        assert_eq!(automaton.next_candidate("x"), None);
+        assert_eq!(automaton.next_candidate("y"), None);
+
+        // loops stop the candidate at a lower bound
+        let automaton = Automaton::from_regexp("ab*c").unwrap();
+        assert_eq!(automaton.first_candidate(), Some("abb".to_string()));
+        assert_eq!(automaton.next_candidate("ab"), Some("abbb".to_string()));
+        assert_eq!(automaton.next_candidate("abc"), Some("ac".to_string()));
+        assert_eq!(automaton.next_candidate("ad"), None);
+
+        let automaton = Automaton::from_regexp("a[^\\x]").unwrap();
+        assert_eq!(automaton.first_candidate(), Some("a\u{0}".to_string()));
+    }
+}
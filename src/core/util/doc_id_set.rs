@@ -11,14 +11,12 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use error::Result;
+use error::{ErrorKind, Result};
 
-use core::search::query_cache::{
-    NotDocIdSet, NotDocIterator, ShortArrayDocIdSet, ShortArrayDocIterator,
-};
 use core::search::{DocIdSet, DocIterator, NO_MORE_DOCS};
-use core::util::bit_set::{FixedBitSet, ImmutableBitSet};
-use core::util::DocId;
+use core::util::bit_set::{BitSet, FixedBitSet, ImmutableBitSet};
+use core::util::bit_util::UnsignedShift;
+use core::util::{Bits, BitsContext, BitsRef, DocId};
 use std::sync::Arc;
 
 pub struct BitDocIdSet<T: ImmutableBitSet> {
@@ -40,15 +38,22 @@ impl<T: ImmutableBitSet> BitDocIdSet<T> {
     }
 }
 
+impl BitDocIdSet<FixedBitSet> {
+    /// Approximate memory usage of this set in bytes.
+    pub fn ram_bytes_used(&self) -> usize {
+        self.set.bits.len() * 8
+    }
+}
+
 impl<T: ImmutableBitSet + 'static> DocIdSet for BitDocIdSet<T> {
     type Iter = BitSetIterator<T>;
     fn iterator(&self) -> Result<Option<Self::Iter>> {
         Ok(Some(BitSetIterator::new(Arc::clone(&self.set), self.cost)?))
     }
 
-    //    fn bits(&self) -> Result<Option<ImmutableBitSetRef>> {
-    //        Ok(Some(Arc::clone(&self.set)))
-    //    }
+    fn bits(&self) -> Result<Option<BitsRef>> {
+        Ok(Some(Arc::clone(&self.set) as BitsRef))
+    }
 }
 
 pub struct BitSetIterator<T: ImmutableBitSet> {
@@ -112,6 +117,15 @@ impl IntArrayDocIdSet {
             length,
         }
     }
+
+    fn contains(&self, doc: DocId) -> bool {
+        self.docs[..self.length].binary_search(&doc).is_ok()
+    }
+
+    /// Approximate memory usage of this set in bytes.
+    pub fn ram_bytes_used(&self) -> usize {
+        self.docs.len() * 4
+    }
 }
 
 impl DocIdSet for IntArrayDocIdSet {
@@ -122,10 +136,6 @@ impl DocIdSet for IntArrayDocIdSet {
             self.length,
         )))
     }
-
-    //    fn bits(&self) -> Result<Option<ImmutableBitSetRef>> {
-    //        Ok(None)
-    //    }
 }
 
 pub struct IntArrayDocIterator {
@@ -184,6 +194,18 @@ pub enum DocIdSetEnum {
     BitDocId(BitDocIdSet<FixedBitSet>),
 }
 
+impl DocIdSetEnum {
+    /// Approximate memory usage of this set in bytes.
+    pub fn ram_bytes_used(&self) -> usize {
+        match self {
+            DocIdSetEnum::ShortArray(s) => s.ram_bytes_used(),
+            DocIdSetEnum::IntArray(s) => s.ram_bytes_used(),
+            DocIdSetEnum::NotDocId(s) => s.set.ram_bytes_used(),
+            DocIdSetEnum::BitDocId(s) => s.ram_bytes_used(),
+        }
+    }
+}
+
 impl DocIdSet for DocIdSetEnum {
     type Iter = DocIdSetDocIterEnum;
     fn iterator(&self) -> Result<Option<Self::Iter>> {
@@ -202,6 +224,15 @@ impl DocIdSet for DocIdSetEnum {
             }
         }
     }
+
+    fn bits(&self) -> Result<Option<BitsRef>> {
+        match self {
+            DocIdSetEnum::BitDocId(s) => s.bits(),
+            DocIdSetEnum::NotDocId(s) => s.bits(),
+            // binary searches aren't the O(1) access `bits` promises
+            DocIdSetEnum::ShortArray(_) | DocIdSetEnum::IntArray(_) => Ok(None),
+        }
+    }
 }
 
 pub enum DocIdSetDocIterEnum {
@@ -300,3 +331,619 @@ impl DocIterator for DocIdSetDocIterEnum {
         }
     }
 }
+
+// Number of documents in a block
+const BLOCK_SIZE: usize = 1 << 16;
+// The maximum length for an array, beyond that point we switch to a bitset
+const MAX_ARRAY_LENGTH: usize = 1 << 12;
+
+/// `DocIdSet` implementation inspired from http://roaringbitmap.org/
+///
+/// The space is divided into blocks of 2^16 bits and each block is encoded
+/// independently. In each block, if less than 2^12 bits are set, then
+/// documents are simply stored in a `u16` array. If more than 2^16-2^12 bits
+/// are set, then the inverse of the set is encoded in a `u16` array.
+/// Otherwise a `FixedBitSet` is used.
+///
+/// Build it with `RoaringDocIdSetBuilder`.
+pub struct RoaringDocIdSet {
+    doc_id_sets: Arc<[Option<DocIdSetEnum>]>,
+    cardinality: usize,
+    max_doc: i32,
+}
+
+impl RoaringDocIdSet {
+    fn new(
+        doc_id_sets: Vec<Option<DocIdSetEnum>>,
+        cardinality: usize,
+        max_doc: i32,
+    ) -> RoaringDocIdSet {
+        RoaringDocIdSet {
+            doc_id_sets: Arc::from(doc_id_sets.into_boxed_slice()),
+            cardinality,
+            max_doc,
+        }
+    }
+
+    /// Number of docs in this set.
+    pub fn cardinality(&self) -> usize {
+        self.cardinality
+    }
+
+    /// Approximate memory usage of this set in bytes.
+    pub fn ram_bytes_used(&self) -> usize {
+        self.doc_id_sets
+            .iter()
+            .map(|set| set.as_ref().map_or(0, DocIdSetEnum::ram_bytes_used))
+            .sum::<usize>()
+            + self.doc_id_sets.len() * ::std::mem::size_of::<Option<DocIdSetEnum>>()
+    }
+}
+
+/// Builds a `RoaringDocIdSet` from doc ids added in increasing order.
+pub struct RoaringDocIdSetBuilder {
+    doc_id_sets: Vec<Option<DocIdSetEnum>>,
+    cardinality: usize,
+
+    max_doc: i32,
+    last_doc_id: DocId,
+    current_block: i32,
+    current_block_cardinality: usize,
+
+    // We start by filling the buffer and when it's full we copy the content of
+    // the buffer to the FixedBitSet and put further documents in that bitset
+    buffer: Vec<u16>,
+    dense_buffer: Option<Box<FixedBitSet>>,
+}
+
+impl RoaringDocIdSetBuilder {
+    pub fn new(max_doc: i32) -> RoaringDocIdSetBuilder {
+        let length = (max_doc + (1 << 16) - 1).unsigned_shift(16);
+        let mut doc_id_sets = Vec::with_capacity(length as usize);
+        for _ in 0..length {
+            doc_id_sets.push(None);
+        }
+
+        RoaringDocIdSetBuilder {
+            doc_id_sets,
+            cardinality: 0,
+            max_doc,
+            last_doc_id: -1,
+            current_block: -1,
+            current_block_cardinality: 0,
+            buffer: vec![0u16; MAX_ARRAY_LENGTH as usize],
+            dense_buffer: None,
+        }
+    }
+
+    fn flush(&mut self) {
+        assert!(self.current_block_cardinality <= BLOCK_SIZE);
+
+        let current_block = self.current_block;
+        let current_block_cardinality = self.current_block_cardinality;
+
+        if current_block_cardinality <= MAX_ARRAY_LENGTH {
+            // Use sparse encoding
+            assert!(self.dense_buffer.is_none());
+            if current_block_cardinality > 0 {
+                let mut docs: Vec<u16> = vec![0u16; current_block_cardinality];
+                docs.copy_from_slice(&self.buffer[0..current_block_cardinality]);
+
+                self.doc_id_sets[current_block as usize] = Some(DocIdSetEnum::ShortArray(
+                    ShortArrayDocIdSet::new(docs, current_block_cardinality),
+                ));
+            }
+        } else {
+            assert!(self.dense_buffer.is_some());
+            assert_eq!(
+                self.dense_buffer.as_mut().unwrap().cardinality(),
+                self.current_block_cardinality
+            );
+
+            if self.dense_buffer.as_mut().unwrap().len() == BLOCK_SIZE as usize
+                && BLOCK_SIZE - self.current_block_cardinality < MAX_ARRAY_LENGTH
+            {
+                let dense_buffer = self.dense_buffer.as_mut().unwrap();
+                // Doc ids are very dense, inverse the encoding
+                let mut exclude_docs =
+                    vec![0u16; (BLOCK_SIZE - self.current_block_cardinality) as usize];
+                let num_bits = dense_buffer.num_bits;
+                dense_buffer.flip(0, num_bits);
+
+                let mut exclude_doc = -1;
+                unsafe {
+                    let ptr = exclude_docs.as_mut_ptr();
+                    for i in 0..exclude_docs.len() {
+                        exclude_doc = dense_buffer.next_set_bit((exclude_doc + 1) as usize);
+                        debug_assert_ne!(exclude_doc, NO_MORE_DOCS);
+                        *ptr.offset(i as isize) = exclude_doc as u16;
+                    }
+                }
+
+                assert!(
+                    exclude_doc as usize + 1 == dense_buffer.len()
+                        || dense_buffer.next_set_bit((exclude_doc + 1) as usize) == NO_MORE_DOCS
+                );
+
+                let length = exclude_docs.len();
+                self.doc_id_sets[self.current_block as usize] =
+                    Some(DocIdSetEnum::NotDocId(NotDocIdSet::new(
+                        ShortArrayDocIdSet::new(exclude_docs, length),
+                        BLOCK_SIZE as i32,
+                    )));
+            } else {
+                // Neither sparse nor super dense, use a fixed bit set
+                let dense_buf = self.dense_buffer.take().unwrap();
+                self.doc_id_sets[self.current_block as usize] =
+                    Some(DocIdSetEnum::BitDocId(BitDocIdSet::new(
+                        Arc::from(dense_buf),
+                        self.current_block_cardinality as usize,
+                    )));
+            }
+        }
+
+        self.cardinality += self.current_block_cardinality;
+        self.dense_buffer = None;
+        self.current_block_cardinality = 0;
+    }
+
+    /// Add a new doc id to this builder.
+    /// NOTE: doc ids must be added in order.
+    pub fn add_doc(&mut self, doc_id: DocId) -> Result<()> {
+        if doc_id <= self.last_doc_id {
+            bail!(ErrorKind::IllegalArgument(format!(
+                "Doc ids must be added in-order, got {} which is <= last_doc_id={}",
+                doc_id, self.last_doc_id
+            )));
+        }
+        if doc_id >= self.max_doc {
+            bail!(ErrorKind::IllegalArgument(format!(
+                "Doc id {} is out of bounds, max_doc={}",
+                doc_id, self.max_doc
+            )));
+        }
+
+        let block = doc_id.unsigned_shift(16);
+        if block != self.current_block {
+            // we went to a different block, let's flush what we buffered and start from fresh
+            self.flush();
+            self.current_block = block;
+        }
+
+        if self.current_block_cardinality < MAX_ARRAY_LENGTH {
+            unsafe {
+                *self
+                    .buffer
+                    .as_mut_ptr()
+                    .offset(self.current_block_cardinality as isize) = doc_id as u16
+            };
+        } else {
+            if self.dense_buffer.is_none() {
+                // the buffer is full, let's move to a fixed bit set
+                let num_bits = (1i32 << 16).min(self.max_doc - (block << 16));
+                let mut fixed_bit_set = Box::new(FixedBitSet::new(num_bits as usize));
+                for doc in &self.buffer {
+                    fixed_bit_set.set(*doc as usize);
+                }
+
+                self.dense_buffer = Some(fixed_bit_set);
+            }
+
+            self.dense_buffer
+                .as_mut()
+                .unwrap()
+                .set((doc_id & 0xFFFF) as usize);
+        }
+
+        self.last_doc_id = doc_id;
+        self.current_block_cardinality += 1;
+
+        Ok(())
+    }
+
+    /// Build the `RoaringDocIdSet` of the added docs.
+    pub fn build(mut self) -> RoaringDocIdSet {
+        self.flush();
+        RoaringDocIdSet::new(self.doc_id_sets, self.cardinality, self.max_doc)
+    }
+}
+
+impl DocIdSet for RoaringDocIdSet {
+    type Iter = RoaringDocIterator;
+    fn iterator(&self) -> Result<Option<Self::Iter>> {
+        if self.cardinality == 0 {
+            Ok(None)
+        } else {
+            Ok(Some(RoaringDocIterator::new(
+                self.doc_id_sets.clone(),
+                self.cardinality,
+            )))
+        }
+    }
+
+    fn bits(&self) -> Result<Option<BitsRef>> {
+        Ok(Some(Arc::new(RoaringBits {
+            doc_id_sets: Arc::clone(&self.doc_id_sets),
+            max_doc: self.max_doc,
+        })))
+    }
+}
+
+/// Random access to the docs of a `RoaringDocIdSet`.
+struct RoaringBits {
+    doc_id_sets: Arc<[Option<DocIdSetEnum>]>,
+    max_doc: i32,
+}
+
+impl Bits for RoaringBits {
+    fn get_with_ctx(&self, ctx: BitsContext, index: usize) -> Result<(bool, BitsContext)> {
+        let doc = index & 0xFFFF;
+        let found = match self.doc_id_sets[index >> 16] {
+            None => false,
+            Some(DocIdSetEnum::ShortArray(ref s)) => s.contains(doc as u16),
+            Some(DocIdSetEnum::NotDocId(ref s)) => !s.set.contains(doc as u16),
+            Some(DocIdSetEnum::BitDocId(ref s)) => s.set.get(doc)?,
+            Some(DocIdSetEnum::IntArray(ref s)) => s.contains(doc as DocId),
+        };
+        Ok((found, ctx))
+    }
+
+    fn len(&self) -> usize {
+        self.max_doc as usize
+    }
+}
+
+/// Iterator over the docs of a `RoaringDocIdSet`.
+pub struct RoaringDocIterator {
+    doc_id_sets: Arc<[Option<DocIdSetEnum>]>,
+    doc: DocId,
+    block: i32,
+    cardinality: usize,
+    sub: Option<DocIdSetDocIterEnum>,
+}
+
+impl RoaringDocIterator {
+    fn new(doc_id_sets: Arc<[Option<DocIdSetEnum>]>, cardinality: usize) -> Self {
+        RoaringDocIterator {
+            doc_id_sets,
+            doc: -1,
+            block: -1,
+            cardinality,
+            // init as stub
+            sub: Some(DocIdSetDocIterEnum::default()),
+        }
+    }
+
+    fn first_doc_from_next_block(&mut self) -> Result<(DocId)> {
+        loop {
+            self.block += 1;
+            if self.block as usize >= self.doc_id_sets.len() {
+                self.sub = None;
+                self.doc = NO_MORE_DOCS;
+
+                return Ok(self.doc);
+            } else if self.doc_id_sets[self.block as usize].is_some() {
+                self.sub = self.doc_id_sets[self.block as usize]
+                    .as_ref()
+                    .unwrap()
+                    .iterator()?;
+                let sub_next = self.sub.as_mut().unwrap().next()?;
+                debug_assert_ne!(sub_next, NO_MORE_DOCS);
+
+                self.doc = (self.block << 16) | sub_next;
+                return Ok(self.doc);
+            }
+        }
+    }
+}
+
+impl DocIterator for RoaringDocIterator {
+    fn doc_id(&self) -> DocId {
+        self.doc
+    }
+
+    fn next(&mut self) -> Result<DocId> {
+        let sub_next = self.sub.as_mut().unwrap().next()?;
+        if sub_next == NO_MORE_DOCS {
+            return self.first_doc_from_next_block();
+        }
+
+        self.doc = (self.block << 16) | sub_next;
+        Ok(self.doc)
+    }
+
+    fn advance(&mut self, target: DocId) -> Result<DocId> {
+        let target_block = target.unsigned_shift(16);
+
+        if target_block != self.block {
+            self.block = target_block;
+            if self.block as usize >= self.doc_id_sets.len() {
+                self.sub = None;
+                self.doc = NO_MORE_DOCS;
+
+                return Ok(self.doc);
+            }
+
+            if self.doc_id_sets[self.block as usize].is_none() {
+                return self.first_doc_from_next_block();
+            }
+
+            self.sub = self.doc_id_sets[self.block as usize]
+                .as_ref()
+                .unwrap()
+                .iterator()?;
+        }
+
+        let sub_next = self.sub.as_mut().unwrap().advance(target & 0xFFFF)?;
+        if sub_next == NO_MORE_DOCS {
+            return self.first_doc_from_next_block();
+        }
+
+        self.doc = (self.block << 16) | sub_next;
+        Ok(self.doc)
+    }
+
+    fn cost(&self) -> usize {
+        self.cardinality as usize
+    }
+}
+
+pub struct ShortArrayDocIdSet {
+    docs: Arc<Vec<u16>>,
+    length: usize,
+}
+
+impl ShortArrayDocIdSet {
+    pub fn new(docs: Vec<u16>, length: usize) -> ShortArrayDocIdSet {
+        ShortArrayDocIdSet {
+            docs: Arc::new(docs),
+            length,
+        }
+    }
+
+    fn contains(&self, doc: u16) -> bool {
+        self.docs[..self.length].binary_search(&doc).is_ok()
+    }
+
+    /// Approximate memory usage of this set in bytes.
+    pub fn ram_bytes_used(&self) -> usize {
+        self.docs.len() * 2
+    }
+}
+
+impl DocIdSet for ShortArrayDocIdSet {
+    type Iter = ShortArrayDocIterator;
+    fn iterator(&self) -> Result<Option<Self::Iter>> {
+        Ok(Some(ShortArrayDocIterator::new(
+            self.docs.clone(),
+            self.length,
+        )))
+    }
+}
+
+pub struct ShortArrayDocIterator {
+    docs: Arc<Vec<u16>>,
+    length: usize,
+    i: i32,
+    doc: DocId,
+}
+
+impl ShortArrayDocIterator {
+    pub fn new(docs: Arc<Vec<u16>>, length: usize) -> ShortArrayDocIterator {
+        ShortArrayDocIterator {
+            docs,
+            length,
+            i: -1,
+            doc: -1,
+        }
+    }
+}
+
+impl DocIterator for ShortArrayDocIterator {
+    fn doc_id(&self) -> DocId {
+        self.doc
+    }
+
+    fn next(&mut self) -> Result<DocId> {
+        self.i += 1;
+        if self.i as usize >= self.length {
+            Ok(NO_MORE_DOCS)
+        } else {
+            self.doc = i32::from(unsafe { *self.docs.as_ptr().offset(self.i as isize) });
+            Ok(self.doc)
+        }
+    }
+
+    fn advance(&mut self, target: DocId) -> Result<DocId> {
+        self.doc = if (self.i + 1) as usize >= self.length {
+            NO_MORE_DOCS
+        } else {
+            let adv = match self.docs[(self.i + 1) as usize..self.length]
+                .binary_search(&(target as u16))
+            {
+                Ok(x) => x,
+                Err(e) => e,
+            };
+            self.i += (adv + 1) as i32;
+            if self.i < self.length as i32 {
+                i32::from(unsafe { *self.docs.as_ptr().offset(self.i as isize) })
+            } else {
+                NO_MORE_DOCS
+            }
+        };
+
+        Ok(self.doc)
+    }
+
+    fn cost(&self) -> usize {
+        self.length
+    }
+}
+
+pub struct NotDocIdSet<T: DocIdSet> {
+    set: T,
+    max_doc: i32,
+}
+
+impl<T: DocIdSet> NotDocIdSet<T> {
+    pub fn new(set: T, max_doc: i32) -> NotDocIdSet<T> {
+        NotDocIdSet { set, max_doc }
+    }
+}
+
+impl<T: DocIdSet> DocIdSet for NotDocIdSet<T> {
+    type Iter = NotDocIterator<T::Iter>;
+    fn iterator(&self) -> Result<Option<Self::Iter>> {
+        match self.set.iterator()? {
+            Some(iter) => Ok(Some(NotDocIterator::new(iter, self.max_doc))),
+            _ => Ok(None),
+        }
+    }
+
+    fn bits(&self) -> Result<Option<BitsRef>> {
+        match self.set.bits()? {
+            Some(bits) => Ok(Some(Arc::new(NotBits {
+                bits,
+                max_doc: self.max_doc,
+            }))),
+            None => Ok(None),
+        }
+    }
+}
+
+/// The negation of the random-access bits of the wrapped set of a `NotDocIdSet`.
+struct NotBits {
+    bits: BitsRef,
+    max_doc: i32,
+}
+
+impl Bits for NotBits {
+    fn get_with_ctx(&self, ctx: BitsContext, index: usize) -> Result<(bool, BitsContext)> {
+        let (found, ctx) = self.bits.get_with_ctx(ctx, index)?;
+        Ok((!found, ctx))
+    }
+
+    fn len(&self) -> usize {
+        self.max_doc as usize
+    }
+}
+
+pub struct NotDocIterator<DI: DocIterator> {
+    max_doc: i32,
+    doc: DocId,
+    next_skipped_doc: i32,
+    iterator: DI,
+}
+
+impl<DI: DocIterator> NotDocIterator<DI> {
+    pub fn new(iterator: DI, max_doc: i32) -> Self {
+        NotDocIterator {
+            max_doc,
+            doc: -1,
+            next_skipped_doc: -1,
+            iterator,
+        }
+    }
+}
+
+impl<DI: DocIterator> DocIterator for NotDocIterator<DI> {
+    fn doc_id(&self) -> DocId {
+        self.doc
+    }
+
+    fn next(&mut self) -> Result<DocId> {
+        let adv = self.doc + 1;
+        self.advance(adv)
+    }
+
+    fn advance(&mut self, target: DocId) -> Result<DocId> {
+        self.doc = target;
+
+        if self.doc > self.next_skipped_doc {
+            self.next_skipped_doc = self.iterator.advance(self.doc)?;
+        }
+
+        loop {
+            if self.doc >= self.max_doc {
+                self.doc = NO_MORE_DOCS;
+                return Ok(self.doc);
+            }
+
+            debug_assert!(self.doc <= self.next_skipped_doc);
+            if self.doc != self.next_skipped_doc {
+                return Ok(self.doc);
+            }
+
+            self.doc += 1;
+            self.next_skipped_doc = self.iterator.next()?;
+        }
+    }
+
+    fn cost(&self) -> usize {
+        self.max_doc as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roaring_doc_id_set() {
+        let block = BLOCK_SIZE as DocId;
+        let max_doc = 3 * block + 100;
+        // a sparse block, a dense block, an almost full block and an empty one
+        let mut docs: Vec<DocId> = (0..block).step_by(1000).collect();
+        docs.extend((block..2 * block).step_by(2));
+        docs.extend((2 * block..3 * block).filter(|d| (d - 2 * block) % 10_000 != 0));
+
+        let mut builder = RoaringDocIdSetBuilder::new(max_doc);
+        for &doc in &docs {
+            builder.add_doc(doc).unwrap();
+        }
+        assert!(builder.add_doc(docs[docs.len() - 1]).is_err());
+        let set = builder.build();
+        assert_eq!(set.cardinality(), docs.len());
+        // only the dense block is stored in a bitset
+        assert!(set.ram_bytes_used() < 2 * (BLOCK_SIZE / 8));
+
+        let mut iter = set.iterator().unwrap().unwrap();
+        assert_eq!(iter.cost(), docs.len());
+        let mut collected = vec![];
+        loop {
+            let doc = iter.next().unwrap();
+            if doc == NO_MORE_DOCS {
+                break;
+            }
+            collected.push(doc);
+        }
+        assert_eq!(collected, docs);
+
+        let mut iter = set.iterator().unwrap().unwrap();
+        assert_eq!(iter.advance(5).unwrap(), 1000);
+        assert_eq!(iter.advance(block + 1).unwrap(), block + 2);
+        assert_eq!(iter.advance(2 * block).unwrap(), 2 * block + 1);
+        assert_eq!(iter.next().unwrap(), 2 * block + 2);
+        assert_eq!(iter.advance(3 * block).unwrap(), NO_MORE_DOCS);
+
+        let bits = set.bits().unwrap().unwrap();
+        assert_eq!(bits.len(), max_doc as usize);
+        let mut expected = docs.iter().peekable();
+        for doc in 0..max_doc {
+            let in_set = expected.peek() == Some(&&doc);
+            if in_set {
+                expected.next();
+            }
+            assert_eq!(bits.get(doc as usize).unwrap(), in_set, "doc {}", doc);
+        }
+    }
+
+    #[test]
+    fn test_empty_roaring_doc_id_set() {
+        let set = RoaringDocIdSetBuilder::new(10).build();
+        assert_eq!(set.cardinality(), 0);
+        assert!(set.iterator().unwrap().is_none());
+        let bits = set.bits().unwrap().unwrap();
+        assert!(!bits.get(3).unwrap());
+    }
+}
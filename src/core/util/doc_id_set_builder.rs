@@ -97,7 +97,7 @@ impl DocIdSetBuilder {
 
     /// Add the content of the provided `DocIterator` to this builder.
     /// NOTE: if you need to build a `DocIdSet` out of a single
-    /// `DocIterator`, you should rather use `RoaringDocIdSet.Builder`.
+    /// `DocIterator`, you should rather use `RoaringDocIdSetBuilder`.
     pub fn add(&mut self, iter: &mut DocIterator) -> Result<()> {
         if let Some(ref mut bit_set) = self.bit_set {
             bit_set.or(iter)?;
@@ -160,14 +160,14 @@ impl DocIdSetBuilder {
             return;
         }
         let current_idx = self.buffers.len() - 1;
-        if self.buffers[current_idx].length >= num_docs + self.buffers[current_idx].array.len() {
+        let length = self.buffers[current_idx].length;
+        let capacity = self.buffers[current_idx].array.len();
+        if capacity - length >= num_docs {
             // current buffer is large enough
             return;
         }
-        if self.buffers[current_idx].length
-            < (self.buffers[current_idx].array.len() - self.buffers[current_idx].array.len())
-                >> 3usize
-        {
+        if length < capacity - (capacity >> 3usize) {
+            // current buffer is less than 7/8 full, resize rather than waste space
             let cap = self.additional_capacity(num_docs);
             self.grow_buffer(current_idx, cap);
         } else {
@@ -223,6 +223,12 @@ impl DocIdSetBuilder {
         self.adder = BulkAddr::FixedBitSet;
     }
 
+    /// Build a `DocIdSet` from the accumulated doc ids.
+    ///
+    /// Once more than `max_doc / 128` values were added, the docs are kept in
+    /// a `FixedBitSet` which is also exposed as random-access `bits()`,
+    /// otherwise they are sorted into a sparse `IntArrayDocIdSet`, which only
+    /// supports iteration.
     pub fn build(&mut self) -> DocIdSetEnum {
         if self.bit_set.is_some() {
             assert!(self.counter >= 0);
@@ -336,3 +342,61 @@ impl Buffer {
         Buffer { array, length }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::search::DocIdSet;
+
+    fn collect_docs(set: &DocIdSetEnum) -> Vec<DocId> {
+        let mut docs = vec![];
+        if let Some(mut iter) = set.iterator().unwrap() {
+            loop {
+                let doc = iter.next().unwrap();
+                if doc == NO_MORE_DOCS {
+                    break;
+                }
+                docs.push(doc);
+            }
+        }
+        docs
+    }
+
+    #[test]
+    fn test_sparse_doc_id_set() {
+        let mut builder = DocIdSetBuilder::with_max_doc(10_000);
+        for doc in &[42, 7, 42, 9000, 7] {
+            builder.grow(1);
+            builder.add_doc(*doc);
+        }
+        // the small additions share a single buffer
+        assert_eq!(builder.buffers.len(), 1);
+
+        let set = builder.build();
+        assert_eq!(collect_docs(&set), vec![7, 42, 9000]);
+        assert!(set.bits().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_dense_doc_id_set() {
+        let mut docs: Vec<DocId> = (0..1000).step_by(3).collect();
+        let length = docs.len();
+        docs.push(NO_MORE_DOCS);
+        let mut iter = IntArrayDocIdSet::new(docs, length)
+            .iterator()
+            .unwrap()
+            .unwrap();
+
+        let mut builder = DocIdSetBuilder::with_max_doc(1000);
+        builder.add(&mut iter).unwrap();
+        let set = builder.build();
+
+        let collected = collect_docs(&set);
+        assert_eq!(collected.len(), length);
+        assert!(collected.iter().all(|doc| doc % 3 == 0));
+        let bits = set.bits().unwrap().unwrap();
+        assert_eq!(bits.len(), 1000);
+        assert!(bits.get(999).unwrap());
+        assert!(!bits.get(998).unwrap());
+    }
+}
@@ -26,6 +26,8 @@ pub mod fst_builder;
 pub mod fst_iteartor;
 pub mod fst_reader;
 pub use self::fst_reader::*;
+pub mod positive_int_output;
+pub use self::positive_int_output::{PositiveIntOutput, PositiveIntOutputFactory};
 
 pub trait Output: Clone + Eq + Hash + Debug {
     type Value;
@@ -0,0 +1,121 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::store::{DataInput, DataOutput};
+use error::Result;
+use std::cmp::min;
+
+use core::util::fst::{Output, OutputFactory};
+
+/// A non-negative integer output, the output of a path being the sum of the
+/// ones of its arcs. The common output of two paths is the smallest one, so
+/// the smallest output of the paths below an arc is pushed onto it.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub struct PositiveIntOutput {
+    value: u64,
+}
+
+impl PositiveIntOutput {
+    pub fn new(value: u64) -> PositiveIntOutput {
+        debug_assert!(value <= i64::max_value() as u64);
+        PositiveIntOutput { value }
+    }
+}
+
+impl Output for PositiveIntOutput {
+    type Value = u64;
+
+    fn prefix(&self, other: &PositiveIntOutput) -> PositiveIntOutput {
+        PositiveIntOutput::new(min(self.value, other.value))
+    }
+
+    fn cat(&self, other: &PositiveIntOutput) -> PositiveIntOutput {
+        PositiveIntOutput::new(self.value + other.value)
+    }
+
+    fn concat(&mut self, other: &PositiveIntOutput) {
+        self.value += other.value;
+    }
+
+    fn subtract(&self, other: &PositiveIntOutput) -> PositiveIntOutput {
+        debug_assert!(self.value >= other.value);
+        PositiveIntOutput::new(self.value - other.value)
+    }
+
+    #[inline]
+    fn is_empty(&self) -> bool {
+        self.value == 0
+    }
+
+    #[inline]
+    fn value(&self) -> u64 {
+        self.value
+    }
+}
+
+#[derive(Copy, Clone, Default)]
+pub struct PositiveIntOutputFactory {}
+
+impl OutputFactory for PositiveIntOutputFactory {
+    type Value = PositiveIntOutput;
+
+    fn empty(&self) -> PositiveIntOutput {
+        PositiveIntOutput::default()
+    }
+
+    fn common(&self, o1: &PositiveIntOutput, o2: &PositiveIntOutput) -> PositiveIntOutput {
+        o1.prefix(o2)
+    }
+
+    fn subtract(&self, o1: &PositiveIntOutput, o2: &PositiveIntOutput) -> PositiveIntOutput {
+        o1.subtract(o2)
+    }
+
+    fn add(&self, prefix: &PositiveIntOutput, output: &PositiveIntOutput) -> PositiveIntOutput {
+        prefix.cat(output)
+    }
+
+    fn read<T: DataInput + ?Sized>(&self, data_in: &mut T) -> Result<PositiveIntOutput> {
+        Ok(PositiveIntOutput::new(data_in.read_vlong()? as u64))
+    }
+
+    fn write<T: DataOutput + ?Sized>(
+        &self,
+        output: &PositiveIntOutput,
+        data_out: &mut T,
+    ) -> Result<()> {
+        data_out.write_vlong(output.value as i64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use core::util::fst::tests::*;
+
+    #[test]
+    fn test_positive_int_output() {
+        let factory = PositiveIntOutputFactory::default();
+        let (o1, o2) = (PositiveIntOutput::new(7), PositiveIntOutput::new(3));
+        assert_eq!(factory.common(&o1, &o2), o2);
+        assert_eq!(factory.add(&o1, &o2).value(), 10);
+        assert_eq!(factory.subtract(&o1, &o2).value(), 4);
+        assert!(factory.empty().is_empty());
+
+        let mut io = TestBufferedDataIO::default();
+        factory.write(&PositiveIntOutput::new(300), &mut io).unwrap();
+        assert_eq!(io.bytes, vec![0xac, 0x02]);
+        assert_eq!(factory.read(&mut io).unwrap().value(), 300);
+    }
+}
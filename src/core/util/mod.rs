@@ -70,6 +70,7 @@ mod byte_ref;
 pub use self::byte_ref::*;
 
 pub mod array;
+pub mod automaton;
 pub mod binary_heap;
 pub mod bit_set;
 pub mod bit_util;
@@ -41,6 +41,7 @@ extern crate crc;
 extern crate crossbeam;
 extern crate fasthash;
 extern crate flate2;
+extern crate libc;
 extern crate memmap;
 extern crate num_cpus;
 extern crate num_traits;